@@ -2,7 +2,8 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr, sync::Arc};
 
 /// The identifier for an edit operation.
 pub type EditId = usize;
@@ -150,20 +151,45 @@ pub struct PatternInstanceId {
 /// `Vec`) and caution must be taken when writing `Edit` implementations. Although optimization
 /// is possible (using trees to cache the paths, for example), it seems impossible to
 /// avoid tagging every atom with its full edit lineage.
+///
+/// To keep the footprint down, paths are shared (reference counted) between specifiers
+/// rather than copied, so all of the atoms that one edit creates share a single path, and
+/// cloning a specifier never allocates. A molecule keeps each distinct path once, and
+/// refers to its atoms by their index in its graph rather than by specifier.
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct AtomSpecifier {
-    pub path: Vec<PatternInstanceId>,
+    #[serde(with = "shared_path")]
+    pub path: Arc<[PatternInstanceId]>,
     pub child_index: usize,
 }
 
+// Shared paths are saved as plain lists, so that files do not depend on how
+// paths are stored.
+mod shared_path {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        path: &Arc<[PatternInstanceId]>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        path[..].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Arc<[PatternInstanceId]>, D::Error> {
+        Vec::<PatternInstanceId>::deserialize(deserializer).map(Into::into)
+    }
+}
+
 impl AtomSpecifier {
     // Creates the trivial AtomSpecifier for the first atom created by edit `owner_id`.
     pub fn new(owner_id: EditId) -> Self {
         AtomSpecifier {
-            path: vec![PatternInstanceId {
+            path: Arc::new([PatternInstanceId {
                 owner_id,
                 instance: 0,
-            }],
+            }]),
             child_index: 0,
         }
     }
@@ -179,4 +205,68 @@ impl AtomSpecifier {
         self.child_index += 1;
         ret
     }
+
+    /// The edit that most recently created this atom: the edit that placed it, or,
+    /// if the atom is a copy, the edit that made the copy.
+    pub fn edit_id(&self) -> EditId {
+        self.path
+            .last()
+            .expect("an AtomSpecifier's path is never empty")
+            .owner_id
+    }
+}
+
+/// Formats the specifier in the notation used in the documentation above, but
+/// with a `/` between pattern instances so that it can be typed: `1.0/3.1:2` is
+/// child 2 of instance 1 of edit 3, applied to instance 0 of edit 1.
+impl fmt::Display for AtomSpecifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, id) in self.path.iter().enumerate() {
+            if i > 0 {
+                write!(f, "/")?;
+            }
+            write!(f, "{}.{}", id.owner_id, id.instance)?;
+        }
+        write!(f, ":{}", self.child_index)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseAtomSpecifierError;
+
+impl fmt::Display for ParseAtomSpecifierError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid atom specifier")
+    }
+}
+
+impl std::error::Error for ParseAtomSpecifierError {}
+
+/// Parses the `Display` notation.  The instance and child index may be left
+/// out when they are zero, so `3` is the same as `3.0:0`.
+impl FromStr for AtomSpecifier {
+    type Err = ParseAtomSpecifierError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, child_index) = match s.trim().split_once(':') {
+            Some((path, child)) => (path, child.parse().map_err(|_| ParseAtomSpecifierError)?),
+            None => (s.trim(), 0),
+        };
+
+        let path = path
+            .split('/')
+            .map(|id| {
+                let (owner_id, instance) = id.split_once('.').unwrap_or((id, "0"));
+                Ok(PatternInstanceId {
+                    owner_id: owner_id.parse().map_err(|_| ParseAtomSpecifierError)?,
+                    instance: instance.parse().map_err(|_| ParseAtomSpecifierError)?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(AtomSpecifier {
+            path: path.into(),
+            child_index,
+        })
+    }
 }
@@ -8,8 +8,12 @@ use winit::event::{DeviceEvent, WindowEvent};
 
 mod bounding_box;
 pub mod ids;
+mod metadata;
+mod plane;
 
 pub use bounding_box::BoundingBox;
+pub use metadata::Metadata;
+pub use plane::Plane;
 
 pub enum InputEvent<'a> {
     Window(WindowEvent<'a>),
@@ -48,6 +52,7 @@ impl_as_bytes!(
     (),
     ultraviolet::Vec2,
     ultraviolet::Vec3,
+    ultraviolet::Vec4,
     ultraviolet::Mat2,
     ultraviolet::Mat3,
     ultraviolet::Mat4,
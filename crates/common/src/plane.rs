@@ -0,0 +1,58 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use serde::{Deserialize, Serialize};
+use ultraviolet::{Mat3, Mat4, Vec3, Vec4};
+
+/// A plane through `point`, facing along the unit vector `normal`.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Plane {
+    pub point: Vec3,
+    pub normal: Vec3,
+}
+
+impl Plane {
+    /// The plane through `point` perpendicular to `normal`, which need not be
+    /// normalized.
+    pub fn new(point: Vec3, normal: Vec3) -> Self {
+        Self {
+            point,
+            normal: normal.normalized(),
+        }
+    }
+
+    /// How far `point` is from the plane, positive on the side the normal
+    /// faces.
+    pub fn signed_distance(&self, point: Vec3) -> f32 {
+        (point - self.point).dot(self.normal)
+    }
+
+    /// The mirror image of `point` in the plane.
+    pub fn reflect_point(&self, point: Vec3) -> Vec3 {
+        point - self.normal * (2.0 * self.signed_distance(point))
+    }
+
+    /// The mirror image of the direction `vector`, which is not moved by the
+    /// plane's offset from the origin.
+    pub fn reflect_vector(&self, vector: Vec3) -> Vec3 {
+        vector - self.normal * (2.0 * vector.dot(self.normal))
+    }
+
+    /// The reflection in the plane as a transform.  Its determinant is -1, so
+    /// it turns right-handed shapes into left-handed ones.
+    pub fn reflection(&self) -> Mat4 {
+        let n = self.normal;
+        let linear = Mat3::new(
+            self.reflect_vector(Vec3::unit_x()),
+            self.reflect_vector(Vec3::unit_y()),
+            self.reflect_vector(Vec3::unit_z()),
+        );
+        let mut transform = linear.into_homogeneous();
+        let offset = n * (2.0 * self.point.dot(n));
+        transform.cols[3] = Vec4::new(offset.x, offset.y, offset.z, 1.0);
+        transform
+    }
+}
+
+// End of File
@@ -0,0 +1,116 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Times the work on a large molecule that finding atoms dominates, on the
+//! neon pump (about six thousand atoms): picking, looking atoms up by their
+//! specifiers, editing and relaxing, stepping through the history, and
+//! describing the atoms for drawing.
+//!
+//! Run with `cargo bench -p atomcad-molecule`, and compare the times before
+//! and after a change on the same machine.
+
+use std::time::{Duration, Instant};
+
+use atomcad_molecule::{
+    edit::{BondedAtom, Edit, EditContext, PdbData, PdbModel},
+    MoleculeEditor,
+};
+use periodic_table::Element;
+use ultraviolet::Vec3;
+
+const REPEATS: usize = 20;
+
+fn main() {
+    let (mut molecule, took) = time(|| {
+        MoleculeEditor::from_feature(Edit::PdbImport(PdbData {
+            name: "Neon Pump".into(),
+            contents: include_str!("../../../assets/neon_pump_imm.pdb").into(),
+            residue_templates: true,
+            model: PdbModel::First,
+            conect_bonds: true,
+            infer_bonds: true,
+        }))
+    });
+    println!(
+        "neon pump: {} atoms, {} bonds",
+        molecule.repr.graph.node_count(),
+        molecule.repr.graph.edge_count()
+    );
+    report("import", took);
+
+    // Rays through the middle of the molecule from around it.
+    let bounds = *molecule.repr.bounding_box();
+    let center = (bounds.min + bounds.max) / 2.0;
+    let distance = (bounds.max - bounds.min).mag();
+    let (hits, took) = time(|| {
+        (0..REPEATS)
+            .filter(|i| {
+                let angle = *i as f32 / REPEATS as f32 * std::f32::consts::TAU;
+                let origin = center + Vec3::new(angle.cos(), 0.3, angle.sin()) * distance;
+                let direction = (center - origin).normalized();
+                molecule.repr.get_ray_hit(origin, direction).is_some()
+            })
+            .count()
+    });
+    report(&format!("ray picks x{} ({} hit)", REPEATS, hits), took);
+
+    let atoms = EditContext::atoms(&molecule.repr);
+    let (_, took) = time(|| {
+        for _ in 0..REPEATS {
+            for atom in &atoms {
+                assert!(molecule.repr.find_atom(atom).is_some());
+                assert!(molecule.repr.pos(atom).is_some());
+            }
+        }
+    });
+    report(&format!("specifier lookups x{}", REPEATS), took);
+
+    // Each edit is applied and the molecule relaxed after it.
+    let targets: Vec<_> = atoms
+        .iter()
+        .filter(|atom| {
+            molecule
+                .repr
+                .find_atom(atom)
+                .is_some_and(|node| node.element == Element::Carbon)
+        })
+        .take(3)
+        .cloned()
+        .collect();
+    let (_, took) = time(|| {
+        for target in targets {
+            molecule.add_edit(Edit::BondedAtom(BondedAtom {
+                target,
+                element: Element::Carbon,
+            }));
+        }
+    });
+    report("3 edits + relax", took);
+
+    let last = molecule.history_step();
+    let (_, took) = time(|| {
+        molecule.set_history_step(1);
+        molecule.set_history_step(last);
+    });
+    report("history back and forth", took);
+
+    let (_, took) = time(|| {
+        for _ in 0..REPEATS {
+            assert!(!molecule.repr.atom_reprs().is_empty());
+        }
+    });
+    report(&format!("atom reprs x{}", REPEATS), took);
+}
+
+fn time<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+fn report(name: &str, took: Duration) {
+    println!("{:>32}: {:>8.1} ms", name, took.as_secs_f64() * 1000.0);
+}
+
+// End of File
@@ -0,0 +1,114 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The specifiers of a molecule's atoms, kept apart from its graph.  Edits and
+//! the history refer to atoms by specifier, but everything else works on the
+//! graph's indices, so the table only has to translate between the two.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use common::ids::{AtomSpecifier, PatternInstanceId};
+
+use crate::molecule::AtomIndex;
+
+// An atom's specifier in compact form: the number of its path in the table,
+// and its child index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct AtomKey {
+    path: u32,
+    child: usize,
+}
+
+/// Finds the atoms of a molecule by their specifiers, and their specifiers by
+/// their index in its graph.
+///
+/// Each distinct path is kept once and numbered.  The atoms that an edit makes
+/// share a path and are numbered from 0 by child index, so below each path the
+/// atoms are a dense list, and looking an atom up hashes only its path.
+#[derive(Clone, Debug, Default)]
+pub struct AtomTable {
+    paths: Vec<Arc<[PatternInstanceId]>>,
+    path_numbers: HashMap<Arc<[PatternInstanceId]>, u32>,
+    // For each path, the atom with each child index, if there is one.
+    children: Vec<Vec<Option<AtomIndex>>>,
+    // For each index of the graph, the atom there, if there is one.
+    keys: Vec<Option<AtomKey>>,
+}
+
+impl AtomTable {
+    /// The index of the atom `spec`, if there is one.
+    pub fn get(&self, spec: &AtomSpecifier) -> Option<AtomIndex> {
+        let path = *self.path_numbers.get(&spec.path[..])?;
+        *self.children[path as usize].get(spec.child_index)?
+    }
+
+    pub fn contains(&self, spec: &AtomSpecifier) -> bool {
+        self.get(spec).is_some()
+    }
+
+    /// The specifier of the atom at `index`.  Its path is shared with the
+    /// table, so this does not allocate.
+    pub fn spec(&self, index: AtomIndex) -> Option<AtomSpecifier> {
+        let key = (*self.keys.get(index.index())?)?;
+        Some(AtomSpecifier {
+            path: self.paths[key.path as usize].clone(),
+            child_index: key.child,
+        })
+    }
+
+    /// Records that the atom `spec` is at `index`, in place of any atom that
+    /// was there.
+    pub fn insert(&mut self, spec: &AtomSpecifier, index: AtomIndex) {
+        self.remove_index(index);
+        let path = self.path_number(&spec.path);
+        let children = &mut self.children[path as usize];
+        if children.len() <= spec.child_index {
+            children.resize(spec.child_index + 1, None);
+        }
+        children[spec.child_index] = Some(index);
+
+        if self.keys.len() <= index.index() {
+            self.keys.resize(index.index() + 1, None);
+        }
+        self.keys[index.index()] = Some(AtomKey {
+            path,
+            child: spec.child_index,
+        });
+    }
+
+    /// Forgets the atom `spec`, and returns where it was.
+    pub fn remove(&mut self, spec: &AtomSpecifier) -> Option<AtomIndex> {
+        let index = self.get(spec)?;
+        self.remove_index(index);
+        Some(index)
+    }
+
+    pub fn clear(&mut self) {
+        self.paths.clear();
+        self.path_numbers.clear();
+        self.children.clear();
+        self.keys.clear();
+    }
+
+    // The number of `path`, which is added to the table if it is new.
+    fn path_number(&mut self, path: &Arc<[PatternInstanceId]>) -> u32 {
+        if let Some(number) = self.path_numbers.get(&path[..]) {
+            return *number;
+        }
+        let number = u32::try_from(self.paths.len()).expect("a molecule has fewer than 2^32 paths");
+        self.paths.push(path.clone());
+        self.path_numbers.insert(path.clone(), number);
+        self.children.push(Vec::new());
+        number
+    }
+
+    fn remove_index(&mut self, index: AtomIndex) {
+        if let Some(key) = self.keys.get_mut(index.index()).and_then(Option::take) {
+            self.children[key.path as usize][key.child] = None;
+        }
+    }
+}
+
+// End of File
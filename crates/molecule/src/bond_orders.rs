@@ -0,0 +1,264 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Perceiving bond orders from geometry.  Imported structures such as PDB
+//! files often give only which atoms are bonded, so every bond starts out
+//! single.  A [`BondOrderAssignment`] estimates the order of each bond from
+//! its length, then gives multiple bonds only to atoms with valence to spare,
+//! so that no atom ends up with more bonds than its valence.
+//!
+//! Bond orders are whole numbers, so aromatic rings are given alternating
+//! single and double bonds (one of their Kekulé structures).
+
+use std::collections::{HashMap, VecDeque};
+
+use common::ids::AtomSpecifier;
+use periodic_table::Element;
+use serde::{Deserialize, Serialize};
+
+use crate::dynamics::parameters;
+use crate::edit::{EditContext, EditError};
+use crate::BondOrder;
+
+/// Sets the order of every bond between atoms with a standard valence from
+/// the bond's length.  Bonds to other atoms, such as metals, are left as they
+/// are.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BondOrderAssignment {
+    /// Bonds whose estimated order (see [`estimated_order`]) is below this
+    /// are single.  Aromatic bonds are estimated at about 1.4.
+    #[serde(default = "default_min_multiple")]
+    pub min_multiple: f32,
+    /// Bonds whose estimated order is at least this may be triple.
+    #[serde(default = "default_min_triple")]
+    pub min_triple: f32,
+}
+
+fn default_min_multiple() -> f32 {
+    1.25
+}
+
+fn default_min_triple() -> f32 {
+    2.2
+}
+
+impl Default for BondOrderAssignment {
+    fn default() -> Self {
+        Self {
+            min_multiple: default_min_multiple(),
+            min_triple: default_min_triple(),
+        }
+    }
+}
+
+/// The order of a bond between `a` and `b` that is `length` angstroms long,
+/// from Pauling's relation between bond order and length (see
+/// [`parameters::bond_length`]).  This is a real number: a carbon-carbon bond
+/// of benzene is about 1.4, and one of ethene about 1.7.
+pub fn estimated_order(a: Element, b: Element, length: f32) -> f32 {
+    let single = parameters::bond_length(a, b, 1);
+    10f32.powf((single - length) / 0.71)
+}
+
+// A bond that the assignment may change, between atoms numbered by their
+// place in `EditContext::atoms`.
+struct Bond {
+    atoms: [usize; 2],
+    estimate: f32,
+}
+
+impl Bond {
+    fn other(&self, atom: usize) -> usize {
+        if self.atoms[0] == atom {
+            self.atoms[1]
+        } else {
+            self.atoms[0]
+        }
+    }
+}
+
+impl BondOrderAssignment {
+    /// The order that each bond between atoms with a standard valence is
+    /// given, in the order of the molecule's atoms.
+    pub fn assign(
+        &self,
+        commands: &dyn EditContext,
+    ) -> Vec<(AtomSpecifier, AtomSpecifier, BondOrder)> {
+        let atoms = commands.atoms();
+        let index: HashMap<&AtomSpecifier, usize> = atoms
+            .iter()
+            .enumerate()
+            .map(|(i, spec)| (spec, i))
+            .collect();
+        let valence: Vec<Option<u8>> = atoms
+            .iter()
+            .map(|spec| {
+                commands
+                    .find_atom(spec)
+                    .and_then(|atom| atom.element.standard_valence())
+            })
+            .collect();
+
+        // How many more bonds each atom has room for if all of its bonds are
+        // single.
+        let mut free: Vec<usize> = atoms
+            .iter()
+            .zip(&valence)
+            .map(|(spec, valence)| {
+                valence.map_or(0, |valence| {
+                    (valence as usize).saturating_sub(commands.bonds(spec).len())
+                })
+            })
+            .collect();
+
+        let mut bonds = Vec::new();
+        for (a, spec) in atoms.iter().enumerate() {
+            if valence[a].is_none() {
+                continue;
+            }
+            for (neighbor, _) in commands.bonds(spec) {
+                let Some(&b) = index.get(&neighbor) else {
+                    continue;
+                };
+                if b <= a || valence[b].is_none() {
+                    continue;
+                }
+                let (Some(atom_a), Some(atom_b), Some(pos_a), Some(pos_b)) = (
+                    commands.find_atom(spec),
+                    commands.find_atom(&neighbor),
+                    commands.pos(spec),
+                    commands.pos(&neighbor),
+                ) else {
+                    continue;
+                };
+                bonds.push(Bond {
+                    atoms: [a, b],
+                    estimate: estimated_order(
+                        atom_a.element,
+                        atom_b.element,
+                        (*pos_a - *pos_b).mag(),
+                    ),
+                });
+            }
+        }
+        let mut orders: Vec<BondOrder> = vec![1; bonds.len()];
+
+        // The shortest bonds are given multiple orders first, so that an atom
+        // with one bond to spare puts it where the geometry most wants it.
+        let mut by_length: Vec<usize> = (0..bonds.len()).collect();
+        by_length.sort_by(|&i, &j| bonds[j].estimate.total_cmp(&bonds[i].estimate));
+        for &i in &by_length {
+            let [a, b] = bonds[i].atoms;
+            if bonds[i].estimate >= self.min_triple && free[a] >= 2 && free[b] >= 2 {
+                orders[i] = 3;
+                free[a] -= 2;
+                free[b] -= 2;
+            }
+        }
+        for &i in &by_length {
+            let [a, b] = bonds[i].atoms;
+            if orders[i] == 1
+                && bonds[i].estimate >= self.min_multiple
+                && free[a] > 0
+                && free[b] > 0
+            {
+                orders[i] = 2;
+                free[a] -= 1;
+                free[b] -= 1;
+            }
+        }
+
+        // Handing out double bonds shortest first can strand atoms in
+        // conjugated systems, such as two atoms across a ring from each
+        // other.  Moving double bonds along alternating paths between two
+        // stranded atoms satisfies both.
+        let mut adjacent: Vec<Vec<usize>> = vec![Vec::new(); atoms.len()];
+        for (i, bond) in bonds.iter().enumerate() {
+            if bond.estimate >= self.min_multiple {
+                adjacent[bond.atoms[0]].push(i);
+                adjacent[bond.atoms[1]].push(i);
+            }
+        }
+        for start in 0..atoms.len() {
+            while free[start] > 0 && augment(start, &bonds, &adjacent, &mut orders, &mut free) {}
+        }
+
+        bonds
+            .iter()
+            .zip(orders)
+            .map(|(bond, order)| {
+                let [a, b] = bond.atoms;
+                (atoms[a].clone(), atoms[b].clone(), order)
+            })
+            .collect()
+    }
+}
+
+// Looks for a path from `start` to another atom with a bond to spare that
+// alternates between single and double bonds, starting and ending with a
+// single bond, and swaps the orders along it.  Returns whether a path was
+// found.
+fn augment(
+    start: usize,
+    bonds: &[Bond],
+    adjacent: &[Vec<usize>],
+    orders: &mut [BondOrder],
+    free: &mut [usize],
+) -> bool {
+    // The bond each atom was reached by, and the atom it was reached from.
+    let mut via: Vec<Option<(usize, usize)>> = vec![None; adjacent.len()];
+    let mut visited = vec![false; adjacent.len()];
+    visited[start] = true;
+    let mut queue = VecDeque::from([start]);
+
+    while let Some(atom) = queue.pop_front() {
+        for &single in &adjacent[atom] {
+            let next = bonds[single].other(atom);
+            if orders[single] != 1 || visited[next] {
+                continue;
+            }
+            visited[next] = true;
+            via[next] = Some((single, atom));
+
+            if free[next] > 0 {
+                free[next] -= 1;
+                free[start] -= 1;
+                let mut end = next;
+                while let Some((single, from)) = via[end] {
+                    orders[single] = 2;
+                    let Some((double, before)) = via[from] else {
+                        break;
+                    };
+                    orders[double] = 1;
+                    end = before;
+                }
+                return true;
+            }
+
+            for &double in &adjacent[next] {
+                let beyond = bonds[double].other(next);
+                if orders[double] != 2 || visited[beyond] {
+                    continue;
+                }
+                visited[beyond] = true;
+                via[beyond] = Some((double, next));
+                queue.push_back(beyond);
+            }
+        }
+    }
+    false
+}
+
+/// Sets the order of each bond as `assignment` perceives it.
+pub(crate) fn spawn_bond_orders(
+    assignment: &BondOrderAssignment,
+    commands: &mut dyn EditContext,
+) -> Result<(), EditError> {
+    for (a, b, order) in assignment.assign(&*commands) {
+        commands.create_bond(&a, &b, order)?;
+    }
+    Ok(())
+}
+
+// End of File
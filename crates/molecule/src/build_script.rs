@@ -0,0 +1,762 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A text format for building molecules, so that they can be written or
+//! generated in a text editor.  Each line is one edit, and the file reads
+//! much like a Z-matrix:
+//!
+//! ```text
+//! # Ethanol
+//! root C      # atom 1
+//! bond C 1    # atom 2, bonded to atom 1
+//! bond O 2    # atom 3, bonded to atom 2
+//! fill        # hydrogens on every atom
+//! pin 1
+//! ```
+//!
+//! The commands are:
+//!
+//! * `root <element>` places the first atom.  It must be the first command,
+//!   unless the script starts with a lattice or a polymer.
+//! * `lattice <structure> <a> <b> <c> [<element> [<element>]] [<constant>]`
+//!   fills a block of `a` by `b` by `c` cells with a crystal, where the
+//!   structure is `diamond`, `fcc`, `graphene` or `lonsdaleite`.  The
+//!   elements are those of the two sublattices (carbon, or copper for `fcc`,
+//!   by default), and the lattice constant is in angstroms.
+//! * `peptide <sequence> [extended]` builds a peptide from the one-letter
+//!   codes of its amino acids, as an α-helix or as an extended strand.
+//!   `dna <sequence> [single]` builds a double helix of DNA from the bases of
+//!   one strand, 5' end first, or just that strand, and `rna <sequence>` a
+//!   strand of RNA.  Any of these can start the script in place of `root`.
+//! * `bond <element> <atom>` bonds a new atom to an existing one.
+//! * `link <atom> <atom> [<order>]` bonds two existing atoms, e.g. to close a
+//!   ring.  The bond is single unless an order from 1 to 3 is given.
+//! * `fill [<atom>...]` adds hydrogens to the given atoms, or to every atom.
+//! * `passivate [<element>...] [with <element>] [max <n>]` caps the dangling
+//!   bonds of atoms of the given elements (or of every element) with hydrogen,
+//!   or with the element after `with`.  With `max`, only atoms bonded to at
+//!   most `n` others are capped.
+//! * `dope <fraction> <element>|vacancy [in <element>...] [seed <n>]` changes
+//!   a random fraction (from 0 to 1) of the atoms, or of those of the given
+//!   elements, into atoms of another element, or deletes them with `vacancy`.
+//!   The same seed always chooses the same atoms; it is 0 by default.
+//! * `orders` sets the order of every bond from its length, e.g. after
+//!   `link`ing the atoms of a ring without giving orders.
+//! * `cut <shape> [at <x> <y> <z>] [outside] [cap <element>]` deletes the
+//!   atoms outside a shape centered on the origin (or on the point after
+//!   `at`), or those inside it with `outside`, along with atoms left hanging
+//!   by the cut.  The shape is `plane [-]x|y|z` (keeping the side the axis
+//!   points away from), `box <a> <b> <c>`, `sphere <radius>` or
+//!   `cylinder x|y|z <radius> <length>`, in angstroms.  With `cap`, the new
+//!   surface is passivated with the given element.
+//! * `torsion <atom> <atom> <atom> <atom> <angle>` sets the dihedral angle of
+//!   four bonded atoms, in degrees, by turning the part of the molecule on one
+//!   side of the bond between the middle two.
+//! * `move <x> <y> <z> <atom>...` moves atoms by an offset in angstroms, and
+//!   `turn x|y|z <angle> <atom>...` turns them about their middle by an angle
+//!   in degrees, as a rigid unit.
+//! * `pin <atom>...` and `unpin <atom>...` pin atoms in place during
+//!   relaxation, or release them.
+//! * `symmetry <n> [x|y|z]` copies everything built so far `n - 1` times,
+//!   rotated about an axis through the origin (the z axis by default), giving
+//!   `n`-fold rotational symmetry.
+//! * `mirror [x|y|z]` reflects everything built so far in the plane through
+//!   the origin perpendicular to an axis (the x axis by default).
+//!
+//! Atoms placed by `root` and `bond` are numbered from 1 in the order they are
+//! placed.  Atoms made by other commands are not numbered, as how many there
+//! are depends on the geometry.  Everything after a `#` is a comment.
+//!
+//! An atom can also be given by its specifier after an `@`, e.g. `@3` or
+//! `@1.0/3.1:2` (see `AtomSpecifier`), as in the lines that [`command`] writes
+//! for edits copied from a molecule's history.  Since the edits of a script
+//! are numbered from 0 in order, these refer to the same atoms only in a
+//! script that builds the same edits.
+
+use std::fmt;
+
+use common::ids::{AtomSpecifier, EditId};
+use common::Plane;
+use periodic_table::Element;
+use ultraviolet::{Bivec3, Rotor3, Vec3};
+
+use crate::bond_orders::BondOrderAssignment;
+use crate::cut::{Cut, CutShape};
+use crate::doping::Doping;
+use crate::edit::{BondedAtom, CreateBond, Edit, HydrogenFill, Pin, TransformAtoms};
+use crate::lattice::{Lattice, LatticeKind};
+use crate::mirror::Mirror;
+use crate::passivation::Passivation;
+use crate::polymer::Polymer;
+use crate::residues::ResidueKind;
+use crate::symmetry::SymmetryCopy;
+use crate::torsion::SetTorsion;
+use crate::MoleculeEditor;
+
+/// A line of a build script that could not be understood.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The line the error is on, counting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Reads a build script into the edits it describes, in order.  The first edit
+/// is always a root atom, a lattice or a polymer.
+pub fn parse(text: &str) -> Result<Vec<Edit>, ParseError> {
+    let mut edits = Vec::new();
+    // The edit that placed each numbered atom.  The edits of a new molecule
+    // are numbered from 0 in order, so each edit's ID is its index.
+    let mut atoms: Vec<EditId> = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let error = |message: String| ParseError {
+            line: index + 1,
+            message,
+        };
+        let line = line.split('#').next().unwrap_or_default();
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            continue;
+        };
+        let args: Vec<&str> = words.collect();
+
+        if edits.is_empty() && !matches!(command, "root" | "lattice" | "peptide" | "dna" | "rna") {
+            return Err(error(
+                "the first command must be `root`, `lattice` or a polymer".to_owned(),
+            ));
+        }
+
+        let edit = match (command, args.as_slice()) {
+            ("root", [element]) => {
+                if !edits.is_empty() {
+                    return Err(error("there can only be one `root`".to_owned()));
+                }
+                atoms.push(edits.len());
+                Edit::RootAtom(parse_element(element).map_err(error)?)
+            }
+            ("bond", [element, target]) => {
+                let element = parse_element(element).map_err(error)?;
+                let target = parse_atom(target, &atoms).map_err(error)?;
+                atoms.push(edits.len());
+                Edit::BondedAtom(BondedAtom { target, element })
+            }
+            ("link", [a1, a2] | [a1, a2, _]) => {
+                let order = match args.get(2).map(|order| order.parse()) {
+                    None => 1,
+                    Some(Ok(order @ 1..=3)) => order,
+                    Some(_) => return Err(error(format!("`{}` is not a bond order", args[2]))),
+                };
+                let pair = [
+                    parse_atom(a1, &atoms).map_err(error)?,
+                    parse_atom(a2, &atoms).map_err(error)?,
+                ];
+                if pair[0] == pair[1] {
+                    return Err(error("an atom cannot be bonded to itself".to_owned()));
+                }
+                Edit::CreateBond(CreateBond { atoms: pair, order })
+            }
+            ("link", _) => {
+                return Err(error("expected `link <atom> <atom> [<order>]`".to_owned()));
+            }
+            ("fill", []) => Edit::HydrogenFill(HydrogenFill { targets: None }),
+            ("fill", targets) => Edit::HydrogenFill(HydrogenFill {
+                targets: Some(parse_atoms(targets, &atoms).map_err(error)?),
+            }),
+            ("torsion", [a, b, c, d, angle]) => {
+                let angle = angle
+                    .parse()
+                    .map_err(|_| error(format!("`{}` is not an angle", angle)))?;
+                Edit::SetTorsion(SetTorsion {
+                    atoms: [
+                        parse_atom(a, &atoms).map_err(error)?,
+                        parse_atom(b, &atoms).map_err(error)?,
+                        parse_atom(c, &atoms).map_err(error)?,
+                        parse_atom(d, &atoms).map_err(error)?,
+                    ],
+                    angle,
+                })
+            }
+            ("torsion", _) => {
+                return Err(error(
+                    "expected `torsion <atom> <atom> <atom> <atom> <angle>`".to_owned(),
+                ));
+            }
+            ("move", [x, y, z, targets @ ..]) if !targets.is_empty() => {
+                let mut offset = [0.0; 3];
+                for (coordinate, text) in offset.iter_mut().zip([x, y, z]) {
+                    *coordinate = text
+                        .parse()
+                        .map_err(|_| error(format!("`{}` is not a distance", text)))?;
+                }
+                Edit::TransformAtoms(TransformAtoms {
+                    targets: parse_atoms(targets, &atoms).map_err(error)?,
+                    rotation: Rotor3::identity(),
+                    translation: Vec3::from(offset),
+                })
+            }
+            ("move", _) => {
+                return Err(error("expected `move <x> <y> <z> <atom>...`".to_owned()));
+            }
+            ("turn", [axis, angle, targets @ ..]) if !targets.is_empty() => {
+                let axis = match *axis {
+                    "x" => Vec3::unit_x(),
+                    "y" => Vec3::unit_y(),
+                    "z" => Vec3::unit_z(),
+                    axis => return Err(error(format!("unknown axis `{}`", axis))),
+                };
+                let angle: f32 = angle
+                    .parse()
+                    .map_err(|_| error(format!("`{}` is not an angle", angle)))?;
+                Edit::TransformAtoms(TransformAtoms {
+                    targets: parse_atoms(targets, &atoms).map_err(error)?,
+                    rotation: Rotor3::from_angle_plane(
+                        angle.to_radians(),
+                        Bivec3::from_normalized_axis(axis),
+                    ),
+                    translation: Vec3::zero(),
+                })
+            }
+            ("turn", _) => {
+                return Err(error("expected `turn x|y|z <angle> <atom>...`".to_owned()));
+            }
+            ("pin" | "unpin", []) => {
+                return Err(error(format!("`{}` needs at least one atom", command)));
+            }
+            ("pin" | "unpin", targets) => Edit::Pin(Pin {
+                targets: parse_atoms(targets, &atoms).map_err(error)?,
+                pinned: command == "pin",
+            }),
+            ("symmetry", [n] | [n, _]) => {
+                let n = match n.parse::<usize>() {
+                    Ok(n) if n >= 2 => n,
+                    _ => return Err(error(format!("`{}` is not a symmetry order", n))),
+                };
+                let axis = match args.get(1).copied() {
+                    None | Some("z") => Vec3::unit_z(),
+                    Some("x") => Vec3::unit_x(),
+                    Some("y") => Vec3::unit_y(),
+                    Some(axis) => return Err(error(format!("unknown axis `{}`", axis))),
+                };
+                Edit::SymmetryCopy(SymmetryCopy::rotational(n, axis, Vec3::zero()))
+            }
+            ("symmetry", _) => {
+                return Err(error("expected `symmetry <n> [x|y|z]`".to_owned()));
+            }
+            ("mirror", [] | [_]) => {
+                let normal = match args.first().copied() {
+                    None | Some("x") => Vec3::unit_x(),
+                    Some("y") => Vec3::unit_y(),
+                    Some("z") => Vec3::unit_z(),
+                    Some(axis) => return Err(error(format!("unknown axis `{}`", axis))),
+                };
+                Edit::Mirror(Mirror {
+                    plane: Plane::new(Vec3::zero(), normal),
+                })
+            }
+            ("mirror", _) => return Err(error("expected `mirror [x|y|z]`".to_owned())),
+            ("lattice", [structure, a, b, c, rest @ ..]) if rest.len() <= 3 => {
+                let kind = match *structure {
+                    "diamond" => LatticeKind::DiamondCubic,
+                    "fcc" => LatticeKind::Fcc,
+                    "graphene" => LatticeKind::Graphene,
+                    "lonsdaleite" => LatticeKind::Lonsdaleite,
+                    _ => return Err(error(format!("unknown structure `{}`", structure))),
+                };
+                let mut lattice = Lattice::new(kind, [0; 3]);
+                for (extent, count) in lattice.extents.iter_mut().zip([a, b, c]) {
+                    *extent = match count.parse() {
+                        Ok(count @ 1..) => count,
+                        _ => return Err(error(format!("`{}` is not a number of cells", count))),
+                    };
+                }
+                let (elements, constant) = match rest {
+                    [elements @ .., constant] if constant.parse::<f32>().is_ok() => {
+                        (elements, Some(constant))
+                    }
+                    elements => (elements, None),
+                };
+                match elements {
+                    [] => {}
+                    [element] => lattice.elements = [parse_element(element).map_err(error)?; 2],
+                    [first, second] => {
+                        lattice.elements = [
+                            parse_element(first).map_err(error)?,
+                            parse_element(second).map_err(error)?,
+                        ]
+                    }
+                    _ => return Err(error("a lattice has at most two elements".to_owned())),
+                }
+                if let Some(constant) = constant {
+                    lattice.constant = match constant.parse() {
+                        Ok(constant) if constant > 0.0 => constant,
+                        _ => {
+                            return Err(error(format!("`{}` is not a lattice constant", constant)))
+                        }
+                    };
+                }
+                Edit::Lattice(lattice)
+            }
+            ("peptide", [sequence] | [sequence, "extended"]) => {
+                let mut polymer = Polymer::new(ResidueKind::AminoAcid, sequence);
+                polymer.helix = args.len() == 1;
+                Edit::Polymer(parse_polymer(polymer).map_err(error)?)
+            }
+            ("peptide", _) => {
+                return Err(error("expected `peptide <sequence> [extended]`".to_owned()));
+            }
+            ("dna", [sequence] | [sequence, "single"]) => {
+                let mut polymer = Polymer::new(ResidueKind::Deoxyribonucleotide, sequence);
+                polymer.duplex = args.len() == 1;
+                Edit::Polymer(parse_polymer(polymer).map_err(error)?)
+            }
+            ("dna", _) => return Err(error("expected `dna <sequence> [single]`".to_owned())),
+            ("rna", [sequence]) => {
+                let polymer = Polymer::new(ResidueKind::Ribonucleotide, sequence);
+                Edit::Polymer(parse_polymer(polymer).map_err(error)?)
+            }
+            ("rna", _) => return Err(error("expected `rna <sequence>`".to_owned())),
+            ("passivate", _) => {
+                let mut passivation = Passivation::default();
+                let mut args = args.iter();
+                while let Some(arg) = args.next() {
+                    match *arg {
+                        "with" => {
+                            let species = args.next().ok_or_else(|| {
+                                error("expected an element after `with`".to_owned())
+                            })?;
+                            passivation.species = parse_element(species).map_err(error)?;
+                        }
+                        "max" => {
+                            let max = args.next().ok_or_else(|| {
+                                error("expected a number of bonds after `max`".to_owned())
+                            })?;
+                            passivation.max_coordination = Some(max.parse().map_err(|_| {
+                                error(format!("`{}` is not a number of bonds", max))
+                            })?);
+                        }
+                        element => passivation
+                            .elements
+                            .push(parse_element(element).map_err(error)?),
+                    }
+                }
+                Edit::Passivation(passivation)
+            }
+            ("dope", [fraction, substitute, rest @ ..]) => {
+                let fraction = match fraction.parse::<f32>() {
+                    Ok(fraction) if (0.0..=1.0).contains(&fraction) => fraction,
+                    _ => return Err(error(format!("`{}` is not a fraction", fraction))),
+                };
+                let substitute = match *substitute {
+                    "vacancy" => None,
+                    element => Some(parse_element(element).map_err(error)?),
+                };
+                let mut doping = Doping {
+                    substitute,
+                    fraction,
+                    elements: Vec::new(),
+                    atoms: Vec::new(),
+                    seed: 0,
+                };
+                let mut rest = rest.iter();
+                let mut in_elements = false;
+                while let Some(arg) = rest.next() {
+                    match *arg {
+                        "in" => in_elements = true,
+                        "seed" => {
+                            let seed = rest
+                                .next()
+                                .ok_or_else(|| error("expected a seed after `seed`".to_owned()))?;
+                            doping.seed = seed
+                                .parse()
+                                .map_err(|_| error(format!("`{}` is not a seed", seed)))?;
+                        }
+                        element if in_elements => {
+                            doping.elements.push(parse_element(element).map_err(error)?)
+                        }
+                        other => return Err(error(format!("unexpected `{}`", other))),
+                    }
+                }
+                Edit::Doping(doping)
+            }
+            ("dope", _) => {
+                return Err(error(
+                    "expected `dope <fraction> <element>|vacancy [in <element>...] [seed <n>]`"
+                        .to_owned(),
+                ));
+            }
+            ("orders", []) => Edit::BondOrders(BondOrderAssignment::default()),
+            ("orders", _) => return Err(error("expected `orders`".to_owned())),
+            ("cut", [shape, rest @ ..]) => Edit::Cut(parse_cut(shape, rest).map_err(error)?),
+            ("cut", _) => {
+                return Err(error(
+                    "expected `cut <shape> [at <x> <y> <z>] [outside] [cap <element>]`".to_owned(),
+                ));
+            }
+            ("lattice", _) => {
+                return Err(error(
+                    "expected `lattice <structure> <a> <b> <c> [<element> [<element>]] [<constant>]`"
+                        .to_owned(),
+                ));
+            }
+            ("root" | "bond", _) => {
+                let usage = if command == "root" {
+                    "root <element>"
+                } else {
+                    "bond <element> <atom>"
+                };
+                return Err(error(format!("expected `{}`", usage)));
+            }
+            _ => return Err(error(format!("unknown command `{}`", command))),
+        };
+        edits.push(edit);
+    }
+
+    if edits.is_empty() {
+        return Err(ParseError {
+            line: text.lines().count().max(1),
+            message: "the script has no commands".to_owned(),
+        });
+    }
+
+    Ok(edits)
+}
+
+/// Builds a new molecule named `name` from a build script.  Each command
+/// becomes an edit in the molecule's history, so the result can be edited like
+/// any other molecule.
+pub fn build(name: &str, text: &str) -> Result<MoleculeEditor, ParseError> {
+    let mut edits = parse(text)?.into_iter();
+    let root = edits.next().expect("a parsed script has a root edit");
+
+    let mut molecule = MoleculeEditor::from_feature(root);
+    molecule.set_name(name);
+    for edit in edits {
+        molecule.insert_edit(edit);
+        molecule.apply_all_edits();
+    }
+
+    Ok(molecule)
+}
+
+/// Writes `edit` as a line of a build script, which reads back as the same
+/// edit.  Atoms are written by their specifiers, after an `@`.  Returns `None`
+/// for edits that the format cannot express, such as imported files, or cuts
+/// with turned shapes.
+pub fn command(edit: &Edit) -> Option<String> {
+    let atoms = |atoms: &[AtomSpecifier]| {
+        atoms
+            .iter()
+            .map(|atom| format!(" @{}", atom))
+            .collect::<String>()
+    };
+    let line: String = match edit {
+        Edit::RootAtom(element) => format!("root {}", element.symbol()),
+        Edit::BondedAtom(BondedAtom { target, element }) => {
+            format!("bond {} @{}", element.symbol(), target)
+        }
+        Edit::CreateBond(CreateBond {
+            atoms: [a1, a2],
+            order,
+        }) => match order {
+            1 => format!("link @{} @{}", a1, a2),
+            order => format!("link @{} @{} {}", a1, a2, order),
+        },
+        Edit::HydrogenFill(HydrogenFill { targets }) => match targets {
+            None => "fill".to_owned(),
+            Some(targets) => format!("fill{}", atoms(targets)),
+        },
+        Edit::SetTorsion(SetTorsion {
+            atoms: [a, b, c, d],
+            angle,
+        }) => {
+            format!("torsion @{} @{} @{} @{} {}", a, b, c, d, angle)
+        }
+        Edit::TransformAtoms(TransformAtoms {
+            targets,
+            rotation,
+            translation,
+        }) if *rotation == Rotor3::identity() => format!(
+            "move {} {} {}{}",
+            translation.x,
+            translation.y,
+            translation.z,
+            atoms(targets)
+        ),
+        Edit::TransformAtoms(TransformAtoms {
+            targets,
+            rotation,
+            translation,
+        }) if *translation == Vec3::zero() => {
+            // A turn about a single axis has only that axis's part of the
+            // bivector, which is minus the sine of half the angle for x and
+            // z, and plus it for y (see `Bivec3::from_normalized_axis`).
+            let (axis, sin) = match (rotation.bv.yz, rotation.bv.xz, rotation.bv.xy) {
+                (yz, 0.0, 0.0) => ("x", -yz),
+                (0.0, xz, 0.0) => ("y", xz),
+                (0.0, 0.0, xy) => ("z", -xy),
+                _ => return None,
+            };
+            let angle = 2.0 * sin.atan2(rotation.s).to_degrees();
+            format!("turn {} {}{}", axis, angle, atoms(targets))
+        }
+        Edit::Pin(Pin { targets, pinned }) => {
+            let command = if *pinned { "pin" } else { "unpin" };
+            format!("{}{}", command, atoms(targets))
+        }
+        Edit::SymmetryCopy(symmetry) => {
+            let n = symmetry.operations.len() + 1;
+            let axis = [
+                ("z", Vec3::unit_z()),
+                ("x", Vec3::unit_x()),
+                ("y", Vec3::unit_y()),
+            ]
+            .into_iter()
+            .find(|(_, axis)| {
+                SymmetryCopy::rotational(n, *axis, Vec3::zero()).operations == symmetry.operations
+            })?
+            .0;
+            format!("symmetry {} {}", n, axis)
+        }
+        Edit::Mirror(Mirror { plane }) if plane.point == Vec3::zero() => {
+            format!(
+                "mirror {}",
+                axis_name(plane.normal)?.trim_start_matches('-')
+            )
+        }
+        Edit::Lattice(lattice) if !lattice.periodic => {
+            let structure = match lattice.kind {
+                LatticeKind::DiamondCubic => "diamond",
+                LatticeKind::Fcc => "fcc",
+                LatticeKind::Graphene => "graphene",
+                LatticeKind::Lonsdaleite => "lonsdaleite",
+            };
+            let [a, b, c] = lattice.extents;
+            let [first, second] = lattice.elements;
+            format!(
+                "lattice {} {} {} {} {} {} {}",
+                structure,
+                a,
+                b,
+                c,
+                first.symbol(),
+                second.symbol(),
+                lattice.constant
+            )
+        }
+        Edit::Polymer(polymer) => match polymer.kind {
+            ResidueKind::AminoAcid if polymer.helix => format!("peptide {}", polymer.sequence),
+            ResidueKind::AminoAcid => format!("peptide {} extended", polymer.sequence),
+            ResidueKind::Deoxyribonucleotide if polymer.duplex => {
+                format!("dna {}", polymer.sequence)
+            }
+            ResidueKind::Deoxyribonucleotide => format!("dna {} single", polymer.sequence),
+            ResidueKind::Ribonucleotide => format!("rna {}", polymer.sequence),
+        },
+        Edit::Passivation(passivation) if passivation.atoms.is_empty() => {
+            let mut line = "passivate".to_owned();
+            for element in &passivation.elements {
+                line += &format!(" {}", element.symbol());
+            }
+            line += &format!(" with {}", passivation.species.symbol());
+            if let Some(max) = passivation.max_coordination {
+                line += &format!(" max {}", max);
+            }
+            line
+        }
+        Edit::Doping(doping) if doping.atoms.is_empty() => {
+            let substitute = doping
+                .substitute
+                .map_or("vacancy", |element| element.symbol());
+            let mut line = format!("dope {} {}", doping.fraction, substitute);
+            if !doping.elements.is_empty() {
+                line += " in";
+                for element in &doping.elements {
+                    line += &format!(" {}", element.symbol());
+                }
+            }
+            line + &format!(" seed {}", doping.seed)
+        }
+        Edit::BondOrders(orders) => {
+            let default = BondOrderAssignment::default();
+            if orders.min_multiple != default.min_multiple
+                || orders.min_triple != default.min_triple
+            {
+                return None;
+            }
+            "orders".to_owned()
+        }
+        Edit::Cut(cut) if cut.min_neighbors == Cut::new(cut.shape).min_neighbors => {
+            let center = cut.shape.center();
+            let shape = match cut.shape {
+                CutShape::HalfSpace(plane) => format!("plane {}", axis_name(plane.normal)?),
+                CutShape::Box {
+                    half_extents,
+                    rotation,
+                    ..
+                } if rotation == Rotor3::identity() => {
+                    let size = half_extents * 2.0;
+                    format!("box {} {} {}", size.x, size.y, size.z)
+                }
+                CutShape::Sphere { radius, .. } => format!("sphere {}", radius),
+                CutShape::Cylinder {
+                    axis,
+                    radius,
+                    half_length,
+                    ..
+                } if !axis_name(axis)?.starts_with('-') => format!(
+                    "cylinder {} {} {}",
+                    axis_name(axis)?,
+                    radius,
+                    half_length * 2.0
+                ),
+                _ => return None,
+            };
+            let mut line = format!("cut {}", shape);
+            if center != Vec3::zero() {
+                line += &format!(" at {} {} {}", center.x, center.y, center.z);
+            }
+            if !cut.keep_inside {
+                line += " outside";
+            }
+            if let Some(cap) = cut.cap {
+                line += &format!(" cap {}", cap.symbol());
+            }
+            line
+        }
+        _ => return None,
+    };
+    Some(line)
+}
+
+// The name of the axis, or the opposite of the axis, that `direction` points
+// along, as written in scripts.
+fn axis_name(direction: Vec3) -> Option<&'static str> {
+    [
+        ("x", Vec3::unit_x()),
+        ("y", Vec3::unit_y()),
+        ("z", Vec3::unit_z()),
+        ("-x", -Vec3::unit_x()),
+        ("-y", -Vec3::unit_y()),
+        ("-z", -Vec3::unit_z()),
+    ]
+    .into_iter()
+    .find(|(_, axis)| *axis == direction)
+    .map(|(name, _)| name)
+}
+
+fn parse_polymer(polymer: Polymer) -> Result<Polymer, String> {
+    match polymer.residues() {
+        Ok(_) => Ok(polymer),
+        Err(letter) => Err(format!("`{}` is not a residue", letter)),
+    }
+}
+
+fn parse_cut(shape: &str, args: &[&str]) -> Result<Cut, String> {
+    let options = args
+        .iter()
+        .position(|arg| matches!(*arg, "at" | "outside" | "cap"))
+        .unwrap_or(args.len());
+    let (sizes, options) = args.split_at(options);
+    let length = |text: &str| match text.parse::<f32>() {
+        Ok(length) if length > 0.0 => Ok(length),
+        _ => Err(format!("`{}` is not a length", text)),
+    };
+    let axis = |text: &str| match text {
+        "x" => Ok(Vec3::unit_x()),
+        "y" => Ok(Vec3::unit_y()),
+        "z" => Ok(Vec3::unit_z()),
+        "-x" => Ok(-Vec3::unit_x()),
+        "-y" => Ok(-Vec3::unit_y()),
+        "-z" => Ok(-Vec3::unit_z()),
+        _ => Err(format!("unknown axis `{}`", text)),
+    };
+
+    let shape = match (shape, sizes) {
+        ("plane", [normal]) => CutShape::HalfSpace(Plane::new(Vec3::zero(), axis(normal)?)),
+        ("box", [a, b, c]) => CutShape::Box {
+            center: Vec3::zero(),
+            half_extents: Vec3::new(length(a)?, length(b)?, length(c)?) * 0.5,
+            rotation: Rotor3::identity(),
+        },
+        ("sphere", [radius]) => CutShape::Sphere {
+            center: Vec3::zero(),
+            radius: length(radius)?,
+        },
+        ("cylinder", [direction, radius, height]) => CutShape::Cylinder {
+            center: Vec3::zero(),
+            axis: axis(direction)?,
+            radius: length(radius)?,
+            half_length: length(height)? * 0.5,
+        },
+        ("plane", _) => return Err("expected `plane [-]x|y|z`".to_owned()),
+        ("box", _) => return Err("expected `box <a> <b> <c>`".to_owned()),
+        ("sphere", _) => return Err("expected `sphere <radius>`".to_owned()),
+        ("cylinder", _) => return Err("expected `cylinder x|y|z <radius> <length>`".to_owned()),
+        (shape, _) => return Err(format!("unknown shape `{}`", shape)),
+    };
+
+    let mut cut = Cut::new(shape);
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match *option {
+            "at" => {
+                let mut center = [0.0; 3];
+                for coordinate in &mut center {
+                    let text = options
+                        .next()
+                        .ok_or_else(|| "expected `at <x> <y> <z>`".to_owned())?;
+                    *coordinate = text
+                        .parse()
+                        .map_err(|_| format!("`{}` is not a coordinate", text))?;
+                }
+                cut.shape = cut.shape.translated(Vec3::from(center));
+            }
+            "outside" => cut.keep_inside = false,
+            "cap" => {
+                let element = options
+                    .next()
+                    .ok_or_else(|| "expected an element after `cap`".to_owned())?;
+                cut.cap = Some(parse_element(element)?);
+            }
+            option => return Err(format!("unknown option `{}`", option)),
+        }
+    }
+    Ok(cut)
+}
+
+fn parse_element(symbol: &str) -> Result<Element, String> {
+    Element::from_symbol(symbol).ok_or_else(|| format!("unknown element `{}`", symbol))
+}
+
+fn parse_atom(number: &str, atoms: &[EditId]) -> Result<AtomSpecifier, String> {
+    if let Some(spec) = number.strip_prefix('@') {
+        return spec
+            .parse()
+            .map_err(|_| format!("`{}` is not an atom specifier", spec));
+    }
+    let edit = number
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| atoms.get(n.checked_sub(1)?))
+        .ok_or_else(|| format!("there is no atom {}", number))?;
+    Ok(AtomSpecifier::new(*edit))
+}
+
+fn parse_atoms(numbers: &[&str], atoms: &[EditId]) -> Result<Vec<AtomSpecifier>, String> {
+    numbers
+        .iter()
+        .map(|number| parse_atom(number, atoms))
+        .collect()
+}
+
+// End of File
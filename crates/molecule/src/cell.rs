@@ -0,0 +1,64 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The unit cells of periodic structures, such as crystals.  A molecule with a
+//! cell stands for an infinite lattice of copies of itself (its periodic
+//! images), one in each cell.  Only the atoms of the molecule itself are ever
+//! stored; the images are drawn by translating them.
+
+use serde::{Deserialize, Serialize};
+use ultraviolet::Vec3;
+
+/// The cell that a periodic structure repeats in, given by its three edge
+/// vectors.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct UnitCell {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub c: Vec3,
+}
+
+impl UnitCell {
+    /// The cell with edges of the given lengths (in angstroms) and angles
+    /// between them (in degrees: alpha between b and c, beta between a and c,
+    /// and gamma between a and b), as given in PDB and CIF files.  The cell is
+    /// in the standard orientation, with `a` along the x axis and `b` in the xy
+    /// plane.
+    pub fn from_parameters(lengths: [f32; 3], angles: [f32; 3]) -> Self {
+        let [la, lb, lc] = lengths;
+        let [alpha, beta, gamma] = angles.map(f32::to_radians);
+        let cx = lc * beta.cos();
+        let cy = lc * (alpha.cos() - beta.cos() * gamma.cos()) / gamma.sin();
+        Self {
+            a: Vec3::new(la, 0.0, 0.0),
+            b: Vec3::new(lb * gamma.cos(), lb * gamma.sin(), 0.0),
+            c: Vec3::new(cx, cy, (lc * lc - cx * cx - cy * cy).max(0.0).sqrt()),
+        }
+    }
+
+    /// The translation from this cell to the one `i`, `j` and `k` cells away
+    /// along `a`, `b` and `c`.
+    pub fn translation(&self, i: i32, j: i32, k: i32) -> Vec3 {
+        self.a * i as f32 + self.b * j as f32 + self.c * k as f32
+    }
+
+    /// The translations to every cell within `range` cells of this one along
+    /// each edge, not including this cell itself.
+    pub fn image_translations(&self, range: u32) -> Vec<Vec3> {
+        let range = range as i32;
+        let mut translations = Vec::new();
+        for i in -range..=range {
+            for j in -range..=range {
+                for k in -range..=range {
+                    if (i, j, k) != (0, 0, 0) {
+                        translations.push(self.translation(i, j, k));
+                    }
+                }
+            }
+        }
+        translations
+    }
+}
+
+// End of File
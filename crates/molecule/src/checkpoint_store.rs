@@ -0,0 +1,269 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Storage for the checkpoints of a `MoleculeEditor`.  Each checkpoint is a
+//! full copy of a molecule's graph and positions, so a large molecule with a
+//! long history cannot keep them all in memory.  Only the most recently used
+//! checkpoints are kept in RAM; older ones are compressed and written to a
+//! temporary directory, and read back when they are needed again.
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::molecule::MoleculeCheckpoint;
+
+/// How many checkpoints of one molecule are kept in memory.
+const MAX_RESIDENT_CHECKPOINTS: usize = 16;
+
+/// The zstd compression level used for spilled checkpoints.  Checkpoints are
+/// written while editing, so speed matters more than size.
+#[cfg(not(target_arch = "wasm32"))]
+const COMPRESSION_LEVEL: i32 = 3;
+
+// Numbers the spill directories of the stores in this process.
+static NEXT_STORE: AtomicUsize = AtomicUsize::new(0);
+
+/// When a `MoleculeEditor` records checkpoints on its own as it applies edits,
+/// so that moving back and forth through a long history does not replay it
+/// from the start.  A checkpoint is recorded as soon as either limit is
+/// reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointPolicy {
+    /// Record a checkpoint after this many edits have been applied since the
+    /// last one, or never if `None`.
+    pub every: Option<usize>,
+    /// Record a checkpoint once replaying the edits since the last one costs
+    /// more than this, or never if `None`.  The cost of an edit is the number
+    /// of atoms in the molecule after it, so a few edits to a large molecule
+    /// count as much as many edits to a small one.
+    pub max_replay_cost: Option<usize>,
+}
+
+impl Default for CheckpointPolicy {
+    fn default() -> Self {
+        Self {
+            every: Some(32),
+            max_replay_cost: Some(100_000),
+        }
+    }
+}
+
+impl CheckpointPolicy {
+    /// Never records checkpoints, which leaves only those made when saving.
+    pub const NEVER: CheckpointPolicy = CheckpointPolicy {
+        every: None,
+        max_replay_cost: None,
+    };
+
+    /// Whether a checkpoint is due, `edits` edits and `cost` replay cost after
+    /// the last one.
+    pub fn is_due(&self, edits: usize, cost: usize) -> bool {
+        self.every.is_some_and(|every| edits >= every)
+            || self.max_replay_cost.is_some_and(|max| cost > max)
+    }
+}
+
+#[derive(Default)]
+pub struct CheckpointStore {
+    resident: HashMap<usize, MoleculeCheckpoint>,
+    // The history steps of the checkpoints that were written to disk.
+    spilled: HashMap<usize, PathBuf>,
+    // Every stored history step, least recently used first.
+    recency: VecDeque<usize>,
+    // Created when the first checkpoint is spilled, and removed with the store.
+    directory: Option<PathBuf>,
+}
+
+impl CheckpointStore {
+    pub fn len(&self) -> usize {
+        self.recency.len()
+    }
+
+    /// The history steps that have a checkpoint, in no particular order.
+    pub fn steps(&self) -> impl Iterator<Item = usize> + '_ {
+        self.recency.iter().copied()
+    }
+
+    /// Stores the checkpoint for `step`, replacing any previous one.  If this
+    /// leaves too many checkpoints in memory, the least recently used are
+    /// spilled to disk.
+    pub fn insert(&mut self, step: usize, checkpoint: MoleculeCheckpoint) {
+        self.remove(step);
+        self.resident.insert(step, checkpoint);
+        self.recency.push_back(step);
+        self.evict();
+    }
+
+    /// A copy of the checkpoint for `step`, read back from disk if it was
+    /// spilled.  A spilled checkpoint that cannot be read is forgotten, and
+    /// `None` is returned as if it had never existed.
+    pub fn get(&mut self, step: usize) -> Option<MoleculeCheckpoint> {
+        if let Some(path) = self.spilled.remove(&step) {
+            let loaded = load(&path);
+            let _ = std::fs::remove_file(&path);
+            match loaded {
+                Ok(checkpoint) => {
+                    self.resident.insert(step, checkpoint);
+                }
+                Err(_) => {
+                    self.recency.retain(|stored| *stored != step);
+                    return None;
+                }
+            }
+        }
+
+        let checkpoint = self.resident.get(&step)?.clone();
+        self.touch(step);
+        self.evict();
+        Some(checkpoint)
+    }
+
+    pub fn remove(&mut self, step: usize) {
+        if self.resident.remove(&step).is_none() {
+            match self.spilled.remove(&step) {
+                Some(path) => {
+                    let _ = std::fs::remove_file(path);
+                }
+                None => return,
+            }
+        }
+        self.recency.retain(|stored| *stored != step);
+    }
+
+    /// Keeps only the checkpoints whose history step satisfies `keep`.
+    pub fn retain(&mut self, mut keep: impl FnMut(usize) -> bool) {
+        let dropped: Vec<usize> = self.steps().filter(|step| !keep(*step)).collect();
+        for step in dropped {
+            self.remove(step);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.retain(|_| false);
+    }
+
+    /// Every checkpoint, read back from disk where needed, for saving or
+    /// copying the molecule.  Checkpoints that cannot be read are left out.
+    pub fn to_map(&self) -> HashMap<usize, MoleculeCheckpoint> {
+        let mut checkpoints = self.resident.clone();
+        for (step, path) in &self.spilled {
+            if let Ok(checkpoint) = load(path) {
+                checkpoints.insert(*step, checkpoint);
+            }
+        }
+        checkpoints
+    }
+
+    // Marks `step` as the most recently used.
+    fn touch(&mut self, step: usize) {
+        self.recency.retain(|stored| *stored != step);
+        self.recency.push_back(step);
+    }
+
+    // Spills the least recently used resident checkpoints until few enough
+    // are left in memory.  If one cannot be written it stays in memory.
+    fn evict(&mut self) {
+        // There is no file system on the web, so every checkpoint stays in
+        // memory there.
+        if cfg!(target_arch = "wasm32") {
+            return;
+        }
+        let mut candidates = self.recency.iter().copied().collect::<Vec<_>>().into_iter();
+        while self.resident.len() > MAX_RESIDENT_CHECKPOINTS {
+            let Some(step) = candidates.next() else {
+                return;
+            };
+            if !self.resident.contains_key(&step) {
+                continue;
+            }
+            let Ok(path) = self.spill_path(step) else {
+                return;
+            };
+            if store(&path, &self.resident[&step]).is_ok() {
+                self.resident.remove(&step);
+                self.spilled.insert(step, path);
+            }
+        }
+    }
+
+    fn spill_path(&mut self, step: usize) -> io::Result<PathBuf> {
+        let directory = match self.directory {
+            Some(ref directory) => directory,
+            None => {
+                let directory = std::env::temp_dir().join(format!(
+                    "atomcad-checkpoints-{}-{}",
+                    std::process::id(),
+                    NEXT_STORE.fetch_add(1, Ordering::Relaxed)
+                ));
+                std::fs::create_dir_all(&directory)?;
+                self.directory.insert(directory)
+            }
+        };
+        Ok(directory.join(format!("{}.bin.zst", step)))
+    }
+}
+
+impl Clone for CheckpointStore {
+    // The copy has its own spill directory, so that either store can be
+    // changed or dropped without affecting the other.
+    fn clone(&self) -> Self {
+        let mut clone = Self::default();
+        let mut checkpoints = self.to_map();
+        for step in &self.recency {
+            if let Some(checkpoint) = checkpoints.remove(step) {
+                clone.insert(*step, checkpoint);
+            }
+        }
+        clone
+    }
+}
+
+impl From<HashMap<usize, MoleculeCheckpoint>> for CheckpointStore {
+    fn from(checkpoints: HashMap<usize, MoleculeCheckpoint>) -> Self {
+        let mut store = Self::default();
+        for (step, checkpoint) in checkpoints {
+            store.insert(step, checkpoint);
+        }
+        store
+    }
+}
+
+impl Drop for CheckpointStore {
+    fn drop(&mut self) {
+        if let Some(ref directory) = self.directory {
+            let _ = std::fs::remove_dir_all(directory);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn store(path: &Path, checkpoint: &MoleculeCheckpoint) -> io::Result<()> {
+    let file = std::fs::File::create(path)?;
+    let mut encoder = zstd::Encoder::new(file, COMPRESSION_LEVEL)?;
+    bincode::serialize_into(&mut encoder, checkpoint).map_err(io::Error::other)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load(path: &Path) -> io::Result<MoleculeCheckpoint> {
+    let decoder = zstd::Decoder::new(std::fs::File::open(path)?)?;
+    bincode::deserialize_from(decoder).map_err(io::Error::other)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn store(_path: &Path, _checkpoint: &MoleculeCheckpoint) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn load(_path: &Path) -> io::Result<MoleculeCheckpoint> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+// End of File
@@ -0,0 +1,156 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Composite edits: several edits combined under one name, such as "add
+//! methyl group", which take a single place (and a single step) in the
+//! history.
+//!
+//! Each step of a composite keeps the ID it had as an edit of its own, so the
+//! atoms it makes are named exactly as they were before the steps were
+//! combined, and edits after the composite that refer to them still find
+//! them.  The atoms outside the composite that its steps refer to are its
+//! anchors.  A composite can be reused on other atoms by
+//! [`Composite::instantiate`], which gives its steps new IDs and points its
+//! anchors at the new atoms.
+
+use std::collections::HashMap;
+
+use common::ids::{AtomSpecifier, EditId, PatternInstanceId};
+use serde::{Deserialize, Serialize};
+
+use crate::edit::{Edit, EditContext, EditError};
+
+/// Edits applied one after the other as a single edit.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Composite {
+    pub name: String,
+    /// The edits, in the order they are applied, each with the ID that the
+    /// atoms it makes are named after.
+    pub steps: Vec<(EditId, Edit)>,
+    /// Whether the steps are hidden in the edit history.
+    #[serde(default)]
+    pub collapsed: bool,
+}
+
+/// Why a composite could not be applied to some atoms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnchorCountError {
+    /// How many anchors the composite has.
+    pub expected: usize,
+    /// How many atoms it was to be applied to.
+    pub found: usize,
+}
+
+/// Why edits could not be combined into a composite.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CombineError {
+    /// There were fewer than two edits, or they were not next to each other
+    /// in the history.
+    NotAdjacent,
+    /// One of the edits is suppressed, which a step cannot be on its own.
+    Suppressed,
+}
+
+impl Composite {
+    /// Whether `atom` was made by one of the steps, either directly or as a
+    /// copy.
+    pub fn makes(&self, atom: &AtomSpecifier) -> bool {
+        atom.path
+            .iter()
+            .any(|instance| self.steps.iter().any(|(id, _)| *id == instance.owner_id))
+    }
+
+    /// The atoms outside the composite that its steps refer to, each once, in
+    /// the order they are first referred to.
+    pub fn anchors(&self) -> Vec<&AtomSpecifier> {
+        let mut anchors: Vec<&AtomSpecifier> = Vec::new();
+        for atom in self.steps.iter().flat_map(|(_, step)| step.references()) {
+            if !self.makes(atom) && !anchors.contains(&atom) {
+                anchors.push(atom);
+            }
+        }
+        anchors
+    }
+
+    /// The references of every step to the anchors, which can be rewritten.
+    pub(crate) fn anchors_mut(&mut self) -> Vec<&mut AtomSpecifier> {
+        let ids: Vec<EditId> = self.steps.iter().map(|(id, _)| *id).collect();
+        self.steps
+            .iter_mut()
+            .flat_map(|(_, step)| step.references_mut())
+            .filter(|atom| {
+                !atom
+                    .path
+                    .iter()
+                    .any(|instance| ids.contains(&instance.owner_id))
+            })
+            .collect()
+    }
+
+    /// A copy of the composite that is applied to `targets` instead of its
+    /// anchors, the first target taking the place of the first anchor and so
+    /// on, with its steps numbered from `first_id`.  The caller must make sure
+    /// that no other edit has an ID in that range.
+    pub fn instantiate(
+        &self,
+        targets: &[AtomSpecifier],
+        first_id: EditId,
+    ) -> Result<Composite, AnchorCountError> {
+        let anchors: Vec<AtomSpecifier> = self.anchors().into_iter().cloned().collect();
+        if anchors.len() != targets.len() {
+            return Err(AnchorCountError {
+                expected: anchors.len(),
+                found: targets.len(),
+            });
+        }
+        let ids: HashMap<EditId, EditId> = self
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(index, (id, _))| (*id, first_id + index))
+            .collect();
+
+        let mut copy = self.clone();
+        for (id, step) in &mut copy.steps {
+            *id = ids[id];
+            for atom in step.references_mut() {
+                *atom = match anchors.iter().position(|anchor| *anchor == *atom) {
+                    Some(index) => targets[index].clone(),
+                    None => renumber(atom, &ids),
+                };
+            }
+        }
+        Ok(copy)
+    }
+}
+
+// `atom` with the IDs in its path changed by `ids`.
+fn renumber(atom: &AtomSpecifier, ids: &HashMap<EditId, EditId>) -> AtomSpecifier {
+    AtomSpecifier {
+        path: atom
+            .path
+            .iter()
+            .map(|instance| PatternInstanceId {
+                owner_id: ids
+                    .get(&instance.owner_id)
+                    .copied()
+                    .unwrap_or(instance.owner_id),
+                instance: instance.instance,
+            })
+            .collect(),
+        child_index: atom.child_index,
+    }
+}
+
+pub(crate) fn spawn_composite(
+    composite: &Composite,
+    commands: &mut dyn EditContext,
+) -> Result<(), EditError> {
+    for (id, step) in &composite.steps {
+        step.apply(id, commands)?;
+    }
+    Ok(())
+}
+
+// End of File
@@ -0,0 +1,260 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Sculpting molecules by cutting them with a shape.  A [`Cut`] deletes every
+//! atom on one side of a plane, or outside (or inside) a box, sphere or
+//! cylinder, so that a part can be carved out of an imported crystal.  Atoms
+//! left hanging by too few bonds at the new surface are trimmed as well, and
+//! the surface can be passivated in the same step.
+
+use std::collections::HashSet;
+
+use common::{
+    ids::{AtomSpecifier, EditId},
+    Plane,
+};
+use periodic_table::Element;
+use serde::{Deserialize, Serialize};
+use ultraviolet::{Rotor3, Vec3};
+
+use crate::edit::{fill_valence, EditContext, EditError};
+
+/// The shape that a [`Cut`] is made with.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum CutShape {
+    /// Everything on the side of the plane that its normal faces is outside.
+    HalfSpace(Plane),
+    /// A box with the given half-widths along its own axes, turned by
+    /// `rotation` from the world axes.
+    Box {
+        center: Vec3,
+        half_extents: Vec3,
+        rotation: Rotor3,
+    },
+    Sphere {
+        center: Vec3,
+        radius: f32,
+    },
+    /// A cylinder about the line through `center` along the unit vector
+    /// `axis`, reaching `half_length` to either side of the center.
+    Cylinder {
+        center: Vec3,
+        axis: Vec3,
+        radius: f32,
+        half_length: f32,
+    },
+}
+
+impl CutShape {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CutShape::HalfSpace(_) => "plane",
+            CutShape::Box { .. } => "box",
+            CutShape::Sphere { .. } => "sphere",
+            CutShape::Cylinder { .. } => "cylinder",
+        }
+    }
+
+    /// The middle of the shape, or the point of a plane that it was placed
+    /// through.
+    pub fn center(&self) -> Vec3 {
+        match *self {
+            CutShape::HalfSpace(plane) => plane.point,
+            CutShape::Box { center, .. }
+            | CutShape::Sphere { center, .. }
+            | CutShape::Cylinder { center, .. } => center,
+        }
+    }
+
+    /// The shape moved by `offset`.
+    pub fn translated(mut self, offset: Vec3) -> Self {
+        match &mut self {
+            CutShape::HalfSpace(plane) => plane.point += offset,
+            CutShape::Box { center, .. }
+            | CutShape::Sphere { center, .. }
+            | CutShape::Cylinder { center, .. } => *center += offset,
+        }
+        self
+    }
+
+    /// The shape grown (or shrunk, for a `factor` below 1) about its center.
+    /// A plane has no size, so is left as it is.
+    pub fn scaled(mut self, factor: f32) -> Self {
+        match &mut self {
+            CutShape::HalfSpace(_) => {}
+            CutShape::Box { half_extents, .. } => *half_extents *= factor,
+            CutShape::Sphere { radius, .. } => *radius *= factor,
+            CutShape::Cylinder {
+                radius,
+                half_length,
+                ..
+            } => {
+                *radius *= factor;
+                *half_length *= factor;
+            }
+        }
+        self
+    }
+
+    /// Whether `point` is inside the shape.  Points on its surface count as
+    /// inside.
+    pub fn contains(&self, point: Vec3) -> bool {
+        match *self {
+            CutShape::HalfSpace(plane) => plane.signed_distance(point) <= 0.0,
+            CutShape::Box {
+                center,
+                half_extents,
+                rotation,
+            } => {
+                let local = rotation.reversed() * (point - center);
+                local.x.abs() <= half_extents.x
+                    && local.y.abs() <= half_extents.y
+                    && local.z.abs() <= half_extents.z
+            }
+            CutShape::Sphere { center, radius } => (point - center).mag_sq() <= radius * radius,
+            CutShape::Cylinder {
+                center,
+                axis,
+                radius,
+                half_length,
+            } => {
+                let offset = point - center;
+                let along = offset.dot(axis);
+                along.abs() <= half_length && (offset - axis * along).mag_sq() <= radius * radius
+            }
+        }
+    }
+}
+
+/// Deletes the atoms on one side of a shape's surface.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Cut {
+    pub shape: CutShape,
+    /// Whether the atoms inside the shape are kept (`true`), or those outside
+    /// it.
+    pub keep_inside: bool,
+    /// Atoms that lose bonds to the cut and are left with fewer bonds than
+    /// this are deleted too, and so on until none are left, so that no chains
+    /// or lone atoms hang off the new surface.
+    #[serde(default = "default_min_neighbors")]
+    pub min_neighbors: usize,
+    /// The element that the dangling bonds of the new surface are capped
+    /// with, or `None` to leave them dangling.
+    #[serde(default)]
+    pub cap: Option<Element>,
+}
+
+fn default_min_neighbors() -> usize {
+    2
+}
+
+impl Cut {
+    /// A cut that keeps what is inside `shape`, and leaves the surface
+    /// uncapped.
+    pub fn new(shape: CutShape) -> Self {
+        Self {
+            shape,
+            keep_inside: true,
+            min_neighbors: default_min_neighbors(),
+            cap: None,
+        }
+    }
+
+    /// A short description of the cut, e.g. "keep inside sphere, H caps", for
+    /// display in lists.
+    pub fn describe(&self) -> String {
+        let mut text = format!(
+            "keep {} {}",
+            if self.keep_inside {
+                "inside"
+            } else {
+                "outside"
+            },
+            self.shape.name()
+        );
+        if let Some(cap) = self.cap {
+            text.push_str(&format!(", {} caps", cap.symbol()));
+        }
+        text
+    }
+
+    /// Whether an atom at `pos` is on the side of the shape that is kept.
+    pub fn keeps(&self, pos: Vec3) -> bool {
+        self.shape.contains(pos) == self.keep_inside
+    }
+
+    /// The atoms that the cut deletes: those on the wrong side of the shape,
+    /// and then those left with too few bonds by the deletion.  Atoms that
+    /// had few bonds to begin with, such as hydrogens, are only deleted if
+    /// they lose one.
+    pub fn removed(&self, commands: &dyn EditContext) -> HashSet<AtomSpecifier> {
+        let mut removed: HashSet<AtomSpecifier> = commands
+            .atoms()
+            .into_iter()
+            .filter(|atom| commands.pos(atom).is_some_and(|pos| !self.keeps(*pos)))
+            .collect();
+
+        let mut frontier: Vec<AtomSpecifier> = removed
+            .iter()
+            .flat_map(|atom| commands.bonds(atom))
+            .map(|(neighbor, _)| neighbor)
+            .filter(|neighbor| !removed.contains(neighbor))
+            .collect();
+        while let Some(atom) = frontier.pop() {
+            if removed.contains(&atom) {
+                continue;
+            }
+            let bonds = commands.bonds(&atom);
+            let kept = bonds
+                .iter()
+                .filter(|(neighbor, _)| !removed.contains(neighbor))
+                .count();
+            if kept < self.min_neighbors.min(bonds.len()) {
+                removed.insert(atom);
+                frontier.extend(
+                    bonds
+                        .into_iter()
+                        .map(|(neighbor, _)| neighbor)
+                        .filter(|neighbor| !removed.contains(neighbor)),
+                );
+            }
+        }
+        removed
+    }
+}
+
+/// Deletes the atoms that `cut` removes, and caps the atoms that lost bonds
+/// to them, naming the caps after the edit `edit_id`.
+pub(crate) fn spawn_cut(
+    cut: &Cut,
+    edit_id: &EditId,
+    commands: &mut dyn EditContext,
+) -> Result<(), EditError> {
+    let removed = cut.removed(&*commands);
+    // The surface is listed in the molecule's order rather than the set's, so
+    // that the caps are named the same each time the edit is applied.
+    let surface: Vec<AtomSpecifier> = commands
+        .atoms()
+        .into_iter()
+        .filter(|atom| {
+            !removed.contains(atom)
+                && commands
+                    .bonds(atom)
+                    .iter()
+                    .any(|(neighbor, _)| removed.contains(neighbor))
+        })
+        .collect();
+    let removed: Vec<AtomSpecifier> = removed.into_iter().collect();
+    commands.remove_atoms(&removed)?;
+
+    if let Some(cap) = cut.cap {
+        let mut spec = AtomSpecifier::new(*edit_id);
+        for atom in &surface {
+            fill_valence(atom, cap, &mut spec, commands)?;
+        }
+    }
+    Ok(())
+}
+
+// End of File
@@ -0,0 +1,139 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Random point defects, for studying how dopants or vacancies change a
+//! structure.  A [`Doping`] chooses a fraction of the candidate atoms at
+//! random, and either changes them into atoms of another element or deletes
+//! them.  The choice is made by a random number generator whose seed is part
+//! of the edit, so the same atoms are chosen each time the history is
+//! replayed, and a study can be reproduced from the saved design.
+
+use common::ids::AtomSpecifier;
+use periodic_table::Element;
+use serde::{Deserialize, Serialize};
+
+use crate::edit::{EditContext, EditError};
+
+/// Substitutes or deletes a random fraction of atoms.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Doping {
+    /// The element that the chosen atoms are changed into, or `None` to
+    /// delete them, leaving vacancies.
+    pub substitute: Option<Element>,
+    /// The fraction of the candidates that are chosen, from 0 to 1.  The
+    /// number chosen is rounded to the nearest whole atom.
+    pub fraction: f32,
+    /// The elements whose atoms are candidates, or empty for every element.
+    /// Atoms that are already of the substitute element never are.
+    #[serde(default)]
+    pub elements: Vec<Element>,
+    /// The atoms that are candidates, such as those of a named selection, or
+    /// empty for every atom.
+    #[serde(default)]
+    pub atoms: Vec<AtomSpecifier>,
+    /// The seed of the random choice.
+    pub seed: u64,
+}
+
+impl Doping {
+    /// A short description, e.g. "5% B, seed 1234", for display in lists.
+    pub fn describe(&self) -> String {
+        let percent = (self.fraction * 1000.0).round() / 10.0;
+        let mut text = match self.substitute {
+            Some(element) => format!("{}% {}", percent, element.symbol()),
+            None => format!("{}% vacancies", percent),
+        };
+        if !self.elements.is_empty() {
+            let symbols: Vec<&str> = self
+                .elements
+                .iter()
+                .map(|element| element.symbol())
+                .collect();
+            text.push_str(" in ");
+            text.push_str(&symbols.join(", "));
+        }
+        if !self.atoms.is_empty() {
+            text.push_str(&format!(", {} atoms", self.atoms.len()));
+        }
+        text.push_str(&format!(", seed {}", self.seed));
+        text
+    }
+
+    /// The atoms that could be chosen, in a fixed order that does not depend
+    /// on how the molecule is stored.
+    pub fn candidates(&self, commands: &dyn EditContext) -> Vec<AtomSpecifier> {
+        let atoms = if self.atoms.is_empty() {
+            commands.atoms()
+        } else {
+            self.atoms.clone()
+        };
+        let mut candidates: Vec<(String, AtomSpecifier)> = atoms
+            .into_iter()
+            .filter(|spec| {
+                commands.find_atom(spec).is_some_and(|atom| {
+                    Some(atom.element) != self.substitute
+                        && (self.elements.is_empty() || self.elements.contains(&atom.element))
+                })
+            })
+            .map(|spec| (spec.to_string(), spec))
+            .collect();
+        candidates.sort_by(|(a, _), (b, _)| a.cmp(b));
+        candidates.dedup_by(|(a, _), (b, _)| a == b);
+        candidates.into_iter().map(|(_, spec)| spec).collect()
+    }
+
+    /// The atoms that are substituted or deleted.
+    pub fn targets(&self, commands: &dyn EditContext) -> Vec<AtomSpecifier> {
+        let mut candidates = self.candidates(commands);
+        let count = ((candidates.len() as f32 * self.fraction.clamp(0.0, 1.0)).round() as usize)
+            .min(candidates.len());
+
+        // The first `count` steps of a Fisher-Yates shuffle.
+        let mut rng = SplitMix64(self.seed);
+        for i in 0..count {
+            let j = i + rng.below(candidates.len() - i);
+            candidates.swap(i, j);
+        }
+        candidates.truncate(count);
+        candidates
+    }
+}
+
+/// Substitutes or deletes the atoms that `doping` chooses.
+pub(crate) fn spawn_doping(
+    doping: &Doping,
+    commands: &mut dyn EditContext,
+) -> Result<(), EditError> {
+    let targets = doping.targets(&*commands);
+    match doping.substitute {
+        Some(element) => {
+            for target in &targets {
+                commands.set_element(target, element)?;
+            }
+        }
+        None => commands.remove_atoms(&targets)?,
+    }
+    Ok(())
+}
+
+// A small, fast generator (Steele, Lea and Flood's SplitMix64).  Its output
+// must never change, or the atoms chosen by saved designs would change too.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // A number from 0 up to but not including `bound`, which must not be 0.
+    fn below(&mut self, bound: usize) -> usize {
+        ((self.next() as u128 * bound as u128) >> 64) as usize
+    }
+}
+
+// End of File
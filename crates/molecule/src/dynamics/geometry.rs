@@ -0,0 +1,73 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Internal coordinates shared by the force fields, with their gradients with
+//! respect to the positions of the atoms involved.
+
+use ultraviolet::Vec3;
+
+/// The cosine of the angle at `center` between the bonds to `a` and `b`, along
+/// with its gradient with respect to `a` and to `b`.  The gradient with respect
+/// to `center` is minus their sum.
+pub(super) fn cos_angle(a: Vec3, center: Vec3, b: Vec3) -> (f32, Vec3, Vec3) {
+    let (ra, rb) = (a - center, b - center);
+    let (la, lb) = (ra.mag(), rb.mag());
+    let (ua, ub) = (ra / la, rb / lb);
+    let cos = ua.dot(ub);
+    (cos, (ub - ua * cos) / la, (ua - ub * cos) / lb)
+}
+
+/// The dihedral angle of the chain `i-j-k-l`, in radians, along with its
+/// gradient with respect to each of the four atoms.  Returns `None` if three
+/// of the atoms are collinear, in which case the angle is undefined.
+///
+/// This uses the formulation of Blondel and Karplus, J. Comput. Chem. 17
+/// (1996), which is stable for all angles.
+pub(super) fn dihedral([i, j, k, l]: [Vec3; 4]) -> Option<(f32, [Vec3; 4])> {
+    let f = i - j;
+    let g = j - k;
+    let h = l - k;
+    let a = f.cross(g);
+    let b = h.cross(g);
+    let (a2, b2, g_len) = (a.mag_sq(), b.mag_sq(), g.mag());
+    if a2 < 1e-8 || b2 < 1e-8 || g_len < 1e-8 {
+        return None;
+    }
+
+    let phi = f32::atan2(b.cross(a).dot(g) / g_len, a.dot(b));
+    let grad_i = -a * (g_len / a2);
+    let grad_l = b * (g_len / b2);
+    let shared = a * (f.dot(g) / (a2 * g_len)) - b * (h.dot(g) / (b2 * g_len));
+    Some((phi, [grad_i, -grad_i + shared, -grad_l - shared, grad_l]))
+}
+
+/// The sine of the angle between the bond `center-out` and the plane through
+/// `center`, `a` and `b` (the Wilson angle), along with its gradient with
+/// respect to `a`, `b` and `out`.  The gradient with respect to `center` is
+/// minus their sum.  Returns `None` if the plane is undefined.
+pub(super) fn sin_out_of_plane(
+    center: Vec3,
+    a: Vec3,
+    b: Vec3,
+    out: Vec3,
+) -> Option<(f32, Vec3, Vec3, Vec3)> {
+    let (ra, rb, rl) = (a - center, b - center, out - center);
+    let normal = ra.cross(rb);
+    let (normal_len, out_len) = (normal.mag(), rl.mag());
+    if normal_len < 1e-8 || out_len < 1e-8 {
+        return None;
+    }
+
+    let (n, u) = (normal / normal_len, rl / out_len);
+    let sin = n.dot(u);
+    let grad_normal = (u - n * sin) / normal_len;
+    Some((
+        sin,
+        rb.cross(grad_normal),
+        grad_normal.cross(ra),
+        (n - u * sin) / out_len,
+    ))
+}
+
+// End of File
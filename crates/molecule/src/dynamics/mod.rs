@@ -0,0 +1,403 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::VecDeque;
+
+use petgraph::visit::{
+    EdgeIndexable as _, EdgeRef as _, IntoEdgeReferences as _, NodeIndexable as _,
+};
+use serde::{Deserialize, Serialize};
+use ultraviolet::Vec3;
+
+use crate::molecule::MoleculeGraph;
+
+mod geometry;
+mod neighbors;
+pub mod parameters;
+mod shake;
+mod simple;
+mod uff;
+
+pub use shake::{shake, ShakeOptions, ShakeReport, ShakeTrial};
+pub use simple::SimpleForceField;
+pub use uff::{UniversalForceField, DEFAULT_VDW_CUTOFF};
+
+/// A model of the potential energy of a molecule as a function of its atoms'
+/// positions.  A force field is built for one particular molecule graph, so it
+/// can precompute which interactions exist between which atoms.
+///
+/// Atoms are identified by their `AtomIndex::index()` in the graph the force
+/// field was built from, and `positions` and `forces` are indexed accordingly
+/// (they are `graph.node_bound()` long, so indices of removed atoms are unused).
+pub trait ForceField {
+    /// Returns the potential energy of the molecule with its atoms at
+    /// `positions`, and adds the force on each atom (the negative gradient of
+    /// the energy) to `forces`.
+    fn evaluate(&self, positions: &[Vec3], forces: &mut [Vec3]) -> f32;
+}
+
+/// Holds the dihedral angle of the chain of atoms `atoms` near `angle`, in
+/// radians.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TorsionRestraint {
+    pub atoms: [usize; 4],
+    pub angle: f32,
+    pub stiffness: f32,
+}
+
+/// A force field with restraints added to it.
+pub struct Restrained<'a> {
+    pub field: &'a dyn ForceField,
+    pub torsions: Vec<TorsionRestraint>,
+    /// Atoms that are held still, as pinned atoms are.
+    pub held: Vec<usize>,
+}
+
+impl ForceField for Restrained<'_> {
+    fn evaluate(&self, positions: &[Vec3], forces: &mut [Vec3]) -> f32 {
+        let mut energy = self.field.evaluate(positions, forces);
+        for torsion in &self.torsions {
+            let Some((phi, gradients)) = geometry::dihedral(torsion.atoms.map(|i| positions[i]))
+            else {
+                continue;
+            };
+            energy += torsion.stiffness * (1.0 - (phi - torsion.angle).cos());
+
+            let de = torsion.stiffness * (phi - torsion.angle).sin();
+            for (atom, gradient) in torsion.atoms.into_iter().zip(gradients) {
+                forces[atom] -= gradient * de;
+            }
+        }
+        for &atom in &self.held {
+            forces[atom] = Vec3::zero();
+        }
+        energy
+    }
+}
+
+/// The force fields that molecules can be relaxed with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ForceFieldKind {
+    /// A fast approximation that only knows about bond lengths and VSEPR
+    /// geometry.
+    #[default]
+    Simple,
+    /// The Universal Force Field, which has parameters for every element.
+    Uff,
+}
+
+impl ForceFieldKind {
+    /// Builds a force field of this kind for the molecule `graph`, ignoring
+    /// van der Waals interactions between atoms more than `vdw_cutoff`
+    /// angstroms apart.  The simple force field only has contact repulsion,
+    /// which ends well within any cutoff, and ignores it.
+    pub fn build(self, graph: &MoleculeGraph, vdw_cutoff: f32) -> Box<dyn ForceField> {
+        match self {
+            ForceFieldKind::Simple => Box::new(SimpleForceField::new(graph)),
+            ForceFieldKind::Uff => Box::new(UniversalForceField::with_cutoff(graph, vdw_cutoff)),
+        }
+    }
+
+    /// The length that this kind of force field relaxes each bond of `graph`
+    /// towards, in angstroms, indexed by `BondIndex::index()`.
+    pub fn ideal_bond_lengths(self, graph: &MoleculeGraph) -> Vec<f32> {
+        match self {
+            ForceFieldKind::Simple => {
+                let mut lengths = vec![0.0; graph.edge_bound()];
+                for edge in graph.edge_references() {
+                    lengths[edge.id().index()] = parameters::bond_length(
+                        graph[edge.source()].element,
+                        graph[edge.target()].element,
+                        *edge.weight(),
+                    );
+                }
+                lengths
+            }
+            ForceFieldKind::Uff => uff::rest_lengths(graph),
+        }
+    }
+
+    /// The stiffness of the restraints that hold set torsions, `k` in
+    /// `E = k (1 - cos(φ - φ0))`.  This is well above the torsional barriers of
+    /// the force field, so that a set torsion survives relaxation.
+    pub fn torsion_restraint_stiffness(self) -> f32 {
+        match self {
+            ForceFieldKind::Simple => 500.0 * parameters::SP3_TORSION_BARRIER,
+            // In kcal/mol, against barriers of a few kcal/mol.
+            ForceFieldKind::Uff => 1000.0,
+        }
+    }
+
+    /// Convergence criteria suited to the units and stiffness of this kind of
+    /// force field.
+    pub fn default_convergence(self) -> ConvergenceCriteria {
+        match self {
+            ForceFieldKind::Simple => ConvergenceCriteria {
+                max_force: 1e-3,
+                energy_delta: 1e-7,
+                max_iterations: MAX_ITERATIONS,
+            },
+            // UFF energies are in kcal/mol and forces in kcal/mol/Å.
+            ForceFieldKind::Uff => ConvergenceCriteria {
+                max_force: 0.1,
+                energy_delta: 1e-6,
+                max_iterations: MAX_ITERATIONS,
+            },
+        }
+    }
+}
+
+/// When relaxation stops.  Forces and energies are in the units of the force
+/// field being used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConvergenceCriteria {
+    /// Relaxation has converged once no atom has a force larger than this on
+    /// it.
+    pub max_force: f32,
+    /// Relaxation has also converged once a step lowers the energy by less
+    /// than this.
+    pub energy_delta: f32,
+    /// Relaxation gives up after this many iterations.
+    pub max_iterations: usize,
+}
+
+/// Why relaxation stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RelaxStatus {
+    /// The largest force fell below `ConvergenceCriteria::max_force`.
+    ForceConverged,
+    /// A step lowered the energy by less than
+    /// `ConvergenceCriteria::energy_delta`.
+    EnergyConverged,
+    /// No step lowered the energy, even in the direction of steepest descent.
+    /// This usually means the energy is as low as its precision allows, but
+    /// the forces are still above the threshold.
+    Stalled,
+    /// `ConvergenceCriteria::max_iterations` was reached first.
+    IterationLimit,
+}
+
+impl RelaxStatus {
+    pub fn is_converged(self) -> bool {
+        matches!(
+            self,
+            RelaxStatus::ForceConverged | RelaxStatus::EnergyConverged
+        )
+    }
+}
+
+/// The outcome of a relaxation.
+#[derive(Clone, Copy, Debug)]
+pub struct RelaxReport {
+    pub status: RelaxStatus,
+    pub iterations: usize,
+    /// The energy of the relaxed molecule.
+    pub energy: f32,
+    /// The largest force left on any atom.
+    pub max_force: f32,
+}
+
+/// The default limit on the number of iterations.
+const MAX_ITERATIONS: usize = 2_000;
+
+/// The number of previous steps L-BFGS uses to estimate the curvature of the
+/// energy.
+const HISTORY_LENGTH: usize = 8;
+
+/// How far the atom under the largest force moves in a steepest descent step,
+/// which is taken when there is no curvature estimate to size the step with.
+const INITIAL_DISPLACEMENT: f32 = 0.05;
+
+/// The furthest an atom can move in a single step, in angstroms.
+const MAX_DISPLACEMENT: f32 = 0.2;
+
+/// A step must lower the energy by at least this fraction of the decrease
+/// predicted by the gradient to be accepted (the Armijo condition).
+const SUFFICIENT_DECREASE: f32 = 1e-4;
+
+/// The number of times a step is halved before the line search gives up.
+const MAX_BACKTRACKS: usize = 20;
+
+/// Relaxes a molecule using a force field of the given kind, with the given
+/// van der Waals cutoff.
+pub fn relax(
+    force_field: ForceFieldKind,
+    vdw_cutoff: f32,
+    graph: &MoleculeGraph,
+    positions: &[Vec3],
+    criteria: &ConvergenceCriteria,
+) -> (Vec<Vec3>, RelaxReport) {
+    relax_with(
+        &*force_field.build(graph, vdw_cutoff),
+        graph,
+        positions,
+        criteria,
+    )
+}
+
+/// Relaxes a molecule by minimizing the energy of `field`, which must have been
+/// built from `graph`, with the L-BFGS method and a backtracking line search.
+/// Pinned atoms do not move.  Positions are indexed by the atoms' indices in
+/// `graph` (see `Molecule::dense_positions`), and the relaxed positions are
+/// returned the same way.
+pub fn relax_with(
+    field: &dyn ForceField,
+    graph: &MoleculeGraph,
+    positions: &[Vec3],
+    criteria: &ConvergenceCriteria,
+) -> (Vec<Vec3>, RelaxReport) {
+    let mut current = positions.to_vec();
+    current.resize(graph.node_bound(), Vec3::zero());
+
+    // Zeroing the forces on pinned atoms is enough to hold them still: every
+    // step L-BFGS takes is built from the forces and earlier steps, so none of
+    // them ever moves a pinned atom.
+    let pinned: Vec<usize> = graph
+        .node_indices()
+        .filter(|index| graph[*index].pinned)
+        .map(|index| index.index())
+        .collect();
+    let evaluate = |positions: &[Vec3], forces: &mut [Vec3]| {
+        let energy = field.evaluate(positions, forces);
+        for &atom in &pinned {
+            forces[atom] = Vec3::zero();
+        }
+        energy
+    };
+
+    let mut forces = vec![Vec3::zero(); current.len()];
+    let mut energy = evaluate(&current, &mut forces);
+    let mut trial = current.clone();
+    let mut trial_forces = forces.clone();
+    // The most recent steps and the changes in force over them, oldest first.
+    let mut history: VecDeque<(Vec<Vec3>, Vec<Vec3>, f64)> = VecDeque::new();
+    let mut report = RelaxReport {
+        status: RelaxStatus::IterationLimit,
+        iterations: 0,
+        energy,
+        max_force: largest(&forces),
+    };
+
+    loop {
+        if report.max_force < criteria.max_force {
+            report.status = RelaxStatus::ForceConverged;
+            break;
+        }
+        if report.iterations >= criteria.max_iterations {
+            break;
+        }
+        report.iterations += 1;
+
+        let mut direction = search_direction(&forces, &history);
+        let mut slope = dot(&direction, &forces);
+        if slope <= 0.0 {
+            // The curvature estimate has gone bad; fall back to steepest
+            // descent.
+            history.clear();
+            direction = search_direction(&forces, &history);
+            slope = dot(&direction, &forces);
+        }
+
+        let mut step = f32::min(1.0, MAX_DISPLACEMENT / largest(&direction));
+        let mut accepted = None;
+        for _ in 0..MAX_BACKTRACKS {
+            for ((trial, current), direction) in trial.iter_mut().zip(&current).zip(&direction) {
+                *trial = *current + *direction * step;
+            }
+            trial_forces
+                .iter_mut()
+                .for_each(|force| *force = Vec3::zero());
+            let trial_energy = evaluate(&trial, &mut trial_forces);
+            if trial_energy <= energy - SUFFICIENT_DECREASE * step * slope as f32 {
+                accepted = Some(trial_energy);
+                break;
+            }
+            step *= 0.5;
+        }
+
+        let Some(trial_energy) = accepted else {
+            if history.is_empty() {
+                report.status = RelaxStatus::Stalled;
+                break;
+            }
+            history.clear();
+            continue;
+        };
+
+        let displacement: Vec<Vec3> = trial.iter().zip(&current).map(|(t, c)| *t - *c).collect();
+        // The change in gradient, which is minus the change in force.
+        let change: Vec<Vec3> = forces
+            .iter()
+            .zip(&trial_forces)
+            .map(|(old, new)| *old - *new)
+            .collect();
+        let curvature = dot(&displacement, &change);
+        if curvature > 0.0 {
+            if history.len() == HISTORY_LENGTH {
+                history.pop_front();
+            }
+            history.push_back((displacement, change, 1.0 / curvature));
+        }
+
+        std::mem::swap(&mut current, &mut trial);
+        std::mem::swap(&mut forces, &mut trial_forces);
+        let decrease = energy - trial_energy;
+        energy = trial_energy;
+        report.energy = energy;
+        report.max_force = largest(&forces);
+
+        if decrease < criteria.energy_delta && report.max_force >= criteria.max_force {
+            report.status = RelaxStatus::EnergyConverged;
+            break;
+        }
+    }
+
+    (current, report)
+}
+
+/// Finds the direction to move the atoms in, by the L-BFGS two-loop recursion
+/// over `history`.  With no history this is the direction of the forces,
+/// scaled so that the atom under the largest force moves
+/// `INITIAL_DISPLACEMENT`.
+fn search_direction(forces: &[Vec3], history: &VecDeque<(Vec<Vec3>, Vec<Vec3>, f64)>) -> Vec<Vec3> {
+    let mut direction = forces.to_vec();
+    let mut alphas = Vec::with_capacity(history.len());
+    for (step, change, rho) in history.iter().rev() {
+        let alpha = rho * dot(step, &direction);
+        axpy(&mut direction, -alpha, change);
+        alphas.push(alpha);
+    }
+
+    let scale = match history.back() {
+        Some((step, change, _)) => dot(step, change) / dot(change, change),
+        None => (INITIAL_DISPLACEMENT / largest(forces).max(f32::MIN_POSITIVE)) as f64,
+    };
+    direction.iter_mut().for_each(|d| *d *= scale as f32);
+
+    for ((step, change, rho), alpha) in history.iter().zip(alphas.into_iter().rev()) {
+        let beta = rho * dot(change, &direction);
+        axpy(&mut direction, alpha - beta, step);
+    }
+
+    direction
+}
+
+/// The dot product of two configurations, as if they were flat vectors.
+fn dot(a: &[Vec3], b: &[Vec3]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a.dot(*b) as f64).sum()
+}
+
+/// Adds `factor * x` to `y`.
+fn axpy(y: &mut [Vec3], factor: f64, x: &[Vec3]) {
+    for (y, x) in y.iter_mut().zip(x) {
+        *y += *x * factor as f32;
+    }
+}
+
+/// The largest of a set of vectors' magnitudes.
+fn largest(vectors: &[Vec3]) -> f32 {
+    vectors.iter().map(|v| v.mag()).fold(0.0, f32::max)
+}
+
+// End of File
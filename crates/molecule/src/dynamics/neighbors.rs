@@ -0,0 +1,95 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A Verlet neighbor list, which finds the pairs of atoms close enough to
+//! interact through non-bonded terms without checking every pair.
+//!
+//! The list holds every pair within the cutoff plus a margin (the skin).  As
+//! long as no atom has moved more than half the skin since the list was built,
+//! no pair outside the list can have come within the cutoff, so the list only
+//! has to be rebuilt occasionally.  Building it uses a spatial index with cells
+//! as wide as the list's radius, so that each atom is only compared with atoms
+//! in its own and the adjacent cells.
+
+use std::collections::HashSet;
+
+use ultraviolet::Vec3;
+
+use crate::spatial::SpatialIndex;
+
+/// The margin added to the cutoff when building the list, in angstroms.  A
+/// larger skin means fewer rebuilds but more pairs to check at each step.
+const SKIN: f32 = 1.0;
+
+pub(super) struct NeighborList {
+    atoms: Vec<usize>,
+    /// Pairs that never interact, with the smaller index first.
+    excluded: HashSet<(usize, usize)>,
+    cutoff: f32,
+    pairs: Vec<[usize; 2]>,
+    /// The positions the list was built from, or empty if it has not been
+    /// built yet.
+    built_at: Vec<Vec3>,
+}
+
+impl NeighborList {
+    /// Creates a list over `atoms`, which finds every pair within `cutoff`
+    /// angstroms except those in `excluded`.
+    pub(super) fn new(atoms: Vec<usize>, excluded: HashSet<(usize, usize)>, cutoff: f32) -> Self {
+        Self {
+            atoms,
+            excluded,
+            cutoff,
+            pairs: Vec::new(),
+            built_at: Vec::new(),
+        }
+    }
+
+    pub(super) fn cutoff(&self) -> f32 {
+        self.cutoff
+    }
+
+    /// Returns every pair of atoms that may be within the cutoff of each other
+    /// at `positions`, rebuilding the list first if the atoms have moved too
+    /// far since it was last built.  Some of the pairs may be further apart
+    /// than the cutoff.
+    pub(super) fn pairs(&mut self, positions: &[Vec3]) -> &[[usize; 2]] {
+        if self.needs_rebuild(positions) {
+            self.rebuild(positions);
+        }
+        &self.pairs
+    }
+
+    fn needs_rebuild(&self, positions: &[Vec3]) -> bool {
+        if self.built_at.len() != positions.len() {
+            return true;
+        }
+        let limit = (0.5 * SKIN) * (0.5 * SKIN);
+        self.atoms
+            .iter()
+            .any(|&atom| (positions[atom] - self.built_at[atom]).mag_sq() > limit)
+    }
+
+    fn rebuild(&mut self, positions: &[Vec3]) {
+        let radius = self.cutoff + SKIN;
+        let index = SpatialIndex::new(
+            self.atoms.iter().map(|&atom| (atom, positions[atom])),
+            radius,
+        );
+
+        self.pairs.clear();
+        for &a in &self.atoms {
+            for b in index.within(positions[a], radius) {
+                // Each pair is seen from both atoms; keep it once.
+                if b > a && !self.excluded.contains(&(a, b)) {
+                    self.pairs.push([a, b]);
+                }
+            }
+        }
+
+        self.built_at = positions.to_vec();
+    }
+}
+
+// End of File
@@ -0,0 +1,61 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Equilibrium geometry and stiffness of bonds, derived from the bonded
+//! elements and the bond order, along with the strength of the other
+//! interactions in the `SimpleForceField`.  Energies are in the units of
+//! `relax`, in which stretching a single carbon-carbon bond by `x` angstroms
+//! costs `x²`.
+
+use periodic_table::Element;
+
+use crate::BondOrder;
+
+/// The stiffness of a single carbon-carbon bond, in the units used by `relax`
+/// (force per angstrom of stretch).  All other force constants are scaled
+/// relative to it.
+const REFERENCE_STIFFNESS: f32 = 2.0;
+
+/// The length of a single carbon-carbon bond, in angstroms.
+const REFERENCE_LENGTH: f32 = 1.50;
+
+/// The equilibrium length of a bond, in angstroms.  This is the sum of the two
+/// atoms' covalent radii, shortened for multiple bonds according to Pauling's
+/// relation `D(n) = D(1) - 0.71 log10(n)`.
+pub fn bond_length(a: Element, b: Element, order: BondOrder) -> f32 {
+    let single = a.covalent_radius() + b.covalent_radius();
+    single - 0.71 * (order.max(1) as f32).log10()
+}
+
+/// The force constant of a bond.  Shorter bonds are stiffer, following Badger's
+/// rule that force constants scale with the inverse cube of the bond length,
+/// and each additional bond order adds the stiffness of another single bond.
+pub fn bond_stiffness(a: Element, b: Element, order: BondOrder) -> f32 {
+    let length = bond_length(a, b, order);
+    REFERENCE_STIFFNESS * order.max(1) as f32 * (REFERENCE_LENGTH / length).powi(3)
+}
+
+/// The stiffness of bond angles, `k` in `E = k (cos θ - cos θ0)²`.  Bending an
+/// angle by ten degrees costs about as much as stretching a bond by 0.07
+/// angstroms, a similar ratio to typical organic force fields.
+pub const ANGLE_STIFFNESS: f32 = 0.2;
+
+/// The barrier to rotation about a bond between two tetrahedral atoms, which
+/// favours staggered conformations.
+pub const SP3_TORSION_BARRIER: f32 = 0.01;
+
+/// The barrier to rotation about a bond between two trigonal planar atoms,
+/// which favours planar conformations.
+pub const SP2_TORSION_BARRIER: f32 = 0.05;
+
+/// The stiffness of the repulsive wall between atoms that are neither bonded
+/// nor bonded to a common atom, `k` in `E = k (d0 - d)²` for `d < d0`.
+pub const REPULSION_STIFFNESS: f32 = 1.0;
+
+/// The distance below which non-bonded atoms repel each other, `d0` above.
+pub fn contact_distance(a: Element, b: Element) -> f32 {
+    a.covalent_radius() + b.covalent_radius() + 1.2
+}
+
+// End of File
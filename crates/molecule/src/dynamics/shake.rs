@@ -0,0 +1,165 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A quick check of whether a design is stable: jostle some of its atoms at
+//! random, as thermal motion would, relax it again, and see whether it goes
+//! back to the geometry it started from.  A part that settles somewhere else
+//! was sitting on a saddle point or in a shallow minimum, and may not hold its
+//! shape once built.
+
+use ultraviolet::Vec3;
+
+use super::{relax_with, ConvergenceCriteria, ForceFieldKind, RelaxReport};
+use crate::molecule::Molecule;
+use crate::selection::AtomSelection;
+use crate::superposition;
+
+/// How a shake test is run.
+#[derive(Clone, Copy, Debug)]
+pub struct ShakeOptions {
+    /// How many times the atoms are shaken and relaxed.
+    pub trials: usize,
+    /// The standard deviation of each atom's displacement along each axis, in
+    /// angstroms.  The default is roughly the thermal motion of a covalently
+    /// bonded atom at room temperature.
+    pub amplitude: f32,
+    /// How far (as an RMSD, in angstroms) a relaxed structure can be from the
+    /// original and still count as having returned to it.
+    pub tolerance: f32,
+    /// The seed of the random displacements, so that a test can be repeated.
+    pub seed: u64,
+}
+
+impl Default for ShakeOptions {
+    fn default() -> Self {
+        Self {
+            trials: 5,
+            amplitude: 0.1,
+            tolerance: 0.05,
+            seed: 0,
+        }
+    }
+}
+
+/// The outcome of one shake and relaxation.
+#[derive(Clone, Copy, Debug)]
+pub struct ShakeTrial {
+    /// The RMSD between the relaxed structure and the original, after
+    /// superposing them, in angstroms.
+    pub rmsd: f32,
+    /// Whether `rmsd` is within the tolerance.
+    pub returned: bool,
+    pub relax: RelaxReport,
+}
+
+/// The outcome of a shake test.
+#[derive(Clone, Debug)]
+pub struct ShakeReport {
+    /// The relaxation of the original structure, which every trial starts
+    /// from and is compared against.
+    pub reference: RelaxReport,
+    pub trials: Vec<ShakeTrial>,
+}
+
+impl ShakeReport {
+    /// Whether the structure returned to its original geometry every time.
+    pub fn is_stable(&self) -> bool {
+        self.trials.iter().all(|trial| trial.returned)
+    }
+
+    /// The largest RMSD of any trial from the original structure.
+    pub fn worst_rmsd(&self) -> f32 {
+        self.trials
+            .iter()
+            .map(|trial| trial.rmsd)
+            .fold(0.0, f32::max)
+    }
+}
+
+/// Shakes the `atoms` of `molecule` and relaxes it again, `options.trials`
+/// times, with the given force field and van der Waals cutoff.  The molecule is relaxed once first, so
+/// that the trials are compared against a minimum rather than against
+/// wherever the atoms happen to be.  Pinned atoms are not shaken.  The
+/// molecule itself is not changed.
+pub fn shake(
+    molecule: &Molecule,
+    atoms: &AtomSelection,
+    force_field: ForceFieldKind,
+    vdw_cutoff: f32,
+    criteria: &ConvergenceCriteria,
+    options: &ShakeOptions,
+) -> ShakeReport {
+    let graph = &molecule.graph;
+    let start = molecule.dense_positions();
+    let field = force_field.build(graph, vdw_cutoff);
+    let (reference, reference_report) = relax_with(&*field, graph, start, criteria);
+
+    // The atoms are compared in the order of the graph, skipping the holes
+    // left in its indices by removed atoms.
+    let indices: Vec<usize> = graph.node_indices().map(|index| index.index()).collect();
+    let reference_points: Vec<Vec3> = indices.iter().map(|&i| reference[i]).collect();
+    let shaken: Vec<usize> = graph
+        .node_indices()
+        .filter(|&index| !graph[index].pinned && atoms.contains(&molecule.spec(index)))
+        .map(|index| index.index())
+        .collect();
+
+    let mut random = SplitMix64(options.seed);
+    let trials = (0..options.trials)
+        .map(|_| {
+            let mut positions = reference.clone();
+            for &atom in &shaken {
+                positions[atom] += Vec3::new(
+                    random.next_gaussian(),
+                    random.next_gaussian(),
+                    random.next_gaussian(),
+                ) * options.amplitude;
+            }
+
+            let (relaxed, report) = relax_with(&*field, graph, &positions, criteria);
+            let relaxed_points: Vec<Vec3> = indices.iter().map(|&i| relaxed[i]).collect();
+            let rmsd = superposition::superpose(&relaxed_points, &reference_points)
+                .map_or(0.0, |superposition| superposition.rmsd);
+            ShakeTrial {
+                rmsd,
+                returned: rmsd <= options.tolerance,
+                relax: report,
+            }
+        })
+        .collect();
+
+    ShakeReport {
+        reference: reference_report,
+        trials,
+    }
+}
+
+// A small, fast generator (SplitMix64).  The quality is ample for jostling
+// atoms, and unlike a system generator it gives the same numbers every time
+// for the same seed.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // A uniform sample from (0, 1].
+    fn next_unit(&mut self) -> f32 {
+        ((self.next_u64() >> 40) + 1) as f32 / (1u64 << 24) as f32
+    }
+
+    // A sample from the standard normal distribution, by the Box-Muller
+    // transform.
+    fn next_gaussian(&mut self) -> f32 {
+        let (u, v) = (self.next_unit(), self.next_unit());
+        (-2.0 * u.ln()).sqrt() * (std::f32::consts::TAU * v).cos()
+    }
+}
+
+// End of File
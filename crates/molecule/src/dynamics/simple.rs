@@ -0,0 +1,208 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+use petgraph::visit::{EdgeRef as _, IntoEdgeReferences as _, NodeIndexable as _};
+use ultraviolet::Vec3;
+
+use super::{geometry, neighbors::NeighborList, parameters, ForceField};
+use crate::{molecule::MoleculeGraph, vsepr, AtomIndex};
+
+struct Bond {
+    atoms: [usize; 2],
+    length: f32,
+    stiffness: f32,
+}
+
+/// The angle between the bonds from `center` to `ends[0]` and to `ends[1]`.
+struct Angle {
+    ends: [usize; 2],
+    center: usize,
+    ideal_cos: f32,
+}
+
+/// The dihedral angle of the chain `atoms[0]-atoms[1]-atoms[2]-atoms[3]`, with
+/// energy `barrier / 2 * (1 + phase * cos(periodicity * φ))`.
+struct Torsion {
+    atoms: [usize; 4],
+    periodicity: f32,
+    phase: f32,
+    barrier: f32,
+}
+
+/// A minimal force field with harmonic bond stretching, angle bending towards
+/// the VSEPR shape of each atom, torsions that keep tetrahedral atoms staggered
+/// and trigonal planar atoms planar, and a short-range wall that keeps all other
+/// atoms from overlapping.  Its parameters are in `parameters`.
+pub struct SimpleForceField {
+    bonds: Vec<Bond>,
+    angles: Vec<Angle>,
+    torsions: Vec<Torsion>,
+    /// Half of the contact distance of each atom's element, indexed by atom.
+    contact_radii: Vec<f32>,
+    /// The pairs of atoms close enough to repel.  The cutoff is the largest
+    /// contact distance, beyond which the repulsion is zero anyway.
+    neighbors: RefCell<NeighborList>,
+}
+
+impl SimpleForceField {
+    pub fn new(graph: &MoleculeGraph) -> Self {
+        let neighbors = |index: AtomIndex| -> Vec<AtomIndex> {
+            let mut neighbors: Vec<AtomIndex> = graph.neighbors(index).collect();
+            neighbors.sort_unstable();
+            neighbors.dedup();
+            neighbors
+        };
+        let domains = |index: AtomIndex| {
+            vsepr::domain_count(
+                graph[index].element,
+                graph.edges(index).map(|e| *e.weight()),
+            )
+        };
+
+        // Pairs that are bonded or bonded to a common atom already have their
+        // distance set by bond and angle terms, so they do not repel.
+        let mut excluded = HashSet::new();
+        let mut exclude = |a: AtomIndex, b: AtomIndex| {
+            excluded.insert((a.min(b), a.max(b)));
+        };
+
+        let mut bonds = Vec::new();
+        for edge in graph.edge_references() {
+            let (a, b) = (edge.source(), edge.target());
+            let (ea, eb) = (graph[a].element, graph[b].element);
+            bonds.push(Bond {
+                atoms: [a.index(), b.index()],
+                length: parameters::bond_length(ea, eb, *edge.weight()),
+                stiffness: parameters::bond_stiffness(ea, eb, *edge.weight()),
+            });
+            exclude(a, b);
+        }
+
+        let mut angles = Vec::new();
+        for center in graph.node_indices() {
+            let neighbors = neighbors(center);
+            let ideal = vsepr::ideal_angle(domains(center));
+            for (i, &a) in neighbors.iter().enumerate() {
+                for &b in &neighbors[i + 1..] {
+                    exclude(a, b);
+                    if let Some(ideal) = ideal {
+                        angles.push(Angle {
+                            ends: [a.index(), b.index()],
+                            center: center.index(),
+                            ideal_cos: ideal.cos(),
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut torsions = Vec::new();
+        for edge in graph.edge_references() {
+            let (b, c) = (edge.source(), edge.target());
+            let (periodicity, phase, barrier) = match (domains(b), domains(c)) {
+                (4, 4) => (3.0, 1.0, parameters::SP3_TORSION_BARRIER),
+                (3, 3) => (2.0, -1.0, parameters::SP2_TORSION_BARRIER),
+                _ => continue,
+            };
+            for a in neighbors(b).into_iter().filter(|&a| a != c) {
+                for d in neighbors(c).into_iter().filter(|&d| d != b && d != a) {
+                    torsions.push(Torsion {
+                        atoms: [a.index(), b.index(), c.index(), d.index()],
+                        periodicity,
+                        phase,
+                        barrier,
+                    });
+                }
+            }
+        }
+
+        // Every contact distance is the sum of a radius for each atom.
+        let mut contact_radii = vec![0.0; graph.node_bound()];
+        for index in graph.node_indices() {
+            let element = graph[index].element;
+            contact_radii[index.index()] = 0.5 * parameters::contact_distance(element, element);
+        }
+        let cutoff = 2.0 * contact_radii.iter().copied().fold(0.0, f32::max);
+        let excluded = excluded
+            .into_iter()
+            .map(|(a, b)| (a.index(), b.index()))
+            .collect();
+        let atoms = graph.node_indices().map(|index| index.index()).collect();
+
+        Self {
+            bonds,
+            angles,
+            torsions,
+            contact_radii,
+            neighbors: RefCell::new(NeighborList::new(atoms, excluded, cutoff)),
+        }
+    }
+}
+
+impl ForceField for SimpleForceField {
+    fn evaluate(&self, positions: &[Vec3], forces: &mut [Vec3]) -> f32 {
+        let mut energy = 0.0;
+
+        for bond in &self.bonds {
+            let [a, b] = bond.atoms;
+            let displacement = positions[b] - positions[a];
+            let stretch = displacement.mag() - bond.length;
+            energy += 0.5 * bond.stiffness * stretch * stretch;
+
+            let force = displacement.normalized() * bond.stiffness * stretch;
+            forces[a] += force;
+            forces[b] -= force;
+        }
+
+        for angle in &self.angles {
+            let [a, b] = angle.ends;
+            let (cos, grad_a, grad_b) =
+                geometry::cos_angle(positions[a], positions[angle.center], positions[b]);
+            let deviation = cos - angle.ideal_cos;
+            energy += parameters::ANGLE_STIFFNESS * deviation * deviation;
+
+            let de = 2.0 * parameters::ANGLE_STIFFNESS * deviation;
+            forces[a] -= grad_a * de;
+            forces[b] -= grad_b * de;
+            forces[angle.center] += (grad_a + grad_b) * de;
+        }
+
+        for torsion in &self.torsions {
+            let Some((phi, gradients)) = geometry::dihedral(torsion.atoms.map(|i| positions[i]))
+            else {
+                // Three of the atoms are collinear, so the angle is undefined.
+                continue;
+            };
+            let n = torsion.periodicity;
+            energy += 0.5 * torsion.barrier * (1.0 + torsion.phase * (n * phi).cos());
+
+            let de = -0.5 * torsion.barrier * torsion.phase * n * (n * phi).sin();
+            for (atom, gradient) in torsion.atoms.into_iter().zip(gradients) {
+                forces[atom] -= gradient * de;
+            }
+        }
+
+        let mut neighbors = self.neighbors.borrow_mut();
+        for &[a, b] in neighbors.pairs(positions) {
+            let displacement = positions[b] - positions[a];
+            let distance = self.contact_radii[a] + self.contact_radii[b];
+            let overlap = distance - displacement.mag();
+            if overlap <= 0.0 {
+                continue;
+            }
+            energy += parameters::REPULSION_STIFFNESS * overlap * overlap;
+
+            let force = displacement.normalized() * 2.0 * parameters::REPULSION_STIFFNESS * overlap;
+            forces[a] -= force;
+            forces[b] += force;
+        }
+
+        energy
+    }
+}
+
+// End of File
@@ -0,0 +1,671 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The Universal Force Field of Rappé, Casewit, Goddard, Skiff and Goddard,
+//! J. Am. Chem. Soc. 114, 10024 (1992).  Energies are in kcal/mol.
+//!
+//! Every atom is given one of the UFF atom types, chosen from its element, its
+//! number of neighbors and its bond orders.  The molecule graph does not record
+//! aromaticity, so the resonant (`_R`) types are never assigned and aromatic
+//! rings are treated as alternating single and double bonds.  Electrostatic
+//! terms are omitted, as atoms carry no partial charges.
+//!
+//! Van der Waals interactions are cut off at a fixed distance, with the energy
+//! shifted so that it goes to zero there instead of jumping.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+use periodic_table::Element;
+use petgraph::visit::{
+    EdgeIndexable as _, EdgeRef as _, IntoEdgeReferences as _, NodeIndexable as _,
+};
+use ultraviolet::Vec3;
+
+use super::{geometry, neighbors::NeighborList, ForceField};
+use crate::{molecule::MoleculeGraph, AtomIndex, BondOrder};
+
+/// The constant relating effective charges to bond and angle force constants,
+/// in kcal Å / mol.
+const FORCE_CONSTANT_SCALE: f32 = 664.12;
+
+/// The distance beyond which van der Waals interactions are ignored, in
+/// angstroms, unless a molecule is given another with
+/// `MoleculeEditor::set_vdw_cutoff`.
+/// At this range the attraction between two carbon atoms is below 0.1% of its
+/// maximum.
+pub const DEFAULT_VDW_CUTOFF: f32 = 10.0;
+
+/// The parameters of one UFF atom type.
+struct AtomType {
+    label: &'static str,
+    element: Element,
+    /// The single bond radius r1, in angstroms.
+    bond_radius: f32,
+    /// The natural bond angle θ0, in degrees.
+    angle: f32,
+    /// The van der Waals distance x1, in angstroms.
+    vdw_distance: f32,
+    /// The van der Waals well depth D1, in kcal/mol.
+    vdw_depth: f32,
+    /// The effective charge Z1, in elementary charges.
+    charge: f32,
+    /// The torsional barrier Vi about bonds between sp3 atoms, in kcal/mol.
+    sp3_torsion: f32,
+    /// The torsional barrier Uj about bonds between sp2 atoms, in kcal/mol.
+    sp2_torsion: f32,
+    /// The GMP electronegativity χ.
+    electronegativity: f32,
+}
+
+impl AtomType {
+    /// The coordination geometry, from the label: '1' linear, '2' trigonal,
+    /// '3' tetrahedral, '4' square planar and '6' octahedral.  Types without
+    /// one (hydrogen, the halogens and the alkali metals) are terminal.
+    fn geometry(&self) -> Option<char> {
+        self.label.chars().find(char::is_ascii_digit)
+    }
+
+    /// The formal oxidation state, for types that specify one.
+    fn oxidation(&self) -> Option<u8> {
+        let (_, oxidation) = self.label.split_once('+')?;
+        oxidation.parse().ok()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+const fn atom_type(
+    label: &'static str,
+    element: Element,
+    bond_radius: f32,
+    angle: f32,
+    vdw_distance: f32,
+    vdw_depth: f32,
+    charge: f32,
+    sp3_torsion: f32,
+    sp2_torsion: f32,
+    electronegativity: f32,
+) -> AtomType {
+    AtomType {
+        label,
+        element,
+        bond_radius,
+        angle,
+        vdw_distance,
+        vdw_depth,
+        charge,
+        sp3_torsion,
+        sp2_torsion,
+        electronegativity,
+    }
+}
+
+/// The UFF atom types, grouped by element in order of atomic number.  The
+/// resonant types and the special hydrogen and oxygen types for bridging
+/// hydrides and zeolites are left out, as they are never assigned.
+// Hafnium's van der Waals distance happens to be close to π.
+#[allow(clippy::approx_constant)]
+#[rustfmt::skip]
+const ATOM_TYPES: &[AtomType] = {
+    use Element::*;
+    &[
+        //         label     element        r1     θ0      x1     D1     Z1     Vi     Uj     χ
+        atom_type("H_",    Hydrogen,     0.354, 180.0,  2.886, 0.044, 0.712, 0.0,   0.0,  4.528),
+        atom_type("He4+4", Helium,       0.849, 90.0,   2.362, 0.056, 0.098, 0.0,   0.0,  9.66),
+        atom_type("Li",    Lithium,      1.336, 180.0,  2.451, 0.025, 1.026, 0.0,   2.0,  3.006),
+        atom_type("Be3+2", Beryllium,    1.074, 109.47, 2.745, 0.085, 1.565, 0.0,   2.0,  4.877),
+        atom_type("B_3",   Boron,        0.838, 109.47, 4.083, 0.180, 1.755, 0.0,   2.0,  5.11),
+        atom_type("B_2",   Boron,        0.828, 120.0,  4.083, 0.180, 1.755, 0.0,   2.0,  5.11),
+        atom_type("C_3",   Carbon,       0.757, 109.47, 3.851, 0.105, 1.912, 2.119, 2.0,  5.343),
+        atom_type("C_2",   Carbon,       0.732, 120.0,  3.851, 0.105, 1.912, 0.0,   2.0,  5.343),
+        atom_type("C_1",   Carbon,       0.706, 180.0,  3.851, 0.105, 1.912, 0.0,   2.0,  5.343),
+        atom_type("N_3",   Nitrogen,     0.700, 106.7,  3.660, 0.069, 2.544, 0.450, 2.0,  6.899),
+        atom_type("N_2",   Nitrogen,     0.685, 111.2,  3.660, 0.069, 2.544, 0.0,   2.0,  6.899),
+        atom_type("N_1",   Nitrogen,     0.656, 180.0,  3.660, 0.069, 2.544, 0.0,   2.0,  6.899),
+        atom_type("O_3",   Oxygen,       0.658, 104.51, 3.500, 0.060, 2.300, 0.018, 2.0,  8.741),
+        atom_type("O_2",   Oxygen,       0.634, 120.0,  3.500, 0.060, 2.300, 0.0,   2.0,  8.741),
+        atom_type("O_1",   Oxygen,       0.639, 180.0,  3.500, 0.060, 2.300, 0.0,   2.0,  8.741),
+        atom_type("F_",    Fluorine,     0.668, 180.0,  3.364, 0.050, 1.735, 0.0,   2.0,  10.874),
+        atom_type("Ne4+4", Neon,         0.920, 90.0,   3.243, 0.042, 0.194, 0.0,   2.0,  11.04),
+        atom_type("Na",    Sodium,       1.539, 180.0,  2.983, 0.030, 1.081, 0.0,   1.25, 2.843),
+        atom_type("Mg3+2", Magnesium,    1.421, 109.47, 3.021, 0.111, 1.787, 0.0,   1.25, 3.951),
+        atom_type("Al3",   Aluminium,    1.244, 109.47, 4.499, 0.505, 1.792, 0.0,   1.25, 4.06),
+        atom_type("Si3",   Silicon,      1.117, 109.47, 4.295, 0.402, 2.323, 1.225, 1.25, 4.168),
+        atom_type("P_3+3", Phosphorus,   1.101, 93.8,   4.147, 0.305, 2.863, 2.4,   1.25, 5.463),
+        atom_type("P_3+5", Phosphorus,   1.056, 109.47, 4.147, 0.305, 2.863, 2.4,   1.25, 5.463),
+        atom_type("S_3+2", Sulfur,       1.064, 92.1,   4.035, 0.274, 2.703, 0.484, 1.25, 6.928),
+        atom_type("S_3+4", Sulfur,       1.049, 103.2,  4.035, 0.274, 2.703, 0.484, 1.25, 6.928),
+        atom_type("S_3+6", Sulfur,       1.027, 109.47, 4.035, 0.274, 2.703, 0.484, 1.25, 6.928),
+        atom_type("S_2",   Sulfur,       0.854, 120.0,  4.035, 0.274, 2.703, 0.0,   1.25, 6.928),
+        atom_type("Cl",    Chlorine,     1.044, 180.0,  3.947, 0.227, 2.348, 0.0,   1.25, 8.564),
+        atom_type("Ar4+4", Argon,        1.032, 90.0,   3.868, 0.185, 0.300, 0.0,   1.25, 9.465),
+        atom_type("K_",    Potassium,    1.953, 180.0,  3.812, 0.035, 1.165, 0.0,   0.7,  2.421),
+        atom_type("Ca6+2", Calcium,      1.761, 90.0,   3.399, 0.238, 2.141, 0.0,   0.7,  3.231),
+        atom_type("Sc3+3", Scandium,     1.513, 109.47, 3.295, 0.019, 2.592, 0.0,   0.7,  3.395),
+        atom_type("Ti3+4", Titanium,     1.412, 109.47, 3.175, 0.017, 2.659, 0.0,   0.7,  3.47),
+        atom_type("Ti6+4", Titanium,     1.412, 90.0,   3.175, 0.017, 2.659, 0.0,   0.7,  3.47),
+        atom_type("V_3+5", Vanadium,     1.402, 109.47, 3.144, 0.016, 2.679, 0.0,   0.7,  3.65),
+        atom_type("Cr6+3", Chromium,     1.345, 90.0,   3.023, 0.015, 2.463, 0.0,   0.7,  3.415),
+        atom_type("Mn6+2", Manganese,    1.382, 90.0,   2.961, 0.013, 2.430, 0.0,   0.7,  3.325),
+        atom_type("Fe3+2", Iron,         1.270, 109.47, 2.912, 0.013, 2.430, 0.0,   0.7,  3.76),
+        atom_type("Fe6+2", Iron,         1.335, 90.0,   2.912, 0.013, 2.430, 0.0,   0.7,  3.76),
+        atom_type("Co6+3", Cobalt,       1.241, 90.0,   2.872, 0.014, 2.430, 0.0,   0.7,  4.105),
+        atom_type("Ni4+2", Nickel,       1.164, 90.0,   2.834, 0.015, 2.430, 0.0,   0.7,  4.465),
+        atom_type("Cu3+1", Copper,       1.302, 109.47, 3.495, 0.005, 1.756, 0.0,   0.7,  4.2),
+        atom_type("Zn3+2", Zinc,         1.193, 109.47, 2.763, 0.124, 1.308, 0.0,   0.7,  5.106),
+        atom_type("Ga3+3", Gallium,      1.260, 109.47, 4.383, 0.415, 1.821, 0.0,   0.7,  3.641),
+        atom_type("Ge3",   Germanium,    1.197, 109.47, 4.280, 0.379, 2.789, 0.701, 0.7,  4.051),
+        atom_type("As3+3", Arsenic,      1.211, 92.1,   4.230, 0.309, 2.864, 1.5,   0.7,  5.188),
+        atom_type("Se3+2", Selenium,     1.190, 90.6,   4.205, 0.291, 2.764, 0.335, 0.7,  6.428),
+        atom_type("Br",    Bromine,      1.192, 180.0,  4.189, 0.251, 2.519, 0.0,   0.7,  7.79),
+        atom_type("Kr4+4", Krypton,      1.147, 90.0,   4.141, 0.220, 0.452, 0.0,   0.7,  8.505),
+        atom_type("Rb",    Rubidium,     2.260, 180.0,  4.114, 0.040, 1.592, 0.0,   0.2,  2.331),
+        atom_type("Sr6+2", Strontium,    2.052, 90.0,   3.641, 0.235, 2.449, 0.0,   0.2,  3.024),
+        atom_type("Y_3+3", Yttrium,      1.698, 109.47, 3.345, 0.072, 3.257, 0.0,   0.2,  3.83),
+        atom_type("Zr3+4", Zirconium,    1.564, 109.47, 3.124, 0.069, 3.667, 0.0,   0.2,  3.4),
+        atom_type("Nb3+5", Niobium,      1.473, 109.47, 3.165, 0.059, 3.618, 0.0,   0.2,  3.55),
+        atom_type("Mo6+6", Molybdenum,   1.467, 90.0,   3.052, 0.056, 3.400, 0.0,   0.2,  3.465),
+        atom_type("Mo3+6", Molybdenum,   1.484, 109.47, 3.052, 0.056, 3.400, 0.0,   0.2,  3.465),
+        atom_type("Tc6+5", Technetium,   1.322, 90.0,   2.998, 0.048, 3.400, 0.0,   0.2,  3.29),
+        atom_type("Ru6+2", Ruthenium,    1.478, 90.0,   2.963, 0.056, 3.400, 0.0,   0.2,  3.575),
+        atom_type("Rh6+3", Rhodium,      1.332, 90.0,   2.929, 0.053, 3.500, 0.0,   0.2,  3.975),
+        atom_type("Pd4+2", Palladium,    1.338, 90.0,   2.899, 0.048, 3.210, 0.0,   0.2,  4.32),
+        atom_type("Ag1+1", Silver,       1.386, 180.0,  3.148, 0.036, 1.956, 0.0,   0.2,  4.436),
+        atom_type("Cd3+2", Cadmium,      1.403, 109.47, 2.848, 0.228, 1.650, 0.0,   0.2,  5.034),
+        atom_type("In3+3", Indium,       1.459, 109.47, 4.463, 0.599, 2.070, 0.0,   0.2,  3.506),
+        atom_type("Sn3",   Tin,          1.398, 109.47, 4.392, 0.567, 2.961, 0.199, 0.2,  3.987),
+        atom_type("Sb3+3", Antimony,     1.407, 91.6,   4.420, 0.449, 2.704, 1.1,   0.2,  4.899),
+        atom_type("Te3+2", Tellurium,    1.386, 90.25,  4.470, 0.398, 2.882, 0.3,   0.2,  5.816),
+        atom_type("I_",    Iodine,       1.382, 180.0,  4.500, 0.339, 2.650, 0.0,   0.2,  6.822),
+        atom_type("Xe4+4", Xenon,        1.267, 90.0,   4.404, 0.332, 0.556, 0.0,   0.2,  7.595),
+        atom_type("Cs",    Cesium,       2.570, 180.0,  4.517, 0.045, 1.573, 0.0,   0.1,  2.183),
+        atom_type("Ba6+2", Barium,       2.277, 90.0,   3.703, 0.364, 2.727, 0.0,   0.1,  2.814),
+        atom_type("La3+3", Lanthanum,    1.943, 109.47, 3.522, 0.017, 3.300, 0.0,   0.1,  2.8355),
+        atom_type("Ce6+3", Cerium,       1.841, 90.0,   3.556, 0.013, 3.300, 0.0,   0.1,  2.774),
+        atom_type("Pr6+3", Praseodymium, 1.823, 90.0,   3.606, 0.010, 3.300, 0.0,   0.1,  2.858),
+        atom_type("Nd6+3", Neodymium,    1.816, 90.0,   3.575, 0.010, 3.300, 0.0,   0.1,  2.8685),
+        atom_type("Pm6+3", Promethium,   1.801, 90.0,   3.547, 0.009, 3.300, 0.0,   0.1,  2.881),
+        atom_type("Sm6+3", Samarium,     1.780, 90.0,   3.520, 0.008, 3.300, 0.0,   0.1,  2.9115),
+        atom_type("Eu6+3", Europium,     1.771, 90.0,   3.493, 0.008, 3.300, 0.0,   0.1,  2.8785),
+        atom_type("Gd6+3", Gadolinium,   1.735, 90.0,   3.368, 0.009, 3.300, 0.0,   0.1,  3.1665),
+        atom_type("Tb6+3", Terbium,      1.732, 90.0,   3.451, 0.007, 3.300, 0.0,   0.1,  3.018),
+        atom_type("Dy6+3", Dysprosium,   1.710, 90.0,   3.428, 0.007, 3.300, 0.0,   0.1,  3.0555),
+        atom_type("Ho6+3", Holmium,      1.696, 90.0,   3.409, 0.007, 3.416, 0.0,   0.1,  3.127),
+        atom_type("Er6+3", Erbium,       1.673, 90.0,   3.391, 0.007, 3.300, 0.0,   0.1,  3.1865),
+        atom_type("Tm6+3", Thulium,      1.660, 90.0,   3.374, 0.006, 3.300, 0.0,   0.1,  3.2514),
+        atom_type("Yb6+3", Ytterbium,    1.637, 90.0,   3.355, 0.228, 2.618, 0.0,   0.1,  3.2889),
+        atom_type("Lu6+3", Lutetium,     1.671, 90.0,   3.640, 0.041, 3.271, 0.0,   0.1,  2.9629),
+        atom_type("Hf3+4", Hafnium,      1.611, 109.47, 3.141, 0.072, 3.921, 0.0,   0.1,  3.7),
+        atom_type("Ta3+5", Tantalum,     1.511, 109.47, 3.170, 0.081, 4.075, 0.0,   0.1,  5.1),
+        atom_type("W_6+6", Tungsten,     1.392, 90.0,   3.069, 0.067, 3.700, 0.0,   0.1,  4.63),
+        atom_type("W_3+4", Tungsten,     1.526, 109.47, 3.069, 0.067, 3.700, 0.0,   0.1,  4.63),
+        atom_type("W_3+6", Tungsten,     1.380, 109.47, 3.069, 0.067, 3.700, 0.0,   0.1,  4.63),
+        atom_type("Re6+5", Rhenium,      1.372, 90.0,   2.954, 0.066, 3.700, 0.0,   0.1,  3.96),
+        atom_type("Re3+7", Rhenium,      1.314, 109.47, 2.954, 0.066, 3.700, 0.0,   0.1,  3.96),
+        atom_type("Os6+6", Osmium,       1.372, 90.0,   3.120, 0.037, 3.700, 0.0,   0.1,  5.14),
+        atom_type("Ir6+3", Iridium,      1.371, 90.0,   2.840, 0.073, 3.731, 0.0,   0.1,  5.0),
+        atom_type("Pt4+2", Platinum,     1.364, 90.0,   2.754, 0.080, 3.382, 0.0,   0.1,  4.79),
+        atom_type("Au4+3", Gold,         1.262, 90.0,   3.293, 0.039, 2.625, 0.0,   0.1,  4.894),
+        atom_type("Hg1+2", Mercury,      1.340, 180.0,  2.705, 0.385, 1.750, 0.0,   0.1,  6.27),
+        atom_type("Tl3+3", Thallium,     1.518, 120.0,  4.347, 0.680, 2.068, 0.0,   0.1,  3.2),
+        atom_type("Pb3",   Lead,         1.459, 109.47, 4.297, 0.663, 2.846, 0.1,   0.1,  3.9),
+        atom_type("Bi3+3", Bismuth,      1.512, 90.0,   4.370, 0.518, 2.470, 1.0,   0.1,  4.69),
+        atom_type("Po3+2", Polonium,     1.500, 90.0,   4.709, 0.325, 2.330, 0.3,   0.1,  4.21),
+        atom_type("At",    Astatine,     1.545, 180.0,  4.750, 0.284, 2.240, 0.0,   0.1,  4.75),
+        atom_type("Rn4+4", Radon,        1.420, 90.0,   4.765, 0.248, 0.583, 0.0,   0.1,  5.37),
+        atom_type("Fr",    Francium,     2.880, 180.0,  4.900, 0.050, 1.847, 0.0,   0.0,  2.0),
+        atom_type("Ra6+2", Radium,       2.512, 90.0,   3.677, 0.404, 2.920, 0.0,   0.0,  2.843),
+        atom_type("Ac6+3", Actinium,     1.983, 90.0,   3.478, 0.033, 3.900, 0.0,   0.0,  2.835),
+        atom_type("Th6+4", Thorium,      1.721, 90.0,   3.396, 0.026, 4.202, 0.0,   0.0,  3.175),
+        atom_type("Pa6+4", Protactinium, 1.711, 90.0,   3.424, 0.022, 3.900, 0.0,   0.0,  2.985),
+        atom_type("U_6+4", Uranium,      1.684, 90.0,   3.395, 0.022, 3.900, 0.0,   0.0,  3.341),
+        atom_type("Np6+4", Neptunium,    1.666, 90.0,   3.424, 0.019, 3.900, 0.0,   0.0,  3.549),
+        atom_type("Pu6+4", Plutonium,    1.657, 90.0,   3.424, 0.016, 3.900, 0.0,   0.0,  3.243),
+        atom_type("Am6+4", Americium,    1.660, 90.0,   3.381, 0.014, 3.900, 0.0,   0.0,  2.9895),
+        atom_type("Cm6+3", Curium,       1.801, 90.0,   3.326, 0.013, 3.900, 0.0,   0.0,  2.8315),
+        atom_type("Bk6+3", Berkelium,    1.761, 90.0,   3.339, 0.013, 3.900, 0.0,   0.0,  3.1935),
+        atom_type("Cf6+3", Californium,  1.750, 90.0,   3.313, 0.013, 3.900, 0.0,   0.0,  3.197),
+        atom_type("Es6+3", Einsteinium,  1.724, 90.0,   3.299, 0.012, 3.900, 0.0,   0.0,  3.333),
+        atom_type("Fm6+3", Fermium,      1.712, 90.0,   3.286, 0.012, 3.900, 0.0,   0.0,  3.4),
+        atom_type("Md6+3", Mendelevium,  1.689, 90.0,   3.274, 0.011, 3.900, 0.0,   0.0,  3.47),
+        atom_type("No6+3", Nobelium,     1.679, 90.0,   3.248, 0.011, 3.900, 0.0,   0.0,  3.475),
+        atom_type("Lw6+3", Lawrencium,   1.698, 90.0,   3.236, 0.011, 3.900, 0.0,   0.0,  3.5),
+    ]
+};
+
+/// Chooses the UFF type of an atom of `element` with `neighbors` bonded atoms
+/// and `pi_bonds` bonds beyond single bonds.
+fn assign_type(element: Element, neighbors: usize, pi_bonds: usize) -> &'static AtomType {
+    // UFF stops at lawrencium.  The superheavy elements use the type of their
+    // lighter congener one period up, which is 32 places earlier.
+    let mut number = element as u8;
+    if number > Element::Lawrencium as u8 {
+        number -= 32;
+    }
+
+    use Element::*;
+    let geometry = match element {
+        // Hypervalent sulfur is tetrahedral even when it has double bonds.
+        Sulfur if neighbors > 2 => '3',
+        Boron | Carbon | Nitrogen | Oxygen | Sulfur => match pi_bonds {
+            0 => '3',
+            1 => '2',
+            _ => '1',
+        },
+        _ if neighbors > 4 => '6',
+        _ => '3',
+    };
+    let oxidation = match element {
+        Phosphorus if neighbors > 3 => Some(5),
+        Phosphorus => Some(3),
+        Sulfur => Some(match neighbors {
+            0..=2 => 2,
+            3 => 4,
+            _ => 6,
+        }),
+        _ => None,
+    };
+
+    ATOM_TYPES
+        .iter()
+        .filter(|candidate| candidate.element as u8 == number)
+        .min_by_key(|candidate| {
+            (
+                candidate.geometry().is_some_and(|g| g != geometry),
+                oxidation.is_some_and(|o| candidate.oxidation() != Some(o)),
+            )
+        })
+        .expect("every element up to lawrencium has a UFF type")
+}
+
+// The UFF type of each atom of `graph`, indexed by `AtomIndex::index()`.
+fn assign_types(graph: &MoleculeGraph) -> Vec<Option<&'static AtomType>> {
+    let mut types = vec![None; graph.node_bound()];
+    for index in graph.node_indices() {
+        let (neighbors, total_order) = graph.edges(index).fold((0, 0), |(n, total), edge| {
+            (n + 1, total + *edge.weight() as usize)
+        });
+        types[index.index()] = Some(assign_type(
+            graph[index].element,
+            neighbors,
+            total_order.saturating_sub(neighbors),
+        ));
+    }
+    types
+}
+
+/// The natural length of each bond of `graph` under UFF, indexed by
+/// `BondIndex::index()`.
+pub(super) fn rest_lengths(graph: &MoleculeGraph) -> Vec<f32> {
+    let types = assign_types(graph);
+    let mut lengths = vec![0.0; graph.edge_bound()];
+    for edge in graph.edge_references() {
+        let (a, b) = (edge.source().index(), edge.target().index());
+        if let (Some(a), Some(b)) = (types[a], types[b]) {
+            lengths[edge.id().index()] = rest_length(a, b, *edge.weight());
+        }
+    }
+    lengths
+}
+
+/// The natural length of a bond between atoms of types `a` and `b`, with a
+/// correction for the bond order and one for the difference in
+/// electronegativity.
+fn rest_length(a: &AtomType, b: &AtomType, order: BondOrder) -> f32 {
+    let (ra, rb) = (a.bond_radius, b.bond_radius);
+    let order_correction = -0.1332 * (ra + rb) * (order.max(1) as f32).ln();
+    let (xa, xb) = (a.electronegativity, b.electronegativity);
+    let electronegativity_correction =
+        ra * rb * (xa.sqrt() - xb.sqrt()).powi(2) / (xa * ra + xb * rb);
+    ra + rb + order_correction - electronegativity_correction
+}
+
+struct Bond {
+    atoms: [usize; 2],
+    length: f32,
+    stiffness: f32,
+}
+
+/// How the energy of a bond angle depends on its cosine.
+#[derive(Clone, Copy)]
+enum AngleShape {
+    /// `E = K (c0 + c1 cos θ + c2 cos 2θ)`, with a minimum at θ0.
+    General { c0: f32, c1: f32, c2: f32 },
+    /// `E = K / n² (1 - cos nθ)`, used for linear (with n = 1 and the sign of
+    /// the cosine flipped), trigonal planar, square planar and octahedral
+    /// centers, which have several natural angles.
+    Periodic(u8),
+}
+
+struct Angle {
+    ends: [usize; 2],
+    center: usize,
+    stiffness: f32,
+    shape: AngleShape,
+}
+
+impl Angle {
+    /// The energy of the angle and its derivative with respect to `cos θ`.
+    fn energy(&self, cos: f32) -> (f32, f32) {
+        let k = self.stiffness;
+        match self.shape {
+            AngleShape::General { c0, c1, c2 } => (
+                k * (c0 + c1 * cos + c2 * (2.0 * cos * cos - 1.0)),
+                k * (c1 + 4.0 * c2 * cos),
+            ),
+            AngleShape::Periodic(1) => (k * (1.0 + cos), k),
+            AngleShape::Periodic(n) => {
+                // cos nθ as a polynomial in cos θ.
+                let (cos_n, dcos_n) = match n {
+                    2 => (2.0 * cos * cos - 1.0, 4.0 * cos),
+                    3 => (4.0 * cos.powi(3) - 3.0 * cos, 12.0 * cos * cos - 3.0),
+                    _ => (
+                        8.0 * cos.powi(4) - 8.0 * cos * cos + 1.0,
+                        32.0 * cos.powi(3) - 16.0 * cos,
+                    ),
+                };
+                let scale = k / (n as f32 * n as f32);
+                (scale * (1.0 - cos_n), -scale * dcos_n)
+            }
+        }
+    }
+}
+
+/// `E = barrier / 2 * (1 - cos(n φ0) cos(n φ))`.
+struct Torsion {
+    atoms: [usize; 4],
+    periodicity: f32,
+    cos_phase: f32,
+    barrier: f32,
+}
+
+/// Keeps the `out` neighbor of a trigonal center in the plane of the other two:
+/// `E = K (1 - cos ω)`, where ω is the angle between the bond to `out` and that
+/// plane.
+struct Inversion {
+    center: usize,
+    plane: [usize; 2],
+    out: usize,
+    stiffness: f32,
+}
+
+/// The Universal Force Field, which has parameters for every element.
+pub struct UniversalForceField {
+    bonds: Vec<Bond>,
+    angles: Vec<Angle>,
+    torsions: Vec<Torsion>,
+    inversions: Vec<Inversion>,
+    /// The square roots of each atom's van der Waals distance and well depth,
+    /// indexed by atom.  The parameters of a pair are the geometric means of
+    /// the two atoms', i.e. the products of these.
+    vdw_roots: Vec<(f32, f32)>,
+    /// The pairs of atoms within the van der Waals cutoff of each other.
+    neighbors: RefCell<NeighborList>,
+}
+
+impl UniversalForceField {
+    pub fn new(graph: &MoleculeGraph) -> Self {
+        Self::with_cutoff(graph, DEFAULT_VDW_CUTOFF)
+    }
+
+    /// Builds the force field for `graph`, ignoring van der Waals interactions
+    /// between atoms more than `cutoff` angstroms apart.
+    pub fn with_cutoff(graph: &MoleculeGraph, cutoff: f32) -> Self {
+        let neighbors = |index: AtomIndex| -> Vec<AtomIndex> {
+            let mut neighbors: Vec<AtomIndex> = graph.neighbors(index).collect();
+            neighbors.sort_unstable();
+            neighbors.dedup();
+            neighbors
+        };
+        let order =
+            |a: AtomIndex, b: AtomIndex| graph.find_edge(a, b).map_or(1, |edge| graph[edge]);
+
+        let types = assign_types(graph);
+        let ty = |index: AtomIndex| types[index.index()].expect("every atom has been typed");
+
+        // Pairs that are bonded or bonded to a common atom interact through
+        // bond and angle terms instead of van der Waals terms.
+        let mut excluded = HashSet::new();
+        let mut exclude = |a: AtomIndex, b: AtomIndex| {
+            excluded.insert((a.min(b), a.max(b)));
+        };
+
+        let mut bonds = Vec::new();
+        for edge in graph.edge_references() {
+            let (a, b) = (edge.source(), edge.target());
+            let length = rest_length(ty(a), ty(b), *edge.weight());
+            bonds.push(Bond {
+                atoms: [a.index(), b.index()],
+                length,
+                stiffness: FORCE_CONSTANT_SCALE * ty(a).charge * ty(b).charge / length.powi(3),
+            });
+            exclude(a, b);
+        }
+
+        let mut angles = Vec::new();
+        let mut inversions = Vec::new();
+        for center in graph.node_indices() {
+            let center_type = ty(center);
+            let theta0 = center_type.angle.to_radians();
+            let cos0 = theta0.cos();
+            let shape = match center_type.geometry() {
+                None | Some('1') => AngleShape::Periodic(1),
+                Some('2') if center_type.angle == 120.0 => AngleShape::Periodic(3),
+                Some('4') | Some('6') => AngleShape::Periodic(4),
+                _ => {
+                    let c2 = 1.0 / (4.0 * theta0.sin().powi(2));
+                    AngleShape::General {
+                        c0: c2 * (2.0 * cos0 * cos0 + 1.0),
+                        c1: -4.0 * c2 * cos0,
+                        c2,
+                    }
+                }
+            };
+
+            let neighbors = neighbors(center);
+            for (i, &a) in neighbors.iter().enumerate() {
+                for &b in &neighbors[i + 1..] {
+                    exclude(a, b);
+
+                    let rab = rest_length(ty(a), center_type, order(a, center));
+                    let rbc = rest_length(ty(b), center_type, order(b, center));
+                    let rac2 = rab * rab + rbc * rbc - 2.0 * rab * rbc * cos0;
+                    let stiffness = FORCE_CONSTANT_SCALE * ty(a).charge * ty(b).charge
+                        / rac2.powf(2.5)
+                        * (3.0 * rab * rbc * (1.0 - cos0 * cos0) - rac2 * cos0);
+                    angles.push(Angle {
+                        ends: [a.index(), b.index()],
+                        center: center.index(),
+                        stiffness,
+                        shape,
+                    });
+                }
+            }
+
+            // Trigonal carbon and nitrogen are kept planar, more strongly for
+            // carbon bonded to a carbonyl oxygen.
+            let planar = matches!(center_type.label, "C_2" | "N_2");
+            if planar && neighbors.len() == 3 {
+                let carbonyl = neighbors.iter().any(|&n| ty(n).label == "O_2");
+                let stiffness = if center_type.element == Element::Carbon && carbonyl {
+                    50.0
+                } else {
+                    6.0
+                };
+                for (i, &out) in neighbors.iter().enumerate() {
+                    let [a, b] = [neighbors[(i + 1) % 3], neighbors[(i + 2) % 3]];
+                    inversions.push(Inversion {
+                        center: center.index(),
+                        plane: [a.index(), b.index()],
+                        out: out.index(),
+                        stiffness: stiffness / 3.0,
+                    });
+                }
+            }
+        }
+
+        let mut torsions = Vec::new();
+        for edge in graph.edge_references() {
+            let (b, c) = (edge.source(), edge.target());
+            let (tb, tc) = (ty(b), ty(c));
+            let group_16 = |t: &AtomType| {
+                matches!(
+                    t.element,
+                    Element::Oxygen | Element::Sulfur | Element::Selenium | Element::Tellurium
+                )
+            };
+            let (periodicity, cos_phase, barrier) = match (tb.geometry(), tc.geometry()) {
+                (Some('3'), Some('3')) if group_16(tb) && group_16(tc) => {
+                    let v = |t: &AtomType| -> f32 {
+                        if t.element == Element::Oxygen {
+                            2.0
+                        } else {
+                            6.8
+                        }
+                    };
+                    (2.0, -1.0, (v(tb) * v(tc)).sqrt())
+                }
+                (Some('3'), Some('3')) => (3.0, -1.0, (tb.sp3_torsion * tc.sp3_torsion).sqrt()),
+                (Some('2'), Some('2')) => {
+                    let order = *edge.weight() as f32;
+                    let barrier =
+                        5.0 * (tb.sp2_torsion * tc.sp2_torsion).sqrt() * (1.0 + 4.18 * order.ln());
+                    (2.0, 1.0, barrier)
+                }
+                (Some('2'), Some('3')) | (Some('3'), Some('2')) => (6.0, 1.0, 1.0),
+                _ => continue,
+            };
+            if barrier == 0.0 {
+                continue;
+            }
+
+            let outer_b: Vec<AtomIndex> = neighbors(b).into_iter().filter(|&a| a != c).collect();
+            let outer_c: Vec<AtomIndex> = neighbors(c).into_iter().filter(|&d| d != b).collect();
+            // The barrier is shared between all of the torsions about the bond.
+            let count = (outer_b.len() * outer_c.len()) as f32;
+            for &a in &outer_b {
+                for &d in outer_c.iter().filter(|&&d| d != a) {
+                    torsions.push(Torsion {
+                        atoms: [a.index(), b.index(), c.index(), d.index()],
+                        periodicity,
+                        cos_phase,
+                        barrier: barrier / count,
+                    });
+                }
+            }
+        }
+
+        let mut vdw_roots = vec![(0.0, 0.0); graph.node_bound()];
+        for index in graph.node_indices() {
+            let atom_type = ty(index);
+            vdw_roots[index.index()] = (atom_type.vdw_distance.sqrt(), atom_type.vdw_depth.sqrt());
+        }
+        let excluded = excluded
+            .into_iter()
+            .map(|(a, b)| (a.index(), b.index()))
+            .collect();
+        let atoms = graph.node_indices().map(|index| index.index()).collect();
+
+        Self {
+            bonds,
+            angles,
+            torsions,
+            inversions,
+            vdw_roots,
+            neighbors: RefCell::new(NeighborList::new(atoms, excluded, cutoff)),
+        }
+    }
+}
+
+impl ForceField for UniversalForceField {
+    fn evaluate(&self, positions: &[Vec3], forces: &mut [Vec3]) -> f32 {
+        let mut energy = 0.0;
+
+        for bond in &self.bonds {
+            let [a, b] = bond.atoms;
+            let displacement = positions[b] - positions[a];
+            let stretch = displacement.mag() - bond.length;
+            energy += 0.5 * bond.stiffness * stretch * stretch;
+
+            let force = displacement.normalized() * bond.stiffness * stretch;
+            forces[a] += force;
+            forces[b] -= force;
+        }
+
+        for angle in &self.angles {
+            let [a, b] = angle.ends;
+            let (cos, grad_a, grad_b) =
+                geometry::cos_angle(positions[a], positions[angle.center], positions[b]);
+            let (e, de) = angle.energy(cos);
+            energy += e;
+
+            forces[a] -= grad_a * de;
+            forces[b] -= grad_b * de;
+            forces[angle.center] += (grad_a + grad_b) * de;
+        }
+
+        for torsion in &self.torsions {
+            let Some((phi, gradients)) = geometry::dihedral(torsion.atoms.map(|i| positions[i]))
+            else {
+                continue;
+            };
+            let n = torsion.periodicity;
+            energy += 0.5 * torsion.barrier * (1.0 - torsion.cos_phase * (n * phi).cos());
+
+            let de = 0.5 * torsion.barrier * torsion.cos_phase * n * (n * phi).sin();
+            for (atom, gradient) in torsion.atoms.into_iter().zip(gradients) {
+                forces[atom] -= gradient * de;
+            }
+        }
+
+        for inversion in &self.inversions {
+            let [a, b] = inversion.plane;
+            let Some((sin, grad_a, grad_b, grad_out)) = geometry::sin_out_of_plane(
+                positions[inversion.center],
+                positions[a],
+                positions[b],
+                positions[inversion.out],
+            ) else {
+                continue;
+            };
+            // ω is within ±90°, so cos ω is never negative.
+            let cos = (1.0 - sin * sin).max(1e-6).sqrt();
+            energy += inversion.stiffness * (1.0 - cos);
+
+            let de = inversion.stiffness * sin / cos;
+            forces[a] -= grad_a * de;
+            forces[b] -= grad_b * de;
+            forces[inversion.out] -= grad_out * de;
+            forces[inversion.center] += (grad_a + grad_b + grad_out) * de;
+        }
+
+        let mut neighbors = self.neighbors.borrow_mut();
+        let cutoff = neighbors.cutoff();
+        for &[a, b] in neighbors.pairs(positions) {
+            let displacement = positions[b] - positions[a];
+            let distance = displacement.mag();
+            if distance >= cutoff {
+                continue;
+            }
+            let (distance_a, depth_a) = self.vdw_roots[a];
+            let (distance_b, depth_b) = self.vdw_roots[b];
+            let (well_distance, depth) = (distance_a * distance_b, depth_a * depth_b);
+            let ratio6 = (well_distance / distance).powi(6);
+            let cutoff_ratio6 = (well_distance / cutoff).powi(6);
+            energy += depth
+                * (ratio6 * ratio6 - 2.0 * ratio6 - cutoff_ratio6 * cutoff_ratio6
+                    + 2.0 * cutoff_ratio6);
+
+            // The derivative of the energy with respect to the distance.
+            let de = 12.0 * depth * (ratio6 - ratio6 * ratio6) / distance;
+            let force = displacement / distance * de;
+            forces[a] += force;
+            forces[b] -= force;
+        }
+
+        energy
+    }
+}
+
+// End of File
@@ -2,15 +2,34 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 use common::ids::*;
 use periodic_table::Element;
 use serde::{Deserialize, Serialize};
 
-use crate::{molecule::AtomNode, BondOrder};
+use crate::{
+    bond_orders::BondOrderAssignment,
+    cell::UnitCell,
+    composite::{CombineError, Composite},
+    cut::Cut,
+    doping::Doping,
+    dynamics::parameters,
+    lattice::Lattice,
+    mirror::Mirror,
+    moiety::AttachMoiety,
+    molecule::{AtomNode, ResidueId},
+    passivation::Passivation,
+    polymer::Polymer,
+    symmetry::SymmetryCopy,
+    torsion::SetTorsion,
+    vsepr,
+    zmatrix::ZMatrix,
+    BondOrder,
+};
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ReferenceType {
     Atom,
     Edit,
@@ -19,17 +38,72 @@ pub enum ReferenceType {
     // etc.
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum EditError {
     BrokenReference(ReferenceType),
     AtomOverwrite,
+    /// A torsion was set about a bond in a ring, whose two sides cannot turn
+    /// independently.
+    BondInRing,
+    /// A polymer's sequence has a letter that is not the code of one of its
+    /// residues.
+    UnknownResidue(char),
+    /// A moiety was attached that is not in the library.
+    UnknownMoiety(String),
+    /// A PDB import asked for a model that its file does not have.
+    MissingModel(u32),
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::BrokenReference(ReferenceType::Atom) => {
+                write!(f, "it refers to an atom that does not exist")
+            }
+            EditError::BrokenReference(ReferenceType::Edit) => {
+                write!(f, "it refers to an edit that does not exist")
+            }
+            EditError::AtomOverwrite => write!(f, "it would replace an existing atom"),
+            EditError::BondInRing => write!(f, "the bond it turns about is in a ring"),
+            EditError::UnknownResidue(code) => write!(f, "'{}' is not a residue", code),
+            EditError::UnknownMoiety(id) => write!(f, "'{}' is not a moiety", id),
+            EditError::MissingModel(model) => write!(f, "the file has no model {}", model),
+        }
+    }
+}
+
+/// Why an edit failed the last time it was applied.
+#[derive(Clone, Debug)]
+pub struct EditDiagnostic {
+    pub edit: EditId,
+    pub error: EditError,
+    /// The atoms that the edit refers to which did not exist when it was
+    /// applied, e.g. because the edit that made them was deleted.
+    pub missing: Vec<AtomSpecifier>,
+}
+
+impl fmt::Display for EditDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        match self.missing.as_slice() {
+            [] => Ok(()),
+            [atom] => write!(f, " ({} is missing)", atom),
+            [atom, rest @ ..] => write!(f, " ({} and {} more are missing)", atom, rest.len()),
+        }
+    }
 }
 
 /// A proxy trait that allows a molecule to be manipulated without exposing its implementation.
 /// Features can only manipulate a molecule using MoleculeCommands.
 pub trait EditContext {
     fn find_atom(&self, spec: &AtomSpecifier) -> Option<&AtomNode>;
+    /// The atom that `spec` faces (see `AtomNode::head`), if it faces one.
+    fn head(&self, spec: &AtomSpecifier) -> Option<AtomSpecifier>;
     fn pos(&self, spec: &AtomSpecifier) -> Option<&ultraviolet::Vec3>;
+    /// Lists every atom in the molecule.
+    fn atoms(&self) -> Vec<AtomSpecifier>;
+    /// Lists the atoms bonded to `spec`, along with the order of each bond.
+    fn bonds(&self, spec: &AtomSpecifier) -> Vec<(AtomSpecifier, BondOrder)>;
     fn add_atom(
         &mut self,
         element: Element,
@@ -51,6 +125,44 @@ pub trait EditContext {
         bond_target: AtomSpecifier,
         bond_order: BondOrder,
     ) -> Result<(), EditError>;
+    /// Pins or unpins an atom, which holds it in place during relaxation.
+    fn set_pinned(&mut self, spec: &AtomSpecifier, pinned: bool) -> Result<(), EditError>;
+    /// Records the PDB chain that an atom belongs to.
+    fn set_chain(&mut self, spec: &AtomSpecifier, chain: Option<char>) -> Result<(), EditError>;
+    /// Records the PDB residue that an atom belongs to.
+    fn set_residue(
+        &mut self,
+        spec: &AtomSpecifier,
+        residue: Option<ResidueId>,
+    ) -> Result<(), EditError>;
+    /// Records the line of the imported file that an atom was read from.
+    fn set_source_line(
+        &mut self,
+        spec: &AtomSpecifier,
+        line: Option<usize>,
+    ) -> Result<(), EditError>;
+    /// Marks a stereocenter as inverted from the configuration it was built
+    /// with, or as not inverted.
+    fn set_stereo_inverted(
+        &mut self,
+        spec: &AtomSpecifier,
+        inverted: bool,
+    ) -> Result<(), EditError>;
+    /// Changes the element of an existing atom, keeping its bonds.
+    fn set_element(&mut self, spec: &AtomSpecifier, element: Element) -> Result<(), EditError>;
+    /// Deletes atoms, along with their bonds.
+    fn remove_atoms(&mut self, specs: &[AtomSpecifier]) -> Result<(), EditError>;
+    /// Moves an existing atom to `pos`.
+    fn set_pos(&mut self, spec: &AtomSpecifier, pos: ultraviolet::Vec3) -> Result<(), EditError>;
+    /// Holds the dihedral angle of the chain `atoms` at `angle`, in degrees,
+    /// whenever the molecule is relaxed from now on.
+    fn restrain_torsion(&mut self, atoms: &[AtomSpecifier; 4], angle: f32);
+    /// Holds atoms still during the next relaxation only, so that it does not
+    /// undo a move that an edit has just made.
+    fn hold_for_relaxation(&mut self, specs: &[AtomSpecifier]);
+    /// Gives the molecule the unit cell it repeats in, or makes it
+    /// non-periodic again with `None`.
+    fn set_cell(&mut self, cell: Option<UnitCell>);
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -59,20 +171,242 @@ pub struct BondedAtom {
     pub element: Element,
 }
 
+/// Bonds two existing atoms, such as the ends of a chain to close a ring.  If
+/// they are already bonded, the bond is given the new order instead.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CreateBond {
+    pub atoms: [AtomSpecifier; 2],
+    pub order: BondOrder,
+}
+
+/// Adds hydrogens to atoms until each has its element's standard valence.  New
+/// hydrogens are arranged around each atom along with its existing bonds
+/// according to VSEPR theory.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HydrogenFill {
+    /// The atoms to fill, or `None` to fill every atom in the molecule.
+    pub targets: Option<Vec<AtomSpecifier>>,
+}
+
+/// Pins atoms in place during relaxation, or releases them.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Pin {
+    pub targets: Vec<AtomSpecifier>,
+    /// Whether the atoms are pinned (`true`) or released (`false`).
+    pub pinned: bool,
+}
+
+/// Changes atoms into atoms of another element, in place and keeping their
+/// bonds, e.g. to dope a lattice.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SetElement {
+    pub targets: Vec<AtomSpecifier>,
+    pub element: Element,
+}
+
+/// Moves atoms as a rigid unit: turns them by `rotation` about their
+/// centroid, then moves them by `translation`.  The moved atoms are held still
+/// during the relaxation that follows, so that it settles the rest of the
+/// molecule around them rather than pulling them back.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TransformAtoms {
+    pub targets: Vec<AtomSpecifier>,
+    pub rotation: ultraviolet::Rotor3,
+    pub translation: ultraviolet::Vec3,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct PdbData {
     pub name: String,
     pub contents: String,
+    /// Whether the standard residues of proteins and nucleic acids are bonded
+    /// from their templates (see [`crate::residues`]) and given hydrogens.
+    /// Imports saved before there were templates do not use them.
+    #[serde(default)]
+    pub residue_templates: bool,
+    /// The models to read from files with several, such as NMR ensembles.
+    /// Imports saved before a model could be chosen read all of them.
+    #[serde(default)]
+    pub model: PdbModel,
+    /// Whether the bonds that the file's CONECT records list are made.
+    #[serde(default)]
+    pub conect_bonds: bool,
+    /// Whether atoms left without bonds, such as those of ligands the file
+    /// gives no CONECT records for, are bonded to the atoms near enough to
+    /// them.
+    #[serde(default)]
+    pub infer_bonds: bool,
+}
+
+/// Which models of a PDB file with several are read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PdbModel {
+    /// Every model, one after another.
+    #[default]
+    All,
+    /// Only the first model.
+    First,
+    /// Only the model with this serial number.
+    Serial(u32),
+}
+
+/// Atoms copied in from elsewhere, such as a part extracted from another
+/// molecule.  Bonds refer to atoms by their index in `atoms`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AtomImport {
+    pub name: String,
+    pub atoms: Vec<(Element, ultraviolet::Vec3)>,
+    pub bonds: Vec<(usize, usize, BondOrder)>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum Edit {
     RootAtom(Element),
     BondedAtom(BondedAtom),
+    CreateBond(CreateBond),
     PdbImport(PdbData),
+    HydrogenFill(HydrogenFill),
+    AtomImport(AtomImport),
+    Pin(Pin),
+    ZMatrixImport(ZMatrix),
+    SymmetryCopy(SymmetryCopy),
+    Mirror(Mirror),
+    Lattice(Lattice),
+    Passivation(Passivation),
+    Cut(Cut),
+    SetTorsion(SetTorsion),
+    TransformAtoms(TransformAtoms),
+    SetElement(SetElement),
+    BondOrders(BondOrderAssignment),
+    Polymer(Polymer),
+    Doping(Doping),
+    Composite(Composite),
+    AttachMoiety(AttachMoiety),
 }
 
 impl Edit {
+    /// A short human readable description of the edit, for display in lists.
+    pub fn name(&self) -> String {
+        match self {
+            Edit::RootAtom(element) => format!("Root Atom ({:?})", element),
+            Edit::BondedAtom(BondedAtom { target, element }) => {
+                format!("Bonded Atom ({:?} on {})", element, target)
+            }
+            Edit::CreateBond(CreateBond {
+                atoms: [a1, a2],
+                order,
+            }) => format!("Create Bond ({} to {}, order {})", a1, a2, order),
+            Edit::PdbImport(PdbData { name, .. }) => format!("PDB Import ({})", name),
+            Edit::HydrogenFill(HydrogenFill { targets: None }) => "Hydrogen Fill".to_owned(),
+            Edit::HydrogenFill(HydrogenFill {
+                targets: Some(targets),
+            }) => format!("Hydrogen Fill ({} atoms)", targets.len()),
+            Edit::AtomImport(AtomImport { name, .. }) => format!("Atom Import ({})", name),
+            Edit::Pin(Pin { targets, pinned }) => format!(
+                "{} ({} atoms)",
+                if *pinned { "Pin" } else { "Unpin" },
+                targets.len()
+            ),
+            Edit::ZMatrixImport(ZMatrix { name, .. }) => format!("Z-Matrix Import ({})", name),
+            Edit::SymmetryCopy(SymmetryCopy { name, operations }) => {
+                format!("Symmetry ({}, {} copies)", name, operations.len())
+            }
+            Edit::Mirror(_) => "Mirror".to_owned(),
+            Edit::Lattice(lattice) => {
+                let [a, b, c] = lattice.cells();
+                format!("Lattice ({}, {}×{}×{} cells)", lattice.kind.name(), a, b, c)
+            }
+            Edit::Passivation(passivation) => {
+                format!("Passivation ({})", passivation.describe())
+            }
+            Edit::Cut(cut) => format!("Cut ({})", cut.describe()),
+            Edit::SetTorsion(SetTorsion {
+                atoms: [a, b, c, d],
+                angle,
+            }) => format!("Set Torsion ({}-{}-{}-{} to {:.1}°)", a, b, c, d, angle),
+            Edit::TransformAtoms(TransformAtoms { targets, .. }) => {
+                format!("Transform Atoms ({} atoms)", targets.len())
+            }
+            Edit::SetElement(SetElement { targets, element }) => {
+                format!("Set Element ({:?}, {} atoms)", element, targets.len())
+            }
+            Edit::BondOrders(_) => "Assign Bond Orders".to_owned(),
+            Edit::Polymer(polymer) => format!("Polymer ({})", polymer.describe()),
+            Edit::Doping(doping) => format!("Doping ({})", doping.describe()),
+            Edit::Composite(Composite { name, steps, .. }) => {
+                format!("{} ({} steps)", name, steps.len())
+            }
+            Edit::AttachMoiety(AttachMoiety {
+                target, moiety_id, ..
+            }) => {
+                let name = crate::moiety::find(moiety_id)
+                    .map_or(moiety_id.as_str(), |moiety| moiety.name.as_str());
+                format!("Attach Moiety ({} on {})", name, target)
+            }
+        }
+    }
+
+    /// The atoms that the edit refers to, which must exist when it is applied.
+    pub fn references(&self) -> Vec<&AtomSpecifier> {
+        match self {
+            Edit::BondedAtom(BondedAtom { target, .. })
+            | Edit::AttachMoiety(AttachMoiety { target, .. }) => vec![target],
+            Edit::CreateBond(CreateBond { atoms, .. }) => atoms.iter().collect(),
+            Edit::SetTorsion(SetTorsion { atoms, .. }) => atoms.iter().collect(),
+            Edit::HydrogenFill(HydrogenFill {
+                targets: Some(targets),
+            })
+            | Edit::Pin(Pin { targets, .. })
+            | Edit::TransformAtoms(TransformAtoms { targets, .. })
+            | Edit::SetElement(SetElement { targets, .. })
+            | Edit::Passivation(Passivation { atoms: targets, .. })
+            | Edit::Doping(Doping { atoms: targets, .. }) => targets.iter().collect(),
+            Edit::Composite(composite) => composite.anchors(),
+            Edit::RootAtom(_)
+            | Edit::PdbImport(_)
+            | Edit::HydrogenFill(HydrogenFill { targets: None })
+            | Edit::AtomImport(_)
+            | Edit::ZMatrixImport(_)
+            | Edit::SymmetryCopy(_)
+            | Edit::Mirror(_)
+            | Edit::Lattice(_)
+            | Edit::Cut(_)
+            | Edit::BondOrders(_)
+            | Edit::Polymer(_) => Vec::new(),
+        }
+    }
+
+    /// The same as [`Edit::references`], but allows the references to be
+    /// rewritten.
+    pub fn references_mut(&mut self) -> Vec<&mut AtomSpecifier> {
+        match self {
+            Edit::BondedAtom(BondedAtom { target, .. })
+            | Edit::AttachMoiety(AttachMoiety { target, .. }) => vec![target],
+            Edit::CreateBond(CreateBond { atoms, .. }) => atoms.iter_mut().collect(),
+            Edit::SetTorsion(SetTorsion { atoms, .. }) => atoms.iter_mut().collect(),
+            Edit::HydrogenFill(HydrogenFill {
+                targets: Some(targets),
+            })
+            | Edit::Pin(Pin { targets, .. })
+            | Edit::TransformAtoms(TransformAtoms { targets, .. })
+            | Edit::SetElement(SetElement { targets, .. })
+            | Edit::Passivation(Passivation { atoms: targets, .. })
+            | Edit::Doping(Doping { atoms: targets, .. }) => targets.iter_mut().collect(),
+            Edit::Composite(composite) => composite.anchors_mut(),
+            Edit::RootAtom(_)
+            | Edit::PdbImport(_)
+            | Edit::HydrogenFill(HydrogenFill { targets: None })
+            | Edit::AtomImport(_)
+            | Edit::ZMatrixImport(_)
+            | Edit::SymmetryCopy(_)
+            | Edit::Mirror(_)
+            | Edit::Lattice(_)
+            | Edit::Cut(_)
+            | Edit::BondOrders(_)
+            | Edit::Polymer(_) => Vec::new(),
+        }
+    }
+
     pub fn apply(&self, edit_id: &EditId, commands: &mut dyn EditContext) -> Result<(), EditError> {
         match self {
             Edit::RootAtom(element) => {
@@ -84,17 +418,124 @@ impl Edit {
                 )?;
             }
             Edit::BondedAtom(BondedAtom { target, element }) => {
-                let spec = AtomSpecifier::new(*edit_id);
+                let pos = bonded_atom_position(target, *element, commands)?;
+                commands.add_bonded_atom(
+                    *element,
+                    pos,
+                    AtomSpecifier::new(*edit_id),
+                    target.clone(),
+                    1,
+                )?;
+            }
+            Edit::CreateBond(CreateBond {
+                atoms: [a1, a2],
+                order,
+            }) => {
+                if a1 == a2 {
+                    return Err(EditError::BrokenReference(ReferenceType::Atom));
+                }
+                commands.create_bond(a1, a2, *order)?;
+            }
+            Edit::PdbImport(data) => {
+                crate::pdb::spawn_pdb(data, edit_id, commands)?;
+            }
+            Edit::HydrogenFill(HydrogenFill { targets }) => {
+                let targets = match targets {
+                    Some(targets) => targets.clone(),
+                    None => commands.atoms(),
+                };
+                let mut spec = AtomSpecifier::new(*edit_id);
 
-                let pos = *commands
-                    .pos(target)
-                    .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
-                let pos = pos + ultraviolet::Vec3::new(5.0, 0.0, 0.0);
+                for target in &targets {
+                    fill_valence(target, Element::Hydrogen, &mut spec, commands)?;
+                }
+            }
+            Edit::AtomImport(AtomImport { atoms, bonds, .. }) => {
+                let mut spec = AtomSpecifier::new(*edit_id);
+                let mut specs = Vec::with_capacity(atoms.len());
 
-                commands.add_bonded_atom(*element, pos, spec, target.clone(), 1)?;
+                for (element, pos) in atoms {
+                    let atom_spec = spec.next_spec();
+                    commands.add_atom(*element, *pos, atom_spec.clone(), None)?;
+                    specs.push(atom_spec);
+                }
+                for (a1, a2, order) in bonds {
+                    match (specs.get(*a1), specs.get(*a2)) {
+                        (Some(a1), Some(a2)) => commands.create_bond(a1, a2, *order)?,
+                        _ => return Err(EditError::BrokenReference(ReferenceType::Atom)),
+                    }
+                }
+            }
+            Edit::Pin(Pin { targets, pinned }) => {
+                for target in targets {
+                    commands.set_pinned(target, *pinned)?;
+                }
+            }
+            Edit::ZMatrixImport(zmatrix) => {
+                crate::zmatrix::spawn_zmatrix(zmatrix, edit_id, commands)?;
+            }
+            Edit::SymmetryCopy(copy) => {
+                crate::symmetry::spawn_symmetry_copy(copy, edit_id, commands)?;
+            }
+            Edit::Mirror(mirror) => {
+                crate::mirror::spawn_mirror(mirror, commands)?;
+            }
+            Edit::Lattice(lattice) => {
+                crate::lattice::spawn_lattice(lattice, edit_id, commands)?;
+            }
+            Edit::Passivation(passivation) => {
+                crate::passivation::spawn_passivation(passivation, edit_id, commands)?;
             }
-            Edit::PdbImport(PdbData { name, contents }) => {
-                crate::pdb::spawn_pdb(name, contents, edit_id, commands)?;
+            Edit::Cut(cut) => {
+                crate::cut::spawn_cut(cut, edit_id, commands)?;
+            }
+            Edit::SetTorsion(torsion) => {
+                crate::torsion::spawn_torsion(torsion, commands)?;
+            }
+            Edit::SetElement(SetElement { targets, element }) => {
+                for target in targets {
+                    commands.set_element(target, *element)?;
+                }
+            }
+            Edit::BondOrders(assignment) => {
+                crate::bond_orders::spawn_bond_orders(assignment, commands)?;
+            }
+            Edit::Polymer(polymer) => {
+                crate::polymer::spawn_polymer(polymer, edit_id, commands)?;
+            }
+            Edit::Doping(doping) => {
+                crate::doping::spawn_doping(doping, commands)?;
+            }
+            Edit::Composite(composite) => {
+                crate::composite::spawn_composite(composite, commands)?;
+            }
+            Edit::AttachMoiety(attach) => {
+                crate::moiety::spawn_moiety(attach, edit_id, commands)?;
+            }
+            Edit::TransformAtoms(TransformAtoms {
+                targets,
+                rotation,
+                translation,
+            }) => {
+                let mut positions = Vec::with_capacity(targets.len());
+                for target in targets {
+                    positions.push(
+                        *commands
+                            .pos(target)
+                            .ok_or(EditError::BrokenReference(ReferenceType::Atom))?,
+                    );
+                }
+                let centroid = positions
+                    .iter()
+                    .fold(ultraviolet::Vec3::zero(), |sum, pos| sum + *pos)
+                    / positions.len().max(1) as f32;
+                for (target, pos) in targets.iter().zip(positions) {
+                    commands.set_pos(
+                        target,
+                        centroid + *rotation * (pos - centroid) + *translation,
+                    )?;
+                }
+                commands.hold_for_relaxation(targets);
             }
         }
 
@@ -102,6 +543,127 @@ impl Edit {
     }
 }
 
+/// Where a [`BondedAtom`] edit would put a new atom of `element` bonded to
+/// `target`: where the target's hybridization puts its next bond, counting the
+/// bonds its valence has room for after this one as domains too so that e.g. a
+/// carbon's second bond is tetrahedral rather than linear.
+pub fn bonded_atom_position(
+    target: &AtomSpecifier,
+    element: Element,
+    commands: &dyn EditContext,
+) -> Result<ultraviolet::Vec3, EditError> {
+    let target_element = commands
+        .find_atom(target)
+        .ok_or(EditError::BrokenReference(ReferenceType::Atom))?
+        .element;
+    let center = *commands
+        .pos(target)
+        .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+
+    let bonds = commands.bonds(target);
+    let existing: Vec<ultraviolet::Vec3> = bonds
+        .iter()
+        .filter_map(|(neighbor, _)| commands.pos(neighbor))
+        .map(|pos| *pos - center)
+        .collect();
+    let orders = bonds.iter().map(|(_, order)| *order);
+    let domains = vsepr::domain_count(target_element, orders.chain([1]));
+    let direction = vsepr::new_bond_direction(&existing, domains);
+
+    Ok(center + direction * parameters::bond_length(target_element, element, 1))
+}
+
+/// Bonds atoms of `species` to `target` by single bonds until its bond orders
+/// add up to its element's standard valence, naming them with successive
+/// specifiers from `spec`.
+pub(crate) fn fill_valence(
+    target: &AtomSpecifier,
+    species: Element,
+    spec: &mut AtomSpecifier,
+    commands: &mut dyn EditContext,
+) -> Result<(), EditError> {
+    let element = commands
+        .find_atom(target)
+        .ok_or(EditError::BrokenReference(ReferenceType::Atom))?
+        .element;
+    let Some(valence) = element.standard_valence() else {
+        return Ok(());
+    };
+
+    let bonds = commands.bonds(target);
+    let bond_order: usize = bonds.iter().map(|(_, order)| *order as usize).sum();
+    let missing = (valence as usize).saturating_sub(bond_order);
+    if missing == 0 {
+        return Ok(());
+    }
+
+    let center = *commands
+        .pos(target)
+        .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+    let existing: Vec<ultraviolet::Vec3> = bonds
+        .iter()
+        .filter_map(|(neighbor, _)| commands.pos(neighbor))
+        .map(|pos| *pos - center)
+        .collect();
+
+    let length = parameters::bond_length(element, species, 1);
+    let domains = vsepr::domain_count(element, bonds.iter().map(|(_, order)| *order));
+
+    // The domains left over once the missing bonds are made hold lone pairs.
+    for direction in vsepr::free_directions(&existing, domains)
+        .into_iter()
+        .take(missing)
+    {
+        commands.add_bonded_atom(
+            species,
+            center + direction * length,
+            spec.next_spec(),
+            target.clone(),
+            1,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The identifier of a folder in an [`EditList`].
+pub type FolderId = usize;
+
+/// A named group of edits, shown together in the edit history.  Folders can
+/// hold other folders, and suppressing a folder suppresses everything in it.
+/// Apart from that, grouping edits is purely organizational: it does not
+/// change the order in which they are applied.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EditFolder {
+    pub id: FolderId,
+    pub name: String,
+    /// The folder this one is in, or `None` if it is at the top level.
+    pub parent: Option<FolderId>,
+    /// The edits directly in the folder, in no particular order.
+    pub edits: Vec<EditId>,
+    /// Whether the folder's contents are hidden in the edit history.
+    pub collapsed: bool,
+    pub suppressed: bool,
+}
+
+/// An edit that refers to atoms made by an earlier edit, so depends on it
+/// coming first and on it still making those atoms.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dependency {
+    /// The edit that refers to the atoms.
+    pub dependent: EditId,
+    /// The edit that makes them, itself or by copying atoms.
+    pub dependency: EditId,
+    /// The atoms referred to.
+    pub atoms: Vec<AtomSpecifier>,
+}
+
+// Whether `atom` was made by the edit `id`, either directly or as a copy, e.g.
+// by a symmetry copy.
+fn made_by(atom: &AtomSpecifier, id: EditId) -> bool {
+    atom.path.iter().any(|instance| instance.owner_id == id)
+}
+
 /// A container that stores a list of features. It allows the list to be manipulated without
 /// changing the indexes of existing features.
 #[derive(Default, Clone, Deserialize, Serialize)]
@@ -109,6 +671,13 @@ pub struct EditList {
     counter: usize,
     order: Vec<EditId>,
     edits: HashMap<EditId, Edit>,
+    // Edits that are kept in the list but skipped when the molecule is built.
+    #[serde(default)]
+    suppressed: HashSet<EditId>,
+    #[serde(default)]
+    folders: Vec<EditFolder>,
+    #[serde(default)]
+    folder_counter: FolderId,
 }
 
 impl EditList {
@@ -126,13 +695,213 @@ impl EditList {
     // Removes the feature with the given `id` from the feature list, shifting all features after it to the left.
     pub fn remove(&mut self, id: EditId) {
         self.edits.remove(&id);
-        self.order.remove(id);
+        self.order.retain(|other| *other != id);
+        self.suppressed.remove(&id);
+        let folder = self.folder_of(id).map(|folder| folder.id);
+        for folder in &mut self.folders {
+            folder.edits.retain(|other| *other != id);
+        }
+        self.prune_folders(folder);
     }
 
     pub fn get(&self, id: &EditId) -> Option<&Edit> {
         self.edits.get(id)
     }
 
+    /// Changes the parameters of the edit `id`, keeping its place and ID.
+    /// Returns the edit as it was, or `None` if there is no such edit.
+    pub fn replace(&mut self, id: EditId, edit: Edit) -> Option<Edit> {
+        let old = self.edits.get_mut(&id)?;
+        Some(std::mem::replace(old, edit))
+    }
+
+    /// Moves the edit `id` so that it ends up at `position` in the list,
+    /// shifting the edits between its old and new places.  A position past
+    /// the end moves it to the end.
+    pub fn move_to(&mut self, id: EditId, position: usize) {
+        let Some(old) = self.position(id) else {
+            return;
+        };
+        self.order.remove(old);
+        self.order.insert(position.min(self.order.len()), id);
+    }
+
+    /// Whether `atom` was made by the edit `id`, either directly, as a copy,
+    /// or by one of its steps if it is a composite.
+    fn makes(&self, atom: &AtomSpecifier, id: EditId) -> bool {
+        made_by(atom, id)
+            || matches!(self.edits.get(&id), Some(Edit::Composite(composite)) if composite.makes(atom))
+    }
+
+    /// The edit in the list that atoms named after `id` belong to: the edit
+    /// `id` itself, or the composite that it is a step of.
+    pub fn top_level(&self, id: EditId) -> EditId {
+        if self.edits.contains_key(&id) {
+            return id;
+        }
+        self.order
+            .iter()
+            .copied()
+            .find(|other| {
+                matches!(&self.edits[other], Edit::Composite(composite)
+                    if composite.steps.iter().any(|(step, _)| *step == id))
+            })
+            .unwrap_or(id)
+    }
+
+    /// The ID that the next edit added to the list will get.
+    pub fn next_id(&self) -> EditId {
+        self.counter
+    }
+
+    /// Sets aside `count` IDs that no edit added later will get, e.g. for the
+    /// steps of a composite, and returns the first of them.
+    pub fn reserve_ids(&mut self, count: usize) -> EditId {
+        let first = self.counter;
+        self.counter += count;
+        first
+    }
+
+    /// Replaces the edits `ids`, which must be next to each other in the
+    /// history, with a single composite edit named `name` in their place (see
+    /// [`Composite`]).  Composites among them are combined step by step.  If
+    /// they were all directly in the same folder, the composite goes in it.
+    /// Returns the ID of the composite.
+    pub fn combine(&mut self, ids: &[EditId], name: String) -> Result<EditId, CombineError> {
+        let mut positions: Vec<usize> = ids.iter().filter_map(|id| self.position(*id)).collect();
+        positions.sort_unstable();
+        positions.dedup();
+        let (Some(&first), Some(&last)) = (positions.first(), positions.last()) else {
+            return Err(CombineError::NotAdjacent);
+        };
+        if positions.len() < 2 || last - first + 1 != positions.len() {
+            return Err(CombineError::NotAdjacent);
+        }
+        let combined = self.order[first..=last].to_vec();
+        if combined.iter().any(|id| self.is_suppressed(*id)) {
+            return Err(CombineError::Suppressed);
+        }
+
+        let mut folders = combined.iter().map(|id| self.folder_of(*id).map(|f| f.id));
+        let folder = folders.next().flatten();
+        let folder = folder.filter(|_| folders.all(|other| other == folder));
+        let mut steps = Vec::new();
+        for id in &combined {
+            match &self.edits[id] {
+                Edit::Composite(composite) => steps.extend(composite.steps.iter().cloned()),
+                edit => steps.push((*id, edit.clone())),
+            }
+        }
+
+        self.insert(
+            Edit::Composite(Composite {
+                name,
+                steps,
+                collapsed: true,
+            }),
+            first,
+        );
+        let id = self.order[first];
+        // The composite goes into the folder before the edits leave it, so
+        // that the folder is not removed for being empty.
+        self.move_to_folder(&[id], folder);
+        for edit in combined {
+            self.remove(edit);
+        }
+        Ok(id)
+    }
+
+    /// Replaces the composite `id` with its steps, as edits of their own with
+    /// the IDs they had as steps.  They go into the folder it was in, and are
+    /// suppressed if it was.  Returns their IDs, or `None` if `id` is not a
+    /// composite.
+    pub fn split(&mut self, id: EditId) -> Option<Vec<EditId>> {
+        let position = self.position(id)?;
+        let Some(Edit::Composite(composite)) = self.edits.get(&id) else {
+            return None;
+        };
+        let steps = composite.steps.clone();
+        let folder = self.folder_of(id).map(|folder| folder.id);
+        let suppressed = self.suppressed.contains(&id);
+
+        let ids: Vec<EditId> = steps.iter().map(|(step, _)| *step).collect();
+        for (offset, (step, edit)) in steps.into_iter().enumerate() {
+            self.order.insert(position + 1 + offset, step);
+            self.edits.insert(step, edit);
+            self.set_suppressed(step, suppressed);
+        }
+        self.move_to_folder(&ids, folder);
+        self.remove(id);
+        Some(ids)
+    }
+
+    /// Hides or shows the steps of the composite `id` in the edit history.
+    pub fn set_composite_collapsed(&mut self, id: EditId, collapsed: bool) {
+        if let Some(Edit::Composite(composite)) = self.edits.get_mut(&id) {
+            composite.collapsed = collapsed;
+        }
+    }
+
+    /// The edits, other than those in `ids`, that refer to atoms made by any
+    /// of `ids`, so would break if they were deleted or changed.
+    pub fn dependents(&self, ids: &[EditId]) -> Vec<Dependency> {
+        let mut dependencies = Vec::new();
+        for dependent in self.order.iter().filter(|id| !ids.contains(id)) {
+            let references = self.edits[dependent].references();
+            for dependency in ids {
+                let atoms: Vec<AtomSpecifier> = references
+                    .iter()
+                    .filter(|atom| self.makes(atom, *dependency))
+                    .map(|atom| (*atom).clone())
+                    .collect();
+                if !atoms.is_empty() {
+                    dependencies.push(Dependency {
+                        dependent: *dependent,
+                        dependency: *dependency,
+                        atoms,
+                    });
+                }
+            }
+        }
+        dependencies
+    }
+
+    /// The dependencies that moving the edit `id` to `position` (see
+    /// [`EditList::move_to`]) would break, because one of the two edits would
+    /// then come before the other that makes the atoms it refers to.
+    pub fn move_conflicts(&self, id: EditId, position: usize) -> Vec<Dependency> {
+        let Some(old) = self.position(id) else {
+            return Vec::new();
+        };
+        let position = position.min(self.order.len() - 1);
+        if position < old {
+            // The edits it passes would come after it.
+            let references = self.edits[&id].references();
+            self.order[position..old]
+                .iter()
+                .filter_map(|dependency| {
+                    let atoms: Vec<AtomSpecifier> = references
+                        .iter()
+                        .filter(|atom| self.makes(atom, *dependency))
+                        .map(|atom| (*atom).clone())
+                        .collect();
+                    (!atoms.is_empty()).then_some(Dependency {
+                        dependent: id,
+                        dependency: *dependency,
+                        atoms,
+                    })
+                })
+                .collect()
+        } else {
+            // The edits it passes would come before it.
+            let passed = &self.order[old + 1..=position];
+            self.dependents(&[id])
+                .into_iter()
+                .filter(|dependency| passed.contains(&dependency.dependent))
+                .collect()
+        }
+    }
+
     // Adds a new feature to the end of the feature list.
     pub fn push_back(&mut self, edit: Edit) -> usize {
         let id = self.counter;
@@ -155,6 +924,189 @@ impl EditList {
     pub fn order(&self) -> &[EditId] {
         &self.order
     }
+
+    /// The position of the edit `id` in the list, if it is in the list.
+    pub fn position(&self, id: EditId) -> Option<usize> {
+        self.order.iter().position(|other| *other == id)
+    }
+
+    /// Whether the edit `id` is skipped when the molecule is built, either
+    /// because it is suppressed itself or because a folder it is in is.
+    pub fn is_suppressed(&self, id: EditId) -> bool {
+        self.suppressed.contains(&id) || self.folder_path(id).iter().any(|folder| folder.suppressed)
+    }
+
+    pub fn set_suppressed(&mut self, id: EditId, suppressed: bool) {
+        if suppressed {
+            self.suppressed.insert(id);
+        } else {
+            self.suppressed.remove(&id);
+        }
+    }
+
+    pub fn folders(&self) -> &[EditFolder] {
+        &self.folders
+    }
+
+    pub fn folder(&self, id: FolderId) -> Option<&EditFolder> {
+        self.folders.iter().find(|folder| folder.id == id)
+    }
+
+    fn folder_mut(&mut self, id: FolderId) -> Option<&mut EditFolder> {
+        self.folders.iter_mut().find(|folder| folder.id == id)
+    }
+
+    /// The folder that the edit `id` is directly in, if any.
+    pub fn folder_of(&self, id: EditId) -> Option<&EditFolder> {
+        self.folders
+            .iter()
+            .find(|folder| folder.edits.contains(&id))
+    }
+
+    /// The folders that the edit `id` is in, from the top level down to the
+    /// one it is directly in.
+    pub fn folder_path(&self, id: EditId) -> Vec<&EditFolder> {
+        let mut path = Vec::new();
+        let mut folder = self.folder_of(id);
+        while let Some(current) = folder {
+            path.push(current);
+            folder = current.parent.and_then(|parent| self.folder(parent));
+        }
+        path.reverse();
+        path
+    }
+
+    /// The folders directly in the folder `parent`, or at the top level if
+    /// `parent` is `None`.
+    pub fn subfolders(&self, parent: Option<FolderId>) -> impl Iterator<Item = &EditFolder> {
+        self.folders
+            .iter()
+            .filter(move |folder| folder.parent == parent)
+    }
+
+    /// Every edit in the folder `id`, including those in the folders inside
+    /// it, in history order.
+    pub fn folder_edits(&self, id: FolderId) -> Vec<EditId> {
+        self.order
+            .iter()
+            .copied()
+            .filter(|edit| self.folder_path(*edit).iter().any(|folder| folder.id == id))
+            .collect()
+    }
+
+    /// Puts the edits `ids` into a new folder named `name`, taking them out of
+    /// any folders they were directly in before.  If they were all directly in
+    /// the same folder, the new folder goes inside that one; otherwise it goes
+    /// at the top level.
+    pub fn group(&mut self, ids: &[EditId], name: String) -> FolderId {
+        let mut parents = ids.iter().map(|id| self.folder_of(*id).map(|f| f.id));
+        let first = parents.next().flatten();
+        let parent = if parents.all(|parent| parent == first) {
+            first
+        } else {
+            None
+        };
+
+        let id = self.create_folder(name, parent);
+        self.move_to_folder(ids, Some(id));
+        id
+    }
+
+    /// Creates an empty folder inside the folder `parent`, or at the top level.
+    pub fn create_folder(&mut self, name: String, parent: Option<FolderId>) -> FolderId {
+        let id = self.folder_counter;
+        self.folder_counter += 1;
+        self.folders.push(EditFolder {
+            id,
+            name,
+            parent: parent.filter(|parent| self.folder(*parent).is_some()),
+            edits: Vec::new(),
+            collapsed: false,
+            suppressed: false,
+        });
+        id
+    }
+
+    /// Moves the edits `ids` directly into the folder `folder`, or out of all
+    /// folders if it is `None`.
+    pub fn move_to_folder(&mut self, ids: &[EditId], folder: Option<FolderId>) {
+        for existing in &mut self.folders {
+            existing.edits.retain(|id| !ids.contains(id));
+        }
+        if let Some(folder) = folder.and_then(|folder| self.folder_mut(folder)) {
+            folder.edits.extend_from_slice(ids);
+        }
+    }
+
+    /// Moves the folder `id` inside the folder `parent`, or to the top level.
+    /// Returns false, changing nothing, if `parent` is the folder itself or
+    /// inside it.
+    pub fn set_folder_parent(&mut self, id: FolderId, parent: Option<FolderId>) -> bool {
+        let mut ancestor = parent;
+        while let Some(current) = ancestor {
+            if current == id {
+                return false;
+            }
+            ancestor = self.folder(current).and_then(|folder| folder.parent);
+        }
+        match self.folder_mut(id) {
+            Some(folder) => {
+                folder.parent = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn rename_folder(&mut self, id: FolderId, name: String) {
+        if let Some(folder) = self.folder_mut(id) {
+            folder.name = name;
+        }
+    }
+
+    pub fn set_folder_collapsed(&mut self, id: FolderId, collapsed: bool) {
+        if let Some(folder) = self.folder_mut(id) {
+            folder.collapsed = collapsed;
+        }
+    }
+
+    pub fn set_folder_suppressed(&mut self, id: FolderId, suppressed: bool) {
+        if let Some(folder) = self.folder_mut(id) {
+            folder.suppressed = suppressed;
+        }
+    }
+
+    /// Removes the folder `id`, moving its edits and folders into the folder
+    /// it was in.
+    pub fn ungroup(&mut self, id: FolderId) {
+        let Some(index) = self.folders.iter().position(|folder| folder.id == id) else {
+            return;
+        };
+        let folder = self.folders.remove(index);
+        for child in &mut self.folders {
+            if child.parent == Some(id) {
+                child.parent = folder.parent;
+            }
+        }
+        if let Some(parent) = folder.parent.and_then(|parent| self.folder_mut(parent)) {
+            parent.edits.extend(folder.edits);
+        }
+    }
+
+    // Removes the folder `id` if it is empty, and then each folder it was in
+    // that is left empty in turn.
+    fn prune_folders(&mut self, mut id: Option<FolderId>) {
+        while let Some(current) = id {
+            let Some(folder) = self.folder(current) else {
+                return;
+            };
+            if !folder.edits.is_empty() || self.subfolders(Some(current)).next().is_some() {
+                return;
+            }
+            id = folder.parent;
+            self.folders.retain(|folder| folder.id != current);
+        }
+    }
 }
 
 /// Allows a FeatureList to be iterated over.
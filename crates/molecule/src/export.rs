@@ -0,0 +1,269 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Writing atoms out for other tools, as PDB files, XYZ files, molfiles, or
+//! fragments that atomCAD can import again.  What is written is an
+//! [`AtomImport`], so any part of a molecule can be exported: see
+//! [`extract_selection`], which can also cap the bonds that were cut to leave
+//! the rest of the molecule behind, and [`transform`], which places the atoms
+//! as the molecule is placed.  Each atom can be written with a note, such as
+//! where it came from (see [`crate::provenance`]), with [`write_annotated`].
+
+use std::collections::HashSet;
+use std::fmt::{self, Write as _};
+
+use common::ids::AtomSpecifier;
+use periodic_table::Element;
+use ultraviolet::Mat4;
+
+use crate::dynamics::parameters::bond_length;
+use crate::edit::{AtomImport, Edit, EditContext as _};
+use crate::molecule::Molecule;
+use crate::parts;
+
+// The most atoms or bonds that the counts line of a V2000 molfile can hold.
+const MOLFILE_LIMIT: usize = 999;
+
+/// The formats that atoms can be exported in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Pdb,
+    Xyz,
+    Molfile,
+    /// An atom import edit, as JSON, to be added to another molecule.
+    Fragment,
+}
+
+impl ExportFormat {
+    /// The usual extension of files in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Pdb => "pdb",
+            ExportFormat::Xyz => "xyz",
+            ExportFormat::Molfile => "sdf",
+            ExportFormat::Fragment => "json",
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "pdb" | "ent" => Some(ExportFormat::Pdb),
+            "xyz" => Some(ExportFormat::Xyz),
+            "sdf" | "mol" => Some(ExportFormat::Molfile),
+            "json" => Some(ExportFormat::Fragment),
+            _ => None,
+        }
+    }
+}
+
+/// Why atoms could not be exported.
+#[derive(Debug)]
+pub enum ExportError {
+    /// The format cannot hold this many atoms or bonds.
+    TooLarge {
+        limit: usize,
+    },
+    Serialize(serde_json::Error),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::TooLarge { limit } => {
+                write!(f, "the format holds at most {} atoms and bonds", limit)
+            }
+            ExportError::Serialize(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Reproduces the atoms `atoms` of `molecule` and the bonds between them, as
+/// [`parts::extract_atoms`] does.  With `cap` set, each bond to an atom that
+/// is left out is replaced by a hydrogen along it instead, at the length of a
+/// bond to hydrogen, so that the part exported has no dangling bonds.
+pub fn extract_selection(
+    molecule: &Molecule,
+    atoms: &[AtomSpecifier],
+    name: &str,
+    cap: bool,
+) -> AtomImport {
+    let atoms: Vec<AtomSpecifier> = atoms
+        .iter()
+        .filter(|spec| molecule.find_atom(spec).is_some())
+        .cloned()
+        .collect();
+    let Edit::AtomImport(mut import) = parts::extract_atoms(molecule, &atoms, name) else {
+        unreachable!("extract_atoms always makes an atom import");
+    };
+    if !cap {
+        return import;
+    }
+
+    let selected: HashSet<&AtomSpecifier> = atoms.iter().collect();
+    for (index, spec) in atoms.iter().enumerate() {
+        let (element, pos) = import.atoms[index];
+        for (neighbor, _) in molecule.bonds(spec) {
+            if selected.contains(&neighbor) {
+                continue;
+            }
+            let Some(neighbor_pos) = molecule.pos(&neighbor) else {
+                continue;
+            };
+            let direction = (*neighbor_pos - pos).normalized();
+            let length = bond_length(element, Element::Hydrogen, 1);
+            import
+                .atoms
+                .push((Element::Hydrogen, pos + direction * length));
+            import.bonds.push((index, import.atoms.len() - 1, 1));
+        }
+    }
+    import
+}
+
+/// Moves the atoms of `import` by `transform`, e.g. to write a molecule's
+/// atoms where the molecule is placed (see
+/// [`MoleculeEditor::local_transform`](crate::MoleculeEditor::local_transform))
+/// rather than where they were built.
+pub fn transform(import: &mut AtomImport, transform: Mat4) {
+    for (_, pos) in &mut import.atoms {
+        *pos = transform.transform_point3(*pos);
+    }
+}
+
+/// Writes the atoms and bonds in the given format.
+pub fn write(import: &AtomImport, format: ExportFormat) -> Result<String, ExportError> {
+    write_annotated(import, format, &[])
+}
+
+/// Writes the atoms and bonds as [`write`] does, with a note on each atom.
+/// The notes are in the order of the atoms, and atoms past the end of them,
+/// such as the hydrogens that cap cut bonds, have none.  PDB files hold the
+/// notes as REMARK 999 records, XYZ files after each atom's coordinates, and
+/// molfiles as an SD data item with a line for each atom.  Fragments are
+/// written without them.
+pub fn write_annotated(
+    import: &AtomImport,
+    format: ExportFormat,
+    notes: &[String],
+) -> Result<String, ExportError> {
+    match format {
+        ExportFormat::Pdb => Ok(write_pdb(import, notes)),
+        ExportFormat::Xyz => Ok(write_xyz(import, notes)),
+        ExportFormat::Molfile => write_molfile(import, notes),
+        ExportFormat::Fragment => serde_json::to_string_pretty(&Edit::AtomImport(import.clone()))
+            .map_err(ExportError::Serialize),
+    }
+}
+
+// A HETATM record for each atom, all in one residue, then CONECT records for
+// the bonds.  Bond orders are not written, as readers disagree on how they
+// are given.  The notes come first, as remarks.
+fn write_pdb(import: &AtomImport, notes: &[String]) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "COMPND    {}", import.name);
+    for (index, note) in notes.iter().enumerate() {
+        let _ = writeln!(text, "REMARK 999 ATOM {:>5} {}", index + 1, note);
+    }
+    for (index, (element, pos)) in import.atoms.iter().enumerate() {
+        let symbol = element.symbol();
+        // Atom names of one-letter elements start in the second column, so
+        // that they line up with those of two-letter elements.
+        let name = if symbol.len() == 1 {
+            format!(" {:<3}", symbol)
+        } else {
+            format!("{:<4}", symbol)
+        };
+        let _ = writeln!(
+            text,
+            "HETATM{:>5} {} UNL A   1    {:>8.3}{:>8.3}{:>8.3}  1.00  0.00          {:>2}",
+            index + 1,
+            name,
+            pos.x,
+            pos.y,
+            pos.z,
+            symbol.to_ascii_uppercase()
+        );
+    }
+
+    let mut neighbors = vec![Vec::new(); import.atoms.len()];
+    for (a, b, _) in &import.bonds {
+        neighbors[*a].push(*b);
+        neighbors[*b].push(*a);
+    }
+    for (index, bonded) in neighbors.iter().enumerate() {
+        // Each record has room for four bonded atoms.
+        for chunk in bonded.chunks(4) {
+            let _ = write!(text, "CONECT{:>5}", index + 1);
+            for other in chunk {
+                let _ = write!(text, "{:>5}", other + 1);
+            }
+            let _ = writeln!(text);
+        }
+    }
+    text.push_str("END\n");
+    text
+}
+
+fn write_xyz(import: &AtomImport, notes: &[String]) -> String {
+    let mut text = format!("{}\n{}\n", import.atoms.len(), import.name);
+    for (index, (element, pos)) in import.atoms.iter().enumerate() {
+        let _ = write!(
+            text,
+            "{:<2} {:>12.6} {:>12.6} {:>12.6}",
+            element.symbol(),
+            pos.x,
+            pos.y,
+            pos.z
+        );
+        match notes.get(index) {
+            Some(note) => {
+                let _ = writeln!(text, "  {}", note);
+            }
+            None => text.push('\n'),
+        }
+    }
+    text
+}
+
+// A V2000 molfile, in the layout that `sdf::parse` reads, ended as an SD file.
+fn write_molfile(import: &AtomImport, notes: &[String]) -> Result<String, ExportError> {
+    if import.atoms.len() > MOLFILE_LIMIT || import.bonds.len() > MOLFILE_LIMIT {
+        return Err(ExportError::TooLarge {
+            limit: MOLFILE_LIMIT,
+        });
+    }
+
+    let mut text = format!("{}\n  atomCAD\n\n", import.name);
+    let _ = writeln!(
+        text,
+        "{:>3}{:>3}  0  0  0  0            999 V2000",
+        import.atoms.len(),
+        import.bonds.len()
+    );
+    for (element, pos) in &import.atoms {
+        let _ = writeln!(
+            text,
+            "{:>10.4}{:>10.4}{:>10.4} {:<3} 0  0  0  0  0  0  0  0  0  0  0  0",
+            pos.x,
+            pos.y,
+            pos.z,
+            element.symbol()
+        );
+    }
+    for (a, b, order) in &import.bonds {
+        let _ = writeln!(text, "{:>3}{:>3}{:>3}  0", a + 1, b + 1, order);
+    }
+    text.push_str("M  END\n");
+    if !notes.is_empty() {
+        text.push_str("> <atomCAD.notes>\n");
+        for note in notes {
+            let _ = writeln!(text, "{}", note);
+        }
+        text.push('\n');
+    }
+    text.push_str("$$$$\n");
+    Ok(text)
+}
+
+// End of File
@@ -0,0 +1,255 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Crystals, built a block of unit cells at a time rather than atom by atom.
+//! A [`Lattice`] edit fills a block with the atoms of one of the common
+//! structures of nanomechanical parts, and bonds each atom to its nearest
+//! neighbours.
+//!
+//! Structures with two sites per primitive cell (diamond, graphene and
+//! lonsdaleite) take an element for each site, so that compounds like silicon
+//! carbide (zincblende, on the diamond lattice) or hexagonal boron nitride (on
+//! the graphene lattice) can be built too.
+
+use common::ids::{AtomSpecifier, EditId};
+use periodic_table::Element;
+use serde::{Deserialize, Serialize};
+use ultraviolet::Vec3;
+
+use crate::cell::UnitCell;
+use crate::edit::{EditContext, EditError};
+use crate::spatial::SpatialIndex;
+
+/// How much further apart than nearest neighbours two atoms can be and still
+/// be bonded.  Second neighbours are at least 40% further away in all of the
+/// structures.
+const BOND_TOLERANCE: f32 = 1.1;
+
+/// The structures that lattices can have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum LatticeKind {
+    /// Two interpenetrating face-centred cubic lattices, each atom bonded
+    /// tetrahedrally to four of the other.
+    DiamondCubic,
+    /// Face-centred cubic, as in most metals.  There is one site, so only the
+    /// first element is used.
+    Fcc,
+    /// A single hexagonal sheet, each atom bonded to three others.  Sheets are
+    /// one atom thick, so the third extent is ignored.
+    Graphene,
+    /// Hexagonal diamond, the wurtzite structure.
+    Lonsdaleite,
+}
+
+impl LatticeKind {
+    pub const ALL: [LatticeKind; 4] = [
+        LatticeKind::DiamondCubic,
+        LatticeKind::Fcc,
+        LatticeKind::Graphene,
+        LatticeKind::Lonsdaleite,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LatticeKind::DiamondCubic => "Diamond cubic",
+            LatticeKind::Fcc => "FCC",
+            LatticeKind::Graphene => "Graphene",
+            LatticeKind::Lonsdaleite => "Lonsdaleite",
+        }
+    }
+
+    /// The lattice constant of the usual carbon (or, for FCC, copper) form of
+    /// the structure, in angstroms.  For the hexagonal structures, this is
+    /// the length of the edge of the hexagon's cell, `a`.
+    pub fn default_constant(self) -> f32 {
+        match self {
+            LatticeKind::DiamondCubic => 3.567,
+            LatticeKind::Fcc => 3.615,
+            LatticeKind::Graphene => 2.46,
+            LatticeKind::Lonsdaleite => 2.52,
+        }
+    }
+
+    /// The edges of the conventional cell with lattice constant `constant`.
+    /// The hexagonal structures have the ideal ratio of `c` to `a`.
+    pub fn cell(self, constant: f32) -> UnitCell {
+        let a = constant;
+        match self {
+            LatticeKind::DiamondCubic | LatticeKind::Fcc => UnitCell {
+                a: Vec3::new(a, 0.0, 0.0),
+                b: Vec3::new(0.0, a, 0.0),
+                c: Vec3::new(0.0, 0.0, a),
+            },
+            LatticeKind::Graphene | LatticeKind::Lonsdaleite => {
+                UnitCell::from_parameters([a, a, a * (8.0f32 / 3.0).sqrt()], [90.0, 90.0, 120.0])
+            }
+        }
+    }
+
+    // The sites of the conventional cell, in fractions of its edges, with the
+    // sublattice (0 or 1) that each belongs to.
+    fn basis(self) -> Vec<([f32; 3], usize)> {
+        const FCC: [[f32; 3]; 4] = [
+            [0.0, 0.0, 0.0],
+            [0.0, 0.5, 0.5],
+            [0.5, 0.0, 0.5],
+            [0.5, 0.5, 0.0],
+        ];
+        // The height of the second site of lonsdaleite above the first, in
+        // fractions of `c`, which makes all four bonds the same length.
+        const U: f32 = 3.0 / 8.0;
+
+        match self {
+            LatticeKind::Fcc => FCC.iter().map(|&site| (site, 0)).collect(),
+            LatticeKind::DiamondCubic => FCC
+                .iter()
+                .map(|&site| (site, 0))
+                .chain(
+                    FCC.iter()
+                        .map(|&[x, y, z]| ([x + 0.25, y + 0.25, z + 0.25], 1)),
+                )
+                .collect(),
+            LatticeKind::Graphene => vec![([0.0, 0.0, 0.0], 0), ([1.0 / 3.0, 2.0 / 3.0, 0.0], 1)],
+            LatticeKind::Lonsdaleite => vec![
+                ([1.0 / 3.0, 2.0 / 3.0, 0.0], 0),
+                ([2.0 / 3.0, 1.0 / 3.0, 0.5], 0),
+                ([1.0 / 3.0, 2.0 / 3.0, U], 1),
+                ([2.0 / 3.0, 1.0 / 3.0, 0.5 + U], 1),
+            ],
+        }
+    }
+
+    // The distance between bonded atoms, in fractions of the lattice
+    // constant.
+    fn bond_fraction(self) -> f32 {
+        match self {
+            LatticeKind::DiamondCubic => 3f32.sqrt() / 4.0,
+            LatticeKind::Fcc => 1.0 / 2f32.sqrt(),
+            LatticeKind::Graphene => 1.0 / 3f32.sqrt(),
+            LatticeKind::Lonsdaleite => (3.0f32 / 8.0).sqrt(),
+        }
+    }
+}
+
+/// Fills a block of `extents` conventional cells with a crystal, and bonds
+/// each atom to its nearest neighbours.  Atoms at the faces of the block are
+/// left with the bonds they have inside it, ready to be filled with hydrogen.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Lattice {
+    pub kind: LatticeKind,
+    /// The elements of the two sublattices.  The same element twice makes an
+    /// elemental crystal.
+    pub elements: [Element; 2],
+    /// The lattice constant, in angstroms (see
+    /// [`LatticeKind::default_constant`]).
+    pub constant: f32,
+    /// The number of conventional cells along each of the cell's edges.
+    pub extents: [u32; 3],
+    /// Whether the block is made periodic, with itself as the unit cell.
+    /// Bonds are not made across the faces of the cell.
+    pub periodic: bool,
+}
+
+impl Lattice {
+    /// A block of carbon (or, for FCC, copper) in the structure `kind`, at its
+    /// usual lattice constant.
+    pub fn new(kind: LatticeKind, extents: [u32; 3]) -> Self {
+        let element = match kind {
+            LatticeKind::Fcc => Element::Copper,
+            _ => Element::Carbon,
+        };
+        Self {
+            kind,
+            elements: [element; 2],
+            constant: kind.default_constant(),
+            extents,
+            periodic: false,
+        }
+    }
+
+    /// The number of cells that the block actually has along each edge.
+    pub fn cells(&self) -> [u32; 3] {
+        let [a, b, c] = self.extents;
+        match self.kind {
+            LatticeKind::Graphene => [a, b, c.min(1)],
+            _ => [a, b, c],
+        }
+    }
+
+    /// The elements and positions of the atoms of the block.
+    pub fn atoms(&self) -> Vec<(Element, Vec3)> {
+        let cell = self.kind.cell(self.constant);
+        let basis = self.kind.basis();
+        let [na, nb, nc] = self.cells();
+        let mut atoms = Vec::with_capacity((na * nb * nc) as usize * basis.len());
+        for i in 0..na {
+            for j in 0..nb {
+                for k in 0..nc {
+                    for ([x, y, z], sublattice) in &basis {
+                        let pos = cell.translation(i as i32, j as i32, k as i32)
+                            + cell.a * *x
+                            + cell.b * *y
+                            + cell.c * *z;
+                        atoms.push((self.elements[*sublattice], pos));
+                    }
+                }
+            }
+        }
+        atoms
+    }
+
+    /// The bonds between the atoms listed by [`Lattice::atoms`], as pairs of
+    /// indices into the list.
+    pub fn bonds(&self, atoms: &[(Element, Vec3)]) -> Vec<(usize, usize)> {
+        let length = self.kind.bond_fraction() * self.constant * BOND_TOLERANCE;
+        let index = SpatialIndex::new(
+            atoms.iter().enumerate().map(|(i, (_, pos))| (i, *pos)),
+            length,
+        );
+        let mut bonds = Vec::new();
+        for (i, (_, pos)) in atoms.iter().enumerate() {
+            let mut neighbors: Vec<usize> = index.within(*pos, length).filter(|&j| j > i).collect();
+            neighbors.sort_unstable();
+            bonds.extend(neighbors.into_iter().map(|j| (i, j)));
+        }
+        bonds
+    }
+
+    /// The unit cell of the whole block.
+    pub fn block_cell(&self) -> UnitCell {
+        let cell = self.kind.cell(self.constant);
+        let [na, nb, nc] = self.cells();
+        UnitCell {
+            a: cell.a * na as f32,
+            b: cell.b * nb as f32,
+            c: cell.c * nc as f32,
+        }
+    }
+}
+
+pub(crate) fn spawn_lattice(
+    lattice: &Lattice,
+    edit_id: &EditId,
+    commands: &mut dyn EditContext,
+) -> Result<(), EditError> {
+    let mut spec = AtomSpecifier::new(*edit_id);
+    let atoms = lattice.atoms();
+    let mut specs = Vec::with_capacity(atoms.len());
+
+    for (element, pos) in &atoms {
+        let atom_spec = spec.next_spec();
+        commands.add_atom(*element, *pos, atom_spec.clone(), None)?;
+        specs.push(atom_spec);
+    }
+    for (a1, a2) in lattice.bonds(&atoms) {
+        commands.create_bond(&specs[a1], &specs[a2], 1)?;
+    }
+    if lattice.periodic {
+        commands.set_cell(Some(lattice.block_cell()));
+    }
+
+    Ok(())
+}
+
+// End of File
@@ -1,9 +1,38 @@
-pub use crate::molecule::{AtomIndex, BondIndex, BondOrder, MoleculeGraph};
-pub use crate::molecule_editor::MoleculeEditor;
+pub use crate::checkpoint_store::CheckpointPolicy;
+pub use crate::molecule::{
+    AtomIndex, AtomProperty, BondIndex, BondOrder, HydrogenDisplay, MoleculeGraph, ResidueId,
+};
+pub use crate::molecule_editor::{EditorTimings, MoleculeEditor};
 
-mod dynamics;
+mod atom_table;
+pub mod bond_orders;
+pub mod build_script;
+pub mod cell;
+mod checkpoint_store;
+pub mod composite;
+pub mod cut;
+pub mod doping;
+pub mod dynamics;
 pub mod edit;
+pub mod export;
+pub mod lattice;
+pub mod mirror;
+pub mod moiety;
 mod molecule;
 mod molecule_editor;
+pub mod parts;
+pub mod passivation;
 mod pdb;
+pub mod polymer;
+pub mod provenance;
+pub mod reindex;
+pub mod residues;
+pub mod sdf;
+pub mod selection;
+pub mod spatial;
+pub mod strain;
+pub mod superposition;
+pub mod symmetry;
+pub mod torsion;
 mod vsepr;
+pub mod zmatrix;
@@ -0,0 +1,110 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Mirror images of molecules.  A reflection keeps every bond length and
+//! angle, but turns each stereocenter into its opposite configuration, so the
+//! result is the other enantiomer of a chiral molecule rather than the same
+//! molecule moved.  The [`Mirror`] edit marks the stereocenters it inverts,
+//! so that the change is not missed.
+
+use std::collections::{HashSet, VecDeque};
+
+use common::{ids::AtomSpecifier, Plane};
+use serde::{Deserialize, Serialize};
+
+use crate::edit::{EditContext, EditError, ReferenceType};
+use crate::BondOrder;
+
+/// How many bonds out from a stereocenter its substituents are compared.
+/// Substituents that only differ further out than this are taken to be the
+/// same, which misses some stereocenters of large molecules but none of the
+/// usual ones.
+const SUBSTITUENT_DEPTH: usize = 4;
+
+/// Reflects every atom in the molecule in `plane`.  The unit cell of a
+/// periodic molecule is left as it is.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Mirror {
+    pub plane: Plane,
+}
+
+/// The atoms of the molecule that are stereocenters: atoms with four bonds
+/// whose substituents all differ, so that swapping any two of them makes a
+/// different molecule.  Substituents are told apart by the elements and bond
+/// orders they are made of, out to a few bonds from the center, rather than by
+/// the full Cahn-Ingold-Prelog rules.
+pub fn stereocenters(commands: &dyn EditContext) -> Vec<AtomSpecifier> {
+    commands
+        .atoms()
+        .into_iter()
+        .filter(|atom| {
+            let bonds = commands.bonds(atom);
+            if bonds.len() != 4 {
+                return false;
+            }
+            let mut signatures: Vec<_> = bonds
+                .iter()
+                .map(|(neighbor, order)| substituent_signature(commands, atom, neighbor, *order))
+                .collect();
+            signatures.sort();
+            signatures.dedup();
+            signatures.len() == 4
+        })
+        .collect()
+}
+
+// Describes the substituent of `center` that starts at `start`, as the sorted
+// list of the depth, element and bond order of each atom reached from it
+// without going back through the center.
+fn substituent_signature(
+    commands: &dyn EditContext,
+    center: &AtomSpecifier,
+    start: &AtomSpecifier,
+    order: BondOrder,
+) -> Vec<(usize, u8, BondOrder)> {
+    let element = |atom: &AtomSpecifier| {
+        commands
+            .find_atom(atom)
+            .map_or(0, |node| node.element as u8)
+    };
+    let mut signature = vec![(0, element(start), order)];
+    let mut visited = HashSet::from([center.clone(), start.clone()]);
+    let mut queue = VecDeque::from([(start.clone(), 0)]);
+    while let Some((atom, depth)) = queue.pop_front() {
+        if depth + 1 >= SUBSTITUENT_DEPTH {
+            continue;
+        }
+        for (neighbor, order) in commands.bonds(&atom) {
+            if visited.insert(neighbor.clone()) {
+                signature.push((depth + 1, element(&neighbor), order));
+                queue.push_back((neighbor, depth + 1));
+            }
+        }
+    }
+    signature.sort_unstable();
+    signature
+}
+
+pub(crate) fn spawn_mirror(
+    mirror: &Mirror,
+    commands: &mut dyn EditContext,
+) -> Result<(), EditError> {
+    let centers = stereocenters(commands);
+    for atom in commands.atoms() {
+        let pos = *commands
+            .pos(&atom)
+            .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+        commands.set_pos(&atom, mirror.plane.reflect_point(pos))?;
+    }
+    for center in centers {
+        let inverted = commands
+            .find_atom(&center)
+            .ok_or(EditError::BrokenReference(ReferenceType::Atom))?
+            .stereo_inverted;
+        commands.set_stereo_inverted(&center, !inverted)?;
+    }
+    Ok(())
+}
+
+// End of File
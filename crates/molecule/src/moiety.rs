@@ -0,0 +1,198 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A library of functional groups, such as methyl, phenyl or adamantyl, that
+//! can be grafted onto an atom in place of one of its hydrogens.
+//!
+//! Each [`Moiety`] is a small molecule graph of heavy atoms, kept in
+//! `moieties.json`.  Its first atom is the one that bonds to the atom it is
+//! grafted onto, and sits at the origin, with that bond pointing down the -z
+//! axis; the rest of the group lies above it.  The hydrogens are not listed,
+//! but filled in after grafting, so that they follow the group's own bonds.
+//!
+//! An [`AttachMoiety`] refers to its moiety by id, like a polymer refers to
+//! its residues, so the library must keep the ids of the moieties it has
+//! offered.
+
+use std::sync::OnceLock;
+
+use common::ids::{AtomSpecifier, EditId};
+use periodic_table::Element;
+use serde::{Deserialize, Serialize};
+use ultraviolet::{Bivec3, Rotor3, Vec3};
+
+use crate::{
+    dynamics::parameters,
+    edit::{self, EditContext, EditError, ReferenceType},
+    BondOrder,
+};
+
+/// A functional group that can be grafted onto an atom.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Moiety {
+    /// The name that edits refer to the moiety by, such as `tert-butyl`.
+    pub id: String,
+    /// The name shown to the user, such as "tert-Butyl".
+    pub name: String,
+    /// The heavy atoms, the first of which bonds to the atom that the group
+    /// is grafted onto.  Positions are in angstroms, as described in the
+    /// module documentation.
+    pub atoms: Vec<(Element, Vec3)>,
+    /// Bonds between the atoms, by their index in `atoms`.
+    pub bonds: Vec<(usize, usize, BondOrder)>,
+}
+
+/// The moieties that are built in, in the order they are offered.
+pub fn library() -> &'static [Moiety] {
+    static LIBRARY: OnceLock<Vec<Moiety>> = OnceLock::new();
+    LIBRARY.get_or_init(|| {
+        serde_json::from_str(include_str!("moieties.json"))
+            .expect("the built-in moieties are well-formed")
+    })
+}
+
+/// The built-in moiety with the given id, if there is one.
+pub fn find(id: &str) -> Option<&'static Moiety> {
+    library().iter().find(|moiety| moiety.id == id)
+}
+
+/// Grafts a moiety onto an atom.  If `target` is a hydrogen bonded to another
+/// atom, it is replaced; otherwise the first hydrogen bonded to `target` is.
+/// An atom with no hydrogens gets the moiety where its next bond would go.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AttachMoiety {
+    pub target: AtomSpecifier,
+    /// The id of a moiety in the [`library`].
+    pub moiety_id: String,
+    /// How far the moiety is turned about the new bond, in degrees.
+    pub orientation: f32,
+}
+
+/// Where an [`AttachMoiety`] puts its moiety.
+pub struct Graft {
+    /// The atom that the moiety bonds to.
+    pub parent: AtomSpecifier,
+    /// The hydrogen that the moiety replaces, if any.
+    pub replaced: Option<AtomSpecifier>,
+    pub moiety: &'static Moiety,
+    /// The positions of the moiety's atoms, in the order they are listed.
+    pub positions: Vec<Vec3>,
+}
+
+impl AttachMoiety {
+    /// Works out where the moiety would go, without changing the molecule.
+    pub fn graft(&self, commands: &dyn EditContext) -> Result<Graft, EditError> {
+        let moiety = find(&self.moiety_id)
+            .ok_or_else(|| EditError::UnknownMoiety(self.moiety_id.clone()))?;
+        let (anchor, _) = *moiety
+            .atoms
+            .first()
+            .ok_or_else(|| EditError::UnknownMoiety(self.moiety_id.clone()))?;
+        let element_of = |atom: &AtomSpecifier| {
+            commands
+                .find_atom(atom)
+                .map(|node| node.element)
+                .ok_or(EditError::BrokenReference(ReferenceType::Atom))
+        };
+
+        let neighbors = commands.bonds(&self.target);
+        let (parent, replaced) = match neighbors.as_slice() {
+            [(neighbor, _)] if element_of(&self.target)? == Element::Hydrogen => {
+                (neighbor.clone(), Some(self.target.clone()))
+            }
+            _ => {
+                let hydrogen = neighbors
+                    .iter()
+                    .map(|(neighbor, _)| neighbor)
+                    .find(|neighbor| element_of(neighbor).ok() == Some(Element::Hydrogen));
+                (self.target.clone(), hydrogen.cloned())
+            }
+        };
+
+        let center = *commands
+            .pos(&parent)
+            .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+        let toward = match &replaced {
+            Some(hydrogen) => *commands
+                .pos(hydrogen)
+                .ok_or(EditError::BrokenReference(ReferenceType::Atom))?,
+            None => edit::bonded_atom_position(&parent, anchor, commands)?,
+        };
+        let direction = (toward - center).normalized();
+        let position =
+            center + direction * parameters::bond_length(element_of(&parent)?, anchor, 1);
+
+        // The bond runs down -z from the moiety's first atom, and from the new
+        // atom back to its parent in the molecule.
+        let rotation = Rotor3::from_angle_plane(
+            self.orientation.to_radians(),
+            Bivec3::from_normalized_axis(direction),
+        ) * rotation_between(-Vec3::unit_z(), -direction);
+        let positions = moiety
+            .atoms
+            .iter()
+            .map(|(_, pos)| position + rotation * *pos)
+            .collect();
+
+        Ok(Graft {
+            parent,
+            replaced,
+            moiety,
+            positions,
+        })
+    }
+}
+
+// The rotation that turns the unit vector `from` onto the unit vector `to`,
+// including when they point in opposite directions.
+fn rotation_between(from: Vec3, to: Vec3) -> Rotor3 {
+    if from.dot(to) > -0.9999 {
+        return Rotor3::from_rotation_between(from, to);
+    }
+    let axis = if from.x.abs() < 0.9 {
+        Vec3::unit_x()
+    } else {
+        Vec3::unit_y()
+    };
+    Rotor3::from_angle_plane(
+        std::f32::consts::PI,
+        Bivec3::from_normalized_axis(from.cross(axis).normalized()),
+    )
+}
+
+pub(crate) fn spawn_moiety(
+    attach: &AttachMoiety,
+    edit_id: &EditId,
+    commands: &mut dyn EditContext,
+) -> Result<(), EditError> {
+    let graft = attach.graft(commands)?;
+    if let Some(hydrogen) = &graft.replaced {
+        commands.remove_atoms(std::slice::from_ref(hydrogen))?;
+    }
+
+    let mut spec = AtomSpecifier::new(*edit_id);
+    let mut specs = Vec::with_capacity(graft.positions.len());
+    for (index, ((element, _), pos)) in graft.moiety.atoms.iter().zip(&graft.positions).enumerate()
+    {
+        let atom_spec = spec.next_spec();
+        if index == 0 {
+            commands.add_bonded_atom(*element, *pos, atom_spec.clone(), graft.parent.clone(), 1)?;
+        } else {
+            commands.add_atom(*element, *pos, atom_spec.clone(), None)?;
+        }
+        specs.push(atom_spec);
+    }
+    for (a1, a2, order) in &graft.moiety.bonds {
+        match (specs.get(*a1), specs.get(*a2)) {
+            (Some(a1), Some(a2)) => commands.create_bond(a1, a2, *order)?,
+            _ => return Err(EditError::BrokenReference(ReferenceType::Atom)),
+        }
+    }
+    for atom in &specs {
+        edit::fill_valence(atom, Element::Hydrogen, &mut spec, commands)?;
+    }
+    Ok(())
+}
+
+// End of File
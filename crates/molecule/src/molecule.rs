@@ -3,23 +3,38 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use std::collections::HashMap;
+use std::fmt;
 
 use common::{ids::AtomSpecifier, BoundingBox};
 use lazy_static::lazy_static;
 use periodic_table::Element;
-use petgraph::{stable_graph, visit::IntoNodeReferences};
+use petgraph::{
+    stable_graph,
+    visit::{EdgeRef as _, IntoNodeReferences, NodeIndexable as _},
+};
 use render::{AtomBuffer, AtomKind, AtomRepr, GlobalRenderResources};
 use serde::{Deserialize, Serialize};
-use serde_with::serde_as;
 use ultraviolet::Vec3;
 
+use crate::atom_table::AtomTable;
+use crate::cell::UnitCell;
+use crate::dynamics::{
+    relax_with, ConvergenceCriteria, ForceFieldKind, RelaxReport, Restrained, TorsionRestraint,
+};
 use crate::edit::{EditContext, EditError, ReferenceType};
+use crate::strain;
 
 lazy_static! {
     pub static ref PERIODIC_TABLE: periodic_table::PeriodicTable =
         periodic_table::PeriodicTable::new();
 }
 
+/// The charge that moves across a single bond per unit of difference in
+/// electronegativity between its atoms, in elementary charges.  This is chosen
+/// to give charges of the size that charge models give for common organic
+/// groups, e.g. about -0.7 on the oxygen of water.
+const CHARGE_PER_ELECTRONEGATIVITY: f32 = 0.3;
+
 /// A graph representation of a molecule.
 /// The molecule graph is stable to ensure that deleting atoms will not change
 /// the index of other atoms. It is undirected because bonds have no direction.
@@ -27,9 +42,12 @@ lazy_static! {
 /// represents.
 pub type MoleculeGraph = stable_graph::StableUnGraph<AtomNode, BondOrder>;
 
-/// A map that gives each atom in a molecule a coordinate. Used to cache structure energy minimization
-/// calculations.
-pub type AtomPositions = HashMap<AtomSpecifier, Vec3>;
+/// The coordinate of each atom in a molecule, indexed by the atom's index in the
+/// molecule's graph, which is the form the force fields work in.  The graph is
+/// stable, so removing an atom leaves a hole in its indices; the graph reuses
+/// holes for the next atoms added, and the entries for them are overwritten
+/// then.  Until then, entries for holes are meaningless.
+pub type AtomPositions = Vec<Vec3>;
 
 /// The order of a bond (i.e. single bond = 1u8, double bond = 2u8, ..). This is a
 /// u8 because we currently do not support fractional bonding, and because bonds
@@ -49,84 +67,438 @@ pub type BondIndex = stable_graph::EdgeIndex;
 
 /// Stores the state of a molecule at some point in time, but without any of the
 /// cached optimization or gpu buffers that a full `Molecule` includes.
-#[serde_as]
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "StoredCheckpoint", into = "StoredCheckpoint")]
 pub struct MoleculeCheckpoint {
     graph: MoleculeGraph,
-    #[serde_as(as = "Vec<(_, _)>")]
+    atoms: AtomTable,
     positions: AtomPositions,
+    cell: Option<UnitCell>,
+    torsions: Vec<([AtomSpecifier; 4], f32)>,
 }
 
-/// Stores the data for each atom in a `Molecule`.
-#[derive(Clone, Serialize, Deserialize)]
+// A checkpoint as it is saved.  Each atom is saved with its specifier, and
+// that of its head, so that files do not depend on how the atoms are found.
+// Files saved before positions were kept in the order of the graph list them
+// by specifier instead.
+#[derive(Serialize, Deserialize)]
+struct StoredCheckpoint {
+    graph: stable_graph::StableUnGraph<StoredAtomNode, BondOrder>,
+    positions: StoredPositions,
+    #[serde(default)]
+    cell: Option<UnitCell>,
+    #[serde(default)]
+    torsions: Vec<([AtomSpecifier; 4], f32)>,
+}
+
+// An atom as it is saved.  See `AtomNode`.
+#[derive(Serialize, Deserialize)]
+struct StoredAtomNode {
+    element: Element,
+    spec: AtomSpecifier,
+    head: Option<AtomSpecifier>,
+    #[serde(default)]
+    pinned: bool,
+    #[serde(default)]
+    chain: Option<char>,
+    #[serde(default)]
+    residue: Option<ResidueId>,
+    #[serde(default)]
+    source_line: Option<usize>,
+    #[serde(default)]
+    stereo_inverted: bool,
+}
+
+enum StoredPositions {
+    Dense(AtomPositions),
+    Keyed(Vec<(AtomSpecifier, Vec3)>),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum UntaggedPositions {
+    Dense(AtomPositions),
+    Keyed(Vec<(AtomSpecifier, Vec3)>),
+}
+
+impl Serialize for StoredPositions {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            StoredPositions::Dense(positions) => positions.serialize(serializer),
+            StoredPositions::Keyed(keyed) => keyed.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for StoredPositions {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        // Only files can hold the old layout.  Compact binary formats, like
+        // the one checkpoints are spilled to disk in, cannot tell the two
+        // apart, and only ever hold the current one.
+        if !deserializer.is_human_readable() {
+            return AtomPositions::deserialize(deserializer).map(StoredPositions::Dense);
+        }
+        Ok(match UntaggedPositions::deserialize(deserializer)? {
+            UntaggedPositions::Dense(positions) => StoredPositions::Dense(positions),
+            UntaggedPositions::Keyed(keyed) => StoredPositions::Keyed(keyed),
+        })
+    }
+}
+
+impl From<MoleculeCheckpoint> for StoredCheckpoint {
+    fn from(checkpoint: MoleculeCheckpoint) -> Self {
+        let atoms = &checkpoint.atoms;
+        let spec = |index| {
+            atoms
+                .spec(index)
+                .expect("Every atom in the graph should have a specifier")
+        };
+        StoredCheckpoint {
+            graph: checkpoint.graph.map(
+                |index, atom| StoredAtomNode {
+                    element: atom.element,
+                    spec: spec(index),
+                    head: atom.head.map(spec),
+                    pinned: atom.pinned,
+                    chain: atom.chain,
+                    residue: atom.residue.clone(),
+                    source_line: atom.source_line,
+                    stereo_inverted: atom.stereo_inverted,
+                },
+                |_, order| *order,
+            ),
+            positions: StoredPositions::Dense(checkpoint.positions),
+            cell: checkpoint.cell,
+            torsions: checkpoint.torsions,
+        }
+    }
+}
+
+impl From<StoredCheckpoint> for MoleculeCheckpoint {
+    fn from(stored: StoredCheckpoint) -> Self {
+        let mut atoms = AtomTable::default();
+        for (index, atom) in stored.graph.node_references() {
+            atoms.insert(&atom.spec, index);
+        }
+        let mut positions = match stored.positions {
+            StoredPositions::Dense(positions) => positions,
+            StoredPositions::Keyed(keyed) => {
+                let keyed: HashMap<AtomSpecifier, Vec3> = keyed.into_iter().collect();
+                let mut positions = vec![Vec3::zero(); stored.graph.node_bound()];
+                for (index, atom) in stored.graph.node_references() {
+                    if let Some(pos) = keyed.get(&atom.spec) {
+                        positions[index.index()] = *pos;
+                    }
+                }
+                positions
+            }
+        };
+        if positions.len() < stored.graph.node_bound() {
+            positions.resize(stored.graph.node_bound(), Vec3::zero());
+        }
+        // Graphs keep their indices when mapped, so the table still holds.
+        let graph = stored.graph.map(
+            |_, atom| AtomNode {
+                element: atom.element,
+                head: atom.head.as_ref().and_then(|head| atoms.get(head)),
+                pinned: atom.pinned,
+                chain: atom.chain,
+                residue: atom.residue.clone(),
+                source_line: atom.source_line,
+                stereo_inverted: atom.stereo_inverted,
+            },
+            |_, order| *order,
+        );
+        MoleculeCheckpoint {
+            graph,
+            atoms,
+            positions,
+            cell: stored.cell,
+            torsions: stored.torsions,
+        }
+    }
+}
+
+/// Stores the data for each atom in a `Molecule`.  The atom's specifier is
+/// kept by the molecule (see [`Molecule::spec`]).
+#[derive(Clone)]
 pub struct AtomNode {
     pub element: Element,
-    pub spec: AtomSpecifier,
     // The atom that this atom was bonded to (and uses as a "forward" direction). If
     // no such atom exists, then this atom is the root atom, and the forward direction
-    // should be taken to be the molecule's +z axis. Although this field is not yet
-    // used (as of september 3rd 2023), it is needed to describe molecular geometry
-    // in terms of bond angles and lengths (which will be useful later on).
-    pub head: Option<AtomSpecifier>,
+    // should be taken to be the molecule's +z axis. It is needed to describe molecular
+    // geometry in terms of bond angles and lengths.
+    pub head: Option<AtomIndex>,
+    /// Pinned atoms are held in place when the molecule is relaxed, so that the
+    /// rest of a large structure does not drift while one part is edited.
+    pub pinned: bool,
+    /// The chain that the atom belongs to, for atoms imported from a PDB file.
+    pub chain: Option<char>,
+    /// The residue that the atom belongs to, for atoms imported from a PDB
+    /// file.
+    pub residue: Option<ResidueId>,
+    /// The line of the file that the atom was read from, counting from 1, for
+    /// atoms imported from a PDB file.
+    pub source_line: Option<usize>,
+    /// Whether the atom is a stereocenter whose configuration a mirror edit
+    /// has inverted, making it the other enantiomer from the one built.
+    pub stereo_inverted: bool,
 }
 
-impl AtomNode {
-    /// Gets a vector with the direction that this atom is "facing". Atoms "face" along one
-    /// of their bonds, or along the molecule's `+z` axis if no bonds exist.
-    pub fn forward(&self, commands: &dyn EditContext) -> Vec3 {
-        match self.head {
-            Some(ref head) => {
-                let head_pos = commands
-                    .pos(head)
-                    .expect("The atom specifier an atom that is bonded should exist");
-                let pos = commands
-                    .pos(&self.spec)
-                    .expect("The atom specifier an atom that is bonded should exist");
-
-                (*head_pos - *pos).normalized()
-            }
-            None => Vec3::unit_z(),
+/// A residue of a PDB file, as its records name it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResidueId {
+    /// The residue's code, such as "ALA" or "HOH".
+    pub name: String,
+    /// Its sequence number within its chain.
+    pub number: u64,
+}
+
+impl fmt::Display for ResidueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.number)
+    }
+}
+
+/// What each atom of a molecule carries as the property it can be colored by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AtomProperty {
+    /// See [`Molecule::partial_charge`].
+    #[default]
+    PartialCharge,
+    /// The strain of the atom's most strained bond under the given force
+    /// field (see [`atom_strains`](crate::strain::atom_strains)).
+    BondStrain(ForceFieldKind),
+}
+
+/// Which of a molecule's hydrogens are drawn.  Hydrogens are usually most of
+/// the atoms of an organic molecule, so hiding them makes its shape easier to
+/// see.  Hidden hydrogens are still part of the molecule: they are relaxed,
+/// saved and exported as usual, but are not drawn or picked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum HydrogenDisplay {
+    #[default]
+    ShowAll,
+    /// Hides the hydrogens bonded only to carbon, keeping those on polar
+    /// atoms such as oxygen and nitrogen, which take part in hydrogen bonds.
+    HideNonpolar,
+    HideAll,
+}
+
+impl HydrogenDisplay {
+    pub const ALL: [HydrogenDisplay; 3] = [
+        HydrogenDisplay::ShowAll,
+        HydrogenDisplay::HideNonpolar,
+        HydrogenDisplay::HideAll,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HydrogenDisplay::ShowAll => "all hydrogens",
+            HydrogenDisplay::HideNonpolar => "polar hydrogens",
+            HydrogenDisplay::HideAll => "no hydrogens",
         }
     }
+
+    /// The policy after this one in [`HydrogenDisplay::ALL`], wrapping around.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|d| *d == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
 }
 
 /// A concrete representation of a molecule, inclding a handle to the GPU buffers needed
 /// to render it.
 #[derive(Default)]
 pub struct Molecule {
-    // TODO: This atom map is a simple but extremely inefficient implementation. This data
-    // is highly structued and repetitive: compression, flattening, and a tree could do
-    // a lot to optimize this.
-    atom_map: HashMap<AtomSpecifier, AtomIndex>,
+    // The atoms' specifiers, which edits find them by.  Everything else refers
+    // to atoms by their index in the graph.
+    atoms: AtomTable,
     pub graph: MoleculeGraph,
     bounding_box: BoundingBox,
     gpu_synced: bool,
     gpu_atoms: Option<AtomBuffer>,
     positions: AtomPositions,
+    property: AtomProperty,
+    hydrogens: HydrogenDisplay,
+    cell: Option<UnitCell>,
+    // The dihedral angles, in degrees, that set torsions hold during
+    // relaxation.
+    torsions: Vec<([AtomSpecifier; 4], f32)>,
+    // Atoms that the next relaxation holds still, as if they were pinned.
+    held: Vec<AtomSpecifier>,
 }
 
 impl Molecule {
+    /// Describes each atom for drawing.  Atoms are grouped by their PDB chain,
+    /// carry the chosen [`AtomProperty`] as the property they can be colored
+    /// by, and are marked hidden if the [`HydrogenDisplay`] hides them.
     pub fn atom_reprs(&self) -> Vec<AtomRepr> {
+        let strains = match self.property {
+            AtomProperty::PartialCharge => None,
+            AtomProperty::BondStrain(force_field) => Some(strain::atom_strains(self, force_field)),
+        };
         self.graph
-            .node_weights()
-            .map(|node| AtomRepr {
-                kind: AtomKind::new(node.element),
-                pos: *self
-                    .pos(&node.spec)
-                    .expect("Every atom in the graph should have a position"),
+            .node_references()
+            .map(|(index, node)| AtomRepr {
+                kind: AtomKind::new(node.element)
+                    .with_pinned(node.pinned)
+                    .with_group(node.chain.and_then(|c| u8::try_from(c).ok()).unwrap_or(0))
+                    .with_hidden(self.is_hidden(index)),
+                pos: self.positions[index.index()],
+                property: match &strains {
+                    Some(strains) => strains.get(&index).copied().unwrap_or_default(),
+                    None => self.partial_charge(index),
+                },
             })
             .collect()
     }
 
+    pub fn property(&self) -> AtomProperty {
+        self.property
+    }
+
+    /// Chooses the property that atoms carry for coloring.  The atoms are
+    /// uploaded again the next time the molecule is synchronized.
+    pub fn set_property(&mut self, property: AtomProperty) {
+        if self.property != property {
+            self.property = property;
+            self.gpu_synced = false;
+        }
+    }
+
+    pub fn hydrogen_display(&self) -> HydrogenDisplay {
+        self.hydrogens
+    }
+
+    /// Chooses which hydrogens are drawn.  Like [`Molecule::set_property`],
+    /// this takes effect the next time the molecule is synchronized.
+    pub fn set_hydrogen_display(&mut self, hydrogens: HydrogenDisplay) {
+        if self.hydrogens != hydrogens {
+            self.hydrogens = hydrogens;
+            self.gpu_synced = false;
+        }
+    }
+
+    /// Whether an atom is hidden by the molecule's [`HydrogenDisplay`].  A
+    /// hydrogen with no bonds counts as polar.
+    pub fn is_hidden(&self, index: AtomIndex) -> bool {
+        if self.graph[index].element != Element::Hydrogen {
+            return false;
+        }
+        match self.hydrogens {
+            HydrogenDisplay::ShowAll => false,
+            HydrogenDisplay::HideAll => true,
+            HydrogenDisplay::HideNonpolar => {
+                let mut neighbors = self.graph.neighbors(index).peekable();
+                neighbors.peek().is_some()
+                    && neighbors.all(|neighbor| self.graph[neighbor].element == Element::Carbon)
+            }
+        }
+    }
+
+    /// Estimates the partial charge of an atom, in elementary charges, from
+    /// the differences in electronegativity across its bonds.  This is a rough
+    /// guide to polarity rather than the result of a charge model.  Bonds to
+    /// elements whose electronegativity is not known add no charge.
+    pub fn partial_charge(&self, index: AtomIndex) -> f32 {
+        let Some(own) = self.graph[index].element.electronegativity() else {
+            return 0.0;
+        };
+        self.graph
+            .edges(index)
+            .filter_map(|edge| {
+                let other = if edge.source() == index {
+                    edge.target()
+                } else {
+                    edge.source()
+                };
+                let theirs = self.graph[other].element.electronegativity()?;
+                Some(CHARGE_PER_ELECTRONEGATIVITY * (theirs - own) * *edge.weight() as f32)
+            })
+            .sum()
+    }
+
+    /// The unit cell that the molecule repeats in, if it is periodic.
+    pub fn cell(&self) -> Option<&UnitCell> {
+        self.cell.as_ref()
+    }
+
+    /// The stereocenters that mirror edits have left in the opposite
+    /// configuration to the one they were built in.
+    pub fn inverted_stereocenters(&self) -> Vec<AtomSpecifier> {
+        self.graph
+            .node_references()
+            .filter(|(_, atom)| atom.stereo_inverted)
+            .map(|(index, _)| self.spec(index))
+            .collect()
+    }
+
     pub fn clear(&mut self) {
-        self.atom_map.clear();
+        self.atoms.clear();
         self.graph.clear();
+        self.positions.clear();
+        self.cell = None;
+        self.torsions.clear();
+        self.held.clear();
         self.bounding_box = Default::default();
         self.gpu_synced = false;
     }
 
-    pub(crate) fn relax(&mut self) {
-        self.positions = crate::dynamics::relax(&self.graph, &self.positions, 0.01);
+    pub(crate) fn relax(
+        &mut self,
+        force_field: ForceFieldKind,
+        vdw_cutoff: f32,
+        criteria: &ConvergenceCriteria,
+    ) -> RelaxReport {
+        let field = force_field.build(&self.graph, vdw_cutoff);
+        let torsions: Vec<TorsionRestraint> = self
+            .torsions
+            .iter()
+            .filter_map(|(atoms, angle)| {
+                Some(TorsionRestraint {
+                    atoms: [
+                        self.atoms.get(&atoms[0])?.index(),
+                        self.atoms.get(&atoms[1])?.index(),
+                        self.atoms.get(&atoms[2])?.index(),
+                        self.atoms.get(&atoms[3])?.index(),
+                    ],
+                    angle: angle.to_radians(),
+                    stiffness: force_field.torsion_restraint_stiffness(),
+                })
+            })
+            .collect();
+        let held: Vec<usize> = std::mem::take(&mut self.held)
+            .iter()
+            .filter_map(|atom| Some(self.atoms.get(atom)?.index()))
+            .collect();
+        let (positions, report) = if torsions.is_empty() && held.is_empty() {
+            relax_with(&*field, &self.graph, &self.positions, criteria)
+        } else {
+            let restrained = Restrained {
+                field: &*field,
+                torsions,
+                held,
+            };
+            relax_with(&restrained, &self.graph, &self.positions, criteria)
+        };
+        // The relaxed positions stop at the last atom, so any holes after it
+        // keep their entries.
+        self.positions[..positions.len()].copy_from_slice(&positions);
+        self.gpu_synced = false;
+        report
+    }
+
+    /// The position of every atom, indexed by the atom's index in the graph
+    /// (see [`AtomPositions`]).
+    pub fn dense_positions(&self) -> &[Vec3] {
+        &self.positions
     }
 
     pub fn reupload_atoms(&mut self, gpu_resources: &GlobalRenderResources) {
@@ -136,7 +508,10 @@ impl Molecule {
         // This is a workaround, but it has bad perf as it always drops and
         // reallocates
 
-        if self.graph.node_count() == 0 {
+        // Molecules over the atom limit are left undrawn rather than risk
+        // running out of GPU memory.
+        let atoms = self.graph.node_count();
+        if atoms == 0 || !gpu_resources.atom_limits().admit_molecule(atoms) {
             self.gpu_atoms = None;
         } else {
             self.gpu_atoms = Some(AtomBuffer::new(gpu_resources, self.atom_reprs()));
@@ -151,21 +526,58 @@ impl Molecule {
 
     pub fn set_checkpoint(&mut self, checkpoint: MoleculeCheckpoint) {
         self.graph = checkpoint.graph;
+        self.atoms = checkpoint.atoms;
+        self.cell = checkpoint.cell;
+        self.torsions = checkpoint.torsions;
         self.positions = checkpoint.positions;
-        self.atom_map.clear();
-
-        for (atom_index, atom) in self.graph.node_references() {
-            self.atom_map.insert(atom.spec.clone(), atom_index);
-        }
     }
 
     pub fn make_checkpoint(&self) -> MoleculeCheckpoint {
         MoleculeCheckpoint {
             graph: self.graph.clone(),
+            atoms: self.atoms.clone(),
             positions: self.positions.clone(),
+            cell: self.cell,
+            torsions: self.torsions.clone(),
         }
     }
 
+    /// The index of the atom `spec` in the molecule's graph.
+    pub fn atom_index(&self, spec: &AtomSpecifier) -> Option<AtomIndex> {
+        self.atoms.get(spec)
+    }
+
+    /// The specifier of the atom at `index` in the molecule's graph, which
+    /// edits refer to it by.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no atom at `index`, like indexing the graph does.
+    pub fn spec(&self, index: AtomIndex) -> AtomSpecifier {
+        self.atoms
+            .spec(index)
+            .expect("Every atom in the graph should have a specifier")
+    }
+
+    /// Gets a vector with the direction that an atom is "facing". Atoms "face" along one
+    /// of their bonds, or along the molecule's `+z` axis if no bonds exist.
+    pub fn forward(&self, index: AtomIndex) -> Vec3 {
+        match self.graph[index].head {
+            Some(head) => {
+                (self.positions[head.index()] - self.positions[index.index()]).normalized()
+            }
+            None => Vec3::unit_z(),
+        }
+    }
+
+    /// The index of the atom `spec` in the molecule's atom buffer, which holds
+    /// the atoms in the order of the graph.
+    pub fn buffer_index(&self, spec: &AtomSpecifier) -> Option<u32> {
+        let index = self.atom_index(spec)?;
+        let position = self.graph.node_indices().position(|other| other == index)?;
+        u32::try_from(position).ok()
+    }
+
     pub fn bounding_box(&self) -> &BoundingBox {
         &self.bounding_box
     }
@@ -199,18 +611,25 @@ impl Molecule {
         // the direction vector is normalized, so 1 unit of time = 1 unit of space
         let num_steps = (t_span / step_size) as usize;
 
-        for _ in 0..num_steps {
-            for atom in self.graph.node_weights() {
+        // The atoms that can be hit are gathered once, rather than looked up
+        // again at every step.
+        let atoms: Vec<(AtomIndex, Vec3, f32)> = self
+            .graph
+            .node_references()
+            .filter(|(index, _)| !self.is_hidden(*index))
+            .map(|(index, atom)| {
+                let atom_pos = self.positions[index.index()];
                 let atom_radius_sq = PERIODIC_TABLE.element_reprs[atom.element as usize]
                     .radius
                     .powi(2);
+                (index, atom_pos, atom_radius_sq)
+            })
+            .collect();
 
-                let atom_pos = *self
-                    .positions
-                    .get(&atom.spec)
-                    .expect("Every atom in the graph should have an associated position");
+        for _ in 0..num_steps {
+            for &(index, atom_pos, atom_radius_sq) in &atoms {
                 if (current_pos - atom_pos).mag_sq() < atom_radius_sq {
-                    return Some(atom.spec.clone());
+                    return Some(self.spec(index));
                 }
             }
 
@@ -219,6 +638,40 @@ impl Molecule {
 
         None
     }
+
+    /// Every visible atom that the ray from `origin` in `direction` passes
+    /// through, with how far along the ray it is entered, nearest first.
+    /// `direction` must be normalized.  Atoms that the ray starts inside are
+    /// entered at 0.
+    pub fn get_ray_hits(&self, origin: Vec3, direction: Vec3) -> Vec<(f32, AtomSpecifier)> {
+        let Some((_, tmax)) = self.bounding_box.ray_hit_times(origin, direction) else {
+            return Vec::new();
+        };
+        if tmax <= 0.0 {
+            return Vec::new();
+        }
+
+        let mut hits: Vec<(f32, AtomIndex)> = self
+            .graph
+            .node_references()
+            .filter(|(index, _)| !self.is_hidden(*index))
+            .filter_map(|(index, atom)| {
+                let radius = PERIODIC_TABLE.element_reprs[atom.element as usize].radius;
+                let to_atom = self.positions[index.index()] - origin;
+                // How far along the ray it passes closest to the atom's
+                // center, and how far it misses the center by there.
+                let along = to_atom.dot(direction);
+                let miss_sq = to_atom.mag_sq() - along * along;
+                let half_chord = (radius * radius - miss_sq).sqrt();
+                (miss_sq < radius * radius && along + half_chord > 0.0)
+                    .then(|| ((along - half_chord).max(0.0), index))
+            })
+            .collect();
+        hits.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        hits.into_iter()
+            .map(|(along, index)| (along, self.spec(index)))
+            .collect()
+    }
 }
 
 impl EditContext for Molecule {
@@ -241,24 +694,35 @@ impl EditContext for Molecule {
         spec: AtomSpecifier,
         head: Option<AtomSpecifier>,
     ) -> Result<(), EditError> {
-        if self.atom_map.contains_key(&spec) {
+        if self.atoms.contains(&spec) {
             return Err(EditError::AtomOverwrite);
         }
+        let head = head.and_then(|head| self.atoms.get(&head));
 
         let index = self.graph.add_node(AtomNode {
             element,
-            spec: spec.clone(),
             head,
+            pinned: false,
+            chain: None,
+            residue: None,
+            source_line: None,
+            stereo_inverted: false,
         });
 
-        self.atom_map.insert(spec.clone(), index);
+        self.atoms.insert(&spec, index);
         self.bounding_box.enclose_sphere(
             pos,
             // TODO: This is
             PERIODIC_TABLE.element_reprs[element as usize].radius,
         );
         self.gpu_synced = false;
-        self.positions.insert(spec, pos);
+        // The graph fills the holes left by removed atoms before adding to the
+        // end, so the atom's entry may already exist.
+        let slot = index.index();
+        if self.positions.len() <= slot {
+            self.positions.resize(slot + 1, Vec3::zero());
+        }
+        self.positions[slot] = pos;
 
         Ok(())
     }
@@ -269,23 +733,184 @@ impl EditContext for Molecule {
         a2: &AtomSpecifier,
         order: BondOrder,
     ) -> Result<(), EditError> {
-        match (self.atom_map.get(a1), self.atom_map.get(a2)) {
-            (Some(&a1_index), Some(&a2_index)) => {
-                self.graph.add_edge(a1_index, a2_index, order);
+        match (self.atoms.get(a1), self.atoms.get(a2)) {
+            (Some(a1_index), Some(a2_index)) => {
+                // Bonding atoms that are already bonded changes the order of
+                // their bond rather than adding a second one.
+                self.graph.update_edge(a1_index, a2_index, order);
                 Ok(())
             }
             _ => Err(EditError::BrokenReference(ReferenceType::Atom)),
         }
     }
 
-    fn find_atom(&self, spec: &AtomSpecifier) -> Option<&AtomNode> {
-        match self.atom_map.get(spec) {
-            Some(atom_index) => self.graph.node_weight(*atom_index),
-            None => None,
+    fn set_pinned(&mut self, spec: &AtomSpecifier, pinned: bool) -> Result<(), EditError> {
+        let index = self
+            .atoms
+            .get(spec)
+            .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+        self.graph[index].pinned = pinned;
+        self.gpu_synced = false;
+        Ok(())
+    }
+
+    fn set_chain(&mut self, spec: &AtomSpecifier, chain: Option<char>) -> Result<(), EditError> {
+        let index = self
+            .atoms
+            .get(spec)
+            .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+        self.graph[index].chain = chain;
+        self.gpu_synced = false;
+        Ok(())
+    }
+
+    fn set_residue(
+        &mut self,
+        spec: &AtomSpecifier,
+        residue: Option<ResidueId>,
+    ) -> Result<(), EditError> {
+        let index = self
+            .atoms
+            .get(spec)
+            .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+        self.graph[index].residue = residue;
+        Ok(())
+    }
+
+    fn set_source_line(
+        &mut self,
+        spec: &AtomSpecifier,
+        line: Option<usize>,
+    ) -> Result<(), EditError> {
+        let index = self
+            .atoms
+            .get(spec)
+            .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+        self.graph[index].source_line = line;
+        Ok(())
+    }
+
+    fn set_element(&mut self, spec: &AtomSpecifier, element: Element) -> Result<(), EditError> {
+        let index = self
+            .atoms
+            .get(spec)
+            .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+        self.graph[index].element = element;
+        self.gpu_synced = false;
+        Ok(())
+    }
+
+    fn set_stereo_inverted(
+        &mut self,
+        spec: &AtomSpecifier,
+        inverted: bool,
+    ) -> Result<(), EditError> {
+        let index = self
+            .atoms
+            .get(spec)
+            .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+        self.graph[index].stereo_inverted = inverted;
+        Ok(())
+    }
+
+    fn remove_atoms(&mut self, specs: &[AtomSpecifier]) -> Result<(), EditError> {
+        for spec in specs {
+            let index = self
+                .atoms
+                .remove(spec)
+                .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+            self.graph.remove_node(index);
+            // The graph reuses the indices of removed atoms, so atoms facing
+            // this one would come to face whichever atom is added next.
+            for atom in self.graph.node_weights_mut() {
+                if atom.head == Some(index) {
+                    atom.head = None;
+                }
+            }
+        }
+        self.torsions
+            .retain(|(atoms, _)| atoms.iter().all(|atom| self.atoms.contains(atom)));
+
+        // The bounding box only ever grows as atoms are added, so it is
+        // measured again around the atoms that are left.
+        self.bounding_box = Default::default();
+        for (index, atom) in self.graph.node_references() {
+            self.bounding_box.enclose_sphere(
+                self.positions[index.index()],
+                PERIODIC_TABLE.element_reprs[atom.element as usize].radius,
+            );
         }
+        self.gpu_synced = false;
+        Ok(())
+    }
+
+    fn set_pos(&mut self, spec: &AtomSpecifier, pos: Vec3) -> Result<(), EditError> {
+        let index = self
+            .atoms
+            .get(spec)
+            .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+        let element = self.graph[index].element;
+        self.bounding_box
+            .enclose_sphere(pos, PERIODIC_TABLE.element_reprs[element as usize].radius);
+        self.gpu_synced = false;
+        self.positions[index.index()] = pos;
+        Ok(())
+    }
+
+    fn set_cell(&mut self, cell: Option<UnitCell>) {
+        self.cell = cell;
+    }
+
+    fn restrain_torsion(&mut self, atoms: &[AtomSpecifier; 4], angle: f32) {
+        // A bond has one torsion, however it is named, so a new one replaces
+        // any set before about the same bond.
+        self.torsions.retain(|(other, _)| {
+            !(other[1] == atoms[1] && other[2] == atoms[2]
+                || other[1] == atoms[2] && other[2] == atoms[1])
+        });
+        self.torsions.push((atoms.clone(), angle));
+    }
+
+    fn hold_for_relaxation(&mut self, specs: &[AtomSpecifier]) {
+        self.held.extend_from_slice(specs);
+    }
+
+    fn find_atom(&self, spec: &AtomSpecifier) -> Option<&AtomNode> {
+        self.graph.node_weight(self.atoms.get(spec)?)
+    }
+
+    fn head(&self, spec: &AtomSpecifier) -> Option<AtomSpecifier> {
+        let head = self.find_atom(spec)?.head?;
+        Some(self.spec(head))
     }
 
     fn pos(&self, spec: &AtomSpecifier) -> Option<&Vec3> {
-        self.positions.get(spec)
+        let index = self.atoms.get(spec)?;
+        self.positions.get(index.index())
+    }
+
+    fn atoms(&self) -> Vec<AtomSpecifier> {
+        self.graph
+            .node_indices()
+            .map(|index| self.spec(index))
+            .collect()
+    }
+
+    fn bonds(&self, spec: &AtomSpecifier) -> Vec<(AtomSpecifier, BondOrder)> {
+        let Some(index) = self.atoms.get(spec) else {
+            return Vec::new();
+        };
+
+        self.graph
+            .edges(index)
+            .map(|edge| {
+                let other = if edge.source() == index {
+                    edge.target()
+                } else {
+                    edge.source()
+                };
+                (self.spec(other), *edge.weight())
+            })
+            .collect()
     }
 }
@@ -2,18 +2,35 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
+use common::{
+    ids::{AtomSpecifier, EditId},
+    Metadata,
+};
+use instant::Instant;
 use serde::{Deserialize, Serialize};
 
-use crate::edit::{Edit, EditList};
+use crate::checkpoint_store::{CheckpointPolicy, CheckpointStore};
+use crate::composite::{AnchorCountError, CombineError, Composite};
+use crate::dynamics::{ConvergenceCriteria, ForceFieldKind, RelaxReport, DEFAULT_VDW_CUTOFF};
+use crate::edit::{Dependency, Edit, EditContext, EditDiagnostic, EditList, FolderId, Pin};
 use crate::molecule::{Molecule, MoleculeCheckpoint};
+use crate::parts;
+use crate::reindex::{self, ReindexError};
+use crate::selection::NamedSelection;
+use crate::symmetry;
+use crate::AtomProperty;
+
+/// How many bulk changes to the edit list can be undone.
+const MAX_UNDO_STEPS: usize = 32;
 
 pub struct MoleculeEditor {
     pub repr: Molecule,
-    #[allow(unused)]
+    metadata: Metadata,
+    // Where the molecule is placed within its component: its atoms are turned
+    // by `rotation` about the origin, then moved by `offset`.
     rotation: ultraviolet::Rotor3,
-    #[allow(unused)]
     offset: ultraviolet::Vec3,
     edits: EditList,
     // The index one greater than the most recently applied feature's location in the feature list.
@@ -24,44 +41,765 @@ pub struct MoleculeEditor {
     // `checkpoints.get(i)` contains the graph and geometry which should be used to render
     // the molecule. This allows feature application and relaxation to be cached until they
     // need to be recomputed. This saves a lot of time, as relaxation is a very expensive operation that does not
-    // commute with feature application.
-    checkpoints: HashMap<usize, MoleculeCheckpoint>,
+    // commute with feature application.  Only the most recently used checkpoints
+    // are kept in memory; the rest are spilled to disk.
+    checkpoints: CheckpointStore,
+    // When checkpoints are recorded while edits are applied.
+    checkpoint_policy: CheckpointPolicy,
     // the history step we cannot equal or exceed without first recomputing. For example, if repr
     // is up to date with the feature list, and then a past feature is changed, dirty_step would change
     // from `features.len()` to the index of the changed feature. This is used to determine if recomputation
     // is needed when moving forwards in the timeline, or if a future checkpoint can be used.
     dirty_step: usize,
+    // The force field used to relax the molecule after each edit.
+    force_field: ForceFieldKind,
+    // The distance beyond which the force field ignores van der Waals
+    // interactions, in angstroms.
+    vdw_cutoff: f32,
+    // When relaxation after each edit stops.
+    convergence: ConvergenceCriteria,
+    // The outcome of the most recent relaxation, or of the first one that did
+    // not converge during the most recent call to `set_history_step`.
+    relax_report: Option<RelaxReport>,
+    timings: EditorTimings,
+    // The edit list as it was before each recent bulk change, most recent last.
+    // These are not persisted.
+    undo_steps: Vec<UndoStep>,
+    // The edit list as it was before each change that was undone since the
+    // last bulk change, most recently undone last.
+    redo_steps: Vec<UndoStep>,
+    // How many bulk changes have been made, not counting undoing or redoing
+    // them.
+    revision: u64,
+    // Whether `add_edit` puts edits into the asymmetric unit of the latest
+    // symmetry copy, so that they are replicated to every symmetry mate.
+    propagate_symmetry: bool,
+    // Sets of atoms saved under names, in the order they were saved.
+    named_selections: Vec<NamedSelection>,
+    // Why edits failed, in the order they were applied.  These are not
+    // persisted, so edits before a checkpoint loaded from a file have none.
+    diagnostics: Vec<EditDiagnostic>,
+}
+
+// What is needed to undo one bulk change to the edit list.
+#[derive(Clone)]
+struct UndoStep {
+    edits: EditList,
+    history_step: usize,
+    // Renumbering the atoms changes the named selections too.
+    named_selections: Vec<NamedSelection>,
+    // The position of the first edit that the change affected.  Checkpoints
+    // before it are valid both before and after the change.
+    first_changed: usize,
+}
+
+/// How long the most recent expensive operations on a `MoleculeEditor` took.
+/// These are not persisted; they exist so that performance can be monitored
+/// while editing.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EditorTimings {
+    /// The total time spent in the most recent call to `set_history_step`,
+    /// including relaxation.
+    pub last_reconstruction: Option<Duration>,
+    /// The time spent relaxing the geometry during the most recent call to
+    /// `set_history_step`.
+    pub last_relax: Option<Duration>,
 }
 
 impl MoleculeEditor {
     pub fn from_feature(edit: Edit) -> Self {
+        let metadata = Metadata::new(match edit {
+            Edit::PdbImport(ref data) => data.name.as_str(),
+            Edit::AtomImport(ref data) => data.name.as_str(),
+            Edit::ZMatrixImport(ref data) => data.name.as_str(),
+            Edit::Lattice(ref lattice) => lattice.kind.name(),
+            Edit::Polymer(_) => "Polymer",
+            _ => "Molecule",
+        });
+
         let mut repr = Molecule::default();
         edit.apply(&0, &mut repr)
             .expect("Primitive features should never return a feature error!");
         // Relaxation is currently causing infinte loops on loaded PDB files.
         // Disabled until the code matures a bit.
         //repr.relax();
+        // Built polymers only have idealised geometry, though, and are always
+        // relaxed.
+        let force_field = ForceFieldKind::default();
+        let convergence = force_field.default_convergence();
+        let relax_report = matches!(edit, Edit::Polymer(_))
+            .then(|| repr.relax(force_field, DEFAULT_VDW_CUTOFF, &convergence));
 
         let mut features = EditList::default();
         features.push_back(edit);
 
         Self {
             repr,
+            metadata,
             rotation: ultraviolet::Rotor3::default(),
             offset: ultraviolet::Vec3::default(),
             edits: features,
             history_step: 1, // This starts at 1 because we applied the primitive feature
             checkpoints: Default::default(),
+            checkpoint_policy: CheckpointPolicy::default(),
             dirty_step: 1, // Although no checkpoints exist, repr is not dirty, so we advance this to its max
+            force_field,
+            vdw_cutoff: DEFAULT_VDW_CUTOFF,
+            convergence,
+            relax_report,
+            timings: EditorTimings::default(),
+            undo_steps: Vec::new(),
+            redo_steps: Vec::new(),
+            revision: 0,
+            propagate_symmetry: false,
+            named_selections: Vec::new(),
+            diagnostics: Vec::new(),
         }
     }
 
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
+
+    pub fn name(&self) -> &str {
+        self.metadata.name()
+    }
+
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.metadata.set_name(name);
+    }
+
     pub fn edits(&self) -> &EditList {
         &self.edits
     }
 
+    /// How the molecule is turned within its component, about the origin of
+    /// its own space.
+    pub fn rotation(&self) -> ultraviolet::Rotor3 {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: ultraviolet::Rotor3) {
+        self.rotation = rotation.normalized();
+    }
+
+    /// How far the molecule is moved within its component, after it is
+    /// turned.
+    pub fn offset(&self) -> ultraviolet::Vec3 {
+        self.offset
+    }
+
+    pub fn set_offset(&mut self, offset: ultraviolet::Vec3) {
+        self.offset = offset;
+    }
+
+    /// The transform from the molecule's own space, in which its atoms are
+    /// built, to its component's: the rotation, then the offset.
+    pub fn local_transform(&self) -> ultraviolet::Mat4 {
+        ultraviolet::Mat4::from_translation(self.offset)
+            * self.rotation.into_matrix().into_homogeneous()
+    }
+
+    pub fn history_step(&self) -> usize {
+        self.history_step
+    }
+
+    /// Why edits failed, for those up to the current history step that did,
+    /// in history order.  An edit that fails may have been partly applied,
+    /// and the edits after it are still applied.
+    pub fn diagnostics(&self) -> impl Iterator<Item = &EditDiagnostic> {
+        self.diagnostics.iter().filter(|diagnostic| {
+            self.edits
+                .position(diagnostic.edit)
+                .is_some_and(|position| position < self.history_step)
+        })
+    }
+
+    /// Why the edit `id` failed, if it did the last time it was applied.
+    pub fn diagnostic(&self, id: EditId) -> Option<&EditDiagnostic> {
+        self.diagnostics().find(|diagnostic| diagnostic.edit == id)
+    }
+
+    pub fn checkpoint_count(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    /// The history steps that have a cached checkpoint, in ascending order.
+    pub fn checkpoint_steps(&self) -> Vec<usize> {
+        let mut steps: Vec<usize> = self.checkpoints.steps().collect();
+        steps.sort_unstable();
+        steps
+    }
+
+    pub fn checkpoint_policy(&self) -> &CheckpointPolicy {
+        &self.checkpoint_policy
+    }
+
+    /// Changes when checkpoints are recorded.  This only affects edits applied
+    /// from now on; cached checkpoints are kept.
+    pub fn set_checkpoint_policy(&mut self, policy: CheckpointPolicy) {
+        self.checkpoint_policy = policy;
+    }
+
+    pub fn timings(&self) -> &EditorTimings {
+        &self.timings
+    }
+
+    pub fn force_field(&self) -> ForceFieldKind {
+        self.force_field
+    }
+
+    /// Changes the force field used for relaxation, and resets the convergence
+    /// criteria to the new force field's defaults.  Every cached checkpoint was
+    /// relaxed with the old force field, so the molecule is rebuilt from its
+    /// first edit.
+    pub fn set_force_field(&mut self, force_field: ForceFieldKind) {
+        if self.force_field == force_field {
+            return;
+        }
+
+        self.force_field = force_field;
+        self.convergence = force_field.default_convergence();
+        if let AtomProperty::BondStrain(_) = self.repr.property() {
+            self.repr
+                .set_property(AtomProperty::BondStrain(force_field));
+        }
+        self.checkpoints.clear();
+        let history_step = self.history_step;
+        self.history_step = 0;
+        self.repr.clear();
+        self.set_history_step(history_step);
+    }
+
+    /// The distance beyond which van der Waals interactions are ignored during
+    /// relaxation, in angstroms.
+    pub fn vdw_cutoff(&self) -> f32 {
+        self.vdw_cutoff
+    }
+
+    /// Changes the van der Waals cutoff used for relaxation.  A shorter cutoff
+    /// relaxes large molecules faster, at the cost of ignoring more of the
+    /// attraction between their distant parts.  As with a new force field,
+    /// the molecule is rebuilt from its first edit.
+    pub fn set_vdw_cutoff(&mut self, vdw_cutoff: f32) {
+        if self.vdw_cutoff == vdw_cutoff {
+            return;
+        }
+
+        self.vdw_cutoff = vdw_cutoff;
+        self.checkpoints.clear();
+        let history_step = self.history_step;
+        self.history_step = 0;
+        self.repr.clear();
+        self.set_history_step(history_step);
+    }
+
+    pub fn convergence(&self) -> &ConvergenceCriteria {
+        &self.convergence
+    }
+
+    /// Changes when relaxation stops.  This only affects edits applied from now
+    /// on; cached checkpoints are kept.
+    pub fn set_convergence(&mut self, convergence: ConvergenceCriteria) {
+        self.convergence = convergence;
+    }
+
+    /// How the most recent relaxation went.  If several edits were applied at
+    /// once, this is the first relaxation that did not converge, if any.
+    pub fn relax_report(&self) -> Option<&RelaxReport> {
+        self.relax_report.as_ref()
+    }
+
+    /// Creates an independent copy of this molecule, including its cached
+    /// checkpoints, and moves the copy to `history_step`.  GPU buffers are not
+    /// shared; the copy's are created when it is next synchronized.
+    pub fn fork_at(&self, history_step: usize) -> Self {
+        let mut repr = Molecule::default();
+        repr.set_checkpoint(self.repr.make_checkpoint());
+
+        let mut fork = Self {
+            repr,
+            metadata: self.metadata.clone(),
+            rotation: self.rotation,
+            offset: self.offset,
+            edits: self.edits.clone(),
+            history_step: self.history_step,
+            checkpoints: self.checkpoints.clone(),
+            checkpoint_policy: self.checkpoint_policy,
+            dirty_step: self.dirty_step,
+            force_field: self.force_field,
+            vdw_cutoff: self.vdw_cutoff,
+            convergence: self.convergence,
+            relax_report: self.relax_report,
+            timings: EditorTimings::default(),
+            undo_steps: Vec::new(),
+            redo_steps: Vec::new(),
+            revision: 0,
+            propagate_symmetry: false,
+            named_selections: self.named_selections.clone(),
+            diagnostics: self.diagnostics.clone(),
+        };
+        fork.set_history_step(history_step);
+        fork
+    }
+
+    /// The sets of atoms saved with the molecule under names, in the order
+    /// they were saved.  They may refer to atoms that do not exist at the
+    /// current history step.
+    pub fn named_selections(&self) -> &[NamedSelection] {
+        &self.named_selections
+    }
+
+    pub fn named_selection(&self, name: &str) -> Option<&NamedSelection> {
+        self.named_selections
+            .iter()
+            .find(|selection| selection.name == name)
+    }
+
+    /// Saves `atoms` under `name`, replacing any set already saved with that
+    /// name.  This does not change the molecule, so it is not an undo step.
+    pub fn save_named_selection(
+        &mut self,
+        name: impl Into<String>,
+        atoms: impl IntoIterator<Item = AtomSpecifier>,
+    ) {
+        let selection = NamedSelection::new(name, atoms);
+        match self
+            .named_selections
+            .iter_mut()
+            .find(|saved| saved.name == selection.name)
+        {
+            Some(saved) => *saved = selection,
+            None => self.named_selections.push(selection),
+        }
+    }
+
+    pub fn remove_named_selection(&mut self, name: &str) -> Option<NamedSelection> {
+        let index = self
+            .named_selections
+            .iter()
+            .position(|selection| selection.name == name)?;
+        Some(self.named_selections.remove(index))
+    }
+
+    /// Inserts `edit` at the history step, without applying it or keeping an
+    /// undo step, as when building a molecule up from a script.
     pub fn insert_edit(&mut self, edit: Edit) {
         self.edits.insert(edit, self.history_step);
+        // Checkpoints after the insertion point were taken without the edit.
+        let history_step = self.history_step;
+        self.checkpoints.retain(|step| step <= history_step);
+    }
+
+    pub fn propagates_symmetry(&self) -> bool {
+        self.propagate_symmetry
+    }
+
+    /// Turns symmetry propagation on or off for `add_edit`.
+    pub fn set_propagate_symmetry(&mut self, propagate: bool) {
+        self.propagate_symmetry = propagate;
+    }
+
+    /// Inserts `edit` at the history step and applies it.
+    ///
+    /// With symmetry propagation on, and a symmetry copy before the history
+    /// step, the edit instead goes into that copy's asymmetric unit, just
+    /// before it, so that it is replicated to every symmetry mate.  References
+    /// to atoms of a mate are redirected to the atoms of the asymmetric unit
+    /// they were copied from.  Edits that refer to atoms made after the
+    /// symmetry copy cannot be moved before it, and are inserted as usual.
+    /// Returns whether the edit was propagated.  Either way, this is a single
+    /// undo step.
+    pub fn add_edit(&mut self, edit: Edit) -> bool {
+        let symmetry = self
+            .propagate_symmetry
+            .then(|| {
+                self.edits.order()[..self.history_step]
+                    .iter()
+                    .rposition(|id| matches!(self.edits.get(id), Some(Edit::SymmetryCopy(_))))
+            })
+            .flatten();
+        let Some(position) = symmetry else {
+            self.push_undo_step(self.history_step);
+            self.insert_edit(edit);
+            self.set_history_step(self.history_step + 1);
+            return false;
+        };
+
+        let symmetry_id = self.edits.order()[position];
+        let mut propagated = edit.clone();
+        for atom in propagated.references_mut() {
+            *atom = symmetry::asymmetric_unit_atom(atom, symmetry_id);
+        }
+        let in_unit = propagated.references_mut().iter().all(|atom| {
+            atom.path.iter().all(|instance| {
+                self.edits
+                    .position(instance.owner_id)
+                    .is_some_and(|index| index < position)
+            })
+        });
+        if !in_unit {
+            self.push_undo_step(self.history_step);
+            self.insert_edit(edit);
+            self.set_history_step(self.history_step + 1);
+            return false;
+        }
+
+        self.push_undo_step(position);
+        self.edits.insert(propagated, position);
+        self.rebuild_from(position, self.history_step + 1);
+        true
+    }
+
+    /// Suppresses the edits `ids`, so that the molecule is built as if they
+    /// were not there, or restores them.  This is a single undo step.
+    pub fn suppress_edits(&mut self, ids: &[EditId], suppressed: bool) {
+        let history_step = self.history_step;
+        self.change_edits(ids, history_step, |edits| {
+            for id in ids {
+                edits.set_suppressed(*id, suppressed);
+            }
+        });
+    }
+
+    /// Deletes the edits `ids`.  This is a single undo step.
+    ///
+    /// Later edits that refer to atoms they created would fail to apply, so
+    /// unless `force` is set, nothing is deleted if there are any, and they are
+    /// returned instead.
+    pub fn remove_edits(&mut self, ids: &[EditId], force: bool) -> Result<(), Vec<Dependency>> {
+        let dependents = self.edits.dependents(ids);
+        if !force && !dependents.is_empty() {
+            return Err(dependents);
+        }
+        let removed_before = self.edits.order()[..self.history_step]
+            .iter()
+            .filter(|id| ids.contains(*id))
+            .count();
+        let history_step = self.history_step - removed_before;
+        self.change_edits(ids, history_step, |edits| {
+            for id in ids {
+                edits.remove(*id);
+            }
+        });
+        Ok(())
+    }
+
+    /// Changes the parameters of the edit `id` to those of `edit`, keeping its
+    /// place in the history.  This is a single undo step.
+    ///
+    /// Later edits that refer to atoms it created might no longer find them,
+    /// so unless `force` is set, nothing is changed if there are any, and they
+    /// are returned instead.
+    pub fn replace_edit(
+        &mut self,
+        id: EditId,
+        edit: Edit,
+        force: bool,
+    ) -> Result<(), Vec<Dependency>> {
+        let dependents = self.edits.dependents(&[id]);
+        if !force && !dependents.is_empty() {
+            return Err(dependents);
+        }
+        let history_step = self.history_step;
+        self.change_edits(&[id], history_step, |edits| {
+            edits.replace(id, edit);
+        });
+        Ok(())
+    }
+
+    /// Moves the edit `id` to `position` in the history (see
+    /// [`EditList::move_to`]).  The history step stays where it is, so the edit
+    /// may go from being applied to not, or the other way.  This is a single
+    /// undo step.
+    ///
+    /// Unless `force` is set, nothing is moved if an edit would then come
+    /// before one that created atoms it refers to, and those dependencies are
+    /// returned instead.
+    pub fn move_edit(
+        &mut self,
+        id: EditId,
+        position: usize,
+        force: bool,
+    ) -> Result<(), Vec<Dependency>> {
+        let Some(old) = self.edits.position(id) else {
+            return Ok(());
+        };
+        let position = position.min(self.edits.len() - 1);
+        if position == old {
+            return Ok(());
+        }
+        let conflicts = self.edits.move_conflicts(id, position);
+        if !force && !conflicts.is_empty() {
+            return Err(conflicts);
+        }
+        let first_changed = old.min(position);
+        self.push_undo_step(first_changed);
+        self.edits.move_to(id, position);
+        self.rebuild_from(first_changed, self.history_step);
+        Ok(())
+    }
+
+    /// Puts the edits `ids` into a new folder named `name` (see
+    /// [`EditList::group`]).  This is a single undo step.
+    pub fn group_edits(&mut self, ids: &[EditId], name: String) -> Option<FolderId> {
+        if ids.iter().all(|id| self.edits.position(*id).is_none()) {
+            return None;
+        }
+        // Folders do not affect the molecule, so nothing has to be rebuilt.
+        self.push_undo_step(self.edits.len());
+        Some(self.edits.group(ids, name))
+    }
+
+    /// Removes the folder `folder`, leaving its contents in the folder it was
+    /// in.  This is a single undo step.
+    pub fn ungroup_edits(&mut self, folder: FolderId) {
+        let ids = self.edits.folder_edits(folder);
+        if ids.is_empty() {
+            self.push_undo_step(self.edits.len());
+            self.edits.ungroup(folder);
+            return;
+        }
+        let history_step = self.history_step;
+        // If the folder was suppressed, its edits are no longer.
+        self.change_edits(&ids, history_step, |edits| edits.ungroup(folder));
+    }
+
+    /// Suppresses everything in the folder `folder`, or restores it.  This is
+    /// a single undo step.
+    pub fn suppress_folder(&mut self, folder: FolderId, suppressed: bool) {
+        let ids = self.edits.folder_edits(folder);
+        let history_step = self.history_step;
+        self.change_edits(&ids, history_step, |edits| {
+            edits.set_folder_suppressed(folder, suppressed)
+        });
+    }
+
+    /// Replaces the edits `ids` with a single composite edit named `name` (see
+    /// [`EditList::combine`]).  If only some of them were applied, the
+    /// composite is not.  This is a single undo step.
+    pub fn combine_edits(&mut self, ids: &[EditId], name: String) -> Result<EditId, CombineError> {
+        let first_changed = ids.iter().filter_map(|id| self.edits.position(*id)).min();
+        let applied = self.edits.order()[..self.history_step]
+            .iter()
+            .filter(|id| ids.contains(*id))
+            .count();
+        let mut edits = self.edits.clone();
+        let id = edits.combine(ids, name)?;
+
+        let history_step = match self.history_step - applied {
+            step if applied == ids.len() => step + 1,
+            step => step,
+        };
+        let first_changed = first_changed.unwrap_or_default();
+        self.push_undo_step(first_changed);
+        self.edits = edits;
+        self.rebuild_from(first_changed, history_step);
+        Ok(id)
+    }
+
+    /// Replaces the composite edit `id` with its steps (see
+    /// [`EditList::split`]).  Returns how many steps it had, or `None` if it
+    /// is not a composite.  This is a single undo step.
+    pub fn split_composite(&mut self, id: EditId) -> Option<usize> {
+        let position = self.edits.position(id)?;
+        let mut edits = self.edits.clone();
+        let steps = edits.split(id)?.len();
+
+        let history_step = if position < self.history_step {
+            self.history_step + steps - 1
+        } else {
+            self.history_step
+        };
+        self.push_undo_step(position);
+        self.edits = edits;
+        self.rebuild_from(position, history_step);
+        Some(steps)
+    }
+
+    /// Inserts a copy of the composite `template`, e.g. one saved to a
+    /// library, applied to `targets` in place of its anchors (see
+    /// [`Composite::instantiate`]), and applies it as [`add_edit`] does.
+    ///
+    /// [`add_edit`]: MoleculeEditor::add_edit
+    pub fn add_composite(
+        &mut self,
+        template: &Composite,
+        targets: &[AtomSpecifier],
+    ) -> Result<(), AnchorCountError> {
+        let composite = template.instantiate(targets, self.edits.next_id())?;
+        self.edits.reserve_ids(composite.steps.len());
+        self.add_edit(Edit::Composite(composite));
+        Ok(())
+    }
+
+    /// Hides or shows the steps of the composite edit `id` in the edit
+    /// history.  This does not change the molecule, so it is not an undo step.
+    pub fn set_composite_collapsed(&mut self, id: EditId, collapsed: bool) {
+        self.edits.set_composite_collapsed(id, collapsed);
+    }
+
+    /// Hides or shows the contents of the folder `folder` in the edit history.
+    /// This does not change the molecule, so it is not an undo step.
+    pub fn set_folder_collapsed(&mut self, folder: FolderId, collapsed: bool) {
+        self.edits.set_folder_collapsed(folder, collapsed);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_steps.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_steps.is_empty()
+    }
+
+    /// Reverts the most recent bulk change to the edit list.  Returns whether
+    /// there was one.
+    pub fn undo(&mut self) -> bool {
+        let Some(step) = self.undo_steps.pop() else {
+            return false;
+        };
+        let redo = self.undo_step(step.first_changed);
+        self.redo_steps.push(redo);
+        self.restore_step(step);
+        true
+    }
+
+    /// Makes again the bulk change to the edit list that was most recently
+    /// undone.  Returns whether there was one.  Any new bulk change forgets
+    /// the changes that were undone before it.
+    pub fn redo(&mut self) -> bool {
+        let Some(step) = self.redo_steps.pop() else {
+            return false;
+        };
+        let undo = self.undo_step(step.first_changed);
+        self.undo_steps.push(undo);
+        self.restore_step(step);
+        true
+    }
+
+    /// Counts the changes made to the edit list, one per undo step, so that a
+    /// caller can tell when there has been a new one.  Undoing and redoing do not count.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Creates an edit that reproduces the atoms created by the edits `ids`,
+    /// as they are at the current history step, so that they can be reused
+    /// elsewhere as a fragment.
+    pub fn fragment(&self, ids: &[EditId], name: &str) -> Edit {
+        let atoms: Vec<_> = EditContext::atoms(&self.repr)
+            .into_iter()
+            .filter(|atom| ids.contains(&self.edits.top_level(atom.edit_id())))
+            .collect();
+        parts::extract_atoms(&self.repr, &atoms, name)
+    }
+
+    /// Replaces the edit history with a single import of the molecule as built
+    /// by every edit, with the atoms numbered breadth-first from `root` (see
+    /// [`reindex`]).  Pinned atoms stay pinned, but PDB chains are not kept.
+    /// This is a single undo step.
+    ///
+    /// Returns the new specifier of each atom, by its old one, so that
+    /// references to the atoms from outside the molecule can be rewritten.
+    pub fn reindex(
+        &mut self,
+        root: Option<&AtomSpecifier>,
+    ) -> Result<HashMap<AtomSpecifier, AtomSpecifier>, ReindexError> {
+        self.apply_all_edits();
+        if self.repr.cell().is_some() {
+            return Err(ReindexError::Periodic);
+        }
+        let order = reindex::breadth_first(&self.repr, root)?;
+
+        let mut edits = EditList::default();
+        edits.push_back(parts::extract_atoms(&self.repr, &order, self.name()));
+        let mut spec = AtomSpecifier::new(edits.order()[0]);
+        let mapping: HashMap<AtomSpecifier, AtomSpecifier> = order
+            .iter()
+            .map(|atom| (atom.clone(), spec.next_spec()))
+            .collect();
+        let pinned: Vec<AtomSpecifier> = order
+            .iter()
+            .filter(|atom| self.repr.find_atom(atom).is_some_and(|node| node.pinned))
+            .map(|atom| mapping[atom].clone())
+            .collect();
+        if !pinned.is_empty() {
+            edits.push_back(Edit::Pin(Pin {
+                targets: pinned,
+                pinned: true,
+            }));
+        }
+
+        self.push_undo_step(0);
+        self.edits = edits;
+        // Atoms that no longer exist are left out of the named selections.
+        for selection in &mut self.named_selections {
+            *selection = NamedSelection::new(
+                std::mem::take(&mut selection.name),
+                selection
+                    .atoms
+                    .iter()
+                    .filter_map(|atom| mapping.get(atom).cloned()),
+            );
+        }
+        self.rebuild_from(0, self.edits.len());
+        Ok(mapping)
+    }
+
+    // Applies `change` to the edit list as one undo step, then rebuilds the
+    // molecule from the first edit in `ids` and moves it to `history_step`.
+    fn change_edits(
+        &mut self,
+        ids: &[EditId],
+        history_step: usize,
+        change: impl FnOnce(&mut EditList),
+    ) {
+        let Some(first_changed) = ids.iter().filter_map(|id| self.edits.position(*id)).min() else {
+            return;
+        };
+        self.push_undo_step(first_changed);
+        change(&mut self.edits);
+        self.rebuild_from(first_changed, history_step);
+    }
+
+    fn push_undo_step(&mut self, first_changed: usize) {
+        if self.undo_steps.len() == MAX_UNDO_STEPS {
+            self.undo_steps.remove(0);
+        }
+        let step = self.undo_step(first_changed);
+        self.undo_steps.push(step);
+        self.redo_steps.clear();
+        self.revision += 1;
+    }
+
+    // What is needed to return to the edit list as it is now.
+    fn undo_step(&self, first_changed: usize) -> UndoStep {
+        UndoStep {
+            edits: self.edits.clone(),
+            history_step: self.history_step,
+            named_selections: self.named_selections.clone(),
+            first_changed,
+        }
+    }
+
+    fn restore_step(&mut self, step: UndoStep) {
+        self.edits = step.edits;
+        self.named_selections = step.named_selections;
+        self.rebuild_from(step.first_changed, step.history_step);
+    }
+
+    // Drops the checkpoints that depend on the edit at `first_changed` or any
+    // after it, and rebuilds the molecule up to `history_step`.
+    fn rebuild_from(&mut self, first_changed: usize, history_step: usize) {
+        self.checkpoints.retain(|step| step <= first_changed);
+        self.history_step = 0;
+        self.repr.clear();
+        self.set_history_step(history_step.min(self.edits.len()));
     }
 
     // Advances the model to a given history step by applying features in the timeline.
@@ -74,12 +812,26 @@ impl MoleculeEditor {
             "history step exceeds edit list size"
         );
 
+        let reconstruction_start = Instant::now();
+        let mut relax_time = Duration::ZERO;
+        let mut relax_report: Option<RelaxReport> = None;
+
         // Find the best checkpoint to start reconstructing from:
-        let best_checkpoint = self
-            .checkpoints
-            .keys()
-            .filter(|candidate| **candidate <= history_step)
-            .max();
+        // A spilled checkpoint that can no longer be read is dropped by the
+        // store, in which case the next best one is tried.
+        let best_checkpoint = loop {
+            let Some(step) = self
+                .checkpoints
+                .steps()
+                .filter(|candidate| *candidate <= history_step)
+                .max()
+            else {
+                break None;
+            };
+            if let Some(checkpoint) = self.checkpoints.get(step) {
+                break Some((step, checkpoint));
+            }
+        };
 
         match best_checkpoint {
             None => {
@@ -92,32 +844,92 @@ impl MoleculeEditor {
                     self.repr.clear();
                 }
             }
-            Some(best_checkpoint) => {
+            Some((step, checkpoint)) => {
                 // If there was, we can go there and resume from that point
-                self.repr
-                    .set_checkpoint(self.checkpoints.get(best_checkpoint).unwrap().clone());
-                self.history_step = *best_checkpoint;
+                self.repr.set_checkpoint(checkpoint);
+                self.history_step = step;
             }
         }
 
-        for edit_id in &self.edits.order()[self.history_step..history_step] {
-            println!("Applying edit {}", edit_id);
+        // How far the molecule is from the last checkpoint, for the checkpoint
+        // policy.  The cost of edits applied before this call is not known,
+        // so it is estimated from the size of the molecule now.
+        let start = self.history_step;
+        // The edits from `start` on are about to be applied again, and their
+        // failures found again.
+        let edits = &self.edits;
+        self.diagnostics.retain(|diagnostic| {
+            edits
+                .position(diagnostic.edit)
+                .is_some_and(|position| position < start)
+        });
+        let last_checkpoint = self
+            .checkpoints
+            .steps()
+            .filter(|step| *step <= start)
+            .max()
+            .unwrap_or(0);
+        let mut edits_since_checkpoint = start - last_checkpoint;
+        let mut replay_cost = edits_since_checkpoint * self.repr.graph.node_count();
+
+        for (offset, edit_id) in self.edits.order()[start..history_step].iter().enumerate() {
+            if self.edits.is_suppressed(*edit_id) {
+                continue;
+            }
             let edit = self
                 .edits
                 .get(edit_id)
                 .expect("Feature IDs referenced by the FeatureList order should exist!");
 
-            if edit.apply(edit_id, &mut self.repr).is_err() {
-                // TODO: Bubble error to the user
-                println!("Failed to apply the edit with id {}", edit_id);
-                dbg!(&edit);
+            // Which references are missing has to be found before the edit
+            // is applied, as it may have made some of them by the time it
+            // fails.
+            let missing: Vec<AtomSpecifier> = edit
+                .references()
+                .into_iter()
+                .filter(|atom| self.repr.find_atom(atom).is_none())
+                .cloned()
+                .collect();
+            if let Err(error) = edit.apply(edit_id, &mut self.repr) {
+                self.diagnostics.push(EditDiagnostic {
+                    edit: *edit_id,
+                    error,
+                    missing,
+                });
             }
 
-            self.repr.relax();
+            let relax_start = Instant::now();
+            let report = self
+                .repr
+                .relax(self.force_field, self.vdw_cutoff, &self.convergence);
+            relax_time += relax_start.elapsed();
+            if relax_report.is_none_or(|previous| previous.status.is_converged()) {
+                relax_report = Some(report);
+            }
+
+            edits_since_checkpoint += 1;
+            replay_cost += self.repr.graph.node_count();
+            if self
+                .checkpoint_policy
+                .is_due(edits_since_checkpoint, replay_cost)
+            {
+                self.checkpoints
+                    .insert(start + offset + 1, self.repr.make_checkpoint());
+                edits_since_checkpoint = 0;
+                replay_cost = 0;
+            }
+        }
+
+        if relax_report.is_some() {
+            self.relax_report = relax_report;
         }
 
         self.dirty_step = history_step;
         self.history_step = history_step;
+        self.timings = EditorTimings {
+            last_reconstruction: Some(reconstruction_start.elapsed()),
+            last_relax: Some(relax_time),
+        };
     }
 
     // equivalent to `set_history_step(features.len()): applies every feature that is in the
@@ -132,12 +944,28 @@ impl MoleculeEditor {
 // like repr.gpu_atoms).
 #[derive(Serialize, Deserialize)]
 struct ProxyMolecule {
+    #[serde(default)]
+    metadata: Metadata,
     rotation: ultraviolet::Rotor3,
     offset: ultraviolet::Vec3,
     edits: EditList,
     history_step: usize,
     checkpoints: HashMap<usize, MoleculeCheckpoint>,
+    #[serde(default)]
+    checkpoint_policy: CheckpointPolicy,
     dirty_step: usize,
+    #[serde(default)]
+    force_field: ForceFieldKind,
+    #[serde(default = "default_vdw_cutoff")]
+    vdw_cutoff: f32,
+    #[serde(default)]
+    named_selections: Vec<NamedSelection>,
+}
+
+// Molecules saved before the cutoff could be changed were relaxed with the
+// default one.
+fn default_vdw_cutoff() -> f32 {
+    DEFAULT_VDW_CUTOFF
 }
 
 impl Serialize for MoleculeEditor {
@@ -150,16 +978,21 @@ impl Serialize for MoleculeEditor {
         // very close to an existing checkpoint). This allows faster loading when the file
         // is reopened.
 
-        let mut checkpoints = self.checkpoints.clone();
+        let mut checkpoints = self.checkpoints.to_map();
         checkpoints.insert(self.history_step, self.repr.make_checkpoint());
 
         let data = ProxyMolecule {
+            metadata: self.metadata.clone(),
             rotation: self.rotation,
             offset: self.offset,
             edits: self.edits.clone(),
             history_step: self.history_step,
             checkpoints,
+            checkpoint_policy: self.checkpoint_policy,
             dirty_step: self.dirty_step,
+            force_field: self.force_field,
+            vdw_cutoff: self.vdw_cutoff,
+            named_selections: self.named_selections.clone(),
         };
 
         data.serialize(serializer)
@@ -177,13 +1010,26 @@ impl<'de> Deserialize<'de> for MoleculeEditor {
 
         let mut molecule = MoleculeEditor {
             repr: Molecule::default(),
+            metadata: data.metadata,
             rotation: data.rotation,
             offset: data.offset,
             edits: data.edits,
             history_step: data.history_step, // This starts at 0 because we haven't applied the features, we've just loaded them
 
-            checkpoints: data.checkpoints,
+            checkpoints: data.checkpoints.into(),
+            checkpoint_policy: data.checkpoint_policy,
             dirty_step: data.dirty_step,
+            force_field: data.force_field,
+            vdw_cutoff: data.vdw_cutoff,
+            convergence: data.force_field.default_convergence(),
+            relax_report: None,
+            timings: EditorTimings::default(),
+            undo_steps: Vec::new(),
+            redo_steps: Vec::new(),
+            revision: 0,
+            propagate_symmetry: false,
+            named_selections: data.named_selections,
+            diagnostics: Vec::new(),
         };
 
         // this advances the history step to the correct location
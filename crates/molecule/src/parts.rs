@@ -0,0 +1,402 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Recognition of parts that occur several times in one molecule, such as the
+//! molecules of a solvent or the copies of a monomer in a crystal.  Each set of
+//! identical copies can be replaced by a single molecule drawn in several
+//! places, which takes less memory and less work to render.
+//!
+//! Parts that are not bonded to the rest of the molecule are found by
+//! splitting it into its connected pieces.  Parts bonded into a chain, such as
+//! the monomers of a polymer, are found by also cutting the bonds that link
+//! them.  Which bonds those are is not known, so each kind of single bond
+//! between heavy atoms that occurs more than once is tried in turn as the
+//! link, and the split that saves the most is kept.  Instancing a part leaves
+//! out the bonds that were cut.
+
+use std::collections::{hash_map::DefaultHasher, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use common::ids::AtomSpecifier;
+use periodic_table::Element;
+use petgraph::{
+    algo::subgraph_isomorphisms_iter,
+    graph::{NodeIndex, UnGraph},
+    visit::EdgeRef as _,
+};
+use ultraviolet::{Rotor3, Vec3};
+
+use crate::edit::{AtomImport, Edit, EditContext as _};
+use crate::molecule::Molecule;
+use crate::superposition::{self, Superposition};
+use crate::{BondIndex, BondOrder};
+
+/// Parts smaller than this are not worth turning into separate components.
+const MIN_PART_ATOMS: usize = 2;
+
+/// The number of ways of matching two copies of a part that are tried when
+/// looking for the one that superposes best.  Symmetric parts can be matched
+/// in many ways (e.g. the 24 of a methane molecule), most of which swap
+/// equivalent atoms.
+const MAX_MATCHINGS: usize = 256;
+
+/// The number of kinds of bond, the most common first, that are tried as the
+/// links between the repeated parts of a chain.
+const MAX_LINK_KINDS: usize = 8;
+
+/// One copy of a repeated part.
+#[derive(Clone, Debug)]
+pub struct PartInstance {
+    /// The atoms of this copy, listed in the same order as the corresponding
+    /// atoms of every other copy.
+    pub atoms: Vec<AtomSpecifier>,
+    /// The rigid motion that carries the first copy onto this one.
+    pub superposition: Superposition,
+}
+
+/// A part that occurs more than once in a molecule.
+#[derive(Clone, Debug)]
+pub struct RepeatedPart {
+    /// Every copy of the part.  The first is the one the others are matched
+    /// against, so its superposition is the identity.
+    pub instances: Vec<PartInstance>,
+}
+
+impl RepeatedPart {
+    /// The number of atoms in one copy of the part.
+    pub fn atom_count(&self) -> usize {
+        self.instances[0].atoms.len()
+    }
+
+    /// The number of atoms that would no longer need to be stored if every
+    /// copy were drawn from the first.
+    pub fn redundant_atoms(&self) -> usize {
+        self.atom_count() * (self.instances.len() - 1)
+    }
+}
+
+// A connected piece of the molecule, copied into a compactly indexed graph so
+// that it can be matched against other pieces.
+struct Fragment {
+    graph: UnGraph<Element, BondOrder>,
+    atoms: Vec<AtomSpecifier>,
+    positions: Vec<Vec3>,
+}
+
+/// Finds the parts of `molecule` that occur more than once.  Two parts are
+/// considered the same if their bond graphs are identical (including elements
+/// and bond orders) and their geometries superpose to within `tolerance`
+/// angstroms RMSD.  The parts are returned with those that save the most atoms
+/// first.
+pub fn find_repeated_parts(molecule: &Molecule, tolerance: f32) -> Vec<RepeatedPart> {
+    let mut best = repeated_parts(molecule, &HashSet::new(), tolerance);
+    let mut best_score = score(&best);
+    for links in link_kinds(molecule) {
+        let parts = repeated_parts(molecule, &links, tolerance);
+        let score = score(&parts);
+        if score > best_score {
+            best = parts;
+            best_score = score;
+        }
+    }
+    best
+}
+
+// How much instancing `parts` gains: the atoms it saves, less one for each
+// copy that has to be drawn, so that a molecule is not split more finely than
+// it needs to be.
+fn score(parts: &[RepeatedPart]) -> isize {
+    parts
+        .iter()
+        .map(|part| part.redundant_atoms() as isize - part.instances.len() as isize)
+        .sum()
+}
+
+// Finds the parts that occur more than once among the pieces that the
+// molecule falls into once the bonds `cut` are cut.
+fn repeated_parts(
+    molecule: &Molecule,
+    cut: &HashSet<BondIndex>,
+    tolerance: f32,
+) -> Vec<RepeatedPart> {
+    // Fragments whose graphs cannot be isomorphic are kept apart by a hash of
+    // their graph invariants, so that only likely matches are compared.
+    let mut buckets: HashMap<u64, Vec<(Fragment, RepeatedPart)>> = HashMap::new();
+
+    for fragment in fragments(molecule, cut) {
+        let bucket = buckets.entry(invariant_hash(&fragment.graph)).or_default();
+        let matched = bucket.iter_mut().find_map(|(template, part)| {
+            Some((part, match_fragments(template, &fragment, tolerance)?))
+        });
+
+        match matched {
+            Some((part, instance)) => part.instances.push(instance),
+            None => {
+                let instance = PartInstance {
+                    atoms: fragment.atoms.clone(),
+                    superposition: Superposition {
+                        rotation: Rotor3::identity(),
+                        translation: Vec3::zero(),
+                        rmsd: 0.0,
+                    },
+                };
+                bucket.push((
+                    fragment,
+                    RepeatedPart {
+                        instances: vec![instance],
+                    },
+                ));
+            }
+        }
+    }
+
+    let mut parts: Vec<RepeatedPart> = buckets
+        .into_values()
+        .flatten()
+        .map(|(_, part)| part)
+        .filter(|part| part.instances.len() > 1)
+        .collect();
+    parts.sort_by_key(|part| std::cmp::Reverse(part.redundant_atoms()));
+    parts
+}
+
+/// Creates an edit that reproduces the atoms `atoms` of `molecule`, and the
+/// bonds between them, in the same positions.  The atoms are added in the
+/// order given.
+pub fn extract_atoms(molecule: &Molecule, atoms: &[AtomSpecifier], name: &str) -> Edit {
+    let indices: HashMap<&AtomSpecifier, usize> = atoms
+        .iter()
+        .enumerate()
+        .map(|(i, spec)| (spec, i))
+        .collect();
+
+    let mut import = AtomImport {
+        name: name.to_owned(),
+        atoms: Vec::with_capacity(atoms.len()),
+        bonds: Vec::new(),
+    };
+    for (index, spec) in atoms.iter().enumerate() {
+        let (Some(atom), Some(pos)) = (molecule.find_atom(spec), molecule.pos(spec)) else {
+            continue;
+        };
+        import.atoms.push((atom.element, *pos));
+        for (neighbor, order) in molecule.bonds(spec) {
+            // Each bond is seen from both ends; keep it once.
+            match indices.get(&neighbor) {
+                Some(&other) if other > index => import.bonds.push((index, other, order)),
+                _ => {}
+            }
+        }
+    }
+
+    Edit::AtomImport(import)
+}
+
+// The bonds that could link the repeated parts of a chain, by kind: single
+// bonds between heavy atoms, told apart by the elements and bonds of the atoms
+// at either end.  Only kinds that occur more than once are listed, the most
+// common first.
+fn link_kinds(molecule: &Molecule) -> Vec<HashSet<BondIndex>> {
+    let graph = &molecule.graph;
+    let hash = |value: &dyn Fn(&mut DefaultHasher)| {
+        let mut hasher = DefaultHasher::new();
+        value(&mut hasher);
+        hasher.finish()
+    };
+    let labels: HashMap<_, u64> = graph
+        .node_indices()
+        .map(|node| {
+            let mut neighbors: Vec<u64> = graph
+                .edges(node)
+                .map(|edge| {
+                    let other = if edge.source() == node {
+                        edge.target()
+                    } else {
+                        edge.source()
+                    };
+                    hash(&|h| (graph[other].element, *edge.weight()).hash(h))
+                })
+                .collect();
+            neighbors.sort_unstable();
+            (node, hash(&|h| (graph[node].element, &neighbors).hash(h)))
+        })
+        .collect();
+
+    let mut kinds: HashMap<(u64, u64), HashSet<BondIndex>> = HashMap::new();
+    for edge in graph.edge_indices() {
+        let Some((a, b)) = graph.edge_endpoints(edge) else {
+            continue;
+        };
+        if graph[edge] != 1
+            || graph[a].element == Element::Hydrogen
+            || graph[b].element == Element::Hydrogen
+        {
+            continue;
+        }
+        let (a, b) = (labels[&a], labels[&b]);
+        kinds.entry((a.min(b), a.max(b))).or_default().insert(edge);
+    }
+
+    let mut kinds: Vec<HashSet<BondIndex>> = kinds
+        .into_values()
+        .filter(|bonds| bonds.len() > 1)
+        .collect();
+    kinds.sort_by_key(|bonds| std::cmp::Reverse(bonds.len()));
+    kinds.truncate(MAX_LINK_KINDS);
+    kinds
+}
+
+// Splits the molecule into the pieces that stay connected once the bonds
+// `cut` are cut, leaving out those too small to be worth instancing.
+fn fragments(molecule: &Molecule, cut: &HashSet<BondIndex>) -> Vec<Fragment> {
+    let graph = &molecule.graph;
+    let mut visited = HashSet::new();
+    let mut fragments = Vec::new();
+
+    for start in graph.node_indices() {
+        if !visited.insert(start) {
+            continue;
+        }
+
+        let mut nodes = Vec::new();
+        let mut stack = vec![start];
+        while let Some(node) = stack.pop() {
+            nodes.push(node);
+            for edge in graph.edges(node).filter(|edge| !cut.contains(&edge.id())) {
+                let other = if edge.source() == node {
+                    edge.target()
+                } else {
+                    edge.source()
+                };
+                if visited.insert(other) {
+                    stack.push(other);
+                }
+            }
+        }
+        if nodes.len() < MIN_PART_ATOMS {
+            continue;
+        }
+
+        let local: HashMap<_, _> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (*node, NodeIndex::new(i)))
+            .collect();
+        let mut fragment = Fragment {
+            graph: UnGraph::with_capacity(nodes.len(), nodes.len()),
+            atoms: Vec::with_capacity(nodes.len()),
+            positions: Vec::with_capacity(nodes.len()),
+        };
+        for node in &nodes {
+            let atom = &graph[*node];
+            fragment.graph.add_node(atom.element);
+            fragment.atoms.push(molecule.spec(*node));
+            fragment
+                .positions
+                .push(molecule.dense_positions()[node.index()]);
+        }
+        for (index, node) in nodes.iter().enumerate() {
+            for edge in graph.edges(*node).filter(|edge| !cut.contains(&edge.id())) {
+                // Each bond is seen from both ends; add it once.
+                let other = if edge.source() == *node {
+                    edge.target()
+                } else {
+                    edge.source()
+                };
+                let other = local[&other];
+                if other.index() > index {
+                    fragment
+                        .graph
+                        .add_edge(NodeIndex::new(index), other, *edge.weight());
+                }
+            }
+        }
+
+        fragments.push(fragment);
+    }
+
+    fragments
+}
+
+// Hashes the graph's atom environments, refined over a few rounds of the
+// Weisfeiler-Lehman procedure.  Isomorphic graphs always hash equally.
+fn invariant_hash(graph: &UnGraph<Element, BondOrder>) -> u64 {
+    const ROUNDS: usize = 3;
+
+    let hash = |value: &dyn Fn(&mut DefaultHasher)| {
+        let mut hasher = DefaultHasher::new();
+        value(&mut hasher);
+        hasher.finish()
+    };
+
+    let mut labels: Vec<u64> = graph
+        .node_weights()
+        .map(|element| hash(&|h| element.hash(h)))
+        .collect();
+    for _ in 0..ROUNDS {
+        labels = graph
+            .node_indices()
+            .map(|node| {
+                let mut neighbors: Vec<(u64, BondOrder)> = graph
+                    .edges(node)
+                    .map(|edge| {
+                        let other = if edge.source() == node {
+                            edge.target()
+                        } else {
+                            edge.source()
+                        };
+                        (labels[other.index()], *edge.weight())
+                    })
+                    .collect();
+                neighbors.sort_unstable();
+                hash(&|h| (labels[node.index()], &neighbors).hash(h))
+            })
+            .collect();
+    }
+
+    labels.sort_unstable();
+    hash(&|h| (graph.edge_count(), &labels).hash(h))
+}
+
+// Matches the atoms of `fragment` to those of `template`, choosing the
+// matching that superposes best.  Returns `None` if the graphs differ or the
+// best superposition is not within `tolerance`.
+fn match_fragments(
+    template: &Fragment,
+    fragment: &Fragment,
+    tolerance: f32,
+) -> Option<PartInstance> {
+    if template.graph.node_count() != fragment.graph.node_count()
+        || template.graph.edge_count() != fragment.graph.edge_count()
+    {
+        return None;
+    }
+
+    let (g0, g1) = (&template.graph, &fragment.graph);
+    let mut node_match = |a: &Element, b: &Element| a == b;
+    let mut edge_match = |a: &BondOrder, b: &BondOrder| a == b;
+    let matchings = subgraph_isomorphisms_iter(&g0, &g1, &mut node_match, &mut edge_match)?;
+
+    let mut best: Option<PartInstance> = None;
+    for mapping in matchings.take(MAX_MATCHINGS) {
+        let target: Vec<Vec3> = mapping.iter().map(|&i| fragment.positions[i]).collect();
+        let Some(superposition) = superposition::superpose(&template.positions, &target) else {
+            continue;
+        };
+        if best
+            .as_ref()
+            .is_some_and(|best| best.superposition.rmsd <= superposition.rmsd)
+        {
+            continue;
+        }
+        best = Some(PartInstance {
+            atoms: mapping.iter().map(|&i| fragment.atoms[i].clone()).collect(),
+            superposition,
+        });
+    }
+
+    best.filter(|instance| instance.superposition.rmsd <= tolerance)
+}
+
+// End of File
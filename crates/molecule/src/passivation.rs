@@ -0,0 +1,117 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Terminating dangling bonds.  Cutting a block out of a lattice or importing
+//! a slab leaves the atoms at its surface with fewer bonds than their valence
+//! calls for.  A [`Passivation`] finds those atoms, optionally only of some
+//! elements, with few enough neighbours or among a set of atoms, and caps
+//! each free valence with an atom of a terminating species (hydrogen, by
+//! default), placed where VSEPR theory puts the missing bonds.
+
+use std::collections::HashSet;
+
+use common::ids::{AtomSpecifier, EditId};
+use periodic_table::Element;
+use serde::{Deserialize, Serialize};
+
+use crate::edit::{fill_valence, EditContext, EditError};
+
+/// Caps the free valences of under-coordinated atoms.  The species should
+/// form a single bond, as hydrogen and the halogens do: the caps are bonded
+/// by single bonds, and are not capped in turn.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Passivation {
+    /// The element that dangling bonds are capped with.
+    pub species: Element,
+    /// The elements whose atoms are passivated, or empty for every element.
+    #[serde(default)]
+    pub elements: Vec<Element>,
+    /// Only atoms bonded to at most this many others are passivated, or all
+    /// atoms if `None`.
+    #[serde(default)]
+    pub max_coordination: Option<usize>,
+    /// The atoms that may be passivated, such as those of a named selection,
+    /// or empty for every atom.
+    #[serde(default)]
+    pub atoms: Vec<AtomSpecifier>,
+}
+
+impl Default for Passivation {
+    fn default() -> Self {
+        Self {
+            species: Element::Hydrogen,
+            elements: Vec::new(),
+            max_coordination: None,
+            atoms: Vec::new(),
+        }
+    }
+}
+
+impl Passivation {
+    /// A short description of the criteria, e.g. "H on C, Si", for display in
+    /// lists.
+    pub fn describe(&self) -> String {
+        let mut text = self.species.symbol().to_owned();
+        if !self.elements.is_empty() {
+            let symbols: Vec<&str> = self
+                .elements
+                .iter()
+                .map(|element| element.symbol())
+                .collect();
+            text.push_str(" on ");
+            text.push_str(&symbols.join(", "));
+        }
+        if let Some(max) = self.max_coordination {
+            text.push_str(&format!(", at most {} bonds", max));
+        }
+        if !self.atoms.is_empty() {
+            text.push_str(&format!(", {} atoms", self.atoms.len()));
+        }
+        text
+    }
+
+    /// The atoms that would be capped: those that match the criteria and
+    /// have fewer bonds than their standard valence.
+    pub fn targets(&self, commands: &dyn EditContext) -> Vec<AtomSpecifier> {
+        let limited_to: HashSet<&AtomSpecifier> = self.atoms.iter().collect();
+        commands
+            .atoms()
+            .into_iter()
+            .filter(|spec| {
+                if !limited_to.is_empty() && !limited_to.contains(spec) {
+                    return false;
+                }
+                let Some(atom) = commands.find_atom(spec) else {
+                    return false;
+                };
+                if !self.elements.is_empty() && !self.elements.contains(&atom.element) {
+                    return false;
+                }
+                let Some(valence) = atom.element.standard_valence() else {
+                    return false;
+                };
+                let bonds = commands.bonds(spec);
+                let bond_order: usize = bonds.iter().map(|(_, order)| *order as usize).sum();
+                bond_order < valence as usize
+                    && self.max_coordination.is_none_or(|max| bonds.len() <= max)
+            })
+            .collect()
+    }
+}
+
+/// Caps every atom that `passivation` selects, naming the caps after the
+/// edit `edit_id`.  The atoms are chosen before any are capped.
+pub(crate) fn spawn_passivation(
+    passivation: &Passivation,
+    edit_id: &EditId,
+    commands: &mut dyn EditContext,
+) -> Result<(), EditError> {
+    let mut spec = AtomSpecifier::new(*edit_id);
+    for target in passivation.targets(&*commands) {
+        fill_valence(&target, passivation.species, &mut spec, commands)?;
+    }
+    Ok(())
+}
+
+// End of File
@@ -2,6 +2,8 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use std::collections::HashMap;
+
 use common::ids::{AtomSpecifier, EditId};
 use lib3dmol::{
     parser::read_pdb_txt,
@@ -10,33 +12,357 @@ use lib3dmol::{
 use periodic_table::Element;
 use ultraviolet::Vec3;
 
-use crate::edit::{EditContext, EditError};
+use crate::cell::UnitCell;
+use crate::edit::{fill_valence, EditContext, EditError, PdbData, PdbModel, ReferenceType};
+use crate::residues::{self, ResidueKind};
+use crate::spatial::SpatialIndex;
+use crate::{BondOrder, ResidueId};
+
+// Consecutive residues are only linked if the atoms that would be bonded are
+// at most this far apart, in angstroms, so that gaps in a chain (such as
+// residues missing from a crystal structure) are left open.
+const MAX_LINK_LENGTH: f32 = 2.0;
+
+// Hydrogens in a file are bonded to the nearest heavy atom of their residue
+// that is at most this far away, in angstroms.
+const MAX_HYDROGEN_BOND_LENGTH: f32 = 1.3;
+
+// Cysteines whose sulfurs are at most this far apart, in angstroms, are
+// joined by a disulfide bond.
+const MAX_DISULFIDE_LENGTH: f32 = 2.5;
+
+// How much longer than the sum of their covalent radii two atoms without
+// bonds can be apart and still have a bond inferred between them.
+const BOND_TOLERANCE: f32 = 1.25;
 
 pub(crate) fn spawn_pdb(
-    name: &str,
-    contents: &str,
+    data: &PdbData,
     edit_id: &EditId,
     commands: &mut dyn EditContext,
 ) -> Result<(), EditError> {
-    // Currently bonds are ignored because lib3dmol does not support
-    // parsing bonding info from PDB files!
     let mut spec = AtomSpecifier::new(*edit_id);
-    let structure = read_pdb_txt(contents, name);
+    let records = model_records(&data.contents, data.model)?;
+    let text = records
+        .iter()
+        .map(|(_, record)| *record)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let structure = read_pdb_txt(&text, &data.name);
+    let lines = record_lines(&records);
+    // Every atom read, by its serial number, for CONECT records to refer to.
+    let mut serials = HashMap::new();
+    let mut atoms_read = Vec::new();
+    // The heavy atoms of residues that came without hydrogens, which are
+    // given them once every residue is bonded to the next.
+    let mut unfilled = Vec::new();
+    let mut sulfurs = Vec::new();
 
     for chain in structure.chains {
+        let mut previous = None;
         for residue in chain.lst_res {
+            let id = ResidueId {
+                name: residue.name.trim().to_owned(),
+                number: residue.res_num,
+            };
+            let mut atoms = HashMap::new();
+            let mut hydrogens = Vec::new();
             for atom in residue.get_atom() {
                 let element = atom_type_to_element(&atom.a_type);
                 let pos: Vec3 = atom.coord.into();
+                let atom_spec = spec.next_spec();
 
-                commands.add_atom(element, pos, spec.next_spec(), None)?;
+                commands.add_atom(element, pos, atom_spec.clone(), None)?;
+                commands.set_chain(&atom_spec, Some(chain.name))?;
+                commands.set_residue(&atom_spec, Some(id.clone()))?;
+                commands.set_source_line(&atom_spec, lines.get(&atom.number).copied())?;
+                serials.entry(atom.number).or_insert(atom_spec.clone());
+                atoms_read.push((atom_spec.clone(), element, pos));
+                if element == Element::Hydrogen {
+                    hydrogens.push(atom_spec);
+                } else {
+                    atoms.insert(residues::normalize_atom_name(&atom.name), atom_spec);
+                }
+            }
+            if data.residue_templates {
+                previous = bond_residue(
+                    &residue.name,
+                    atoms,
+                    &hydrogens,
+                    previous.as_ref(),
+                    &mut unfilled,
+                    commands,
+                )?;
+                if let Some((_, atoms)) = previous.as_ref().filter(|_| id.name == "CYS") {
+                    sulfurs.extend(atoms.get("SG").cloned());
+                }
             }
         }
     }
 
+    for (i, a) in sulfurs.iter().enumerate() {
+        for b in &sulfurs[i + 1..] {
+            if distance(&*commands, a, b)? <= MAX_DISULFIDE_LENGTH {
+                commands.create_bond(a, b, 1)?;
+            }
+        }
+    }
+    if data.conect_bonds {
+        for ((a, b), order) in conect_bonds(&records) {
+            if let (Some(a), Some(b)) = (serials.get(&a), serials.get(&b)) {
+                // The templates know the orders of the bonds they make, which
+                // CONECT records often leave out.
+                if !commands.bonds(a).iter().any(|(atom, _)| atom == b) {
+                    commands.create_bond(a, b, order)?;
+                }
+            }
+        }
+    }
+    if data.infer_bonds {
+        infer_bonds(&atoms_read, commands)?;
+    }
+    for atom in &unfilled {
+        fill_valence(atom, Element::Hydrogen, &mut spec, commands)?;
+    }
+    commands.set_cell(read_cell(&data.contents));
+
     Ok(())
 }
 
+// A standard residue's kind and its heavy atoms, by name.
+type Residue = (ResidueKind, HashMap<String, AtomSpecifier>);
+
+// Bonds the atoms of a residue as its template gives, and to the residue
+// before it in the chain.  Hydrogens from the file are bonded to the nearest
+// heavy atom; if there are none, the heavy atoms are added to `unfilled`.
+// Returns the residue for the next one to link to, or `None` if it is not a
+// standard residue.
+fn bond_residue(
+    code: &str,
+    atoms: HashMap<String, AtomSpecifier>,
+    hydrogens: &[AtomSpecifier],
+    previous: Option<&Residue>,
+    unfilled: &mut Vec<AtomSpecifier>,
+    commands: &mut dyn EditContext,
+) -> Result<Option<Residue>, EditError> {
+    let Some(template) = residues::template(code) else {
+        return Ok(None);
+    };
+    for (a, b, order) in template.bonds {
+        if let (Some(a), Some(b)) = (atoms.get(*a), atoms.get(*b)) {
+            commands.create_bond(a, b, *order)?;
+        }
+    }
+    if let Some((kind, before)) = previous.filter(|(kind, _)| *kind == template.kind) {
+        let (from, to) = kind.link();
+        if let (Some(a), Some(b)) = (before.get(from), atoms.get(to)) {
+            if distance(&*commands, a, b)? <= MAX_LINK_LENGTH {
+                commands.create_bond(a, b, 1)?;
+            }
+        }
+    }
+
+    if hydrogens.is_empty() {
+        // In the template's order, so that the hydrogens are named the same
+        // each time the file is imported.
+        unfilled.extend(
+            template
+                .atoms
+                .iter()
+                .filter_map(|(name, _)| atoms.get(*name).cloned()),
+        );
+    }
+    for hydrogen in hydrogens {
+        let mut nearest = None;
+        for atom in atoms.values() {
+            let length = distance(&*commands, hydrogen, atom)?;
+            if length <= MAX_HYDROGEN_BOND_LENGTH
+                && nearest
+                    .as_ref()
+                    .is_none_or(|(_, nearest)| length < *nearest)
+            {
+                nearest = Some((atom, length));
+            }
+        }
+        if let Some((atom, _)) = nearest {
+            commands.create_bond(hydrogen, atom, 1)?;
+        }
+    }
+
+    Ok(Some((template.kind, atoms)))
+}
+
+fn distance(
+    commands: &dyn EditContext,
+    a: &AtomSpecifier,
+    b: &AtomSpecifier,
+) -> Result<f32, EditError> {
+    match (commands.pos(a), commands.pos(b)) {
+        (Some(a), Some(b)) => Ok((*a - *b).mag()),
+        _ => Err(EditError::BrokenReference(ReferenceType::Atom)),
+    }
+}
+
+// The records of the models chosen by `model`, along with those that are not
+// part of any model, each with its line in the file, counting from 1.  Files
+// with only one structure have no MODEL records, and all of their records are
+// read.
+fn model_records(contents: &str, model: PdbModel) -> Result<Vec<(usize, &str)>, EditError> {
+    let lines = contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line));
+    if model == PdbModel::All {
+        return Ok(lines.collect());
+    }
+    let mut records = Vec::new();
+    // The model whose records are being read, and whether it is the one
+    // chosen.
+    let mut current: Option<bool> = None;
+    let mut found = false;
+    for (line, record) in lines {
+        if record.starts_with("MODEL") {
+            let serial = record
+                .get(10..14)
+                .and_then(|serial| serial.trim().parse().ok());
+            let chosen = match model {
+                PdbModel::Serial(model) => serial == Some(model),
+                _ => !found,
+            };
+            found |= chosen;
+            current = Some(chosen);
+        } else if record.starts_with("ENDMDL") {
+            current = None;
+        } else if current != Some(false) {
+            records.push((line, record));
+        }
+    }
+    match model {
+        PdbModel::Serial(model) if !found => Err(EditError::MissingModel(model)),
+        _ => Ok(records),
+    }
+}
+
+// The line of each ATOM and HETATM record, by the serial number of its atom,
+// which lib3dmol keeps but does not say where it read.  Only the first record
+// of each serial is kept.
+fn record_lines(records: &[(usize, &str)]) -> HashMap<u64, usize> {
+    let mut lines = HashMap::new();
+    for (line, record) in records {
+        if !(record.starts_with("ATOM") || record.starts_with("HETATM")) {
+            continue;
+        }
+        if let Some(serial) = serial(record, 6) {
+            lines.entry(serial).or_insert(*line);
+        }
+    }
+    lines
+}
+
+// The bonds that the CONECT records list, which lib3dmol skips, between the
+// atoms with the given serial numbers.  A bond listed more than once in a
+// record is a multiple bond, of that order.
+fn conect_bonds(records: &[(usize, &str)]) -> HashMap<(u64, u64), BondOrder> {
+    let mut bonds = HashMap::new();
+    for (_, record) in records {
+        if !record.starts_with("CONECT") {
+            continue;
+        }
+        let Some(from) = serial(record, 6) else {
+            continue;
+        };
+        let mut orders: HashMap<u64, BondOrder> = HashMap::new();
+        for start in [11, 16, 21, 26] {
+            if let Some(to) = serial(record, start).filter(|to| *to != from) {
+                *orders.entry(to).or_default() += 1;
+            }
+        }
+        // Each bond is usually listed from both of its atoms.
+        for (to, order) in orders {
+            let order = order.min(3);
+            let listed = bonds.entry((from.min(to), from.max(to))).or_insert(order);
+            *listed = (*listed).max(order);
+        }
+    }
+    bonds
+}
+
+// Bonds each atom that has no bonds to the atoms no further from it than the
+// sum of their covalent radii, with some tolerance.  A hydrogen is only bonded
+// to the nearest of them, so other atoms leave hydrogens to bond themselves.
+fn infer_bonds(
+    atoms: &[(AtomSpecifier, Element, Vec3)],
+    commands: &mut dyn EditContext,
+) -> Result<(), EditError> {
+    let unbonded: Vec<usize> = (0..atoms.len())
+        .filter(|index| commands.bonds(&atoms[*index].0).is_empty())
+        .collect();
+    if unbonded.is_empty() {
+        return Ok(());
+    }
+    let largest = atoms
+        .iter()
+        .map(|(_, element, _)| element.covalent_radius())
+        .fold(0.0, f32::max);
+    let index = SpatialIndex::new(
+        atoms
+            .iter()
+            .enumerate()
+            .map(|(index, (_, _, pos))| (index, *pos)),
+        2.0 * largest * BOND_TOLERANCE,
+    );
+
+    for a in unbonded {
+        let (spec, element, pos) = &atoms[a];
+        let radius = element.covalent_radius();
+        let mut neighbors: Vec<(usize, f32)> = index
+            .within(*pos, (radius + largest) * BOND_TOLERANCE)
+            .filter(|b| *b != a)
+            .filter_map(|b| {
+                let (_, other, other_pos) = &atoms[b];
+                if *other == Element::Hydrogen && *element != Element::Hydrogen {
+                    return None;
+                }
+                let length = (*other_pos - *pos).mag();
+                let bonded = length <= (radius + other.covalent_radius()) * BOND_TOLERANCE;
+                bonded.then_some((b, length))
+            })
+            .collect();
+        if *element == Element::Hydrogen {
+            neighbors.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+            neighbors.truncate(1);
+        }
+        for (b, _) in neighbors {
+            commands.create_bond(spec, &atoms[b].0, 1)?;
+        }
+    }
+    Ok(())
+}
+
+// The serial number in the five columns of `record` from `start`.
+fn serial(record: &str, start: usize) -> Option<u64> {
+    record.get(start..start + 5)?.trim().parse().ok()
+}
+
+// Reads the unit cell from the CRYST1 record, which lib3dmol skips.  Structures
+// that are not crystals (e.g. from NMR) often give a cell of 1 angstrom cubed
+// in place of none, so cells that small are ignored.
+fn read_cell(contents: &str) -> Option<UnitCell> {
+    let record = contents.lines().find(|line| line.starts_with("CRYST1"))?;
+    let field = |start: usize, end: usize| -> Option<f32> {
+        record
+            .get(start..end.min(record.len()))?
+            .trim()
+            .parse()
+            .ok()
+    };
+    let lengths = [field(6, 15)?, field(15, 24)?, field(24, 33)?];
+    let angles = [field(33, 40)?, field(40, 47)?, field(47, 54)?];
+    if lengths.iter().any(|length| *length <= 1.0) {
+        return None;
+    }
+    Some(UnitCell::from_parameters(lengths, angles))
+}
+
 fn atom_type_to_element(atom_type: &AtomType) -> Element {
     match atom_type {
         AtomType::Hydrogen => Element::Hydrogen,
@@ -0,0 +1,919 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Biopolymers built from their sequence.  A [`Polymer`] strings the residue
+//! templates of [`crate::residues`] together into a peptide, a double helix
+//! of DNA or a strand of RNA, with the standard geometry of its backbone, so
+//! that a scaffold of hundreds of atoms can be had by typing its sequence.
+//!
+//! The backbone is chained from standard bond angles and dihedrals: an
+//! α-helix or an extended strand for peptides, B-DNA and A-RNA for nucleic
+//! acids.  Everything else is grown out from it with idealised angles: side
+//! chains are extended, aromatic rings are laid flat as regular polygons, and
+//! the second strand of DNA is fitted to the first by its base pairs.  The
+//! result is only a starting point, and is relaxed once it is built.
+
+use std::collections::{HashMap, VecDeque};
+use std::f32::consts::PI;
+
+use common::ids::{AtomSpecifier, EditId};
+use periodic_table::Element;
+use serde::{Deserialize, Serialize};
+use ultraviolet::{Mat3, Vec2, Vec3};
+
+use crate::dynamics::parameters;
+use crate::edit::{fill_valence, EditContext, EditError};
+use crate::residues::{self, ResidueKind, ResidueTemplate};
+use crate::zmatrix::{off_axis, place};
+use crate::BondOrder;
+
+/// The angle between two bonds of a tetrahedral atom, in degrees.
+const TETRAHEDRAL: f32 = 109.47;
+
+/// The length of the bonds of aromatic rings as they are first laid out, in
+/// angstroms.
+const RING_BOND: f32 = 1.39;
+
+/// The distance between the atoms of a Watson-Crick hydrogen bond, in
+/// angstroms, which the strands of a duplex are placed to give.
+const BASE_PAIR_DISTANCE: f32 = 2.9;
+
+/// A peptide, or a strand of nucleic acid, built from its sequence.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Polymer {
+    pub kind: ResidueKind,
+    /// The one-letter codes of the residues, from the N terminus of a peptide
+    /// or the 5' end of a nucleic acid.  Whitespace is ignored.
+    pub sequence: String,
+    /// Whether a peptide is coiled into an α-helix, rather than laid out as an
+    /// extended strand.  Nucleic acids are always helical.
+    #[serde(default)]
+    pub helix: bool,
+    /// Whether DNA is built with its complementary strand, as a double helix.
+    /// Peptides and RNA are built as single strands.
+    #[serde(default)]
+    pub duplex: bool,
+}
+
+/// The atoms of a built polymer, and the bonds between them as pairs of
+/// indices into the atoms.
+pub type PolymerAtoms = (Vec<(Element, Vec3)>, Vec<(usize, usize, BondOrder)>);
+
+impl Polymer {
+    /// A helical peptide, a double helix of DNA or a strand of RNA, with the
+    /// given sequence.
+    pub fn new(kind: ResidueKind, sequence: &str) -> Self {
+        Self {
+            kind,
+            sequence: sequence.to_owned(),
+            helix: true,
+            duplex: kind == ResidueKind::Deoxyribonucleotide,
+        }
+    }
+
+    /// The templates of the sequence's residues, in order, or the first
+    /// letter that is not the code of a residue of the polymer's kind.
+    pub fn residues(&self) -> Result<Vec<&'static ResidueTemplate>, char> {
+        self.sequence
+            .chars()
+            .filter(|letter| !letter.is_whitespace())
+            .map(|letter| residues::from_letter(self.kind, letter).ok_or(letter))
+            .collect()
+    }
+
+    /// A short description of the polymer, e.g. "DNA duplex, 12 residues",
+    /// for display in lists.
+    pub fn describe(&self) -> String {
+        let length = self
+            .sequence
+            .chars()
+            .filter(|letter| !letter.is_whitespace())
+            .count();
+        let shape = match self.kind {
+            ResidueKind::AminoAcid if self.helix => "helical peptide",
+            ResidueKind::AminoAcid => "extended peptide",
+            ResidueKind::Deoxyribonucleotide if self.duplex => "DNA duplex",
+            ResidueKind::Deoxyribonucleotide => "DNA strand",
+            ResidueKind::Ribonucleotide => "RNA strand",
+        };
+        format!("{}, {} residues", shape, length)
+    }
+
+    /// The polymer's heavy atoms and the bonds between them, or the first
+    /// letter of the sequence that is not a residue.  A nucleic acid's helix
+    /// runs up the z axis from its 5' end.
+    pub fn build(&self) -> Result<PolymerAtoms, char> {
+        let templates = self.residues()?;
+        if templates.is_empty() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+        let mut strand = Strand::new(self.kind, &templates, self.helix);
+        if self.kind == ResidueKind::AminoAcid {
+            return Ok(strand.into_atoms());
+        }
+
+        // The helix is found from a strand of at least two residues; every
+        // residue has the same backbone, so a single one is built twice.
+        let frame = if templates.len() >= 2 {
+            strand.helix_frame()
+        } else {
+            Strand::new(self.kind, &[templates[0]; 2], self.helix).helix_frame()
+        };
+        strand.transform(&frame);
+        if !self.duplex || self.kind != ResidueKind::Deoxyribonucleotide {
+            return Ok(strand.into_atoms());
+        }
+
+        let complement: Vec<&'static ResidueTemplate> = templates
+            .iter()
+            .rev()
+            .map(|template| {
+                residues::from_letter(self.kind, complement(template.letter))
+                    .expect("every nucleotide has a complement")
+            })
+            .collect();
+        let mut partner = Strand::new(self.kind, &complement, self.helix);
+        partner.transform(&frame);
+        partner.pair_with(&strand);
+        Ok(strand.merge(partner).into_atoms())
+    }
+}
+
+// The one-letter code of the DNA base that pairs with `letter`.
+fn complement(letter: char) -> char {
+    match letter {
+        'A' => 'T',
+        'T' => 'A',
+        'G' => 'C',
+        _ => 'G',
+    }
+}
+
+// The backbone atoms of a residue, in the order they are chained, each with
+// the bond angle it makes with the two atoms before it and the dihedral angle
+// it makes with the three before it, in degrees.
+fn backbone(kind: ResidueKind, helix: bool) -> [(&'static str, f32, f32); 6] {
+    match kind {
+        // The dihedrals are ψ of the residue before, ω, and φ.  The last
+        // three entries are unused.
+        ResidueKind::AminoAcid => {
+            let (phi, psi) = if helix {
+                (-57.0, -47.0)
+            } else {
+                (-139.0, 135.0)
+            };
+            [
+                ("N", 116.2, psi),
+                ("CA", 121.7, 180.0),
+                ("C", 111.2, phi),
+                ("", 0.0, 0.0),
+                ("", 0.0, 0.0),
+                ("", 0.0, 0.0),
+            ]
+        }
+        // The dihedrals are ε of the residue before, then ζ, α, β, γ and δ.
+        ResidueKind::Deoxyribonucleotide => [
+            ("P", 119.7, 155.0),
+            ("O5'", 104.0, -95.0),
+            ("C5'", 120.9, -47.0),
+            ("C4'", 110.2, -146.0),
+            ("C3'", 115.0, 36.0),
+            ("O3'", 110.0, 156.0),
+        ],
+        ResidueKind::Ribonucleotide => [
+            ("P", 119.7, -153.0),
+            ("O5'", 104.0, -71.0),
+            ("C5'", 120.9, -68.0),
+            ("C4'", 110.2, 178.0),
+            ("C3'", 115.0, 54.0),
+            ("O3'", 110.0, 82.0),
+        ],
+    }
+}
+
+// Dihedrals that the atoms grown out from the backbone are given rather than
+// the usual ones, as the names of the four atoms and the angle in degrees:
+// the glycosidic bond, χ, which turns the base.
+fn fixed_dihedrals(
+    kind: ResidueKind,
+) -> &'static [(&'static str, &'static str, &'static str, &'static str, f32)] {
+    match kind {
+        ResidueKind::AminoAcid => &[],
+        ResidueKind::Deoxyribonucleotide => &[
+            ("O4'", "C1'", "N9", "C8", 100.0),
+            ("O4'", "C1'", "N9", "C4", -80.0),
+            ("O4'", "C1'", "N1", "C6", 100.0),
+            ("O4'", "C1'", "N1", "C2", -80.0),
+        ],
+        ResidueKind::Ribonucleotide => &[
+            ("O4'", "C1'", "N9", "C8", 22.0),
+            ("O4'", "C1'", "N9", "C4", -158.0),
+            ("O4'", "C1'", "N1", "C6", 22.0),
+            ("O4'", "C1'", "N1", "C2", -158.0),
+        ],
+    }
+}
+
+// The handedness of the stereocentres: for each, the center and three of the
+// atoms bonded to it, `[a, b, c]`, placed so that `a · (b × c)` (taken from
+// the center) is positive if the flag is set and negative if not.  These give
+// L-amino acids and D-sugars.
+fn stereocenters(kind: ResidueKind) -> &'static [(&'static str, [&'static str; 3], bool)] {
+    match kind {
+        ResidueKind::AminoAcid => &[("CA", ["N", "C", "CB"], true)],
+        ResidueKind::Deoxyribonucleotide | ResidueKind::Ribonucleotide => &[
+            ("C4'", ["O4'", "C3'", "C5'"], false),
+            ("C3'", ["O3'", "C4'", "C2'"], true),
+            ("C2'", ["O2'", "C1'", "C3'"], false),
+            ("C1'", ["O4'", "N9", "C2'"], false),
+            ("C1'", ["O4'", "N1", "C2'"], false),
+        ],
+    }
+}
+
+// The atoms of each Watson-Crick pair that are hydrogen bonded, the first of
+// each pair in the purine.
+fn hydrogen_bonds(purine: char) -> &'static [(&'static str, &'static str)] {
+    match purine {
+        'G' => &[("N1", "N3"), ("N2", "O2"), ("O6", "N4")],
+        _ => &[("N1", "N3"), ("N6", "O4")],
+    }
+}
+
+// Lays out fused rings flat, as regular polygons with sides of
+// `RING_BOND`: `first` about the origin, then each of `rest` on the far side
+// of an edge it shares with those already placed.
+fn ring_layout(first: &[usize], rest: &[Vec<usize>]) -> HashMap<usize, Vec2> {
+    let mut layout = HashMap::new();
+    let radius = RING_BOND / (2.0 * (PI / first.len() as f32).sin());
+    for (i, atom) in first.iter().enumerate() {
+        let angle = 2.0 * PI * i as f32 / first.len() as f32;
+        layout.insert(*atom, Vec2::new(angle.cos(), angle.sin()) * radius);
+    }
+
+    let mut rest: Vec<&Vec<usize>> = rest.iter().collect();
+    while let Some(next) = rest.iter().position(|ring| {
+        (0..ring.len()).any(|i| {
+            layout.contains_key(&ring[i]) && layout.contains_key(&ring[(i + 1) % ring.len()])
+        })
+    }) {
+        let ring = rest.remove(next);
+        let n = ring.len();
+        let i = (0..n)
+            .find(|i| layout.contains_key(&ring[*i]) && layout.contains_key(&ring[(i + 1) % n]))
+            .expect("the ring shares an edge");
+        let (a, b) = (layout[&ring[i]], layout[&ring[(i + 1) % n]]);
+
+        // The new ring's center is across the shared edge from the middle of
+        // the atoms placed so far.
+        let middle = layout
+            .values()
+            .fold(Vec2::zero(), |sum, point| sum + *point)
+            / layout.len() as f32;
+        let edge = (b - a).normalized();
+        let mut normal = Vec2::new(-edge.y, edge.x);
+        if normal.dot((a + b) / 2.0 - middle) < 0.0 {
+            normal = -normal;
+        }
+        let center = (a + b) / 2.0 + normal * (RING_BOND / (2.0 * (PI / n as f32).tan()));
+
+        let (from_a, from_b) = (a - center, b - center);
+        let start = from_a.y.atan2(from_a.x);
+        let turn = from_a.x * from_b.y - from_a.y * from_b.x;
+        let step = 2.0 * PI / n as f32 * turn.signum();
+        let radius = RING_BOND / (2.0 * (PI / n as f32).sin());
+        for k in 2..n {
+            let angle = start + step * k as f32;
+            layout
+                .entry(ring[(i + k) % n])
+                .or_insert(center + Vec2::new(angle.cos(), angle.sin()) * radius);
+        }
+    }
+    layout
+}
+
+// A single chain of residues, which is placed as it is built.
+struct Strand {
+    kind: ResidueKind,
+    elements: Vec<Element>,
+    names: Vec<&'static str>,
+    positions: Vec<Option<Vec3>>,
+    bonds: Vec<(usize, usize, BondOrder)>,
+    neighbors: Vec<Vec<(usize, BondOrder)>>,
+    // The atoms of each residue, by name, with the residue's one-letter code.
+    residues: Vec<(char, HashMap<&'static str, usize>)>,
+}
+
+// A rigid motion, as a rotation about the origin followed by a translation.
+struct Frame {
+    rotation: Mat3,
+    translation: Vec3,
+}
+
+impl Strand {
+    fn new(kind: ResidueKind, templates: &[&'static ResidueTemplate], helix: bool) -> Self {
+        let mut strand = Self {
+            kind,
+            elements: Vec::new(),
+            names: Vec::new(),
+            positions: Vec::new(),
+            bonds: Vec::new(),
+            neighbors: Vec::new(),
+            residues: Vec::new(),
+        };
+        let last = templates.len() - 1;
+        for (i, template) in templates.iter().enumerate() {
+            let mut atoms = HashMap::new();
+            for &(name, element) in template.atoms {
+                // Only the C terminus has a second carboxyl oxygen, and the 5'
+                // end is left without its phosphate.
+                let present = match name {
+                    "OXT" => i == last,
+                    "P" | "OP1" | "OP2" => i > 0,
+                    "OP3" => false,
+                    _ => true,
+                };
+                if present {
+                    atoms.insert(name, strand.elements.len());
+                    strand.elements.push(element);
+                    strand.names.push(name);
+                    strand.positions.push(None);
+                    strand.neighbors.push(Vec::new());
+                }
+            }
+            for &(a, b, order) in template.bonds {
+                if let (Some(&a), Some(&b)) = (atoms.get(a), atoms.get(b)) {
+                    strand.bond(a, b, order);
+                }
+            }
+            if let Some((_, before)) = strand.residues.last() {
+                let (from, to) = kind.link();
+                if let (Some(&a), Some(&b)) = (before.get(from), atoms.get(to)) {
+                    strand.bond(a, b, 1);
+                }
+            }
+            strand.residues.push((template.letter, atoms));
+        }
+
+        strand.chain_backbone(helix);
+        strand.grow();
+        strand
+    }
+
+    fn bond(&mut self, a: usize, b: usize, order: BondOrder) {
+        self.bonds.push((a, b, order));
+        self.neighbors[a].push((b, order));
+        self.neighbors[b].push((a, order));
+    }
+
+    fn pos(&self, atom: usize) -> Vec3 {
+        self.positions[atom].expect("atoms are placed before they are built from")
+    }
+
+    fn bond_length(&self, a: usize, b: usize) -> f32 {
+        let order = self.neighbors[a]
+            .iter()
+            .find(|(neighbor, _)| *neighbor == b)
+            .map_or(1, |(_, order)| *order);
+        parameters::bond_length(self.elements[a], self.elements[b], order)
+    }
+
+    // Places the backbone atoms of every residue one after another, each from
+    // the three before it.
+    fn chain_backbone(&mut self, helix: bool) {
+        let backbone = backbone(self.kind, helix);
+        let chain: Vec<(usize, f32, f32)> = self
+            .residues
+            .iter()
+            .flat_map(|(_, atoms)| {
+                backbone.iter().filter_map(|(name, angle, dihedral)| {
+                    Some((*atoms.get(name)?, *angle, *dihedral))
+                })
+            })
+            .collect();
+
+        for (i, &(atom, angle, dihedral)) in chain.iter().enumerate() {
+            let pos = match i {
+                0 => Vec3::zero(),
+                1 => self.pos(chain[0].0) + Vec3::unit_x() * self.bond_length(chain[0].0, atom),
+                _ => {
+                    let (b, c) = (self.pos(chain[i - 2].0), self.pos(chain[i - 1].0));
+                    let a = match i {
+                        2 => b + Vec3::unit_y(),
+                        _ => self.pos(chain[i - 3].0),
+                    };
+                    let length = self.bond_length(chain[i - 1].0, atom);
+                    place(a, b, c, length, angle, dihedral)
+                }
+            };
+            self.positions[atom] = Some(pos);
+        }
+    }
+
+    // Places every other atom, working outwards from those already placed.
+    fn grow(&mut self) {
+        let mut queue: VecDeque<usize> = (0..self.positions.len())
+            .filter(|atom| self.positions[*atom].is_some())
+            .collect();
+        while let Some(atom) = queue.pop_front() {
+            for (next, _) in self.neighbors[atom].clone() {
+                if self.positions[next].is_none() {
+                    self.positions[next] = Some(self.place_from(atom, next));
+                    queue.push_back(next);
+                    queue.extend(self.place_ring_system(atom, next));
+                }
+            }
+        }
+    }
+
+    // Whether `atom` is trigonal planar: a carbon or nitrogen with a multiple
+    // bond, or a nitrogen conjugated with one, as in amides and aromatic
+    // rings.
+    fn is_trigonal(&self, atom: usize) -> bool {
+        let multiple = |atom: usize| self.neighbors[atom].iter().any(|(_, order)| *order >= 2);
+        match self.elements[atom] {
+            Element::Carbon => multiple(atom),
+            Element::Nitrogen => {
+                multiple(atom)
+                    || self.neighbors[atom]
+                        .iter()
+                        .any(|(neighbor, _)| multiple(*neighbor))
+            }
+            _ => false,
+        }
+    }
+
+    // The position of `atom`, bonded to the placed atom `center`.
+    fn place_from(&self, center: usize, atom: usize) -> Vec3 {
+        let closing = self.neighbors[atom]
+            .iter()
+            .map(|(neighbor, _)| *neighbor)
+            .find(|neighbor| *neighbor != center && self.positions[*neighbor].is_some());
+        if let Some(other) = closing {
+            return self.close_ring(center, other, atom);
+        }
+
+        let placed: Vec<usize> = self.neighbors[center]
+            .iter()
+            .map(|(neighbor, _)| *neighbor)
+            .filter(|neighbor| *neighbor != atom && self.positions[*neighbor].is_some())
+            .collect();
+        let origin = self.pos(center);
+        let length = self.bond_length(center, atom);
+        let trigonal = self.is_trigonal(center);
+        let units: Vec<Vec3> = placed
+            .iter()
+            .map(|neighbor| (self.pos(*neighbor) - origin).normalized())
+            .collect();
+
+        let direction = match units.as_slice() {
+            [] => Vec3::unit_x(),
+            [_] => {
+                let angle = if trigonal { 120.0 } else { TETRAHEDRAL };
+                let (reference, dihedral) = self.reference(placed[0], center, atom, 180.0);
+                return place(
+                    reference,
+                    self.pos(placed[0]),
+                    origin,
+                    length,
+                    angle,
+                    dihedral,
+                );
+            }
+            [u, v] => {
+                let bisector = -(*u + *v).normalized();
+                if trigonal {
+                    bisector
+                } else {
+                    // The two free corners of a tetrahedron lie either side of
+                    // the plane of the other two.
+                    let normal = u.cross(*v).normalized();
+                    let half = (TETRAHEDRAL / 2.0).to_radians();
+                    let above = bisector * half.cos() + normal * half.sin();
+                    let below = bisector * half.cos() - normal * half.sin();
+                    match self.handedness(center, atom, &placed) {
+                        Some((positive, [a, b])) => {
+                            let volume = |direction: Vec3| {
+                                let pos = |neighbor: Option<usize>| match neighbor {
+                                    Some(neighbor) => self.pos(neighbor) - origin,
+                                    None => direction,
+                                };
+                                pos(a).dot(pos(b).cross(pos(None)))
+                            };
+                            if (volume(above) > 0.0) == positive {
+                                above
+                            } else {
+                                below
+                            }
+                        }
+                        None => above,
+                    }
+                }
+            }
+            units => -units
+                .iter()
+                .fold(Vec3::zero(), |sum, unit| sum + *unit)
+                .normalized(),
+        };
+        origin + direction * length
+    }
+
+    // The position of `atom`, which closes a ring by bonding both `center`
+    // and `other`: at the right distance from each, on the side away from
+    // the rest of the ring.
+    fn close_ring(&self, center: usize, other: usize, atom: usize) -> Vec3 {
+        let (origin, end) = (self.pos(center), self.pos(other));
+        let (first, second) = (
+            self.bond_length(center, atom),
+            self.bond_length(other, atom),
+        );
+        let span = (end - origin).mag();
+        let axis = (end - origin) / span;
+        let (along, across) = if span < first + second {
+            let along = (span * span + first * first - second * second) / (2.0 * span);
+            (along, (first * first - along * along).max(0.0).sqrt())
+        } else {
+            (span * first / (first + second), 0.0)
+        };
+
+        let ring: Vec<Vec3> = [center, other]
+            .iter()
+            .flat_map(|end| &self.neighbors[*end])
+            .map(|(neighbor, _)| *neighbor)
+            .filter(|neighbor| ![center, other, atom].contains(neighbor))
+            .filter_map(|neighbor| self.positions[neighbor])
+            .collect();
+        let foot = origin + axis * along;
+        let away = match ring.len() {
+            0 => foot - origin,
+            n => foot - ring.iter().fold(Vec3::zero(), |sum, pos| sum + *pos) / n as f32,
+        };
+        let away = away - axis * away.dot(axis);
+        let away = if away.mag_sq() > 1e-6 {
+            away.normalized()
+        } else {
+            axis.cross(off_axis(axis)).normalized()
+        };
+        foot + away * across
+    }
+
+    // The aromatic rings fused with `atom`, if it is in one, each as its atoms
+    // in order around it.  A ring is aromatic (or at least flat) here if all
+    // of its atoms are trigonal.
+    fn planar_rings(&self, atom: usize) -> Vec<Vec<usize>> {
+        let mut rings: Vec<Vec<usize>> = Vec::new();
+        let mut frontier = vec![atom];
+        while let Some(from) = frontier.pop() {
+            for (to, _) in &self.neighbors[from] {
+                let Some(ring) = self.smallest_ring(from, *to) else {
+                    continue;
+                };
+                let known = rings.iter().any(|known| {
+                    known.len() == ring.len() && ring.iter().all(|atom| known.contains(atom))
+                });
+                if !known {
+                    frontier.extend(&ring);
+                    rings.push(ring);
+                }
+            }
+        }
+        rings
+    }
+
+    // The smallest ring of trigonal atoms, of at most six, that includes the
+    // bond from `from` to `to`, starting with those two atoms.
+    fn smallest_ring(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        if !self.is_trigonal(from) || !self.is_trigonal(to) {
+            return None;
+        }
+        // A search from `to` back to `from` that does not cross the bond.
+        let mut reached_by: HashMap<usize, usize> = HashMap::from([(to, to)]);
+        let mut frontier = vec![to];
+        for _ in 0..5 {
+            let mut next = Vec::new();
+            for atom in frontier {
+                for (neighbor, _) in &self.neighbors[atom] {
+                    if *neighbor == from && atom != to {
+                        let mut ring = vec![from, atom];
+                        while *ring.last()? != to {
+                            ring.push(reached_by[ring.last()?]);
+                        }
+                        ring[1..].reverse();
+                        return Some(ring);
+                    }
+                    if *neighbor != from
+                        && self.is_trigonal(*neighbor)
+                        && !reached_by.contains_key(neighbor)
+                    {
+                        reached_by.insert(*neighbor, atom);
+                        next.push(*neighbor);
+                    }
+                }
+            }
+            frontier = next;
+        }
+        None
+    }
+
+    // Places the flat ring system that `atom`, just placed from `center`, is
+    // part of, if it is, as a whole: laid out as regular polygons fused edge
+    // to edge, and turned so that `atom`'s bond to `center` points straight
+    // out of it.  Returns the atoms placed.
+    fn place_ring_system(&mut self, center: usize, atom: usize) -> Vec<usize> {
+        let rings = self.planar_rings(atom);
+        let Some(first) = rings.first() else {
+            return Vec::new();
+        };
+        let layout = ring_layout(first, &rings[1..]);
+
+        // The ring system's plane is set by the dihedral of one of `atom`'s
+        // neighbors in it, like that of any other atom, but stands across the
+        // chain it hangs from unless told otherwise.
+        let flat = layout[&atom];
+        let ring_neighbors: Vec<usize> = self.neighbors[atom]
+            .iter()
+            .map(|(neighbor, _)| *neighbor)
+            .filter(|neighbor| layout.contains_key(neighbor))
+            .collect();
+        let outwards = -ring_neighbors
+            .iter()
+            .fold(Vec2::zero(), |sum, neighbor| {
+                sum + (layout[neighbor] - flat).normalized()
+            })
+            .normalized();
+        let toward = (layout[&ring_neighbors[0]] - flat).normalized();
+        let sideways = (toward - outwards * toward.dot(outwards)).normalized();
+        let angle = outwards.dot(toward).clamp(-1.0, 1.0).acos().to_degrees();
+
+        let (origin, pos) = (self.pos(center), self.pos(atom));
+        let (reference, dihedral) = match self.neighbors[center]
+            .iter()
+            .any(|(neighbor, _)| *neighbor != atom && self.positions[*neighbor].is_some())
+        {
+            true => self.reference(center, atom, ring_neighbors[0], 90.0),
+            false => (origin + off_axis(pos - origin), 90.0),
+        };
+        let out = (origin - pos).normalized();
+        let side = place(reference, origin, pos, 1.0, angle, dihedral) - pos;
+        let side = (side - out * side.dot(out)).normalized();
+
+        let mut placed = Vec::new();
+        for (ring_atom, point) in layout {
+            if self.positions[ring_atom].is_none() {
+                let offset = point - flat;
+                self.positions[ring_atom] =
+                    Some(pos + out * offset.dot(outwards) + side * offset.dot(sideways));
+                placed.push(ring_atom);
+            }
+        }
+        placed.sort_unstable();
+        placed
+    }
+
+    // The stereocentre rule for placing `atom` on `center`, if there is one:
+    // whether the signed volume should be positive, and the two atoms it is
+    // taken with, in the order that puts `atom` last.  `None` stands for
+    // `atom` itself.
+    fn handedness(
+        &self,
+        center: usize,
+        atom: usize,
+        placed: &[usize],
+    ) -> Option<(bool, [Option<usize>; 2])> {
+        let neighbor = |name: &str| {
+            if self.names[atom] == name {
+                return Some(None);
+            }
+            placed
+                .iter()
+                .find(|neighbor| self.names[**neighbor] == name)
+                .map(|neighbor| Some(*neighbor))
+        };
+        stereocenters(self.kind)
+            .iter()
+            .filter(|(name, ..)| *name == self.names[center])
+            .find_map(|(_, [a, b, c], positive)| {
+                let atoms = [neighbor(a)?, neighbor(b)?, neighbor(c)?];
+                // `a · (b × c)` is unchanged by rotating the three, so `atom`
+                // is turned to the end.
+                match atoms {
+                    [None, b, c] => Some((*positive, [b, c])),
+                    [a, None, c] => Some((*positive, [c, a])),
+                    [a, b, None] => Some((*positive, [a, b])),
+                    _ => None,
+                }
+            })
+    }
+
+    // The atom that the dihedral of `atom` about the bond from `previous` to
+    // `center` is measured from, and the dihedral it is placed at: a fixed
+    // one if there is one, and otherwise `otherwise`.
+    fn reference(
+        &self,
+        previous: usize,
+        center: usize,
+        atom: usize,
+        otherwise: f32,
+    ) -> (Vec3, f32) {
+        let candidates: Vec<usize> = self.neighbors[previous]
+            .iter()
+            .map(|(neighbor, _)| *neighbor)
+            .filter(|neighbor| *neighbor != center && self.positions[*neighbor].is_some())
+            .collect();
+        let names = [self.names[previous], self.names[center], self.names[atom]];
+        for &reference in &candidates {
+            let fixed = fixed_dihedrals(self.kind)
+                .iter()
+                .find(|(r, a, b, c, _)| [*a, *b, *c] == names && *r == self.names[reference]);
+            if let Some((.., dihedral)) = fixed {
+                return (self.pos(reference), *dihedral);
+            }
+        }
+        match candidates.first() {
+            Some(&reference) => (self.pos(reference), otherwise),
+            None => {
+                let axis = self.pos(center) - self.pos(previous);
+                (self.pos(previous) + off_axis(axis), otherwise)
+            }
+        }
+    }
+
+    // The screw motion that carries the first residue onto the second, as the
+    // frame whose z axis is the helix axis, pointing along the strand.
+    fn helix_frame(&self) -> Frame {
+        let frame = |residue: usize| {
+            let atoms = &self.residues[residue].1;
+            let [a, b, c] = ["C5'", "C4'", "C3'"].map(|name| self.pos(atoms[name]));
+            let x = (b - a).normalized();
+            let y = ((c - a) - x * (c - a).dot(x)).normalized();
+            (Mat3::new(x, y, x.cross(y)), a)
+        };
+        let (first, origin) = frame(0);
+        let (second, moved) = frame(1);
+        let rotation = second * first.transposed();
+        let translation = moved - rotation * origin;
+
+        // The axis and angle of the rotation, and the point on the axis that
+        // the screw turns about.
+        let [c0, c1, c2] = rotation.cols;
+        let skew = Vec3::new(c1.z - c2.y, c2.x - c0.z, c0.y - c1.x);
+        let cos = ((c0.x + c1.y + c2.z) - 1.0) / 2.0;
+        let mut axis = skew.normalized();
+        let mut angle = (skew.mag() / 2.0).atan2(cos);
+        if translation.dot(axis) < 0.0 {
+            axis = -axis;
+            angle = -angle;
+        }
+        let across = translation - axis * translation.dot(axis);
+        let center = (across + axis.cross(across) / (angle / 2.0).tan()) / 2.0;
+
+        // The x axis points from the helix axis to the first atom.
+        let start = self.pos(self.residues[0].1[backbone(self.kind, true)[1].0]) - center;
+        let x = (start - axis * start.dot(axis)).normalized();
+        let helix = Mat3::new(x, axis.cross(x), axis);
+        Frame {
+            rotation: helix.transposed(),
+            translation: -(helix.transposed() * center),
+        }
+    }
+
+    fn transform(&mut self, frame: &Frame) {
+        for pos in self.positions.iter_mut().flatten() {
+            *pos = frame.rotation * *pos + frame.translation;
+        }
+    }
+
+    // Turns this strand, which lies on the same helix as `other` but runs
+    // the same way, about a dyad axis across the helix so that it pairs with
+    // `other` base for base.  The dyad is found by search: coarsely, then
+    // finely about the best coarse fit.
+    fn pair_with(&mut self, other: &Strand) {
+        let pairs: Vec<(Vec3, usize)> = other
+            .residues
+            .iter()
+            .zip(self.residues.iter().rev())
+            .flat_map(|((letter, atoms), (partner_letter, partner))| {
+                let purine_first = matches!(letter, 'A' | 'G');
+                let purine = if purine_first {
+                    *letter
+                } else {
+                    *partner_letter
+                };
+                hydrogen_bonds(purine)
+                    .iter()
+                    .filter_map(move |(in_purine, in_pyrimidine)| {
+                        let (ours, theirs) = if purine_first {
+                            (in_purine, in_pyrimidine)
+                        } else {
+                            (in_pyrimidine, in_purine)
+                        };
+                        Some((other.pos(*atoms.get(ours)?), *partner.get(theirs)?))
+                    })
+            })
+            .collect();
+        let heights: Vec<f32> = self.positions.iter().flatten().map(|pos| pos.z).collect();
+        let middle = (heights.iter().copied().fold(f32::INFINITY, f32::min)
+            + heights.iter().copied().fold(f32::NEG_INFINITY, f32::max))
+            / 2.0;
+
+        let dyad = |angle: f32, height: f32, pos: Vec3| {
+            let axis = Vec3::new(angle.to_radians().cos(), angle.to_radians().sin(), 0.0);
+            let offset = pos - Vec3::new(0.0, 0.0, height);
+            Vec3::new(0.0, 0.0, height) + axis * (2.0 * offset.dot(axis)) - offset
+        };
+        let misfit = |angle: f32, height: f32| {
+            pairs
+                .iter()
+                .map(|(pos, atom)| {
+                    let distance = (dyad(angle, height, self.pos(*atom)) - *pos).mag();
+                    (distance - BASE_PAIR_DISTANCE).powi(2)
+                })
+                .sum::<f32>()
+        };
+        let search = |angles: (f32, f32, f32), heights: (f32, f32, f32)| {
+            let mut best = (f32::INFINITY, angles.0, heights.0);
+            let mut angle = angles.0;
+            while angle <= angles.1 {
+                let mut height = heights.0;
+                while height <= heights.1 {
+                    let fit = misfit(angle, height);
+                    if fit < best.0 {
+                        best = (fit, angle, height);
+                    }
+                    height += heights.2;
+                }
+                angle += angles.2;
+            }
+            best
+        };
+        let (_, angle, height) = search((0.0, 358.0, 2.0), (middle - 3.0, middle + 3.0, 0.2));
+        let (_, angle, height) = search(
+            (angle - 2.0, angle + 2.0, 0.1),
+            (height - 0.2, height + 0.2, 0.01),
+        );
+
+        for pos in self.positions.iter_mut().flatten() {
+            *pos = dyad(angle, height, *pos);
+        }
+    }
+
+    // Both strands' atoms, this one's first.
+    fn merge(mut self, other: Strand) -> Self {
+        let offset = self.elements.len();
+        self.elements.extend(other.elements);
+        self.names.extend(other.names);
+        self.positions.extend(other.positions);
+        self.neighbors
+            .extend(other.neighbors.into_iter().map(|neighbors| {
+                neighbors
+                    .into_iter()
+                    .map(|(neighbor, order)| (neighbor + offset, order))
+                    .collect()
+            }));
+        self.bonds.extend(
+            other
+                .bonds
+                .into_iter()
+                .map(|(a, b, order)| (a + offset, b + offset, order)),
+        );
+        self
+    }
+
+    fn into_atoms(self) -> PolymerAtoms {
+        let atoms = self
+            .elements
+            .into_iter()
+            .zip(self.positions)
+            .map(|(element, pos)| (element, pos.unwrap_or_default()))
+            .collect();
+        (atoms, self.bonds)
+    }
+}
+
+/// Adds the polymer's atoms, then hydrogens to fill the valence of each.
+pub(crate) fn spawn_polymer(
+    polymer: &Polymer,
+    edit_id: &EditId,
+    commands: &mut dyn EditContext,
+) -> Result<(), EditError> {
+    let (atoms, bonds) = polymer.build().map_err(EditError::UnknownResidue)?;
+    let mut spec = AtomSpecifier::new(*edit_id);
+    let mut specs = Vec::with_capacity(atoms.len());
+
+    for (element, pos) in &atoms {
+        let atom_spec = spec.next_spec();
+        commands.add_atom(*element, *pos, atom_spec.clone(), None)?;
+        specs.push(atom_spec);
+    }
+    for (a1, a2, order) in bonds {
+        commands.create_bond(&specs[a1], &specs[a2], order)?;
+    }
+    for atom in &specs {
+        fill_valence(atom, Element::Hydrogen, &mut spec, commands)?;
+    }
+
+    Ok(())
+}
+
+// End of File
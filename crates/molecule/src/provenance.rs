@@ -0,0 +1,147 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Where each atom of a molecule came from, so that a complex build can be
+//! audited.
+//!
+//! Every atom is named by the edits that made it (see [`AtomSpecifier`]): the
+//! feature that created it, then each pattern that copied it.  Its provenance
+//! is read from its specifier and the molecule's edit list, along with the
+//! source the feature made it from.  That is a line of a file for atoms read
+//! from a PDB file (which is recorded as the atom is read), the file for other
+//! imports, or the feature's parameters, written as a line of a build script
+//! (see [`build_script::command`]), for atoms that a feature generated.
+
+use std::fmt;
+
+use common::ids::{AtomSpecifier, EditId};
+
+use crate::build_script;
+use crate::edit::{Edit, EditContext as _};
+use crate::{MoleculeEditor, ResidueId};
+
+/// What a feature made an atom from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AtomSource {
+    /// An imported file, named `name`, and the line of it that the atom was
+    /// read from, counting from 1, if that is known.
+    File { name: String, line: Option<usize> },
+    /// The parameters of the feature that generated it, as a line of a build
+    /// script, or `None` if they cannot be written as one.
+    Parameters(Option<String>),
+}
+
+/// A copy of an atom made by a pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatternCopy {
+    pub edit: EditId,
+    /// The pattern's position in the edit list, counting from 1, or `None` if
+    /// it has been deleted.
+    pub edit_number: Option<usize>,
+    /// Which of the pattern's copies it is, counting from 0 for the original.
+    pub instance: usize,
+}
+
+/// Where an atom came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Provenance {
+    /// The feature that created the atom.
+    pub feature: EditId,
+    /// The feature's position in the edit list, counting from 1.
+    pub feature_number: usize,
+    /// What the feature is called, such as "PDB Import (1crn.pdb)".
+    pub feature_name: String,
+    /// The patterns that copied the atom, oldest first.
+    pub copies: Vec<PatternCopy>,
+    pub source: AtomSource,
+    /// The PDB chain and residue the atom belongs to, if it was imported from
+    /// a PDB file.
+    pub chain: Option<char>,
+    pub residue: Option<ResidueId>,
+}
+
+/// Finds where the atom `spec` of `molecule` came from.  Returns `None` if
+/// there is no such atom, or the feature that created it has been deleted.
+pub fn find(molecule: &MoleculeEditor, spec: &AtomSpecifier) -> Option<Provenance> {
+    let node = molecule.repr.find_atom(spec)?;
+    let (created, copies) = spec.path.split_first()?;
+    let edits = molecule.edits();
+    let feature = created.owner_id;
+    let edit = edits.get(&feature)?;
+    // Copies that were not given the line of their original look it up.
+    let line = node.source_line.or_else(|| {
+        let original = AtomSpecifier {
+            path: spec.path[..1].into(),
+            child_index: spec.child_index,
+        };
+        molecule.repr.find_atom(&original)?.source_line
+    });
+    let source = match edit {
+        Edit::PdbImport(data) => AtomSource::File {
+            name: data.name.clone(),
+            line,
+        },
+        Edit::AtomImport(data) => AtomSource::File {
+            name: data.name.clone(),
+            line,
+        },
+        Edit::ZMatrixImport(data) => AtomSource::File {
+            name: data.name.clone(),
+            line,
+        },
+        edit => AtomSource::Parameters(build_script::command(edit)),
+    };
+    Some(Provenance {
+        feature,
+        feature_number: edits.position(feature)? + 1,
+        feature_name: edit.name(),
+        copies: copies
+            .iter()
+            .map(|copy| PatternCopy {
+                edit: copy.owner_id,
+                edit_number: edits.position(copy.owner_id).map(|index| index + 1),
+                instance: copy.instance,
+            })
+            .collect(),
+        source,
+        chain: node.chain,
+        residue: node.residue.clone(),
+    })
+}
+
+/// Describes the provenance on one line, e.g. "created by #1 PDB Import
+/// (1crn.pdb) from line 215 of 1crn.pdb (chain A, CYS 32)".
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "created by #{} {}",
+            self.feature_number, self.feature_name
+        )?;
+        match &self.source {
+            AtomSource::File {
+                name,
+                line: Some(line),
+            } => write!(f, " from line {} of {}", line, name)?,
+            AtomSource::File { name, line: None } => write!(f, " from {}", name)?,
+            AtomSource::Parameters(Some(parameters)) => write!(f, " as `{}`", parameters)?,
+            AtomSource::Parameters(None) => {}
+        }
+        match (self.chain, &self.residue) {
+            (Some(chain), Some(residue)) => write!(f, " (chain {}, {})", chain, residue)?,
+            (Some(chain), None) => write!(f, " (chain {})", chain)?,
+            (None, Some(residue)) => write!(f, " ({})", residue)?,
+            (None, None) => {}
+        }
+        for copy in &self.copies {
+            match copy.edit_number {
+                Some(number) => write!(f, ", copy {} by #{}", copy.instance, number)?,
+                None => write!(f, ", copy {} by a deleted edit", copy.instance)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// End of File
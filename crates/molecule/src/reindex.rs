@@ -0,0 +1,87 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Renumbering the atoms of a molecule.  An atom's specifier records every
+//! edit that made or copied it (see [`AtomSpecifier`]), so after a long
+//! history the specifiers are long, and the atoms are in the order that the
+//! edits happened to add them rather than along the structure.
+//!
+//! Re-indexing replaces the history with a single import of the atoms as they
+//! are, numbered breadth-first from a chosen root, so that every specifier is
+//! as short as it can be and bonded atoms are listed near each other in
+//! exports.  See [`MoleculeEditor::reindex`](crate::MoleculeEditor::reindex).
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use common::ids::AtomSpecifier;
+
+use crate::edit::EditContext;
+use crate::molecule::Molecule;
+
+/// Why a molecule could not be re-indexed.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReindexError {
+    /// The molecule repeats in a unit cell, which an import cannot hold.
+    Periodic,
+    /// The root is not an atom of the molecule.
+    UnknownRoot,
+}
+
+impl fmt::Display for ReindexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReindexError::Periodic => write!(f, "periodic molecules cannot be re-indexed"),
+            ReindexError::UnknownRoot => write!(f, "the root is not an atom of the molecule"),
+        }
+    }
+}
+
+/// The atoms of `molecule` in breadth-first order along the bonds, starting
+/// from `root`, or from the first atom if there is no root.  Pieces that are
+/// not bonded to the root follow, each in breadth-first order from its first
+/// atom.  Neighbours are visited in the molecule's own order, so the result
+/// does not change from one call to the next.
+pub fn breadth_first(
+    molecule: &Molecule,
+    root: Option<&AtomSpecifier>,
+) -> Result<Vec<AtomSpecifier>, ReindexError> {
+    let atoms = EditContext::atoms(molecule);
+    if let Some(root) = root {
+        if molecule.find_atom(root).is_none() {
+            return Err(ReindexError::UnknownRoot);
+        }
+    }
+    let rank: HashMap<&AtomSpecifier, usize> = atoms
+        .iter()
+        .enumerate()
+        .map(|(index, atom)| (atom, index))
+        .collect();
+
+    let mut order = Vec::with_capacity(atoms.len());
+    let mut visited: HashSet<AtomSpecifier> = HashSet::with_capacity(atoms.len());
+    for start in root.into_iter().chain(&atoms) {
+        if !visited.insert(start.clone()) {
+            continue;
+        }
+        let mut queue = VecDeque::from([start.clone()]);
+        while let Some(atom) = queue.pop_front() {
+            let mut neighbors: Vec<AtomSpecifier> = molecule
+                .bonds(&atom)
+                .into_iter()
+                .map(|(neighbor, _)| neighbor)
+                .filter(|neighbor| !visited.contains(neighbor))
+                .collect();
+            neighbors.sort_by_key(|neighbor| rank.get(neighbor).copied());
+            for neighbor in neighbors {
+                visited.insert(neighbor.clone());
+                queue.push_back(neighbor);
+            }
+            order.push(atom);
+        }
+    }
+    Ok(order)
+}
+
+// End of File
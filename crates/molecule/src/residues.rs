@@ -0,0 +1,372 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Templates of the standard residues of proteins and nucleic acids.  A PDB
+//! file names each atom within its residue (`CA`, `O3'`, ...) but, for the
+//! standard residues, leaves out the bonds between them.  A
+//! [`ResidueTemplate`] gives those bonds and their orders, so that imported
+//! biomolecules come out with the right bonds, and with hydrogens where the
+//! file has none.
+//!
+//! Heavy atoms only are listed.  Ionizable groups are in their neutral forms,
+//! and histidine is protonated on `NE2`.  Aromatic rings are given one of
+//! their Kekulé structures.
+
+use periodic_table::Element;
+use periodic_table::Element::{
+    Carbon as C, Nitrogen as N, Oxygen as O, Phosphorus as P, Sulfur as S,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::BondOrder;
+
+/// The kind of polymer that a residue is a monomer of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ResidueKind {
+    AminoAcid,
+    Deoxyribonucleotide,
+    Ribonucleotide,
+}
+
+impl ResidueKind {
+    /// The atoms of consecutive residues that are bonded to link them: the
+    /// first is in the earlier residue and the second in the later one.
+    pub fn link(&self) -> (&'static str, &'static str) {
+        match self {
+            ResidueKind::AminoAcid => ("C", "N"),
+            ResidueKind::Deoxyribonucleotide | ResidueKind::Ribonucleotide => ("O3'", "P"),
+        }
+    }
+}
+
+/// The heavy atoms of a residue and the bonds between them.
+#[derive(Debug)]
+pub struct ResidueTemplate {
+    /// The residue's name in PDB files, such as `ALA` or `DA`.
+    pub code: &'static str,
+    /// The residue's one-letter code, as used in sequences.
+    pub letter: char,
+    pub kind: ResidueKind,
+    /// The residue's atoms, by their names in PDB files.
+    pub atoms: &'static [(&'static str, Element)],
+    /// Bonds between the residue's atoms, by name.  Atoms that only the
+    /// residues at the ends of a chain have, such as `OXT`, are included.
+    pub bonds: &'static [(&'static str, &'static str, BondOrder)],
+}
+
+impl ResidueTemplate {
+    /// The element of the atom named `name`, or `None` if the residue has no
+    /// atom of that name.
+    pub fn element(&self, name: &str) -> Option<Element> {
+        self.atoms
+            .iter()
+            .find(|(atom, _)| *atom == name)
+            .map(|(_, element)| *element)
+    }
+}
+
+/// The template of the residue named `code` in PDB files, or `None` if it is
+/// not a standard residue.
+pub fn template(code: &str) -> Option<&'static ResidueTemplate> {
+    let code = code.trim();
+    TEMPLATES.iter().find(|template| template.code == code)
+}
+
+/// The template of the residue of `kind` with the one-letter code `letter`,
+/// such as `W` for tryptophan.
+pub fn from_letter(kind: ResidueKind, letter: char) -> Option<&'static ResidueTemplate> {
+    let letter = letter.to_ascii_uppercase();
+    TEMPLATES
+        .iter()
+        .find(|template| template.kind == kind && template.letter == letter)
+}
+
+/// An atom's name as written in current PDB files.  Older files mark the
+/// sugar atoms of nucleotides with `*` rather than `'`, and name the
+/// phosphate oxygens `O1P` and `O2P`.
+pub fn normalize_atom_name(name: &str) -> String {
+    match name.trim() {
+        "O1P" => "OP1".to_owned(),
+        "O2P" => "OP2".to_owned(),
+        "O3P" => "OP3".to_owned(),
+        name => name.replace('*', "'"),
+    }
+}
+
+// Atoms and bonds shared by every amino acid.
+macro_rules! amino_acid {
+    ($code:literal, $letter:literal, [$($atom:tt),*], [$($bond:tt),*]) => {
+        ResidueTemplate {
+            code: $code,
+            letter: $letter,
+            kind: ResidueKind::AminoAcid,
+            atoms: &[("N", N), ("CA", C), ("C", C), ("O", O), ("OXT", O) $(, $atom)*],
+            bonds: &[("N", "CA", 1), ("CA", "C", 1), ("C", "O", 2), ("C", "OXT", 1) $(, $bond)*],
+        }
+    };
+}
+
+// Atoms and bonds shared by every nucleotide: the phosphate and the sugar,
+// which has a hydroxyl at `C2'` in RNA.
+macro_rules! nucleotide {
+    ($code:literal, $letter:literal, Deoxyribonucleotide, [$($atom:tt),*], [$($bond:tt),*]) => {
+        nucleotide!(@ $code, $letter, Deoxyribonucleotide, [$($atom),*], [$($bond),*])
+    };
+    ($code:literal, $letter:literal, Ribonucleotide, [$($atom:tt),*], [$($bond:tt),*]) => {
+        nucleotide!(
+            @ $code, $letter, Ribonucleotide,
+            [("O2'", O) $(, $atom)*],
+            [("C2'", "O2'", 1) $(, $bond)*]
+        )
+    };
+    (@ $code:literal, $letter:literal, $kind:ident, [$($atom:tt),*], [$($bond:tt),*]) => {
+        ResidueTemplate {
+            code: $code,
+            letter: $letter,
+            kind: ResidueKind::$kind,
+            atoms: &[
+                ("P", P), ("OP1", O), ("OP2", O), ("OP3", O), ("O5'", O), ("C5'", C),
+                ("C4'", C), ("O4'", O), ("C3'", C), ("O3'", O), ("C2'", C), ("C1'", C)
+                $(, $atom)*
+            ],
+            bonds: &[
+                ("P", "OP1", 2), ("P", "OP2", 1), ("P", "OP3", 1), ("P", "O5'", 1),
+                ("O5'", "C5'", 1), ("C5'", "C4'", 1), ("C4'", "O4'", 1), ("C4'", "C3'", 1),
+                ("C3'", "O3'", 1), ("C3'", "C2'", 1), ("C2'", "C1'", 1), ("C1'", "O4'", 1)
+                $(, $bond)*
+            ],
+        }
+    };
+}
+
+// The bases, attached to the sugar at `N9` (purines) or `N1` (pyrimidines).
+macro_rules! adenine {
+    ($code:literal, $kind:ident) => {
+        nucleotide!(
+            $code,
+            'A',
+            $kind,
+            [
+                ("N9", N),
+                ("C8", C),
+                ("N7", N),
+                ("C5", C),
+                ("C6", C),
+                ("N6", N),
+                ("N1", N),
+                ("C2", C),
+                ("N3", N),
+                ("C4", C)
+            ],
+            [
+                ("C1'", "N9", 1),
+                ("N9", "C8", 1),
+                ("C8", "N7", 2),
+                ("N7", "C5", 1),
+                ("C5", "C6", 1),
+                ("C6", "N6", 1),
+                ("C6", "N1", 2),
+                ("N1", "C2", 1),
+                ("C2", "N3", 2),
+                ("N3", "C4", 1),
+                ("C4", "C5", 2),
+                ("C4", "N9", 1)
+            ]
+        )
+    };
+}
+
+macro_rules! guanine {
+    ($code:literal, $kind:ident) => {
+        nucleotide!(
+            $code,
+            'G',
+            $kind,
+            [
+                ("N9", N),
+                ("C8", C),
+                ("N7", N),
+                ("C5", C),
+                ("C6", C),
+                ("O6", O),
+                ("N1", N),
+                ("C2", C),
+                ("N2", N),
+                ("N3", N),
+                ("C4", C)
+            ],
+            [
+                ("C1'", "N9", 1),
+                ("N9", "C8", 1),
+                ("C8", "N7", 2),
+                ("N7", "C5", 1),
+                ("C5", "C6", 1),
+                ("C6", "O6", 2),
+                ("C6", "N1", 1),
+                ("N1", "C2", 1),
+                ("C2", "N2", 1),
+                ("C2", "N3", 2),
+                ("N3", "C4", 1),
+                ("C4", "C5", 2),
+                ("C4", "N9", 1)
+            ]
+        )
+    };
+}
+
+macro_rules! cytosine {
+    ($code:literal, $kind:ident) => {
+        nucleotide!(
+            $code,
+            'C',
+            $kind,
+            [
+                ("N1", N),
+                ("C2", C),
+                ("O2", O),
+                ("N3", N),
+                ("C4", C),
+                ("N4", N),
+                ("C5", C),
+                ("C6", C)
+            ],
+            [
+                ("C1'", "N1", 1),
+                ("N1", "C2", 1),
+                ("C2", "O2", 2),
+                ("C2", "N3", 1),
+                ("N3", "C4", 2),
+                ("C4", "N4", 1),
+                ("C4", "C5", 1),
+                ("C5", "C6", 2),
+                ("C6", "N1", 1)
+            ]
+        )
+    };
+}
+
+// Thymine and uracil differ only by thymine's methyl group at `C5`.
+macro_rules! pyrimidinedione {
+    ($code:literal, $letter:literal, $kind:ident, [$($atom:tt),*], [$($bond:tt),*]) => {
+        nucleotide!(
+            $code, $letter, $kind,
+            [("N1", N), ("C2", C), ("O2", O), ("N3", N), ("C4", C), ("O4", O), ("C5", C),
+             ("C6", C) $(, $atom)*],
+            [("C1'", "N1", 1), ("N1", "C2", 1), ("C2", "O2", 2), ("C2", "N3", 1),
+             ("N3", "C4", 1), ("C4", "O4", 2), ("C4", "C5", 1), ("C5", "C6", 2),
+             ("C6", "N1", 1) $(, $bond)*]
+        )
+    };
+}
+
+#[rustfmt::skip]
+static TEMPLATES: &[ResidueTemplate] = &[
+    amino_acid!("GLY", 'G', [], []),
+    amino_acid!("ALA", 'A', [("CB", C)], [("CA", "CB", 1)]),
+    amino_acid!("SER", 'S', [("CB", C), ("OG", O)], [("CA", "CB", 1), ("CB", "OG", 1)]),
+    amino_acid!("CYS", 'C', [("CB", C), ("SG", S)], [("CA", "CB", 1), ("CB", "SG", 1)]),
+    amino_acid!(
+        "VAL", 'V',
+        [("CB", C), ("CG1", C), ("CG2", C)],
+        [("CA", "CB", 1), ("CB", "CG1", 1), ("CB", "CG2", 1)]
+    ),
+    amino_acid!(
+        "THR", 'T',
+        [("CB", C), ("OG1", O), ("CG2", C)],
+        [("CA", "CB", 1), ("CB", "OG1", 1), ("CB", "CG2", 1)]
+    ),
+    amino_acid!(
+        "LEU", 'L',
+        [("CB", C), ("CG", C), ("CD1", C), ("CD2", C)],
+        [("CA", "CB", 1), ("CB", "CG", 1), ("CG", "CD1", 1), ("CG", "CD2", 1)]
+    ),
+    amino_acid!(
+        "ILE", 'I',
+        [("CB", C), ("CG1", C), ("CG2", C), ("CD1", C)],
+        [("CA", "CB", 1), ("CB", "CG1", 1), ("CB", "CG2", 1), ("CG1", "CD1", 1)]
+    ),
+    amino_acid!(
+        "MET", 'M',
+        [("CB", C), ("CG", C), ("SD", S), ("CE", C)],
+        [("CA", "CB", 1), ("CB", "CG", 1), ("CG", "SD", 1), ("SD", "CE", 1)]
+    ),
+    amino_acid!(
+        "PRO", 'P',
+        [("CB", C), ("CG", C), ("CD", C)],
+        [("CA", "CB", 1), ("CB", "CG", 1), ("CG", "CD", 1), ("CD", "N", 1)]
+    ),
+    amino_acid!(
+        "PHE", 'F',
+        [("CB", C), ("CG", C), ("CD1", C), ("CD2", C), ("CE1", C), ("CE2", C), ("CZ", C)],
+        [("CA", "CB", 1), ("CB", "CG", 1), ("CG", "CD1", 2), ("CD1", "CE1", 1),
+         ("CE1", "CZ", 2), ("CZ", "CE2", 1), ("CE2", "CD2", 2), ("CD2", "CG", 1)]
+    ),
+    amino_acid!(
+        "TYR", 'Y',
+        [("CB", C), ("CG", C), ("CD1", C), ("CD2", C), ("CE1", C), ("CE2", C), ("CZ", C),
+         ("OH", O)],
+        [("CA", "CB", 1), ("CB", "CG", 1), ("CG", "CD1", 2), ("CD1", "CE1", 1),
+         ("CE1", "CZ", 2), ("CZ", "CE2", 1), ("CE2", "CD2", 2), ("CD2", "CG", 1),
+         ("CZ", "OH", 1)]
+    ),
+    amino_acid!(
+        "TRP", 'W',
+        [("CB", C), ("CG", C), ("CD1", C), ("CD2", C), ("NE1", N), ("CE2", C), ("CE3", C),
+         ("CZ2", C), ("CZ3", C), ("CH2", C)],
+        [("CA", "CB", 1), ("CB", "CG", 1), ("CG", "CD1", 2), ("CD1", "NE1", 1),
+         ("NE1", "CE2", 1), ("CE2", "CD2", 2), ("CD2", "CG", 1), ("CE2", "CZ2", 1),
+         ("CZ2", "CH2", 2), ("CH2", "CZ3", 1), ("CZ3", "CE3", 2), ("CE3", "CD2", 1)]
+    ),
+    amino_acid!(
+        "HIS", 'H',
+        [("CB", C), ("CG", C), ("ND1", N), ("CD2", C), ("CE1", C), ("NE2", N)],
+        [("CA", "CB", 1), ("CB", "CG", 1), ("CG", "ND1", 1), ("ND1", "CE1", 2),
+         ("CE1", "NE2", 1), ("NE2", "CD2", 1), ("CD2", "CG", 2)]
+    ),
+    amino_acid!(
+        "ASP", 'D',
+        [("CB", C), ("CG", C), ("OD1", O), ("OD2", O)],
+        [("CA", "CB", 1), ("CB", "CG", 1), ("CG", "OD1", 2), ("CG", "OD2", 1)]
+    ),
+    amino_acid!(
+        "GLU", 'E',
+        [("CB", C), ("CG", C), ("CD", C), ("OE1", O), ("OE2", O)],
+        [("CA", "CB", 1), ("CB", "CG", 1), ("CG", "CD", 1), ("CD", "OE1", 2),
+         ("CD", "OE2", 1)]
+    ),
+    amino_acid!(
+        "ASN", 'N',
+        [("CB", C), ("CG", C), ("OD1", O), ("ND2", N)],
+        [("CA", "CB", 1), ("CB", "CG", 1), ("CG", "OD1", 2), ("CG", "ND2", 1)]
+    ),
+    amino_acid!(
+        "GLN", 'Q',
+        [("CB", C), ("CG", C), ("CD", C), ("OE1", O), ("NE2", N)],
+        [("CA", "CB", 1), ("CB", "CG", 1), ("CG", "CD", 1), ("CD", "OE1", 2),
+         ("CD", "NE2", 1)]
+    ),
+    amino_acid!(
+        "LYS", 'K',
+        [("CB", C), ("CG", C), ("CD", C), ("CE", C), ("NZ", N)],
+        [("CA", "CB", 1), ("CB", "CG", 1), ("CG", "CD", 1), ("CD", "CE", 1),
+         ("CE", "NZ", 1)]
+    ),
+    amino_acid!(
+        "ARG", 'R',
+        [("CB", C), ("CG", C), ("CD", C), ("NE", N), ("CZ", C), ("NH1", N), ("NH2", N)],
+        [("CA", "CB", 1), ("CB", "CG", 1), ("CG", "CD", 1), ("CD", "NE", 1),
+         ("NE", "CZ", 1), ("CZ", "NH1", 1), ("CZ", "NH2", 2)]
+    ),
+    adenine!("DA", Deoxyribonucleotide),
+    guanine!("DG", Deoxyribonucleotide),
+    cytosine!("DC", Deoxyribonucleotide),
+    pyrimidinedione!("DT", 'T', Deoxyribonucleotide, [("C7", C)], [("C5", "C7", 1)]),
+    adenine!("A", Ribonucleotide),
+    guanine!("G", Ribonucleotide),
+    cytosine!("C", Ribonucleotide),
+    pyrimidinedione!("U", 'U', Ribonucleotide, [], []),
+];
+
+// End of File
@@ -0,0 +1,135 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! MDL molfiles, and SD files (a series of molfiles separated by `$$$$`
+//! lines), as written by most chemistry software.  Only the V2000 format is
+//! read, and only the first molecule of an SD file.
+//!
+//! A molfile has three header lines, a counts line, then a line for each atom
+//! and a line for each bond, in fixed-width columns:
+//!
+//! ```text
+//! Water
+//!
+//!
+//!   3  2  0  0  0  0            999 V2000
+//!     0.0000    0.0000    0.0000 O   0  0  0  0  0  0  0  0  0  0  0  0
+//!     0.9572    0.0000    0.0000 H   0  0  0  0  0  0  0  0  0  0  0  0
+//!    -0.2400    0.9266    0.0000 H   0  0  0  0  0  0  0  0  0  0  0  0
+//!   1  2  1  0
+//!   1  3  1  0
+//! M  END
+//! ```
+//!
+//! Aromatic bonds (type 4) are read as single bonds, as which of them are
+//! double is not recorded.
+
+use std::ops::Range;
+
+use periodic_table::Element;
+use ultraviolet::Vec3;
+
+use crate::build_script::ParseError;
+use crate::edit::AtomImport;
+
+// The lines before the counts line.
+const HEADER_LINES: usize = 3;
+
+/// Reads the first molecule of a molfile or SD file as an import of its atoms
+/// and bonds.
+pub fn parse(name: &str, text: &str) -> Result<AtomImport, ParseError> {
+    let lines: Vec<&str> = text.lines().collect();
+    let line = |index: usize| {
+        lines.get(index).copied().ok_or_else(|| ParseError {
+            line: index + 1,
+            message: "the file ends too soon".to_owned(),
+        })
+    };
+
+    let counts = line(HEADER_LINES)?;
+    if counts.contains("V3000") {
+        return Err(ParseError {
+            line: HEADER_LINES + 1,
+            message: "V3000 molfiles are not supported".to_owned(),
+        });
+    }
+    let atom_count: usize = number(counts, 0..3, HEADER_LINES)?;
+    let bond_count: usize = number(counts, 3..6, HEADER_LINES)?;
+
+    let first_atom = HEADER_LINES + 1;
+    let atoms = (first_atom..first_atom + atom_count)
+        .map(|index| {
+            let text = line(index)?;
+            let pos = Vec3::new(
+                number(text, 0..10, index)?,
+                number(text, 10..20, index)?,
+                number(text, 20..30, index)?,
+            );
+            let symbol = field(text, 31..34);
+            let element = Element::from_symbol(symbol).ok_or_else(|| ParseError {
+                line: index + 1,
+                message: format!("unknown element `{}`", symbol),
+            })?;
+            Ok((element, pos))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let first_bond = first_atom + atom_count;
+    let bonds = (first_bond..first_bond + bond_count)
+        .map(|index| {
+            let text = line(index)?;
+            let atom = |range: Range<usize>| {
+                let number: usize = number(text, range, index)?;
+                match number.checked_sub(1) {
+                    Some(atom) if atom < atom_count => Ok(atom),
+                    _ => Err(ParseError {
+                        line: index + 1,
+                        message: format!("there is no atom {}", number),
+                    }),
+                }
+            };
+            let order = match number::<u8>(text, 6..9, index)? {
+                order @ 1..=3 => order,
+                4 => 1,
+                other => {
+                    return Err(ParseError {
+                        line: index + 1,
+                        message: format!("unsupported bond type {}", other),
+                    })
+                }
+            };
+            Ok((atom(0..3)?, atom(3..6)?, order))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(AtomImport {
+        name: name.to_owned(),
+        atoms,
+        bonds,
+    })
+}
+
+// The columns `range` of `line`, without surrounding spaces.  Columns past the
+// end of the line are empty.
+fn field(line: &str, range: Range<usize>) -> &str {
+    let end = range.end.min(line.len());
+    line.get(range.start.min(end)..end)
+        .unwrap_or_default()
+        .trim()
+}
+
+// Reads the columns `range` of the line at `index` as a number.
+fn number<T: std::str::FromStr>(
+    line: &str,
+    range: Range<usize>,
+    index: usize,
+) -> Result<T, ParseError> {
+    let text = field(line, range);
+    text.parse().map_err(|_| ParseError {
+        line: index + 1,
+        message: format!("expected a number, not `{}`", text),
+    })
+}
+
+// End of File
@@ -0,0 +1,192 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Commands that change which atoms of a molecule are selected, by following
+//! its bonds or by distance.  Selections are sets of atom specifiers, so they
+//! stay valid as edits are added or replayed; atoms that no longer exist are
+//! ignored.
+
+use std::collections::HashSet;
+
+use common::ids::{AtomSpecifier, EditId};
+use petgraph::visit::Bfs;
+use serde::{Deserialize, Serialize};
+
+use crate::edit::{Edit, EditContext as _};
+use crate::molecule::Molecule;
+use crate::spatial::SpatialIndex;
+use crate::AtomIndex;
+
+/// A set of atoms in one molecule.
+pub type AtomSelection = HashSet<AtomSpecifier>;
+
+/// A set of atoms saved with a molecule under a name, such as "binding site",
+/// so that it can be selected again later or used as the targets of an edit.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct NamedSelection {
+    pub name: String,
+    /// The atoms, in a fixed order so that saved files do not change when
+    /// the selection does not.
+    pub atoms: Vec<AtomSpecifier>,
+}
+
+impl NamedSelection {
+    pub fn new(name: impl Into<String>, atoms: impl IntoIterator<Item = AtomSpecifier>) -> Self {
+        let mut atoms: Vec<AtomSpecifier> = atoms.into_iter().collect();
+        atoms.sort_by_cached_key(|atom| atom.to_string());
+        atoms.dedup();
+        Self {
+            name: name.into(),
+            atoms,
+        }
+    }
+
+    /// The atoms that exist in `molecule` as it is now.
+    pub fn select(&self, molecule: &Molecule) -> AtomSelection {
+        self.atoms
+            .iter()
+            .filter(|atom| molecule.find_atom(atom).is_some())
+            .cloned()
+            .collect()
+    }
+}
+
+/// Adds every atom within `shells` bonds of the selection.
+pub fn grow(molecule: &Molecule, selection: &AtomSelection, shells: usize) -> AtomSelection {
+    let mut grown = selection.clone();
+    let mut frontier: Vec<AtomIndex> = indices(molecule, selection).collect();
+
+    for _ in 0..shells {
+        let mut next = Vec::new();
+        for index in frontier {
+            for neighbor in molecule.graph.neighbors(index) {
+                if grown.insert(molecule.spec(neighbor)) {
+                    next.push(neighbor);
+                }
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        frontier = next;
+    }
+
+    grown
+}
+
+/// Removes the selected atoms that are bonded to an unselected atom, `shells`
+/// times over.  Atoms with no unselected neighbors, such as a whole selected
+/// molecule, are never removed.
+pub fn shrink(molecule: &Molecule, selection: &AtomSelection, shells: usize) -> AtomSelection {
+    let mut shrunk: AtomSelection = indices(molecule, selection)
+        .map(|index| molecule.spec(index))
+        .collect();
+
+    for _ in 0..shells {
+        let boundary: Vec<AtomSpecifier> = indices(molecule, &shrunk)
+            .filter(|index| {
+                molecule
+                    .graph
+                    .neighbors(*index)
+                    .any(|neighbor| !shrunk.contains(&molecule.spec(neighbor)))
+            })
+            .map(|index| molecule.spec(index))
+            .collect();
+        if boundary.is_empty() {
+            break;
+        }
+        for spec in &boundary {
+            shrunk.remove(spec);
+        }
+    }
+
+    shrunk
+}
+
+/// Selects every atom connected to the selection by any chain of bonds.
+pub fn connected(molecule: &Molecule, selection: &AtomSelection) -> AtomSelection {
+    let mut connected = AtomSelection::new();
+
+    for start in indices(molecule, selection) {
+        if connected.contains(&molecule.spec(start)) {
+            continue;
+        }
+        let mut bfs = Bfs::new(&molecule.graph, start);
+        while let Some(index) = bfs.next(&molecule.graph) {
+            connected.insert(molecule.spec(index));
+        }
+    }
+
+    connected
+}
+
+/// Selects every atom of the molecule that is not selected.
+pub fn invert(molecule: &Molecule, selection: &AtomSelection) -> AtomSelection {
+    molecule
+        .graph
+        .node_indices()
+        .map(|index| molecule.spec(index))
+        .filter(|spec| !selection.contains(spec))
+        .collect()
+}
+
+/// Adds every atom within `distance` angstroms of a selected atom, whether or
+/// not it is bonded to the selection.  This is useful for picking out the
+/// surroundings of a site, e.g. a shell of solvent.
+pub fn within_distance(
+    molecule: &Molecule,
+    selection: &AtomSelection,
+    distance: f32,
+) -> AtomSelection {
+    let index = SpatialIndex::new(
+        molecule
+            .graph
+            .node_indices()
+            .map(|index| (index.index(), molecule.dense_positions()[index.index()])),
+        distance.max(1.0),
+    );
+
+    let mut near = selection.clone();
+    for spec in selection {
+        let Some(pos) = molecule.pos(spec) else {
+            continue;
+        };
+        for other in index.within(*pos, distance) {
+            near.insert(molecule.spec(AtomIndex::new(other)));
+        }
+    }
+
+    near
+}
+
+/// The atoms that the edit `id` refers to or made, of those that exist: where
+/// to look when the edit did not do what was intended.
+pub fn edit_atoms(molecule: &Molecule, id: EditId, edit: &Edit) -> AtomSelection {
+    let mut atoms: AtomSelection = edit
+        .references()
+        .into_iter()
+        .filter(|spec| molecule.atom_index(spec).is_some())
+        .cloned()
+        .collect();
+    atoms.extend(
+        molecule
+            .graph
+            .node_indices()
+            .map(|index| molecule.spec(index))
+            .filter(|spec| spec.edit_id() == id),
+    );
+    atoms
+}
+
+// The graph indices of the selected atoms that still exist.
+fn indices<'a>(
+    molecule: &'a Molecule,
+    selection: &'a AtomSelection,
+) -> impl Iterator<Item = AtomIndex> + 'a {
+    selection
+        .iter()
+        .filter_map(|spec| molecule.atom_index(spec))
+}
+
+// End of File
@@ -0,0 +1,60 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A spatial index for finding the points near a given position without
+//! checking every point.
+//!
+//! The points are sorted into a grid of cubic cells.  A search only looks at
+//! the cells that overlap the search radius, so it is fastest when the radius
+//! is close to the cell size.
+
+use std::collections::HashMap;
+
+use ultraviolet::Vec3;
+
+pub struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<[i32; 3], Vec<(usize, Vec3)>>,
+}
+
+impl SpatialIndex {
+    /// Indexes `points`, each of which is identified by a number (e.g. an atom
+    /// index) that searches return.
+    pub fn new(points: impl IntoIterator<Item = (usize, Vec3)>, cell_size: f32) -> Self {
+        let mut index = Self {
+            cell_size,
+            cells: HashMap::new(),
+        };
+        for (id, pos) in points {
+            index
+                .cells
+                .entry(index.cell_of(pos))
+                .or_default()
+                .push((id, pos));
+        }
+        index
+    }
+
+    /// Lists the points within `radius` of `center`, in no particular order.
+    pub fn within(&self, center: Vec3, radius: f32) -> impl Iterator<Item = usize> + '_ {
+        let reach = (radius / self.cell_size).ceil() as i32;
+        let [x, y, z] = self.cell_of(center);
+        let radius_sq = radius * radius;
+
+        (-reach..=reach)
+            .flat_map(move |dx| (-reach..=reach).map(move |dy| (dx, dy)))
+            .flat_map(move |(dx, dy)| (-reach..=reach).map(move |dz| [x + dx, y + dy, z + dz]))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .filter(move |(_, pos)| (*pos - center).mag_sq() <= radius_sq)
+            .map(|(id, _)| *id)
+    }
+
+    fn cell_of(&self, pos: Vec3) -> [i32; 3] {
+        let cell = pos / self.cell_size;
+        [cell.x, cell.y, cell.z].map(|c| c.floor() as i32)
+    }
+}
+
+// End of File
@@ -0,0 +1,100 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Checks on the bonds of a molecule: how far each is stretched or compressed
+//! from the length its force field would give it, and which atoms have more
+//! bonds than their valence allows.  Feature edits can leave a molecule in a
+//! state that relaxation cannot fix (e.g. a bond to an atom on the far side of
+//! the molecule), and these make such problems easy to spot.
+
+use std::collections::HashMap;
+
+use common::ids::AtomSpecifier;
+use petgraph::visit::{EdgeRef as _, IntoEdgeReferences as _};
+
+use crate::dynamics::ForceFieldKind;
+use crate::molecule::Molecule;
+use crate::{AtomIndex, BondOrder};
+
+/// How far one bond is from its ideal length.
+#[derive(Clone, Debug)]
+pub struct BondStrain {
+    pub atoms: (AtomSpecifier, AtomSpecifier),
+    pub order: BondOrder,
+    /// The bond's length, in angstroms.
+    pub length: f32,
+    /// The length the force field relaxes the bond towards, in angstroms.
+    pub ideal: f32,
+}
+
+impl BondStrain {
+    /// The relative deviation from the ideal length: positive if the bond is
+    /// stretched, negative if it is compressed.
+    pub fn strain(&self) -> f32 {
+        (self.length - self.ideal) / self.ideal
+    }
+}
+
+/// The strain of every bond of `molecule`, with ideal lengths taken from
+/// `force_field`.
+pub fn bond_strains(molecule: &Molecule, force_field: ForceFieldKind) -> Vec<BondStrain> {
+    let graph = &molecule.graph;
+    let positions = molecule.dense_positions();
+    let ideal = force_field.ideal_bond_lengths(graph);
+    graph
+        .edge_references()
+        .map(|edge| {
+            let (a, b) = (edge.source(), edge.target());
+            BondStrain {
+                atoms: (molecule.spec(a), molecule.spec(b)),
+                order: *edge.weight(),
+                length: (positions[a.index()] - positions[b.index()]).mag(),
+                ideal: ideal[edge.id().index()],
+            }
+        })
+        .collect()
+}
+
+/// The strain of each atom's most strained bond, keeping its sign.  Atoms
+/// without bonds are left out.
+pub fn atom_strains(molecule: &Molecule, force_field: ForceFieldKind) -> HashMap<AtomIndex, f32> {
+    let graph = &molecule.graph;
+    let positions = molecule.dense_positions();
+    let ideal = force_field.ideal_bond_lengths(graph);
+    let mut strains: HashMap<AtomIndex, f32> = HashMap::new();
+    for edge in graph.edge_references() {
+        let (a, b) = (edge.source(), edge.target());
+        let ideal = ideal[edge.id().index()];
+        let strain = ((positions[a.index()] - positions[b.index()]).mag() - ideal) / ideal;
+        for atom in [a, b] {
+            let worst = strains.entry(atom).or_insert(0.0);
+            if strain.abs() > worst.abs() {
+                *worst = strain;
+            }
+        }
+    }
+    strains
+}
+
+/// The atoms whose bonds add up to more than their element's standard
+/// valence.  Elements without a standard valence are never reported.
+pub fn overbonded_atoms(molecule: &Molecule) -> Vec<AtomSpecifier> {
+    let graph = &molecule.graph;
+    graph
+        .node_indices()
+        .filter(|index| {
+            let Some(valence) = graph[*index].element.standard_valence() else {
+                return false;
+            };
+            let total: usize = graph
+                .edges(*index)
+                .map(|edge| *edge.weight() as usize)
+                .sum();
+            total > valence as usize
+        })
+        .map(|index| molecule.spec(index))
+        .collect()
+}
+
+// End of File
@@ -0,0 +1,138 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Rigid superposition of one set of points onto another, as used to align two
+//! variants of a design before comparing them.
+
+use ultraviolet::{Mat4, Rotor3, Vec3};
+
+/// The rigid motion that best superposes one set of points onto another, in the
+/// least-squares sense.
+#[derive(Clone, Copy, Debug)]
+pub struct Superposition {
+    /// Applied first, about the origin.
+    pub rotation: Rotor3,
+    /// Applied after the rotation.
+    pub translation: Vec3,
+    /// The root-mean-square distance between the corresponding points once
+    /// superposed, in angstroms.
+    pub rmsd: f32,
+}
+
+impl Superposition {
+    pub fn apply(&self, point: Vec3) -> Vec3 {
+        self.rotation * point + self.translation
+    }
+
+    pub fn transform(&self) -> Mat4 {
+        Mat4::from_translation(self.translation) * self.rotation.into_matrix().into_homogeneous()
+    }
+}
+
+/// Finds the rotation and translation that move each point of `mobile` as close
+/// as possible to the corresponding point of `target`, by Horn's quaternion
+/// method (equivalent to the Kabsch algorithm, but with no need for an SVD).
+/// Returns `None` if the slices differ in length or are empty.
+pub fn superpose(mobile: &[Vec3], target: &[Vec3]) -> Option<Superposition> {
+    if mobile.len() != target.len() || mobile.is_empty() {
+        return None;
+    }
+
+    let centroid =
+        |points: &[Vec3]| points.iter().fold(Vec3::zero(), |sum, p| sum + *p) / points.len() as f32;
+    let (mobile_centroid, target_centroid) = (centroid(mobile), centroid(target));
+
+    // The cross-covariance matrix of the centred points, in double precision as
+    // it sums over every atom.
+    let mut s = [[0.0f64; 3]; 3];
+    for (m, t) in mobile.iter().zip(target) {
+        let (m, t) = (*m - mobile_centroid, *t - target_centroid);
+        let (m, t) = ([m.x, m.y, m.z], [t.x, t.y, t.z]);
+        for (row, m) in s.iter_mut().zip(m) {
+            for (entry, t) in row.iter_mut().zip(t) {
+                *entry += m as f64 * t as f64;
+            }
+        }
+    }
+
+    let [[xx, xy, xz], [yx, yy, yz], [zx, zy, zz]] = s;
+    let n = [
+        [xx + yy + zz, yz - zy, zx - xz, xy - yx],
+        [yz - zy, xx - yy - zz, xy + yx, zx + xz],
+        [zx - xz, xy + yx, -xx + yy - zz, yz + zy],
+        [xy - yx, zx + xz, yz + zy, -xx - yy + zz],
+    ];
+    let [w, x, y, z] = largest_eigenvector(n);
+
+    let rotation =
+        Rotor3::from_quaternion_array([x as f32, y as f32, z as f32, w as f32]).normalized();
+    let translation = target_centroid - rotation * mobile_centroid;
+    let mut superposition = Superposition {
+        rotation,
+        translation,
+        rmsd: 0.0,
+    };
+
+    let squared_error: f32 = mobile
+        .iter()
+        .zip(target)
+        .map(|(m, t)| (superposition.apply(*m) - *t).mag_sq())
+        .sum();
+    superposition.rmsd = (squared_error / mobile.len() as f32).sqrt();
+
+    Some(superposition)
+}
+
+/// Finds the eigenvector of a symmetric 4x4 matrix with the largest eigenvalue,
+/// using cyclic Jacobi rotations.
+fn largest_eigenvector(mut a: [[f64; 4]; 4]) -> [f64; 4] {
+    let mut v = [[0.0; 4]; 4];
+    for (i, row) in v.iter_mut().enumerate() {
+        row[i] = 1.0;
+    }
+
+    for _ in 0..50 {
+        let off_diagonal: f64 = (0..4)
+            .flat_map(|p| (p + 1..4).map(move |q| (p, q)))
+            .map(|(p, q)| a[p][q] * a[p][q])
+            .sum();
+        if off_diagonal < 1e-22 {
+            break;
+        }
+
+        for p in 0..4 {
+            for q in p + 1..4 {
+                if a[p][q] == 0.0 {
+                    continue;
+                }
+                // Choose the rotation that zeroes a[p][q].
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+
+                for row in &mut a {
+                    let (ap, aq) = (row[p], row[q]);
+                    row[p] = c * ap - s * aq;
+                    row[q] = s * ap + c * aq;
+                }
+                let (row_p, row_q) = (a[p], a[q]);
+                a[p] = std::array::from_fn(|k| c * row_p[k] - s * row_q[k]);
+                a[q] = std::array::from_fn(|k| s * row_p[k] + c * row_q[k]);
+                for row in &mut v {
+                    let (vp, vq) = (row[p], row[q]);
+                    row[p] = c * vp - s * vq;
+                    row[q] = s * vp + c * vq;
+                }
+            }
+        }
+    }
+
+    let largest = (0..4)
+        .max_by(|&i, &j| a[i][i].total_cmp(&a[j][j]))
+        .unwrap_or(0);
+    [v[0][largest], v[1][largest], v[2][largest], v[3][largest]]
+}
+
+// End of File
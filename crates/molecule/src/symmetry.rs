@@ -0,0 +1,171 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Structures built from symmetry: an asymmetric unit, and copies of it (its
+//! symmetry mates) placed by the operations of a point or space group.
+//!
+//! The copies are made by a [`SymmetryCopy`] edit, which replicates whatever
+//! the edits before it built.  Edits inserted before it are therefore
+//! replicated to every mate, which is how symmetry-propagating edits work (see
+//! [`MoleculeEditor::add_edit`](crate::MoleculeEditor::add_edit)).  A copied
+//! atom's specifier is the original's with a pattern instance for the copy
+//! pushed onto its path, so an atom of a mate can always be traced back to the
+//! atom of the asymmetric unit it was copied from.
+
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+
+use common::ids::{AtomSpecifier, EditId, PatternInstanceId};
+use serde::{Deserialize, Serialize};
+use ultraviolet::{Bivec3, Rotor3, Vec3};
+
+use crate::edit::{EditContext, EditError, ReferenceType};
+
+/// One operation of a symmetry group: a rotation about the origin, followed by
+/// a translation.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct SymmetryOperation {
+    pub rotation: Rotor3,
+    pub translation: Vec3,
+}
+
+impl SymmetryOperation {
+    /// A rotation by `angle` radians about the line through `center` along
+    /// `axis`.
+    pub fn rotation_about(axis: Vec3, center: Vec3, angle: f32) -> Self {
+        let plane = Bivec3::from_normalized_axis(axis.normalized());
+        let rotation = Rotor3::from_angle_plane(angle, plane);
+        Self {
+            rotation,
+            translation: center - rotation * center,
+        }
+    }
+
+    pub fn translation(translation: Vec3) -> Self {
+        Self {
+            rotation: Rotor3::identity(),
+            translation,
+        }
+    }
+
+    pub fn apply(&self, point: Vec3) -> Vec3 {
+        self.rotation * point + self.translation
+    }
+}
+
+/// Copies every atom in the molecule, along with the bonds between them, once
+/// for each operation.  The identity is implied, so it should not be listed.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SymmetryCopy {
+    /// A short description of the symmetry, e.g. `C3` or a space group.
+    pub name: String,
+    pub operations: Vec<SymmetryOperation>,
+}
+
+impl SymmetryCopy {
+    /// `n`-fold rotational symmetry about the line through `center` along
+    /// `axis`.
+    pub fn rotational(n: usize, axis: Vec3, center: Vec3) -> Self {
+        Self {
+            name: format!("C{}", n),
+            operations: (1..n)
+                .map(|k| SymmetryOperation::rotation_about(axis, center, TAU * k as f32 / n as f32))
+                .collect(),
+        }
+    }
+}
+
+/// Names the copy of `atom` made by the `instance`th operation of the
+/// symmetry copy `edit_id`.
+pub fn mate(atom: &AtomSpecifier, edit_id: EditId, instance: usize) -> AtomSpecifier {
+    let mut path = atom.path.to_vec();
+    path.push(PatternInstanceId {
+        owner_id: edit_id,
+        instance,
+    });
+    AtomSpecifier {
+        path: path.into(),
+        child_index: atom.child_index,
+    }
+}
+
+/// Names the atom of the asymmetric unit of the symmetry copy `edit_id` that
+/// `atom` was copied from, or `atom` itself if it is not a copy.
+pub fn asymmetric_unit_atom(atom: &AtomSpecifier, edit_id: EditId) -> AtomSpecifier {
+    AtomSpecifier {
+        path: atom
+            .path
+            .iter()
+            .filter(|instance| instance.owner_id != edit_id)
+            .cloned()
+            .collect(),
+        child_index: atom.child_index,
+    }
+}
+
+pub(crate) fn spawn_symmetry_copy(
+    copy: &SymmetryCopy,
+    edit_id: &EditId,
+    commands: &mut dyn EditContext,
+) -> Result<(), EditError> {
+    let unit = commands.atoms();
+    let indices: HashMap<&AtomSpecifier, usize> =
+        unit.iter().enumerate().map(|(i, atom)| (atom, i)).collect();
+    // Each bond of the asymmetric unit, once.
+    let bonds: Vec<_> = unit
+        .iter()
+        .enumerate()
+        .flat_map(|(index, atom)| {
+            commands
+                .bonds(atom)
+                .into_iter()
+                .filter(|(neighbor, _)| indices.get(neighbor).is_some_and(|&n| n > index))
+                .map(|(neighbor, order)| (atom.clone(), neighbor, order))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    for (instance, operation) in copy.operations.iter().enumerate() {
+        for atom in &unit {
+            let node = commands
+                .find_atom(atom)
+                .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+            let (element, pinned, chain, source_line) =
+                (node.element, node.pinned, node.chain, node.source_line);
+            let residue = node.residue.clone();
+            let head = commands
+                .head(atom)
+                .map(|head| mate(&head, *edit_id, instance));
+            let pos = *commands
+                .pos(atom)
+                .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+
+            let spec = mate(atom, *edit_id, instance);
+            commands.add_atom(element, operation.apply(pos), spec.clone(), head)?;
+            if pinned {
+                commands.set_pinned(&spec, true)?;
+            }
+            if chain.is_some() {
+                commands.set_chain(&spec, chain)?;
+            }
+            if residue.is_some() {
+                commands.set_residue(&spec, residue)?;
+            }
+            if source_line.is_some() {
+                commands.set_source_line(&spec, source_line)?;
+            }
+        }
+        for (a, b, order) in &bonds {
+            commands.create_bond(
+                &mate(a, *edit_id, instance),
+                &mate(b, *edit_id, instance),
+                *order,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+// End of File
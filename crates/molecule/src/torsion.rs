@@ -0,0 +1,111 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Turning part of a molecule about a bond.  A [`SetTorsion`] poses a flexible
+//! molecule by setting the dihedral angle of four bonded atoms directly, rather
+//! than by moving atoms one at a time and fighting the minimizer to keep the
+//! bonds intact.  Everything on one side of the middle bond turns rigidly
+//! about it, so bond lengths and angles are kept.
+
+use std::collections::{HashSet, VecDeque};
+
+use common::ids::AtomSpecifier;
+use serde::{Deserialize, Serialize};
+use ultraviolet::{Bivec3, Rotor3};
+
+use crate::edit::{EditContext, EditError, ReferenceType};
+
+/// Sets the dihedral angle of the chain `a-b-c-d` about the `b-c` bond, in
+/// degrees, by turning one side of the bond.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SetTorsion {
+    pub atoms: [AtomSpecifier; 4],
+    pub angle: f32,
+}
+
+/// The dihedral angle of the chain `a-b-c-d` about the `b-c` bond, in degrees
+/// from -180 to 180, or `None` if an atom does not exist.
+pub fn dihedral(atoms: &[AtomSpecifier; 4], commands: &dyn EditContext) -> Option<f32> {
+    let [a, b, c, d] = [0, 1, 2, 3].map(|i| commands.pos(&atoms[i]).copied());
+    let (b1, b2, b3) = (b? - a?, c? - b?, d? - c?);
+    let n1 = b1.cross(b2);
+    let n2 = b2.cross(b3);
+    Some((b2.mag() * b1.dot(n2)).atan2(n1.dot(n2)).to_degrees())
+}
+
+/// The atoms that turn when the bond from `fixed` to `moving` is twisted: the
+/// `moving` atom and everything reached from it without crossing that bond.
+/// Returns `None` if the bond is in a ring, so that both sides are the same.
+pub fn rotating_side(
+    fixed: &AtomSpecifier,
+    moving: &AtomSpecifier,
+    commands: &dyn EditContext,
+) -> Option<Vec<AtomSpecifier>> {
+    let mut side = vec![moving.clone()];
+    let mut visited = HashSet::from([moving.clone()]);
+    let mut queue = VecDeque::from([moving.clone()]);
+    while let Some(atom) = queue.pop_front() {
+        for (neighbor, _) in commands.bonds(&atom) {
+            if atom == *moving && neighbor == *fixed {
+                continue;
+            }
+            if neighbor == *fixed {
+                return None;
+            }
+            if visited.insert(neighbor.clone()) {
+                side.push(neighbor.clone());
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    Some(side)
+}
+
+pub(crate) fn spawn_torsion(
+    torsion: &SetTorsion,
+    commands: &mut dyn EditContext,
+) -> Result<(), EditError> {
+    let [a, b, c, d] = &torsion.atoms;
+    let current = dihedral(&torsion.atoms, commands)
+        .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+    if !commands.bonds(b).iter().any(|(other, _)| other == c) {
+        return Err(EditError::BrokenReference(ReferenceType::Atom));
+    }
+
+    // The side away from the molecule's root turns, so that the rest of the
+    // molecule stays where it was built: atoms are placed from their head, so
+    // if `b` was placed from `c`, `b` is on the far side.  The axis points
+    // from the fixed atom to the moving one, so the turn is the same either
+    // way.
+    let b_from_c = commands.head(b).as_ref() == Some(c);
+    let (fixed, moving) = if b_from_c { (c, b) } else { (b, c) };
+    let side = rotating_side(fixed, moving, commands).ok_or(EditError::BondInRing)?;
+    if side.contains(a) == side.contains(d) {
+        // `a` and `d` are on the same side, so the dihedral cannot change.
+        return Err(EditError::BondInRing);
+    }
+
+    let center = *commands
+        .pos(fixed)
+        .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+    let axis = (*commands
+        .pos(moving)
+        .ok_or(EditError::BrokenReference(ReferenceType::Atom))?
+        - center)
+        .normalized();
+    let turn = (torsion.angle - current).to_radians();
+    let rotation = Rotor3::from_angle_plane(turn, Bivec3::from_normalized_axis(axis));
+    for atom in &side {
+        let pos = *commands
+            .pos(atom)
+            .ok_or(EditError::BrokenReference(ReferenceType::Atom))?;
+        commands.set_pos(atom, center + rotation * (pos - center))?;
+    }
+    // Relaxation would otherwise turn the bond back to the force field's
+    // preferred angle.
+    commands.restrain_torsion(&torsion.atoms, torsion.angle);
+    Ok(())
+}
+
+// End of File
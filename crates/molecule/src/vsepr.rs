@@ -1,6 +1,11 @@
 use std::f32;
 use std::f32::consts::PI;
 
+use periodic_table::Element;
+use ultraviolet::Vec3;
+
+use crate::BondOrder;
+
 pub struct Angles {
     // Angle from the +x axis to the projection of the target in the meridian plane, measured counterclockwise (i.e. the +y axis is at pi/2 radians)
     pub azimuthal: f32,
@@ -8,7 +13,6 @@ pub struct Angles {
     pub polar: f32,
 }
 
-#[allow(dead_code)]
 pub static TETRAHEDRAL_ANGLE: f32 = 1.910_633_2; // acos(-1 / 3)
 #[allow(dead_code)]
 pub static BOND_SHAPES: [Option<&[Angles]>; 7] = [
@@ -116,3 +120,132 @@ pub static BOND_SHAPES: [Option<&[Angles]>; 7] = [
     ]),
     // TODO: Investigate wether or not we need to support hypervalent bonding or if this is enough.
 ];
+
+/// The number of electron domains around an atom of `element` with bonds of
+/// the given orders: one per bonded neighbor (a multiple bond occupies a single
+/// domain), plus one for each bond that its standard valence has room for,
+/// plus one for each of its lone pairs.  The lone pairs are what bend a
+/// hydroxyl oxygen and make ammonia pyramidal.
+pub fn domain_count(element: Element, orders: impl IntoIterator<Item = BondOrder>) -> usize {
+    let (neighbors, order) = orders
+        .into_iter()
+        .fold((0, 0), |(neighbors, total), order| {
+            (neighbors + 1, total + order as usize)
+        });
+    let Some(valence) = element.standard_valence().map(usize::from) else {
+        return neighbors;
+    };
+    let unfilled = valence.saturating_sub(order);
+    // The valence electrons left over once the atom's bonds, including those
+    // it has room for, have taken one each.
+    let lone_pairs = element.valence_electrons().map_or(0, |electrons| {
+        usize::from(electrons).saturating_sub(order.max(valence)) / 2
+    });
+    neighbors + unfilled + lone_pairs
+}
+
+/// The angle between any two bonds of an atom with `domains` electron domains
+/// in the shapes this module supports: linear, trigonal planar and tetrahedral.
+pub fn ideal_angle(domains: usize) -> Option<f32> {
+    match domains {
+        2 => Some(PI),
+        3 => Some(2.0 * PI / 3.0),
+        4 => Some(TETRAHEDRAL_ANGLE),
+        _ => None,
+    }
+}
+
+/// Returns some unit vector perpendicular to `v`.
+fn perpendicular(v: Vec3) -> Vec3 {
+    let other = if v.x.abs() < 0.9 {
+        Vec3::unit_x()
+    } else {
+        Vec3::unit_y()
+    };
+    v.cross(other).normalized()
+}
+
+/// Normalizes `v`, or returns `fallback()` if `v` is too short to have a
+/// meaningful direction.
+fn normalized_or(v: Vec3, fallback: impl FnOnce() -> Vec3) -> Vec3 {
+    if v.mag_sq() < 1e-6 {
+        fallback()
+    } else {
+        v.normalized()
+    }
+}
+
+/// Given the directions of the bonds an atom already has, returns the directions
+/// of the `domains - existing.len()` bonds it is missing, such that all of the
+/// atom's electron domains are arranged as VSEPR theory predicts: linear for two
+/// domains, trigonal planar for three, and tetrahedral for four.
+///
+/// An atom with no bonds is oriented along the molecule's +z axis.  At most four
+/// domains are supported; nothing is returned for more, or if the atom already
+/// has `domains` bonds.
+pub fn free_directions(existing: &[Vec3], domains: usize) -> Vec<Vec3> {
+    if domains > 4 || existing.len() >= domains {
+        return Vec::new();
+    }
+
+    // A lone domain has no angle to another; any value will do.
+    let ideal = ideal_angle(domains).unwrap_or(PI);
+
+    let existing: Vec<Vec3> = existing.iter().map(|d| d.normalized()).collect();
+    match existing[..] {
+        [] => {
+            let mut directions = vec![Vec3::unit_z()];
+            directions.extend(free_directions(&[Vec3::unit_z()], domains));
+            directions
+        }
+        [a] => {
+            // Fan the remaining domains evenly around `a`, each at the ideal
+            // angle from it.
+            let p = perpendicular(a);
+            let q = a.cross(p);
+            let count = domains - 1;
+            (0..count)
+                .map(|i| {
+                    let phi = 2.0 * PI * i as f32 / count as f32;
+                    (p * phi.cos() + q * phi.sin()) * ideal.sin() + a * ideal.cos()
+                })
+                .collect()
+        }
+        [a, b] => {
+            let bisector = normalized_or(-(a + b), || perpendicular(a));
+            if domains == 3 {
+                return vec![bisector];
+            }
+
+            // The two remaining tetrahedral domains lie in the plane that
+            // contains the bisector and is perpendicular to `a` and `b`'s plane.
+            let normal = normalized_or(bisector.cross(a), || perpendicular(bisector));
+            let half = ideal / 2.0;
+            vec![
+                bisector * half.cos() + normal * half.sin(),
+                bisector * half.cos() - normal * half.sin(),
+            ]
+        }
+        [a, b, c] => {
+            let planar_normal = || normalized_or(a.cross(b), || perpendicular(a));
+            vec![normalized_or(-(a + b + c), planar_normal)]
+        }
+        _ => unreachable!("fewer than four existing domains"),
+    }
+}
+
+/// Chooses the direction for one new bond on an atom that already has bonds in
+/// the `existing` directions and will have `domains` electron domains in total.
+/// Falls back to pointing away from the existing bonds when the shape is not
+/// one that `free_directions` supports.
+pub fn new_bond_direction(existing: &[Vec3], domains: usize) -> Vec3 {
+    free_directions(existing, domains)
+        .first()
+        .copied()
+        .unwrap_or_else(|| {
+            let away = -existing
+                .iter()
+                .fold(Vec3::zero(), |acc, d| acc + d.normalized());
+            normalized_or(away, Vec3::unit_x)
+        })
+}
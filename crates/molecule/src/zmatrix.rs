@@ -0,0 +1,390 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Z-matrices, which place each atom by its distance from an earlier atom, the
+//! angle it makes with a second, and the dihedral it makes with a third.  They
+//! are a common way of exchanging structures in quantum chemistry:
+//!
+//! ```text
+//! O
+//! H 1 0.96
+//! H 1 0.96 2 104.5
+//! ```
+//!
+//! Atoms are referred to by number, counting from 1, or by their label (e.g.
+//! `O1`).  Lengths are in angstroms and angles in degrees.  Values may also be
+//! given by name and defined after the atoms and a blank line, as `name value`
+//! or `name = value`.
+//!
+//! A Z-matrix does not say which atoms are bonded.  Each atom is bonded to the
+//! atom it is placed from if they are close enough to be, and uses that atom as
+//! its head.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+
+use common::ids::{AtomSpecifier, EditId};
+use periodic_table::Element;
+use serde::{Deserialize, Serialize};
+use ultraviolet::Vec3;
+
+use crate::build_script::ParseError;
+use crate::edit::{EditContext, EditError};
+use crate::molecule::Molecule;
+use crate::AtomIndex;
+
+/// How much longer than the sum of their covalent radii two atoms can be apart
+/// and still be bonded.
+const BOND_TOLERANCE: f32 = 1.25;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ZMatrix {
+    pub name: String,
+    pub atoms: Vec<ZMatrixAtom>,
+}
+
+/// A row of a Z-matrix.  Atoms are referred to by their index in the matrix,
+/// counting from 0, and must come before the atom that refers to them.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ZMatrixAtom {
+    pub element: Element,
+    /// The atom this one is placed from, and the distance to it.
+    pub distance: Option<(usize, f32)>,
+    /// A second atom, and the angle it makes with this one at the first, in
+    /// degrees.
+    pub angle: Option<(usize, f32)>,
+    /// A third atom, and the dihedral angle from it to this one about the
+    /// bond between the second and first, in degrees.
+    pub dihedral: Option<(usize, f32)>,
+}
+
+// A row as written, before its values are looked up.
+struct Row<'a> {
+    line: usize,
+    element: Element,
+    references: Vec<(usize, &'a str)>,
+}
+
+impl ZMatrix {
+    /// Reads a Z-matrix in the format described in the module documentation.
+    pub fn parse(name: &str, text: &str) -> Result<Self, ParseError> {
+        let mut rows: Vec<Row> = Vec::new();
+        let mut labels: HashMap<&str, usize> = HashMap::new();
+        let mut variables: HashMap<&str, f32> = HashMap::new();
+        let mut in_variables = false;
+
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| ParseError {
+                line: index + 1,
+                message,
+            };
+            let line = line.split('#').next().unwrap_or_default();
+            let words: Vec<&str> = line
+                .split(|c: char| c.is_whitespace() || c == '=')
+                .filter(|word| !word.is_empty())
+                .collect();
+
+            if words.is_empty() {
+                in_variables |= !rows.is_empty();
+                continue;
+            }
+            if in_variables {
+                let [variable, value] = words[..] else {
+                    return Err(error("expected `name value`".to_owned()));
+                };
+                let value = value
+                    .parse()
+                    .map_err(|_| error(format!("`{}` is not a number", value)))?;
+                variables.insert(variable, value);
+                continue;
+            }
+
+            let label = words[0];
+            let symbol = label.trim_end_matches(|c: char| c.is_ascii_digit());
+            let element = Element::from_symbol(symbol)
+                .ok_or_else(|| error(format!("unknown element `{}`", symbol)))?;
+
+            let expected = 1 + 2 * rows.len().min(3);
+            if words.len() != expected {
+                return Err(error(format!(
+                    "atom {} needs {} references",
+                    rows.len() + 1,
+                    rows.len().min(3)
+                )));
+            }
+
+            let mut references = Vec::new();
+            for pair in words[1..].chunks(2) {
+                let reference = pair[0]
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|n| n.checked_sub(1))
+                    .filter(|n| *n < rows.len())
+                    .or_else(|| labels.get(pair[0]).copied())
+                    .ok_or_else(|| error(format!("there is no earlier atom `{}`", pair[0])))?;
+                if references.iter().any(|(other, _)| *other == reference) {
+                    return Err(error(format!("atom `{}` is used twice", pair[0])));
+                }
+                references.push((reference, pair[1]));
+            }
+
+            labels.insert(label, rows.len());
+            rows.push(Row {
+                line: index + 1,
+                element,
+                references,
+            });
+        }
+
+        if rows.is_empty() {
+            return Err(ParseError {
+                line: text.lines().count().max(1),
+                message: "there are no atoms".to_owned(),
+            });
+        }
+
+        let atoms = rows
+            .into_iter()
+            .map(|row| {
+                let mut references = row.references.iter().map(|(reference, value)| {
+                    let value = lookup(value, &variables).ok_or_else(|| ParseError {
+                        line: row.line,
+                        message: format!("`{}` is neither a number nor defined", value),
+                    })?;
+                    Ok((*reference, value))
+                });
+                Ok(ZMatrixAtom {
+                    element: row.element,
+                    distance: references.next().transpose()?,
+                    angle: references.next().transpose()?,
+                    dihedral: references.next().transpose()?,
+                })
+            })
+            .collect::<Result<_, ParseError>>()?;
+
+        Ok(Self {
+            name: name.to_owned(),
+            atoms,
+        })
+    }
+
+    /// Describes a molecule by internal coordinates.  The atoms are ordered so
+    /// that each is placed from an atom it is bonded to (its head, if it has
+    /// one), apart from the first atom of each fragment.
+    pub fn from_molecule(name: &str, molecule: &Molecule) -> Self {
+        let graph = &molecule.graph;
+        let pos = |index: AtomIndex| molecule.dense_positions()[index.index()];
+
+        // Each atom, with the row of the atom it was reached from, in breadth
+        // first order.
+        let mut order: Vec<(AtomIndex, Option<usize>)> = Vec::new();
+        let mut row_of: HashMap<AtomIndex, usize> = HashMap::new();
+        for start in graph.node_indices() {
+            if row_of.contains_key(&start) {
+                continue;
+            }
+            row_of.insert(start, order.len());
+            order.push((start, None));
+            let mut next = order.len() - 1;
+            while next < order.len() {
+                for neighbor in graph.neighbors(order[next].0) {
+                    if let Entry::Vacant(entry) = row_of.entry(neighbor) {
+                        entry.insert(order.len());
+                        order.push((neighbor, Some(next)));
+                    }
+                }
+                next += 1;
+            }
+        }
+
+        let positions: Vec<Vec3> = order.iter().map(|(index, _)| pos(*index)).collect();
+        let mut atoms: Vec<ZMatrixAtom> = Vec::with_capacity(order.len());
+
+        for (row, (index, parent)) in order.iter().enumerate() {
+            let head = graph[*index]
+                .head
+                .map(|head| row_of[&head])
+                .filter(|head| *head < row);
+            // The first atom of a fragment is placed from the nearest atom.
+            let nearest = || {
+                (0..row).min_by(|a, b| {
+                    let da = (positions[*a] - positions[row]).mag_sq();
+                    let db = (positions[*b] - positions[row]).mag_sq();
+                    da.total_cmp(&db)
+                })
+            };
+            let Some(c) = head.or(*parent).or_else(nearest) else {
+                atoms.push(ZMatrixAtom {
+                    element: graph[*index].element,
+                    distance: None,
+                    angle: None,
+                    dihedral: None,
+                });
+                continue;
+            };
+
+            // Prefer references that follow the chain of atoms back towards
+            // the start, as a chemist would write them.
+            let chain = |row: usize| atoms[row].distance.map(|(reference, _)| reference);
+            let b = chain(c).or_else(|| (0..row).find(|other| *other != c));
+            let a = b.and_then(|b| {
+                let usable: Vec<usize> = chain(b)
+                    .into_iter()
+                    .chain(0..row)
+                    .filter(|other| *other != b && *other != c)
+                    .collect();
+                usable
+                    .iter()
+                    .find(|a| !collinear(positions[**a], positions[b], positions[c]))
+                    .or(usable.first())
+                    .copied()
+            });
+
+            let here = positions[row];
+            atoms.push(ZMatrixAtom {
+                element: graph[*index].element,
+                distance: Some((c, (here - positions[c]).mag())),
+                angle: b.map(|b| (b, angle(positions[b], positions[c], here))),
+                dihedral: a
+                    .zip(b)
+                    .map(|(a, b)| (a, dihedral(positions[a], positions[b], positions[c], here))),
+            });
+        }
+
+        Self {
+            name: name.to_owned(),
+            atoms,
+        }
+    }
+
+    /// Finds the Cartesian position of each atom.  The first atom is at the
+    /// origin, the second on the +z axis from it, and the third in the xz plane.
+    pub fn positions(&self) -> Vec<Vec3> {
+        let mut positions: Vec<Vec3> = Vec::with_capacity(self.atoms.len());
+
+        for atom in &self.atoms {
+            let pos = match (atom.distance, atom.angle, atom.dihedral) {
+                (Some((c, distance)), Some((b, angle)), dihedral) => {
+                    let (b, c) = (positions[b], positions[c]);
+                    let (a, dihedral) = match dihedral {
+                        Some((a, dihedral)) if !collinear(positions[a], b, c) => {
+                            (positions[a], dihedral)
+                        }
+                        // Without a usable third atom, the angle is taken in
+                        // the plane of the second and first atoms and the x
+                        // axis (or y, if they lie along x).
+                        _ => (b + off_axis(c - b), 0.0),
+                    };
+                    place(a, b, c, distance, angle, dihedral)
+                }
+                (Some((c, distance)), None, _) => positions[c] + Vec3::unit_z() * distance,
+                (None, ..) => Vec3::zero(),
+            };
+            positions.push(pos);
+        }
+
+        positions
+    }
+}
+
+/// Writes the Z-matrix with atoms referred to by number.
+impl fmt::Display for ZMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for atom in &self.atoms {
+            write!(f, "{}", atom.element.symbol())?;
+            if let Some((reference, distance)) = atom.distance {
+                write!(f, " {} {:.4}", reference + 1, distance)?;
+            }
+            for (reference, angle) in atom.angle.into_iter().chain(atom.dihedral) {
+                write!(f, " {} {:.3}", reference + 1, angle)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn spawn_zmatrix(
+    zmatrix: &ZMatrix,
+    edit_id: &EditId,
+    commands: &mut dyn EditContext,
+) -> Result<(), EditError> {
+    let mut spec = AtomSpecifier::new(*edit_id);
+    let positions = zmatrix.positions();
+    let mut specs: Vec<AtomSpecifier> = Vec::with_capacity(zmatrix.atoms.len());
+
+    for (atom, pos) in zmatrix.atoms.iter().zip(positions.iter()) {
+        let atom_spec = spec.next_spec();
+        let head = atom.distance.map(|(reference, distance)| {
+            let other = zmatrix.atoms[reference].element;
+            let bonded = distance
+                <= BOND_TOLERANCE * (atom.element.covalent_radius() + other.covalent_radius());
+            (specs[reference].clone(), bonded)
+        });
+
+        match head {
+            Some((head, true)) => {
+                commands.add_bonded_atom(atom.element, *pos, atom_spec.clone(), head, 1)?
+            }
+            _ => commands.add_atom(atom.element, *pos, atom_spec.clone(), None)?,
+        }
+        specs.push(atom_spec);
+    }
+
+    Ok(())
+}
+
+// A number, or a variable, possibly negated.
+fn lookup(value: &str, variables: &HashMap<&str, f32>) -> Option<f32> {
+    if let Ok(number) = value.parse() {
+        return Some(number);
+    }
+    match value.strip_prefix('-') {
+        Some(variable) => variables.get(variable).map(|value| -value),
+        None => variables.get(value).copied(),
+    }
+}
+
+// Places an atom `distance` from `c`, at `angle` degrees to `b` about `c`, and
+// at a dihedral of `dihedral` degrees to `a` about the `b`-`c` bond.
+pub(crate) fn place(a: Vec3, b: Vec3, c: Vec3, distance: f32, angle: f32, dihedral: f32) -> Vec3 {
+    let (angle, dihedral) = (angle.to_radians(), dihedral.to_radians());
+    let bc = (c - b).normalized();
+    let n = (b - a).cross(bc).normalized();
+    let m = n.cross(bc);
+
+    c + distance
+        * (-angle.cos() * bc + angle.sin() * dihedral.cos() * m + angle.sin() * dihedral.sin() * n)
+}
+
+// The angle at `center` between `a` and `b`, in degrees.
+fn angle(a: Vec3, center: Vec3, b: Vec3) -> f32 {
+    let (u, v) = (a - center, b - center);
+    let cos = u.dot(v) / (u.mag() * v.mag());
+    cos.clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+// The dihedral angle of the chain a-b-c-d about the b-c bond, in degrees.
+fn dihedral(a: Vec3, b: Vec3, c: Vec3, d: Vec3) -> f32 {
+    let (b1, b2, b3) = (b - a, c - b, d - c);
+    let n1 = b1.cross(b2);
+    let n2 = b2.cross(b3);
+    (b2.mag() * b1.dot(n2)).atan2(n1.dot(n2)).to_degrees()
+}
+
+fn collinear(a: Vec3, b: Vec3, c: Vec3) -> bool {
+    (a - b).cross(c - b).mag_sq() < 1e-6
+}
+
+// A direction that is not parallel to `axis`.
+pub(crate) fn off_axis(axis: Vec3) -> Vec3 {
+    if axis.normalized().cross(Vec3::unit_x()).mag_sq() > 1e-6 {
+        Vec3::unit_x()
+    } else {
+        Vec3::unit_y()
+    }
+}
+
+// End of File
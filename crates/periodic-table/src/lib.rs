@@ -133,6 +133,30 @@ pub enum Element {
 }
 const_assert_eq!(Element::Oganesson as usize, 118);
 
+// Chemical symbols, indexed by atomic number - 1.
+const SYMBOLS: [&str; 118] = [
+    "H", "He", "Li", "Be", "B", "C", "N", "O", "F", "Ne", "Na", "Mg", "Al", "Si", "P", "S", "Cl",
+    "Ar", "K", "Ca", "Sc", "Ti", "V", "Cr", "Mn", "Fe", "Co", "Ni", "Cu", "Zn", "Ga", "Ge", "As",
+    "Se", "Br", "Kr", "Rb", "Sr", "Y", "Zr", "Nb", "Mo", "Tc", "Ru", "Rh", "Pd", "Ag", "Cd", "In",
+    "Sn", "Sb", "Te", "I", "Xe", "Cs", "Ba", "La", "Ce", "Pr", "Nd", "Pm", "Sm", "Eu", "Gd", "Tb",
+    "Dy", "Ho", "Er", "Tm", "Yb", "Lu", "Hf", "Ta", "W", "Re", "Os", "Ir", "Pt", "Au", "Hg", "Tl",
+    "Pb", "Bi", "Po", "At", "Rn", "Fr", "Ra", "Ac", "Th", "Pa", "U", "Np", "Pu", "Am", "Cm", "Bk",
+    "Cf", "Es", "Fm", "Md", "No", "Lr", "Rf", "Db", "Sg", "Bh", "Hs", "Mt", "Ds", "Rg", "Cn", "Nh",
+    "Fl", "Mc", "Lv", "Ts", "Og",
+];
+
+// Single-bond covalent radii in picometers, indexed by atomic number - 1.
+// Source: P. Pyykkö and M. Atsumi, Chem. Eur. J. 15 (2009) 186-197.
+const COVALENT_RADII: [u8; 118] = [
+    32, 46, 133, 102, 85, 75, 71, 63, 64, 67, 155, 139, 126, 116, 111, 103, 99, 96, 196, 171, 148,
+    136, 134, 122, 119, 116, 111, 110, 112, 118, 124, 121, 121, 116, 114, 117, 210, 185, 163, 154,
+    147, 138, 128, 125, 125, 120, 128, 136, 142, 140, 140, 136, 133, 131, 232, 196, 180, 163, 176,
+    174, 173, 172, 168, 169, 168, 167, 166, 165, 164, 170, 162, 152, 146, 137, 131, 129, 122, 123,
+    124, 133, 144, 144, 151, 145, 147, 142, 223, 201, 186, 175, 169, 170, 171, 172, 166, 166, 168,
+    168, 165, 167, 173, 176, 161, 157, 149, 143, 141, 134, 129, 128, 121, 122, 136, 143, 162, 175,
+    165, 157,
+];
+
 impl Element {
     pub const MIN: Self = Element::Hydrogen; // 1
     pub const MAX: Self = Element::Oganesson; // 118
@@ -144,6 +168,86 @@ impl Element {
             None
         }
     }
+
+    /// The element's chemical symbol, e.g. "C" for carbon.
+    pub fn symbol(&self) -> &'static str {
+        SYMBOLS[*self as usize - 1]
+    }
+
+    /// Looks up an element by its chemical symbol.  The comparison ignores
+    /// case, so both "Si" and "SI" (as written in PDB files) are accepted.
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        let index = SYMBOLS
+            .iter()
+            .position(|candidate| candidate.eq_ignore_ascii_case(symbol))?;
+        Self::from_atomic_number(index as u8 + 1)
+    }
+
+    /// The element's single-bond covalent radius, in angstroms.
+    pub fn covalent_radius(&self) -> f32 {
+        COVALENT_RADII[*self as usize - 1] as f32 / 100.0
+    }
+
+    /// The number of covalent bonds this element usually forms in neutral
+    /// organic and main-group compounds, or `None` if it has no single typical
+    /// valence (e.g. noble gases and metals).
+    pub fn standard_valence(&self) -> Option<u8> {
+        use Element::*;
+
+        match self {
+            Hydrogen | Fluorine | Chlorine | Bromine | Iodine => Some(1),
+            Oxygen | Sulfur | Selenium => Some(2),
+            Boron | Nitrogen | Phosphorus | Arsenic | Aluminium => Some(3),
+            Carbon | Silicon | Germanium => Some(4),
+            _ => None,
+        }
+    }
+
+    /// The number of electrons in the element's outer shell, or `None` if it
+    /// has no standard valence (see [`Element::standard_valence`]).
+    pub fn valence_electrons(&self) -> Option<u8> {
+        use Element::*;
+
+        match self {
+            Hydrogen => Some(1),
+            Boron | Aluminium => Some(3),
+            Carbon | Silicon | Germanium => Some(4),
+            Nitrogen | Phosphorus | Arsenic => Some(5),
+            Oxygen | Sulfur | Selenium => Some(6),
+            Fluorine | Chlorine | Bromine | Iodine => Some(7),
+            _ => None,
+        }
+    }
+
+    /// The element's Pauling electronegativity, or `None` if it is not known
+    /// here.  Only the main-group elements common in molecular machinery are
+    /// listed.
+    pub fn electronegativity(&self) -> Option<f32> {
+        use Element::*;
+
+        match self {
+            Hydrogen => Some(2.20),
+            Lithium => Some(0.98),
+            Boron => Some(2.04),
+            Carbon => Some(2.55),
+            Nitrogen => Some(3.04),
+            Oxygen => Some(3.44),
+            Fluorine => Some(3.98),
+            Sodium => Some(0.93),
+            Magnesium => Some(1.31),
+            Aluminium => Some(1.61),
+            Silicon => Some(1.90),
+            Phosphorus => Some(2.19),
+            Sulfur => Some(2.58),
+            Chlorine => Some(3.16),
+            Germanium => Some(2.01),
+            Arsenic => Some(2.18),
+            Selenium => Some(2.55),
+            Bromine => Some(2.96),
+            Iodine => Some(2.66),
+            _ => None,
+        }
+    }
 }
 
 pub struct PeriodicTable {
@@ -9,8 +9,8 @@ use std::{cmp, mem};
 use ultraviolet::Vec3;
 
 /// Packed bit field
-/// | 0 .. 6 | ----------- | 7 .. 31 |
-///   ^ atomic number - 1    ^ unspecified
+/// | 0 .. 6 | ----------- | 7 | ------ | 8 .. 15 | -- | 16 | ----- | 17 .. 31 |
+///   ^ atomic number - 1   ^ pinned     ^ group        ^ hidden    ^ unspecified
 ///
 /// TODO: Try using a buffer as an atom radius lookup table.
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -21,6 +21,42 @@ impl AtomKind {
         Self(((element as u8 - 1) & 0b111_1111) as u32)
     }
 
+    /// Marks the atom as pinned in place, which is drawn as a dark ring.
+    pub fn with_pinned(self, pinned: bool) -> Self {
+        if pinned {
+            Self(self.0 | 0b1000_0000)
+        } else {
+            Self(self.0 & !0b1000_0000)
+        }
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.0 & 0b1000_0000 != 0
+    }
+
+    /// Puts the atom in a numbered group, such as a PDB chain, which it is
+    /// colored by when atoms are colored by group.  Group 0 is no group.
+    pub fn with_group(self, group: u8) -> Self {
+        Self((self.0 & !0xff00) | ((group as u32) << 8))
+    }
+
+    pub fn group(&self) -> u8 {
+        (self.0 >> 8) as u8
+    }
+
+    /// Marks the atom as hidden, so that it is not drawn.
+    pub fn with_hidden(self, hidden: bool) -> Self {
+        if hidden {
+            Self(self.0 | 1 << 16)
+        } else {
+            Self(self.0 & !(1 << 16))
+        }
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.0 & 1 << 16 != 0
+    }
+
     pub fn element(&self) -> Element {
         let n = (self.0 & 0b111_1111) as u8 + 1;
         Element::from_atomic_number(n)
@@ -36,9 +72,12 @@ unsafe impl AsBytes for AtomKind {}
 pub struct AtomRepr {
     pub pos: Vec3, // with respect to fragment center
     pub kind: AtomKind,
+    /// A scalar that the atom is colored by when atoms are colored by
+    /// property, such as its partial charge.
+    pub property: f32,
 }
 
-static_assertions::const_assert_eq!(mem::size_of::<AtomRepr>(), 16);
+static_assertions::const_assert_eq!(mem::size_of::<AtomRepr>(), 20);
 unsafe impl AsBytes for AtomRepr {}
 
 /// Essentially a per-fragment uniform.
@@ -48,9 +87,16 @@ pub struct AtomBufferHeader;
 
 unsafe impl AsBytes for AtomBufferHeader {}
 
+// How far the bounds of a buffer reach past the centers of its atoms, in
+// angstroms: at least the radius of the largest atom drawn.
+const BOUNDS_MARGIN: f32 = 2.5;
+
 pub struct AtomBuffer {
     bind_group: wgpu::BindGroup,
     number_of_atoms: usize,
+    gpu_memory: u64,
+    // The center and radius of a sphere around the atoms.
+    bounds: (Vec3, f32),
 }
 
 impl AtomBuffer {
@@ -71,11 +117,14 @@ impl AtomBuffer {
         };
         let mut atom_pos =
             Vec::with_capacity((texel_count * 4 * mem::size_of::<f32>() + 255) & !255);
-        let mut atom_kind = Vec::with_capacity((texel_count * mem::size_of::<u8>() + 255) & !255);
+        let mut atom_kind = Vec::with_capacity((texel_count * mem::size_of::<u32>() + 255) & !255);
+        let (mut min, mut max) = (Vec3::broadcast(f32::MAX), Vec3::broadcast(f32::MIN));
         for atom in atoms {
+            min = min.min_by_component(atom.pos);
+            max = max.max_by_component(atom.pos);
             atom_pos.extend_from_slice(atom.pos.as_bytes());
-            atom_pos.extend_from_slice(&[0; 4]); // padding
-            atom_kind.extend(&(atom.kind.0 as u8).to_ne_bytes());
+            atom_pos.extend_from_slice(&atom.property.to_ne_bytes());
+            atom_kind.extend(&atom.kind.0.to_ne_bytes());
         }
         atom_pos.resize(atom_pos.capacity(), 0);
         atom_kind.resize(atom_kind.capacity(), 0);
@@ -134,7 +183,7 @@ impl AtomBuffer {
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::R8Uint,
+                format: wgpu::TextureFormat::R32Uint,
                 usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
                 view_formats: &[],
             });
@@ -149,12 +198,17 @@ impl AtomBuffer {
             &atom_kind,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(size.width * mem::size_of::<u8>() as u32),
+                bytes_per_row: Some(size.width * mem::size_of::<u32>() as u32),
                 rows_per_image: Some(size.height),
             },
             size,
         );
 
+        // Each texel holds one Rgba32Float position and property, and one
+        // R32Uint kind.
+        let texels = size.width as u64 * size.height as u64;
+        let gpu_memory = texels * (4 * mem::size_of::<f32>() + mem::size_of::<u32>()) as u64;
+
         let pos_texture_view = pos_texture.create_view(&wgpu::TextureViewDescriptor::default());
         let kind_texture_view = kind_texture.create_view(&wgpu::TextureViewDescriptor::default());
 
@@ -178,6 +232,8 @@ impl AtomBuffer {
         Self {
             bind_group,
             number_of_atoms,
+            gpu_memory,
+            bounds: ((min + max) * 0.5, (max - min).mag() * 0.5 + BOUNDS_MARGIN),
         }
     }
 
@@ -192,6 +248,16 @@ impl AtomBuffer {
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// The center and radius of a sphere that the atoms are drawn within.
+    pub fn bounds(&self) -> (Vec3, f32) {
+        self.bounds
+    }
+
+    /// The number of bytes of GPU memory used by this buffer's textures.
+    pub fn gpu_memory(&self) -> u64 {
+        self.gpu_memory
+    }
 }
 
 // End of File
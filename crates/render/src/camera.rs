@@ -4,6 +4,7 @@
 
 use crate::bind_groups::AsBindingResource;
 use common::{AsBytes, InputEvent};
+use serde::{Deserialize, Serialize};
 use std::mem;
 use ultraviolet::{Mat4, Vec3};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
@@ -18,12 +19,95 @@ pub struct CameraRepr {
 
 unsafe impl AsBytes for CameraRepr {}
 
+/// How the scene is projected onto the view.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Projection {
+    /// Distant objects look smaller, as they do to the eye.
+    #[default]
+    Perspective,
+    /// Objects look the same size however far away they are, so that parallel
+    /// edges stay parallel.  This makes alignment easier to judge.
+    Orthographic,
+}
+
+/// Where a camera is, and which way it looks, so that a view can be saved and
+/// returned to later.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct CameraPose {
+    /// The point that the camera looks at and turns about.
+    pub focus: Vec3,
+    /// The heading and elevation of the camera, as taken by
+    /// [`Camera::orbit_to`].
+    pub yaw: f32,
+    pub pitch: f32,
+    /// How far the camera is from its focus.
+    pub distance: f32,
+}
+
+impl CameraPose {
+    /// The direction from the focus towards the camera.
+    pub fn toward_camera(&self) -> Vec3 {
+        Vec3::new(
+            self.yaw.sin() * self.pitch.cos(),
+            self.yaw.cos() * self.pitch.cos(),
+            self.pitch.sin(),
+        )
+    }
+
+    /// Where the camera is.
+    pub fn eye(&self) -> Vec3 {
+        self.focus + self.distance * self.toward_camera()
+    }
+
+    /// The same view, but turning about the point `radius` in front of the
+    /// camera: the camera stays where it is, and the focus moves along the
+    /// line of sight.  This helps to look around inside a hollow structure,
+    /// where the focus would otherwise be out of reach behind a wall.
+    pub fn with_orbit_radius(self, radius: f32) -> Self {
+        let radius = radius.max(0.001);
+        Self {
+            focus: self.eye() - radius * self.toward_camera(),
+            distance: radius,
+            ..self
+        }
+    }
+}
+
 pub trait Camera {
     fn resize(&mut self, aspect: f32, fov: f32, near: f32);
+    fn set_projection(&mut self, projection: Projection);
     fn update(&mut self, event: InputEvent) -> bool;
     fn finalize(&mut self);
     fn repr(&self) -> CameraRepr;
     fn position(&self) -> Vec3;
+    /// Moves the camera so that a sphere at `focus` with the given `radius`
+    /// fills the view, keeping the current viewing direction.
+    fn frame(&mut self, focus: Vec3, radius: f32);
+    /// Turns the camera about its focus to look from the given heading (`yaw`,
+    /// about the z axis from +y towards +x) and elevation (`pitch`, above the
+    /// xy plane), in radians.  With `animate`, the camera turns over the next
+    /// few frames rather than at once.
+    fn orbit_to(&mut self, yaw: f32, pitch: f32, animate: bool);
+    fn pose(&self) -> CameraPose;
+    /// Where the camera is moving to, or where it is if it is not moving.
+    fn destination(&self) -> CameraPose {
+        self.pose()
+    }
+    /// Moves the camera to `pose`, over the next few frames if `animate` is
+    /// set.
+    fn set_pose(&mut self, pose: CameraPose, animate: bool);
+    /// Whether the camera is moving by itself, so that frames have to be drawn
+    /// without waiting for input.
+    fn is_moving(&self) -> bool;
+    /// Whether the camera is being turned about its focus, by the user or by
+    /// itself.
+    fn is_orbiting(&self) -> bool {
+        false
+    }
+    /// Tells the camera how many physical pixels there are to each logical
+    /// pixel of the window it is shown in, for input that is measured in
+    /// pixels, such as trackpad scrolling.
+    fn set_scale_factor(&mut self, _scale_factor: f64) {}
 }
 
 pub struct RenderCamera {
@@ -32,6 +116,8 @@ pub struct RenderCamera {
 
     fov: f32,
     near: f32,
+    projection: Projection,
+    scale_factor: f64,
     camera: Option<Box<dyn Camera>>,
     camera_was_updated: bool,
 }
@@ -50,6 +136,8 @@ impl RenderCamera {
 
             fov,
             near,
+            projection: Projection::default(),
+            scale_factor: 1.0,
             camera: None,
             camera_was_updated: false,
         }
@@ -61,6 +149,8 @@ impl RenderCamera {
         size: PhysicalSize<u32>,
     ) {
         camera.resize(size.width as f32 / size.height as f32, self.fov, self.near);
+        camera.set_projection(self.projection);
+        camera.set_scale_factor(self.scale_factor);
         self.camera = Some(Box::new(camera));
         self.camera_was_updated = true;
     }
@@ -75,6 +165,29 @@ impl RenderCamera {
         self.camera_was_updated = true;
     }
 
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    /// Switches between perspective and orthographic projection.  The setting
+    /// carries over to cameras set later.
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+        if let Some(camera) = self.camera.as_mut() {
+            camera.set_projection(projection);
+            self.camera_was_updated = true;
+        }
+    }
+
+    /// Changes the scale factor of the window.  The setting carries over to
+    /// cameras set later.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+        if let Some(camera) = self.camera.as_mut() {
+            camera.set_scale_factor(scale_factor);
+        }
+    }
+
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
         if let Some(camera) = self.camera.as_mut() {
             camera.resize(
@@ -102,6 +215,51 @@ impl RenderCamera {
         // });
     }
 
+    pub fn frame(&mut self, focus: Vec3, radius: f32) {
+        if let Some(camera) = self.camera.as_mut() {
+            camera.frame(focus, radius);
+            self.camera_was_updated = true;
+        }
+    }
+
+    pub fn orbit_to(&mut self, yaw: f32, pitch: f32, animate: bool) {
+        if let Some(camera) = self.camera.as_mut() {
+            camera.orbit_to(yaw, pitch, animate);
+            self.camera_was_updated = true;
+        }
+    }
+
+    pub fn pose(&self) -> Option<CameraPose> {
+        self.camera.as_ref().map(|camera| camera.pose())
+    }
+
+    pub fn destination(&self) -> Option<CameraPose> {
+        self.camera.as_ref().map(|camera| camera.destination())
+    }
+
+    pub fn set_pose(&mut self, pose: CameraPose, animate: bool) {
+        if let Some(camera) = self.camera.as_mut() {
+            camera.set_pose(pose, animate);
+            self.camera_was_updated = true;
+        }
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.camera
+            .as_ref()
+            .is_some_and(|camera| camera.is_moving())
+    }
+
+    pub fn is_orbiting(&self) -> bool {
+        self.camera
+            .as_ref()
+            .is_some_and(|camera| camera.is_orbiting())
+    }
+
+    pub(crate) fn repr(&self) -> Option<CameraRepr> {
+        self.camera.as_ref().map(|camera| camera.repr())
+    }
+
     #[must_use = "returns bool indicating whether a camera is currently set or not"]
     pub(crate) fn upload(&mut self, queue: &wgpu::Queue) -> bool {
         if let Some(camera) = self.camera.as_mut() {
@@ -140,6 +298,20 @@ impl RenderCamera {
         let x = (2.0 * pixel.x as f32 - viewport_size.width as f32) / viewport_size.width as f32;
         let y = (viewport_size.height as f32 - 2.0 * pixel.y as f32) / viewport_size.height as f32;
 
+        // Under orthographic projection every ray points straight into the
+        // view, from the point on the near plane under the pixel.
+        if self.projection == Projection::Orthographic {
+            let origin = camera_repr
+                .projection_view
+                .inversed()
+                .transform_point3(Vec3::new(x, y, 1.0));
+            let direction = camera_repr
+                .view
+                .inversed()
+                .transform_vec3(Vec3::new(0.0, 0.0, -1.0));
+            return Some((origin, direction.normalized()));
+        }
+
         // 2. Create a ray in clip space.
         let ray_clip = Vec3::new(x, y, -self.near);
 
@@ -159,6 +331,29 @@ impl RenderCamera {
 
         Some((camera.position(), ray_dir))
     }
+
+    /// Finds the pixel that `point` is drawn at, for anchoring labels to
+    /// positions in the scene.  Returns `None` if there is no camera or the
+    /// point is behind it.
+    pub fn project(
+        &self,
+        point: Vec3,
+        viewport_size: &PhysicalSize<u32>,
+    ) -> Option<PhysicalPosition<f64>> {
+        let camera_repr = self.camera.as_ref()?.repr();
+        let clip = camera_repr.projection_view * point.into_homogeneous_point();
+        if clip.w <= 0.0 {
+            return None;
+        }
+
+        // The inverse of the conversion to normalized device coordinates in
+        // `get_ray_from`.
+        let (x, y) = (clip.x / clip.w, clip.y / clip.w);
+        Some(PhysicalPosition::new(
+            ((x + 1.0) * 0.5 * viewport_size.width as f32) as f64,
+            ((1.0 - y) * 0.5 * viewport_size.height as f32) as f64,
+        ))
+    }
 }
 
 impl AsBindingResource for RenderCamera {
@@ -0,0 +1,48 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Capturing a single frame, to go with a report of a rendering bug.
+//!
+//! When asked to, the renderer wraps its next frame in a graphics debugger
+//! capture, which RenderDoc (or Xcode, on macOS) records if it is attached to
+//! the process, and saves in its own capture directory.  Whether or not one is
+//! attached, it also reads back what the frame drew and notes the adapter and
+//! settings that drew it, so that a capture can be attached to a bug report by
+//! users who have never heard of RenderDoc.
+
+use serde::Serialize;
+use winit::dpi::PhysicalSize;
+
+/// What was captured of a frame.
+#[derive(Clone, Debug, Serialize)]
+pub struct FrameCapture {
+    /// The name of the GPU, as the driver reports it.
+    pub adapter: String,
+    /// The graphics API it was driven through, such as "Vulkan".
+    pub backend: String,
+    pub driver: String,
+    pub driver_info: String,
+    /// Whether the adapter is a software fallback.
+    pub software: bool,
+    pub gpu_driven_rendering: bool,
+    pub width: u32,
+    pub height: u32,
+    pub draw_calls: usize,
+    pub culled_draws: usize,
+    pub fxaa: bool,
+    pub depth_prepass: bool,
+    pub conservative_limits: bool,
+    /// The molecular pass's color target, before antialiasing, as 8-bit sRGB
+    /// RGBA pixels row by row from the top.
+    #[serde(skip)]
+    pub pixels: Vec<u8>,
+}
+
+impl FrameCapture {
+    pub fn size(&self) -> PhysicalSize<u32> {
+        PhysicalSize::new(self.width, self.height)
+    }
+}
+
+// End of File
@@ -0,0 +1,86 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Color spaces.  Colors are written down in sRGB, as they are in other tools
+//! (element colors, palettes, tints), but the renderer shades and blends in
+//! linear light, and the swapchain encodes its output back to sRGB.  Colors
+//! are converted once, on the way to the GPU, with [`srgb_to_linear`].
+
+use serde::{Deserialize, Serialize};
+use ultraviolet::{Mat3, Vec3};
+
+/// Converts a color from sRGB to linear RGB with the same primaries.
+pub fn srgb_to_linear(color: Vec3) -> Vec3 {
+    color.map(|channel| {
+        if channel <= 0.04045 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    })
+}
+
+/// Converts a color from linear RGB back to sRGB.
+pub fn linear_to_srgb(color: Vec3) -> Vec3 {
+    color.map(|channel| {
+        if channel <= 0.003_130_8 {
+            channel * 12.92
+        } else {
+            1.055 * channel.powf(1.0 / 2.4) - 0.055
+        }
+    })
+}
+
+/// The color space that frames are encoded in for the display.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum OutputColorSpace {
+    /// Standard sRGB, which every display shows correctly.
+    #[default]
+    Srgb,
+    /// Display P3, for wide-gamut displays that are sent their pixels as they
+    /// are.  Platforms that color-manage windows themselves (e.g. macOS, which
+    /// assumes sRGB) should stay on sRGB, or colors will be oversaturated.
+    DisplayP3,
+}
+
+impl OutputColorSpace {
+    pub const ALL: [OutputColorSpace; 2] = [OutputColorSpace::Srgb, OutputColorSpace::DisplayP3];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputColorSpace::Srgb => "sRGB",
+            OutputColorSpace::DisplayP3 => "Display P3",
+        }
+    }
+
+    /// The color space with the given name, ignoring case, spaces and dashes,
+    /// so that "display-p3" is Display P3.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let simplified: String = name
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .collect::<String>()
+            .to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|space| space.name().replace(' ', "").to_ascii_lowercase() == simplified)
+    }
+
+    /// Takes linear sRGB to linear RGB in this color space's primaries.  Both
+    /// spaces share the sRGB transfer function, so the swapchain encodes the
+    /// result correctly either way.
+    pub(crate) fn conversion(self) -> Mat3 {
+        match self {
+            OutputColorSpace::Srgb => Mat3::identity(),
+            // Both have a D65 white point, so this is exact up to rounding.
+            OutputColorSpace::DisplayP3 => Mat3::new(
+                Vec3::new(0.822_462, 0.033_194, 0.017_083),
+                Vec3::new(0.177_538, 0.966_806, 0.072_397),
+                Vec3::new(0.0, 0.0, 0.910_520),
+            ),
+        }
+    }
+}
+
+// End of File
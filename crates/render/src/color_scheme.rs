@@ -0,0 +1,167 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::BTreeMap;
+
+use periodic_table::{Element, PeriodicTable};
+use serde::{Deserialize, Serialize};
+use ultraviolet::Vec3;
+
+use crate::color::{linear_to_srgb, srgb_to_linear};
+
+/// The built-in sets of element colors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum BaseColors {
+    /// The colors used by Jmol, which cover every element.
+    #[default]
+    Jmol,
+    /// The classic Corey-Pauling-Koltun colors, as used by RasMol.  Elements
+    /// without a color of their own are drawn in pink.
+    Cpk,
+    /// The Jmol colors, converted to shades of gray by their luminance.  This
+    /// suits printing, and leaves color free for highlighting.
+    Grayscale,
+}
+
+impl BaseColors {
+    pub const ALL: [BaseColors; 3] = [BaseColors::Jmol, BaseColors::Cpk, BaseColors::Grayscale];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BaseColors::Jmol => "Jmol",
+            BaseColors::Cpk => "CPK",
+            BaseColors::Grayscale => "Grayscale",
+        }
+    }
+
+    /// The scheme after this one in [`BaseColors::ALL`], going back to the
+    /// first after the last.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|base| *base == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    fn color(self, element: Element, jmol: Vec3) -> Vec3 {
+        match self {
+            BaseColors::Jmol => jmol,
+            BaseColors::Cpk => cpk_color(element),
+            // Luminance is a sum of light, so it is taken in linear RGB.
+            BaseColors::Grayscale => linear_to_srgb(Vec3::broadcast(
+                srgb_to_linear(jmol).dot(Vec3::new(0.2126, 0.7152, 0.0722)),
+            )),
+        }
+    }
+}
+
+/// The colors that atoms are drawn in: one of the built-in sets, with some
+/// elements given colors of their own.  Colors are in sRGB, as they are
+/// written in other tools.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct ColorScheme {
+    pub base: BaseColors,
+    /// Colors that replace the base colors of particular elements.
+    pub overrides: BTreeMap<Element, Vec3>,
+}
+
+impl ColorScheme {
+    pub fn color(&self, element: Element) -> Vec3 {
+        let table = PeriodicTable::new();
+        self.overrides.get(&element).copied().unwrap_or_else(|| {
+            let jmol = table.element_reprs[element as usize - 1].color;
+            self.base.color(element, jmol)
+        })
+    }
+
+    /// Gives `element` a color of its own, or returns it to the base color
+    /// with `None`.
+    pub fn set_override(&mut self, element: Element, color: Option<Vec3>) {
+        match color {
+            Some(color) => self.overrides.insert(element, color),
+            None => self.overrides.remove(&element),
+        };
+    }
+
+    /// Recolors the elements of `table`, which must hold the Jmol colors, in
+    /// the linear RGB that the shaders work in.
+    pub(crate) fn apply(&self, table: &mut PeriodicTable) {
+        for (index, repr) in table.element_reprs.iter_mut().enumerate() {
+            let element = Element::from_atomic_number(index as u8 + 1)
+                .expect("the periodic table has one entry per element");
+            repr.color = srgb_to_linear(match self.overrides.get(&element) {
+                Some(color) => *color,
+                None => self.base.color(element, repr.color),
+            });
+        }
+    }
+}
+
+/// What atoms are colored by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub enum AtomColoring {
+    /// The colors of their elements, in the current color scheme.
+    #[default]
+    Element,
+    /// A color for each molecule drawn, so that the components of an assembly
+    /// stand apart from each other.
+    Component,
+    /// A color for each group that atoms are put in (see
+    /// [`AtomKind::with_group`](crate::AtomKind::with_group)), such as the
+    /// chains of a PDB structure.  Atoms in no group keep their element colors.
+    Group,
+    /// A ramp over each atom's property (e.g. its partial charge), from blue at
+    /// `low` through white to red at `high`.
+    Property { low: f32, high: f32 },
+}
+
+impl AtomColoring {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AtomColoring::Element => "Element",
+            AtomColoring::Component => "Component",
+            AtomColoring::Group => "Chain",
+            AtomColoring::Property { .. } => "Property",
+        }
+    }
+}
+
+/// The `index`th of a series of colors that are easy to tell apart, as used to
+/// color components and groups, in sRGB.  This matches `palette` in
+/// `atom.wgsl`.
+pub fn palette_color(index: u32) -> Vec3 {
+    // Successive hues are spaced by the golden ratio, so that they never
+    // repeat and neighbors always differ.
+    let hue = (index as f32 * 0.618_034).fract();
+    let (saturation, value) = (0.55, 0.95);
+    let channel = |offset: f32| {
+        let k = ((hue + offset).fract() * 6.0 - 3.0).abs() - 1.0;
+        value * (1.0 + saturation * (k.clamp(0.0, 1.0) - 1.0))
+    };
+    Vec3::new(channel(1.0), channel(2.0 / 3.0), channel(1.0 / 3.0))
+}
+
+fn cpk_color(element: Element) -> Vec3 {
+    use Element::*;
+
+    let (r, g, b) = match element {
+        Hydrogen => (255, 255, 255),
+        Helium => (255, 192, 203),
+        Lithium => (178, 34, 34),
+        Boron | Chlorine => (0, 255, 0),
+        Carbon => (200, 200, 200),
+        Nitrogen => (143, 143, 255),
+        Oxygen => (240, 0, 0),
+        Fluorine | Silicon | Gold => (218, 165, 32),
+        Sodium => (0, 0, 255),
+        Magnesium => (34, 139, 34),
+        Aluminium | Calcium | Titanium | Chromium | Manganese | Silver => (128, 128, 144),
+        Phosphorus | Iron | Barium => (255, 165, 0),
+        Sulfur => (255, 200, 50),
+        Nickel | Copper | Zinc | Bromine => (165, 42, 42),
+        Iodine => (160, 32, 240),
+        _ => (255, 20, 147),
+    };
+    Vec3::new(r as f32, g as f32, b as f32) / 255.0
+}
+
+// End of File
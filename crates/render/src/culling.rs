@@ -0,0 +1,104 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Culling molecules by the size they are drawn at.  In a zoomed-out view of
+//! a large assembly, most molecules cover a pixel or two, but cost as much to
+//! draw as if they filled the view.  Those whose bounds are drawn smaller than
+//! a threshold are left out, or stood in for by a single point so that the
+//! shape of the assembly still shows.
+
+use serde::{Deserialize, Serialize};
+use ultraviolet::{Mat4, Vec3};
+
+/// What is drawn in place of a molecule that is too small on screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum CullMode {
+    /// Nothing.
+    Skip,
+    /// One of its atoms, as a single point.
+    Point,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ScreenCulling {
+    /// Molecules whose bounds are drawn less than this many physical pixels
+    /// across are culled.
+    pub min_pixels: f32,
+    pub mode: CullMode,
+}
+
+impl Default for ScreenCulling {
+    fn default() -> Self {
+        Self {
+            min_pixels: 2.0,
+            mode: CullMode::Point,
+        }
+    }
+}
+
+impl ScreenCulling {
+    /// Parses culling written as `mode:pixels`, such as `skip:3`, where the
+    /// mode is `skip` or `point`.  Either part may be left out, taking its
+    /// default.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut culling = Self::default();
+        for part in spec
+            .split(':')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+        {
+            match part {
+                "skip" => culling.mode = CullMode::Skip,
+                "point" => culling.mode = CullMode::Point,
+                pixels => {
+                    culling.min_pixels = pixels
+                        .parse()
+                        .ok()
+                        .filter(|pixels: &f32| *pixels >= 0.0)
+                        .ok_or_else(|| {
+                            format!("expected `skip`, `point` or a size, not `{}`", pixels)
+                        })?;
+                }
+            }
+        }
+        Ok(culling)
+    }
+
+    /// Whether a sphere of `radius` about `center`, moved by `transform`,
+    /// is drawn smaller than the threshold when seen through `projection`
+    /// and `view` by a view `view_height` pixels tall.  Spheres that the
+    /// camera is in, or that are behind it, never are.
+    pub(crate) fn culls(
+        &self,
+        (center, radius): (Vec3, f32),
+        transform: &Mat4,
+        projection: &Mat4,
+        view: &Mat4,
+        view_height: f32,
+    ) -> bool {
+        // Transforms may scale, so the radius grows with the largest axis.
+        let scale = transform.cols[..3]
+            .iter()
+            .map(|col| col.xyz().mag())
+            .fold(0.0, f32::max);
+        let radius = radius * scale;
+        let center = (*view * *transform).transform_point3(center);
+        // How many pixels a unit length across the view covers.  Perspective
+        // projections leave w zero, and orthographic ones one.
+        let pixels_per_unit = if projection.cols[3].w == 1.0 {
+            projection.cols[1].y * view_height * 0.5
+        } else {
+            // The view looks down -z.
+            let depth = -center.z;
+            if depth <= radius {
+                return false;
+            }
+            projection.cols[1].y / depth * view_height * 0.5
+        };
+        let pixels = 2.0 * radius * pixels_per_unit;
+        pixels < self.min_pixels
+    }
+}
+
+// End of File
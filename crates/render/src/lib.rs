@@ -4,13 +4,19 @@
 
 pub use crate::{
     atom_buffer::{AtomBuffer, AtomKind, AtomRepr},
-    camera::{Camera, CameraRepr, RenderCamera},
+    camera::{Camera, CameraPose, CameraRepr, Projection, RenderCamera},
+    capture::FrameCapture,
+    color::{linear_to_srgb, srgb_to_linear, OutputColorSpace},
+    color_scheme::{palette_color, AtomColoring, BaseColors, ColorScheme},
+    culling::{CullMode, ScreenCulling},
+    limits::{AtomLimits, SceneLoad},
+    overlay::Overlay,
 };
 use crate::{bind_groups::AsBindingResource as _, buffer_vec::BufferVec};
 use common::AsBytes as _;
 use periodic_table::PeriodicTable;
-use std::rc::Rc;
-use ultraviolet::Vec2;
+use std::{cell::Cell, rc::Rc};
+use ultraviolet::{Vec2, Vec3, Vec4};
 use wgpu::util::DeviceExt as _;
 use winit::{dpi::PhysicalSize, window::Window};
 
@@ -18,6 +24,12 @@ mod atom_buffer;
 mod bind_groups;
 mod buffer_vec;
 mod camera;
+mod capture;
+mod color;
+mod color_scheme;
+mod culling;
+mod limits;
+mod overlay;
 mod passes;
 
 #[macro_export]
@@ -34,9 +46,32 @@ const SWAPCHAIN_FORMAT: wgpu::TextureFormat =
         wgpu::TextureFormat::Bgra8UnormSrgb
     };
 
-#[derive(Default)]
+// The formats of the window-sized textures owned by the render passes: the
+// molecular pass's color, depth and normals targets, and the fxaa output, if
+// there is one.
+const RENDER_TARGET_FORMATS: [wgpu::TextureFormat; 4] = [
+    SWAPCHAIN_FORMAT,
+    wgpu::TextureFormat::Depth32Float,
+    wgpu::TextureFormat::Rgba16Float,
+    SWAPCHAIN_FORMAT,
+];
+
+/// What the user is pointing at in the view, which is highlighted when the
+/// atoms are drawn.
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Interactions {
     // pub selected_fragments: HashSet<FragmentId>,
+    /// The atom under the cursor, if any.
+    pub hovered: Option<HoveredAtom>,
+}
+
+/// An atom of one of the atom buffers passed to [`Renderer::render`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HoveredAtom {
+    /// The index of the buffer in the order they are drawn.
+    pub draw: usize,
+    /// The index of the atom in the buffer.
+    pub atom: u32,
 }
 
 pub struct GlobalRenderResources {
@@ -44,12 +79,81 @@ pub struct GlobalRenderResources {
     pub(crate) queue: wgpu::Queue,
     pub(crate) atom_bgl: wgpu::BindGroupLayout,
     pub(crate) linear_sampler: wgpu::Sampler,
+    pub(crate) atom_limits: Cell<AtomLimits>,
     // pub(crate) staging_belt: Arc<Mutex<wgpu::util::StagingBelt>>,
 }
 
+impl GlobalRenderResources {
+    /// The limits on how many atoms are drawn, capped by what the device can
+    /// hold.
+    pub fn atom_limits(&self) -> AtomLimits {
+        self.atom_limits.get()
+    }
+}
+
+/// The color behind the atoms unless another is chosen, in linear RGB.
+pub const DEFAULT_BACKGROUND: Vec3 = Vec3::new(0.703125, 0.703125, 0.703125);
+
 pub struct RenderOptions {
     pub fxaa: Option<()>,         // to be filled out with fxaa configuration options
     pub attempt_gpu_driven: bool, // Will attempt to drive rendering, culling, etc on gpu if supported by the adapter
+    /// The color behind the atoms, in linear RGB.
+    pub background: Vec3,
+    /// Fades distant atoms into the background, or `None` to draw every atom
+    /// at full strength.
+    pub fog: Option<Fog>,
+    pub color_scheme: ColorScheme,
+    pub coloring: AtomColoring,
+    /// The color space that frames are encoded in for the display.
+    pub output_color_space: OutputColorSpace,
+    pub atom_limits: AtomLimits,
+    /// Draws the atoms into the depth buffer before shading them, so that
+    /// atoms hidden behind others are not shaded.  This is faster for dense
+    /// scenes, and slower for sparse ones.
+    pub depth_prepass: bool,
+    /// Leaves out molecules that are drawn too small to make out, or draws
+    /// them as points, or `None` to draw every molecule in full.
+    pub culling: Option<ScreenCulling>,
+    /// Asks the device only for the limits that every adapter supports, rather
+    /// than wgpu's defaults, for drivers that claim more than they can do.
+    pub conservative_limits: bool,
+}
+
+/// Depth cueing, which fades atoms into the background color the farther they
+/// are from the camera, so that the front of a large structure stands out.
+/// Distances are in angstroms.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fog {
+    /// Atoms begin to fade at `start` and are gone beyond `end`.
+    Linear { start: f32, end: f32 },
+    /// Atoms fade by `1 - exp(-density * distance)`, and are never quite gone.
+    Exponential { density: f32 },
+}
+
+/// An estimate of the GPU memory used for rendering, in bytes, broken down by
+/// the kind of resource it is used for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GpuMemoryUsage {
+    pub atoms: u64,
+    pub transforms: u64,
+    pub render_targets: u64,
+    pub uniforms: u64,
+}
+
+impl GpuMemoryUsage {
+    pub fn total(&self) -> u64 {
+        self.atoms + self.transforms + self.render_targets + self.uniforms
+    }
+}
+
+/// A rectangle of the window, in physical pixels, that a set of atoms is drawn
+/// into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
 }
 
 #[repr(C, align(16))]
@@ -69,12 +173,23 @@ impl MolecularVertexConsts {
     }
 }
 
+// Where the renderer draws: the swapchain of a window, or a texture that can
+// be read back without one.
+enum RenderTarget {
+    Surface {
+        surface: wgpu::Surface,
+        config: wgpu::SurfaceConfiguration,
+    },
+    Texture(wgpu::Texture),
+}
+
 #[allow(dead_code)]
 pub struct Renderer {
-    surface_config: wgpu::SurfaceConfiguration,
-    surface: wgpu::Surface,
+    target: RenderTarget,
     render_resources: Rc<GlobalRenderResources>,
     size: PhysicalSize<u32>,
+    // The number of physical pixels to each logical pixel of the window.
+    scale_factor: f64,
 
     vertex_contants: MolecularVertexConsts,
     vertex_contants_buffer: wgpu::Buffer,
@@ -83,25 +198,36 @@ pub struct Renderer {
     camera: RenderCamera,
 
     molecular_pass: passes::MolecularPass,
-    fxaa_pass: passes::FxaaPass,
+    // Antialiasing, unless it was turned off in the options.
+    fxaa_pass: Option<passes::FxaaPass>,
     blit_pass: passes::BlitPass,
+    overlay_pass: passes::OverlayPass,
 
     fragment_transforms: BufferVec<(), ultraviolet::Mat4>,
 
+    // Whether the window is split into two views side by side.
+    split: bool,
+    interactions: Interactions,
+    // What is drawn over the views.
+    overlay: Overlay,
+
     gpu_driven_rendering: bool,
     options: RenderOptions,
+    // How the atoms of the last frame compared with the scene limits.
+    scene_load: SceneLoad,
+    // The molecules of the last frame that were culled for being too small.
+    culled_draws: usize,
+    adapter_info: wgpu::AdapterInfo,
+    // Whether the next frame is to be captured, and the last frame captured,
+    // if it has not been taken yet.
+    capture_next_frame: bool,
+    capture: Option<FrameCapture>,
 }
 
 impl Renderer {
     pub async fn new(window: &Window, options: RenderOptions) -> (Self, Rc<GlobalRenderResources>) {
         let size = window.inner_size();
-
-        // The instance is a handle to our GPU.
-        // Backends::PRIMARY => Vulkan + Metal + DX12 + Browser WebGPU
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY | wgpu::Backends::GL,
-            dx12_shader_compiler: Default::default(),
-        });
+        let instance = Self::create_instance();
 
         // # Safety
         //
@@ -109,15 +235,103 @@ impl Renderer {
         let surface = unsafe { instance.create_surface(window) }
             .expect("failed to retrieve surface for window");
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: !options.attempt_gpu_driven,
-            })
+        let adapter = Self::request_adapter(&instance, Some(&surface), &options)
             .await
             .expect("failed to find an appropriate adapter");
 
+        let (device, queue, gpu_driven_rendering) = Self::request_device(&adapter, &options).await;
+        let adapter_info = adapter.get_info();
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: SWAPCHAIN_FORMAT,
+            width: size.width,
+            height: size.height,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![SWAPCHAIN_FORMAT],
+        };
+
+        surface.configure(&device, &config);
+
+        let (mut renderer, render_resources) = Self::with_target(
+            RenderTarget::Surface { surface, config },
+            size,
+            device,
+            queue,
+            gpu_driven_rendering,
+            adapter_info,
+            options,
+        );
+        renderer.scale_factor = window.scale_factor();
+        renderer.camera.set_scale_factor(renderer.scale_factor);
+        (renderer, render_resources)
+    }
+
+    /// Creates a renderer that draws into a texture of the given size instead
+    /// of a window, e.g. to render images from tests or the command line.  Use
+    /// [`Renderer::read_pixels`] to get at what was rendered.  Returns `None`
+    /// if no GPU adapter is available.
+    pub async fn new_headless(
+        size: PhysicalSize<u32>,
+        options: RenderOptions,
+    ) -> Option<(Self, Rc<GlobalRenderResources>)> {
+        let instance = Self::create_instance();
+
+        let adapter = Self::request_adapter(&instance, None, &options).await?;
+
+        let (device, queue, gpu_driven_rendering) = Self::request_device(&adapter, &options).await;
+        let texture = Self::create_target_texture(&device, size);
+
+        Some(Self::with_target(
+            RenderTarget::Texture(texture),
+            size,
+            device,
+            queue,
+            gpu_driven_rendering,
+            adapter.get_info(),
+            options,
+        ))
+    }
+
+    fn create_instance() -> wgpu::Instance {
+        // The instance is a handle to our GPU.
+        // Backends::PRIMARY => Vulkan + Metal + DX12 + Browser WebGPU
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::PRIMARY | wgpu::Backends::GL,
+            dx12_shader_compiler: Default::default(),
+        })
+    }
+
+    // Finds an adapter, preferring the software fallback adapter unless
+    // GPU-driven rendering is to be attempted.  Not every platform has a
+    // fallback adapter, so without one any adapter is taken instead.
+    async fn request_adapter(
+        instance: &wgpu::Instance,
+        compatible_surface: Option<&wgpu::Surface>,
+        options: &RenderOptions,
+    ) -> Option<wgpu::Adapter> {
+        let request = |force_fallback_adapter| {
+            instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface,
+                force_fallback_adapter,
+            })
+        };
+        if !options.attempt_gpu_driven {
+            if let Some(adapter) = request(true).await {
+                return Some(adapter);
+            }
+        }
+        request(false).await
+    }
+
+    // Creates the device, with the features needed for GPU-driven rendering if
+    // it was asked for and the adapter has them.  Also returns whether it does.
+    async fn request_device(
+        adapter: &wgpu::Adapter,
+        options: &RenderOptions,
+    ) -> (wgpu::Device, wgpu::Queue, bool) {
         let software_driven_features = wgpu::Features::empty();
         let gpu_driven_features = software_driven_features
             | wgpu::Features::VERTEX_WRITABLE_STORAGE
@@ -144,6 +358,8 @@ impl Renderer {
                     // we're building for the web we'll have to disable some.
                     limits: if cfg!(target_family = "wasm") {
                         wgpu::Limits::downlevel_webgl2_defaults()
+                    } else if options.conservative_limits {
+                        wgpu::Limits::downlevel_defaults()
                     } else {
                         wgpu::Limits::default()
                     },
@@ -153,9 +369,22 @@ impl Renderer {
             .await
             .expect("failed to create device");
 
+        (device, queue, gpu_driven_rendering)
+    }
+
+    fn with_target(
+        target: RenderTarget,
+        size: PhysicalSize<u32>,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        gpu_driven_rendering: bool,
+        adapter_info: wgpu::AdapterInfo,
+        options: RenderOptions,
+    ) -> (Self, Rc<GlobalRenderResources>) {
         let camera = RenderCamera::new_empty(&device, 0.7, 0.1);
 
-        let periodic_table = PeriodicTable::new();
+        let mut periodic_table = PeriodicTable::new();
+        options.color_scheme.apply(&mut periodic_table);
 
         let vertex_contants = MolecularVertexConsts::new(
             Vec2::new(1.73, -1.0),
@@ -171,21 +400,9 @@ impl Renderer {
         let periodic_table_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None,
             contents: periodic_table.element_reprs.as_bytes(),
-            usage: wgpu::BufferUsages::UNIFORM,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: SWAPCHAIN_FORMAT,
-            width: size.width,
-            height: size.height,
-            present_mode: wgpu::PresentMode::AutoVsync,
-            alpha_mode: wgpu::CompositeAlphaMode::Auto,
-            view_formats: vec![SWAPCHAIN_FORMAT],
-        };
-
-        surface.configure(&device, &surface_config);
-
         let atom_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: None,
             entries: &[
@@ -213,33 +430,48 @@ impl Renderer {
         });
         let linear_sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
 
+        let atom_limits = options
+            .atom_limits
+            .for_device(device.limits().max_texture_dimension_2d);
         let render_resources = Rc::new(GlobalRenderResources {
             device,
             queue,
             atom_bgl,
             linear_sampler,
+            atom_limits: Cell::new(atom_limits),
         });
 
         let fragment_transforms =
             BufferVec::new(&render_resources.device, wgpu::BufferUsages::VERTEX, ());
 
-        let (molecular_pass, color_texture) = passes::MolecularPass::new(
+        let (mut molecular_pass, color_texture) = passes::MolecularPass::new(
             &render_resources,
             camera.as_binding_resource(),
             &vertex_contants_buffer,
             &periodic_table_buffer,
             size,
+            options.background,
+            options.fog,
         );
-        let (fxaa_pass, fxaa_texture) =
-            passes::FxaaPass::new(&render_resources, size, &color_texture);
-        let blit_pass = passes::BlitPass::new(&render_resources, &fxaa_texture);
+        molecular_pass.set_depth_prepass(options.depth_prepass);
+        let (fxaa_pass, blit_input) = match options.fxaa {
+            Some(()) => {
+                let (fxaa_pass, fxaa_texture) =
+                    passes::FxaaPass::new(&render_resources, size, &color_texture);
+                (Some(fxaa_pass), fxaa_texture)
+            }
+            None => (None, color_texture),
+        };
+        let blit_pass =
+            passes::BlitPass::new(&render_resources, &blit_input, options.output_color_space);
+        let overlay_pass = passes::OverlayPass::new(&render_resources, size);
 
         (
             Self {
-                surface_config,
-                surface,
+                target,
                 render_resources: Rc::clone(&render_resources),
                 size,
+                scale_factor: 1.0,
 
                 vertex_contants,
                 vertex_contants_buffer,
@@ -250,32 +482,207 @@ impl Renderer {
                 molecular_pass,
                 fxaa_pass,
                 blit_pass,
+                overlay_pass,
 
                 fragment_transforms,
 
+                split: false,
+                interactions: Interactions::default(),
+                overlay: Overlay::default(),
+
                 gpu_driven_rendering,
                 options,
+                scene_load: SceneLoad::Normal,
+                culled_draws: 0,
+                adapter_info,
+                capture_next_frame: false,
+                capture: None,
             },
             render_resources,
         )
     }
 
+    /// The number of physical pixels to each logical pixel of the window,
+    /// which is 1 for a headless renderer.
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Follows the window to a display with a different scale factor, where
+    /// it is `new_size` physical pixels.  The textures are remade at the new
+    /// size, so that the view stays sharp rather than being stretched.
+    pub fn rescale(&mut self, scale_factor: f64, new_size: PhysicalSize<u32>) {
+        self.scale_factor = scale_factor;
+        self.camera.set_scale_factor(scale_factor);
+        self.resize(new_size);
+    }
+
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
         self.size = new_size;
-        self.surface_config.width = new_size.width;
-        self.surface_config.height = new_size.height;
-
-        self.surface
-            .configure(&self.render_resources.device, &self.surface_config);
+        match &mut self.target {
+            RenderTarget::Surface { surface, config } => {
+                config.width = new_size.width;
+                config.height = new_size.height;
+                surface.configure(&self.render_resources.device, config);
+            }
+            RenderTarget::Texture(texture) => {
+                *texture = Self::create_target_texture(&self.render_resources.device, new_size);
+            }
+        }
 
         let (color_texture, _normals_texture) =
             self.molecular_pass.update(&self.render_resources, new_size);
-        let fxaa_texture = self
-            .fxaa_pass
-            .update(&self.render_resources, color_texture, new_size);
-        self.blit_pass.update(&self.render_resources, fxaa_texture);
+        let blit_input = match &mut self.fxaa_pass {
+            Some(fxaa_pass) => fxaa_pass.update(&self.render_resources, color_texture, new_size),
+            None => color_texture,
+        };
+        self.blit_pass.update(&self.render_resources, blit_input);
+        self.overlay_pass.resize(&self.render_resources, new_size);
+
+        self.camera.resize(self.view_size());
+    }
 
-        self.camera.resize(new_size);
+    /// The size of each view, which is the whole window unless it is split.
+    pub fn view_size(&self) -> PhysicalSize<u32> {
+        if self.split {
+            PhysicalSize::new((self.size.width / 2).max(1), self.size.height)
+        } else {
+            self.size
+        }
+    }
+
+    /// Splits the window into two views side by side, which share the camera so
+    /// that they always show the same viewpoint.
+    pub fn set_split(&mut self, split: bool) {
+        if self.split != split {
+            self.split = split;
+            self.camera.resize(self.view_size());
+        }
+    }
+
+    pub fn is_split(&self) -> bool {
+        self.split
+    }
+
+    pub fn background(&self) -> Vec3 {
+        self.options.background
+    }
+
+    /// Changes the color behind the atoms, in linear RGB.  Fog fades into it.
+    pub fn set_background(&mut self, background: Vec3) {
+        self.options.background = background;
+        self.molecular_pass
+            .set_fog(&self.render_resources, background, self.options.fog);
+    }
+
+    pub fn fog(&self) -> Option<Fog> {
+        self.options.fog
+    }
+
+    pub fn set_fog(&mut self, fog: Option<Fog>) {
+        self.options.fog = fog;
+        self.molecular_pass
+            .set_fog(&self.render_resources, self.options.background, fog);
+    }
+
+    pub fn color_scheme(&self) -> &ColorScheme {
+        &self.options.color_scheme
+    }
+
+    /// Changes the colors that atoms are drawn in.  This takes effect on the
+    /// next frame without reuploading any atoms.
+    pub fn set_color_scheme(&mut self, color_scheme: ColorScheme) {
+        self.periodic_table = PeriodicTable::new();
+        color_scheme.apply(&mut self.periodic_table);
+        self.render_resources.queue.write_buffer(
+            &self.periodic_table_buffer,
+            0,
+            self.periodic_table.element_reprs.as_bytes(),
+        );
+        self.options.color_scheme = color_scheme;
+    }
+
+    pub fn output_color_space(&self) -> OutputColorSpace {
+        self.options.output_color_space
+    }
+
+    /// Changes the color space that frames are encoded in.  Display P3 only
+    /// looks right on wide-gamut displays that the platform does not
+    /// color-manage; see [`OutputColorSpace::DisplayP3`].
+    pub fn set_output_color_space(&mut self, color_space: OutputColorSpace) {
+        self.options.output_color_space = color_space;
+        self.blit_pass
+            .set_color_space(&self.render_resources, color_space);
+    }
+
+    pub fn atom_limits(&self) -> AtomLimits {
+        self.render_resources.atom_limits()
+    }
+
+    /// Changes the limits on how many atoms are drawn.  Molecules already
+    /// uploaded are kept until they next change.
+    pub fn set_atom_limits(&mut self, limits: AtomLimits) {
+        self.options.atom_limits = limits;
+        let max_texture_dimension = self
+            .render_resources
+            .device
+            .limits()
+            .max_texture_dimension_2d;
+        self.render_resources
+            .atom_limits
+            .set(limits.for_device(max_texture_dimension));
+    }
+
+    pub fn depth_prepass(&self) -> bool {
+        self.options.depth_prepass
+    }
+
+    /// Turns the depth pre-pass on or off from the next frame on; see
+    /// [`RenderOptions::depth_prepass`].
+    pub fn set_depth_prepass(&mut self, depth_prepass: bool) {
+        self.options.depth_prepass = depth_prepass;
+        self.molecular_pass.set_depth_prepass(depth_prepass);
+    }
+
+    /// How the atoms of the last frame compared with the scene limits.
+    pub fn scene_load(&self) -> SceneLoad {
+        self.scene_load
+    }
+
+    pub fn culling(&self) -> Option<ScreenCulling> {
+        self.options.culling
+    }
+
+    /// Changes which molecules are culled for being drawn too small, from the
+    /// next frame on; see [`RenderOptions::culling`].
+    pub fn set_culling(&mut self, culling: Option<ScreenCulling>) {
+        self.options.culling = culling;
+    }
+
+    /// How many molecules of the last frame were culled for being drawn too
+    /// small, whether left out or drawn as points.
+    pub fn culled_draws(&self) -> usize {
+        self.culled_draws
+    }
+
+    pub fn coloring(&self) -> AtomColoring {
+        self.options.coloring
+    }
+
+    /// Changes what atoms are colored by.  Coloring by component replaces the
+    /// tints of the molecules drawn.
+    pub fn set_coloring(&mut self, coloring: AtomColoring) {
+        self.options.coloring = coloring;
+    }
+
+    /// Sets what is highlighted in the views rendered from now on.
+    pub fn set_interactions(&mut self, interactions: Interactions) {
+        self.interactions = interactions;
+    }
+
+    /// Sets what is drawn over the views rendered from now on.
+    pub fn set_overlay(&mut self, overlay: Overlay) {
+        self.overlay = overlay;
     }
 
     pub fn upload_transforms(
@@ -289,10 +696,63 @@ impl Renderer {
                 .push_small(&self.render_resources, encoder, &transforms[..]);
     }
 
+    /// Renders the atoms with the matching transforms and tints.  A tint of
+    /// `None` draws the atoms in the colors of their elements.
     pub fn render<'a>(
         &mut self,
         atoms: impl IntoIterator<Item = &'a AtomBuffer>,
         transforms: Vec<ultraviolet::Mat4>,
+        tints: Vec<Option<Vec3>>,
+    ) {
+        self.set_split(false);
+        let viewport = self.viewport(0);
+        let hovered = self.interactions.hovered;
+        self.render_views(
+            atoms.into_iter().map(|atoms| (atoms, viewport)),
+            transforms,
+            tints,
+            hovered,
+        );
+    }
+
+    /// Renders two sets of atoms side by side, as seen by the same camera.
+    /// `transforms` and `tints` hold those of the `left` atoms followed by those
+    /// of the `right` atoms.
+    pub fn render_side_by_side<'a>(
+        &mut self,
+        left: impl IntoIterator<Item = &'a AtomBuffer>,
+        right: impl IntoIterator<Item = &'a AtomBuffer>,
+        transforms: Vec<ultraviolet::Mat4>,
+        tints: Vec<Option<Vec3>>,
+    ) {
+        self.set_split(true);
+        let (left_viewport, right_viewport) = (self.viewport(0), self.viewport(1));
+        let atoms = left
+            .into_iter()
+            .map(|atoms| (atoms, left_viewport))
+            .chain(right.into_iter().map(|atoms| (atoms, right_viewport)));
+        // The draws of the two views do not match those of the single view,
+        // so nothing is highlighted.
+        self.render_views(atoms, transforms, tints, None);
+    }
+
+    // The rectangle covered by the `index`th view from the left.
+    fn viewport(&self, index: u32) -> Viewport {
+        let size = self.view_size();
+        Viewport {
+            x: (index * size.width) as f32,
+            y: 0.0,
+            width: size.width as f32,
+            height: size.height as f32,
+        }
+    }
+
+    fn render_views<'a>(
+        &mut self,
+        atoms: impl IntoIterator<Item = (&'a AtomBuffer, Viewport)>,
+        transforms: Vec<ultraviolet::Mat4>,
+        tints: Vec<Option<Vec3>>,
+        hovered: Option<HoveredAtom>,
     ) {
         let mut encoder = self
             .render_resources
@@ -306,30 +766,64 @@ impl Renderer {
             return;
         }
 
+        let capturing = std::mem::take(&mut self.capture_next_frame);
+        if capturing {
+            self.render_resources.device.start_capture();
+        }
+
+        let tints = match self.options.coloring {
+            AtomColoring::Component => (0..tints.len() as u32)
+                .map(|index| Some(palette_color(index)))
+                .collect(),
+            _ => tints,
+        };
+        let (atoms, transforms, tints, hovered) =
+            self.cull_draws(atoms, transforms, tints, hovered);
+
         self.upload_transforms(&mut encoder, transforms);
+        self.molecular_pass.upload_draws(
+            &self.render_resources,
+            &tints,
+            self.options.coloring,
+            hovered,
+        );
         // self.upload_new_transforms(&mut encoder, world);
         // self.update_transforms(&mut encoder, world);
 
-        let frame = self
-            .surface
-            .get_current_texture()
-            .map(|mut frame| {
-                if frame.suboptimal {
-                    // try again
-                    frame = self
-                        .surface
-                        .get_current_texture()
-                        .expect("could not retrieve swapchain on second try");
-                    if frame.suboptimal {
-                        log::warn!("suboptimal swapchain frame");
-                    }
-                }
-                frame
-            })
-            .expect("failed to get next swapchain");
+        let frame = match &self.target {
+            RenderTarget::Surface { surface, .. } => Some(
+                surface
+                    .get_current_texture()
+                    .map(|mut frame| {
+                        if frame.suboptimal {
+                            // try again
+                            frame = surface
+                                .get_current_texture()
+                                .expect("could not retrieve swapchain on second try");
+                            if frame.suboptimal {
+                                log::warn!("suboptimal swapchain frame");
+                            }
+                        }
+                        frame
+                    })
+                    .expect("failed to get next swapchain"),
+            ),
+            RenderTarget::Texture(_) => None,
+        };
+        let output = match (&frame, &self.target) {
+            (Some(frame), _) => &frame.texture,
+            (None, RenderTarget::Texture(texture)) => texture,
+            (None, RenderTarget::Surface { .. }) => unreachable!("a surface always has a frame"),
+        }
+        .create_view(&wgpu::TextureViewDescriptor::default());
 
-        self.molecular_pass
-            .run(&mut encoder, atoms, self.fragment_transforms.inner_buffer());
+        let atoms = self.limit_atoms(atoms);
+        self.molecular_pass.run(
+            &self.render_resources,
+            &mut encoder,
+            atoms,
+            self.fragment_transforms.inner_buffer(),
+        );
 
         // if interactions.selected_fragments.len() != 0 {
         //     log::warn!("trying to render to stencil");
@@ -342,18 +836,308 @@ impl Renderer {
         // }
 
         // run fxaa pass
-        self.fxaa_pass.run(&mut encoder);
+        if let Some(fxaa_pass) = &self.fxaa_pass {
+            fxaa_pass.run(&mut encoder);
+        }
 
         // blit to screen
-        self.blit_pass.run(
+        self.blit_pass.run(&mut encoder, &output);
+        self.overlay_pass.run(
+            &self.render_resources,
             &mut encoder,
-            &frame
-                .texture
-                .create_view(&wgpu::TextureViewDescriptor::default()),
+            &output,
+            self.overlay.shapes(),
         );
 
         self.render_resources.queue.submit(Some(encoder.finish()));
-        frame.present();
+        if capturing {
+            self.render_resources.device.stop_capture();
+            self.capture = Some(self.describe_frame());
+        }
+        if let Some(frame) = frame {
+            frame.present();
+        }
+    }
+
+    /// Captures the next frame that is rendered, which can then be taken with
+    /// [`Renderer::take_capture`].  See [`FrameCapture`].
+    pub fn capture_next_frame(&mut self) {
+        self.capture_next_frame = true;
+    }
+
+    /// Takes the last frame captured, if one has been since this was last
+    /// called.
+    pub fn take_capture(&mut self) -> Option<FrameCapture> {
+        self.capture.take()
+    }
+
+    // Notes what the frame just rendered drew, and what drew it.  This waits
+    // for the GPU to finish.
+    fn describe_frame(&self) -> FrameCapture {
+        let color_texture = self.molecular_pass.color_texture();
+        FrameCapture {
+            adapter: self.adapter_info.name.clone(),
+            backend: format!("{:?}", self.adapter_info.backend),
+            driver: self.adapter_info.driver.clone(),
+            driver_info: self.adapter_info.driver_info.clone(),
+            software: self.adapter_info.device_type == wgpu::DeviceType::Cpu,
+            gpu_driven_rendering: self.gpu_driven_rendering,
+            width: color_texture.width(),
+            height: color_texture.height(),
+            draw_calls: self.draw_calls(),
+            culled_draws: self.culled_draws,
+            fxaa: self.fxaa_pass.is_some(),
+            depth_prepass: self.options.depth_prepass,
+            conservative_limits: self.options.conservative_limits,
+            pixels: self.read_color(color_texture),
+        }
+    }
+
+    // Finds the draws whose molecules are drawn smaller than the culling
+    // threshold, and either leaves them out, along with their transforms and
+    // tints, or marks them to be drawn as a point.  Each draw comes back with
+    // whether it is such a stand-in.
+    #[allow(clippy::type_complexity)]
+    fn cull_draws<'a>(
+        &mut self,
+        atoms: impl IntoIterator<Item = (&'a AtomBuffer, Viewport)>,
+        transforms: Vec<ultraviolet::Mat4>,
+        tints: Vec<Option<Vec3>>,
+        hovered: Option<HoveredAtom>,
+    ) -> (
+        Vec<(&'a AtomBuffer, Viewport, bool)>,
+        Vec<ultraviolet::Mat4>,
+        Vec<Option<Vec3>>,
+        Option<HoveredAtom>,
+    ) {
+        let camera = self.camera.repr();
+        let culled: Vec<(&AtomBuffer, Viewport, bool)> = atoms
+            .into_iter()
+            .enumerate()
+            .map(|(index, (atoms, viewport))| {
+                let culled = match (self.options.culling, &camera, transforms.get(index)) {
+                    (Some(culling), Some(camera), Some(transform)) => culling.culls(
+                        atoms.bounds(),
+                        transform,
+                        &camera.projection,
+                        &camera.view,
+                        viewport.height,
+                    ),
+                    _ => false,
+                };
+                (atoms, viewport, culled)
+            })
+            .collect();
+        self.culled_draws = culled.iter().filter(|(_, _, culled)| *culled).count();
+
+        let skip = self
+            .options
+            .culling
+            .is_some_and(|culling| culling.mode == CullMode::Skip);
+        if !skip || self.culled_draws == 0 {
+            return (culled, transforms, tints, hovered);
+        }
+        // The hovered atom is no longer hovered if its molecule is left out,
+        // and otherwise moves down by the draws left out before it.
+        let hovered = hovered.and_then(|hovered| {
+            let (_, _, culled_hovered) = culled.get(hovered.draw)?;
+            let before = culled[..hovered.draw]
+                .iter()
+                .filter(|(_, _, culled)| *culled)
+                .count();
+            (!culled_hovered).then_some(HoveredAtom {
+                draw: hovered.draw - before,
+                ..hovered
+            })
+        });
+        let kept = |index: usize| !matches!(culled.get(index), Some((_, _, true)));
+        let transforms = transforms
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| kept(*index))
+            .map(|(_, transform)| transform)
+            .collect();
+        let tints = tints
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| kept(*index))
+            .map(|(_, tint)| tint)
+            .collect();
+        let atoms = culled
+            .iter()
+            .filter(|(_, _, culled)| !culled)
+            .copied()
+            .collect();
+        (atoms, transforms, tints, hovered)
+    }
+
+    // Compares the atoms to be drawn with the scene limits, reporting when
+    // that changes, and switches to drawing points or leaves out the last
+    // molecules to stay within them.  Molecules drawn as a point count as one
+    // atom.
+    fn limit_atoms<'a>(
+        &mut self,
+        mut atoms: Vec<(&'a AtomBuffer, Viewport, bool)>,
+    ) -> Vec<(&'a AtomBuffer, Viewport, bool)> {
+        let drawn_len = |(atoms, _, stand_in): &(&AtomBuffer, Viewport, bool)| {
+            if *stand_in {
+                1
+            } else {
+                atoms.len()
+            }
+        };
+        let limits = self.render_resources.atom_limits();
+        let total = atoms.iter().map(drawn_len).sum();
+        let load = limits.scene_load(total);
+        if load != self.scene_load {
+            match load {
+                SceneLoad::Normal => log::info!("the scene of {} atoms is {}", total, load),
+                _ => log::warn!("the scene of {} atoms is {}", total, load),
+            }
+            self.scene_load = load;
+        }
+
+        if load == SceneLoad::Truncated {
+            let mut drawn = 0;
+            let kept = atoms
+                .iter()
+                .take_while(|draw| {
+                    drawn += drawn_len(draw);
+                    drawn <= limits.max_per_scene
+                })
+                .count();
+            atoms.truncate(kept);
+        }
+        self.molecular_pass.set_points(load >= SceneLoad::Points);
+        atoms
+    }
+
+    /// Reads back the most recently rendered frame of a headless renderer (see
+    /// [`Renderer::new_headless`]) as 8-bit sRGB RGBA pixels, row by row from
+    /// the top.  Returns `None` for a renderer that draws to a window.
+    ///
+    /// This waits for the GPU to finish, so it is meant for tests and batch
+    /// rendering rather than for every frame.
+    pub fn read_pixels(&self) -> Option<Vec<u8>> {
+        let RenderTarget::Texture(texture) = &self.target else {
+            return None;
+        };
+        Some(self.read_color(texture))
+    }
+
+    // Reads back a texture in `SWAPCHAIN_FORMAT` as 8-bit sRGB RGBA pixels.
+    fn read_color(&self, texture: &wgpu::Texture) -> Vec<u8> {
+        let mut pixels = self.read_texture(texture, wgpu::TextureAspect::All, 4);
+        if SWAPCHAIN_FORMAT == wgpu::TextureFormat::Bgra8UnormSrgb {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        pixels
+    }
+
+    /// Reads back the depth of the most recently rendered frame, as the
+    /// distance in front of the camera of what each pixel shows, row by row
+    /// from the top.  The background is infinitely far away.  Like
+    /// [`Renderer::read_pixels`], this waits for the GPU to finish.
+    pub fn read_depth(&self) -> Option<Vec<f32>> {
+        let inverse_projection = self.camera.repr()?.projection.inversed();
+        let bytes = self.read_texture(
+            self.molecular_pass.depth_texture(),
+            wgpu::TextureAspect::DepthOnly,
+            4,
+        );
+        let depths = bytes
+            .chunks_exact(4)
+            .map(|depth| {
+                let depth = f32::from_le_bytes([depth[0], depth[1], depth[2], depth[3]]);
+                // The depth is the same across the view, so the center of the
+                // view is as good as the pixel itself.
+                let view = inverse_projection * Vec4::new(0.0, 0.0, depth, 1.0);
+                if view.w == 0.0 {
+                    f32::INFINITY
+                } else {
+                    -view.z / view.w
+                }
+            })
+            .collect();
+        Some(depths)
+    }
+
+    /// Reads back the normals of the most recently rendered frame, in the
+    /// camera's space (x right, y up, and z towards the viewer), row by row
+    /// from the top.  The background has zero normals.  Like
+    /// [`Renderer::read_pixels`], this waits for the GPU to finish.
+    pub fn read_normals(&self) -> Vec<Vec3> {
+        let bytes = self.read_texture(
+            self.molecular_pass.normals_texture(),
+            wgpu::TextureAspect::All,
+            8,
+        );
+        bytes
+            .chunks_exact(8)
+            .map(|texel| {
+                let channel = |i: usize| f16_to_f32(u16::from_le_bytes([texel[i], texel[i + 1]]));
+                Vec3::new(channel(0), channel(2), channel(4))
+            })
+            .collect()
+    }
+
+    // Copies the whole of `texture` back from the GPU, as rows of
+    // `bytes_per_pixel` bytes per pixel from the top.
+    fn read_texture(
+        &self,
+        texture: &wgpu::Texture,
+        aspect: wgpu::TextureAspect,
+        bytes_per_pixel: u32,
+    ) -> Vec<u8> {
+        let device = &self.render_resources.device;
+        let (width, height) = (texture.width(), texture.height());
+
+        // Each row of the copy must start at a multiple of the alignment.
+        let row_bytes = width * bytes_per_pixel;
+        let padded_row_bytes = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: padded_row_bytes as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect,
+                ..texture.as_image_copy()
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        self.render_resources.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| {
+            if let Err(err) = result {
+                log::error!("could not read back the rendered frame: {}", err);
+            }
+        });
+        device.poll(wgpu::Maintain::Wait);
+
+        let mut bytes = Vec::with_capacity((row_bytes * height) as usize);
+        for row in slice.get_mapped_range().chunks(padded_row_bytes as usize) {
+            bytes.extend_from_slice(&row[..row_bytes as usize]);
+        }
+        buffer.unmap();
+        bytes
     }
 
     // pub fn render(
@@ -428,13 +1212,55 @@ impl Renderer {
 
     /// Immediately calls resize on the supplied camera.
     pub fn set_camera<C: Camera + 'static>(&mut self, camera: C) {
-        self.camera.set_camera(camera, self.size);
+        self.camera.set_camera(camera, self.view_size());
     }
 
     pub fn camera(&mut self) -> &mut RenderCamera {
         &mut self.camera
     }
 
+    /// The number of draw calls that the last frame was drawn with.  Runs of
+    /// draws of the same atoms that look the same, such as the copies of a
+    /// pattern, are drawn as one call with an instance for each.
+    pub fn draw_calls(&self) -> usize {
+        self.molecular_pass.draw_calls()
+    }
+
+    /// Estimates the GPU memory used to render the given atom buffers along
+    /// with the renderer's own resources.  The swapchain itself is excluded,
+    /// as it is owned by the surface, but a headless renderer's target texture
+    /// is included.
+    pub fn gpu_memory<'a>(
+        &self,
+        atoms: impl IntoIterator<Item = &'a AtomBuffer>,
+    ) -> GpuMemoryUsage {
+        let pixels = self.size.width as u64 * self.size.height as u64;
+        let targets = if self.fxaa_pass.is_some() {
+            &RENDER_TARGET_FORMATS[..]
+        } else {
+            &RENDER_TARGET_FORMATS[..3]
+        };
+        let render_targets = targets
+            .iter()
+            .chain(match self.target {
+                RenderTarget::Surface { .. } => None,
+                RenderTarget::Texture(_) => Some(&SWAPCHAIN_FORMAT),
+            })
+            .map(|format| pixels * format.block_size(None).unwrap_or(0) as u64)
+            .sum();
+
+        GpuMemoryUsage {
+            atoms: atoms.into_iter().map(AtomBuffer::gpu_memory).sum(),
+            transforms: self.fragment_transforms.inner_buffer().size(),
+            render_targets,
+            uniforms: self.vertex_contants_buffer.size()
+                + self.periodic_table_buffer.size()
+                + self.molecular_pass.per_draw_buffer_size()
+                + self.molecular_pass.fog_buffer_size()
+                + std::mem::size_of::<CameraRepr>() as u64,
+        }
+    }
+
     // pub fn update_render_config(&mut self, enabled: bool) {
 
     // }
@@ -488,6 +1314,16 @@ impl Renderer {
     //     }
     // }
 
+    // The texture that a headless renderer draws into in place of a window.
+    fn create_target_texture(device: &wgpu::Device, size: PhysicalSize<u32>) -> wgpu::Texture {
+        Self::create_texture(
+            device,
+            size,
+            SWAPCHAIN_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        )
+    }
+
     fn create_texture(
         device: &wgpu::Device,
         size: PhysicalSize<u32>,
@@ -511,4 +1347,17 @@ impl Renderer {
     }
 }
 
+// Widens a half-precision float, as stored in the normals texture.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
 // End of File
@@ -0,0 +1,153 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Limits on how many atoms are drawn, so that a huge import slows down or is
+//! simplified rather than exhausting GPU memory and losing the device.  Each
+//! limit has a soft level, past which a warning is logged, and a hard level:
+//! molecules past it are not uploaded at all, and scenes past it are drawn as
+//! points, then cut short.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// The number of atoms in a row of an atom buffer's textures.
+pub(crate) const ATOMS_PER_ROW: usize = 2048;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AtomLimits {
+    /// Molecules with more atoms than this are reported when uploaded.
+    pub warn_per_molecule: usize,
+    /// Molecules with more atoms than this are not uploaded, and so not
+    /// drawn.  The device's largest texture caps this too.
+    pub max_per_molecule: usize,
+    /// Frames drawing more atoms than this, counting every instance, are
+    /// reported.
+    pub warn_per_scene: usize,
+    /// Frames drawing more atoms than this draw each as a single point rather
+    /// than a shaded sphere.
+    pub points_per_scene: usize,
+    /// Frames drawing more atoms than this leave out the molecules that
+    /// would take them past it.
+    pub max_per_scene: usize,
+}
+
+impl Default for AtomLimits {
+    fn default() -> Self {
+        Self {
+            warn_per_molecule: 1_000_000,
+            max_per_molecule: 16_000_000,
+            warn_per_scene: 10_000_000,
+            points_per_scene: 50_000_000,
+            max_per_scene: 500_000_000,
+        }
+    }
+}
+
+/// How a frame's atoms compare with the scene limits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SceneLoad {
+    #[default]
+    Normal,
+    /// Past the soft limit.
+    Heavy,
+    /// Drawn as points.
+    Points,
+    /// Past the hard limit, so some molecules are left out.
+    Truncated,
+}
+
+impl AtomLimits {
+    /// Parses limits written as comma-separated `name=count` pairs, such as
+    /// `max-molecule=2000000,points=20000000`, starting from the defaults.
+    /// The names are `warn-molecule`, `max-molecule`, `warn-scene`, `points`
+    /// and `max-scene`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut limits = Self::default();
+        for pair in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            let (name, count) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected `name=count`, not `{}`", pair))?;
+            let count: usize = count
+                .trim()
+                .replace('_', "")
+                .parse()
+                .map_err(|_| format!("`{}` is not a number of atoms", count))?;
+            let limit = match name.trim() {
+                "warn-molecule" => &mut limits.warn_per_molecule,
+                "max-molecule" => &mut limits.max_per_molecule,
+                "warn-scene" => &mut limits.warn_per_scene,
+                "points" => &mut limits.points_per_scene,
+                "max-scene" => &mut limits.max_per_scene,
+                name => return Err(format!("unknown limit `{}`", name)),
+            };
+            *limit = count;
+        }
+        Ok(limits)
+    }
+
+    /// The limits, with the largest molecule no bigger than a device whose
+    /// textures are at most `max_texture_dimension` texels on a side can hold.
+    pub(crate) fn for_device(self, max_texture_dimension: u32) -> Self {
+        Self {
+            max_per_molecule: self
+                .max_per_molecule
+                .min(ATOMS_PER_ROW * max_texture_dimension as usize),
+            ..self
+        }
+    }
+
+    /// Whether a molecule of `atoms` atoms may be uploaded.  Molecules past
+    /// either limit are logged.
+    pub fn admit_molecule(&self, atoms: usize) -> bool {
+        if atoms > self.max_per_molecule {
+            log::error!(
+                "not drawing a molecule of {} atoms, which is over the limit of {}",
+                atoms,
+                self.max_per_molecule
+            );
+            return false;
+        }
+        if atoms > self.warn_per_molecule {
+            log::warn!(
+                "uploading a molecule of {} atoms, which is over the soft limit of {}",
+                atoms,
+                self.warn_per_molecule
+            );
+        }
+        true
+    }
+
+    /// How a frame drawing `atoms` atoms in all compares with the limits.
+    pub fn scene_load(&self, atoms: usize) -> SceneLoad {
+        if atoms > self.max_per_scene {
+            SceneLoad::Truncated
+        } else if atoms > self.points_per_scene {
+            SceneLoad::Points
+        } else if atoms > self.warn_per_scene {
+            SceneLoad::Heavy
+        } else {
+            SceneLoad::Normal
+        }
+    }
+}
+
+impl fmt::Display for SceneLoad {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneLoad::Normal => write!(f, "within the atom limits"),
+            SceneLoad::Heavy => write!(f, "over the soft atom limit"),
+            SceneLoad::Points => write!(f, "over the point limit, so atoms are drawn as points"),
+            SceneLoad::Truncated => {
+                write!(f, "over the atom limit, so some molecules are left out")
+            }
+        }
+    }
+}
+
+// End of File
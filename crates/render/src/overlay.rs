@@ -0,0 +1,192 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Flat shapes and text drawn over the views, such as the panels of the user
+//! interface.
+
+use common::AsBytes;
+use std::f32::consts::TAU;
+use ultraviolet::{Vec2, Vec4};
+
+// One shape of an overlay, as it is uploaded.  See overlay.wgsl.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub(crate) struct OverlayShape {
+    rect: Vec4,
+    color: Vec4,
+    arc: Vec4,
+}
+
+unsafe impl AsBytes for OverlayShape {}
+
+/// What is drawn over the views: filled rectangles, parts of rings, and lines
+/// of text, each over those added before it.  Positions and sizes are in
+/// physical pixels from the top left of the window, and colors are linear RGB
+/// with alpha.
+#[derive(Clone, Debug, Default)]
+pub struct Overlay {
+    shapes: Vec<OverlayShape>,
+    // The size of each dot of the font.
+    dot: f32,
+}
+
+// The width and height of a glyph, in dots.
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+
+impl Overlay {
+    /// An empty overlay, with text sized for a window with `scale_factor`
+    /// physical pixels to each logical one.
+    pub fn new(scale_factor: f64) -> Self {
+        Self {
+            shapes: Vec::new(),
+            dot: (2.0 * scale_factor).round().max(1.0) as f32,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    pub(crate) fn shapes(&self) -> &[OverlayShape] {
+        &self.shapes
+    }
+
+    /// Fills a rectangle.
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Vec4) {
+        self.shapes.push(OverlayShape {
+            rect: Vec4::new(x, y, width, height),
+            color,
+            arc: Vec4::zero(),
+        });
+    }
+
+    /// Fills the part of a ring around `center` between the `inner` and
+    /// `outer` radius, from `start` to `end`, which are in radians clockwise
+    /// from the top.
+    pub fn arc(&mut self, center: Vec2, inner: f32, outer: f32, start: f32, end: f32, color: Vec4) {
+        let start_wrapped = start.rem_euclid(TAU);
+        self.shapes.push(OverlayShape {
+            rect: Vec4::new(center.x - outer, center.y - outer, 2.0 * outer, 2.0 * outer),
+            color,
+            arc: Vec4::new(inner, outer, start_wrapped, start_wrapped + (end - start)),
+        });
+    }
+
+    /// Writes a line of `text` with its top left corner at `x`, `y`, and
+    /// returns how wide it is.  Characters the font does not have are drawn as
+    /// question marks.
+    pub fn text(&mut self, x: f32, y: f32, text: &str, color: Vec4) -> f32 {
+        let advance = self.dot * (GLYPH_WIDTH + 1) as f32;
+        for (index, c) in text.chars().enumerate() {
+            let left = x + index as f32 * advance;
+            for (column, bits) in glyph(c).iter().enumerate() {
+                // Each run of dots down a column is one rectangle.
+                let mut row = 0;
+                while row < GLYPH_HEIGHT {
+                    if bits & (1 << row) == 0 {
+                        row += 1;
+                        continue;
+                    }
+                    let top = row;
+                    while row < GLYPH_HEIGHT && bits & (1 << row) != 0 {
+                        row += 1;
+                    }
+                    self.rect(
+                        left + column as f32 * self.dot,
+                        y + top as f32 * self.dot,
+                        self.dot,
+                        (row - top) as f32 * self.dot,
+                        color,
+                    );
+                }
+            }
+        }
+        self.text_width(text)
+    }
+
+    /// How wide `text` is when written.
+    pub fn text_width(&self, text: &str) -> f32 {
+        match text.chars().count() {
+            0 => 0.0,
+            count => self.dot * ((GLYPH_WIDTH + 1) * count - 1) as f32,
+        }
+    }
+
+    /// How far apart lines of text are.
+    pub fn line_height(&self) -> f32 {
+        self.dot * (GLYPH_HEIGHT + 2) as f32
+    }
+}
+
+// The columns of the glyph of `c`, from the left, with the top row in the
+// lowest bit.
+fn glyph(c: char) -> &'static [u8; GLYPH_WIDTH] {
+    match c {
+        ' '..='~' => &FONT[c as usize - ' ' as usize],
+        '°' => &[0x00, 0x06, 0x09, 0x09, 0x06],
+        'Å' => &[0x78, 0x14, 0x15, 0x14, 0x78],
+        '·' => &[0x00, 0x00, 0x08, 0x00, 0x00],
+        '×' => &[0x22, 0x14, 0x08, 0x14, 0x22],
+        _ => &FONT['?' as usize - ' ' as usize],
+    }
+}
+
+// A 5×7 font of the printable ASCII characters, from space to tilde.
+#[rustfmt::skip]
+const FONT: [[u8; GLYPH_WIDTH]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5f, 0x00, 0x00],
+    [0x00, 0x07, 0x00, 0x07, 0x00], [0x14, 0x7f, 0x14, 0x7f, 0x14],
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62],
+    [0x36, 0x49, 0x56, 0x20, 0x50], [0x00, 0x00, 0x07, 0x00, 0x00],
+    [0x00, 0x1c, 0x22, 0x41, 0x00], [0x00, 0x41, 0x22, 0x1c, 0x00],
+    [0x14, 0x08, 0x3e, 0x08, 0x14], [0x08, 0x08, 0x3e, 0x08, 0x08],
+    [0x00, 0x50, 0x30, 0x00, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08],
+    [0x00, 0x60, 0x60, 0x00, 0x00], [0x20, 0x10, 0x08, 0x04, 0x02],
+    // 0-9
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], [0x00, 0x42, 0x7f, 0x40, 0x00],
+    [0x42, 0x61, 0x51, 0x49, 0x46], [0x21, 0x41, 0x45, 0x4b, 0x31],
+    [0x18, 0x14, 0x12, 0x7f, 0x10], [0x27, 0x45, 0x45, 0x45, 0x39],
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], [0x01, 0x71, 0x09, 0x05, 0x03],
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x06, 0x49, 0x49, 0x29, 0x1e],
+    [0x00, 0x36, 0x36, 0x00, 0x00], [0x00, 0x56, 0x36, 0x00, 0x00],
+    [0x08, 0x14, 0x22, 0x41, 0x00], [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x51, 0x09, 0x06],
+    // @, A-Z
+    [0x32, 0x49, 0x79, 0x41, 0x3e], [0x7e, 0x11, 0x11, 0x11, 0x7e],
+    [0x7f, 0x49, 0x49, 0x49, 0x36], [0x3e, 0x41, 0x41, 0x41, 0x22],
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], [0x7f, 0x49, 0x49, 0x49, 0x41],
+    [0x7f, 0x09, 0x09, 0x09, 0x01], [0x3e, 0x41, 0x49, 0x49, 0x7a],
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], [0x00, 0x41, 0x7f, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3f, 0x01], [0x7f, 0x08, 0x14, 0x22, 0x41],
+    [0x7f, 0x40, 0x40, 0x40, 0x40], [0x7f, 0x02, 0x0c, 0x02, 0x7f],
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], [0x3e, 0x41, 0x41, 0x41, 0x3e],
+    [0x7f, 0x09, 0x09, 0x09, 0x06], [0x3e, 0x41, 0x51, 0x21, 0x5e],
+    [0x7f, 0x09, 0x19, 0x29, 0x46], [0x46, 0x49, 0x49, 0x49, 0x31],
+    [0x01, 0x01, 0x7f, 0x01, 0x01], [0x3f, 0x40, 0x40, 0x40, 0x3f],
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], [0x3f, 0x40, 0x38, 0x40, 0x3f],
+    [0x63, 0x14, 0x08, 0x14, 0x63], [0x07, 0x08, 0x70, 0x08, 0x07],
+    [0x61, 0x51, 0x49, 0x45, 0x43], [0x00, 0x7f, 0x41, 0x41, 0x00],
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x7f, 0x00],
+    [0x04, 0x02, 0x01, 0x02, 0x04], [0x40, 0x40, 0x40, 0x40, 0x40],
+    // `, a-z
+    [0x00, 0x01, 0x02, 0x04, 0x00], [0x20, 0x54, 0x54, 0x54, 0x78],
+    [0x7f, 0x48, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x20],
+    [0x38, 0x44, 0x44, 0x48, 0x7f], [0x38, 0x54, 0x54, 0x54, 0x18],
+    [0x08, 0x7e, 0x09, 0x01, 0x02], [0x0c, 0x52, 0x52, 0x52, 0x3e],
+    [0x7f, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7d, 0x40, 0x00],
+    [0x20, 0x40, 0x44, 0x3d, 0x00], [0x7f, 0x10, 0x28, 0x44, 0x00],
+    [0x00, 0x41, 0x7f, 0x40, 0x00], [0x7c, 0x04, 0x18, 0x04, 0x78],
+    [0x7c, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38],
+    [0x7c, 0x14, 0x14, 0x14, 0x08], [0x08, 0x14, 0x14, 0x18, 0x7c],
+    [0x7c, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x20],
+    [0x04, 0x3f, 0x44, 0x40, 0x20], [0x3c, 0x40, 0x40, 0x20, 0x7c],
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], [0x3c, 0x40, 0x30, 0x40, 0x3c],
+    [0x44, 0x28, 0x10, 0x28, 0x44], [0x0c, 0x50, 0x50, 0x50, 0x3c],
+    [0x44, 0x64, 0x54, 0x4c, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00],
+    [0x00, 0x00, 0x7f, 0x00, 0x00], [0x00, 0x41, 0x36, 0x08, 0x00],
+    [0x08, 0x04, 0x08, 0x10, 0x08],
+];
+
+// End of File
@@ -40,6 +40,18 @@ var<uniform> periodic_table: PeriodicTable;
 @group(0) @binding(2)
 var<uniform> vertices: array<Vertex, 3>;
 
+struct Fog {
+    background: vec3<f32>,
+    // 0 for no fog, 1 for linear fog and 2 for exponential fog.
+    mode: u32,
+    start: f32,
+    end: f32,
+    density: f32,
+};
+
+@group(0) @binding(3)
+var<uniform> fog: Fog;
+
 struct Atom {
     pos: vec3<f32>,
     kind: u32,
@@ -50,6 +62,21 @@ var atoms_pos: texture_2d<f32>;
 @group(1) @binding(1)
 var atoms_kind: texture_2d<u32>;
 
+struct PerDraw {
+    // rgb is the tint color, and a is how far it replaces the element color.
+    tint: vec4<f32>,
+    // 0 to color by element, 1 by group and 2 by property, ramped from `low`
+    // to `high`.
+    coloring: u32,
+    low: f32,
+    high: f32,
+    // The index of the atom drawn with a glowing rim, or 0xffffffff for none.
+    highlight: u32,
+};
+
+@group(2) @binding(0)
+var<uniform> per_draw: PerDraw;
+
 struct AtomVertexInput {
     @builtin(vertex_index)
     index: u32,
@@ -72,12 +99,56 @@ struct AtomVertexOutput {
     position_clip_space: vec4<f32>,
     @location(2) @interpolate(flat)
     element_vec: vec4<f32>,
+    @location(3) @interpolate(flat)
+    pinned: u32,
     @location(4) @interpolate(flat)
     center_view_space: vec4<f32>,
     @location(5)
     position_view_space: vec4<f32>,
+    @location(6) @interpolate(flat)
+    highlighted: u32,
 };
 
+// Matches `srgb_to_linear` in `color.rs`.  Colors are shaded in linear RGB.
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let lower = color / 12.92;
+    let higher = pow((color + vec3(0.055)) / 1.055, vec3(2.4));
+    return select(higher, lower, color <= vec3(0.04045));
+}
+
+// The `index`th of a series of colors that are easy to tell apart, in linear
+// RGB.  This matches `palette_color` in `color_scheme.rs`, which is in sRGB.
+fn palette(index: u32) -> vec3<f32> {
+    let hue = fract(f32(index) * 0.618034);
+    let k = clamp(abs(fract(vec3(hue) + vec3(1.0, 2.0 / 3.0, 1.0 / 3.0)) * 6.0 - 3.0) - 1.0, vec3(0.0), vec3(1.0));
+    return srgb_to_linear(0.95 * mix(vec3(1.0), k, 0.55));
+}
+
+// Blue at 0, white at 0.5 and red at 1, in linear RGB.  The ramp is blended
+// in sRGB, so that white sits in the perceptual middle.
+fn ramp(t: f32) -> vec3<f32> {
+    let blue = vec3(0.2, 0.3, 1.0);
+    let red = vec3(1.0, 0.2, 0.2);
+    let s = clamp(t, 0.0, 1.0);
+    if (s < 0.5) {
+        return srgb_to_linear(mix(blue, vec3(1.0), s * 2.0));
+    }
+    return srgb_to_linear(mix(vec3(1.0), red, s * 2.0 - 1.0));
+}
+
+// The color of an atom of the given kind and property, before shading.
+fn atom_color(kind: u32, property: f32) -> vec3<f32> {
+    let element = periodic_table.elements[kind & 0x7fu];
+    var base_color = element.color;
+    let group = (kind >> 8u) & 0xffu;
+    if (per_draw.coloring == 1u && group != 0u) {
+        base_color = palette(group);
+    } else if (per_draw.coloring == 2u) {
+        base_color = ramp((property - per_draw.low) / max(per_draw.high - per_draw.low, 0.0001));
+    }
+    return mix(base_color, per_draw.tint.rgb, per_draw.tint.a);
+}
+
 @vertex
 fn vs_main(in: AtomVertexInput) -> AtomVertexOutput {
     let idx = in.index / 3u;
@@ -86,7 +157,8 @@ fn vs_main(in: AtomVertexInput) -> AtomVertexOutput {
     let texel_kind = textureLoad(atoms_kind, coord, 0);
     let atom = Atom(texel_pos.xyz, texel_kind.x);
     let element = periodic_table.elements[atom.kind & 0x7fu];
-    let element_vec = vec4<f32>(element.color, element.radius);
+    let color = atom_color(atom.kind, texel_pos.w);
+    let element_vec = vec4<f32>(color, element.radius);
     let vertex = element.radius * vertices[in.index % 3u].xy;
 
     let part_fragment_transform = mat4x4<f32>(
@@ -106,11 +178,19 @@ fn vs_main(in: AtomVertexInput) -> AtomVertexOutput {
         1.0
     );
 
-    let position_clip_space = camera.projection_view * position_worldspace;
+    // Hidden atoms are moved outside the clip volume, so that they are
+    // clipped away.
+    var position_clip_space = camera.projection_view * position_worldspace;
+    if ((atom.kind >> 16u) & 1u) == 1u {
+        position_clip_space = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
     let center_view_space = camera.view * vec4<f32>(atom.pos, 0.0);
     let position_view_space = camera.view * position_worldspace;
 
-    return AtomVertexOutput(position_clip_space, vertex, position_clip_space, element_vec, center_view_space, position_view_space);
+    let pinned = (atom.kind >> 7u) & 1u;
+    let highlighted = u32(idx == per_draw.highlight);
+
+    return AtomVertexOutput(position_clip_space, vertex, position_clip_space, element_vec, pinned, center_view_space, position_view_space, highlighted);
 }
 
 alias AtomFragmentInput = AtomVertexOutput;
@@ -128,26 +208,180 @@ fn map(value: f32, low1: f32, high1: f32, low2: f32, high2: f32) -> f32 {
     return low2 + (value - low1) * (high2 - low2) / (high1 - low1);
 }
 
-@fragment
-fn fs_main(in: AtomFragmentInput) -> AtomFragmentOutput {
+// How far a point `distance` in front of the camera has faded into the
+// background, from 0 (not at all) to 1 (entirely).
+fn fog_amount(distance: f32) -> f32 {
+    if (fog.mode == 1u) {
+        return clamp((distance - fog.start) / max(fog.end - fog.start, 0.0001), 0.0, 1.0);
+    }
+    if (fog.mode == 2u) {
+        return 1.0 - exp(-fog.density * max(distance, 0.0));
+    }
+    return 0.0;
+}
+
+// How far the sphere's surface is in front of its billboard at a fragment, or
+// a negative distance for a fragment outside the sphere.
+fn surface_offset(in: AtomFragmentInput) -> f32 {
+    let radius = in.element_vec.w;
+    let dist = length(in.uv);
+    if (dist > radius) {
+        return -1.0;
+    }
+    return sqrt(radius * radius - dist * dist);
+}
+
+// The depth of the sphere's surface, `z` in front of the billboard.
+fn surface_depth(in: AtomFragmentInput, z: f32) -> f32 {
+    let in_pos_clipspace = in.position_clip_space + camera.projection[2] * z;
+    return in_pos_clipspace.z / in_pos_clipspace.w;
+}
+
+struct ShadedFragment {
+    @location(0)
+    color: vec4<f32>,
+    @location(1)
+    normal: vec4<f32>,
+}
+
+// The color and normal of the sphere's surface, `z` in front of the
+// billboard.
+fn shade(in: AtomFragmentInput, z: f32) -> ShadedFragment {
     let element = Element(in.element_vec.xyz, in.element_vec.w);
     let dist = length(in.uv);
-    if (dist > element.radius) {
+
+    // Pinned atoms are outlined with a dark ring.
+    var base_color = element.color;
+    if (in.pinned != 0u && dist > 0.75 * element.radius) {
+        base_color = vec3(0.1, 0.1, 0.1);
+    }
+
+    let shaded = base_color * map(z, 0.0, element.radius, 0.25, 1.0);
+    // The view looks down -z, and the sphere's surface is `z` nearer than the
+    // billboard.
+    let distance = -(in.position_view_space.z + z);
+    var color = vec4(mix(shaded, fog.background, fog_amount(distance)), 1.0);
+    // The highlighted atom glows around its edge.  The glow is added after
+    // the fog, so that it shows however far away the atom is.
+    if (in.highlighted != 0u) {
+        let rim = smoothstep(0.6, 1.0, dist / element.radius);
+        color = vec4(color.rgb + rim * vec3(1.0, 0.9, 0.4), 1.0);
+    }
+    let normal = vec4(normalize(in.position_view_space.xyz - in.center_view_space.xyz), 0.0);
+
+    return ShadedFragment(color, normal);
+}
+
+@fragment
+fn fs_main(in: AtomFragmentInput) -> AtomFragmentOutput {
+    let z = surface_offset(in);
+    if (z < 0.0) {
         discard;
     }
+    let shaded = shade(in, z);
+    return AtomFragmentOutput(surface_depth(in, z), shaded.color, shaded.normal);
+}
 
-    let z = sqrt(element.radius * element.radius - dist * dist);
-    let in_pos_clipspace = in.position_clip_space + camera.projection[2] * z;
+// The depth pre-pass, which only finds the nearest surface at each pixel.
+struct DepthFragmentOutput {
+    @builtin(frag_depth)
+    depth: f32,
+}
 
-    let depth = in_pos_clipspace.z / in_pos_clipspace.w;
+@fragment
+fn fs_depth(in: AtomFragmentInput) -> DepthFragmentOutput {
+    let z = surface_offset(in);
+    if (z < 0.0) {
+        discard;
+    }
+    return DepthFragmentOutput(surface_depth(in, z));
+}
 
-    let color = vec4(
-        element.color * map(z, 0.0, element.radius, 0.25, 1.0),
-        1.0
+// The depth found by the pre-pass.  The color pass after it cannot leave the
+// depth test to the hardware, which would only run it after shading since the
+// shader writes the depth, so each fragment tests itself before it is shaded.
+@group(3) @binding(0)
+var prepass_depth: texture_depth_2d;
+
+// How much nearer than a fragment the pre-pass may have found the surface and
+// still let the fragment through, as a fraction of the depth.  This allows for
+// the two passes rounding differently.
+const DEPTH_TOLERANCE: f32 = 0.00001;
+
+@fragment
+fn fs_tested(in: AtomFragmentInput) -> ShadedFragment {
+    let z = surface_offset(in);
+    if (z < 0.0) {
+        discard;
+    }
+    // Depths are reversed, with 0 at infinity, so nearer is greater.
+    let nearest = textureLoad(prepass_depth, vec2<i32>(in.position.xy), 0);
+    if (surface_depth(in, z) < nearest * (1.0 - DEPTH_TOLERANCE)) {
+        discard;
+    }
+    return shade(in, z);
+}
+
+// Atoms drawn as single points, for scenes with too many atoms to shade.  Each
+// vertex is one atom, flat colored and facing the camera.
+struct PointVertexOutput {
+    @builtin(position)
+    position: vec4<f32>,
+    @location(0) @interpolate(flat)
+    color: vec3<f32>,
+    @location(1)
+    distance: f32,
+};
+
+@vertex
+fn vs_point(in: AtomVertexInput) -> PointVertexOutput {
+    let coord = vec2<u32>(in.index & 0x000007ffu, in.index >> 11u);
+    let texel_pos = textureLoad(atoms_pos, coord, 0);
+    let kind = textureLoad(atoms_kind, coord, 0).x;
+
+    let part_fragment_transform = mat4x4<f32>(
+        in.part_fragment_transform_0,
+        in.part_fragment_transform_1,
+        in.part_fragment_transform_2,
+        in.part_fragment_transform_3
     );
-    let normal = vec4(normalize(in.position_view_space.xyz - in.center_view_space.xyz), 0.0);
+    let position = part_fragment_transform * vec4<f32>(texel_pos.xyz, 1.0);
+    let distance = -(camera.view * position).z;
+
+    var position_clip_space = camera.projection_view * position;
+    if ((kind >> 16u) & 1u) == 1u {
+        position_clip_space = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+    }
+
+    return PointVertexOutput(position_clip_space, atom_color(kind, texel_pos.w), distance);
+}
+
+struct PointFragmentOutput {
+    @location(0)
+    color: vec4<f32>,
+    @location(1)
+    normal: vec4<f32>,
+}
+
+fn shade_point(in: PointVertexOutput) -> PointFragmentOutput {
+    let color = vec4(mix(in.color, fog.background, fog_amount(in.distance)), 1.0);
+    return PointFragmentOutput(color, vec4(0.0, 0.0, 1.0, 0.0));
+}
 
-    return AtomFragmentOutput(depth, color, normal);
+@fragment
+fn fs_point(in: PointVertexOutput) -> PointFragmentOutput {
+    return shade_point(in);
+}
+
+// Points shaded after the depth pre-pass, which test themselves against it
+// as `fs_tested` does.
+@fragment
+fn fs_point_tested(in: PointVertexOutput) -> PointFragmentOutput {
+    let nearest = textureLoad(prepass_depth, vec2<i32>(in.position.xy), 0);
+    if (in.position.z < nearest * (1.0 - DEPTH_TOLERANCE)) {
+        discard;
+    }
+    return shade_point(in);
 }
 
 // End of File
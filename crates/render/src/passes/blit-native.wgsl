@@ -14,10 +14,14 @@ struct FullscreenVertexOutput {
 var linear_sampler: sampler;
 @group(0) @binding(1)
 var color_texture: texture_2d<f32>;
+// Takes linear sRGB to the linear RGB of the output color space.
+@group(0) @binding(2)
+var<uniform> conversion: mat3x3<f32>;
 
 @fragment
 fn main(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(color_texture, linear_sampler, in.uv);
+    let color = textureSample(color_texture, linear_sampler, in.uv);
+    return vec4(conversion * color.rgb, color.a);
 }
 
 // End of File
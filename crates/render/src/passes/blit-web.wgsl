@@ -14,10 +14,13 @@ struct FullscreenVertexOutput {
 var linear_sampler: sampler;
 @group(0) @binding(1)
 var color_texture: texture_2d<f32>;
+// Takes linear sRGB to the linear RGB of the output color space.
+@group(0) @binding(2)
+var<uniform> conversion: mat3x3<f32>;
 
 fn linear_to_srgb(input_color: vec4<f32>) -> vec4<f32> {
     let cutoff = vec3<f32>(input_color.rgb < vec3(0.0031308));
-    let higher = vec3(1.005) * pow(input_color.rgb, vec3(1.0 / 2.4)) - vec3(0.055);
+    let higher = vec3(1.055) * pow(input_color.rgb, vec3(1.0 / 2.4)) - vec3(0.055);
     let lower = input_color.rgb * vec3(12.92);
 
     return vec4<f32>(mix(higher, lower, cutoff), input_color.a);
@@ -27,7 +30,8 @@ fn linear_to_srgb(input_color: vec4<f32>) -> vec4<f32> {
 fn main(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
     // Currently, webgpu doesn't automatically convert linear rgb outputs to
     // srgb so we do it manually.
-    return linear_to_srgb(textureSample(color_texture, linear_sampler, in.uv));
+    let color = textureSample(color_texture, linear_sampler, in.uv);
+    return linear_to_srgb(vec4(conversion * color.rgb, color.a));
 }
 
 // End of File
@@ -2,23 +2,42 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use crate::{GlobalRenderResources, SWAPCHAIN_FORMAT};
+use crate::{GlobalRenderResources, OutputColorSpace, SWAPCHAIN_FORMAT};
+use common::AsBytes as _;
+use ultraviolet::Vec4;
+use wgpu::util::DeviceExt as _;
 
 pub struct BlitPass {
     bind_group_layout: wgpu::BindGroupLayout,
     pipeline: wgpu::RenderPipeline,
     render_bundle: wgpu::RenderBundle,
+    // The matrix from linear sRGB to the output color space, as the three
+    // columns of a `mat3x3`, each padded to a `vec4`.
+    conversion_buffer: wgpu::Buffer,
 }
 
 impl BlitPass {
-    pub fn new(render_resources: &GlobalRenderResources, input: &wgpu::TextureView) -> Self {
+    pub fn new(
+        render_resources: &GlobalRenderResources,
+        input: &wgpu::TextureView,
+        color_space: OutputColorSpace,
+    ) -> Self {
         let bind_group_layout = create_bind_group_layout(&render_resources.device);
         let pipeline = create_blit_pipeline(&render_resources.device, &bind_group_layout);
+        let conversion_buffer =
+            render_resources
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: None,
+                    contents: conversion_columns(color_space)[..].as_bytes(),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
         let render_bundle = create_blit_render_bundle(
             &render_resources.device,
             &bind_group_layout,
             &render_resources.linear_sampler,
             input,
+            &conversion_buffer,
             &pipeline,
         );
 
@@ -26,9 +45,23 @@ impl BlitPass {
             bind_group_layout,
             pipeline,
             render_bundle,
+            conversion_buffer,
         }
     }
 
+    /// Encodes frames in `color_space` from the next one on.
+    pub fn set_color_space(
+        &self,
+        render_resources: &GlobalRenderResources,
+        color_space: OutputColorSpace,
+    ) {
+        render_resources.queue.write_buffer(
+            &self.conversion_buffer,
+            0,
+            conversion_columns(color_space)[..].as_bytes(),
+        );
+    }
+
     pub fn run(&self, encoder: &mut wgpu::CommandEncoder, frame: &wgpu::TextureView) {
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
@@ -57,6 +90,7 @@ impl BlitPass {
             &self.bind_group_layout,
             &render_resources.linear_sampler,
             input,
+            &self.conversion_buffer,
             &self.pipeline,
         );
     }
@@ -82,6 +116,16 @@ fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
                 },
                 count: None,
             },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
         ],
     })
 }
@@ -140,6 +184,7 @@ fn create_blit_render_bundle(
     bind_group_layout: &wgpu::BindGroupLayout,
     linear_sampler: &wgpu::Sampler,
     input_texture: &wgpu::TextureView,
+    conversion_buffer: &wgpu::Buffer,
     blit_pipeline: &wgpu::RenderPipeline,
 ) -> wgpu::RenderBundle {
     let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -154,6 +199,10 @@ fn create_blit_render_bundle(
                 binding: 1,
                 resource: wgpu::BindingResource::TextureView(input_texture),
             },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: conversion_buffer.as_entire_binding(),
+            },
         ],
     });
 
@@ -171,4 +220,11 @@ fn create_blit_render_bundle(
     encoder.finish(&wgpu::RenderBundleDescriptor { label: None })
 }
 
+fn conversion_columns(color_space: OutputColorSpace) -> [Vec4; 3] {
+    let conversion = color_space.conversion();
+    conversion
+        .cols
+        .map(|column| column.into_homogeneous_vector())
+}
+
 // End of File
@@ -5,9 +5,11 @@
 mod blit;
 mod fxaa;
 mod molecular;
+mod overlay;
 
 pub use blit::BlitPass;
 pub use fxaa::FxaaPass;
 pub use molecular::MolecularPass;
+pub use overlay::OverlayPass;
 
 // End of File
@@ -2,25 +2,145 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use crate::{AtomBuffer, GlobalRenderResources, Renderer, SWAPCHAIN_FORMAT};
-use std::{convert::TryInto as _, mem};
+use crate::{
+    color::srgb_to_linear, AtomBuffer, AtomColoring, Fog, GlobalRenderResources, HoveredAtom,
+    Renderer, Viewport, SWAPCHAIN_FORMAT,
+};
+use common::AsBytes;
+use std::{convert::TryInto as _, mem, num::NonZeroU64};
+use ultraviolet::{Vec3, Vec4};
 use winit::dpi::PhysicalSize;
 
 // Renders atoms
 pub struct MolecularPass {
-    pipeline: wgpu::RenderPipeline,
+    // Indexed by `AtomPipeline`.
+    pipelines: Vec<wgpu::RenderPipeline>,
+    // Draws each atom as a single point, for scenes too large to shade.
+    points: bool,
+    // With the depth pre-pass, the atoms are first drawn into the depth
+    // buffer alone, and then shaded only where they are nearest, so that
+    // atoms hidden behind others in dense scenes are not shaded.
+    depth_prepass: bool,
+    // Gives the shaded pass the depth found by the pre-pass.
+    prepass_depth_bgl: wgpu::BindGroupLayout,
+    prepass_depth_bg: wgpu::BindGroup,
     top_level_bg: wgpu::BindGroup,
 
-    color_texture: wgpu::TextureView,
-    depth_texture: wgpu::TextureView,
+    // The background color and fog settings.  The atoms are drawn over the
+    // background, and fade into it with distance.
+    fog_buffer: wgpu::Buffer,
+    background: wgpu::Color,
+
+    // The uniforms of every draw, one after another.  Each draw binds its own
+    // entry with a dynamic offset.
+    per_draw_bgl: wgpu::BindGroupLayout,
+    per_draw_buffer: wgpu::Buffer,
+    per_draw_bg: wgpu::BindGroup,
+    per_draw_capacity: usize,
+
+    // The uniforms last uploaded, so that draws that look the same can be
+    // told apart from those that do not.
+    per_draw: Vec<PerDrawUniforms>,
+
+    // The draws of the last frame, recorded into render bundles so that only
+    // the draws whose inputs have changed need to be encoded again.  Runs of
+    // draws of the same atoms that look the same are recorded as one draw of
+    // several instances.
+    draws: Vec<CachedDraw>,
+
+    // The color, depth and normals are kept as textures as well as views, so
+    // that they can be read back for export and frame captures.
+    color_texture: wgpu::Texture,
+    color_view: wgpu::TextureView,
+    depth_texture: wgpu::Texture,
+    depth_view: wgpu::TextureView,
     // stencil_texture: wgpu::TextureView,
     // for deferred rendering/ambient occlusion approximation
-    normals_texture: wgpu::TextureView,
+    normals_texture: wgpu::Texture,
+    normals_view: wgpu::TextureView,
 
     #[allow(dead_code)]
     driven: Driven,
 }
 
+// The uniforms that may change from one molecule to the next.  Each is padded
+// to 256 bytes, the largest alignment that a device may require of dynamic
+// uniform buffer offsets.
+#[derive(Clone, Copy, PartialEq)]
+#[repr(C, align(256))]
+struct PerDrawUniforms {
+    // The rgb channels hold the tint color, and alpha how far it replaces the
+    // element colors: 0 draws by element, and 1 in the tint color alone.
+    tint: Vec4,
+    // What the atoms are colored by before tinting: 0 for element, 1 for
+    // group and 2 for property, which is ramped from `low` to `high`.
+    coloring: u32,
+    low: f32,
+    high: f32,
+    // The index of the atom drawn with a glowing rim, or `NO_HIGHLIGHT`.
+    highlight: u32,
+}
+
+unsafe impl AsBytes for PerDrawUniforms {}
+
+// The highlighted atom of a draw with none.
+const NO_HIGHLIGHT: u32 = u32::MAX;
+
+#[repr(C, align(16))]
+struct FogUniforms {
+    background: Vec3,
+    // 0 for no fog, 1 for linear fog and 2 for exponential fog.
+    mode: u32,
+    start: f32,
+    end: f32,
+    density: f32,
+    _padding: f32,
+}
+
+unsafe impl AsBytes for FogUniforms {}
+
+// A draw of one or more instances of a molecule, recorded into a render
+// bundle.
+struct CachedDraw {
+    key: DrawKey,
+    viewport: Viewport,
+    bundle: wgpu::RenderBundle,
+    // The draw into the depth pre-pass, if there is one.
+    depth_bundle: Option<wgpu::RenderBundle>,
+}
+
+// Everything a recorded draw refers to.  Buffer contents may change from frame
+// to frame without re-recording, but the buffers themselves may not.
+#[derive(PartialEq, Eq)]
+struct DrawKey {
+    atoms: wgpu::Id<wgpu::BindGroup>,
+    number_of_atoms: usize,
+    transforms: wgpu::Id<wgpu::Buffer>,
+    per_draw: wgpu::Id<wgpu::BindGroup>,
+    // The first of the draws that the instances stand for, whose transform
+    // and uniforms they start from, and how many there are.
+    first: usize,
+    instances: usize,
+    points: bool,
+    // Whether the draw is of a single point standing in for the atoms.
+    stand_in: bool,
+    // The depth found by the pre-pass, if the draw is tested against it.
+    prepass_depth: Option<wgpu::Id<wgpu::BindGroup>>,
+}
+
+// The pipelines that atoms are drawn with.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AtomPipeline {
+    Spheres,
+    Points,
+    // Spheres or points drawn into the depth buffer alone.
+    Depth,
+    PointDepth,
+    // Spheres or points shaded where the depth pre-pass found them nearest.
+    Tested,
+    TestedPoints,
+}
+
 #[repr(C)]
 #[allow(dead_code)]
 struct DrawIndirect {
@@ -47,37 +167,138 @@ impl MolecularPass {
         vertex_constants_buffer: &wgpu::Buffer,
         periodic_table_buffer: &wgpu::Buffer,
         size: PhysicalSize<u32>,
+        background: Vec3,
+        fog: Option<Fog>,
     ) -> (Self, wgpu::TextureView) {
         let top_level_bgl = create_top_level_bgl(&render_resources.device);
-        let pipeline = create_render_pipeline(
-            &render_resources.device,
+        let per_draw_bgl = create_per_draw_bgl(&render_resources.device);
+        let prepass_depth_bgl = create_prepass_depth_bgl(&render_resources.device);
+        let bind_group_layouts = [
             &top_level_bgl,
             &render_resources.atom_bgl,
-        );
+            &per_draw_bgl,
+            &prepass_depth_bgl,
+        ];
+        let pipelines = AtomPipeline::ALL
+            .iter()
+            .map(|kind| {
+                create_render_pipeline(&render_resources.device, &bind_group_layouts, *kind)
+            })
+            .collect();
+        let fog_buffer = render_resources
+            .device
+            .create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: mem::size_of::<FogUniforms>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
         let top_level_bg = create_top_level_bg(
             &render_resources.device,
             &top_level_bgl,
             camera_binding_resource,
             vertex_constants_buffer,
             periodic_table_buffer,
+            &fog_buffer,
         );
 
+        let per_draw_capacity = 1;
+        let per_draw_buffer = create_per_draw_buffer(&render_resources.device, per_draw_capacity);
+        let per_draw_bg =
+            create_per_draw_bg(&render_resources.device, &per_draw_bgl, &per_draw_buffer);
+
         let color_texture = create_color_texture(&render_resources.device, size);
         let depth_texture = create_depth_texture(&render_resources.device, size);
+        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let prepass_depth_bg =
+            create_prepass_depth_bg(&render_resources.device, &prepass_depth_bgl, &depth_view);
         let normals_texture = create_normals_texture(&render_resources.device, size);
 
-        (
-            Self {
-                pipeline,
-                top_level_bg,
+        let mut pass = Self {
+            pipelines,
+            points: false,
+            depth_prepass: false,
+            prepass_depth_bgl,
+            prepass_depth_bg,
+            top_level_bg,
 
-                color_texture: color_texture.create_view(&wgpu::TextureViewDescriptor::default()),
-                depth_texture,
-                normals_texture,
-                driven: Driven::CpuDriven,
-            },
-            color_texture.create_view(&wgpu::TextureViewDescriptor::default()),
-        )
+            fog_buffer,
+            background: wgpu::Color::BLACK,
+
+            per_draw_bgl,
+            per_draw_buffer,
+            per_draw_bg,
+            per_draw_capacity,
+            per_draw: Vec::new(),
+
+            draws: Vec::new(),
+
+            color_view: color_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            color_texture,
+            depth_view,
+            depth_texture,
+            normals_view: normals_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            normals_texture,
+            driven: Driven::CpuDriven,
+        };
+        pass.set_fog(render_resources, background, fog);
+        let color_view = pass
+            .color_texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        (pass, color_view)
+    }
+
+    /// Draws each atom as a single point rather than a shaded sphere, from
+    /// the next frame on.
+    pub fn set_points(&mut self, points: bool) {
+        self.points = points;
+    }
+
+    /// Draws the atoms into the depth buffer before shading them, from the
+    /// next frame on, so that only the nearest atom at each pixel is shaded.
+    /// This saves time in dense scenes, where many atoms are hidden behind
+    /// others, at the cost of drawing every atom twice.
+    pub fn set_depth_prepass(&mut self, depth_prepass: bool) {
+        self.depth_prepass = depth_prepass;
+    }
+
+    /// Sets the color behind the atoms, in linear RGB, and how the atoms fade
+    /// into it with distance.
+    pub fn set_fog(
+        &mut self,
+        render_resources: &GlobalRenderResources,
+        background: Vec3,
+        fog: Option<Fog>,
+    ) {
+        let (mode, start, end, density) = match fog {
+            None => (0, 0.0, 0.0, 0.0),
+            Some(Fog::Linear { start, end }) => (1, start, end, 0.0),
+            Some(Fog::Exponential { density }) => (2, 0.0, 0.0, density),
+        };
+        let uniforms = FogUniforms {
+            background,
+            mode,
+            start,
+            end,
+            density,
+            _padding: 0.0,
+        };
+        render_resources
+            .queue
+            .write_buffer(&self.fog_buffer, 0, uniforms.as_bytes());
+
+        self.background = wgpu::Color {
+            r: background.x as f64,
+            g: background.y as f64,
+            b: background.z as f64,
+            a: 1.0,
+        };
+    }
+
+    /// The size of the buffer holding the fog settings, in bytes.
+    pub fn fog_buffer_size(&self) -> u64 {
+        self.fog_buffer.size()
     }
 
     // Returns `(color texture, normals texture)`
@@ -86,44 +307,158 @@ impl MolecularPass {
         render_resources: &GlobalRenderResources,
         size: PhysicalSize<u32>,
     ) -> (&wgpu::TextureView, &wgpu::TextureView) {
-        self.color_texture = create_color_texture(&render_resources.device, size)
+        self.color_texture = create_color_texture(&render_resources.device, size);
+        self.color_view = self
+            .color_texture
             .create_view(&wgpu::TextureViewDescriptor::default());
         self.depth_texture = create_depth_texture(&render_resources.device, size);
+        self.depth_view = self
+            .depth_texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.prepass_depth_bg = create_prepass_depth_bg(
+            &render_resources.device,
+            &self.prepass_depth_bgl,
+            &self.depth_view,
+        );
         self.normals_texture = create_normals_texture(&render_resources.device, size);
+        self.normals_view = self
+            .normals_texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
 
-        (&self.color_texture, &self.normals_texture)
+        (&self.color_view, &self.normals_view)
     }
 
+    /// The color of the last frame drawn, in `SWAPCHAIN_FORMAT`, before
+    /// antialiasing.
+    pub fn color_texture(&self) -> &wgpu::Texture {
+        &self.color_texture
+    }
+
+    /// The depth of the last frame drawn, in `Depth32Float`, with 0 at
+    /// infinity.
+    pub fn depth_texture(&self) -> &wgpu::Texture {
+        &self.depth_texture
+    }
+
+    /// The view-space normals of the last frame drawn, in `Rgba16Float`, with
+    /// zero for the background.
+    pub fn normals_texture(&self) -> &wgpu::Texture {
+        &self.normals_texture
+    }
+
+    /// Uploads the tint of each molecule to be drawn by the next `run`, in the
+    /// order they will be drawn, along with what to color their atoms by.
+    /// Tints are in sRGB, and are shaded in linear RGB.  `None` leaves a molecule's atoms in the colors given by `coloring`.  The
+    /// `hovered` atom, if any, is drawn highlighted.
+    pub fn upload_draws(
+        &mut self,
+        render_resources: &GlobalRenderResources,
+        tints: &[Option<Vec3>],
+        coloring: AtomColoring,
+        hovered: Option<HoveredAtom>,
+    ) {
+        let (mode, low, high) = match coloring {
+            AtomColoring::Element | AtomColoring::Component => (0, 0.0, 0.0),
+            AtomColoring::Group => (1, 0.0, 0.0),
+            AtomColoring::Property { low, high } => (2, low, high),
+        };
+        let uniforms: Vec<PerDrawUniforms> = tints
+            .iter()
+            .enumerate()
+            .map(|(draw, tint)| PerDrawUniforms {
+                tint: match tint {
+                    Some(color) => srgb_to_linear(*color).into_homogeneous_point(),
+                    None => Vec4::zero(),
+                },
+                coloring: mode,
+                low,
+                high,
+                highlight: match hovered {
+                    Some(hovered) if hovered.draw == draw => hovered.atom,
+                    _ => NO_HIGHLIGHT,
+                },
+            })
+            .collect();
+
+        if uniforms.len() > self.per_draw_capacity {
+            self.per_draw_capacity = uniforms.len().next_power_of_two();
+            self.per_draw_buffer =
+                create_per_draw_buffer(&render_resources.device, self.per_draw_capacity);
+            self.per_draw_bg = create_per_draw_bg(
+                &render_resources.device,
+                &self.per_draw_bgl,
+                &self.per_draw_buffer,
+            );
+        }
+
+        if !uniforms.is_empty() {
+            render_resources
+                .queue
+                .write_buffer(&self.per_draw_buffer, 0, uniforms.as_bytes());
+        }
+        self.per_draw = uniforms;
+    }
+
+    /// The number of draw calls made by the last `run`, after instancing.
+    pub fn draw_calls(&self) -> usize {
+        self.draws.len()
+    }
+
+    /// The size of the buffer holding the per-draw uniforms, in bytes.
+    pub fn per_draw_buffer_size(&self) -> u64 {
+        self.per_draw_buffer.size()
+    }
+
+    // Draws the atoms, each into its viewport.  Draws marked as stand-ins
+    // draw only the first atom, as a point.
     // TODO: figure out how to multithread this
     pub fn run<'a>(
-        &self,
+        &mut self,
+        render_resources: &GlobalRenderResources,
         encoder: &mut wgpu::CommandEncoder,
-        atoms: impl IntoIterator<Item = &'a AtomBuffer>,
+        atoms: impl IntoIterator<Item = (&'a AtomBuffer, Viewport, bool)>,
         fragment_transforms: &wgpu::Buffer,
         // fragments: impl IntoIterator<Item = &'a Fragment>,
         // fragment_transforms: &wgpu::Buffer,
         // per_fragment: &HashMap<FragmentId, (PartId, u64 /* transform index */)>,
     ) {
+        let atoms: Vec<(&AtomBuffer, Viewport, bool)> = atoms.into_iter().collect();
+        self.record_draws(render_resources, &atoms, fragment_transforms);
+
+        // The pre-pass, if any, leaves the depth buffer for the shaded pass
+        // to test against rather than write.
+        let prepass = self.draws.iter().any(|draw| draw.depth_bundle.is_some());
+        if prepass {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            execute_draws(&mut rpass, &self.draws, |draw| draw.depth_bundle.as_ref());
+        }
+
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
             color_attachments: &[
                 Some(wgpu::RenderPassColorAttachment {
-                    view: &self.color_texture,
+                    view: &self.color_view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.703125,
-                            g: 0.703125,
-                            b: 0.703125,
-                            a: 1.000000,
-                        }),
+                        load: wgpu::LoadOp::Clear(self.background),
                         store: true,
                     },
                 }),
                 // multiple render targets
                 // render to normals texture
                 Some(wgpu::RenderPassColorAttachment {
-                    view: &self.normals_texture,
+                    view: &self.normals_view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
@@ -131,32 +466,237 @@ impl MolecularPass {
                     },
                 }),
             ],
-            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth_texture,
-                depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(0.0),
-                    store: true,
-                }),
-                stencil_ops: None,
-            }),
+            depth_stencil_attachment: (!prepass).then_some(
+                wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                },
+            ),
         });
+        execute_draws(&mut rpass, &self.draws, |draw| Some(&draw.bundle));
+    }
 
-        rpass.set_pipeline(&self.pipeline);
-        rpass.set_bind_group(0, &self.top_level_bg, &[]);
+    // Re-records the draws whose atoms or buffers have changed since the last
+    // frame, and drops those that are no longer drawn.  Each run of draws of
+    // the same atoms, into the same viewport and with the same uniforms, is
+    // recorded as a single draw with an instance for each transform.
+    fn record_draws(
+        &mut self,
+        render_resources: &GlobalRenderResources,
+        atoms: &[(&AtomBuffer, Viewport, bool)],
+        fragment_transforms: &wgpu::Buffer,
+    ) {
+        let mut batch = 0;
+        let mut first = 0;
+        while first < atoms.len() {
+            let (atoms_inst, viewport, stand_in) = atoms[first];
+            let instances = atoms[first..]
+                .iter()
+                .enumerate()
+                .take_while(|(offset, (other, other_viewport, other_stand_in))| {
+                    other.bind_group().global_id() == atoms_inst.bind_group().global_id()
+                        && *other_viewport == viewport
+                        && *other_stand_in == stand_in
+                        && self.per_draw.get(first + offset) == self.per_draw.get(first)
+                })
+                .count();
 
-        for (idx, atoms_inst) in atoms.into_iter().enumerate() {
-            let transform_offset = (idx * mem::size_of::<ultraviolet::Mat4>()) as u64;
+            let key = DrawKey {
+                atoms: atoms_inst.bind_group().global_id(),
+                number_of_atoms: atoms_inst.len(),
+                transforms: fragment_transforms.global_id(),
+                per_draw: self.per_draw_bg.global_id(),
+                first,
+                instances,
+                points: self.points,
+                stand_in,
+                prepass_depth: self
+                    .depth_prepass
+                    .then(|| self.prepass_depth_bg.global_id()),
+            };
+            if !self.draws.get(batch).is_some_and(|draw| draw.key == key) {
+                let record = |kind| {
+                    self.record_draw(
+                        &render_resources.device,
+                        kind,
+                        first,
+                        instances,
+                        atoms_inst,
+                        stand_in,
+                        fragment_transforms,
+                    )
+                };
+                let points = self.points || stand_in;
+                let (bundle, depth_bundle) = match (points, key.prepass_depth) {
+                    (false, None) => (record(AtomPipeline::Spheres), None),
+                    (true, None) => (record(AtomPipeline::Points), None),
+                    (false, Some(_)) => (
+                        record(AtomPipeline::Tested),
+                        Some(record(AtomPipeline::Depth)),
+                    ),
+                    (true, Some(_)) => (
+                        record(AtomPipeline::TestedPoints),
+                        Some(record(AtomPipeline::PointDepth)),
+                    ),
+                };
+                let draw = CachedDraw {
+                    key,
+                    viewport,
+                    bundle,
+                    depth_bundle,
+                };
+                if batch < self.draws.len() {
+                    self.draws[batch] = draw;
+                } else {
+                    self.draws.push(draw);
+                }
+            }
+            self.draws[batch].viewport = viewport;
 
-            rpass.set_vertex_buffer(
-                0,
-                fragment_transforms.slice(
-                    transform_offset..transform_offset + mem::size_of::<ultraviolet::Mat4>() as u64,
-                ),
-            );
+            batch += 1;
+            first += instances;
+        }
+        self.draws.truncate(batch);
+    }
 
-            rpass.set_bind_group(1, atoms_inst.bind_group(), &[]);
-            rpass.draw(0..(atoms_inst.len() * 3).try_into().unwrap(), 0..1);
+    // Records `instances` instances of the atoms with the `kind` pipeline,
+    // using the transforms from the `first`th on and the `first`th per-draw
+    // uniforms.  A `stand_in` draws only the first atom.
+    #[allow(clippy::too_many_arguments)]
+    fn record_draw(
+        &self,
+        device: &wgpu::Device,
+        kind: AtomPipeline,
+        first: usize,
+        instances: usize,
+        atoms_inst: &AtomBuffer,
+        stand_in: bool,
+        fragment_transforms: &wgpu::Buffer,
+    ) -> wgpu::RenderBundle {
+        let mut encoder =
+            device.create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
+                label: None,
+                color_formats: kind.color_formats(),
+                depth_stencil: kind
+                    .depth_stencil()
+                    .map(|state| wgpu::RenderBundleDepthStencil {
+                        format: state.format,
+                        depth_read_only: false,
+                        stencil_read_only: true,
+                    }),
+                sample_count: 1,
+                multiview: None,
+            });
+
+        encoder.set_pipeline(&self.pipelines[kind as usize]);
+        encoder.set_bind_group(0, &self.top_level_bg, &[]);
+
+        let transform_size = mem::size_of::<ultraviolet::Mat4>() as u64;
+        let transform_offset = first as u64 * transform_size;
+
+        encoder.set_vertex_buffer(
+            0,
+            fragment_transforms
+                .slice(transform_offset..transform_offset + instances as u64 * transform_size),
+        );
+
+        encoder.set_bind_group(1, atoms_inst.bind_group(), &[]);
+        encoder.set_bind_group(
+            2,
+            &self.per_draw_bg,
+            &[(first * mem::size_of::<PerDrawUniforms>()) as u32],
+        );
+        if kind.tested() {
+            encoder.set_bind_group(3, &self.prepass_depth_bg, &[]);
         }
+        // A sphere takes a triangle, and a point a single vertex.
+        let vertices_per_atom = if kind.points() { 1 } else { 3 };
+        let atoms = if stand_in { 1 } else { atoms_inst.len() };
+        encoder.draw(
+            0..(atoms * vertices_per_atom).try_into().unwrap(),
+            0..instances.try_into().unwrap(),
+        );
+        encoder.finish(&wgpu::RenderBundleDescriptor { label: None })
+    }
+}
+
+// Replays the bundles chosen by `bundle` from `draws`.  Bundles can't set the
+// viewport, so the draws are replayed in runs that share one.
+fn execute_draws<'a>(
+    rpass: &mut wgpu::RenderPass<'a>,
+    draws: &'a [CachedDraw],
+    bundle: impl Fn(&'a CachedDraw) -> Option<&'a wgpu::RenderBundle>,
+) {
+    let mut start = 0;
+    while start < draws.len() {
+        let viewport = draws[start].viewport;
+        let end = start
+            + draws[start..]
+                .iter()
+                .take_while(|draw| draw.viewport == viewport)
+                .count();
+
+        rpass.set_viewport(
+            viewport.x,
+            viewport.y,
+            viewport.width,
+            viewport.height,
+            0.0,
+            1.0,
+        );
+        let bundles: Vec<&wgpu::RenderBundle> =
+            draws[start..end].iter().filter_map(&bundle).collect();
+        rpass.execute_bundles(bundles);
+
+        start = end;
+    }
+}
+
+impl AtomPipeline {
+    const ALL: [AtomPipeline; 6] = [
+        AtomPipeline::Spheres,
+        AtomPipeline::Points,
+        AtomPipeline::Depth,
+        AtomPipeline::PointDepth,
+        AtomPipeline::Tested,
+        AtomPipeline::TestedPoints,
+    ];
+
+    fn points(self) -> bool {
+        matches!(
+            self,
+            AtomPipeline::Points | AtomPipeline::PointDepth | AtomPipeline::TestedPoints
+        )
+    }
+
+    fn tested(self) -> bool {
+        matches!(self, AtomPipeline::Tested | AtomPipeline::TestedPoints)
+    }
+
+    fn color_formats(self) -> &'static [Option<wgpu::TextureFormat>] {
+        match self {
+            AtomPipeline::Depth | AtomPipeline::PointDepth => &[],
+            _ => &[
+                Some(SWAPCHAIN_FORMAT),
+                Some(wgpu::TextureFormat::Rgba16Float),
+            ],
+        }
+    }
+
+    // The shaded pass after a pre-pass tests the depth itself, and has no
+    // depth buffer.
+    fn depth_stencil(self) -> Option<wgpu::DepthStencilState> {
+        (!self.tested()).then(|| wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Greater,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        })
     }
 }
 
@@ -197,16 +737,104 @@ fn create_top_level_bgl(device: &wgpu::Device) -> wgpu::BindGroupLayout {
                 },
                 count: None,
             },
+            // fog
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
         ],
     })
 }
 
+fn create_per_draw_bgl(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: NonZeroU64::new(mem::size_of::<PerDrawUniforms>() as u64),
+            },
+            count: None,
+        }],
+    })
+}
+
+fn create_per_draw_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: (capacity * mem::size_of::<PerDrawUniforms>()) as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_per_draw_bg(
+    device: &wgpu::Device,
+    per_draw_bgl: &wgpu::BindGroupLayout,
+    per_draw_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: per_draw_bgl,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            // Each draw sees only its own uniforms.
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: per_draw_buffer,
+                offset: 0,
+                size: NonZeroU64::new(mem::size_of::<PerDrawUniforms>() as u64),
+            }),
+        }],
+    })
+}
+
+fn create_prepass_depth_bgl(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Depth,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        }],
+    })
+}
+
+fn create_prepass_depth_bg(
+    device: &wgpu::Device,
+    prepass_depth_bgl: &wgpu::BindGroupLayout,
+    depth_view: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: prepass_depth_bgl,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(depth_view),
+        }],
+    })
+}
+
 fn create_top_level_bg(
     device: &wgpu::Device,
     top_level_bgl: &wgpu::BindGroupLayout,
     camera_binding_resource: wgpu::BindingResource,
     vertex_constants_buffer: &wgpu::Buffer,
     periodic_table_buffer: &wgpu::Buffer,
+    fog_buffer: &wgpu::Buffer,
 ) -> wgpu::BindGroup {
     device.create_bind_group(&wgpu::BindGroupDescriptor {
         label: None,
@@ -235,29 +863,50 @@ fn create_top_level_bg(
                     size: None,
                 }),
             },
+            // fog
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: fog_buffer,
+                    offset: 0,
+                    size: None,
+                }),
+            },
         ],
     })
 }
 
 fn create_render_pipeline(
     device: &wgpu::Device,
-    top_level_bgl: &wgpu::BindGroupLayout,
-    atom_bgl: &wgpu::BindGroupLayout,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    kind: AtomPipeline,
 ) -> wgpu::RenderPipeline {
+    // Only the shaded pass after a pre-pass reads its depth.
+    let bind_group_layouts = if kind.tested() {
+        bind_group_layouts
+    } else {
+        &bind_group_layouts[..3]
+    };
     let atom_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: None,
-        bind_group_layouts: &[top_level_bgl, atom_bgl],
+        bind_group_layouts,
         push_constant_ranges: &[],
     });
 
     let atom_shader = device.create_shader_module(wgpu::include_wgsl!("atom.wgsl"));
+    let points = kind.points();
+    let targets: Vec<Option<wgpu::ColorTargetState>> = kind
+        .color_formats()
+        .iter()
+        .map(|format| format.map(Into::into))
+        .collect();
 
     device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: None,
         layout: Some(&atom_pipeline_layout),
         vertex: wgpu::VertexState {
             module: &atom_shader,
-            entry_point: "vs_main",
+            entry_point: if points { "vs_point" } else { "vs_main" },
             buffers: &[wgpu::VertexBufferLayout {
                 array_stride: mem::size_of::<ultraviolet::Mat4>() as _,
                 step_mode: wgpu::VertexStepMode::Instance,
@@ -270,16 +919,26 @@ fn create_render_pipeline(
                 ],
             }],
         },
-        fragment: Some(wgpu::FragmentState {
+        // Points need no fragment shader to be drawn into the depth buffer.
+        fragment: match kind {
+            AtomPipeline::Spheres => Some("fs_main"),
+            AtomPipeline::Points => Some("fs_point"),
+            AtomPipeline::Depth => Some("fs_depth"),
+            AtomPipeline::PointDepth => None,
+            AtomPipeline::Tested => Some("fs_tested"),
+            AtomPipeline::TestedPoints => Some("fs_point_tested"),
+        }
+        .map(|entry_point| wgpu::FragmentState {
             module: &atom_shader,
-            entry_point: "fs_main",
-            targets: &[
-                Some(SWAPCHAIN_FORMAT.into()),
-                Some(wgpu::TextureFormat::Rgba16Float.into()),
-            ],
+            entry_point,
+            targets: &targets,
         }),
         primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
+            topology: if points {
+                wgpu::PrimitiveTopology::PointList
+            } else {
+                wgpu::PrimitiveTopology::TriangleList
+            },
             strip_index_format: None,
             front_face: wgpu::FrontFace::Ccw,
             cull_mode: Some(wgpu::Face::Front),
@@ -287,13 +946,7 @@ fn create_render_pipeline(
             polygon_mode: wgpu::PolygonMode::Fill,
             conservative: false,
         },
-        depth_stencil: Some(wgpu::DepthStencilState {
-            format: wgpu::TextureFormat::Depth32Float,
-            depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Greater,
-            stencil: wgpu::StencilState::default(),
-            bias: wgpu::DepthBiasState::default(),
-        }),
+        depth_stencil: kind.depth_stencil(),
         multisample: wgpu::MultisampleState {
             count: 1,
             mask: !0,
@@ -308,28 +961,32 @@ fn create_color_texture(device: &wgpu::Device, size: PhysicalSize<u32>) -> wgpu:
         device,
         size,
         SWAPCHAIN_FORMAT,
-        wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC,
     )
 }
 
-fn create_depth_texture(device: &wgpu::Device, size: PhysicalSize<u32>) -> wgpu::TextureView {
+fn create_depth_texture(device: &wgpu::Device, size: PhysicalSize<u32>) -> wgpu::Texture {
     Renderer::create_texture(
         device,
         size,
         wgpu::TextureFormat::Depth32Float,
-        wgpu::TextureUsages::RENDER_ATTACHMENT,
+        wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC,
     )
-    .create_view(&wgpu::TextureViewDescriptor::default())
 }
 
-fn create_normals_texture(device: &wgpu::Device, size: PhysicalSize<u32>) -> wgpu::TextureView {
+fn create_normals_texture(device: &wgpu::Device, size: PhysicalSize<u32>) -> wgpu::Texture {
     Renderer::create_texture(
         device,
         size,
         wgpu::TextureFormat::Rgba16Float,
-        wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC,
     )
-    .create_view(&wgpu::TextureViewDescriptor::default())
 }
 
 // End of File
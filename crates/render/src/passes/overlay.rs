@@ -0,0 +1,166 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::{overlay::OverlayShape, GlobalRenderResources, SWAPCHAIN_FORMAT};
+use common::AsBytes as _;
+use std::mem;
+use ultraviolet::Vec4;
+use wgpu::util::DeviceExt as _;
+use winit::dpi::PhysicalSize;
+
+/// Draws the shapes of an [`Overlay`](crate::Overlay) over the frame, after it
+/// has been blitted to the screen.
+pub struct OverlayPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    // The size of the window, in pixels.
+    viewport_buffer: wgpu::Buffer,
+}
+
+impl OverlayPass {
+    pub fn new(render_resources: &GlobalRenderResources, size: PhysicalSize<u32>) -> Self {
+        let device = &render_resources.device;
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let viewport_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: viewport(size).as_bytes(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: viewport_buffer.as_entire_binding(),
+            }],
+        });
+        let pipeline = create_overlay_pipeline(device, &bind_group_layout);
+
+        Self {
+            pipeline,
+            bind_group,
+            viewport_buffer,
+        }
+    }
+
+    pub fn resize(&self, render_resources: &GlobalRenderResources, size: PhysicalSize<u32>) {
+        render_resources
+            .queue
+            .write_buffer(&self.viewport_buffer, 0, viewport(size).as_bytes());
+    }
+
+    pub fn run(
+        &self,
+        render_resources: &GlobalRenderResources,
+        encoder: &mut wgpu::CommandEncoder,
+        frame: &wgpu::TextureView,
+        shapes: &[OverlayShape],
+    ) {
+        if shapes.is_empty() {
+            return;
+        }
+        let shape_buffer =
+            render_resources
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: None,
+                    contents: shapes.as_bytes(),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: frame,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    // Draw over what was blitted.
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.set_vertex_buffer(0, shape_buffer.slice(..));
+        rpass.draw(0..6, 0..shapes.len() as u32);
+    }
+}
+
+fn viewport(size: PhysicalSize<u32>) -> Vec4 {
+    Vec4::new(size.width as f32, size.height as f32, 0.0, 0.0)
+}
+
+fn create_overlay_pipeline(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    let shader = device.create_shader_module(wgpu::include_wgsl!("overlay.wgsl"));
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: mem::size_of::<OverlayShape>() as _,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: &wgpu::vertex_attr_array![
+                    // rect, color and arc
+                    0 => Float32x4,
+                    1 => Float32x4,
+                    2 => Float32x4,
+                ],
+            }],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: SWAPCHAIN_FORMAT,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            unclipped_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+// End of File
@@ -0,0 +1,83 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+// Flat shapes drawn over the views: filled rectangles, and parts of rings
+// centered in their rectangles.  Positions are in physical pixels from the top
+// left of the window.
+
+struct Shape {
+    // x, y, width and height.
+    @location(0)
+    rect: vec4<f32>,
+    // Linear RGB, with alpha.
+    @location(1)
+    color: vec4<f32>,
+    // The inner and outer radius of a ring, and the angles it starts and ends
+    // at, in radians clockwise from the top.  An outer radius of zero fills the
+    // rectangle instead.
+    @location(2)
+    arc: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position)
+    position: vec4<f32>,
+    // From the center of the rectangle, in pixels.
+    @location(0)
+    offset: vec2<f32>,
+    @location(1)
+    color: vec4<f32>,
+    @location(2)
+    arc: vec4<f32>,
+};
+
+// The size of the window in pixels, in x and y.
+@group(0) @binding(0)
+var<uniform> viewport: vec4<f32>;
+
+const TAU: f32 = 6.283185307179586;
+
+// Each shape is drawn as two triangles, from six vertices.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, shape: Shape) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+    let pixel = shape.rect.xy + corner * shape.rect.zw;
+    let clip_position = pixel / viewport.xy * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+
+    return VertexOutput(
+        vec4<f32>(clip_position, 0.0, 1.0),
+        (corner - 0.5) * shape.rect.zw,
+        shape.color,
+        shape.arc,
+    );
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if in.arc.y > 0.0 {
+        let radius = length(in.offset);
+        if radius < in.arc.x || radius > in.arc.y {
+            discard;
+        }
+        // With y pointing down, this is clockwise from the top.
+        var angle = atan2(in.offset.x, -in.offset.y);
+        if angle < in.arc.z {
+            angle += TAU;
+        }
+        if angle < in.arc.z || angle > in.arc.w {
+            discard;
+        }
+    }
+    return in.color;
+}
+
+// End of File
@@ -2,54 +2,499 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use molecule::MoleculeEditor;
-use render::AtomBuffer;
-use ultraviolet::Mat4;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
 
+use common::{ids::AtomSpecifier, Metadata, Plane};
+use molecule::{
+    edit::EditContext,
+    parts::{self, RepeatedPart},
+    superposition::{self, Superposition},
+    HydrogenDisplay, MoleculeEditor,
+};
+use render::{AtomBuffer, ColorScheme, HoveredAtom, Projection};
+use serde::{Deserialize, Deserializer, Serialize};
+use ultraviolet::{Mat4, Vec3};
+
+use crate::{Configuration, Drive, ExternalRef, Measurement, MeasurementValue, Pattern, SavedView};
+
+// The tint that the periodic images of molecules are drawn in, so that they
+// can be told apart from the atoms themselves.
+const PERIODIC_IMAGE_TINT: Vec3 = Vec3::new(0.7, 0.75, 0.85);
+
+static NEXT_COMPONENT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A stable identifier for a `Component`. It is assigned when the component is
+/// created and does not change when the component is moved around the assembly
+/// tree, so it can be used to refer to a component across frames (unlike its
+/// index in `Assembly::direct_children`, which shifts as siblings are added or
+/// removed).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct ComponentId(u64);
+
+impl ComponentId {
+    fn next() -> Self {
+        Self(NEXT_COMPONENT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl fmt::Display for ComponentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for ComponentId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = u64::deserialize(deserializer)?;
+        // Components created after this one was loaded must not be given its
+        // id.
+        NEXT_COMPONENT_ID.fetch_max(id + 1, Ordering::Relaxed);
+        Ok(Self(id))
+    }
+}
+
+/// Where a component is in the assembly tree: the ids of the sub-assemblies
+/// that hold it, outermost first, followed by its own.  Unlike a
+/// `ComponentId` alone, a path tells which sub-assemblies to open to reach the
+/// component, and stops resolving if the component is moved to another one.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "Vec<ComponentId>")]
+pub struct AssemblyPath(Vec<ComponentId>);
+
+impl AssemblyPath {
+    /// The component that the path leads to.
+    pub fn id(&self) -> ComponentId {
+        *self.0.last().expect("paths are never empty")
+    }
+
+    /// The sub-assemblies that hold the component, outermost first.
+    pub fn ancestors(&self) -> &[ComponentId] {
+        &self.0[..self.0.len() - 1]
+    }
+
+    /// How many sub-assemblies deep the component is.  Direct children of the
+    /// root assembly have depth 0.
+    pub fn depth(&self) -> usize {
+        self.0.len() - 1
+    }
+}
+
+impl TryFrom<Vec<ComponentId>> for AssemblyPath {
+    type Error = &'static str;
+
+    fn try_from(ids: Vec<ComponentId>) -> Result<Self, Self::Error> {
+        if ids.is_empty() {
+            return Err("an assembly path needs at least one component");
+        }
+        Ok(Self(ids))
+    }
+}
+
+impl fmt::Display for AssemblyPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, id) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str("/")?;
+            }
+            write!(f, "{}", id)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
 enum ComponentType {
     Molecule(Box<MoleculeEditor>),
-    SubAssembly(Assembly),
+    SubAssembly(Box<Assembly>),
 }
 
+/// A component is saved with its id, so that the configurations, measurements
+/// and patterns of the assembly still refer to it when it is loaded.  Its
+/// molecule's GPU buffers are not saved, and are uploaded again the next time
+/// the assembly is synchronized.
+#[derive(Deserialize, Serialize)]
 pub struct Component {
+    id: ComponentId,
+    #[serde(default)]
+    metadata: Metadata,
+    #[serde(default = "default_visible")]
+    visible: bool,
+    #[serde(default)]
+    locked: bool,
+    #[serde(default)]
+    tint: Option<Vec3>,
+    #[serde(default)]
+    hydrogens: Option<HydrogenDisplay>,
     transform: Mat4,
+    #[serde(default)]
+    drive: Option<Drive>,
+    /// Further places that the component's molecule is drawn, each as a
+    /// motion of its atoms within the molecule's own space.
+    #[serde(default)]
+    copies: Vec<Mat4>,
+    #[serde(default)]
+    source: Option<ExternalRef>,
     data: ComponentType,
 }
 
+fn default_visible() -> bool {
+    true
+}
+
 impl Component {
+    /// Creates a component holding `molecule`. The component is initially
+    /// named after the molecule.
     pub fn from_molecule(molecule: MoleculeEditor, transform: Mat4) -> Self {
         Self {
+            id: ComponentId::next(),
+            metadata: Metadata::new(molecule.name()),
+            visible: true,
+            locked: false,
+            tint: None,
+            hydrogens: None,
             transform,
+            drive: None,
+            copies: Vec::new(),
+            source: None,
             data: ComponentType::Molecule(Box::new(molecule)),
         }
     }
 
+    /// Creates a component holding a sub-assembly. The component is initially
+    /// named after the assembly.
     pub fn from_assembly(assembly: Assembly, transform: Mat4) -> Self {
         Self {
+            id: ComponentId::next(),
+            metadata: Metadata::new(assembly.name()),
+            visible: true,
+            locked: false,
+            tint: None,
+            hydrogens: None,
             transform,
-            data: ComponentType::SubAssembly(assembly),
+            drive: None,
+            copies: Vec::new(),
+            source: None,
+            data: ComponentType::SubAssembly(Box::new(assembly)),
         }
     }
+
+    pub fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
+
+    pub fn name(&self) -> &str {
+        self.metadata.name()
+    }
+
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.metadata.set_name(name);
+    }
+
+    /// Hidden components (and everything inside them, if they are
+    /// sub-assemblies) are not drawn.
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    /// Locked components (and everything inside them, if they are
+    /// sub-assemblies) cannot be edited, moved or removed, which protects
+    /// reference structures while the rest of the design is worked on.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    pub fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+    }
+
+    /// A color that the component is drawn in instead of the colors of its
+    /// elements, e.g. to grey out a substrate.  A sub-assembly's tint applies to
+    /// everything inside it that has no tint of its own.
+    pub fn tint(&self) -> Option<Vec3> {
+        self.tint
+    }
+
+    /// Sets the tint.  Passing `None` draws the component by element again.
+    pub fn set_tint(&mut self, tint: Option<Vec3>) {
+        self.tint = tint;
+    }
+
+    /// Which hydrogens the component's molecules are drawn with, in place of
+    /// the assembly's [`Assembly::hydrogen_display`].  Like a tint, a
+    /// sub-assembly's choice applies to everything inside it that has none of
+    /// its own.
+    pub fn hydrogen_display(&self) -> Option<HydrogenDisplay> {
+        self.hydrogens
+    }
+
+    /// Sets which hydrogens are drawn.  Passing `None` follows the assembly
+    /// again.
+    pub fn set_hydrogen_display(&mut self, hydrogens: Option<HydrogenDisplay>) {
+        self.hydrogens = hydrogens;
+    }
+
+    pub fn transform(&self) -> Mat4 {
+        self.transform
+    }
+
+    /// Whether the component's own transform is a reflection, which draws
+    /// its molecules as their mirror images.  See [`Assembly::is_mirrored`]
+    /// for whether it is drawn mirrored once its parents are counted.
+    pub fn is_mirrored(&self) -> bool {
+        self.transform.determinant() < 0.0
+    }
+
+    /// The driven degree of freedom that moves this component when the
+    /// assembly is animated, if any.
+    pub fn drive(&self) -> Option<&Drive> {
+        self.drive.as_ref()
+    }
+
+    /// The copies of the component's molecule that are drawn along with it,
+    /// such as those of a part that was instanced (see
+    /// [`Assembly::instance_parts`]).  Each is a motion of the atoms within
+    /// the molecule's own space, so the copies follow the component wherever
+    /// it is moved.  They share its molecule and its buffers, so they cost no
+    /// memory of their own, and like the copies of a pattern they are not
+    /// picked.
+    pub fn copies(&self) -> &[Mat4] {
+        &self.copies
+    }
+
+    /// The file that the component's molecule is linked to, if any.
+    pub fn source(&self) -> Option<&ExternalRef> {
+        self.source.as_ref()
+    }
+
+    /// Links the component's molecule to a file, or unlinks it with `None`,
+    /// leaving the copy embedded.
+    pub fn set_source(&mut self, source: Option<ExternalRef>) {
+        self.source = source;
+    }
+
+    /// Replaces the molecule of a linked component with one read again from
+    /// its file, whose contents are now `contents`.  The component keeps its
+    /// name, placement and everything else.  Returns false if the component
+    /// is not linked, or is locked.
+    pub fn update_source(&mut self, molecule: MoleculeEditor, contents: &[u8]) -> bool {
+        let Some(uri) = self.source.as_ref().map(|source| source.uri.clone()) else {
+            return false;
+        };
+        let Some(current) = self.molecule_mut() else {
+            return false;
+        };
+        *current = molecule;
+        self.source = Some(ExternalRef::new(uri, contents));
+        true
+    }
+
+    // The transform the component is drawn with: its own, after the motion of
+    // its drive, after the placement of its molecule if it holds one.
+    fn displayed_transform(&self) -> Mat4 {
+        match &self.drive {
+            Some(drive) => self.transform * drive.transform() * self.molecule_transform(),
+            None => self.placed_transform(),
+        }
+    }
+
+    // The transform the component was designed with: its own, after the
+    // placement of its molecule if it holds one.
+    fn placed_transform(&self) -> Mat4 {
+        self.transform * self.molecule_transform()
+    }
+
+    // Where the molecule is placed within the component, by its own rotation
+    // and offset.
+    fn molecule_transform(&self) -> Mat4 {
+        match &self.data {
+            ComponentType::Molecule(molecule) => molecule.local_transform(),
+            ComponentType::SubAssembly(_) => Mat4::identity(),
+        }
+    }
+
+    // The middle of the atoms in the component, in its parent's space, as it
+    // was designed.  Returns `None` if the component holds no atoms.
+    fn center(&self) -> Option<Vec3> {
+        let center = match &self.data {
+            ComponentType::Molecule(molecule) => {
+                if molecule.repr.graph.node_count() == 0 {
+                    return None;
+                }
+                let bounds = molecule.repr.bounding_box();
+                (bounds.min + bounds.max) / 2.0
+            }
+            ComponentType::SubAssembly(assembly) => assembly.center()?,
+        };
+        Some(self.placed_transform().transform_point3(center))
+    }
+
+    pub fn molecule(&self) -> Option<&MoleculeEditor> {
+        match &self.data {
+            ComponentType::Molecule(molecule) => Some(molecule),
+            ComponentType::SubAssembly(_) => None,
+        }
+    }
+
+    /// Returns `None` if the component is locked, as well as if it holds a
+    /// sub-assembly.
+    pub fn molecule_mut(&mut self) -> Option<&mut MoleculeEditor> {
+        if self.locked {
+            return None;
+        }
+        match &mut self.data {
+            ComponentType::Molecule(molecule) => Some(molecule),
+            ComponentType::SubAssembly(_) => None,
+        }
+    }
+
+    pub fn sub_assembly(&self) -> Option<&Assembly> {
+        match &self.data {
+            ComponentType::Molecule(_) => None,
+            ComponentType::SubAssembly(assembly) => Some(assembly),
+        }
+    }
+
+    /// Returns `None` if the component is locked, as well as if it holds a
+    /// molecule.
+    pub fn sub_assembly_mut(&mut self) -> Option<&mut Assembly> {
+        if self.locked {
+            return None;
+        }
+        match &mut self.data {
+            ComponentType::Molecule(_) => None,
+            ComponentType::SubAssembly(assembly) => Some(assembly),
+        }
+    }
+}
+
+/// The reasons that `Assembly::reparent` can refuse to move a component.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReparentError {
+    /// The component being moved does not exist in this assembly.
+    UnknownComponent,
+    /// The new parent does not exist, or is not a sub-assembly.
+    InvalidParent,
+    /// The new parent is the component itself or one of its descendants.
+    Cycle,
+    /// The component, its current parent or its new parent is locked.
+    Locked,
+}
+
+/// The reasons that `Assembly::set_transform` can refuse to move a component.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TransformError {
+    /// The component does not exist in this assembly.
+    UnknownComponent,
+    /// The component is locked.
+    Locked,
+}
+
+/// The reasons that `Assembly::superpose` can fail.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SuperposeError {
+    /// One of the components does not exist in this assembly.
+    UnknownComponent,
+    /// One of the components is a sub-assembly rather than a molecule.
+    NotAMolecule,
+    /// The molecules have no atoms in common to superpose over.
+    NoCommonAtoms,
+    /// The component to be moved is locked.
+    Locked,
 }
 
-#[derive(Default)]
+/// The reasons that `Assembly::add_pattern` can refuse a pattern.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PatternError {
+    /// The seed does not exist in this assembly.
+    UnknownComponent,
+    /// The seed is a sub-assembly rather than a molecule.
+    NotAMolecule,
+    /// The pattern would not place any copies.
+    NoInstances,
+}
+
+/// The reasons that `Assembly::instance_parts` can fail.
+#[derive(Debug, PartialEq, Eq)]
+pub enum InstancePartsError {
+    /// The component does not exist in this assembly.
+    UnknownComponent,
+    /// The component is a sub-assembly rather than a molecule.
+    NotAMolecule,
+    /// The component is locked.
+    Locked,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Assembly {
+    metadata: Metadata,
     components: Vec<Component>,
+    measurements: Vec<Measurement>,
+    patterns: Vec<Pattern>,
+    explode: f32,
+    periodic_images: u32,
+    configurations: Vec<Configuration>,
+    color_scheme: ColorScheme,
+    hydrogens: HydrogenDisplay,
+    saved_view: Option<SavedView>,
 }
 
 impl Assembly {
     pub fn from_components(components: impl IntoIterator<Item = Component>) -> Self {
         Self {
+            metadata: Metadata::default(),
             components: components.into_iter().collect(),
+            measurements: Vec::new(),
+            patterns: Vec::new(),
+            explode: 0.0,
+            periodic_images: 0,
+            configurations: Vec::new(),
+            color_scheme: ColorScheme::default(),
+            hydrogens: HydrogenDisplay::default(),
+            saved_view: None,
         }
     }
 
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
+
+    pub fn name(&self) -> &str {
+        self.metadata.name()
+    }
+
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.metadata.set_name(name);
+    }
+
+    /// Calls `f` on every molecule, locked or not, along with its world
+    /// transform.  This is for changing how molecules are shown; edits go
+    /// through [`Assembly::component_mut`], which keeps locked components out
+    /// of reach.
     pub fn walk_mut(&mut self, mut f: impl FnMut(&mut MoleculeEditor, Mat4)) {
         let mut stack: Vec<(&mut Assembly, Mat4)> = vec![(self, Mat4::default())];
 
         while let Some((assembly, acc_transform)) = stack.pop() {
             for component in &mut assembly.components {
-                let new_transform = component.transform * acc_transform;
+                let new_transform = component.placed_transform() * acc_transform;
                 match &mut component.data {
                     ComponentType::Molecule(ref mut molecule) => {
                         f(molecule, new_transform);
@@ -62,26 +507,133 @@ impl Assembly {
         }
     }
 
-    pub fn collect_atoms_and_transforms(&self) -> (Vec<&AtomBuffer>, Vec<Mat4>) {
+    /// Collects the atoms of every visible molecule, along with the world
+    /// transform and tint that each is drawn with.  The periodic images of
+    /// molecules with a unit cell are included, each as another draw of the
+    /// same atoms, and so are the copies of components and those placed by
+    /// patterns.
+    pub fn collect_atoms_and_transforms(&self) -> (Vec<&AtomBuffer>, Vec<Mat4>, Vec<Option<Vec3>>) {
+        let (_, molecules, transforms, tints) = self.collect_draws();
+        (molecules, transforms, tints)
+    }
+
+    /// Finds where the atom `atom` of the molecule `id` is drawn among the
+    /// draws of [`collect_atoms_and_transforms`](Self::collect_atoms_and_transforms),
+    /// so that it can be highlighted.  Its periodic images are not.
+    pub fn hovered_atom(&self, id: ComponentId, atom: &AtomSpecifier) -> Option<HoveredAtom> {
+        let (components, ..) = self.collect_draws();
+        let draw = components.iter().position(|component| *component == id)?;
+        let index = self.component(id)?.molecule()?.repr.buffer_index(atom)?;
+        Some(HoveredAtom { draw, atom: index })
+    }
+
+    // The draws of `collect_atoms_and_transforms`, along with the component
+    // that each draws.
+    #[allow(clippy::type_complexity)]
+    fn collect_draws(
+        &self,
+    ) -> (
+        Vec<ComponentId>,
+        Vec<&AtomBuffer>,
+        Vec<Mat4>,
+        Vec<Option<Vec3>>,
+    ) {
         // The number of direct children of the world is an estimate of the
         // lower bound of the number of molecules. It is only possible for this to
         // overestimate if a child assembly contains zero children (which is unusual).
         let mut transforms = Vec::<Mat4>::with_capacity(self.components.len());
         let mut molecules = Vec::<&AtomBuffer>::with_capacity(self.components.len());
+        let mut tints = Vec::<Option<Vec3>>::with_capacity(self.components.len());
+        let mut components = Vec::<ComponentId>::with_capacity(self.components.len());
 
         // DFS
-        let mut stack: Vec<(&Assembly, Mat4)> = vec![(self, Mat4::default())];
+        let mut stack: Vec<(&Assembly, Mat4, Option<Vec3>)> = vec![(self, Mat4::default(), None)];
 
-        while let Some((assembly, acc_transform)) = stack.pop() {
-            for component in &assembly.components {
-                let new_transform = component.transform * acc_transform;
+        while let Some((assembly, acc_transform, acc_tint)) = stack.pop() {
+            let offsets = assembly.explode_offsets(self.explode);
+            for (component, offset) in assembly.components.iter().zip(offsets) {
+                if !component.visible {
+                    continue;
+                }
+                let new_transform = Mat4::from_translation(offset)
+                    * component.displayed_transform()
+                    * acc_transform;
+                let new_tint = component.tint.or(acc_tint);
                 match &component.data {
                     ComponentType::Molecule(molecule) => {
                         if let Some(atoms) = molecule.repr.atoms() {
                             molecules.push(atoms);
                             transforms.push(new_transform);
+                            tints.push(new_tint);
+                            components.push(component.id);
+                            for copy in &component.copies {
+                                molecules.push(atoms);
+                                transforms.push(new_transform * *copy);
+                                tints.push(new_tint);
+                                components.push(component.id);
+                            }
+
+                            let images = molecule.repr.cell().map_or(Vec::new(), |cell| {
+                                cell.image_translations(self.periodic_images)
+                            });
+                            for translation in images {
+                                molecules.push(atoms);
+                                transforms
+                                    .push(new_transform * Mat4::from_translation(translation));
+                                tints.push(Some(PERIODIC_IMAGE_TINT));
+                                components.push(component.id);
+                            }
                         }
                     }
+                    ComponentType::SubAssembly(sub_assembly) => {
+                        stack.push((sub_assembly, new_transform, new_tint));
+                    }
+                }
+            }
+        }
+
+        // The copies placed by patterns come after everything else, so that
+        // the first draw of a component is always the component itself.  The
+        // copies of each draw follow one another, so that the renderer can
+        // draw them all as instances of one draw of the seed's atoms.
+        let originals = components.len();
+        for pattern in &self.patterns {
+            let instances = pattern.instance_transforms();
+            for draw in 0..originals {
+                if components[draw] != pattern.seed {
+                    continue;
+                }
+                for instance in &instances {
+                    molecules.push(molecules[draw]);
+                    transforms.push(*instance * transforms[draw]);
+                    tints.push(tints[draw]);
+                    components.push(pattern.seed);
+                }
+            }
+        }
+
+        (components, molecules, transforms, tints)
+    }
+
+    /// Lists the molecules that are drawn, i.e. those not hidden themselves or by
+    /// an ancestor, along with the world transforms they are drawn with.
+    pub fn visible_molecules(&self) -> Vec<(ComponentId, &MoleculeEditor, Mat4)> {
+        let mut molecules = Vec::new();
+        let mut stack: Vec<(&Assembly, Mat4)> = vec![(self, Mat4::default())];
+
+        while let Some((assembly, acc_transform)) = stack.pop() {
+            let offsets = assembly.explode_offsets(self.explode);
+            for (component, offset) in assembly.components.iter().zip(offsets) {
+                if !component.visible {
+                    continue;
+                }
+                let new_transform = Mat4::from_translation(offset)
+                    * component.displayed_transform()
+                    * acc_transform;
+                match &component.data {
+                    ComponentType::Molecule(molecule) => {
+                        molecules.push((component.id, &**molecule, new_transform));
+                    }
                     ComponentType::SubAssembly(sub_assembly) => {
                         stack.push((sub_assembly, new_transform));
                     }
@@ -89,18 +641,307 @@ impl Assembly {
             }
         }
 
-        (molecules, transforms)
+        molecules
+    }
+
+    /// Finds the visible atom hit by the ray from `origin` in `direction` that is
+    /// closest to `origin`.  The ray is given in this assembly's space.
+    pub fn pick(&self, origin: Vec3, direction: Vec3) -> Option<(ComponentId, AtomSpecifier)> {
+        let mut closest: Option<(f32, ComponentId, AtomSpecifier)> = None;
+
+        for (id, molecule, transform) in self.visible_molecules() {
+            let inverse = transform.inversed();
+            let local_origin = inverse.transform_point3(origin);
+            let local_direction = inverse.transform_vec3(direction).normalized();
+
+            let Some(hit) = molecule.repr.get_ray_hit(local_origin, local_direction) else {
+                continue;
+            };
+            let Some(pos) = molecule.repr.pos(&hit) else {
+                continue;
+            };
+            let distance = (transform.transform_point3(*pos) - origin).mag();
+
+            if !matches!(&closest, Some((best, ..)) if *best <= distance) {
+                closest = Some((distance, id, hit));
+            }
+        }
+
+        closest.map(|(_, id, hit)| (id, hit))
+    }
+
+    /// Every visible atom hit by the ray from `origin` in `direction`, nearest
+    /// to `origin` first, e.g. to choose an atom hidden behind another.  The
+    /// ray is given in this assembly's space.
+    pub fn pick_all(&self, origin: Vec3, direction: Vec3) -> Vec<(ComponentId, AtomSpecifier)> {
+        let mut hits: Vec<(f32, ComponentId, AtomSpecifier)> = Vec::new();
+
+        for (id, molecule, transform) in self.visible_molecules() {
+            let inverse = transform.inversed();
+            let local_origin = inverse.transform_point3(origin);
+            let local_direction = inverse.transform_vec3(direction).normalized();
+
+            for (along, hit) in molecule.repr.get_ray_hits(local_origin, local_direction) {
+                let entry = transform.transform_point3(local_origin + along * local_direction);
+                hits.push(((entry - origin).mag(), id, hit));
+            }
+        }
+
+        hits.sort_by(|(a, ..), (b, ..)| a.total_cmp(b));
+        hits.into_iter().map(|(_, id, hit)| (id, hit)).collect()
+    }
+
+    /// The position at which an atom is drawn, in this assembly's space.
+    /// Returns `None` if the atom does not exist or is hidden.
+    pub fn atom_position(&self, id: ComponentId, atom: &AtomSpecifier) -> Option<Vec3> {
+        self.visible_molecules()
+            .into_iter()
+            .find(|(component, ..)| *component == id)
+            .and_then(|(_, molecule, transform)| {
+                Some(transform.transform_point3(*molecule.repr.pos(atom)?))
+            })
+    }
+
+    /// The measurements kept in this assembly, in the order they were added.
+    pub fn measurements(&self) -> &[Measurement] {
+        &self.measurements
+    }
+
+    /// Keeps a measurement in the assembly.  Measurements are annotations
+    /// rather than part of the design, so they can be added to locked
+    /// components.
+    pub fn add_measurement(&mut self, measurement: Measurement) {
+        self.measurements.push(measurement);
+    }
+
+    pub fn remove_measurement(&mut self, index: usize) -> Option<Measurement> {
+        (index < self.measurements.len()).then(|| self.measurements.remove(index))
+    }
+
+    /// Rewrites the references to atoms of the molecule `id` that are kept in
+    /// this assembly and those below it, such as measurements, after its atoms
+    /// were renumbered (see [`MoleculeEditor::reindex`]).
+    pub fn remap_atoms(
+        &mut self,
+        id: ComponentId,
+        mapping: &HashMap<AtomSpecifier, AtomSpecifier>,
+    ) {
+        for measurement in &mut self.measurements {
+            measurement.remap(id, mapping);
+        }
+        for component in &mut self.components {
+            if let ComponentType::SubAssembly(sub_assembly) = &mut component.data {
+                sub_assembly.remap_atoms(id, mapping);
+            }
+        }
+    }
+
+    /// The patterns kept in this assembly, in the order they were added.
+    pub fn patterns(&self) -> &[Pattern] {
+        &self.patterns
+    }
+
+    /// Keeps a pattern of copies of a molecule in this assembly.  The copies
+    /// are drawn wherever the seed is, hidden when it is hidden, and follow
+    /// every edit to it.  A pattern whose seed is later removed draws nothing.
+    pub fn add_pattern(&mut self, pattern: Pattern) -> Result<(), PatternError> {
+        let seed = self
+            .component(pattern.seed)
+            .ok_or(PatternError::UnknownComponent)?;
+        if seed.molecule().is_none() {
+            return Err(PatternError::NotAMolecule);
+        }
+        if pattern.instance_transforms().is_empty() {
+            return Err(PatternError::NoInstances);
+        }
+        self.patterns.push(pattern);
+        Ok(())
+    }
+
+    pub fn remove_pattern(&mut self, index: usize) -> Option<Pattern> {
+        (index < self.patterns.len()).then(|| self.patterns.remove(index))
+    }
+
+    /// Measures between the atoms as they are currently drawn.  Returns `None`
+    /// if any of them no longer exists or is hidden.
+    pub fn measure(&self, measurement: &Measurement) -> Option<MeasurementValue> {
+        let positions = measurement
+            .atoms()
+            .iter()
+            .map(|(id, atom)| self.atom_position(*id, atom))
+            .collect::<Option<Vec<Vec3>>>()?;
+        Measurement::value(&positions)
+    }
+
+    /// How far the components are spread apart to show how they fit together.
+    /// Each component is moved away from the middle of the assembly or
+    /// sub-assembly it is in by this fraction of its distance from it, so 0
+    /// shows the assembly as designed.  Like hiding, exploding changes where
+    /// components are drawn and picked, not where they are.
+    /// How many cells out from each periodic molecule its images are drawn,
+    /// along each edge of its unit cell.  0 draws no images.
+    pub fn periodic_images(&self) -> u32 {
+        self.periodic_images
+    }
+
+    pub fn set_periodic_images(&mut self, range: u32) {
+        self.periodic_images = range;
+    }
+
+    pub fn explode(&self) -> f32 {
+        self.explode
+    }
+
+    pub fn set_explode(&mut self, explode: f32) {
+        self.explode = explode.max(0.0);
+    }
+
+    // The middle of the direct children, in this assembly's space.
+    fn center(&self) -> Option<Vec3> {
+        let centers: Vec<Vec3> = self
+            .components
+            .iter()
+            .filter_map(Component::center)
+            .collect();
+        (!centers.is_empty())
+            .then(|| centers.iter().fold(Vec3::zero(), |sum, c| sum + *c) / centers.len() as f32)
+    }
+
+    // How far each direct child is moved when the assembly is exploded by
+    // `explode`, in order.
+    fn explode_offsets(&self, explode: f32) -> Vec<Vec3> {
+        let center = if explode > 0.0 { self.center() } else { None };
+        self.components
+            .iter()
+            .map(|component| match (center, component.center()) {
+                (Some(center), Some(own)) => (own - center) * explode,
+                _ => Vec3::zero(),
+            })
+            .collect()
+    }
+
+    /// The configurations saved in this assembly, in the order they were
+    /// saved.
+    pub fn configurations(&self) -> &[Configuration] {
+        &self.configurations
+    }
+
+    /// Describes how the assembly is currently shown, as a configuration named
+    /// `name`.  The assembly does not know about the camera, so that is left
+    /// for the caller to fill in.
+    pub fn capture_configuration(&self, name: impl Into<String>) -> Configuration {
+        Configuration {
+            name: name.into(),
+            hidden: self
+                .components()
+                .filter(|c| !c.visible)
+                .map(|c| c.id)
+                .collect(),
+            tints: self
+                .components()
+                .filter_map(|c| Some((c.id, c.tint?)))
+                .collect(),
+            explode: self.explode,
+            camera: None,
+            projection: Projection::default(),
+        }
+    }
+
+    /// Keeps a configuration in the assembly, replacing any with the same
+    /// name.
+    pub fn save_configuration(&mut self, configuration: Configuration) {
+        match self
+            .configurations
+            .iter_mut()
+            .find(|saved| saved.name == configuration.name)
+        {
+            Some(saved) => *saved = configuration,
+            None => self.configurations.push(configuration),
+        }
+    }
+
+    pub fn remove_configuration(&mut self, name: &str) -> Option<Configuration> {
+        let index = self.configurations.iter().position(|c| c.name == name)?;
+        Some(self.configurations.remove(index))
+    }
+
+    /// Shows the assembly as `configuration` describes.  Configurations only
+    /// change how the design is shown, so locked components are changed too.
+    pub fn apply_configuration(&mut self, configuration: &Configuration) {
+        self.explode = configuration.explode;
+        let mut stack: Vec<&mut Assembly> = vec![self];
+
+        while let Some(assembly) = stack.pop() {
+            for component in assembly.components.iter_mut() {
+                component.visible = !configuration.hidden.contains(&component.id);
+                component.tint = configuration
+                    .tints
+                    .iter()
+                    .find(|(id, _)| *id == component.id)
+                    .map(|(_, tint)| *tint);
+                if let ComponentType::SubAssembly(sub_assembly) = &mut component.data {
+                    stack.push(sub_assembly);
+                }
+            }
+        }
+    }
+
+    /// The view that the assembly was last shown in, if it has been kept.
+    /// Like the color scheme, it is saved along with the assembly, and it is
+    /// up to the caller to keep it up to date and to restore it.
+    pub fn saved_view(&self) -> Option<&SavedView> {
+        self.saved_view.as_ref()
+    }
+
+    pub fn set_saved_view(&mut self, saved_view: Option<SavedView>) {
+        self.saved_view = saved_view;
+    }
+
+    /// The colors that the assembly's atoms are drawn in.  This is kept with
+    /// the assembly so that it is saved along with it, but it is up to the
+    /// caller to pass it to the renderer.
+    pub fn color_scheme(&self) -> &ColorScheme {
+        &self.color_scheme
+    }
+
+    pub fn set_color_scheme(&mut self, color_scheme: ColorScheme) {
+        self.color_scheme = color_scheme;
+    }
+
+    /// Which hydrogens are drawn in the components that do not choose for
+    /// themselves (see [`Component::hydrogen_display`]).  Only the choice of
+    /// the assembly being drawn counts; those of its sub-assemblies are
+    /// ignored.
+    pub fn hydrogen_display(&self) -> HydrogenDisplay {
+        self.hydrogens
+    }
+
+    pub fn set_hydrogen_display(&mut self, hydrogens: HydrogenDisplay) {
+        self.hydrogens = hydrogens;
     }
 
     /// Recursively synchronize the atom data of each molecule to the GPU.
     pub fn synchronize_buffers(&mut self, gpu_resources: &render::GlobalRenderResources) {
+        let hydrogens = self.hydrogens;
+        self.synchronize_buffers_with(gpu_resources, hydrogens);
+    }
+
+    // Synchronizes the molecules, drawing the hydrogens of those that do not
+    // choose for themselves as `hydrogens` says.
+    fn synchronize_buffers_with(
+        &mut self,
+        gpu_resources: &render::GlobalRenderResources,
+        hydrogens: HydrogenDisplay,
+    ) {
         for component in self.components.iter_mut() {
+            let hydrogens = component.hydrogens.unwrap_or(hydrogens);
             match &mut component.data {
                 ComponentType::Molecule(ref mut molecule) => {
+                    molecule.repr.set_hydrogen_display(hydrogens);
                     molecule.repr.reupload_atoms(gpu_resources);
                 }
                 ComponentType::SubAssembly(ref mut assembly) => {
-                    assembly.synchronize_buffers(gpu_resources);
+                    assembly.synchronize_buffers_with(gpu_resources, hydrogens);
                 }
             }
         }
@@ -112,4 +953,574 @@ impl Assembly {
     pub fn direct_children(&self) -> &Vec<Component> {
         &self.components
     }
+
+    /// Iterates over every component in this assembly's tree, depth first, with
+    /// each sub-assembly's component visited before its children.
+    pub fn components(&self) -> impl Iterator<Item = &Component> {
+        let mut stack = vec![self.components.iter()];
+        std::iter::from_fn(move || loop {
+            let component = match stack.last_mut()?.next() {
+                Some(component) => component,
+                None => {
+                    stack.pop();
+                    continue;
+                }
+            };
+            if let Some(assembly) = component.sub_assembly() {
+                stack.push(assembly.components.iter());
+            }
+            return Some(component);
+        })
+    }
+
+    /// Computes the transform from the local space of the component `id` (its
+    /// molecule's own space, if it holds one) to the space of this assembly,
+    /// composed in the same order used for rendering.
+    /// The motion of drives is left out, so this is where the component was
+    /// designed to be.
+    pub fn world_transform(&self, id: ComponentId) -> Option<Mat4> {
+        self.components.iter().find_map(|component| {
+            if component.id == id {
+                Some(component.placed_transform())
+            } else {
+                component
+                    .sub_assembly()?
+                    .world_transform(id)
+                    .map(|transform| transform * component.transform)
+            }
+        })
+    }
+
+    /// Adds a component as a direct child of this assembly, returning its ID.
+    pub fn add_component(&mut self, component: Component) -> ComponentId {
+        let id = component.id;
+        self.components.push(component);
+        id
+    }
+
+    /// Adds a component as a child of the sub-assembly component `parent`, or
+    /// of this assembly if `parent` is `None`, before the child at `index`, or
+    /// after the last child if `index` is `None` or past the end.
+    pub fn insert_component(
+        &mut self,
+        parent: Option<ComponentId>,
+        index: Option<usize>,
+        component: Component,
+    ) -> Result<ComponentId, ReparentError> {
+        if parent.is_some_and(|parent| self.is_locked(parent) == Some(true)) {
+            return Err(ReparentError::Locked);
+        }
+        let siblings = match parent {
+            None => &mut self.components,
+            Some(parent) => {
+                &mut self
+                    .component_mut(parent)
+                    .and_then(Component::sub_assembly_mut)
+                    .ok_or(ReparentError::InvalidParent)?
+                    .components
+            }
+        };
+        let id = component.id;
+        let index = index.map_or(siblings.len(), |index| index.min(siblings.len()));
+        siblings.insert(index, component);
+        Ok(id)
+    }
+
+    /// Where the component `id` is in the tree: the sub-assembly component
+    /// that holds it, or `None` if this assembly does, and its index among
+    /// that parent's children.
+    pub fn location(&self, id: ComponentId) -> Option<(Option<ComponentId>, usize)> {
+        if let Some(index) = self.components.iter().position(|c| c.id == id) {
+            return Some((None, index));
+        }
+        self.components.iter().find_map(|component| {
+            let (parent, index) = component.sub_assembly()?.location(id)?;
+            Some((parent.or(Some(component.id)), index))
+        })
+    }
+
+    /// The path through the tree to the component `id`.
+    pub fn path(&self, id: ComponentId) -> Option<AssemblyPath> {
+        let mut ids = self.ancestors(id)?;
+        ids.push(id);
+        Some(AssemblyPath(ids))
+    }
+
+    // The sub-assembly components that hold the component `id`, outermost
+    // first.
+    fn ancestors(&self, id: ComponentId) -> Option<Vec<ComponentId>> {
+        if self.components.iter().any(|c| c.id == id) {
+            return Some(Vec::new());
+        }
+        self.components.iter().find_map(|component| {
+            let mut ids = component.sub_assembly()?.ancestors(id)?;
+            ids.insert(0, component.id);
+            Some(ids)
+        })
+    }
+
+    /// Follows `path` down the tree.  Returns `None` if any component along it
+    /// is missing or has been moved elsewhere.
+    pub fn resolve(&self, path: &AssemblyPath) -> Option<&Component> {
+        let (last, ancestors) = path.0.split_last()?;
+        let mut assembly = self;
+        for id in ancestors {
+            assembly = assembly
+                .components
+                .iter()
+                .find(|c| c.id == *id)?
+                .sub_assembly()?;
+        }
+        assembly.components.iter().find(|c| c.id == *last)
+    }
+
+    /// Finds a component anywhere in this assembly's tree.
+    pub fn component(&self, id: ComponentId) -> Option<&Component> {
+        self.components.iter().find_map(|component| {
+            if component.id == id {
+                Some(component)
+            } else {
+                component.sub_assembly()?.component(id)
+            }
+        })
+    }
+
+    /// Finds a component anywhere in this assembly's tree, except inside a
+    /// locked sub-assembly.
+    pub fn component_mut(&mut self, id: ComponentId) -> Option<&mut Component> {
+        self.components.iter_mut().find_map(|component| {
+            if component.id == id {
+                Some(component)
+            } else {
+                component.sub_assembly_mut()?.component_mut(id)
+            }
+        })
+    }
+
+    /// Whether the component `id` is locked, either itself or because one of
+    /// the sub-assemblies containing it is.  Returns `None` if the component
+    /// is not in the tree.
+    pub fn is_locked(&self, id: ComponentId) -> Option<bool> {
+        self.components.iter().find_map(|component| {
+            if component.id == id {
+                Some(component.locked)
+            } else {
+                let locked = component.sub_assembly()?.is_locked(id)?;
+                Some(locked || component.locked)
+            }
+        })
+    }
+
+    /// Changes the transform of the component `id`, relative to its parent.
+    pub fn set_transform(
+        &mut self,
+        id: ComponentId,
+        transform: Mat4,
+    ) -> Result<(), TransformError> {
+        match self.is_locked(id) {
+            None => Err(TransformError::UnknownComponent),
+            Some(true) => Err(TransformError::Locked),
+            Some(false) => {
+                let component = self
+                    .component_mut(id)
+                    .expect("unlocked components are reachable");
+                component.transform = transform;
+                Ok(())
+            }
+        }
+    }
+
+    /// Reflects the component `id` in `plane`, which is in this assembly's
+    /// space.  The result is the mirror image, not a moved copy: a chiral
+    /// molecule becomes its other enantiomer.  Atom billboards are built after
+    /// the transform, so a reflection draws correctly, and a drive turns the
+    /// mirrored component about its own axis as before.
+    pub fn mirror(&mut self, id: ComponentId, plane: &Plane) -> Result<(), TransformError> {
+        match self.is_locked(id) {
+            None => Err(TransformError::UnknownComponent),
+            Some(true) => Err(TransformError::Locked),
+            Some(false) => {
+                // World transforms are composed as `own * ancestors`, so the
+                // reflection follows the component's own transform.
+                let component = self
+                    .component_mut(id)
+                    .expect("unlocked components are reachable");
+                component.transform = plane.reflection() * component.transform;
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether the component `id` is drawn as a mirror image, because an odd
+    /// number of the transforms from it up to this assembly are reflections.
+    pub fn is_mirrored(&self, id: ComponentId) -> Option<bool> {
+        self.world_transform(id)
+            .map(|transform| transform.determinant() < 0.0)
+    }
+
+    /// Gives a component a driven degree of freedom, or removes it with `None`.
+    pub fn set_drive(
+        &mut self,
+        id: ComponentId,
+        drive: Option<Drive>,
+    ) -> Result<(), TransformError> {
+        match self.is_locked(id) {
+            None => Err(TransformError::UnknownComponent),
+            Some(true) => Err(TransformError::Locked),
+            Some(false) => {
+                let component = self
+                    .component_mut(id)
+                    .expect("unlocked components are reachable");
+                component.drive = drive;
+                Ok(())
+            }
+        }
+    }
+
+    /// Moves every drive on by `seconds` of motion.  Returns whether anything
+    /// moved.  Locked components are held still.
+    pub fn advance_drives(&mut self, seconds: f32) -> bool {
+        let mut moved = false;
+        let mut stack: Vec<&mut Assembly> = vec![self];
+
+        while let Some(assembly) = stack.pop() {
+            for component in assembly.components.iter_mut().filter(|c| !c.locked) {
+                if let Some(drive) = &mut component.drive {
+                    drive.advance(seconds);
+                    moved = true;
+                }
+                if let ComponentType::SubAssembly(sub_assembly) = &mut component.data {
+                    stack.push(sub_assembly);
+                }
+            }
+        }
+
+        moved
+    }
+
+    /// Returns every drive to its designed position.
+    pub fn reset_drives(&mut self) {
+        let mut stack: Vec<&mut Assembly> = vec![self];
+
+        while let Some(assembly) = stack.pop() {
+            for component in assembly.components.iter_mut() {
+                if let Some(drive) = &mut component.drive {
+                    drive.reset();
+                }
+                if let ComponentType::SubAssembly(sub_assembly) = &mut component.data {
+                    stack.push(sub_assembly);
+                }
+            }
+        }
+    }
+
+    /// Removes a component from anywhere in this assembly's tree and returns it.
+    /// Returns `None` if the component is not in the tree or is locked.
+    pub fn remove_component(&mut self, id: ComponentId) -> Option<Component> {
+        if let Some(index) = self.components.iter().position(|c| c.id == id) {
+            if self.components[index].locked {
+                return None;
+            }
+            return Some(self.components.remove(index));
+        }
+
+        self.components
+            .iter_mut()
+            .filter_map(Component::sub_assembly_mut)
+            .find_map(|assembly| assembly.remove_component(id))
+    }
+
+    /// Moves a component to a new place in the assembly tree. If `new_parent` is
+    /// `None` the component becomes a direct child of this assembly, otherwise
+    /// it becomes a child of the sub-assembly component `new_parent`.  The
+    /// component's own transform is changed to keep it where it was in the
+    /// world.
+    pub fn reparent(
+        &mut self,
+        id: ComponentId,
+        new_parent: Option<ComponentId>,
+    ) -> Result<(), ReparentError> {
+        self.reparent_at(id, new_parent, None)
+    }
+
+    /// Like [`Assembly::reparent`], but puts the component before the child
+    /// of its new parent at `index`, rather than after the last, which also
+    /// rearranges the children of a parent.
+    pub fn reparent_at(
+        &mut self,
+        id: ComponentId,
+        new_parent: Option<ComponentId>,
+        index: Option<usize>,
+    ) -> Result<(), ReparentError> {
+        let component = self.component(id).ok_or(ReparentError::UnknownComponent)?;
+        if self.is_locked(id) == Some(true)
+            || new_parent.is_some_and(|parent_id| self.is_locked(parent_id) == Some(true))
+        {
+            return Err(ReparentError::Locked);
+        }
+
+        if let Some(parent_id) = new_parent {
+            // Moving a component into itself (or into one of its own children)
+            // would detach that whole subtree from the tree.
+            let is_descendant = component
+                .sub_assembly()
+                .and_then(|assembly| assembly.component(parent_id))
+                .is_some();
+            if parent_id == id || is_descendant {
+                return Err(ReparentError::Cycle);
+            }
+            if self
+                .component(parent_id)
+                .and_then(Component::sub_assembly)
+                .is_none()
+            {
+                return Err(ReparentError::InvalidParent);
+            }
+        }
+
+        // The transform that a parent puts its children's own transforms in.
+        let frame = |parent: Option<ComponentId>| {
+            parent.map_or(Some(Mat4::identity()), |parent| {
+                self.world_transform(parent)
+            })
+        };
+        let (old_parent, _) = self
+            .location(id)
+            .expect("the component was found in the tree above");
+        let moved = (old_parent != new_parent).then(|| {
+            let old_frame = frame(old_parent).expect("the old parent holds the component");
+            let new_frame = frame(new_parent).expect("the new parent was validated above");
+            (old_frame, new_frame)
+        });
+
+        let mut component = self
+            .remove_component(id)
+            .expect("the component was found in the tree above");
+        if let Some((old_frame, new_frame)) = moved {
+            // World transforms are composed as `own * molecule * parent`, so
+            // keeping the world transform the same in the new parent takes
+            // undoing the new parent's frame and putting the old one's in its
+            // place.
+            let local = component.molecule_transform();
+            component.transform =
+                component.transform * local * old_frame * new_frame.inversed() * local.inversed();
+        }
+        self.insert_component(new_parent, index, component)
+            .expect("the new parent was validated above");
+
+        Ok(())
+    }
+
+    /// Moves the molecule `mobile` rigidly so that it lies as close as possible
+    /// to the molecule `target`, and returns the motion that was applied (in
+    /// this assembly's space) along with the remaining RMSD.  Atoms are matched
+    /// by specifier, so this is meaningful for variants of the same design,
+    /// such as a copy that was edited further.
+    pub fn superpose(
+        &mut self,
+        mobile: ComponentId,
+        target: ComponentId,
+    ) -> Result<Superposition, SuperposeError> {
+        let world_atoms = |id| {
+            let molecule = self
+                .component(id)
+                .ok_or(SuperposeError::UnknownComponent)?
+                .molecule()
+                .ok_or(SuperposeError::NotAMolecule)?;
+            let transform = self
+                .world_transform(id)
+                .expect("the component was found in the tree above");
+            Ok((&molecule.repr, transform))
+        };
+        let (mobile_molecule, mobile_transform) = world_atoms(mobile)?;
+        let (target_molecule, target_transform) = world_atoms(target)?;
+        if self.is_locked(mobile) == Some(true) {
+            return Err(SuperposeError::Locked);
+        }
+
+        let (mobile_positions, target_positions): (Vec<Vec3>, Vec<Vec3>) =
+            EditContext::atoms(mobile_molecule)
+                .iter()
+                .filter_map(|spec| {
+                    let mobile_pos = mobile_molecule.pos(spec)?;
+                    let target_pos = target_molecule.pos(spec)?;
+                    Some((
+                        mobile_transform.transform_point3(*mobile_pos),
+                        target_transform.transform_point3(*target_pos),
+                    ))
+                })
+                .unzip();
+        let superposition = superposition::superpose(&mobile_positions, &target_positions)
+            .ok_or(SuperposeError::NoCommonAtoms)?;
+
+        // World transforms are composed as `own * ancestors`, so following the
+        // world transform with the motion is the same as following the
+        // component's own transform with it.
+        let component = self
+            .component_mut(mobile)
+            .expect("unlocked components are reachable");
+        component.transform = superposition.transform() * component.transform;
+
+        Ok(superposition)
+    }
+
+    /// Splits the molecule `id` into a component for each of `parts` (as found
+    /// by `molecule::parts::find_repeated_parts`), whose molecule is drawn
+    /// once for every copy of the part (see [`Component::copies`]), plus a
+    /// component for the atoms left over.  The bonds that joined the parts to
+    /// one another and to the rest are not kept.  The component becomes a
+    /// sub-assembly holding these, and keeps its ID, name and place in the
+    /// tree.  The new molecules start from the current atoms, so the component
+    /// that was replaced, with its edit history, is returned, to be put back
+    /// by undoing (see [`AssemblyCommand::Replace`](crate::AssemblyCommand)).
+    pub fn instance_parts(
+        &mut self,
+        id: ComponentId,
+        parts: &[RepeatedPart],
+    ) -> Result<Component, InstancePartsError> {
+        let component = self
+            .component(id)
+            .ok_or(InstancePartsError::UnknownComponent)?;
+        let molecule = component
+            .molecule()
+            .ok_or(InstancePartsError::NotAMolecule)?;
+        if self.is_locked(id) == Some(true) {
+            return Err(InstancePartsError::Locked);
+        }
+        // The new components sit in a sub-assembly with no transform of its
+        // own, so placing them as the molecule was placed puts their atoms
+        // where its atoms were.
+        let transform = component.placed_transform();
+
+        let mut components = Vec::new();
+        let mut instanced = HashSet::new();
+        for (number, part) in parts.iter().enumerate() {
+            let name = format!("{} part {}", molecule.name(), number + 1);
+            let edit = parts::extract_atoms(&molecule.repr, &part.instances[0].atoms, &name);
+            for instance in &part.instances {
+                instanced.extend(instance.atoms.iter().cloned());
+            }
+            let mut part_component =
+                Component::from_molecule(MoleculeEditor::from_feature(edit), transform);
+            // The first copy is the one extracted, so its motion is the
+            // identity.
+            part_component.copies = part.instances[1..]
+                .iter()
+                .map(|instance| instance.superposition.transform())
+                .collect();
+            components.push(part_component);
+        }
+
+        let rest: Vec<AtomSpecifier> = EditContext::atoms(&molecule.repr)
+            .into_iter()
+            .filter(|spec| !instanced.contains(spec))
+            .collect();
+        if !rest.is_empty() {
+            let name = format!("{} rest", molecule.name());
+            let edit = parts::extract_atoms(&molecule.repr, &rest, &name);
+            components.push(Component::from_molecule(
+                MoleculeEditor::from_feature(edit),
+                transform,
+            ));
+        }
+
+        let mut assembly = Assembly::from_components(components);
+        assembly.set_name(component.name());
+        let replacement = Component {
+            id,
+            metadata: component.metadata.clone(),
+            visible: component.visible,
+            locked: false,
+            tint: component.tint,
+            hydrogens: component.hydrogens,
+            transform: Mat4::identity(),
+            drive: component.drive,
+            copies: Vec::new(),
+            source: None,
+            data: ComponentType::SubAssembly(Box::new(assembly)),
+        };
+        Ok(self
+            .replace_component(replacement)
+            .expect("unlocked components are reachable"))
+    }
+
+    /// Puts `component` in the place of the component with the same ID, and
+    /// returns the one it replaced.  Returns `None` if there is no such
+    /// component, or it is locked.
+    pub fn replace_component(&mut self, component: Component) -> Option<Component> {
+        if self.is_locked(component.id)? {
+            return None;
+        }
+        let current = self.component_mut(component.id)?;
+        Some(std::mem::replace(current, component))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use molecule::edit::{Edit, HydrogenFill};
+    use periodic_table::Element;
+    use ultraviolet::Rotor3;
+
+    use super::*;
+
+    // Methane, built by edits, so that loading it has to apply them again.
+    fn methane() -> MoleculeEditor {
+        let mut molecule = MoleculeEditor::from_feature(Edit::RootAtom(Element::Carbon));
+        molecule.add_edit(Edit::HydrogenFill(HydrogenFill { targets: None }));
+        molecule
+    }
+
+    #[test]
+    fn round_trip_keeps_nested_assemblies() {
+        let inner =
+            Component::from_molecule(methane(), Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0)));
+        let sub_assembly = Component::from_assembly(
+            Assembly::from_components([inner]),
+            Rotor3::from_rotation_xy(0.5)
+                .into_matrix()
+                .into_homogeneous(),
+        );
+        let outer =
+            Component::from_molecule(methane(), Mat4::from_translation(Vec3::new(-4.0, 0.0, 0.5)));
+        let assembly = Assembly::from_components([outer, sub_assembly]);
+
+        let json = serde_json::to_string(&assembly).unwrap();
+        let loaded: Assembly = serde_json::from_str(&json).unwrap();
+
+        let ids =
+            |assembly: &Assembly| assembly.components().map(Component::id).collect::<Vec<_>>();
+        assert_eq!(ids(&assembly), ids(&loaded));
+        for saved in assembly.components() {
+            let id = saved.id();
+            let component = loaded.component(id).unwrap();
+            assert_eq!(assembly.path(id), loaded.path(id));
+            assert_eq!(saved.transform(), component.transform());
+            assert_eq!(assembly.world_transform(id), loaded.world_transform(id));
+
+            match (saved.molecule(), component.molecule()) {
+                (Some(saved), Some(molecule)) => {
+                    let atoms = EditContext::atoms(&saved.repr);
+                    assert_eq!(atoms.len(), 5);
+                    assert_eq!(atoms, EditContext::atoms(&molecule.repr));
+                    for atom in &atoms {
+                        assert_eq!(
+                            saved.repr.find_atom(atom).map(|node| node.element),
+                            molecule.repr.find_atom(atom).map(|node| node.element)
+                        );
+                        assert_eq!(saved.repr.pos(atom), molecule.repr.pos(atom));
+                        assert_eq!(saved.repr.bonds(atom), molecule.repr.bonds(atom));
+                    }
+                    // The buffers are not saved, but the atoms they are
+                    // uploaded from when the assembly is next synchronized
+                    // are rebuilt as they were.
+                    let reprs = molecule.repr.atom_reprs();
+                    assert_eq!(reprs.len(), 5);
+                    assert!(reprs == saved.repr.atom_reprs());
+                }
+                (None, None) => {}
+                _ => panic!("component {} changed kind", id),
+            }
+        }
+    }
 }
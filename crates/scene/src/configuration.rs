@@ -0,0 +1,48 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::HashSet;
+
+use render::{CameraPose, Projection};
+use serde::{Deserialize, Serialize};
+use ultraviolet::Vec3;
+
+use crate::ComponentId;
+
+/// A named way of showing an assembly, e.g. "full assembly", "cutaway" or
+/// "mechanism only": which components are hidden, how they are tinted, how
+/// far the assembly is exploded, and where the camera is.  Switching between
+/// configurations changes only how the design is shown, never the design
+/// itself.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Configuration {
+    pub name: String,
+    /// The hidden components.  Components that are not listed, including any
+    /// added after the configuration was saved, are shown.
+    pub hidden: HashSet<ComponentId>,
+    /// The tinted components, with their tints.
+    pub tints: Vec<(ComponentId, Vec3)>,
+    /// See [`Assembly::explode`](crate::Assembly::explode).
+    pub explode: f32,
+    /// The camera, if there was one when the configuration was saved.
+    pub camera: Option<CameraPose>,
+    pub projection: Projection,
+}
+
+/// The view that the author last had of an assembly, kept with it so that
+/// reopening the design shows it as they left it.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct SavedView {
+    pub camera: Option<CameraPose>,
+    pub projection: Projection,
+    /// The names of the panels that were open.  What they are is up to the
+    /// application.
+    pub open_panels: Vec<String>,
+    /// The name of the tool that was in use.
+    pub tool: Option<String>,
+    /// The name of the configuration last saved or switched to, if any.
+    pub configuration: Option<String>,
+}
+
+// End of File
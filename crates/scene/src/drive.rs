@@ -0,0 +1,94 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use serde::{Deserialize, Serialize};
+use ultraviolet::{Bivec3, Mat4, Rotor3, Vec3};
+
+/// The kinds of motion that a drive can produce.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum DriveKind {
+    /// Rotation about the drive's axis, e.g. a gear turning on its shaft.
+    /// Positions are in radians.
+    Rotation,
+    /// Sliding along the drive's axis, e.g. a rod in a bearing.  Positions are
+    /// in angstroms.
+    Translation,
+}
+
+/// A driven degree of freedom of a component, which moves the component's
+/// contents in its own coordinates (before its transform places them) when the
+/// assembly is animated.
+///
+/// The motion is only shown, not applied: the component's transform stays
+/// where it was designed, so stopping an animation never changes the design.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Drive {
+    pub kind: DriveKind,
+    /// A point on the axis, in the component's coordinates.
+    pub origin: Vec3,
+    /// The direction of the axis, in the component's coordinates.
+    pub axis: Vec3,
+    /// How fast the drive moves, in radians or angstroms per second.
+    pub speed: f32,
+    /// How far the drive has moved from the designed position.
+    #[serde(default)]
+    pub position: f32,
+}
+
+impl Drive {
+    /// A drive that turns about the axis through `origin` along `axis` at
+    /// `speed` radians per second.
+    pub fn rotation(origin: Vec3, axis: Vec3, speed: f32) -> Self {
+        Self {
+            kind: DriveKind::Rotation,
+            origin,
+            axis: axis.normalized(),
+            speed,
+            position: 0.0,
+        }
+    }
+
+    /// A drive that slides along `axis` at `speed` angstroms per second.
+    pub fn translation(axis: Vec3, speed: f32) -> Self {
+        Self {
+            kind: DriveKind::Translation,
+            origin: Vec3::zero(),
+            axis: axis.normalized(),
+            speed,
+            position: 0.0,
+        }
+    }
+
+    /// Moves the drive on by `seconds` of motion.  Rotations wrap around after
+    /// a full turn, so that long animations do not lose precision.
+    pub fn advance(&mut self, seconds: f32) {
+        self.position += self.speed * seconds;
+        if self.kind == DriveKind::Rotation {
+            self.position = self.position.rem_euclid(std::f32::consts::TAU);
+        }
+    }
+
+    /// Returns the drive to the designed position.
+    pub fn reset(&mut self) {
+        self.position = 0.0;
+    }
+
+    /// The motion of the drive at its current position.
+    pub fn transform(&self) -> Mat4 {
+        match self.kind {
+            DriveKind::Rotation => {
+                let rotation = Rotor3::from_angle_plane(
+                    self.position,
+                    Bivec3::from_normalized_axis(self.axis),
+                );
+                Mat4::from_translation(self.origin)
+                    * rotation.into_matrix().into_homogeneous()
+                    * Mat4::from_translation(-self.origin)
+            }
+            DriveKind::Translation => Mat4::from_translation(self.axis * self.position),
+        }
+    }
+}
+
+// End of File
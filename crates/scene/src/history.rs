@@ -0,0 +1,344 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Undo and redo for a whole project.
+//!
+//! Each molecule keeps the changes to its own edit list (see
+//! [`MoleculeEditor::undo`]), but adding, deleting, moving, renaming and
+//! rearranging the components of an assembly are not edits of any molecule.
+//! A [`ProjectHistory`] keeps those as [`AssemblyCommand`]s, and interleaves
+//! them with the molecules' own changes in the order they were made, so that
+//! undoing and redoing step back and forth through both alike.
+//!
+//! Every command, when carried out, gives back the command that reverses it:
+//! removing a component gives back the command that puts it back where it
+//! was, and so on.  Undoing carries out the reversing command and keeps what
+//! it gives back to redo with.
+
+use std::collections::HashMap;
+
+use molecule::MoleculeEditor;
+use ultraviolet::Mat4;
+
+use crate::{Assembly, Component, ComponentId, ReparentError, TransformError};
+
+/// How many steps can be undone.
+const MAX_STEPS: usize = 100;
+
+/// A change to the components of an assembly.
+pub enum AssemblyCommand {
+    /// Adds `component` as a child of `parent`, or of the assembly itself if
+    /// that is `None`, before the child at `index`, or after the last if
+    /// `index` is `None`.
+    Insert {
+        component: Box<Component>,
+        parent: Option<ComponentId>,
+        index: Option<usize>,
+    },
+    /// Deletes the component `id`, and everything in it.
+    Remove {
+        id: ComponentId,
+    },
+    /// Places the component `id` with `transform`, relative to its parent.
+    SetTransform {
+        id: ComponentId,
+        transform: Mat4,
+    },
+    Rename {
+        id: ComponentId,
+        name: String,
+    },
+    /// Moves the component `id` to `parent`, before its child at `index`, as
+    /// for [`AssemblyCommand::Insert`].
+    Move {
+        id: ComponentId,
+        parent: Option<ComponentId>,
+        index: Option<usize>,
+    },
+    /// Puts `component` in the place of the component with the same ID, e.g.
+    /// to turn a molecule back from the sub-assembly it was split into.
+    Replace {
+        component: Box<Component>,
+    },
+}
+
+/// Why an [`AssemblyCommand`] could not be carried out.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CommandError {
+    /// The component does not exist in the assembly.
+    UnknownComponent,
+    /// The component, or where it was to go, is locked.
+    Locked,
+    /// The component cannot be moved there.
+    Reparent(ReparentError),
+}
+
+impl From<TransformError> for CommandError {
+    fn from(err: TransformError) -> Self {
+        match err {
+            TransformError::UnknownComponent => CommandError::UnknownComponent,
+            TransformError::Locked => CommandError::Locked,
+        }
+    }
+}
+
+impl From<ReparentError> for CommandError {
+    fn from(err: ReparentError) -> Self {
+        match err {
+            ReparentError::UnknownComponent => CommandError::UnknownComponent,
+            ReparentError::Locked => CommandError::Locked,
+            err => CommandError::Reparent(err),
+        }
+    }
+}
+
+impl AssemblyCommand {
+    /// Carries out the command, and returns the command that reverses it.
+    pub fn execute(self, assembly: &mut Assembly) -> Result<AssemblyCommand, CommandError> {
+        match self {
+            AssemblyCommand::Insert {
+                component,
+                parent,
+                index,
+            } => {
+                let id = assembly.insert_component(parent, index, *component)?;
+                Ok(AssemblyCommand::Remove { id })
+            }
+            AssemblyCommand::Remove { id } => {
+                let (parent, index) = assembly
+                    .location(id)
+                    .ok_or(CommandError::UnknownComponent)?;
+                let component = assembly.remove_component(id).ok_or(CommandError::Locked)?;
+                Ok(AssemblyCommand::Insert {
+                    component: Box::new(component),
+                    parent,
+                    index: Some(index),
+                })
+            }
+            AssemblyCommand::SetTransform { id, transform } => {
+                let previous = assembly
+                    .component(id)
+                    .ok_or(CommandError::UnknownComponent)?
+                    .transform();
+                assembly.set_transform(id, transform)?;
+                Ok(AssemblyCommand::SetTransform {
+                    id,
+                    transform: previous,
+                })
+            }
+            AssemblyCommand::Rename { id, name } => {
+                let component = assembly
+                    .component_mut(id)
+                    .ok_or(CommandError::UnknownComponent)?;
+                let previous = component.name().to_owned();
+                component.set_name(name);
+                Ok(AssemblyCommand::Rename { id, name: previous })
+            }
+            AssemblyCommand::Move { id, parent, index } => {
+                let (previous_parent, previous_index) = assembly
+                    .location(id)
+                    .ok_or(CommandError::UnknownComponent)?;
+                assembly.reparent_at(id, parent, index)?;
+                Ok(AssemblyCommand::Move {
+                    id,
+                    parent: previous_parent,
+                    index: Some(previous_index),
+                })
+            }
+            AssemblyCommand::Replace { component } => {
+                if assembly.component(component.id()).is_none() {
+                    return Err(CommandError::UnknownComponent);
+                }
+                let previous = assembly
+                    .replace_component(*component)
+                    .ok_or(CommandError::Locked)?;
+                Ok(AssemblyCommand::Replace {
+                    component: Box::new(previous),
+                })
+            }
+        }
+    }
+}
+
+// One step of the history, which can be undone or redone.
+enum Step {
+    // A change to the components of the assembly, along with what it was
+    // described as when it was made.
+    Assembly(AssemblyCommand, String),
+    // A change to the edit list of the molecule of a component, which the
+    // molecule keeps itself.
+    Molecule(ComponentId),
+}
+
+/// The changes made to a project, to be undone and redone.
+#[derive(Default)]
+pub struct ProjectHistory {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    // The revision of each molecule's edit list as last seen, by component.
+    revisions: HashMap<ComponentId, u64>,
+}
+
+impl ProjectHistory {
+    /// Carries out `command`, as one step to be undone, described as
+    /// `description`, such as "delete Gear".
+    pub fn execute(
+        &mut self,
+        assembly: &mut Assembly,
+        command: AssemblyCommand,
+        description: impl Into<String>,
+    ) -> Result<(), CommandError> {
+        self.sync(assembly);
+        let reverse = command.execute(assembly)?;
+        self.push(Step::Assembly(reverse, description.into()));
+        Ok(())
+    }
+
+    /// Records a change to the assembly that has already been made, where
+    /// `reverse` is the command that reverses it.  This is for changes, such
+    /// as superposing one molecule onto another, that are not themselves
+    /// commands.
+    pub fn record(
+        &mut self,
+        assembly: &Assembly,
+        reverse: AssemblyCommand,
+        description: impl Into<String>,
+    ) {
+        self.sync(assembly);
+        self.push(Step::Assembly(reverse, description.into()));
+    }
+
+    /// Records that the component `id` was just added to the assembly.
+    pub fn record_added(&mut self, assembly: &Assembly, id: ComponentId) {
+        let description = assembly.component(id).map_or_else(
+            || "add component".to_owned(),
+            |c| format!("add {}", c.name()),
+        );
+        self.record(assembly, AssemblyCommand::Remove { id }, description);
+    }
+
+    /// Takes note of the changes made to the molecules' edit lists since this
+    /// was last called, so that they are undone in turn with the changes to
+    /// the assembly.  This is cheap, and should be called often, e.g. every
+    /// frame, for the order of the changes to be kept.
+    pub fn sync(&mut self, assembly: &Assembly) {
+        // Each new revision is one undo step of the molecule.
+        let mut changed: Vec<ComponentId> = Vec::new();
+        for component in assembly.components() {
+            let Some(molecule) = component.molecule() else {
+                continue;
+            };
+            let revision = molecule.revision();
+            if let Some(seen) = self.revisions.insert(component.id(), revision) {
+                for _ in seen..revision {
+                    changed.push(component.id());
+                }
+            }
+        }
+        for id in changed {
+            self.push(Step::Molecule(id));
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Undoes the most recent change, to the assembly or to a molecule's
+    /// edits.  Returns a description of what was undone, or `None` if there
+    /// was nothing to undo.  Changes that can no longer be undone, such as
+    /// those of a molecule that has since been locked, are skipped.
+    pub fn undo(&mut self, assembly: &mut Assembly) -> Option<String> {
+        self.sync(assembly);
+        while let Some(step) = self.undo.pop() {
+            if let Some((step, description)) = Self::reverse(assembly, step, MoleculeEditor::undo) {
+                self.redo.push(step);
+                return Some(description);
+            }
+        }
+        None
+    }
+
+    /// Redoes the most recently undone change.  Returns a description of what
+    /// was redone, or `None` if there was nothing to redo.
+    pub fn redo(&mut self, assembly: &mut Assembly) -> Option<String> {
+        self.sync(assembly);
+        while let Some(step) = self.redo.pop() {
+            if let Some((step, description)) = Self::reverse(assembly, step, MoleculeEditor::redo) {
+                self.undo.push(step);
+                return Some(description);
+            }
+        }
+        None
+    }
+
+    // Keeps a new step, which forgets what was undone before it.
+    fn push(&mut self, step: Step) {
+        if self.undo.len() == MAX_STEPS {
+            self.undo.remove(0);
+        }
+        self.undo.push(step);
+        self.redo.clear();
+    }
+
+    // Reverses `step`, returning the step that reverses it again, along with
+    // a description of it.
+    fn reverse(
+        assembly: &mut Assembly,
+        step: Step,
+        molecule_change: fn(&mut MoleculeEditor) -> bool,
+    ) -> Option<(Step, String)> {
+        match step {
+            Step::Assembly(command, description) => {
+                let reverse = command.execute(assembly).ok()?;
+                Some((Step::Assembly(reverse, description.clone()), description))
+            }
+            Step::Molecule(id) => {
+                let component = assembly.component_mut(id)?;
+                let description = format!("change to {}", component.name());
+                molecule_change(component.molecule_mut()?)
+                    .then_some((Step::Molecule(id), description))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use molecule::edit::{BondedAtom, Edit, EditContext};
+    use periodic_table::Element;
+
+    use super::*;
+
+    #[test]
+    fn undo_takes_back_only_the_last_placed_atom() {
+        let mut assembly = Assembly::default();
+        let mut history = ProjectHistory::default();
+        let molecule = MoleculeEditor::from_feature(Edit::RootAtom(Element::Carbon));
+        let id = assembly.add_component(Component::from_molecule(molecule, Mat4::identity()));
+        history.record_added(&assembly, id);
+        history.sync(&assembly);
+
+        let molecule = assembly.component_mut(id).unwrap().molecule_mut().unwrap();
+        let carbon = EditContext::atoms(&molecule.repr)[0].clone();
+        molecule.add_edit(Edit::BondedAtom(BondedAtom {
+            target: carbon.clone(),
+            element: Element::Oxygen,
+        }));
+        assert_eq!(EditContext::atoms(&molecule.repr).len(), 2);
+
+        assert!(history.undo(&mut assembly).is_some());
+        let molecule = assembly.component(id).unwrap().molecule().unwrap();
+        assert_eq!(EditContext::atoms(&molecule.repr), vec![carbon]);
+
+        assert!(history.undo(&mut assembly).is_some());
+        assert!(assembly.component(id).is_none());
+    }
+}
+
+// End of File
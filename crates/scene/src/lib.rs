@@ -2,6 +2,23 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-pub use assembly::{Assembly, Component};
+pub use assembly::{
+    Assembly, AssemblyPath, Component, ComponentId, InstancePartsError, PatternError,
+    ReparentError, SuperposeError, TransformError,
+};
+pub use configuration::{Configuration, SavedView};
+pub use drive::{Drive, DriveKind};
+pub use history::{AssemblyCommand, CommandError, ProjectHistory};
+pub use measurement::{Measurement, MeasurementValue};
+pub use pattern::{Pattern, PatternKind};
+pub use reference::{content_hash, ExternalRef};
+pub use validation::{validate, IssueKind, ValidationIssue, ValidationReport};
 
 mod assembly;
+mod configuration;
+mod drive;
+mod history;
+mod measurement;
+mod pattern;
+mod reference;
+mod validation;
@@ -0,0 +1,96 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use common::ids::AtomSpecifier;
+use serde::{Deserialize, Serialize};
+use ultraviolet::Vec3;
+
+use crate::ComponentId;
+
+/// A distance, angle or dihedral between atoms, which is kept in the scene and
+/// re-measured whenever it is shown, so that it follows the atoms as the design
+/// changes.  The atoms may belong to different molecules.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Measurement {
+    atoms: Vec<(ComponentId, AtomSpecifier)>,
+}
+
+impl Measurement {
+    /// Measures between 2 atoms (a distance), 3 (the angle at the middle atom)
+    /// or 4 (the dihedral about the bond between the middle two).  Returns
+    /// `None` for any other number of atoms.
+    pub fn new(atoms: Vec<(ComponentId, AtomSpecifier)>) -> Option<Self> {
+        (2..=4).contains(&atoms.len()).then_some(Self { atoms })
+    }
+
+    pub fn atoms(&self) -> &[(ComponentId, AtomSpecifier)] {
+        &self.atoms
+    }
+
+    /// Follows the atoms of the molecule `id` to their new specifiers, after
+    /// they were renumbered.  Atoms not in `mapping` are left as they are.
+    pub fn remap(&mut self, id: ComponentId, mapping: &HashMap<AtomSpecifier, AtomSpecifier>) {
+        for (component, atom) in &mut self.atoms {
+            if *component != id {
+                continue;
+            }
+            if let Some(new) = mapping.get(atom) {
+                *atom = new.clone();
+            }
+        }
+    }
+
+    /// Computes the measurement from the positions of its atoms, in order.
+    pub fn value(positions: &[Vec3]) -> Option<MeasurementValue> {
+        match *positions {
+            [a, b] => Some(MeasurementValue::Distance((b - a).mag())),
+            [a, b, c] => Some(MeasurementValue::Angle(angle(a - b, c - b))),
+            [a, b, c, d] => Some(MeasurementValue::Dihedral(dihedral(a, b, c, d))),
+            _ => None,
+        }
+    }
+}
+
+/// The result of a measurement.  Angles are in degrees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MeasurementValue {
+    /// In angstroms.
+    Distance(f32),
+    /// Between 0 and 180 degrees.
+    Angle(f32),
+    /// Between -180 and 180 degrees.
+    Dihedral(f32),
+}
+
+impl fmt::Display for MeasurementValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeasurementValue::Distance(distance) => write!(f, "{:.3} Å", distance),
+            MeasurementValue::Angle(angle) | MeasurementValue::Dihedral(angle) => {
+                write!(f, "{:.1}°", angle)
+            }
+        }
+    }
+}
+
+// The angle between two vectors.
+fn angle(u: Vec3, v: Vec3) -> f32 {
+    let cos = u.dot(v) / (u.mag() * v.mag());
+    cos.clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+// The signed dihedral angle of the chain a-b-c-d about the b-c bond.
+fn dihedral(a: Vec3, b: Vec3, c: Vec3, d: Vec3) -> f32 {
+    let (b1, b2, b3) = (b - a, c - b, d - c);
+    let n1 = b1.cross(b2);
+    let n2 = b2.cross(b3);
+    let y = b2.mag() * b1.dot(n2);
+    let x = n1.dot(n2);
+    y.atan2(x).to_degrees()
+}
+
+// End of File
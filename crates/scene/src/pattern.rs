@@ -0,0 +1,107 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::f32::consts::TAU;
+
+use serde::{Deserialize, Serialize};
+use ultraviolet::{Bivec3, Mat4, Rotor3, Vec3};
+
+use crate::ComponentId;
+
+/// The ways that a pattern can place copies of its seed.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum PatternKind {
+    /// A single copy, moved by `offset`.
+    Duplicate { offset: Vec3 },
+    /// A row of `count` instances, counting the seed, each `step` further on
+    /// from the one before.
+    Linear { count: u32, step: Vec3 },
+    /// A ring of `count` instances, counting the seed, spaced evenly around
+    /// the axis through `origin` along `axis`.
+    Circular {
+        count: u32,
+        origin: Vec3,
+        axis: Vec3,
+    },
+}
+
+/// Copies of a molecule placed by a rule rather than one by one.  The copies
+/// are not components of their own: they show the seed's atoms wherever the
+/// rule puts them, so editing the seed changes every copy.
+///
+/// Patterns are kept by the assembly at the root of the tree, and their
+/// offsets and axes are in its space.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Pattern {
+    pub seed: ComponentId,
+    pub kind: PatternKind,
+}
+
+impl Pattern {
+    pub fn duplicate(seed: ComponentId, offset: Vec3) -> Self {
+        Self {
+            seed,
+            kind: PatternKind::Duplicate { offset },
+        }
+    }
+
+    pub fn linear(seed: ComponentId, count: u32, step: Vec3) -> Self {
+        Self {
+            seed,
+            kind: PatternKind::Linear { count, step },
+        }
+    }
+
+    pub fn circular(seed: ComponentId, count: u32, origin: Vec3, axis: Vec3) -> Self {
+        Self {
+            seed,
+            kind: PatternKind::Circular {
+                count,
+                origin,
+                axis: axis.normalized(),
+            },
+        }
+    }
+
+    /// A short human readable description of the pattern, for display in
+    /// lists.
+    pub fn name(&self) -> String {
+        match self.kind {
+            PatternKind::Duplicate { .. } => format!("Duplicate of {:?}", self.seed),
+            PatternKind::Linear { count, .. } => {
+                format!("Linear pattern of {:?} ({} instances)", self.seed, count)
+            }
+            PatternKind::Circular { count, .. } => {
+                format!("Circular pattern of {:?} ({} instances)", self.seed, count)
+            }
+        }
+    }
+
+    /// Where each copy is put, as a transform applied after the seed's own.
+    /// The seed itself is not included.
+    pub fn instance_transforms(&self) -> Vec<Mat4> {
+        match self.kind {
+            PatternKind::Duplicate { offset } => vec![Mat4::from_translation(offset)],
+            PatternKind::Linear { count, step } => (1..count)
+                .map(|index| Mat4::from_translation(step * index as f32))
+                .collect(),
+            PatternKind::Circular {
+                count,
+                origin,
+                axis,
+            } => (1..count)
+                .map(|index| {
+                    let angle = TAU * index as f32 / count as f32;
+                    let rotation =
+                        Rotor3::from_angle_plane(angle, Bivec3::from_normalized_axis(axis));
+                    Mat4::from_translation(origin)
+                        * rotation.into_matrix().into_homogeneous()
+                        * Mat4::from_translation(-origin)
+                })
+                .collect(),
+        }
+    }
+}
+
+// End of File
@@ -0,0 +1,56 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Components that link to a molecule kept in a file of its own, rather than
+//! only embedding it, so that a part shared between projects can be changed
+//! in one place.
+//!
+//! A linked component still keeps a copy of the molecule, which is what is
+//! drawn and saved with the project, along with a hash of the file it was
+//! read from.  When the project is loaded, the file is read again: if its
+//! hash has changed, the copy can be updated from it, or kept as it is.
+
+use serde::{Deserialize, Serialize};
+
+/// Where a linked component's molecule comes from.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ExternalRef {
+    /// The path or URL of the file.
+    pub uri: String,
+    /// The [`content_hash`] of the file that the copy was read from.
+    pub hash: u64,
+}
+
+impl ExternalRef {
+    /// A reference to the file at `uri`, whose contents are `contents`.
+    pub fn new(uri: impl Into<String>, contents: &[u8]) -> Self {
+        Self {
+            uri: uri.into(),
+            hash: content_hash(contents),
+        }
+    }
+
+    pub fn is_url(&self) -> bool {
+        self.uri.contains("://")
+    }
+
+    /// Whether the file, now that it holds `contents`, has changed since the
+    /// copy was read from it.
+    pub fn is_changed(&self, contents: &[u8]) -> bool {
+        content_hash(contents) != self.hash
+    }
+}
+
+/// A hash of the contents of a file, which is the same on every platform and
+/// in every version, unlike that of `std`'s `DefaultHasher`, so that it can be
+/// saved.  This is 64-bit FNV-1a.
+pub fn content_hash(contents: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    contents.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
+// End of File
@@ -0,0 +1,249 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Checks of a whole assembly that are worth making before it is exported or
+//! shared: its formula and net charge, atoms whose valences are not satisfied,
+//! atoms of different components that overlap, and stereocenters that a
+//! mirror has left in the opposite configuration to the one they were built
+//! in.
+//!
+//! None of the formats that molecules are built from record charges, so they
+//! are worked out from the bonds.  An atom with lone pairs (e.g. nitrogen or
+//! oxygen) that has one bond more than its standard valence has donated a
+//! lone pair, as in ammonium, and is a cation.  An atom with an empty orbital
+//! (e.g. boron) that has one bond more has accepted a pair, as in
+//! borohydride, and is an anion.  Any other atom with too many bonds is
+//! reported as overbonded.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+
+use common::ids::AtomSpecifier;
+use molecule::{mirror, spatial::SpatialIndex};
+use periodic_table::Element;
+use ultraviolet::Vec3;
+
+use crate::{Assembly, ComponentId};
+
+/// How close two atoms of different components can be, as a multiple of the
+/// sum of their covalent radii, before they clash.  Atoms that are not bonded
+/// are normally kept further apart than this by their van der Waals radii,
+/// which are roughly twice as large.
+const CLASH_FACTOR: f32 = 1.5;
+
+/// What is wrong with the atoms of an issue.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IssueKind {
+    /// The atom has `missing` fewer bonds than its standard valence.
+    UnpairedValence { missing: u8 },
+    /// The atom has `excess` more bonds than its valence allows, and cannot
+    /// be explained as an ion.
+    Overbonded { excess: u8 },
+    /// The atom has a formal charge, worked out from its bonds.
+    Charged { charge: i32 },
+    /// Two atoms of different components are only `distance` angstroms apart.
+    Clash { distance: f32 },
+    /// The atom is a stereocenter with the opposite configuration to the one
+    /// it was built with, because of a mirror edit or because its component
+    /// is drawn mirrored, but not both.  Like a charge, this is reported
+    /// rather than counted as a fault.
+    InvertedStereocenter,
+}
+
+/// One problem found by [`validate`], with the atoms it concerns.
+#[derive(Clone, Debug)]
+pub struct ValidationIssue {
+    pub kind: IssueKind,
+    pub atoms: Vec<(ComponentId, AtomSpecifier)>,
+}
+
+impl ValidationIssue {
+    /// A one-line description of the issue, naming its atoms.
+    pub fn description(&self, assembly: &Assembly) -> String {
+        let atoms: Vec<String> = self
+            .atoms
+            .iter()
+            .map(|(id, atom)| match assembly.component(*id) {
+                Some(component) => format!("{} of {}", atom, component.name()),
+                None => atom.to_string(),
+            })
+            .collect();
+        let atoms = atoms.join(" and ");
+        match self.kind {
+            IssueKind::UnpairedValence { missing: 1 } => {
+                format!("atom {} has an unpaired valence", atoms)
+            }
+            IssueKind::UnpairedValence { missing } => {
+                format!("atom {} has {} unpaired valences", atoms, missing)
+            }
+            IssueKind::Overbonded { excess } => {
+                format!("atom {} has {} bonds too many", atoms, excess)
+            }
+            IssueKind::Charged { charge } => format!("atom {} has a charge of {:+}", atoms, charge),
+            IssueKind::Clash { distance } => {
+                format!("atoms {} clash, {:.2} Å apart", atoms, distance)
+            }
+            IssueKind::InvertedStereocenter => {
+                format!("atom {} is an inverted stereocenter", atoms)
+            }
+        }
+    }
+}
+
+/// The result of validating an assembly.
+#[derive(Clone, Debug, Default)]
+pub struct ValidationReport {
+    /// The number of atoms of each element.
+    pub formula: BTreeMap<Element, usize>,
+    /// The sum of the formal charges, in elementary charges.
+    pub net_charge: i32,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Whether the assembly is neutral and nothing is wrong with it.
+    pub fn is_clean(&self) -> bool {
+        self.net_charge == 0
+            && self.issues.iter().all(|issue| {
+                matches!(
+                    issue.kind,
+                    IssueKind::Charged { .. } | IssueKind::InvertedStereocenter
+                )
+            })
+    }
+
+    /// The formula in Hill order (carbon, then hydrogen, then the rest
+    /// alphabetically, or all alphabetically if there is no carbon), e.g.
+    /// `C2H6O`.
+    pub fn formula_string(&self) -> String {
+        let mut elements: Vec<(&Element, &usize)> = self.formula.iter().collect();
+        let has_carbon = self.formula.contains_key(&Element::Carbon);
+        elements.sort_by_key(|(element, _)| match element {
+            Element::Carbon => (0, ""),
+            Element::Hydrogen if has_carbon => (1, ""),
+            element => (2, element.symbol()),
+        });
+        elements
+            .into_iter()
+            .map(|(element, count)| match count {
+                1 => element.symbol().to_owned(),
+                count => format!("{}{}", element.symbol(), count),
+            })
+            .collect()
+    }
+}
+
+/// Validates the visible molecules of `assembly`, where they are drawn.
+/// Hidden components are left out.
+pub fn validate(assembly: &Assembly) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    // Every atom, for finding clashes.
+    let mut atoms: Vec<(ComponentId, AtomSpecifier, Element, Vec3)> = Vec::new();
+
+    for (id, molecule, transform) in assembly.visible_molecules() {
+        let graph = &molecule.repr.graph;
+        // A transform with a negative determinant is a reflection, which
+        // inverts every stereocenter of the molecule as it is drawn.
+        let mirrored: HashSet<AtomSpecifier> = if transform.determinant() < 0.0 {
+            mirror::stereocenters(&molecule.repr).into_iter().collect()
+        } else {
+            HashSet::new()
+        };
+        let positions = molecule.repr.dense_positions();
+        for index in graph.node_indices() {
+            let atom = &graph[index];
+            let spec = molecule.repr.spec(index);
+            *report.formula.entry(atom.element).or_default() += 1;
+            if atom.stereo_inverted != mirrored.contains(&spec) {
+                report.issues.push(ValidationIssue {
+                    kind: IssueKind::InvertedStereocenter,
+                    atoms: vec![(id, spec.clone())],
+                });
+            }
+            atoms.push((
+                id,
+                spec.clone(),
+                atom.element,
+                transform.transform_point3(positions[index.index()]),
+            ));
+
+            let Some(valence) = atom.element.standard_valence() else {
+                continue;
+            };
+            let bonds: u32 = graph.edges(index).map(|edge| *edge.weight() as u32).sum();
+            let kind = match bonds.cmp(&(valence as u32)) {
+                Ordering::Equal => continue,
+                Ordering::Less => IssueKind::UnpairedValence {
+                    missing: valence - bonds as u8,
+                },
+                Ordering::Greater => {
+                    let excess = bonds - valence as u32;
+                    match formal_charge(atom.element) {
+                        Some(charge) if excess == 1 => {
+                            report.net_charge += charge;
+                            IssueKind::Charged { charge }
+                        }
+                        _ => IssueKind::Overbonded {
+                            excess: excess.min(u8::MAX as u32) as u8,
+                        },
+                    }
+                }
+            };
+            report.issues.push(ValidationIssue {
+                kind,
+                atoms: vec![(id, spec)],
+            });
+        }
+    }
+
+    report.issues.extend(find_clashes(&atoms));
+    report
+}
+
+// The charge of an atom of `element` with one bond more than its standard
+// valence, or `None` if such an atom is not a plausible ion.
+fn formal_charge(element: Element) -> Option<i32> {
+    use Element::*;
+
+    match element {
+        Nitrogen | Phosphorus | Arsenic | Oxygen | Sulfur | Selenium => Some(1),
+        Boron | Aluminium => Some(-1),
+        _ => None,
+    }
+}
+
+fn find_clashes(atoms: &[(ComponentId, AtomSpecifier, Element, Vec3)]) -> Vec<ValidationIssue> {
+    let Some(largest) = atoms
+        .iter()
+        .map(|(_, _, element, _)| element.covalent_radius())
+        .reduce(f32::max)
+    else {
+        return Vec::new();
+    };
+    let index = SpatialIndex::new(
+        atoms.iter().enumerate().map(|(i, (.., pos))| (i, *pos)),
+        2.0 * largest * CLASH_FACTOR,
+    );
+
+    let mut clashes = Vec::new();
+    for (i, (id, spec, element, pos)) in atoms.iter().enumerate() {
+        let radius = element.covalent_radius();
+        for j in index.within(*pos, (radius + largest) * CLASH_FACTOR) {
+            let (other_id, other_spec, other_element, other_pos) = &atoms[j];
+            if j <= i || other_id == id {
+                continue;
+            }
+            let distance = (*other_pos - *pos).mag();
+            if distance < (radius + other_element.covalent_radius()) * CLASH_FACTOR {
+                clashes.push(ValidationIssue {
+                    kind: IssueKind::Clash { distance },
+                    atoms: vec![(*id, spec.clone()), (*other_id, other_spec.clone())],
+                });
+            }
+        }
+    }
+    clashes
+}
+
+// End of File
@@ -0,0 +1,142 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Procedurally generated stress-test scenes.
+//!
+//! The scenes produced here are built the same way a user would build them:
+//! every atom is created by an [`Edit`] that is inserted into a
+//! [`MoleculeEditor`] and then applied (and relaxed) through the normal edit
+//! pipeline.  The generator is fully deterministic, so the same configuration
+//! always yields the same scene, which makes rendering and relaxation timings
+//! comparable across runs and across builds.
+
+use common::ids::AtomSpecifier;
+use molecule::{
+    edit::{BondedAtom, Edit},
+    MoleculeEditor,
+};
+use periodic_table::Element;
+use scene::{Assembly, Component};
+use ultraviolet::{Mat4, Vec3};
+
+/// The environment variable that, when set to a value like `16x256`, causes
+/// the application to start with a benchmark scene of 16 components with 256
+/// atoms each instead of the demo scene.
+pub const BENCHMARK_ENV_VAR: &str = "ATOMCAD_BENCHMARK";
+
+// The elements cycled through when building each chain, so that the scene
+// exercises more than one entry of the periodic table.
+const ELEMENTS: [Element; 4] = [
+    Element::Carbon,
+    Element::Nitrogen,
+    Element::Oxygen,
+    Element::Silicon,
+];
+
+/// Describes the size and layout of a generated benchmark scene.
+#[derive(Clone, Debug)]
+pub struct BenchmarkConfig {
+    /// The number of components (each holding one molecule) in the scene.
+    pub components: usize,
+    /// The number of atoms in each component's molecule.
+    pub atoms_per_component: usize,
+    /// The distance between neighboring components, in angstroms.
+    pub spacing: f32,
+}
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        Self {
+            components: 8,
+            atoms_per_component: 32,
+            spacing: 10.0,
+        }
+    }
+}
+
+impl BenchmarkConfig {
+    /// Parses a configuration of the form `<components>x<atoms per component>`,
+    /// e.g. `16x256`.  The spacing is left at its default value.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let (components, atoms) = spec.trim().split_once(['x', 'X'])?;
+        let components = components.trim().parse().ok()?;
+        let atoms_per_component = atoms.trim().parse().ok()?;
+
+        if components == 0 || atoms_per_component == 0 {
+            return None;
+        }
+
+        Some(Self {
+            components,
+            atoms_per_component,
+            ..Default::default()
+        })
+    }
+
+    /// Reads the configuration from the [`BENCHMARK_ENV_VAR`] environment
+    /// variable, if it is set and well formed.
+    pub fn from_env() -> Option<Self> {
+        let spec = std::env::var(BENCHMARK_ENV_VAR).ok()?;
+        let config = Self::parse(&spec);
+        if config.is_none() {
+            log::warn!("ignoring malformed {}={:?}", BENCHMARK_ENV_VAR, spec);
+        }
+        config
+    }
+}
+
+/// Builds a single linear chain molecule of `atoms` atoms, one edit at a time.
+fn make_chain(atoms: usize, first_element: usize) -> MoleculeEditor {
+    let element = |i: usize| ELEMENTS[(first_element + i) % ELEMENTS.len()];
+    let mut molecule = MoleculeEditor::from_feature(Edit::RootAtom(element(0)));
+
+    // Edit IDs are handed out sequentially starting from the root atom's edit,
+    // and each edit creates exactly one atom, so the atom created by edit `i`
+    // is always `AtomSpecifier::new(i)`.
+    for i in 1..atoms {
+        molecule.insert_edit(Edit::BondedAtom(BondedAtom {
+            target: AtomSpecifier::new(i - 1),
+            element: element(i),
+        }));
+        molecule.apply_all_edits();
+    }
+
+    molecule
+}
+
+/// Generates a benchmark scene according to `config`.  The components are laid
+/// out on a square grid in the y-z plane; each molecule's chain extends along
+/// the x axis.
+pub fn generate_scene(config: &BenchmarkConfig) -> Assembly {
+    #[cfg(not(target_arch = "wasm32"))]
+    let start = std::time::Instant::now();
+
+    let columns = (config.components as f32).sqrt().ceil().max(1.0) as usize;
+    let components = (0..config.components).map(|i| {
+        let (row, column) = (i / columns, i % columns);
+        let offset = Vec3::new(
+            0.0,
+            row as f32 * config.spacing,
+            column as f32 * config.spacing,
+        );
+
+        let mut molecule = make_chain(config.atoms_per_component, i);
+        molecule.set_name(format!("Benchmark Chain {}", i));
+        Component::from_molecule(molecule, Mat4::from_translation(offset))
+    });
+    let mut assembly = Assembly::from_components(components);
+    assembly.set_name("Benchmark");
+
+    #[cfg(not(target_arch = "wasm32"))]
+    log::info!(
+        "generated benchmark scene ({} components x {} atoms) in {:?}",
+        config.components,
+        config.atoms_per_component,
+        start.elapsed()
+    );
+
+    assembly
+}
+
+// End of File
@@ -12,15 +12,27 @@
 //! the camera's current state into parameters used by the rendering system.
 
 use common::InputEvent;
-use render::{Camera, CameraRepr};
+use instant::Instant;
+use render::{Camera, CameraPose, CameraRepr, Projection};
 use ultraviolet::{projection, Mat4, Vec3};
 use winit::{
     dpi::PhysicalPosition,
-    event::{DeviceEvent, ElementState, MouseButton, MouseScrollDelta, WindowEvent},
+    event::{DeviceEvent, ElementState, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent},
 };
 
 const PI: f32 = std::f32::consts::PI;
 
+// How far in front of and behind the focus the orthographic view reaches, in
+// angstroms.  Unlike the perspective view, it can't extend to infinity.
+const ORTHOGRAPHIC_DEPTH: f32 = 10_000.0;
+
+// How long an animated turn to a new orientation takes, in seconds.
+const TURN_DURATION: f32 = 0.3;
+
+// How quickly a mouse wheel zoom glides to a stop: the time in which it covers
+// all but 1/e of the distance it has left, in seconds.
+const ZOOM_GLIDE_TIME: f32 = 0.06;
+
 // Make sure that the given value is between min and max, inclusive.  This is
 // used to keep the user from rotating beyond the vertical.
 //
@@ -37,19 +49,170 @@ fn clamp(mut x: f32, min: f32, max: f32) -> f32 {
     x
 }
 
+/// The standard views of the focus.  Each looks along an axis, apart from the
+/// isometric view, which looks equally along all three.  The scene's z axis is
+/// up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViewPreset {
+    Front,
+    Back,
+    Left,
+    Right,
+    Top,
+    Bottom,
+    Isometric,
+}
+
+impl ViewPreset {
+    pub const ALL: [ViewPreset; 7] = [
+        ViewPreset::Front,
+        ViewPreset::Back,
+        ViewPreset::Left,
+        ViewPreset::Right,
+        ViewPreset::Top,
+        ViewPreset::Bottom,
+        ViewPreset::Isometric,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ViewPreset::Front => "Front",
+            ViewPreset::Back => "Back",
+            ViewPreset::Left => "Left",
+            ViewPreset::Right => "Right",
+            ViewPreset::Top => "Top",
+            ViewPreset::Bottom => "Bottom",
+            ViewPreset::Isometric => "Isometric",
+        }
+    }
+
+    /// The yaw and pitch of the camera in this view, as taken by
+    /// [`Camera::orbit_to`].  The front view looks along +y, and the top and
+    /// bottom views keep the front of the scene towards the bottom of the
+    /// view.
+    pub fn orientation(self) -> (f32, f32) {
+        match self {
+            ViewPreset::Front => (PI, 0.0),
+            ViewPreset::Back => (0.0, 0.0),
+            ViewPreset::Left => (-PI / 2.0, 0.0),
+            ViewPreset::Right => (PI / 2.0, 0.0),
+            ViewPreset::Top => (PI, PI / 2.0),
+            ViewPreset::Bottom => (PI, -PI / 2.0),
+            // From the front right, above the scene.
+            ViewPreset::Isometric => (3.0 * PI / 4.0, (1.0 / 2f32.sqrt()).atan()),
+        }
+    }
+}
+
+/// The exact steps that the camera can be moved by from the keyboard, so that
+/// a view can be found again by repeating the same keys, e.g. to take
+/// screenshots that line up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraStep {
+    OrbitLeft,
+    OrbitRight,
+    OrbitUp,
+    OrbitDown,
+    DollyIn,
+    DollyOut,
+    /// Moves the focus away from the camera, which stays where it is.
+    PushFocus,
+    /// Moves the focus towards the camera, which stays where it is.
+    PullFocus,
+}
+
+impl CameraStep {
+    /// How far an orbit step turns the camera, in radians.
+    pub const ORBIT_ANGLE: f32 = PI / 12.0;
+
+    /// How much a dolly step changes the distance to the focus.  Dollying in
+    /// divides the distance by this and dollying out multiplies it, so that
+    /// one undoes the other exactly.  Pushing and pulling the focus change
+    /// the distance in the same way.
+    pub const DOLLY_FACTOR: f32 = 1.1;
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CameraStep::OrbitLeft => "orbited left 15°",
+            CameraStep::OrbitRight => "orbited right 15°",
+            CameraStep::OrbitUp => "orbited up 15°",
+            CameraStep::OrbitDown => "orbited down 15°",
+            CameraStep::DollyIn => "dollied in 10%",
+            CameraStep::DollyOut => "dollied out 10%",
+            CameraStep::PushFocus => "pushed the focus away 10%",
+            CameraStep::PullFocus => "pulled the focus in 10%",
+        }
+    }
+
+    /// The pose one step on from `pose`.  The pitch stops short of the
+    /// vertical, as it does when dragging.
+    pub fn apply(self, pose: CameraPose) -> CameraPose {
+        let (yaw, pitch, distance) = (pose.yaw, pose.pitch, pose.distance);
+        let (yaw, pitch, distance) = match self {
+            CameraStep::OrbitLeft => (yaw - Self::ORBIT_ANGLE, pitch, distance),
+            CameraStep::OrbitRight => (yaw + Self::ORBIT_ANGLE, pitch, distance),
+            CameraStep::OrbitUp => (yaw, pitch + Self::ORBIT_ANGLE, distance),
+            CameraStep::OrbitDown => (yaw, pitch - Self::ORBIT_ANGLE, distance),
+            CameraStep::DollyIn => (yaw, pitch, distance / Self::DOLLY_FACTOR),
+            CameraStep::DollyOut => (yaw, pitch, distance * Self::DOLLY_FACTOR),
+            CameraStep::PushFocus => {
+                return pose.with_orbit_radius(distance * Self::DOLLY_FACTOR);
+            }
+            CameraStep::PullFocus => {
+                return pose.with_orbit_radius(distance / Self::DOLLY_FACTOR);
+            }
+        };
+        CameraPose {
+            yaw: yaw.rem_euclid(PI * 2.0),
+            pitch: clamp(pitch, (-PI / 2.0) + 0.001, (PI / 2.0) - 0.001),
+            distance,
+            ..pose
+        }
+    }
+}
+
+// An animated move from one pose to another.
+struct Turn {
+    from: CameraPose,
+    to: CameraPose,
+    start: Instant,
+}
+
+// A zoom from a mouse wheel that is still under way.  Wheels move in coarse
+// notches, so rather than jumping, the camera glides the distance each notch
+// asks for.  Trackpads send their own momentum, so they need no glide.
+struct ZoomGlide {
+    // How much further the distance to the focus is to change, in angstroms.
+    remaining: f32,
+    last_frame: Instant,
+}
+
 /// The arcball camera is the simplest camera type, used in the part editing
 /// view.  It allows the user to rotate the camera around a focus point,
 /// usually the center of the part or assembly being worked on, and zoom
 /// in and out.
+///
+/// Trackpads are handled natively: pinching zooms, two-finger rotation turns
+/// the view about the vertical, and two-finger scrolling zooms with the
+/// momentum that the system gives it.
 pub struct ArcballCamera {
     camera: CameraRepr,
 
     mouse_button_pressed: bool,
+    fov: f32,
+    aspect: f32,
+    near: f32,
+    projection: Projection,
     focus: Vec3,
     yaw: f32,
     pitch: f32,
     distance: f32,
     speed: f32,
+    // The number of physical pixels to each logical pixel, so that trackpads
+    // zoom as far for the same gesture on any display.
+    scale_factor: f32,
+    turn: Option<Turn>,
+    glide: Option<ZoomGlide>,
 }
 
 impl ArcballCamera {
@@ -57,11 +220,18 @@ impl ArcballCamera {
         Self {
             camera: CameraRepr::default(),
             mouse_button_pressed: false,
+            fov: PI / 2.0,
+            aspect: 1.0,
+            near: 0.1,
+            projection: Projection::default(),
             focus,
             yaw: 0.0,
             pitch: 0.0,
             distance,
             speed,
+            scale_factor: 1.0,
+            turn: None,
+            glide: None,
         }
     }
 
@@ -72,31 +242,154 @@ impl ArcballCamera {
     fn add_pitch(&mut self, dpitch: f32) {
         self.pitch = clamp(self.pitch + dpitch, (-PI / 2.0) + 0.001, (PI / 2.0) - 0.001);
     }
+
+    // Moves the camera along its current turn, if any.  Returns whether it
+    // moved.
+    fn advance_turn(&mut self) -> bool {
+        let Some(turn) = &self.turn else {
+            return false;
+        };
+        let t = (turn.start.elapsed().as_secs_f32() / TURN_DURATION).min(1.0);
+        // Ease in and out.
+        let eased = t * t * (3.0 - 2.0 * t);
+        let (from, to) = (turn.from, turn.to);
+
+        self.focus = from.focus + eased * (to.focus - from.focus);
+        self.distance = from.distance + eased * (to.distance - from.distance);
+        self.yaw = (from.yaw + eased * (to.yaw - from.yaw)) % (PI * 2.0);
+        self.pitch = from.pitch;
+        self.add_pitch(eased * (to.pitch - from.pitch));
+        if t >= 1.0 {
+            self.turn = None;
+        }
+        true
+    }
+
+    // Moves the camera along its zoom glide, if any.  Returns whether it
+    // moved.
+    fn advance_glide(&mut self) -> bool {
+        let Some(glide) = &mut self.glide else {
+            return false;
+        };
+        let now = Instant::now();
+        let elapsed = now.duration_since(glide.last_frame).as_secs_f32();
+        let step = glide.remaining * (1.0 - (-elapsed / ZOOM_GLIDE_TIME).exp());
+        glide.remaining -= step;
+        glide.last_frame = now;
+        self.distance = (self.distance + step).max(0.001);
+        if glide.remaining.abs() < 0.001 {
+            self.glide = None;
+        }
+        true
+    }
+
+    // Adds `change` to the distance to the focus, gliding there from wherever
+    // the current glide has got to.
+    fn glide_by(&mut self, change: f32) {
+        match &mut self.glide {
+            Some(glide) => glide.remaining += change,
+            None => {
+                self.glide = Some(ZoomGlide {
+                    remaining: change,
+                    last_frame: Instant::now(),
+                })
+            }
+        }
+    }
+
+    // Stops the camera moving by itself, as when the user takes hold of it.
+    fn stop(&mut self) {
+        self.turn = None;
+        self.glide = None;
+    }
+
+    fn projection_matrix(&self) -> Mat4 {
+        match self.projection {
+            Projection::Perspective => projection::perspective_reversed_infinite_z_wgpu_dx_gl(
+                self.fov,
+                self.aspect,
+                self.near,
+            ),
+            // The orthographic view is sized to show what the perspective view
+            // shows at the focus, so that switching between them keeps the
+            // focus the same size and zooming works the same way.  Passing the
+            // far plane as the near one reverses the depth, as in the
+            // perspective view.
+            Projection::Orthographic => {
+                let top = self.distance * (self.fov / 2.0).tan();
+                let right = top * self.aspect;
+                projection::orthographic_wgpu_dx(
+                    -right,
+                    right,
+                    -top,
+                    top,
+                    self.distance + ORTHOGRAPHIC_DEPTH,
+                    self.distance - ORTHOGRAPHIC_DEPTH,
+                )
+            }
+        }
+    }
 }
 
 impl Camera for ArcballCamera {
     fn resize(&mut self, aspect: f32, fov: f32, near: f32) {
-        self.camera.projection =
-            projection::perspective_reversed_infinite_z_wgpu_dx_gl(fov, aspect, near);
+        self.fov = fov;
+        self.aspect = aspect;
+        self.near = near;
+    }
+
+    fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
     }
 
     fn update(&mut self, event: InputEvent) -> bool {
         match event {
             InputEvent::Window(event) => match event {
-                WindowEvent::MouseWheel { delta, .. } => {
+                WindowEvent::MouseWheel { delta, phase, .. } => {
                     match delta {
                         MouseScrollDelta::LineDelta(_, delta) => {
-                            self.distance = (self.distance - delta * self.speed * 10.0).max(0.001);
+                            self.turn = None;
+                            self.glide_by(-delta * self.speed * 10.0);
                         }
+                        // Pixel deltas come from trackpads, which keep
+                        // scrolling after the fingers lift for as long as the
+                        // system's momentum lasts.
                         MouseScrollDelta::PixelDelta(PhysicalPosition { y, .. }) => {
-                            self.distance = (self.distance - y as f32 * self.speed).max(0.001);
+                            if phase == TouchPhase::Started {
+                                self.stop();
+                            }
+                            let y = y as f32 / self.scale_factor;
+                            self.distance = (self.distance - y * self.speed).max(0.001);
                         }
                     }
                     true
                 }
+                // The delta is how much the pinch has grown the view since the
+                // last event, e.g. 0.1 to show it a tenth larger.
+                WindowEvent::TouchpadMagnify { delta, phase, .. } => {
+                    if phase == TouchPhase::Started {
+                        self.stop();
+                    }
+                    let growth = (1.0 + delta as f32).max(0.1);
+                    self.distance = (self.distance / growth).max(0.001);
+                    true
+                }
+                // The delta is in degrees, anticlockwise.  Turning the camera
+                // the other way makes the scene follow the fingers round.
+                WindowEvent::TouchpadRotate { delta, phase, .. } => {
+                    if phase == TouchPhase::Started {
+                        self.stop();
+                    }
+                    self.add_yaw(delta.to_radians());
+                    true
+                }
                 WindowEvent::MouseInput { state, button, .. } => {
                     if button == MouseButton::Left {
                         self.mouse_button_pressed = state == ElementState::Pressed;
+                        // Grabbing the camera stops it moving by itself.
+                        if self.mouse_button_pressed {
+                            self.stop();
+                        }
                     }
                     false
                 }
@@ -114,11 +407,17 @@ impl Camera for ArcballCamera {
                 }
                 _ => false,
             },
-            InputEvent::BeginningFrame => false,
+            InputEvent::BeginningFrame => {
+                let turned = self.advance_turn();
+                self.advance_glide() || turned
+            }
         }
     }
 
     fn finalize(&mut self) {
+        // The orthographic projection depends on the distance to the focus, so
+        // it is rebuilt along with the view.
+        self.camera.projection = self.projection_matrix();
         self.camera.view = Mat4::look_at(self.position(), self.focus, Vec3::unit_z());
         self.camera.projection_view = self.camera.projection * self.camera.view;
     }
@@ -128,13 +427,78 @@ impl Camera for ArcballCamera {
     }
 
     fn position(&self) -> Vec3 {
-        self.focus
-            + self.distance
-                * Vec3::new(
-                    self.yaw.sin() * self.pitch.cos(),
-                    self.yaw.cos() * self.pitch.cos(),
-                    self.pitch.sin(),
-                )
+        self.pose().eye()
+    }
+
+    fn frame(&mut self, focus: Vec3, radius: f32) {
+        self.glide = None;
+        self.focus = focus;
+        // Back off far enough that the sphere is tangent to the view cone.
+        self.distance = (radius / (self.fov / 2.0).sin()).max(0.001);
+    }
+
+    fn orbit_to(&mut self, yaw: f32, pitch: f32, animate: bool) {
+        let pose = CameraPose {
+            yaw,
+            pitch,
+            ..self.pose()
+        };
+        self.set_pose(pose, animate);
+    }
+
+    fn pose(&self) -> CameraPose {
+        CameraPose {
+            focus: self.focus,
+            yaw: self.yaw,
+            pitch: self.pitch,
+            distance: self.distance,
+        }
+    }
+
+    fn destination(&self) -> CameraPose {
+        self.turn
+            .as_ref()
+            .map_or_else(|| self.pose(), |turn| turn.to)
+    }
+
+    fn set_pose(&mut self, pose: CameraPose, animate: bool) {
+        self.glide = None;
+        if !animate {
+            self.turn = None;
+            self.focus = pose.focus;
+            self.distance = pose.distance.max(0.001);
+            self.yaw = pose.yaw % (PI * 2.0);
+            self.pitch = 0.0;
+            self.add_pitch(pose.pitch);
+            return;
+        }
+
+        // Turn the short way around.
+        let mut yaw_change = (pose.yaw - self.yaw).rem_euclid(PI * 2.0);
+        if yaw_change > PI {
+            yaw_change -= PI * 2.0;
+        }
+        self.turn = Some(Turn {
+            from: self.pose(),
+            to: CameraPose {
+                yaw: self.yaw + yaw_change,
+                distance: pose.distance.max(0.001),
+                ..pose
+            },
+            start: Instant::now(),
+        });
+    }
+
+    fn is_moving(&self) -> bool {
+        self.turn.is_some() || self.glide.is_some()
+    }
+
+    fn is_orbiting(&self) -> bool {
+        self.mouse_button_pressed || self.turn.is_some()
+    }
+
+    fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor as f32;
     }
 }
 
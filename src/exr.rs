@@ -0,0 +1,129 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Writing renders as OpenEXR images, so that they can be composited and
+//! relit in other tools.  Only what those tools need is written: a single
+//! part of uncompressed 32-bit float scanlines, with any number of named
+//! channels.  Layers are channels whose names share a prefix, such as `N.X`,
+//! `N.Y` and `N.Z` for the normals.
+
+use std::io::{self, Write};
+
+// The magic number that starts every OpenEXR file, and the version: 2, for a
+// single-part scanline image.
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+
+// The codes of the formats the header refers to.
+const PIXEL_TYPE_FLOAT: i32 = 2;
+const NO_COMPRESSION: u8 = 0;
+const INCREASING_Y: u8 = 0;
+
+/// A channel of an image, with one value per pixel, row by row from the top.
+pub struct Channel<'a> {
+    pub name: &'a str,
+    pub values: &'a [f32],
+}
+
+/// Writes the image of `width` by `height` pixels with the given channels.
+/// Every channel must have a value for each pixel.
+pub fn write(
+    out: &mut impl Write,
+    width: u32,
+    height: u32,
+    channels: &[Channel],
+) -> io::Result<()> {
+    let pixels = width as usize * height as usize;
+    if channels
+        .iter()
+        .any(|channel| channel.values.len() != pixels)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "a channel does not have a value for every pixel",
+        ));
+    }
+    // Readers expect the channels in alphabetical order, both in the header
+    // and in the scanlines.
+    let mut channels: Vec<&Channel> = channels.iter().collect();
+    channels.sort_by_key(|channel| channel.name);
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&VERSION);
+
+    let mut channel_list = Vec::new();
+    for channel in &channels {
+        channel_list.extend_from_slice(channel.name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+        // Not perceptually linear, then three reserved bytes.
+        channel_list.extend_from_slice(&[0, 0, 0, 0]);
+        // Sampled at every pixel, across and down.
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channel_list.push(0);
+    attribute(&mut header, "channels", "chlist", &channel_list);
+
+    attribute(&mut header, "compression", "compression", &[NO_COMPRESSION]);
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|coordinate| coordinate.to_le_bytes())
+        .collect();
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[INCREASING_Y]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    // Each scanline is its own chunk, found through a table of offsets from
+    // the start of the file.
+    let line_bytes = width as usize * channels.len() * 4;
+    let chunk_bytes = 8 + line_bytes;
+    let first_chunk = header.len() + height as usize * 8;
+    out.write_all(&header)?;
+    for y in 0..height as usize {
+        out.write_all(&((first_chunk + y * chunk_bytes) as u64).to_le_bytes())?;
+    }
+
+    let mut line = Vec::with_capacity(line_bytes);
+    for y in 0..height as usize {
+        line.clear();
+        let row = y * width as usize..(y + 1) * width as usize;
+        for channel in &channels {
+            for value in &channel.values[row.clone()] {
+                line.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        out.write_all(&(y as i32).to_le_bytes())?;
+        out.write_all(&(line_bytes as i32).to_le_bytes())?;
+        out.write_all(&line)?;
+    }
+    Ok(())
+}
+
+// Appends an attribute of the header.
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+// End of File
@@ -23,7 +23,13 @@
 //! * A basic 3D view, with a camera that can be controlled using the mouse
 //!   and keyboard.
 //!
-//! * A basic menu bar, with a File menu that can be used to open PDB files.
+//! * A basic menu bar, and a file dialog (Ctrl/Cmd+O) that opens PDB files,
+//!   molfiles and Z-matrices into the scene.
+//!
+//! * A toolbar of interactive tools, cycled through with Tab, among them an
+//!   atom placement tool (Alt+H), which adds atoms of an element chosen with
+//!   the number keys wherever the mouse is clicked, and a bond tool (Alt+J),
+//!   which bonds two atoms clicked in turn.
 //!
 //! As is common with binary applications, the main entry point is in the
 //! `main.rs` file, and the rest of the application is implemented in this
@@ -32,12 +38,22 @@
 //! [wgpu]: https://crates.io/crates/wgpu
 //! [rui]: https://crates.io/crates/rui
 
+/// Procedurally generated stress-test scenes, used to measure rendering and
+/// relaxation performance reproducibly.
+pub mod benchmark;
 /// The API for controlling the camera in the 3D view, and having it respond
 /// to user events.
 pub mod camera;
+/// Writing renders as OpenEXR images, with depth and normals for compositing.
+pub mod exr;
 /// A platform-independent abstraction over the windowing system's interface
 /// for menus and menubars.  Used to setup the application menubar on startup.
 pub mod menubar;
+/// Rendering thumbnails of molecules from the command line, without a window.
+pub mod thumbnail;
+/// The state and behaviour of the panels shown in the overlay on top of the 3D
+/// view.
+pub mod ui;
 
 // This module is not public.  It is a common abstraction over the various
 // platform-specific APIs.  For example, `platform::menubar` exposes an API
@@ -58,22 +74,187 @@ pub const APP_NAME: &str = "atomCAD";
 pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const APP_LICENSE: &str = env!("CARGO_PKG_LICENSE");
 
-use camera::ArcballCamera;
-use common::InputEvent;
+/// The environment variable naming a saved molecule to open in review mode,
+/// with editing disabled.  Comments made while reviewing are saved next to it,
+/// with `.comments.json` appended to its name.
+pub const REVIEW_ENV_VAR: &str = "ATOMCAD_REVIEW";
+
+/// The environment variable naming a build script to open as a new molecule.
+/// See [`molecule::build_script`] for the format.  Files ending in `.zmat` are
+/// read as Z-matrices instead (see [`molecule::zmatrix`]), PDB files and
+/// molfiles are imported, and saved molecules (`.json`) are loaded.
+pub const BUILD_ENV_VAR: &str = "ATOMCAD_BUILD";
+
+/// The environment variable naming a saved project to open.  The files that
+/// its linked components refer to are read again, and those that have changed
+/// are offered to update the components with.
+pub const PROJECT_ENV_VAR: &str = "ATOMCAD_PROJECT";
+
+/// The environment variable choosing the color space that frames are encoded
+/// in: "srgb" (the default) or "display-p3", for wide-gamut displays that the
+/// platform does not color-manage.  See [`render::OutputColorSpace`].
+pub const COLOR_SPACE_ENV_VAR: &str = "ATOMCAD_COLOR_SPACE";
+
+/// The environment variable overriding the limits on how many atoms are drawn,
+/// as comma-separated `name=count` pairs such as `points=20000000`.  See
+/// [`render::AtomLimits::parse`] for the names.
+pub const ATOM_LIMITS_ENV_VAR: &str = "ATOMCAD_ATOM_LIMITS";
+
+/// The environment variable turning on the depth pre-pass when set to "1",
+/// which speeds up drawing dense scenes such as large lattices.  F10 turns it
+/// on and off while running.  See [`render::RenderOptions::depth_prepass`].
+pub const DEPTH_PREPASS_ENV_VAR: &str = "ATOMCAD_DEPTH_PREPASS";
+
+/// The environment variable turning on the culling of molecules drawn too
+/// small to make out, as `mode:pixels` such as `skip:3`, or empty for the
+/// defaults.  F11 turns it on and off while running.  See
+/// [`render::ScreenCulling::parse`].
+pub const CULLING_ENV_VAR: &str = "ATOMCAD_CULLING";
+
+/// The environment variable that, when set to "1", notes where each atom came
+/// from in selections exported with Ctrl+E: the edit that created it, and the
+/// line of the file it was read from or the parameters it was made with.  See
+/// [`molecule::provenance`] and [`molecule::export::write_annotated`].
+pub const EXPORT_PROVENANCE_ENV_VAR: &str = "ATOMCAD_EXPORT_PROVENANCE";
+
+/// The environment variable starting in safe mode when set to "1", as does
+/// passing [`SAFE_MODE_FLAG`] or holding Shift while the window opens.  Safe
+/// mode is for drivers that cannot cope with the usual renderer: it asks for
+/// the software fallback adapter where there is one, with only the limits that
+/// every adapter supports, and leaves out antialiasing, the depth pre-pass and
+/// culling, so that projects can still be opened and exported.
+pub const SAFE_MODE_ENV_VAR: &str = "ATOMCAD_SAFE_MODE";
+
+/// The command-line flag starting in safe mode.  See [`SAFE_MODE_ENV_VAR`].
+pub const SAFE_MODE_FLAG: &str = "--safe-mode";
+
+// How many pixels a trackpad scrolls for each line that a wheel would, when
+// tools count scrolling in lines.
+const PIXELS_PER_LINE: f64 = 40.0;
+
+// Where the library of composite edits is kept between sessions.
+const COMPOSITE_LIBRARY_PATH: &str = "composites.json";
+
+// The most cells out from a periodic molecule that its images can be drawn.
+const MAX_PERIODIC_IMAGES: u32 = 2;
+
+// The kinds of file that the file dialog offers to open, by extension.
+const OPEN_FILTERS: &[(&str, &[&str])] = &[
+    ("Molecules", &["pdb", "ent", "sdf", "mol", "zmat"]),
+    ("PDB files", &["pdb", "ent"]),
+    ("Molfiles", &["sdf", "mol"]),
+    ("Z-matrices", &["zmat"]),
+];
+
+// The kind of file that projects are saved as.
+const PROJECT_FILTERS: &[(&str, &[&str])] = &[("Projects", &["atomcad"])];
+
+// How much of a file a background import reads at a time, between checks
+// for being cancelled.
+const IMPORT_CHUNK_SIZE: usize = 1 << 20;
+
+// How often the progress of a background import is checked.
+const IMPORT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+// The kinds of file that the selection can be exported as, by extension.
+const EXPORT_FILTERS: &[(&str, &[&str])] = &[
+    ("PDB files", &["pdb"]),
+    ("XYZ files", &["xyz"]),
+    ("Molfiles", &["sdf"]),
+    ("atomCAD fragments", &["json"]),
+];
+
+// How far each press of Alt+[ or Alt+] turns the measured dihedral, in
+// degrees.
+const TORSION_STEP: f32 = 15.0;
+
+// How far Alt and the arrow keys move the selected atoms, in angstroms.
+const NUDGE_DISTANCE: f32 = 0.5;
+
+// How far Alt+Shift and the left and right arrow keys turn the selected
+// atoms, in degrees.
+const NUDGE_ANGLE: f32 = 15.0;
+
+// The partial charge, in elementary charges, at which coloring by charge
+// reaches full blue (negative) or red (positive).
+const CHARGE_RANGE: f32 = 0.5;
+
+// How far a bond has to be stretched or compressed, as a fraction of its
+// ideal length, for its atoms to be drawn in full red or blue.
+const STRAIN_RANGE: f32 = 0.1;
+
+// Bonds stretched or compressed by more than this fraction of their ideal
+// length are reported when the strain overlay is shown.
+const STRAIN_WARNING: f32 = 0.05;
+
+use benchmark::BenchmarkConfig;
+use camera::{ArcballCamera, CameraStep, ViewPreset};
+use common::{ids::AtomSpecifier, InputEvent, Plane};
+use menubar::AppCommand;
 use molecule::{
-    edit::{Edit, PdbData},
-    MoleculeEditor,
+    bond_orders::BondOrderAssignment,
+    build_script,
+    composite::CombineError,
+    dynamics::ShakeOptions,
+    edit::{Edit, EditContext, PdbData, PdbModel, Pin, TransformAtoms},
+    export::{self, ExportFormat},
+    mirror::Mirror,
+    provenance, sdf, strain,
+    torsion::{self, SetTorsion},
+    zmatrix::ZMatrix,
+    AtomProperty, HydrogenDisplay, MoleculeEditor,
+};
+use render::{
+    AtomColoring, AtomLimits, FrameCapture, GlobalRenderResources, OutputColorSpace, Projection,
+    RenderCamera, RenderOptions, Renderer, ScreenCulling,
+};
+use scene::{
+    Assembly, AssemblyCommand, CommandError, Component, ComponentId, Drive, ExternalRef,
+    ProjectHistory,
 };
-use render::{GlobalRenderResources, Interactions, RenderOptions, Renderer};
-use scene::{Assembly, Component};
 
 use std::rc::Rc;
-use ultraviolet::{Mat4, Vec3};
+use ui::{
+    clipboard::{Fragment, PasteError},
+    command_log::{CommandLog, RerunError},
+    compare::CompareView,
+    composites::ApplyCompositeError,
+    configurations::EXPLODE_STEP,
+    diagnostics::DiagnosticRow,
+    documents::{CloseError, Document},
+    edit_history::{EditHistory, EditTreeEntry},
+    embed::EmbeddedViewer,
+    frame_capture,
+    import::{ImportDialog, ImportJob, ImportProgress, ImportStage},
+    inspector::{Inspector, InspectorError},
+    lattice::LatticePanel,
+    layout,
+    links::LinkedFiles,
+    overlay::{self, Hit},
+    parts::PartsOffer,
+    passivation::PassivationPanel,
+    patterns::PatternShape,
+    polymer::PolymerPanel,
+    radial_menu::RadialItem,
+    review::ReviewMode,
+    search::SearchBox,
+    selection::{Selection, DEFAULT_PROXIMITY},
+    status,
+    timeline::{Timeline, TIMELINE_HEIGHT},
+    tools::{Pointer, Tool, ToolContext, ToolKind},
+    turntable::IdleTurntable,
+    validation::ValidationPanel,
+    Panels,
+};
+use ultraviolet::{Bivec3, Mat4, Rotor3, Vec3};
 use winit::{
     dpi::PhysicalPosition,
-    event::{ElementState, Event, StartCause, WindowEvent},
+    event::{
+        ElementState, Event, KeyEvent, MouseButton, MouseScrollDelta, StartCause, TouchPhase,
+        WindowEvent,
+    },
     event_loop::{ControlFlow, EventLoop, EventLoopBuilder},
-    keyboard::KeyCode,
+    keyboard::{KeyCode, ModifiersState},
     window::{Window, WindowBuilder},
 };
 
@@ -82,6 +263,10 @@ fn make_pdb_demo_scene() -> MoleculeEditor {
     MoleculeEditor::from_feature(Edit::PdbImport(PdbData {
         name: "Neon Pump".into(),
         contents: include_str!("../assets/neon_pump_imm.pdb").into(),
+        residue_templates: true,
+        model: PdbModel::First,
+        conect_bonds: true,
+        infer_bonds: true,
     }))
 }
 
@@ -99,27 +284,90 @@ fn make_salt_demo_scene() -> MoleculeEditor {
     molecule
 }
 
+// The color space named by `COLOR_SPACE_ENV_VAR`, or sRGB.
+fn output_color_space() -> OutputColorSpace {
+    let Ok(name) = std::env::var(COLOR_SPACE_ENV_VAR) else {
+        return OutputColorSpace::Srgb;
+    };
+    OutputColorSpace::from_name(&name).unwrap_or_else(|| {
+        log::warn!("ignoring unknown {}={:?}", COLOR_SPACE_ENV_VAR, name);
+        OutputColorSpace::Srgb
+    })
+}
+
+// The atom limits given by `ATOM_LIMITS_ENV_VAR`, or the defaults.
+fn atom_limits() -> AtomLimits {
+    let Ok(spec) = std::env::var(ATOM_LIMITS_ENV_VAR) else {
+        return AtomLimits::default();
+    };
+    AtomLimits::parse(&spec).unwrap_or_else(|err| {
+        log::warn!(
+            "ignoring malformed {}={:?}: {}",
+            ATOM_LIMITS_ENV_VAR,
+            spec,
+            err
+        );
+        AtomLimits::default()
+    })
+}
+
+// The culling given by `CULLING_ENV_VAR`, or `None` if it is not set.
+fn screen_culling() -> Option<ScreenCulling> {
+    let spec = std::env::var(CULLING_ENV_VAR).ok()?;
+    Some(ScreenCulling::parse(&spec).unwrap_or_else(|err| {
+        log::warn!("ignoring malformed {}={:?}: {}", CULLING_ENV_VAR, spec, err);
+        ScreenCulling::default()
+    }))
+}
+
+// Whether to start in safe mode, as asked for by `SAFE_MODE_FLAG`,
+// `SAFE_MODE_ENV_VAR` or by holding Shift.  Not every platform reports the
+// modifiers before the window has focus, so Shift is not always seen.
+fn safe_mode(modifiers: &ModifiersState) -> bool {
+    std::env::args().any(|arg| arg == SAFE_MODE_FLAG)
+        || std::env::var(SAFE_MODE_ENV_VAR).is_ok_and(|value| value == "1")
+        || modifiers.shift_key()
+}
+
 async fn resume_renderer(
     window: &Window,
-) -> (Renderer, Rc<GlobalRenderResources>, Assembly, Interactions) {
+    safe_mode: bool,
+) -> (Renderer, Rc<GlobalRenderResources>, Assembly) {
+    if safe_mode {
+        log::info!(
+            "starting in safe mode, with the fallback adapter, conservative limits and no \
+             antialiasing, depth pre-pass or culling"
+        );
+    }
     let (renderer, gpu_resources) = Renderer::new(
         window,
         RenderOptions {
-            fxaa: Some(()), // placeholder
-            attempt_gpu_driven: true,
+            fxaa: if safe_mode { None } else { Some(()) },
+            attempt_gpu_driven: !safe_mode,
+            background: render::DEFAULT_BACKGROUND,
+            fog: None,
+            color_scheme: Default::default(),
+            coloring: AtomColoring::Element,
+            output_color_space: output_color_space(),
+            atom_limits: atom_limits(),
+            depth_prepass: !safe_mode
+                && std::env::var(DEPTH_PREPASS_ENV_VAR).is_ok_and(|value| value == "1"),
+            culling: if safe_mode { None } else { screen_culling() },
+            conservative_limits: safe_mode,
         },
     )
     .await;
 
-    let molecule = make_pdb_demo_scene();
-
-    let assembly = Assembly::from_components([Component::from_molecule(molecule, Mat4::default())]);
-    let interactions = Interactions::default();
-
-    (renderer, gpu_resources, assembly, interactions)
+    let assembly = match BenchmarkConfig::from_env() {
+        Some(config) => benchmark::generate_scene(&config),
+        None => {
+            let molecule = make_pdb_demo_scene();
+            Assembly::from_components([Component::from_molecule(molecule, Mat4::default())])
+        }
+    };
+    (renderer, gpu_resources, assembly)
 }
 
-#[cfg_attr(feature = "cargo-clippy", allow(clippy::too_many_arguments))]
 fn handle_event(
     event: Event<()>,
     control_flow: &mut ControlFlow,
@@ -127,9 +375,11 @@ fn handle_event(
     renderer: &mut Option<Renderer>,
     gpu_resources: &mut Option<Rc<GlobalRenderResources>>,
     world: &mut Option<Assembly>,
-    interactions: &mut Option<Interactions>,
-    cursor_pos: &PhysicalPosition<f64>,
+    panels: &mut Panels,
 ) {
+    // A copy, so that the panels can be changed while it is read.
+    let input = panels.input;
+    let (cursor_pos, modifiers) = (&input.cursor_pos, &input.modifiers);
     match event {
         Event::NewEvents(StartCause::Init) => {
             // Will be called once when the event loop starts.
@@ -142,6 +392,21 @@ fn handle_event(
                 renderer.resize(new_size);
             }
         }
+        // Moving the window to a display with a different density changes
+        // how many physical pixels it covers, without necessarily resizing
+        // it, so the render targets are remade here too.
+        Event::WindowEvent {
+            event:
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                },
+            ..
+        } => {
+            if let Some(renderer) = renderer {
+                renderer.rescale(scale_factor, *new_inner_size);
+            }
+        }
         Event::MainEventsCleared => {
             // The event queue is empty, so we can safely redraw the window.
             if window.is_some() {
@@ -161,20 +426,87 @@ fn handle_event(
                             (height * scale_factor) as u32,
                         );
                         window.set_inner_size(new_size);
+                        // Zooming the page changes the scale factor.
                         if let Some(renderer) = renderer {
-                            renderer.resize(new_size);
+                            renderer.rescale(scale_factor, new_size);
                         }
                         Some(())
                     })
                 })();
                 if let Some(renderer) = renderer {
                     if let Some(world) = world {
-                        if let Some(_interactions) = interactions {
-                            if let Some(gpu_resources) = gpu_resources {
+                        if panels.animation.is_playing() {
+                            // Keep drawing frames rather than waiting for input.
+                            panels.animation.advance(world);
+                            *control_flow = ControlFlow::Poll;
+                        }
+                        if renderer.camera().is_moving() {
+                            *control_flow = ControlFlow::Poll;
+                        }
+                        // Clear the last report once it has been up long enough.
+                        if let Some(wait) = status::time_until_cleared() {
+                            wake_after(control_flow, wait);
+                        }
+                        if panels.turntable.advance(renderer.camera()) {
+                            *control_flow = ControlFlow::Poll;
+                        } else if let Some(wait) = panels.turntable.time_until_turning() {
+                            wake_after(control_flow, wait);
+                        }
+                        // Keep the statistics panel current, drawing it again
+                        // when they are due to be recomputed.
+                        panels.statistics.refresh(world, renderer);
+                        if let Some(wait) = panels.statistics.time_until_refresh() {
+                            wake_after(control_flow, wait);
+                        }
+                        if let Some(gpu_resources) = gpu_resources {
+                            panels.timeline.update(world);
+                            if panels.compare.is_open() {
+                                panels.compare.synchronize_buffers(gpu_resources);
+                                if let Some(window) = window.as_ref() {
+                                    let overlay = overlay::draw(panels, world, window, renderer);
+                                    renderer.set_overlay(overlay);
+                                }
+                                panels.compare.render(renderer);
+                            } else {
+                                panels.orbit_marker.update(renderer.camera());
                                 world.synchronize_buffers(gpu_resources);
+                                panels
+                                    .tools
+                                    .active_tool()
+                                    .synchronize_buffers(gpu_resources);
+                                panels.orbit_marker.synchronize_buffers(gpu_resources);
+                                if let Some(window) = window.as_ref() {
+                                    hover(panels, window, renderer, world, cursor_pos);
+                                    let overlay = overlay::draw(panels, world, window, renderer);
+                                    renderer.set_overlay(overlay);
+                                }
+                                renderer.set_interactions(panels.hover.interactions(world));
+                                let (mut atoms, mut transforms, mut tints) =
+                                    world.collect_atoms_and_transforms();
+                                for (atom, transform, tint) in panels
+                                    .tools
+                                    .render_overlay()
+                                    .into_iter()
+                                    .chain(panels.orbit_marker.render_overlay())
+                                {
+                                    atoms.push(atom);
+                                    transforms.push(transform);
+                                    tints.push(tint);
+                                }
+                                renderer.render(atoms, transforms, tints);
                             }
-                            let (atoms, transforms) = world.collect_atoms_and_transforms();
-                            renderer.render(atoms, transforms);
+                            if let Some(capture) = renderer.take_capture() {
+                                save_frame_capture(panels, &capture);
+                            }
+                        }
+                        // Keep the molecules' changes in order with those to
+                        // the assembly, so that they are undone in turn.
+                        panels.history.sync(world);
+                        panels.command_log.sync(world);
+                        // Warn about edits as they fail; the problems panel
+                        // lists all of them.
+                        for problem in panels.diagnostics.refresh(world) {
+                            log::warn!("{}", problem.description);
                         }
                     }
                 }
@@ -184,7 +516,11 @@ fn handle_event(
             event: WindowEvent::CloseRequested,
             ..
         } => {
-            // The user has requested to close the window.
+            // The user has requested to close the window.  Keep the view
+            // in the design so that it is saved with it.
+            if let (Some(renderer), Some(world)) = (renderer.as_mut(), world.as_mut()) {
+                layout::remember(panels, renderer.camera(), world);
+            }
             // Drop the window to fire the `Destroyed` event.
             *window = None;
         }
@@ -202,166 +538,3706 @@ fn handle_event(
         }
         Event::WindowEvent { event, .. } => {
             if let Some(renderer) = renderer {
+                // The embedded viewer only lets the camera and the measurement
+                // tool see input.
+                if !panels.embed.allows(&event, modifiers) {
+                    renderer.camera().update(InputEvent::Window(event));
+                    return;
+                }
                 match event {
                     WindowEvent::KeyboardInput { event: key, .. } => {
-                        if key.physical_key == KeyCode::Space && key.state == ElementState::Released
-                        {
-                            if let Some(window) = window {
-                                match renderer
-                                    .camera()
-                                    .get_ray_from(cursor_pos, &window.inner_size())
-                                {
-                                    Some((ray_origin, ray_direction)) => {
-                                        world.as_mut().unwrap().walk_mut(|molecule, _| {
-                                            if let Some(hit) =
-                                                molecule.repr.get_ray_hit(ray_origin, ray_direction)
-                                            {
-                                                println!("Atom {:?} clicked!", hit);
-                                                // molecule.push_feature(AtomFeature {
-                                                //     target: hit,
-                                                //     element: periodic_table::Element::Carbon,
-                                                // });
-                                                // molecule.apply_all_features();
-                                                // molecule.reupload_atoms(
-                                                //     gpu_resources.as_ref().unwrap(),
-                                                // );
-                                            }
-                                        });
+                        // While the search box is open it receives all typing.
+                        if panels.search.is_open() {
+                            if key.state == ElementState::Pressed {
+                                if let Some(world) = world {
+                                    handle_search_key(&mut panels.search, &key, world, renderer);
+                                }
+                            }
+                            return;
+                        }
+                        // So does the polymer builder's sequence.
+                        if panels.polymer.is_open() {
+                            if key.state == ElementState::Pressed {
+                                if let Some(world) = world {
+                                    handle_polymer_key(
+                                        &mut panels.polymer,
+                                        &mut panels.history,
+                                        &key,
+                                        renderer,
+                                        world,
+                                    );
+                                }
+                            }
+                            return;
+                        }
+                        // So does the outliner.
+                        if panels.outliner.is_open() {
+                            if key.state == ElementState::Pressed {
+                                if let Some(world) = world {
+                                    handle_outliner_key(panels, &key, *modifiers, world);
+                                }
+                            }
+                            return;
+                        }
+                        // So does the comment being written, if any.
+                        if panels.review.is_drafting() {
+                            if key.state == ElementState::Pressed {
+                                handle_comment_key(&mut panels.review, &key);
+                            }
+                            return;
+                        }
+                        if key.state != ElementState::Released {
+                            return;
+                        }
+                        // The active tool has the first say over keys.
+                        if let (Some(window), Some(world)) = (window.as_ref(), world.as_mut()) {
+                            if use_active_tool(
+                                panels,
+                                window,
+                                renderer,
+                                world,
+                                cursor_pos,
+                                |tool, context| tool.on_key(context, key.physical_key, modifiers),
+                            ) {
+                                return;
+                            }
+                        }
+                        match key.physical_key {
+                            KeyCode::Escape if panels.import.is_busy() => {
+                                if let Some(window) = window {
+                                    cancel_import(&mut panels.import, window);
+                                }
+                            }
+                            KeyCode::Escape if !panels.tools.is_active(ToolKind::RotateView) => {
+                                switch_tool(panels, ToolKind::RotateView);
+                            }
+                            KeyCode::Tab if modifiers.control_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    cycle_documents(
+                                        panels,
+                                        renderer,
+                                        world,
+                                        !modifiers.shift_key(),
+                                    );
+                                }
+                            }
+                            KeyCode::Tab => {
+                                let editing = panels.review.allows_editing();
+                                panels.tools.cycle(!modifiers.shift_key(), editing);
+                            }
+                            KeyCode::Space if modifiers.shift_key() => {
+                                if let (Some(window), Some(world)) = (window, world) {
+                                    if let Some((component, atom)) =
+                                        pick_atom(window, renderer, world, cursor_pos)
+                                    {
+                                        panels.selection.toggle(component, atom);
+                                        report_selection(&panels.selection);
+                                    }
+                                }
+                            }
+                            KeyCode::BracketRight if is_command_key_held(modifiers) => {
+                                if let Some(world) = world {
+                                    panels.selection.select_within(world, DEFAULT_PROXIMITY);
+                                    report_selection(&panels.selection);
+                                }
+                            }
+                            code @ (KeyCode::ArrowLeft
+                            | KeyCode::ArrowRight
+                            | KeyCode::ArrowUp
+                            | KeyCode::ArrowDown)
+                                if modifiers.alt_key() =>
+                            {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                let (offset, turn) = match code {
+                                    KeyCode::ArrowLeft if modifiers.shift_key() => {
+                                        ((0.0, 0.0), NUDGE_ANGLE)
                                     }
-                                    None => {
-                                        println!("failed to create ray!");
+                                    KeyCode::ArrowRight if modifiers.shift_key() => {
+                                        ((0.0, 0.0), -NUDGE_ANGLE)
                                     }
+                                    KeyCode::ArrowLeft => ((-NUDGE_DISTANCE, 0.0), 0.0),
+                                    KeyCode::ArrowRight => ((NUDGE_DISTANCE, 0.0), 0.0),
+                                    KeyCode::ArrowUp => ((0.0, NUDGE_DISTANCE), 0.0),
+                                    _ => ((0.0, -NUDGE_DISTANCE), 0.0),
+                                };
+                                if let Some(world) = world {
+                                    transform_selection(
+                                        &panels.selection,
+                                        renderer,
+                                        world,
+                                        offset,
+                                        turn,
+                                    );
                                 }
                             }
-                        }
-                    }
-                    _ => {
-                        renderer.camera().update(InputEvent::Window(event));
-                    }
-                }
-            }
-        }
-        Event::DeviceEvent { event, .. } => {
-            if let Some(renderer) = renderer {
-                renderer.camera().update(InputEvent::Device(event));
-            }
-        }
-        _ => {
-            // Unknown event; do nothing.
-        }
-    }
-}
-
-fn run(event_loop: EventLoop<()>, mut window: Option<Window>) {
-    // The event handling loop is terminated when the main window is closed.
-    // We can trigger this by dropping the window, so we wrap it in the Option
-    // type.  This is a bit of a hack, but it works.  We require that we are
-    // called with a valid window, however.
-    window.as_ref().expect("window should exist");
-
-    // On mobile platforms the window is destroyed when the application is
-    // suspended, so we need to be able to drop these resources and recreate
-    // as necessary.
-    let mut renderer: Option<Renderer> = None;
-    let mut gpu_resources: Option<Rc<GlobalRenderResources>> = None;
-    let mut world: Option<Assembly> = None;
-    let mut interactions: Option<Interactions> = None;
-    let mut cursor_pos: PhysicalPosition<f64> = Default::default();
-
-    // Run the event loop.
-    let mut running = false;
-    event_loop.run(move |event, _, control_flow| {
-        // When we are done handling this event, suspend until the next event.
-        *control_flow = ControlFlow::Wait;
-
-        // On some platforms, namely wasm32 + webgl2, the window is not yet
-        // ready to create the rendering surface when Event::Resumed is
-        // received.  We therefore just record the fact that the we're in the
-        // running state.
-        match event {
-            Event::Resumed => {
-                // Called on iOS or Android when the application is brought
-                // into focus.  The GPU resources need to be reallocated on
-                // resume.
-                running = true;
-            }
-            Event::Suspended => {
-                // Called on iOS or Android when the application is sent to
-                // the background.  We preemptively destroy the window and any
-                // used GPU resources as the system might take them from us.
-                running = false;
-                interactions = None;
-                world = None;
-                gpu_resources = None;
-                renderer = None;
-                window = None;
-            }
-
-            // The event system does not expose the cursor position on-demand.
-            // We track all the mouse movement events to make this easier to
-            // access later.
-            Event::WindowEvent {
-                event: WindowEvent::CursorMoved { position, .. },
-                ..
-            } => {
-                cursor_pos = position;
-            }
-
-            _ => (),
-        }
-
-        // Check that we've received Event::Resumed, and the window's inner
-        // dimensions are defined.  (Prevents a panic on wasm32 + webgl2).
-        if running && renderer.is_none() {
-            let size = window.as_ref().unwrap().inner_size();
-            if size.width > 0 && size.height > 0 {
-                futures::executor::block_on(async {
-                    let (mut r, g, w, i) = resume_renderer(window.as_ref().unwrap()).await;
-                    r.set_camera(ArcballCamera::new(Vec3::zero(), 100.0, 1.0));
-                    renderer = Some(r);
-                    gpu_resources = Some(g);
-                    world = Some(w);
-                    interactions = Some(i);
-                });
-            }
-        }
-
-        // Handle events.
-        handle_event(
-            event,
-            control_flow,
-            &mut window,
-            &mut renderer,
-            &mut gpu_resources,
-            &mut world,
-            &mut interactions,
-            &cursor_pos,
-        );
-    })
-}
-
-pub fn start(event_loop_builder: &mut EventLoopBuilder<()>) {
-    let menu = menubar::setup_menu_bar(event_loop_builder);
-    let event_loop = event_loop_builder.build();
-
-    // Create the main window.
-    let window = match WindowBuilder::new().with_title(APP_NAME).build(&event_loop) {
-        Err(e) => {
-            println!("Failed to create window: {}", e);
-            std::process::exit(1);
-        }
-        Ok(window) => window,
-    };
-
-    // Add the menu bar to the window / application instance, using native
-    // APIs.
-    menubar::attach_menu_bar(&window, &menu);
+                            KeyCode::BracketLeft | KeyCode::BracketRight if modifiers.alt_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    let step = if key.physical_key == KeyCode::BracketRight {
+                                        TORSION_STEP
+                                    } else {
+                                        -TORSION_STEP
+                                    };
+                                    twist_measured_torsion(world, step);
+                                }
+                            }
+                            KeyCode::BracketRight => {
+                                if let Some(world) = world {
+                                    panels.selection.grow(world, 1);
+                                    report_selection(&panels.selection);
+                                }
+                            }
+                            KeyCode::BracketLeft => {
+                                if let Some(world) = world {
+                                    panels.selection.shrink(world, 1);
+                                    report_selection(&panels.selection);
+                                }
+                            }
+                            KeyCode::KeyL if is_command_key_held(modifiers) => {
+                                if let Some(world) = world {
+                                    panels.selection.select_connected(world);
+                                    report_selection(&panels.selection);
+                                }
+                            }
+                            KeyCode::KeyI if is_command_key_held(modifiers) => {
+                                if let Some(world) = world {
+                                    panels.selection.invert(world);
+                                    report_selection(&panels.selection);
+                                }
+                            }
+                            KeyCode::Space => {
+                                if let (Some(window), Some(world)) = (window, world) {
+                                    match pick_atom(window, renderer, world, cursor_pos) {
+                                        Some((component, hit)) => {
+                                            log::info!("atom {} of {:?} picked", hit, component);
+                                        }
+                                        None => log::debug!("no atom under the cursor"),
+                                    }
+                                }
+                            }
+                            // The command log: F2 steps back through the
+                            // commands run so far, Shift+F2 runs the one
+                            // shown again, and Ctrl+F2 copies it as a line of
+                            // a build script.
+                            KeyCode::F2 if modifiers.shift_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    rerun_command(&panels.command_log, world);
+                                }
+                            }
+                            KeyCode::F2 if is_command_key_held(modifiers) => {
+                                copy_command(&panels.command_log);
+                            }
+                            KeyCode::F2 => match panels.command_log.highlight_previous() {
+                                Some(_) => report_command_log(&panels.command_log),
+                                None => log::info!("no commands have been run"),
+                            },
+                            // Linked files: F5 checks them for changes,
+                            // Shift+F5 updates the components whose files
+                            // have changed, and Ctrl+F5 keeps their copies.
+                            KeyCode::F5 if modifiers.shift_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    update_links(panels, world);
+                                }
+                            }
+                            KeyCode::F5 if is_command_key_held(modifiers) => {
+                                match panels.links.keep() {
+                                    0 => log::info!("no linked components need updating"),
+                                    kept => {
+                                        log::info!("kept the copies of {} linked components", kept)
+                                    }
+                                }
+                            }
+                            KeyCode::F5 => {
+                                if let Some(world) = world {
+                                    if check_links(&mut panels.links, world) == 0 {
+                                        log::info!(
+                                            "no components are linked; press Ctrl+Shift+O to link a file"
+                                        );
+                                    }
+                                }
+                            }
+                            KeyCode::F3 => {
+                                panels.statistics.toggle();
+                            }
+                            KeyCode::F10 => {
+                                toggle_depth_prepass(renderer);
+                            }
+                            KeyCode::F11 => {
+                                toggle_culling(renderer);
+                            }
+                            KeyCode::F8 => {
+                                if let Some(world) = world {
+                                    match panels
+                                        .diagnostics
+                                        .choose_next(world, &mut panels.edit_history)
+                                    {
+                                        Some(problem) => {
+                                            log::info!("{}", problem.description);
+                                            select_problem(panels, world, &problem);
+                                        }
+                                        None => log::info!("no edits have failed"),
+                                    }
+                                }
+                            }
+                            // Composite edits: F6 combines the selected
+                            // edits into one (Shift splits the highlighted
+                            // one), F7 saves the highlighted one to the
+                            // library, and F9 applies the chosen one from the
+                            // library to the selected atoms (Shift chooses
+                            // the next).
+                            KeyCode::F6 if modifiers.shift_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    match panels.edit_history.split_highlighted(world) {
+                                        Some((name, steps)) => {
+                                            log::info!("split {} into {} edits", name, steps);
+                                            report_edit_tree(&panels.edit_history, world);
+                                        }
+                                        None => {
+                                            log::info!("highlight a composite edit to split it")
+                                        }
+                                    }
+                                }
+                            }
+                            KeyCode::F6 => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    combine_selected_edits(&mut panels.edit_history, world);
+                                }
+                            }
+                            KeyCode::F7 => {
+                                if let Some(world) = world {
+                                    save_composite(panels, world);
+                                }
+                            }
+                            KeyCode::F9 if modifiers.shift_key() => match panels.composites.cycle()
+                            {
+                                Some(composite) => log::info!("applying {}", composite.name),
+                                None => log::info!("no composite edits have been saved"),
+                            },
+                            KeyCode::F9 => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    apply_composite(panels, world);
+                                }
+                            }
+                            KeyCode::F4 if modifiers.shift_key() => {
+                                compare_files(&mut panels.compare);
+                            }
+                            KeyCode::F4 => {
+                                if let Some(world) = world {
+                                    toggle_compare(panels, world);
+                                }
+                            }
+                            KeyCode::KeyA if modifiers.alt_key() && modifiers.shift_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    assign_bond_orders_active(panels, world);
+                                }
+                            }
+                            KeyCode::KeyA if modifiers.alt_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let (Some(window), Some(world)) = (window, world) {
+                                    superpose_onto_active(
+                                        window,
+                                        renderer,
+                                        world,
+                                        &panels.timeline,
+                                        &mut panels.history,
+                                        cursor_pos,
+                                    );
+                                }
+                            }
+                            KeyCode::KeyC if modifiers.alt_key() && modifiers.shift_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    add_pattern(panels, renderer, world, PatternShape::Circular);
+                                }
+                            }
+                            KeyCode::KeyC if modifiers.alt_key() => {
+                                if let (Some(window), Some(world)) = (window, world) {
+                                    begin_comment(
+                                        &mut panels.review,
+                                        window,
+                                        renderer,
+                                        world,
+                                        cursor_pos,
+                                    );
+                                }
+                            }
+                            KeyCode::KeyL if modifiers.alt_key() && modifiers.shift_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    add_pattern(panels, renderer, world, PatternShape::Linear);
+                                }
+                            }
+                            KeyCode::KeyL if modifiers.alt_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let (Some(window), Some(world)) = (window, world) {
+                                    toggle_lock(window, renderer, world, cursor_pos);
+                                }
+                            }
+                            KeyCode::KeyF if modifiers.alt_key() && modifiers.shift_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    pin_selection(&panels.selection, world);
+                                }
+                            }
+                            KeyCode::KeyF if modifiers.alt_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let (Some(window), Some(world)) = (window, world) {
+                                    toggle_pin(window, renderer, world, cursor_pos);
+                                }
+                            }
+                            // Which hydrogens are drawn does not change the
+                            // design, so it is allowed while reviewing.
+                            KeyCode::KeyD if modifiers.alt_key() && modifiers.shift_key() => {
+                                if let (Some(window), Some(world)) = (window, world) {
+                                    cycle_component_hydrogens(window, renderer, world, cursor_pos);
+                                }
+                            }
+                            KeyCode::KeyD if modifiers.alt_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let (Some(window), Some(world)) = (window, world) {
+                                    toggle_drive(window, renderer, world, cursor_pos);
+                                }
+                            }
+                            KeyCode::KeyX if modifiers.alt_key() && modifiers.shift_key() => {
+                                switch_tool(panels, ToolKind::Cut);
+                            }
+                            KeyCode::KeyZ if modifiers.alt_key() && modifiers.shift_key() => {
+                                switch_tool(panels, ToolKind::Moiety);
+                            }
+                            KeyCode::KeyW if is_command_key_held(modifiers) => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    close_document(panels, renderer, world);
+                                }
+                            }
+                            KeyCode::KeyW if modifiers.alt_key() && modifiers.shift_key() => {
+                                switch_tool(panels, ToolKind::Brush);
+                            }
+                            KeyCode::KeyK if modifiers.alt_key() && modifiers.shift_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    add_lattice(
+                                        &panels.lattice,
+                                        &mut panels.history,
+                                        renderer,
+                                        world,
+                                    );
+                                }
+                            }
+                            KeyCode::KeyJ if modifiers.alt_key() && modifiers.shift_key() => {
+                                let kind = panels.lattice.cycle_kind();
+                                log::info!("new lattices will be {}", kind.name());
+                            }
+                            KeyCode::KeyH if modifiers.alt_key() && modifiers.shift_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    passivate_active(panels, world);
+                                }
+                            }
+                            // Doping substitutes the element chosen for
+                            // placing atoms.
+                            KeyCode::KeyI if modifiers.alt_key() && modifiers.shift_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    dope_active(panels, world);
+                                }
+                            }
+                            KeyCode::KeyT if modifiers.alt_key() && modifiers.shift_key() => {
+                                panels.doping.cycle_preset();
+                                log::info!("doping: {}", panels.doping.describe());
+                            }
+                            KeyCode::KeyY if modifiers.alt_key() && modifiers.shift_key() => {
+                                let species = panels.passivation.cycle_species();
+                                log::info!("dangling bonds will be capped with {:?}", species);
+                            }
+                            KeyCode::KeyE if modifiers.alt_key() && modifiers.shift_key() => {
+                                if let (Some(window), Some(world)) = (window, world) {
+                                    toggle_passivated_element(
+                                        &mut panels.passivation,
+                                        window,
+                                        renderer,
+                                        world,
+                                        cursor_pos,
+                                    );
+                                }
+                            }
+                            KeyCode::KeyM if modifiers.alt_key() && modifiers.shift_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    mirror_active(panels, renderer, world);
+                                }
+                            }
+                            KeyCode::KeyM
+                                if is_command_key_held(modifiers) && modifiers.shift_key() =>
+                            {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let (Some(window), Some(world)) = (window, world) {
+                                    mirror_component(
+                                        window,
+                                        renderer,
+                                        world,
+                                        &mut panels.history,
+                                        cursor_pos,
+                                    );
+                                }
+                            }
+                            // Animation only moves what is shown, so it is
+                            // allowed while reviewing.
+                            KeyCode::KeyM if modifiers.alt_key() => {
+                                panels.animation.toggle();
+                            }
+                            KeyCode::KeyM if modifiers.shift_key() => {
+                                if let Some(world) = world {
+                                    panels.animation.stop(world);
+                                }
+                            }
+                            KeyCode::KeyR if modifiers.alt_key() && modifiers.shift_key() => {
+                                if let Some(world) = world {
+                                    panels.inspector.next_field();
+                                    report_inspector(panels, world);
+                                }
+                            }
+                            // Measuring does not change the design, so it is
+                            // allowed while reviewing.
+                            KeyCode::KeyR if modifiers.alt_key() => {
+                                switch_tool(panels, ToolKind::Measure);
+                            }
+                            KeyCode::KeyO
+                                if is_command_key_held(modifiers) && modifiers.alt_key() =>
+                            {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    open_project_in_tab(panels, renderer, world);
+                                }
+                            }
+                            KeyCode::KeyO
+                                if is_command_key_held(modifiers) && modifiers.shift_key() =>
+                            {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                link_molecule_file(&mut panels.import);
+                            }
+                            KeyCode::KeyO if is_command_key_held(modifiers) => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                open_molecule_file(&mut panels.import);
+                            }
+                            KeyCode::KeyS if is_command_key_held(modifiers) => {
+                                if let Some(world) = world {
+                                    save_project(panels, renderer.camera(), world);
+                                }
+                            }
+                            KeyCode::KeyO if modifiers.alt_key() && modifiers.shift_key() => {
+                                if let Some(world) = world {
+                                    let hydrogens = world.hydrogen_display().next();
+                                    world.set_hydrogen_display(hydrogens);
+                                    log::info!("showing {}", hydrogens.name());
+                                }
+                            }
+                            KeyCode::KeyO if modifiers.alt_key() => {
+                                toggle_projection(renderer);
+                            }
+                            KeyCode::KeyH if modifiers.alt_key() => {
+                                switch_tool(panels, ToolKind::PlaceAtom);
+                            }
+                            KeyCode::KeyJ if modifiers.alt_key() => {
+                                switch_tool(panels, ToolKind::Bond);
+                            }
+                            // The standard views, on the numpad keys that
+                            // other 3D tools use.  Holding Ctrl shows the
+                            // opposite view, and Shift snaps to the view
+                            // instead of turning to it.
+                            code @ (KeyCode::Numpad1
+                            | KeyCode::Numpad3
+                            | KeyCode::Numpad7
+                            | KeyCode::Numpad0) => {
+                                let opposite = modifiers.control_key();
+                                let preset = match code {
+                                    KeyCode::Numpad1 if opposite => ViewPreset::Back,
+                                    KeyCode::Numpad1 => ViewPreset::Front,
+                                    KeyCode::Numpad3 if opposite => ViewPreset::Left,
+                                    KeyCode::Numpad3 => ViewPreset::Right,
+                                    KeyCode::Numpad7 if opposite => ViewPreset::Bottom,
+                                    KeyCode::Numpad7 => ViewPreset::Top,
+                                    _ => ViewPreset::Isometric,
+                                };
+                                show_view(renderer, preset, !modifiers.shift_key());
+                            }
+                            KeyCode::Numpad5 => {
+                                toggle_projection(renderer);
+                            }
+                            // With Ctrl, + and - move the focus instead,
+                            // keeping the camera where it is, e.g. to turn
+                            // about a point inside a hollow structure.
+                            code @ (KeyCode::NumpadAdd | KeyCode::NumpadSubtract)
+                                if modifiers.control_key() =>
+                            {
+                                let step = match code {
+                                    KeyCode::NumpadAdd => CameraStep::PushFocus,
+                                    _ => CameraStep::PullFocus,
+                                };
+                                step_camera(renderer, step);
+                            }
+                            // Exact steps around and towards the focus, on
+                            // the numpad keys around the standard views.
+                            code @ (KeyCode::Numpad4
+                            | KeyCode::Numpad6
+                            | KeyCode::Numpad8
+                            | KeyCode::Numpad2
+                            | KeyCode::NumpadAdd
+                            | KeyCode::NumpadSubtract) => {
+                                let step = match code {
+                                    KeyCode::Numpad4 => CameraStep::OrbitLeft,
+                                    KeyCode::Numpad6 => CameraStep::OrbitRight,
+                                    KeyCode::Numpad8 => CameraStep::OrbitUp,
+                                    KeyCode::Numpad2 => CameraStep::OrbitDown,
+                                    KeyCode::NumpadAdd => CameraStep::DollyIn,
+                                    _ => CameraStep::DollyOut,
+                                };
+                                step_camera(renderer, step);
+                            }
+                            KeyCode::KeyN if is_command_key_held(modifiers) => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    new_document(panels, renderer, world);
+                                }
+                            }
+                            KeyCode::KeyN if modifiers.alt_key() && modifiers.shift_key() => {
+                                if let Some(world) = world {
+                                    match panels.selection.save_named(world) {
+                                        Some(name) => log::info!("saved the selection as {}", name),
+                                        None => log::info!(
+                                            "select atoms of an unlocked molecule to save them"
+                                        ),
+                                    }
+                                }
+                            }
+                            KeyCode::KeyV if modifiers.alt_key() && modifiers.shift_key() => {
+                                if let Some(world) = world {
+                                    let active = panels.timeline.component(world);
+                                    match panels.selection.cycle_named(world, active) {
+                                        Some(name) => log::info!(
+                                            "selected {}: {}",
+                                            name,
+                                            panels.selection.summary()
+                                        ),
+                                        None => log::info!("no named selections saved"),
+                                    }
+                                }
+                            }
+                            KeyCode::KeyN if modifiers.alt_key() => {
+                                if let Some(world) = world {
+                                    let name = panels.configurations.next_name(world);
+                                    panels.configurations.save(
+                                        world,
+                                        renderer.camera(),
+                                        name.clone(),
+                                    );
+                                    log::info!("saved the current view as {}", name);
+                                }
+                            }
+                            KeyCode::KeyV if modifiers.alt_key() => {
+                                if let Some(world) = world {
+                                    match panels.configurations.cycle(world, renderer.camera()) {
+                                        Some(name) => log::info!("showing {}", name),
+                                        None => log::info!("no configurations saved"),
+                                    }
+                                }
+                            }
+                            KeyCode::KeyE if modifiers.alt_key() => {
+                                if let Some(world) = world {
+                                    let step = if modifiers.shift_key() {
+                                        -EXPLODE_STEP
+                                    } else {
+                                        EXPLODE_STEP
+                                    };
+                                    world.set_explode(world.explode() + step);
+                                    log::info!("exploded by {:.2}", world.explode());
+                                }
+                            }
+                            KeyCode::KeyS if modifiers.alt_key() && modifiers.shift_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                panels.polymer.open();
+                                log::info!(
+                                    "type a sequence to build a {}; Tab changes the kind, Enter builds it",
+                                    panels.polymer.polymer().describe()
+                                );
+                            }
+                            KeyCode::KeyS if modifiers.alt_key() => {
+                                if let Some(world) = world {
+                                    cycle_color_scheme(renderer, world);
+                                }
+                            }
+                            KeyCode::KeyB if modifiers.alt_key() && modifiers.shift_key() => {
+                                if let Some(world) = world {
+                                    toggle_strain_overlay(renderer, world);
+                                }
+                            }
+                            KeyCode::KeyB if modifiers.alt_key() => {
+                                cycle_coloring(renderer, world.as_mut());
+                            }
+                            KeyCode::KeyZ if modifiers.alt_key() => {
+                                if let Some(world) = world {
+                                    warn_if_invalid(world);
+                                    export_zmatrix(&panels.timeline, world);
+                                }
+                            }
+                            KeyCode::KeyP if modifiers.alt_key() && modifiers.shift_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    match panels.patterns.remove_last(world) {
+                                        Some(pattern) => log::info!("removed {}", pattern.name()),
+                                        None => log::info!("no patterns to remove"),
+                                    }
+                                }
+                            }
+                            KeyCode::KeyP if modifiers.alt_key() => {
+                                if let Some(world) = world {
+                                    find_repeated_parts(&mut panels.parts, &panels.timeline, world);
+                                }
+                            }
+                            KeyCode::KeyI if modifiers.alt_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    if let Err(err) =
+                                        panels.parts.accept(world, &mut panels.history)
+                                    {
+                                        log::warn!("could not instance the parts: {:?}", err);
+                                    }
+                                }
+                            }
+                            KeyCode::KeyF if is_command_key_held(modifiers) => {
+                                panels.search.open();
+                            }
+                            KeyCode::KeyQ if modifiers.alt_key() && modifiers.shift_key() => {
+                                if let Some(world) = world {
+                                    match panels.validation.choose_next(world, renderer.camera()) {
+                                        Some(index) => log::info!("framed issue {}", index + 1),
+                                        None => log::info!(
+                                            "no issues to frame; press Alt+Q to validate"
+                                        ),
+                                    }
+                                }
+                            }
+                            KeyCode::KeyQ if modifiers.alt_key() => {
+                                if let Some(world) = world {
+                                    report_validation(&mut panels.validation, world);
+                                }
+                            }
+                            KeyCode::KeyW if modifiers.alt_key() => {
+                                if let Some(world) = world {
+                                    cycle_periodic_images(world);
+                                }
+                            }
+                            KeyCode::KeyY if modifiers.alt_key() => {
+                                if let Some(world) = world {
+                                    toggle_symmetry_propagation(&panels.timeline, world);
+                                }
+                            }
+                            KeyCode::KeyT if is_command_key_held(modifiers) => {
+                                if let Some(world) = world {
+                                    panels.outliner.open(world);
+                                }
+                            }
+                            KeyCode::KeyT if modifiers.alt_key() => {
+                                if let Some(world) = world {
+                                    shake_selection(&panels.selection, world);
+                                }
+                            }
+                            KeyCode::KeyU if modifiers.alt_key() && modifiers.shift_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    match panels.edit_history.toggle_folder_suppressed(world) {
+                                        Some(true) => log::info!("suppressed the folder"),
+                                        Some(false) => log::info!("restored the folder"),
+                                        None => log::info!("the highlighted edit is in no folder"),
+                                    }
+                                }
+                            }
+                            KeyCode::KeyU if modifiers.alt_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    match panels.edit_history.suppress_selected(world) {
+                                        Some(true) => log::info!("suppressed the selected edits"),
+                                        Some(false) => log::info!("restored the selected edits"),
+                                        None => {}
+                                    }
+                                }
+                            }
+                            KeyCode::Delete | KeyCode::Backspace if modifiers.alt_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    // Shift deletes them even if later edits depend on them.
+                                    let force = modifiers.shift_key();
+                                    match panels.edit_history.delete_selected(world, force) {
+                                        Ok(count) => log::info!("deleted {} edits", count),
+                                        Err(conflicts) => log_conflicts("not deleted", &conflicts),
+                                    }
+                                }
+                            }
+                            KeyCode::PageUp | KeyCode::PageDown if modifiers.alt_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    let earlier = key.physical_key == KeyCode::PageUp;
+                                    // Shift moves it even past edits it depends on.
+                                    let force = modifiers.shift_key();
+                                    match panels.edit_history.move_selected(world, earlier, force) {
+                                        Ok(Some(number)) => {
+                                            log::info!("moved the edit to #{}", number)
+                                        }
+                                        Ok(None) => log::info!("select one edit to move it"),
+                                        Err(conflicts) => log_conflicts("not moved", &conflicts),
+                                    }
+                                }
+                            }
+                            KeyCode::PageUp | KeyCode::PageDown => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    let steps = if key.physical_key == KeyCode::PageUp {
+                                        1.0
+                                    } else {
+                                        -1.0
+                                    };
+                                    place_active_molecule(panels, world, Some(steps));
+                                }
+                            }
+                            KeyCode::Home => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    place_active_molecule(panels, world, None);
+                                }
+                            }
+                            KeyCode::KeyG if modifiers.alt_key() && modifiers.shift_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    if let Some(name) = panels.edit_history.ungroup(world) {
+                                        log::info!("ungrouped {}", name);
+                                        report_edit_tree(&panels.edit_history, world);
+                                    }
+                                }
+                            }
+                            KeyCode::KeyG if modifiers.alt_key() => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    let name = panels.edit_history.next_folder_name(world);
+                                    panels.edit_history.group_selected(world, name.clone());
+                                    log::info!("grouped the selected edits into {}", name);
+                                    report_edit_tree(&panels.edit_history, world);
+                                }
+                            }
+                            KeyCode::KeyK if modifiers.alt_key() => {
+                                if let Some(world) = world {
+                                    if panels.edit_history.toggle_collapsed(world).is_some() {
+                                        report_edit_tree(&panels.edit_history, world);
+                                    }
+                                }
+                            }
+                            KeyCode::KeyX if modifiers.alt_key() => {
+                                if let Some(world) = world {
+                                    warn_if_invalid(world);
+                                    export_fragment(&panels.edit_history, world);
+                                }
+                            }
+                            KeyCode::KeyC if is_command_key_held(modifiers) => {
+                                if let Some(world) = world {
+                                    copy_selection(panels, world);
+                                }
+                            }
+                            // Pasting goes into the molecule shown on the
+                            // timeline, or with Shift, into a new molecule.
+                            KeyCode::KeyV if is_command_key_held(modifiers) => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    paste(panels, world, modifiers.shift_key());
+                                }
+                            }
+                            // Exporting the selection, with Shift capping the
+                            // bonds that were cut with hydrogens.
+                            KeyCode::KeyE if is_command_key_held(modifiers) => {
+                                if let Some(world) = world {
+                                    warn_if_invalid(world);
+                                    export_selection(
+                                        &panels.selection,
+                                        world,
+                                        modifiers.shift_key(),
+                                    );
+                                }
+                            }
+                            KeyCode::KeyR if is_command_key_held(modifiers) => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let (Some(window), Some(world)) = (window, world) {
+                                    reindex_active(panels, window, renderer, world, cursor_pos);
+                                }
+                            }
+                            KeyCode::KeyD if is_command_key_held(modifiers) => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    add_pattern(panels, renderer, world, PatternShape::Duplicate);
+                                }
+                            }
+                            KeyCode::KeyZ
+                                if is_command_key_held(modifiers) && modifiers.shift_key() =>
+                            {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    redo(panels, world);
+                                }
+                            }
+                            KeyCode::KeyY if is_command_key_held(modifiers) => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    redo(panels, world);
+                                }
+                            }
+                            KeyCode::KeyZ if is_command_key_held(modifiers) => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    undo(panels, world);
+                                }
+                            }
+                            KeyCode::Delete | KeyCode::Backspace
+                                if is_command_key_held(modifiers) =>
+                            {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                if let Some(world) = world {
+                                    delete_component(panels, world);
+                                }
+                            }
+                            // Developer command: capture the next frame, to
+                            // attach to a report of a rendering bug.
+                            KeyCode::F12 if modifiers.shift_key() => {
+                                renderer.capture_next_frame();
+                            }
+                            // Developer command: replace the scene with a
+                            // procedurally generated stress test.
+                            KeyCode::F12 => {
+                                if refuse_in_review(&panels.review) {
+                                    return;
+                                }
+                                let config = BenchmarkConfig::from_env().unwrap_or_default();
+                                *world = Some(benchmark::generate_scene(&config));
+                                panels.history = ProjectHistory::default();
+                            }
+                            _ => {}
+                        }
+                    }
+                    // Clicks on the panels drawn over the view go to them,
+                    // rather than to the tools or the camera.
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    } if panels.overlay.is_pressed()
+                        || (state == ElementState::Pressed
+                            && panels.overlay.hit(*cursor_pos).is_some()) =>
+                    {
+                        if let Some(world) = world {
+                            click_overlay(panels, world, state, cursor_pos);
+                        }
+                    }
+                    // Alt-clicking an atom shows the edit that created it.
+                    // With Shift held too, that edit is added to (or taken
+                    // out of) the selected edits instead.
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    } if modifiers.alt_key() => {
+                        if let (Some(window), Some(world)) = (window, world) {
+                            if let Some((component, atom)) =
+                                pick_atom(window, renderer, world, cursor_pos)
+                            {
+                                if modifiers.shift_key() {
+                                    panels.edit_history.toggle_atom(world, component, &atom);
+                                    log::info!(
+                                        "{} edits selected",
+                                        panels.edit_history.selected().len()
+                                    );
+                                } else {
+                                    jump_to_edit(&mut panels.edit_history, world, component, &atom);
+                                    report_provenance(&panels.inspector, world, component, &atom);
+                                }
+                            }
+                        }
+                    }
+                    // Presses on the timeline strip scrub through the history
+                    // instead of rotating the camera.
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    } if panels.timeline.is_dragging()
+                        || (state == ElementState::Pressed
+                            && window
+                                .as_ref()
+                                .is_some_and(|window| is_over_timeline(window, cursor_pos))) =>
+                    {
+                        if let (Some(window), Some(world)) = (window, world) {
+                            let position = timeline_position(window, cursor_pos);
+                            match state {
+                                ElementState::Pressed => {
+                                    panels.timeline.begin_drag(position, world)
+                                }
+                                ElementState::Released => panels.timeline.end_drag(world),
+                            }
+                        }
+                    }
+                    WindowEvent::CursorMoved { .. } if panels.timeline.is_dragging() => {
+                        if let (Some(window), Some(world)) = (window, world) {
+                            panels
+                                .timeline
+                                .drag_to(timeline_position(window, cursor_pos), world);
+                        }
+                    }
+                    // Anything else with the pointer goes to the active tool
+                    // first, and then to the camera if the tool left it alone.
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    } => {
+                        let handled = match (window, world) {
+                            (Some(window), Some(world)) => use_active_tool(
+                                panels,
+                                window,
+                                renderer,
+                                world,
+                                cursor_pos,
+                                |tool, context| match state {
+                                    ElementState::Pressed => tool.on_pointer_down(context),
+                                    ElementState::Released => tool.on_pointer_up(context),
+                                },
+                            ),
+                            _ => false,
+                        };
+                        if !handled {
+                            renderer.camera().update(InputEvent::Window(event));
+                        }
+                    }
+                    // Scrolling goes to the active tool first too, e.g. to
+                    // choose between the atoms under a click that is still
+                    // held.
+                    WindowEvent::MouseWheel { delta, .. } => {
+                        let lines = match delta {
+                            MouseScrollDelta::LineDelta(_, y) => y,
+                            MouseScrollDelta::PixelDelta(PhysicalPosition { y, .. }) => {
+                                (y / PIXELS_PER_LINE) as f32
+                            }
+                        };
+                        let handled = match (window, world) {
+                            (Some(window), Some(world)) => use_active_tool(
+                                panels,
+                                window,
+                                renderer,
+                                world,
+                                cursor_pos,
+                                |tool, context| tool.on_scroll(context, lines),
+                            ),
+                            _ => false,
+                        };
+                        if !handled {
+                            renderer.camera().update(InputEvent::Window(event));
+                        }
+                    }
+                    // The camera sees every move, so that it can follow drags.
+                    WindowEvent::CursorMoved { .. } => {
+                        if let (Some(window), Some(world)) = (window, world) {
+                            use_active_tool(
+                                panels,
+                                window,
+                                renderer,
+                                world,
+                                cursor_pos,
+                                |tool, context| tool.on_pointer_move(context),
+                            );
+                        }
+                        renderer.camera().update(InputEvent::Window(event));
+                    }
+                    // A finger or pen held still opens the radial menu, and
+                    // while it is open, moving chooses between its items
+                    // instead of turning the camera.
+                    WindowEvent::Touch(touch) => {
+                        let menu = &mut panels.radial_menu;
+                        let forward = match touch.phase {
+                            TouchPhase::Started => {
+                                let scale_factor =
+                                    window.as_ref().map_or(1.0, |window| window.scale_factor());
+                                menu.press(touch.id, touch.location, scale_factor);
+                                true
+                            }
+                            TouchPhase::Moved => {
+                                menu.move_to(touch.id, touch.location);
+                                !menu.is_open()
+                            }
+                            TouchPhase::Ended => {
+                                if let (Some(item), Some(world)) = (menu.release(touch.id), world) {
+                                    choose_radial_item(panels, world, item);
+                                }
+                                true
+                            }
+                            TouchPhase::Cancelled => {
+                                menu.cancel();
+                                true
+                            }
+                        };
+                        if forward {
+                            renderer.camera().update(InputEvent::Window(event));
+                        }
+                    }
+                    _ => {
+                        renderer.camera().update(InputEvent::Window(event));
+                    }
+                }
+            }
+        }
+        Event::DeviceEvent { event, .. } => {
+            if let Some(renderer) = renderer {
+                renderer.camera().update(InputEvent::Device(event));
+            }
+        }
+        _ => {
+            // Unknown event; do nothing.
+        }
+    }
+}
+
+// Whether `event` is the user doing something, which stops the idle
+// turntable.
+fn is_user_input(event: &Event<()>) -> bool {
+    matches!(
+        event,
+        Event::WindowEvent {
+            event: WindowEvent::KeyboardInput { .. }
+                | WindowEvent::MouseInput { .. }
+                | WindowEvent::MouseWheel { .. }
+                | WindowEvent::TouchpadMagnify { .. }
+                | WindowEvent::TouchpadRotate { .. }
+                | WindowEvent::CursorMoved { .. }
+                | WindowEvent::Touch(_),
+            ..
+        }
+    )
+}
+
+// Wakes the event loop after `wait`, if it would otherwise sleep until the
+// next event.
+fn wake_after(control_flow: &mut ControlFlow, wait: std::time::Duration) {
+    if *control_flow != ControlFlow::Wait {
+        return;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        *control_flow = ControlFlow::WaitUntil(std::time::Instant::now() + wait);
+    }
+    // FIXME: The web event loop keeps time with its own clock type, so keep
+    // drawing frames instead.
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = wait;
+        *control_flow = ControlFlow::Poll;
+    }
+}
+
+fn show_view(renderer: &mut Renderer, preset: ViewPreset, animate: bool) {
+    let (yaw, pitch) = preset.orientation();
+    renderer.camera().orbit_to(yaw, pitch, animate);
+    log::info!("{} view", preset.name());
+}
+
+// Moves the camera one step on from where it is going, without animating, so
+// that the same keys always give the same view.
+fn step_camera(renderer: &mut Renderer, step: CameraStep) {
+    let camera = renderer.camera();
+    if let Some(pose) = camera.destination() {
+        let pose = step.apply(pose);
+        camera.set_pose(pose, false);
+        log::info!("{} (orbit radius {:.1} Å)", step.name(), pose.distance);
+    }
+}
+
+fn toggle_projection(renderer: &mut Renderer) {
+    let projection = match renderer.camera().projection() {
+        Projection::Perspective => Projection::Orthographic,
+        Projection::Orthographic => Projection::Perspective,
+    };
+    renderer.camera().set_projection(projection);
+    log::info!("{:?} projection", projection);
+}
+
+fn toggle_depth_prepass(renderer: &mut Renderer) {
+    let depth_prepass = !renderer.depth_prepass();
+    renderer.set_depth_prepass(depth_prepass);
+    log::info!(
+        "depth pre-pass {}",
+        if depth_prepass { "on" } else { "off" }
+    );
+}
+
+// Turns the culling of molecules drawn too small on or off.  Turning it on
+// uses the culling given by `CULLING_ENV_VAR`, or the defaults.
+fn toggle_culling(renderer: &mut Renderer) {
+    let culling = match renderer.culling() {
+        Some(_) => None,
+        None => Some(screen_culling().unwrap_or_default()),
+    };
+    renderer.set_culling(culling);
+    match culling {
+        Some(culling) => log::info!(
+            "culling molecules under {} pixels ({:?})",
+            culling.min_pixels,
+            culling.mode
+        ),
+        None => log::info!("culling off"),
+    }
+}
+
+// Switches to the next built-in color scheme, keeping any colors given to
+// particular elements.  The scheme is kept in the assembly so that it is saved
+// with the project.
+fn cycle_color_scheme(renderer: &mut Renderer, world: &mut Assembly) {
+    let mut color_scheme = world.color_scheme().clone();
+    color_scheme.base = color_scheme.base.next();
+    log::info!("{} colors", color_scheme.base.name());
+    renderer.set_color_scheme(color_scheme.clone());
+    world.set_color_scheme(color_scheme);
+}
+
+// Switches to coloring atoms by the next of element, component, chain and
+// partial charge.  This also turns off the bond strain overlay.
+fn cycle_coloring(renderer: &mut Renderer, world: Option<&mut Assembly>) {
+    if let Some(world) = world {
+        world.walk_mut(|molecule, _| molecule.repr.set_property(AtomProperty::PartialCharge));
+    }
+    let coloring = match renderer.coloring() {
+        AtomColoring::Element => AtomColoring::Component,
+        AtomColoring::Component => AtomColoring::Group,
+        AtomColoring::Group => AtomColoring::Property {
+            low: -CHARGE_RANGE,
+            high: CHARGE_RANGE,
+        },
+        AtomColoring::Property { .. } => AtomColoring::Element,
+    };
+    renderer.set_coloring(coloring);
+    log::info!("coloring atoms by {}", coloring.name().to_lowercase());
+}
+
+// Colors each atom by the strain of its most strained bond, from blue where it
+// is compressed to red where it is stretched, or goes back to coloring atoms
+// by element.  Badly strained bonds and overbonded atoms are warned about.
+fn toggle_strain_overlay(renderer: &mut Renderer, world: &mut Assembly) {
+    let mut showing = false;
+    world.walk_mut(|molecule, _| {
+        showing |= matches!(molecule.repr.property(), AtomProperty::BondStrain(_));
+    });
+    if showing && matches!(renderer.coloring(), AtomColoring::Property { .. }) {
+        world.walk_mut(|molecule, _| molecule.repr.set_property(AtomProperty::PartialCharge));
+        renderer.set_coloring(AtomColoring::Element);
+        log::info!("hid the bond strain overlay");
+        return;
+    }
+
+    world.walk_mut(|molecule, _| {
+        let force_field = molecule.force_field();
+        molecule
+            .repr
+            .set_property(AtomProperty::BondStrain(force_field));
+
+        let strains = strain::bond_strains(&molecule.repr, force_field);
+        let strained = strains
+            .iter()
+            .filter(|bond| bond.strain().abs() > STRAIN_WARNING)
+            .count();
+        if let Some(worst) = strains
+            .iter()
+            .max_by(|a, b| a.strain().abs().total_cmp(&b.strain().abs()))
+            .filter(|_| strained > 0)
+        {
+            log::warn!(
+                "{}: {} of {} bonds are more than {:.0}% off their ideal length; the worst is {}-{} at {:.2} Å instead of {:.2} Å",
+                molecule.name(),
+                strained,
+                strains.len(),
+                STRAIN_WARNING * 100.0,
+                worst.atoms.0,
+                worst.atoms.1,
+                worst.length,
+                worst.ideal
+            );
+        }
+        for atom in strain::overbonded_atoms(&molecule.repr) {
+            log::warn!("{}: atom {} has more bonds than its valence", molecule.name(), atom);
+        }
+    });
+    renderer.set_coloring(AtomColoring::Property {
+        low: -STRAIN_RANGE,
+        high: STRAIN_RANGE,
+    });
+    log::info!("showing bond strain");
+}
+
+// Finds the atom under the cursor.
+fn pick_atom(
+    window: &Window,
+    renderer: &mut Renderer,
+    world: &Assembly,
+    cursor_pos: &PhysicalPosition<f64>,
+) -> Option<(ComponentId, AtomSpecifier)> {
+    let (origin, direction) = renderer
+        .camera()
+        .get_ray_from(cursor_pos, &window.inner_size())?;
+    world.pick(origin, direction)
+}
+
+fn jump_to_edit(
+    history: &mut EditHistory,
+    world: &Assembly,
+    component: ComponentId,
+    atom: &AtomSpecifier,
+) {
+    history.jump_to_atom(world, component, atom);
+
+    if let Some(row) = history.rows(world).into_iter().find(|row| row.highlighted) {
+        log::info!(
+            "atom {} was created by edit #{}: {}",
+            atom,
+            row.number,
+            row.label
+        );
+    }
+}
+
+// Compares the molecule shown in the timeline as it is now with how it is at
+// the end of its history (or, if it is already there, one step earlier).
+fn toggle_compare(panels: &mut Panels, world: &Assembly) {
+    if panels.compare.is_open() {
+        panels.compare.close();
+        return;
+    }
+
+    let Some(component) = panels.timeline.component(world) else {
+        return;
+    };
+    let Some(molecule) = world.component(component).and_then(|c| c.molecule()) else {
+        return;
+    };
+    let last_step = molecule.edits().len();
+    let step = molecule.history_step().min(last_step.saturating_sub(1));
+    if panels
+        .compare
+        .open_history(world, component, step, last_step)
+    {
+        report_compare(&panels.compare);
+    }
+}
+
+// Asks for two saved projects, and compares their molecules side by side.
+fn compare_files(compare: &mut CompareView) {
+    let Some(left) = platform::file_dialog::pick_file("Compare", PROJECT_FILTERS) else {
+        return;
+    };
+    let Some(right) = platform::file_dialog::pick_file("Compare With", PROJECT_FILTERS) else {
+        return;
+    };
+    let read = |path: &std::path::Path| {
+        std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))
+    };
+    let opened = read(&left).and_then(|left_text| {
+        let right_text = read(&right)?;
+        compare
+            .open_projects(&left_text, &right_text)
+            .map_err(|err| err.to_string())
+    });
+    match opened {
+        Ok(()) => report_compare(compare),
+        Err(err) => log::warn!("could not compare the projects: {}", err),
+    }
+}
+
+// Says which variants are shown side by side, and how closely they match.
+fn report_compare(compare: &CompareView) {
+    if let Some((left, right)) = compare.sides() {
+        log::info!("comparing {} with {}", left.label, right.label);
+    }
+    match compare.alignment() {
+        Some(alignment) => log::info!(
+            "RMSD {:.3} Å over {} matched atoms",
+            alignment.rmsd,
+            alignment.matched_atoms
+        ),
+        None => log::info!("the variants have no atoms in common"),
+    }
+}
+
+// Superposes the molecule under the cursor onto the one shown in the timeline.
+fn superpose_onto_active(
+    window: &Window,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+    timeline: &Timeline,
+    history: &mut ProjectHistory,
+    cursor_pos: &PhysicalPosition<f64>,
+) {
+    let Some(target) = timeline.component(world) else {
+        return;
+    };
+    let Some((mobile, _)) = pick_atom(window, renderer, world, cursor_pos) else {
+        log::info!("no molecule under the cursor to superpose");
+        return;
+    };
+    if mobile == target {
+        return;
+    }
+
+    let Some(transform) = world.component(mobile).map(Component::transform) else {
+        return;
+    };
+    match world.superpose(mobile, target) {
+        Ok(superposition) => {
+            history.record(
+                world,
+                AssemblyCommand::SetTransform {
+                    id: mobile,
+                    transform,
+                },
+                "superpose",
+            );
+            log::info!(
+                "superposed {:?} onto {:?} with an RMSD of {:.3} Å",
+                mobile,
+                target,
+                superposition.rmsd
+            )
+        }
+        Err(err) => log::warn!(
+            "could not superpose {:?} onto {:?}: {:?}",
+            mobile,
+            target,
+            err
+        ),
+    }
+}
+
+// Reports that a command was refused because the design is being reviewed.
+// Returns whether it was.
+fn refuse_in_review(review: &ReviewMode) -> bool {
+    if !review.allows_editing() {
+        log::info!("the design is open for review and cannot be changed");
+    }
+    !review.allows_editing()
+}
+
+// Opens the saved molecule at `path` for review, along with any comments
+// already made on it.
+fn open_for_review(path: &str, review: &mut ReviewMode) -> Option<Assembly> {
+    let molecule: MoleculeEditor = match std::fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|json| serde_json::from_str(&json).map_err(|err| err.to_string()))
+    {
+        Ok(molecule) => molecule,
+        Err(err) => {
+            log::error!("could not open {} for review: {}", path, err);
+            return None;
+        }
+    };
+
+    review.set_active(true);
+    if let Ok(json) = std::fs::read_to_string(comments_path(path)) {
+        if let Err(err) = review.load_comments(&json) {
+            log::warn!("ignoring unreadable comments on {}: {}", path, err);
+        }
+    }
+    log::info!(
+        "reviewing {} ({} comments); press Alt+C to comment on the atom under the cursor",
+        path,
+        review.comments().len()
+    );
+
+    Some(Assembly::from_components([Component::from_molecule(
+        molecule,
+        Mat4::default(),
+    )]))
+}
+
+// Builds a molecule from the build script or Z-matrix at `path`, named after
+// the file.
+fn open_build_script(path: &str) -> Option<Assembly> {
+    let molecule = match read_molecule(std::path::Path::new(path)) {
+        Ok(molecule) => molecule,
+        Err(err) => {
+            log::error!("could not build {}: {}", path, err);
+            return None;
+        }
+    };
+
+    Some(Assembly::from_components([Component::from_molecule(
+        molecule,
+        Mat4::default(),
+    )]))
+}
+
+// Asks for a file to open, and starts importing the molecule in it in the
+// background.  See `finish_import` for how it is added to the assembly.
+fn open_molecule_file(import: &mut ImportDialog) {
+    let Some(path) = platform::file_dialog::pick_file("Open", OPEN_FILTERS) else {
+        return;
+    };
+    let name = path.file_name().map_or(path.display().to_string(), |name| {
+        name.to_string_lossy().into_owned()
+    });
+    import.open(ImportJob::start(name, move |progress| {
+        import_molecule(&path, progress)
+    }));
+}
+
+// Asks for a file to link, and imports the molecule in it as `open_molecule_file`
+// does, but keeps a reference to the file with the component, so that it can
+// be updated when the file changes.
+fn link_molecule_file(import: &mut ImportDialog) {
+    let Some(path) = platform::file_dialog::pick_file("Link", OPEN_FILTERS) else {
+        return;
+    };
+    let name = path.file_name().map_or(path.display().to_string(), |name| {
+        name.to_string_lossy().into_owned()
+    });
+    let link = path.display().to_string();
+    import.open(
+        ImportJob::start(name, move |progress| import_molecule(&path, progress)).linking(link),
+    );
+}
+
+// Reads the file that a linked component refers to.  Only local files can be
+// read for now; links to URLs keep their copies.
+fn read_link(uri: &str) -> Result<Vec<u8>, String> {
+    if uri.contains("://") {
+        return Err("links to URLs cannot be followed yet".to_owned());
+    }
+    std::fs::read(uri).map_err(|err| err.to_string())
+}
+
+// Reads the molecule in a linked file, as `read_molecule` does.
+fn parse_link(uri: &str, contents: &[u8]) -> Result<MoleculeEditor, String> {
+    let (name, extension) = name_and_extension(std::path::Path::new(uri));
+    let text = String::from_utf8(contents.to_vec()).map_err(|err| err.to_string())?;
+    let mut molecule = parse_molecule(&name, extension.as_deref(), text)?;
+    molecule.apply_all_edits();
+    Ok(molecule)
+}
+
+// Checks the files of the linked components for changes, and reports what was
+// found, with the keys that update them.  Returns how many components are
+// linked.
+fn check_links(links: &mut LinkedFiles, world: &Assembly) -> usize {
+    let linked = links.check(world, read_link, parse_link);
+    if linked == 0 {
+        return 0;
+    }
+    for link in links.unavailable() {
+        log::warn!(
+            "could not read {} for {}, so its copy is kept: {}",
+            link.uri,
+            link.name,
+            link.reason
+        );
+    }
+    if links.changed().is_empty() {
+        log::info!("the {} linked components are up to date", linked);
+        return linked;
+    }
+    for link in links.changed() {
+        log::info!(
+            "{} has changed since {} was read from it",
+            link.uri,
+            link.name
+        );
+    }
+    log::info!("press Shift+F5 to update them from their files, or Ctrl+F5 to keep the copies");
+    linked
+}
+
+// Updates the linked components whose files have changed.
+fn update_links(panels: &mut Panels, world: &mut Assembly) {
+    let updated = panels.links.update(world);
+    if updated.is_empty() {
+        log::info!("no linked components need updating; press F5 to check them");
+        return;
+    }
+    for id in &updated {
+        // The new molecule's edits were not run this session.
+        panels.command_log.forget(*id);
+    }
+    panels.edit_history.clear_selection();
+    log::info!("updated {} linked components", updated.len());
+}
+
+// Opens the project saved at `path`, and checks the files of its linked
+// components.
+fn open_project(path: &str, panels: &mut Panels) -> Option<Assembly> {
+    let assembly: Assembly = match std::fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|json| serde_json::from_str(&json).map_err(|err| err.to_string()))
+    {
+        Ok(assembly) => assembly,
+        Err(err) => {
+            log::error!("could not open {}: {}", path, err);
+            return None;
+        }
+    };
+    log::info!("opened {}", path);
+    panels.documents.set_active_path(path, &assembly);
+    check_links(&mut panels.links, &assembly);
+    Some(assembly)
+}
+
+// Asks for a saved project, and opens it as a new document, in a tab after the
+// one being shown.
+fn open_project_in_tab(panels: &mut Panels, renderer: &mut Renderer, world: &mut Assembly) {
+    let Some(path) = platform::file_dialog::pick_file("Open Project", PROJECT_FILTERS) else {
+        return;
+    };
+    let assembly: Assembly = match std::fs::read_to_string(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|json| serde_json::from_str(&json).map_err(|err| err.to_string()))
+    {
+        Ok(assembly) => assembly,
+        Err(err) => {
+            log::error!("could not open {}: {}", path.display(), err);
+            return;
+        }
+    };
+    log::info!("opened {}", path.display());
+    let shown = take_document(panels, renderer, world);
+    let document = panels.documents.open(
+        shown,
+        Document {
+            saved: serde_json::to_string(&assembly).ok(),
+            assembly,
+            path: Some(path),
+            ..Default::default()
+        },
+    );
+    show_document(panels, renderer, world, document);
+    check_links(&mut panels.links, world);
+}
+
+// Opens a new, empty document, in a tab after the one being shown.
+fn new_document(panels: &mut Panels, renderer: &mut Renderer, world: &mut Assembly) {
+    let shown = take_document(panels, renderer, world);
+    let document = panels.documents.open(shown, Document::default());
+    show_document(panels, renderer, world, document);
+}
+
+// Switches to the next document, or the previous one if not `forward`.
+fn cycle_documents(
+    panels: &mut Panels,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+    forward: bool,
+) {
+    let shown = take_document(panels, renderer, world);
+    let document = panels.documents.cycle(forward, shown);
+    show_document(panels, renderer, world, document);
+}
+
+// Closes the document being shown, unless it is the only one.  One with
+// unsaved changes is only closed when asked again.
+fn close_document(panels: &mut Panels, renderer: &mut Renderer, world: &mut Assembly) {
+    let document = match panels.documents.close_active(world) {
+        Ok(document) => document,
+        Err(CloseError::LastDocument) => {
+            log::info!("the last document cannot be closed");
+            return;
+        }
+        Err(CloseError::UnsavedChanges) => {
+            let label = panels
+                .documents
+                .tabs(world)
+                .into_iter()
+                .find(|tab| tab.active);
+            log::warn!(
+                "{} has unsaved changes; save it, or close it again to discard them",
+                label.map_or_else(String::new, |tab| tab.label)
+            );
+            return;
+        }
+    };
+    log::info!("closed {}", world.name());
+    show_document(panels, renderer, world, document);
+}
+
+// Takes the state of the document being shown out of the world, the panels
+// and the camera, to be parked while another is shown.
+fn take_document(panels: &mut Panels, renderer: &mut Renderer, world: &mut Assembly) -> Document {
+    let camera = renderer.camera();
+    Document {
+        assembly: std::mem::take(world),
+        selection: std::mem::take(&mut panels.selection),
+        camera: camera.pose().map(|pose| (pose, camera.projection())),
+        history: std::mem::take(&mut panels.history),
+        path: None,
+        saved: None,
+        command_log: std::mem::take(&mut panels.command_log),
+        compare: std::mem::take(&mut panels.compare),
+        configurations: std::mem::take(&mut panels.configurations),
+        diagnostics: std::mem::take(&mut panels.diagnostics),
+        inspector: std::mem::take(&mut panels.inspector),
+        links: std::mem::take(&mut panels.links),
+        outliner: std::mem::take(&mut panels.outliner),
+        parts: std::mem::take(&mut panels.parts),
+        timeline: std::mem::take(&mut panels.timeline),
+    }
+}
+
+// Shows `document` in place of the one whose state was taken.  A document
+// shown for the first time is shown as its saved view describes, if it has
+// one.
+fn show_document(
+    panels: &mut Panels,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+    document: Document,
+) {
+    *world = document.assembly;
+    panels.selection = document.selection;
+    panels.history = document.history;
+    panels.command_log = document.command_log;
+    panels.compare = document.compare;
+    panels.configurations = document.configurations;
+    panels.diagnostics = document.diagnostics;
+    panels.inspector = document.inspector;
+    panels.links = document.links;
+    panels.outliner = document.outliner;
+    panels.parts = document.parts;
+    panels.timeline = document.timeline;
+    panels.edit_history.clear_selection();
+    match document.camera {
+        Some((pose, projection)) => {
+            renderer.camera().set_pose(pose, false);
+            renderer.camera().set_projection(projection);
+        }
+        None => {
+            let editing = panels.review.allows_editing();
+            layout::restore(panels, renderer.camera(), world, editing);
+        }
+    }
+    report_documents(panels, world);
+}
+
+// Lists the open documents, with the one being shown in brackets.
+fn report_documents(panels: &Panels, world: &Assembly) {
+    let tabs: Vec<String> = panels
+        .documents
+        .tabs(world)
+        .into_iter()
+        .map(|tab| {
+            if tab.active {
+                format!("[{}]", tab.label)
+            } else {
+                tab.label
+            }
+        })
+        .collect();
+    log::info!("documents: {}", tabs.join("  "));
+}
+
+// Asks where to save the project, and saves the assembly there, with linked
+// components keeping both their links and their copies, and with the view
+// and panel layout it is being shown with.
+fn save_project(panels: &mut Panels, camera: &RenderCamera, world: &mut Assembly) {
+    let Some(path) = platform::file_dialog::save_file(
+        "Save Project",
+        &format!("{}.atomcad", world.name()),
+        PROJECT_FILTERS,
+    ) else {
+        return;
+    };
+    layout::remember(panels, camera, world);
+    let json = match serde_json::to_string(world) {
+        Ok(json) => json,
+        Err(err) => {
+            log::warn!("could not save the project: {}", err);
+            return;
+        }
+    };
+    match std::fs::write(&path, json) {
+        Ok(()) => {
+            log::info!("saved the project to {}", path.display());
+            panels.documents.set_active_path(path, world);
+        }
+        Err(err) => log::warn!("could not write {}: {}", path.display(), err),
+    }
+}
+
+// Saves a captured frame next to the project.
+fn save_frame_capture(panels: &Panels, capture: &FrameCapture) {
+    match frame_capture::save(capture, panels.documents.active_path()) {
+        Ok(path) => log::info!(
+            "saved a capture of the frame drawn by {} ({}) to {}",
+            capture.adapter,
+            capture.backend,
+            path.display()
+        ),
+        Err(err) => log::warn!("could not save the frame capture: {}", err),
+    }
+}
+
+// Adds the molecule from a background import to the assembly as a new
+// component, with the camera framed on it, once the import has finished.
+// While it runs, the progress dialog is shown in the window's title.
+fn finish_import(
+    import: &mut ImportDialog,
+    history: &mut ProjectHistory,
+    window: &Window,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+) {
+    let Some((name, result, link)) = import.take_finished() else {
+        if let Some(job) = import.job() {
+            window.set_title(&job.dialog_text());
+        }
+        return;
+    };
+    window.set_title(APP_NAME);
+    match result {
+        Ok(molecule) => {
+            frame_molecule(renderer, &molecule);
+            let mut component = Component::from_molecule(molecule, Mat4::default());
+            // The file is read again for its hash, rather than passing it back
+            // from the worker; if it changed in between, the next check of the
+            // links will offer the change.
+            match link
+                .map(|path| read_link(&path).map(|contents| ExternalRef::new(path, &contents)))
+            {
+                Some(Ok(source)) => {
+                    log::info!("linked {}", source.uri);
+                    component.set_source(Some(source));
+                }
+                Some(Err(err)) => log::warn!("could not link {}, so it is embedded: {}", name, err),
+                None => log::info!("opened {}", name),
+            }
+            let id = world.add_component(component);
+            history.record_added(world, id);
+        }
+        Err(err) => log::error!("could not open {}: {}", name, err),
+    }
+}
+
+// Cancels the background import, leaving the assembly as it was.
+fn cancel_import(import: &mut ImportDialog, window: &Window) {
+    if let Some(name) = import.cancel() {
+        window.set_title(APP_NAME);
+        log::info!("cancelled opening {}", name);
+    }
+}
+
+// Replaces the scene with the embedded viewer's structure, downloaded as
+// `download`, with the camera framed on it.
+fn show_embedded_structure(
+    embed: &EmbeddedViewer,
+    download: Result<String, String>,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+) {
+    let url = embed.url().unwrap_or_default();
+    let (name, extension) = embed.file_name();
+    match download.and_then(|text| parse_molecule(&name, extension.as_deref(), text)) {
+        Ok(molecule) => {
+            frame_molecule(renderer, &molecule);
+            log::info!("showing {}", url);
+            *world =
+                Assembly::from_components([Component::from_molecule(molecule, Mat4::default())]);
+        }
+        Err(err) => log::error!("could not show {}: {}", url, err),
+    }
+}
+
+fn frame_molecule(renderer: &mut Renderer, molecule: &MoleculeEditor) {
+    if molecule.repr.graph.node_count() > 0 {
+        let bounds = molecule.repr.bounding_box();
+        let center = (bounds.min + bounds.max) * 0.5;
+        renderer
+            .camera()
+            .frame(center, (bounds.max - bounds.min).mag() * 0.5);
+    }
+}
+
+// Reads a molecule named after the file at `path`.  See `parse_molecule` for
+// how the file is read.
+fn read_molecule(path: &std::path::Path) -> Result<MoleculeEditor, String> {
+    let (name, extension) = name_and_extension(path);
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    parse_molecule(&name, extension.as_deref(), text)
+}
+
+// Reads a molecule as `read_molecule` does, and applies its edits, reporting
+// how far it has got to `progress`.  This gives up between steps once the
+// import is cancelled.
+fn import_molecule(
+    path: &std::path::Path,
+    progress: &ImportProgress,
+) -> Result<MoleculeEditor, String> {
+    use std::io::Read as _;
+
+    let cancelled = || Err("cancelled".to_owned());
+    let (name, extension) = name_and_extension(path);
+    let mut file = std::fs::File::open(path).map_err(|err| err.to_string())?;
+    let total = file.metadata().map_or(0, |metadata| metadata.len());
+    let mut bytes = Vec::with_capacity(total as usize);
+    let mut chunk = vec![0; IMPORT_CHUNK_SIZE];
+    loop {
+        if progress.is_cancelled() {
+            return cancelled();
+        }
+        let read = file.read(&mut chunk).map_err(|err| err.to_string())?;
+        if read == 0 {
+            break;
+        }
+        bytes.extend_from_slice(&chunk[..read]);
+        if total > 0 {
+            progress.set_fraction(bytes.len() as f32 / total as f32);
+        }
+    }
+    let text = String::from_utf8(bytes).map_err(|err| err.to_string())?;
+
+    progress.set_stage(ImportStage::Parsing);
+    let mut molecule = parse_molecule(&name, extension.as_deref(), text)?;
+    if progress.is_cancelled() {
+        return cancelled();
+    }
+    progress.set_stage(ImportStage::Applying);
+    molecule.apply_all_edits();
+    Ok(molecule)
+}
+
+// The name that a molecule read from `path` is given, and the extension that
+// says how to read it, in lower case.
+fn name_and_extension(path: &std::path::Path) -> (String, Option<String>) {
+    let name = path.file_stem().map_or("Molecule".into(), |stem| {
+        stem.to_string_lossy().into_owned()
+    });
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    (name, extension)
+}
+
+// Reads the contents of a file with the given extension as a molecule named
+// `name`.  Saved molecules are loaded, PDB files, molfiles and Z-matrices are
+// imported, and anything else is read as a build script.
+fn parse_molecule(
+    name: &str,
+    extension: Option<&str>,
+    text: String,
+) -> Result<MoleculeEditor, String> {
+    match extension {
+        Some("json") => serde_json::from_str(&text).map_err(|err| err.to_string()),
+        Some("pdb" | "ent") => Ok(MoleculeEditor::from_feature(Edit::PdbImport(PdbData {
+            name: name.to_owned(),
+            contents: text,
+            residue_templates: true,
+            model: PdbModel::First,
+            conect_bonds: true,
+            infer_bonds: true,
+        }))),
+        Some("sdf" | "mol") => sdf::parse(name, &text)
+            .map(|import| MoleculeEditor::from_feature(Edit::AtomImport(import)))
+            .map_err(|err| err.to_string()),
+        Some("zmat") => ZMatrix::parse(name, &text)
+            .map(|zmatrix| MoleculeEditor::from_feature(Edit::ZMatrixImport(zmatrix)))
+            .map_err(|err| err.to_string()),
+        _ => build_script::build(name, &text).map_err(|err| err.to_string()),
+    }
+}
+
+fn comments_path(review_path: &str) -> String {
+    format!("{}.comments.json", review_path)
+}
+
+// Starts a comment on the atom under the cursor.
+fn begin_comment(
+    review: &mut ReviewMode,
+    window: &Window,
+    renderer: &mut Renderer,
+    world: &Assembly,
+    cursor_pos: &PhysicalPosition<f64>,
+) {
+    let Some((id, atom)) = pick_atom(window, renderer, world, cursor_pos) else {
+        log::info!("no atom under the cursor to comment on");
+        return;
+    };
+    let Some(component) = world.component(id) else {
+        return;
+    };
+    log::info!("commenting on atom {}; press Enter to save", atom);
+    review.begin_comment(component.name(), Some(atom));
+}
+
+fn handle_comment_key(review: &mut ReviewMode, key: &KeyEvent) {
+    match key.physical_key {
+        KeyCode::Escape => review.cancel(),
+        KeyCode::Enter | KeyCode::NumpadEnter => {
+            if let Some(comment) = review.commit() {
+                log::info!("comment on {}: {}", comment.molecule, comment.text);
+            }
+            // Save straight away, so that nothing is lost if the session ends.
+            if let Ok(path) = std::env::var(REVIEW_ENV_VAR) {
+                let saved = review
+                    .comments_json()
+                    .map_err(|err| err.to_string())
+                    .and_then(|json| {
+                        std::fs::write(comments_path(&path), json).map_err(|err| err.to_string())
+                    });
+                if let Err(err) = saved {
+                    log::error!("could not save the review comments: {}", err);
+                }
+            }
+        }
+        KeyCode::Backspace => review.backspace(),
+        _ => match key.text.as_deref() {
+            Some(text) if !text.chars().any(char::is_control) => review.push_str(text),
+            _ => {}
+        },
+    }
+}
+
+// Locks or unlocks the component under the cursor.
+fn toggle_lock(
+    window: &Window,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+    cursor_pos: &PhysicalPosition<f64>,
+) {
+    let Some((id, _)) = pick_atom(window, renderer, world, cursor_pos) else {
+        return;
+    };
+    match world.component_mut(id) {
+        Some(component) => {
+            component.set_locked(!component.is_locked());
+            let state = if component.is_locked() {
+                "locked"
+            } else {
+                "unlocked"
+            };
+            log::info!("{} {}", state, component.name());
+        }
+        None => log::info!("the component is inside a locked assembly"),
+    }
+}
+
+// Steps the hydrogens drawn in the component under the cursor through each
+// policy and then back to following the assembly's.
+fn cycle_component_hydrogens(
+    window: &Window,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+    cursor_pos: &PhysicalPosition<f64>,
+) {
+    let Some((id, _)) = pick_atom(window, renderer, world, cursor_pos) else {
+        return;
+    };
+    let global = world.hydrogen_display();
+    let Some(component) = world.component_mut(id) else {
+        log::info!("the component is inside a locked assembly");
+        return;
+    };
+    let hydrogens = match component.hydrogen_display() {
+        None => Some(HydrogenDisplay::ALL[0]),
+        Some(hydrogens) if hydrogens.next() == HydrogenDisplay::ALL[0] => None,
+        Some(hydrogens) => Some(hydrogens.next()),
+    };
+    component.set_hydrogen_display(hydrogens);
+    match hydrogens {
+        Some(hydrogens) => log::info!("showing {} in {}", hydrogens.name(), component.name()),
+        None => log::info!(
+            "{} follows the assembly, showing {}",
+            component.name(),
+            global.name()
+        ),
+    }
+}
+
+// Pins the selected atoms, or releases them if they are all pinned already.
+fn pin_selection(selection: &Selection, world: &mut Assembly) {
+    let Some(id) = selection.component().filter(|_| !selection.is_empty()) else {
+        log::info!("select atoms to pin them");
+        return;
+    };
+    let Some(molecule) = world
+        .component_mut(id)
+        .and_then(|component| component.molecule_mut())
+    else {
+        log::info!("the molecule is locked");
+        return;
+    };
+    let targets: Vec<AtomSpecifier> = selection
+        .atoms()
+        .iter()
+        .filter(|atom| molecule.repr.find_atom(atom).is_some())
+        .cloned()
+        .collect();
+    let pinned = !targets.iter().all(|atom| {
+        molecule
+            .repr
+            .find_atom(atom)
+            .is_some_and(|node| node.pinned)
+    });
+    let count = targets.len();
+    molecule.add_edit(Edit::Pin(Pin { targets, pinned }));
+    log::info!(
+        "{} {} atoms",
+        if pinned { "pinned" } else { "released" },
+        count
+    );
+}
+
+fn report_selection(selection: &Selection) {
+    log::info!("{}", selection.summary());
+}
+
+// Lists the fields of the molecule on the timeline, marking the one chosen.
+fn report_inspector(panels: &Panels, world: &Assembly) {
+    let Some(id) = panels.timeline.component(world) else {
+        log::info!("there is no molecule to inspect");
+        return;
+    };
+    for row in panels.inspector.rows(world, id) {
+        log::info!(
+            "{} {}: {:.2}{}",
+            if row.chosen { ">" } else { " " },
+            row.label,
+            row.value,
+            row.unit
+        );
+    }
+    // A single selected atom of the molecule is inspected as well.
+    let atoms = panels.selection.atoms();
+    if panels.selection.component() == Some(id) && atoms.len() == 1 {
+        if let Some(atom) = atoms.iter().next() {
+            report_provenance(&panels.inspector, world, id, atom);
+        }
+    }
+}
+
+// Reports where an atom came from.
+fn report_provenance(
+    inspector: &Inspector,
+    world: &Assembly,
+    component: ComponentId,
+    atom: &AtomSpecifier,
+) {
+    match inspector.provenance(world, component, atom) {
+        Some(provenance) => log::info!("atom {} was {}", atom, provenance),
+        None => log::info!("where atom {} came from is not known", atom),
+    }
+}
+
+// Steps the chosen field of the inspector for the molecule on the timeline by
+// `steps`, or puts the molecule back where it was built if `None`.
+fn place_active_molecule(panels: &Panels, world: &mut Assembly, steps: Option<f32>) {
+    let Some(id) = panels.timeline.component(world) else {
+        return;
+    };
+    let placed = match steps {
+        Some(steps) => panels.inspector.step(world, id, steps).map(|value| {
+            let field = panels.inspector.field();
+            log::info!("{}: {:.2}{}", field.label(), value, field.unit());
+        }),
+        None => panels
+            .inspector
+            .reset(world, id)
+            .map(|()| log::info!("the molecule is back where it was built")),
+    };
+    match placed {
+        Ok(()) => {}
+        Err(InspectorError::Locked) => log::info!("the molecule is locked"),
+        Err(InspectorError::NotAMolecule) => {}
+    }
+}
+
+// Copies the selected atoms to the clipboard, and to the system clipboard as
+// text, so that they can be pasted into another window.
+fn copy_selection(panels: &mut Panels, world: &Assembly) {
+    let Some(fragment) = panels.clipboard.copy(&panels.selection, world) else {
+        log::info!("select some atoms to copy");
+        return;
+    };
+    platform::clipboard::set_text(&fragment.to_text());
+    log::info!("copied {}", fragment.atoms.name);
+}
+
+// Pastes what was copied, preferring a fragment on the system clipboard, which
+// may have come from another window.
+fn paste(panels: &mut Panels, world: &mut Assembly, as_component: bool) {
+    if let Some(fragment) =
+        platform::clipboard::get_text().and_then(|text| Fragment::from_text(&text))
+    {
+        panels.clipboard.set(fragment);
+    }
+    let pasted = if as_component {
+        panels.clipboard.paste_as_component(world).map(|id| {
+            panels.history.record_added(world, id);
+            format!("pasted as {:?}", id)
+        })
+    } else {
+        match panels.timeline.component(world) {
+            Some(id) => panels
+                .clipboard
+                .paste_into(world, id)
+                .map(|count| format!("pasted {} atoms into {:?}", count, id)),
+            None => Err(PasteError::NoMolecule),
+        }
+    };
+    match pasted {
+        Ok(message) => log::info!("{}", message),
+        Err(PasteError::Empty) => log::info!("nothing to paste"),
+        Err(PasteError::NoMolecule) => log::info!("no molecule to paste into"),
+        Err(PasteError::Locked) => log::info!("the molecule is locked"),
+    }
+}
+
+// Lists the commands run so far, marking the highlighted one.
+fn report_command_log(command_log: &CommandLog) {
+    for row in command_log.rows() {
+        let marker = if row.highlighted { ">" } else { " " };
+        match &row.script {
+            Some(script) => log::info!("{} {}. {}  `{}`", marker, row.number, row.label, script),
+            None => log::info!("{} {}. {}", marker, row.number, row.label),
+        }
+    }
+}
+
+// Runs the command highlighted in the command log again.
+fn rerun_command(command_log: &CommandLog, world: &mut Assembly) {
+    match command_log.rerun(world) {
+        Ok(id) => log::info!(
+            "ran {} again on {}",
+            command_log
+                .highlighted()
+                .map_or_else(String::new, |entry| entry.edit.name()),
+            world
+                .component(id)
+                .map_or("the molecule", |component| component.name())
+        ),
+        Err(RerunError::NothingHighlighted) => {
+            log::info!("press F2 to choose a command to run again")
+        }
+        Err(RerunError::MissingComponent) => {
+            log::info!("the molecule that the command was run on has been deleted")
+        }
+        Err(RerunError::Locked) => log::info!("the molecule is locked"),
+    }
+}
+
+// Copies the command highlighted in the command log to the system clipboard,
+// as a line of a build script.
+fn copy_command(command_log: &CommandLog) {
+    match (command_log.highlighted(), command_log.script()) {
+        (None, _) => log::info!("press F2 to choose a command to copy"),
+        (Some(_), None) => log::info!("the command cannot be written as a build script"),
+        (Some(_), Some(script)) => {
+            platform::clipboard::set_text(script);
+            log::info!("copied `{}`", script);
+        }
+    }
+}
+
+// Undoes the most recent change to the project, whether to the components of
+// the assembly or to a molecule's edits.
+fn undo(panels: &mut Panels, world: &mut Assembly) {
+    match panels.history.undo(world) {
+        Some(description) => {
+            panels.edit_history.clear_selection();
+            log::info!("undid {}", description);
+        }
+        None => log::info!("nothing to undo"),
+    }
+}
+
+fn redo(panels: &mut Panels, world: &mut Assembly) {
+    match panels.history.redo(world) {
+        Some(description) => {
+            panels.edit_history.clear_selection();
+            log::info!("redid {}", description);
+        }
+        None => log::info!("nothing to redo"),
+    }
+}
+
+// Deletes the component that the selected atoms belong to, or else the one
+// shown on the timeline, as a step that can be undone.
+fn delete_component(panels: &mut Panels, world: &mut Assembly) {
+    let Some(id) = panels
+        .selection
+        .component()
+        .or_else(|| panels.timeline.component(world))
+    else {
+        log::info!("select a component to delete");
+        return;
+    };
+    let name = world
+        .component(id)
+        .map_or_else(String::new, |component| component.name().to_owned());
+    let description = format!("delete {}", name);
+    match panels
+        .history
+        .execute(world, AssemblyCommand::Remove { id }, description)
+    {
+        Ok(()) => {
+            panels.selection.clear();
+            log::info!("deleted {}", name);
+        }
+        Err(CommandError::Locked) => log::info!("{} is locked", name),
+        Err(err) => log::warn!("could not delete {}: {:?}", name, err),
+    }
+}
+
+// Makes a pattern of copies of the molecule shown on the timeline, laid out
+// from the current view.
+fn add_pattern(
+    panels: &mut Panels,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+    shape: PatternShape,
+) {
+    let Some(seed) = panels.timeline.component(world) else {
+        log::info!("no molecule to make a pattern of");
+        return;
+    };
+    let Some(pose) = renderer.camera().destination() else {
+        return;
+    };
+    match panels.patterns.add(world, seed, shape, &pose) {
+        Ok(pattern) => log::info!("added {}", pattern.name()),
+        Err(err) => log::warn!("could not make the pattern: {:?}", err),
+    }
+}
+
+// Finds the atom under the cursor again, for the highlight and the tooltip.
+fn hover(
+    panels: &mut Panels,
+    window: &Window,
+    renderer: &mut Renderer,
+    world: &Assembly,
+    cursor_pos: &PhysicalPosition<f64>,
+) {
+    let hit = pointer_at(window, renderer, cursor_pos).hit(world);
+    panels.hover.update(hit);
+}
+
+// Where the pointer at `cursor_pos` is in the scene, for the tools.
+fn pointer_at(
+    window: &Window,
+    renderer: &mut Renderer,
+    cursor_pos: &PhysicalPosition<f64>,
+) -> Pointer {
+    let camera = renderer.camera();
+    Pointer {
+        ray: camera.get_ray_from(cursor_pos, &window.inner_size()),
+        focus: camera.pose().map_or(Vec3::zero(), |pose| pose.focus),
+    }
+}
+
+// Passes input to the active tool with `input`, which returns whether the
+// tool handled it.
+fn use_active_tool(
+    panels: &mut Panels,
+    window: &Window,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+    cursor_pos: &PhysicalPosition<f64>,
+    input: impl FnOnce(&mut dyn Tool, ToolContext) -> bool,
+) -> bool {
+    let pointer = pointer_at(window, renderer, cursor_pos);
+    let context = ToolContext {
+        assembly: world,
+        history: &mut panels.history,
+        selection: &mut panels.selection,
+        pointer: &pointer,
+    };
+    input(panels.tools.active_tool(), context)
+}
+
+// Switches to the tool `kind`, or back to rotating the view if it is already
+// active.  Tools that change the design are refused while reviewing.
+fn switch_tool(panels: &mut Panels, kind: ToolKind) {
+    if kind.edits() && refuse_in_review(&panels.review) {
+        return;
+    }
+    panels.tools.toggle(kind);
+}
+
+// Carries out the command chosen from the radial menu.
+fn choose_radial_item(panels: &mut Panels, world: &mut Assembly, item: RadialItem) {
+    let tool = match item {
+        RadialItem::PlaceAtom => ToolKind::PlaceAtom,
+        RadialItem::Bond => ToolKind::Bond,
+        RadialItem::Measure => ToolKind::Measure,
+        RadialItem::Delete => {
+            if !refuse_in_review(&panels.review) {
+                delete_component(panels, world);
+            }
+            return;
+        }
+        RadialItem::Undo => {
+            if !refuse_in_review(&panels.review) {
+                undo(panels, world);
+            }
+            return;
+        }
+    };
+    choose_tool(panels, tool);
+}
+
+// Switches to `kind`, unless it would change a design open for review.
+fn choose_tool(panels: &mut Panels, kind: ToolKind) {
+    if kind.edits() && refuse_in_review(&panels.review) {
+        return;
+    }
+    panels.tools.choose(kind);
+}
+
+// Carries out a click on the overlay: a press on one of its buttons, or the
+// release that ends it.  A row of the outliner is picked up when pressed, and
+// is selected if released where it was, or else dropped where it is released.
+fn click_overlay(
+    panels: &mut Panels,
+    world: &mut Assembly,
+    state: ElementState,
+    cursor_pos: &PhysicalPosition<f64>,
+) {
+    if state == ElementState::Released {
+        match panels.overlay.release(*cursor_pos) {
+            Some((Hit::OutlinerRow(node), Some(Hit::OutlinerRow(target)))) if target == node => {
+                panels.outliner.cancel_drag();
+                panels.outliner.click(node);
+            }
+            Some((Hit::OutlinerRow(_), released_on)) if panels.outliner.dragging().is_some() => {
+                let target = match released_on {
+                    Some(Hit::OutlinerRow(target)) => Some(target),
+                    Some(Hit::OutlinerEmpty) => None,
+                    _ => {
+                        panels.outliner.cancel_drag();
+                        return;
+                    }
+                };
+                if let Err(err) = panels
+                    .outliner
+                    .drop_onto(world, &mut panels.history, target)
+                {
+                    log::warn!("could not move the component: {:?}", err);
+                }
+            }
+            _ => {}
+        }
+        return;
+    }
+    match panels.overlay.press(*cursor_pos) {
+        Some(Hit::Tool(kind)) => choose_tool(panels, kind),
+        Some(Hit::OutlinerRow(node)) => {
+            // Moving a component is an edit, so in review mode rows can only
+            // be selected.
+            if panels.review.allows_editing() {
+                panels.outliner.begin_drag(node);
+            }
+        }
+        Some(Hit::OutlinerFold(id)) => panels.outliner.toggle_expanded(id),
+        Some(Hit::OutlinerVisibility(node)) => panels.outliner.toggle_visibility(world, node),
+        Some(Hit::OutlinerLock(node)) => {
+            if !refuse_in_review(&panels.review) {
+                panels.outliner.toggle_locked(world, node);
+            }
+        }
+        Some(Hit::Problem(component, edit)) => {
+            if let Some(problem) =
+                panels
+                    .diagnostics
+                    .choose(world, &mut panels.edit_history, component, edit)
+            {
+                select_problem(panels, world, &problem);
+            }
+        }
+        Some(Hit::OutlinerEmpty) | None => {}
+    }
+}
+
+// Selects the atoms of the problem just chosen, so that they are shown in the
+// view, and reports the history it is highlighted in.
+fn select_problem(panels: &mut Panels, world: &Assembly, problem: &DiagnosticRow) {
+    panels
+        .selection
+        .select(problem.component, problem.atoms(world));
+    report_edit_tree(&panels.edit_history, world);
+}
+
+// Pins the atom under the cursor in place during relaxation, or releases it.
+// This is recorded as an edit, so that it is part of the molecule's history.
+fn toggle_pin(
+    window: &Window,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+    cursor_pos: &PhysicalPosition<f64>,
+) {
+    let Some((id, atom)) = pick_atom(window, renderer, world, cursor_pos) else {
+        return;
+    };
+    let Some(molecule) = world
+        .component_mut(id)
+        .and_then(|component| component.molecule_mut())
+    else {
+        log::info!("the molecule is locked");
+        return;
+    };
+    let Some(pinned) = molecule.repr.find_atom(&atom).map(|node| !node.pinned) else {
+        return;
+    };
+
+    let propagated = molecule.add_edit(Edit::Pin(Pin {
+        targets: vec![atom.clone()],
+        pinned,
+    }));
+    log::info!(
+        "{} atom {}{}",
+        if pinned { "pinned" } else { "released" },
+        atom,
+        if propagated {
+            " and its symmetry mates"
+        } else {
+            ""
+        }
+    );
+}
+
+// Draws the periodic images of molecules with a unit cell one cell further
+// out, going back to none after the most.
+fn cycle_periodic_images(world: &mut Assembly) {
+    let range = (world.periodic_images() + 1) % (MAX_PERIODIC_IMAGES + 1);
+    world.set_periodic_images(range);
+    let periodic = world
+        .visible_molecules()
+        .iter()
+        .any(|(_, molecule, _)| molecule.repr.cell().is_some());
+    match (range, periodic) {
+        (0, _) => log::info!("periodic images hidden"),
+        (_, true) => log::info!("periodic images shown {} cells out", range),
+        (_, false) => log::info!(
+            "periodic images will be shown {} cells out, but no molecule has a unit cell",
+            range
+        ),
+    }
+}
+
+// Turns symmetry propagation on or off for the molecule shown in the
+// timeline, so that edits to one asymmetric unit are made to every symmetry
+// mate.
+fn toggle_symmetry_propagation(timeline: &Timeline, world: &mut Assembly) {
+    let Some(molecule) = timeline
+        .component(world)
+        .and_then(|id| world.component_mut(id))
+        .and_then(|component| component.molecule_mut())
+    else {
+        return;
+    };
+    let propagate = !molecule.propagates_symmetry();
+    molecule.set_propagate_symmetry(propagate);
+    log::info!(
+        "symmetry propagation is {} for {}",
+        if propagate { "on" } else { "off" },
+        molecule.name()
+    );
+}
+
+// Gives the component under the cursor a drive that spins it about the z axis
+// through its middle, or removes the drive it has.
+fn toggle_drive(
+    window: &Window,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+    cursor_pos: &PhysicalPosition<f64>,
+) {
+    // Half a turn per second.
+    const SPEED: f32 = std::f32::consts::PI;
+
+    let Some((id, _)) = pick_atom(window, renderer, world, cursor_pos) else {
+        return;
+    };
+    let Some(component) = world.component(id) else {
+        return;
+    };
+    let drive = match (component.drive(), component.molecule()) {
+        (Some(_), _) => None,
+        (None, Some(molecule)) => {
+            let bounds = molecule.repr.bounding_box();
+            let center = (bounds.min + bounds.max) * 0.5;
+            Some(Drive::rotation(center, Vec3::unit_z(), SPEED))
+        }
+        (None, None) => return,
+    };
+    let name = component.name().to_owned();
+
+    match world.set_drive(id, drive) {
+        Ok(()) if drive.is_some() => {
+            log::info!("{} is driven; press Alt+M to play the animation", name)
+        }
+        Ok(()) => log::info!("{} is no longer driven", name),
+        Err(err) => log::warn!("could not drive {}: {:?}", name, err),
+    }
+}
+
+// Writes the molecule shown in the timeline to a Z-matrix file named after it,
+// in the working directory.
+fn export_zmatrix(timeline: &Timeline, world: &Assembly) {
+    let Some(molecule) = timeline
+        .component(world)
+        .and_then(|component| world.component(component))
+        .and_then(|component| component.molecule())
+    else {
+        return;
+    };
+
+    let zmatrix = ZMatrix::from_molecule(molecule.name(), &molecule.repr);
+    let path = format!("{}.zmat", molecule.name());
+    match std::fs::write(&path, zmatrix.to_string()) {
+        Ok(()) => log::info!("wrote {} atoms to {}", zmatrix.atoms.len(), path),
+        Err(err) => log::warn!("could not write {}: {}", path, err),
+    }
+}
+
+// Renumbers the atoms of the molecule shown on the timeline breadth-first from
+// the atom under the cursor, or from its first atom, and follows the selection
+// and measurements to the new specifiers.
+fn reindex_active(
+    panels: &mut Panels,
+    window: &Window,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+    cursor_pos: &PhysicalPosition<f64>,
+) {
+    let Some(id) = panels.timeline.component(world) else {
+        return;
+    };
+    let root = pick_atom(window, renderer, world, cursor_pos)
+        .filter(|(component, _)| *component == id)
+        .map(|(_, atom)| atom);
+    let Some(molecule) = world
+        .component_mut(id)
+        .and_then(|component| component.molecule_mut())
+    else {
+        log::info!("the molecule is locked");
+        return;
+    };
+    match molecule.reindex(root.as_ref()) {
+        Ok(mapping) => {
+            log::info!("re-indexed {} atoms of {:?}", mapping.len(), id);
+            world.remap_atoms(id, &mapping);
+            panels.selection.remap(id, &mapping);
+        }
+        Err(err) => log::warn!("could not re-index {:?}: {}", id, err),
+    }
+}
+
+// Adds a block of crystal as a new component, centred where the camera is
+// looking.
+fn add_lattice(
+    lattice: &LatticePanel,
+    history: &mut ProjectHistory,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+) {
+    let Some(pose) = renderer.camera().destination() else {
+        return;
+    };
+    let molecule = lattice.build();
+    let bounds = molecule.repr.bounding_box();
+    let center = (bounds.min + bounds.max) * 0.5;
+    log::info!(
+        "added a block of {} with {} atoms",
+        lattice.kind.name(),
+        molecule.repr.graph.node_count()
+    );
+    let id = world.add_component(Component::from_molecule(
+        molecule,
+        Mat4::from_translation(pose.focus - center),
+    ));
+    history.record_added(world, id);
+}
+
+// Builds the polymer typed into the panel and adds it where the camera is
+// looking, then closes the panel.  If the sequence has a letter that is not a
+// residue, the panel is left open so that it can be corrected.
+fn add_polymer(
+    panel: &mut PolymerPanel,
+    history: &mut ProjectHistory,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+) {
+    let Some(pose) = renderer.camera().destination() else {
+        return;
+    };
+    if panel.polymer().sequence.is_empty() {
+        log::info!("type a sequence to build first");
+        return;
+    }
+    let molecule = match panel.build() {
+        Ok(molecule) => molecule,
+        Err(letter) => {
+            log::warn!(
+                "`{}` is not a residue of a {}",
+                letter,
+                panel.polymer().describe()
+            );
+            return;
+        }
+    };
+    let bounds = molecule.repr.bounding_box();
+    let center = (bounds.min + bounds.max) * 0.5;
+    log::info!(
+        "added a {} with {} atoms",
+        panel.polymer().describe(),
+        molecule.repr.graph.node_count()
+    );
+    let id = world.add_component(Component::from_molecule(
+        molecule,
+        Mat4::from_translation(pose.focus - center),
+    ));
+    history.record_added(world, id);
+    panel.close();
+}
+
+// Sets the order of each bond of the molecule shown on the timeline from its
+// length, e.g. after importing a PDB file that only lists which atoms are
+// bonded.
+fn assign_bond_orders_active(panels: &Panels, world: &mut Assembly) {
+    let Some(id) = panels.timeline.component(world) else {
+        log::info!("no molecule to assign bond orders to");
+        return;
+    };
+    let Some(molecule) = world
+        .component_mut(id)
+        .and_then(|component| component.molecule_mut())
+    else {
+        log::info!("the molecule is locked");
+        return;
+    };
+    let assignment = BondOrderAssignment::default();
+    let orders = assignment.assign(&molecule.repr);
+    let count = |order| orders.iter().filter(|(_, _, o)| *o == order).count();
+    let (doubles, triples) = (count(2), count(3));
+    molecule.add_edit(Edit::BondOrders(assignment));
+    log::info!(
+        "assigned the orders of {} bonds in {:?}: {} double and {} triple",
+        orders.len(),
+        id,
+        doubles,
+        triples
+    );
+}
+
+// Caps the dangling bonds of the molecule shown on the timeline, on the
+// atoms that match the passivation criteria.  If atoms of the molecule are
+// selected, only they are capped.
+fn passivate_active(panels: &mut Panels, world: &mut Assembly) {
+    let Some(id) = panels.timeline.component(world) else {
+        log::info!("no molecule to passivate");
+        return;
+    };
+    let Some(molecule) = world
+        .component_mut(id)
+        .and_then(|component| component.molecule_mut())
+    else {
+        log::info!("the molecule is locked");
+        return;
+    };
+    let selected = if panels.selection.component() == Some(id) {
+        panels.selection.atoms().iter().cloned().collect()
+    } else {
+        Vec::new()
+    };
+    let edit = panels.passivation.edit(selected);
+    let Edit::Passivation(criteria) = &edit else {
+        unreachable!("the passivation panel makes passivation edits");
+    };
+    let targets = criteria.targets(&molecule.repr);
+    if targets.is_empty() {
+        log::info!(
+            "{:?} has no dangling bonds to cap ({})",
+            id,
+            criteria.describe()
+        );
+        return;
+    }
+
+    let species = criteria.species;
+    let before = molecule.repr.graph.node_count();
+    molecule.add_edit(edit);
+    log::info!(
+        "capped {} atoms of {:?} with {} atoms of {:?}",
+        targets.len(),
+        id,
+        molecule.repr.graph.node_count() - before,
+        species
+    );
+}
+
+// Moves the selected atoms as a rigid unit, `right` and `up` angstroms across
+// the screen, and turns them `turn` degrees about the line of sight.  The move
+// is recorded as a transform edit, so it replays with the history.
+fn transform_selection(
+    selection: &Selection,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+    (right, up): (f32, f32),
+    turn: f32,
+) {
+    let Some(id) = selection.component().filter(|_| !selection.is_empty()) else {
+        log::info!("select atoms to move them");
+        return;
+    };
+    let (Some(pose), Some(transform)) =
+        (renderer.camera().destination(), world.world_transform(id))
+    else {
+        return;
+    };
+    let toward_camera = Vec3::new(
+        pose.yaw.sin() * pose.pitch.cos(),
+        pose.yaw.cos() * pose.pitch.cos(),
+        pose.pitch.sin(),
+    );
+    let across = Vec3::new(pose.yaw.cos(), -pose.yaw.sin(), 0.0);
+    let screen_up = across.cross(toward_camera);
+    // The edit works in the molecule's own space.
+    let inverse = transform.inversed();
+    let translation = inverse.transform_vec3(screen_up * up - across * right);
+    let axis = inverse.transform_vec3(toward_camera).normalized();
+    let rotation = Rotor3::from_angle_plane(turn.to_radians(), Bivec3::from_normalized_axis(axis));
+
+    let Some(molecule) = world
+        .component_mut(id)
+        .and_then(|component| component.molecule_mut())
+    else {
+        log::info!("the molecule is locked");
+        return;
+    };
+    let targets: Vec<AtomSpecifier> = selection.atoms().iter().cloned().collect();
+    let count = targets.len();
+    molecule.add_edit(Edit::TransformAtoms(TransformAtoms {
+        targets,
+        rotation,
+        translation,
+    }));
+    log::info!("moved {} atoms of {:?}", count, id);
+}
+
+// Turns the bond of the most recent dihedral measurement by `step` degrees,
+// by setting its torsion.  The four atoms must be in the same molecule.
+fn twist_measured_torsion(world: &mut Assembly, step: f32) {
+    let Some((id, atoms)) = world.measurements().iter().rev().find_map(|measurement| {
+        let [(id, a), (_, b), (_, c), (_, d)] = measurement.atoms() else {
+            return None;
+        };
+        measurement
+            .atoms()
+            .iter()
+            .all(|(other, _)| other == id)
+            .then(|| (*id, [a.clone(), b.clone(), c.clone(), d.clone()]))
+    }) else {
+        log::info!("measure a dihedral within a molecule to set its torsion");
+        return;
+    };
+    let Some(molecule) = world
+        .component_mut(id)
+        .and_then(|component| component.molecule_mut())
+    else {
+        log::info!("the molecule is locked");
+        return;
+    };
+    let Some(current) = torsion::dihedral(&atoms, &molecule.repr) else {
+        log::warn!("the measured atoms are no longer in {:?}", id);
+        return;
+    };
+    // Kept within -180 to 180 degrees, as dihedrals are measured.
+    let angle = (current + step + 180.0).rem_euclid(360.0) - 180.0;
+    molecule.add_edit(Edit::SetTorsion(SetTorsion { atoms, angle }));
+    log::info!("set the torsion of {:?} to {:.1}°", id, angle);
+}
+
+// Substitutes or deletes a random fraction of the atoms of the molecule shown
+// on the timeline, as the doping preset says.  If atoms of the molecule are
+// selected, only they can be chosen.
+fn dope_active(panels: &mut Panels, world: &mut Assembly) {
+    let Some(id) = panels.timeline.component(world) else {
+        log::info!("no molecule to dope");
+        return;
+    };
+    let Some(molecule) = world
+        .component_mut(id)
+        .and_then(|component| component.molecule_mut())
+    else {
+        log::info!("the molecule is locked");
+        return;
+    };
+    let selected: Vec<AtomSpecifier> = if panels.selection.component() == Some(id) {
+        panels.selection.atoms().iter().cloned().collect()
+    } else {
+        Vec::new()
+    };
+    let edit = panels
+        .doping
+        .edit(panels.tools.placement.element(), selected);
+    let Edit::Doping(doping) = &edit else {
+        unreachable!("the doping panel makes doping edits");
+    };
+    let changed = doping.targets(&molecule.repr).len();
+    if changed == 0 {
+        log::info!("too few atoms of {:?} to dope ({})", id, doping.describe());
+        return;
+    }
+
+    log::info!(
+        "doped {} atoms of {:?} ({})",
+        changed,
+        id,
+        doping.describe()
+    );
+    molecule.add_edit(edit);
+}
+
+// Limits passivation to the element of the atom under the cursor, or stops
+// limiting it to that element.
+fn toggle_passivated_element(
+    passivation: &mut PassivationPanel,
+    window: &Window,
+    renderer: &mut Renderer,
+    world: &Assembly,
+    cursor_pos: &PhysicalPosition<f64>,
+) {
+    let Some((id, atom)) = pick_atom(window, renderer, world, cursor_pos) else {
+        return;
+    };
+    let Some(node) = world
+        .component(id)
+        .and_then(|component| component.molecule())
+        .and_then(|molecule| molecule.repr.find_atom(&atom))
+    else {
+        return;
+    };
+    passivation.toggle_element(node.element);
+    log::info!("passivating with {}", passivation.criteria.describe());
+}
+
+// The plane that mirrors are made in: through `center`, upright on the
+// screen, so that the mirror image swaps left and right.
+fn mirror_plane(renderer: &mut Renderer, center: Vec3) -> Option<Plane> {
+    let pose = renderer.camera().destination()?;
+    let across = Vec3::new(pose.yaw.cos(), -pose.yaw.sin(), 0.0);
+    Some(Plane::new(center, across))
+}
+
+// Adds a mirror edit to the molecule shown on the timeline, reflecting its
+// atoms about its middle, and reports the stereocenters that are left
+// inverted.
+fn mirror_active(panels: &mut Panels, renderer: &mut Renderer, world: &mut Assembly) {
+    let Some(id) = panels.timeline.component(world) else {
+        log::info!("no molecule to mirror");
+        return;
+    };
+    let Some(transform) = world.world_transform(id) else {
+        return;
+    };
+    let Some(molecule) = world
+        .component_mut(id)
+        .and_then(|component| component.molecule_mut())
+    else {
+        log::info!("the molecule is locked");
+        return;
+    };
+    let bounds = molecule.repr.bounding_box();
+    let center = (bounds.min + bounds.max) * 0.5;
+    let Some(plane) = mirror_plane(renderer, transform.transform_point3(center)) else {
+        return;
+    };
+    // The edit works in the molecule's own space.
+    let inverse = transform.inversed();
+    let plane = Plane::new(
+        inverse.transform_point3(plane.point),
+        inverse.transform_vec3(plane.normal),
+    );
+
+    molecule.add_edit(Edit::Mirror(Mirror { plane }));
+    let inverted = molecule.repr.inverted_stereocenters();
+    match inverted.len() {
+        0 => log::info!("mirrored {:?}", id),
+        count => log::info!(
+            "mirrored {:?}, leaving {} inverted stereocenters",
+            id,
+            count
+        ),
+    }
+}
+
+// Reflects the component under the cursor about its middle, so that it is
+// drawn as its mirror image.
+fn mirror_component(
+    window: &Window,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+    history: &mut ProjectHistory,
+    cursor_pos: &PhysicalPosition<f64>,
+) {
+    let Some((id, _)) = pick_atom(window, renderer, world, cursor_pos) else {
+        log::info!("no molecule under the cursor to mirror");
+        return;
+    };
+    let Some(center) = world
+        .component(id)
+        .and_then(|component| component.molecule())
+        .zip(world.world_transform(id))
+        .map(|(molecule, transform)| {
+            let bounds = molecule.repr.bounding_box();
+            transform.transform_point3((bounds.min + bounds.max) * 0.5)
+        })
+    else {
+        return;
+    };
+    let Some(plane) = mirror_plane(renderer, center) else {
+        return;
+    };
+    let Some(transform) = world.component(id).map(Component::transform) else {
+        return;
+    };
+    let mirrored = world.mirror(id, &plane);
+    if mirrored.is_ok() {
+        history.record(
+            world,
+            AssemblyCommand::SetTransform { id, transform },
+            "mirror",
+        );
+    }
+    match mirrored {
+        Ok(()) if world.is_mirrored(id) == Some(true) => {
+            log::info!("{:?} is now drawn as its mirror image", id)
+        }
+        Ok(()) => log::info!("{:?} is no longer mirrored", id),
+        Err(err) => log::warn!("could not mirror {:?}: {:?}", id, err),
+    }
+}
+
+// Exports the selected atoms, and the bonds between them, to a file chosen in
+// the file dialog, in the format of its extension.  With `cap` set, the bonds
+// to the atoms left behind are capped with hydrogens.
+fn export_selection(selection: &Selection, world: &Assembly, cap: bool) {
+    let Some(molecule) = selection
+        .component()
+        .and_then(|component| world.component(component))
+        .and_then(|component| component.molecule())
+    else {
+        log::info!("select some atoms to export");
+        return;
+    };
+    let Some(path) = platform::file_dialog::save_file(
+        "Export Selection",
+        &format!("{}.pdb", molecule.name()),
+        EXPORT_FILTERS,
+    ) else {
+        return;
+    };
+    let Some(format) = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(ExportFormat::from_extension)
+    else {
+        log::warn!("cannot export as {}", path.display());
+        return;
+    };
+
+    let atoms: Vec<_> = EditContext::atoms(&molecule.repr)
+        .into_iter()
+        .filter(|atom| selection.atoms().contains(atom))
+        .collect();
+    let name = format!("{} atoms from {}", atoms.len(), molecule.name());
+    let mut import = export::extract_selection(&molecule.repr, &atoms, &name, cap);
+    export::transform(&mut import, molecule.local_transform());
+    let notes: Vec<String> =
+        if std::env::var(EXPORT_PROVENANCE_ENV_VAR).is_ok_and(|value| value == "1") {
+            atoms
+                .iter()
+                .map(|atom| {
+                    provenance::find(molecule, atom)
+                        .map_or_else(|| "unknown".to_owned(), |provenance| provenance.to_string())
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+    let written = export::write_annotated(&import, format, &notes)
+        .map_err(|err| err.to_string())
+        .and_then(|text| std::fs::write(&path, text).map_err(|err| err.to_string()));
+    match written {
+        Ok(()) => log::info!("wrote {} atoms to {}", import.atoms.len(), path.display()),
+        Err(err) => log::warn!("could not write {}: {}", path.display(), err),
+    }
+}
+
+// Checks that the selected part of a design is stable by shaking it, and
+// reports how far each trial settled from where it started.
+fn shake_selection(selection: &Selection, world: &Assembly) {
+    let Some(report) = selection.shake(world, &ShakeOptions::default()) else {
+        log::info!("select some atoms to shake");
+        return;
+    };
+    for (number, trial) in report.trials.iter().enumerate() {
+        log::info!(
+            "shake {}: settled {:.3} Å from the original ({:?} after {} iterations)",
+            number + 1,
+            trial.rmsd,
+            trial.relax.status,
+            trial.relax.iterations
+        );
+    }
+    if report.is_stable() {
+        log::info!("the structure returned to its original geometry every time");
+    } else {
+        log::warn!(
+            "the structure did not always return to its original geometry (worst {:.3} Å)",
+            report.worst_rmsd()
+        );
+    }
+}
+
+// Combines the selected edits into a composite edit.
+fn combine_selected_edits(history: &mut EditHistory, world: &mut Assembly) {
+    let name = history.next_composite_name(world);
+    match history.combine_selected(world, name.clone()) {
+        Ok(()) => {
+            log::info!("combined the selected edits into {}", name);
+            report_edit_tree(history, world);
+        }
+        Err(CombineError::NotAdjacent) => {
+            log::info!("select two or more edits next to each other to combine them")
+        }
+        Err(CombineError::Suppressed) => log::info!("suppressed edits cannot be combined"),
+    }
+}
+
+// Saves the highlighted composite edit to the library, and the library to its
+// file.
+fn save_composite(panels: &mut Panels, world: &Assembly) {
+    let Some(composite) = panels.edit_history.highlighted_composite(world) else {
+        log::info!("highlight a composite edit to save it");
+        return;
+    };
+    panels.composites.save(composite);
+    let saved = panels
+        .composites
+        .json()
+        .map_err(|err| err.to_string())
+        .and_then(|json| {
+            std::fs::write(COMPOSITE_LIBRARY_PATH, json).map_err(|err| err.to_string())
+        });
+    match saved {
+        Ok(()) => log::info!(
+            "saved {} to the library ({} composites); press F9 to apply it to the selected atoms",
+            composite.name,
+            panels.composites.composites().len()
+        ),
+        Err(err) => log::warn!("could not write {}: {}", COMPOSITE_LIBRARY_PATH, err),
+    }
+}
+
+// Applies the chosen composite edit from the library to the selected atoms.
+fn apply_composite(panels: &mut Panels, world: &mut Assembly) {
+    match panels.composites.apply(world, &panels.selection) {
+        Ok(count) => {
+            let name = panels.composites.chosen().map_or("", |c| c.name.as_str());
+            log::info!("applied {} {} times", name, count);
+        }
+        Err(ApplyCompositeError::Empty) => log::info!("no composite edits have been saved"),
+        Err(ApplyCompositeError::NoAtoms) => {
+            log::info!("select atoms to apply the composite edit to")
+        }
+        Err(ApplyCompositeError::Locked) => log::info!("the selected molecule is locked"),
+        Err(ApplyCompositeError::AnchorCount(error)) => log::info!(
+            "the composite edit is applied to {} atoms, but {} are selected",
+            error.expected,
+            error.found
+        ),
+    }
+}
+
+// Explains why edits were not deleted or moved, one dependency per line.
+fn log_conflicts(what: &str, conflicts: &[String]) {
+    log::info!(
+        "{}, as edits would lose atoms they refer to (hold Shift to force):",
+        what
+    );
+    for conflict in conflicts {
+        log::info!("  {}", conflict);
+    }
+}
+
+// Lists the edits as a tree, with their folders indented by depth.
+fn report_edit_tree(history: &EditHistory, world: &Assembly) {
+    for row in history.tree(world) {
+        let indent = "  ".repeat(row.depth);
+        match row.entry {
+            EditTreeEntry::Folder(folder) => log::info!(
+                "{}{} {} ({} edits){}",
+                indent,
+                if folder.collapsed { "+" } else { "-" },
+                folder.name,
+                folder.edit_count,
+                if folder.suppressed {
+                    ", suppressed"
+                } else {
+                    ""
+                }
+            ),
+            EditTreeEntry::Edit(edit) => log::info!(
+                "{}{}#{} {}{}{}",
+                indent,
+                match edit.collapsed {
+                    Some(true) => "+ ",
+                    Some(false) => "- ",
+                    None => "",
+                },
+                edit.number,
+                edit.label,
+                if edit.suppressed { " (suppressed)" } else { "" },
+                edit.problem
+                    .map(|problem| format!(" (failed: {})", problem))
+                    .unwrap_or_default()
+            ),
+            EditTreeEntry::Step(step) => log::info!("{}{}. {}", indent, step.number, step.label),
+        }
+    }
+}
+
+// Writes the atoms created by the selected edits to a fragment file, which
+// holds them as a single import edit.
+fn export_fragment(history: &EditHistory, world: &Assembly) {
+    if history.selected().is_empty() {
+        log::info!("no edits are selected");
+        return;
+    }
+    let Some(edit) = history.export_selected(world, "Fragment") else {
+        return;
+    };
+
+    let path = "fragment.json";
+    let json = match serde_json::to_string_pretty(&edit) {
+        Ok(json) => json,
+        Err(err) => {
+            log::warn!("could not export the fragment: {}", err);
+            return;
+        }
+    };
+    match std::fs::write(path, json) {
+        Ok(()) => log::info!("wrote the fragment to {}", path),
+        Err(err) => log::warn!("could not write {}: {}", path, err),
+    }
+}
+
+// Validates the assembly, and reports its summary and each problem found.
+fn report_validation(panel: &mut ValidationPanel, world: &Assembly) {
+    panel.run(world);
+    if let Some(summary) = panel.summary() {
+        log::info!("{}", summary);
+    }
+    for (index, row) in panel.rows(world).iter().enumerate() {
+        log::info!("  {}. {}", index + 1, row.description);
+    }
+}
+
+// Warns about anything wrong with the assembly before part of it is exported.
+fn warn_if_invalid(world: &Assembly) {
+    let report = scene::validate(world);
+    if !report.is_clean() {
+        log::warn!(
+            "the assembly has {} issues and a net charge of {:+}; press Alt+Q to review them",
+            report.issues.len(),
+            report.net_charge
+        );
+    }
+}
+
+// Looks for repeated parts in the molecule shown in the timeline.
+fn find_repeated_parts(offer: &mut PartsOffer, timeline: &Timeline, world: &Assembly) {
+    let Some(component) = timeline.component(world) else {
+        return;
+    };
+
+    if !offer.analyze(world, component) {
+        log::info!("no repeated parts found");
+        return;
+    }
+    for row in offer.rows() {
+        log::info!(
+            "{} copies of a {}-atom part ({} atoms saved by instancing)",
+            row.copies,
+            row.atoms,
+            row.saved_atoms
+        );
+    }
+    log::info!("press Alt+I to instance the repeated parts");
+}
+
+// The timeline's height is in logical pixels, so that it stays the same size
+// to the eye on any display.
+fn is_over_timeline(window: &Window, cursor_pos: &PhysicalPosition<f64>) -> bool {
+    let height = window.inner_size().height as f64;
+    cursor_pos.y >= height - TIMELINE_HEIGHT * window.scale_factor()
+}
+
+// The cursor's horizontal position as a fraction of the timeline's width.
+fn timeline_position(window: &Window, cursor_pos: &PhysicalPosition<f64>) -> f32 {
+    let width = window.inner_size().width.max(1) as f64;
+    (cursor_pos.x / width) as f32
+}
+
+// The modifier used for application shortcuts: command on macOS, control
+// everywhere else.
+fn is_command_key_held(modifiers: &ModifiersState) -> bool {
+    if cfg!(target_os = "macos") {
+        modifiers.super_key()
+    } else {
+        modifiers.control_key()
+    }
+}
+
+fn handle_search_key(
+    search: &mut SearchBox,
+    key: &KeyEvent,
+    world: &Assembly,
+    renderer: &mut Renderer,
+) {
+    match key.physical_key {
+        KeyCode::Escape => {
+            search.close();
+            return;
+        }
+        KeyCode::Enter | KeyCode::NumpadEnter => {
+            if let Some(hit) = search.choose(renderer.camera()) {
+                log::info!("search: jumped to {}", hit.label);
+            }
+            return;
+        }
+        KeyCode::ArrowUp => search.move_highlight(-1),
+        KeyCode::ArrowDown => search.move_highlight(1),
+        KeyCode::Backspace => search.backspace(world),
+        _ => match key.text.as_deref() {
+            Some(text) if !text.chars().any(char::is_control) => search.push_str(text, world),
+            _ => return,
+        },
+    }
+
+    log::info!(
+        "search {:?}: {} results{}",
+        search.query(),
+        search.hits().len(),
+        search
+            .highlighted()
+            .map(|hit| format!(", highlighted {}", hit.label))
+            .unwrap_or_default()
+    );
+}
+
+fn handle_polymer_key(
+    panel: &mut PolymerPanel,
+    history: &mut ProjectHistory,
+    key: &KeyEvent,
+    renderer: &mut Renderer,
+    world: &mut Assembly,
+) {
+    match key.physical_key {
+        KeyCode::Escape => {
+            panel.close();
+            return;
+        }
+        KeyCode::Enter | KeyCode::NumpadEnter => {
+            add_polymer(panel, history, renderer, world);
+            return;
+        }
+        KeyCode::Tab => panel.cycle_kind(),
+        KeyCode::Backspace => panel.backspace(),
+        _ => match key.text.as_deref() {
+            Some(text) if !text.chars().any(char::is_control) => panel.push_str(text),
+            _ => return,
+        },
+    }
+
+    log::info!(
+        "{} {:?}",
+        panel.polymer().describe(),
+        panel.polymer().sequence
+    );
+}
+
+// Moves through the outliner's rows, or changes the selected component, as
+// `key` says.  Moving and locking components are edits, which review mode
+// refuses; hiding them only changes the view.
+fn handle_outliner_key(
+    panels: &mut Panels,
+    key: &KeyEvent,
+    modifiers: ModifiersState,
+    world: &mut Assembly,
+) {
+    let outliner = &mut panels.outliner;
+    outliner.prune(world);
+    match key.physical_key {
+        KeyCode::Escape if outliner.dragging().is_some() => outliner.cancel_drag(),
+        KeyCode::Escape => outliner.close(),
+        KeyCode::ArrowUp => outliner.select_next(world, false),
+        KeyCode::ArrowDown => outliner.select_next(world, true),
+        KeyCode::ArrowLeft | KeyCode::ArrowRight => {
+            if let Some(id) = outliner.selected() {
+                outliner.set_expanded(id, key.physical_key == KeyCode::ArrowRight);
+            }
+        }
+        KeyCode::KeyV => {
+            if let Some(node) = outliner.selected_node(world) {
+                outliner.toggle_visibility(world, node);
+            }
+        }
+        KeyCode::KeyL => {
+            if refuse_in_review(&panels.review) {
+                return;
+            }
+            if let Some(node) = outliner.selected_node(world) {
+                outliner.toggle_locked(world, node);
+            }
+        }
+        KeyCode::KeyM => {
+            if refuse_in_review(&panels.review) {
+                return;
+            }
+            if outliner.dragging().is_none() {
+                if let Some(node) = outliner.selected_node(world) {
+                    outliner.begin_drag(node);
+                }
+            } else {
+                // Shift drops the component at the end of the root assembly.
+                let target = if modifiers.shift_key() {
+                    None
+                } else {
+                    outliner.selected_node(world)
+                };
+                if let Err(err) = outliner.drop_onto(world, &mut panels.history, target) {
+                    log::warn!("could not move the component: {:?}", err);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn run(event_loop: EventLoop<()>, mut window: Option<Window>, embed: EmbeddedViewer) {
+    // The event handling loop is terminated when the main window is closed.
+    // We can trigger this by dropping the window, so we wrap it in the Option
+    // type.  This is a bit of a hack, but it works.  We require that we are
+    // called with a valid window, however.
+    window.as_ref().expect("window should exist");
+
+    // On mobile platforms the window is destroyed when the application is
+    // suspended, so we need to be able to drop these resources and recreate
+    // as necessary.
+    let mut renderer: Option<Renderer> = None;
+    let mut gpu_resources: Option<Rc<GlobalRenderResources>> = None;
+    let mut world: Option<Assembly> = None;
+    let mut panels = Panels {
+        embed,
+        turntable: IdleTurntable::from_env(),
+        ..Default::default()
+    };
+    if let Ok(json) = std::fs::read_to_string(COMPOSITE_LIBRARY_PATH) {
+        if let Err(err) = panels.composites.load(&json) {
+            log::warn!("ignoring unreadable {}: {}", COMPOSITE_LIBRARY_PATH, err);
+        }
+    }
+
+    // Run the event loop.
+    let mut running = false;
+    event_loop.run(move |event, _, control_flow| {
+        // When we are done handling this event, suspend until the next event.
+        *control_flow = ControlFlow::Wait;
+
+        if is_user_input(&event) {
+            panels.turntable.input();
+        }
+
+        // On some platforms, namely wasm32 + webgl2, the window is not yet
+        // ready to create the rendering surface when Event::Resumed is
+        // received.  We therefore just record the fact that the we're in the
+        // running state.
+        match event {
+            Event::Resumed => {
+                // Called on iOS or Android when the application is brought
+                // into focus.  The GPU resources need to be reallocated on
+                // resume.
+                running = true;
+            }
+            Event::Suspended => {
+                // Called on iOS or Android when the application is sent to
+                // the background.  We preemptively destroy the window and any
+                // used GPU resources as the system might take them from us.
+                running = false;
+                world = None;
+                gpu_resources = None;
+                renderer = None;
+                window = None;
+            }
+
+            // The event system does not expose the cursor position on-demand.
+            // We track all the mouse movement events to make this easier to
+            // access later.
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } => {
+                panels.input.cursor_pos = position;
+            }
+            Event::WindowEvent {
+                event: WindowEvent::ModifiersChanged(new_modifiers),
+                ..
+            } => {
+                panels.input.modifiers = new_modifiers.state();
+            }
+
+            _ => (),
+        }
+
+        // Check that we've received Event::Resumed, and the window's inner
+        // dimensions are defined.  (Prevents a panic on wasm32 + webgl2).
+        if running && renderer.is_none() {
+            let size = window.as_ref().unwrap().inner_size();
+            if size.width > 0 && size.height > 0 {
+                futures::executor::block_on(async {
+                    let (mut r, g, w) = resume_renderer(
+                        window.as_ref().unwrap(),
+                        safe_mode(&panels.input.modifiers),
+                    )
+                    .await;
+                    r.set_camera(ArcballCamera::new(Vec3::zero(), 100.0, 1.0));
+                    renderer = Some(r);
+                    gpu_resources = Some(g);
+                    world = Some(w);
+                    panels.history = ProjectHistory::default();
+                    if let Ok(path) = std::env::var(BUILD_ENV_VAR) {
+                        if let Some(assembly) = open_build_script(&path) {
+                            world = Some(assembly);
+                        }
+                    }
+                    if let Ok(path) = std::env::var(PROJECT_ENV_VAR) {
+                        if let Some(assembly) = open_project(&path, &mut panels) {
+                            world = Some(assembly);
+                        }
+                    }
+                    if let Ok(path) = std::env::var(REVIEW_ENV_VAR) {
+                        if let Some(assembly) = open_for_review(&path, &mut panels.review) {
+                            world = Some(assembly);
+                        }
+                    }
+                    // Show the design as it was left, if that was saved with it.
+                    if let (Some(renderer), Some(world)) = (&mut renderer, &world) {
+                        let editing = panels.review.allows_editing();
+                        if layout::restore(&mut panels, renderer.camera(), world, editing) {
+                            log::info!("restored the saved view of {}", world.name());
+                        }
+                    }
+                });
+            }
+        }
+
+        // Show the embedded viewer's structure once it has been downloaded
+        // and there is a scene to show it in.
+        if let (Some(renderer), Some(world)) = (&mut renderer, &mut world) {
+            if let Some(download) = panels.embed.take_download() {
+                show_embedded_structure(&panels.embed, download, renderer, world);
+            }
+        }
+
+        // Attach a background import once it finishes, and check on it again
+        // shortly while it runs, so that its progress is shown.
+        if let (Some(window), Some(renderer), Some(world)) = (&window, &mut renderer, &mut world) {
+            finish_import(
+                &mut panels.import,
+                &mut panels.history,
+                window,
+                renderer,
+                world,
+            );
+        }
+        if panels.import.is_busy() {
+            wake_after(control_flow, IMPORT_POLL_INTERVAL);
+        }
+
+        // Carry out the commands chosen from the menubar.
+        for command in menubar::take_commands() {
+            match command {
+                AppCommand::ToggleMeasurements => switch_tool(&mut panels, ToolKind::Measure),
+            }
+        }
+
+        // Open the radial menu once a press has been held long enough, and
+        // check again when it would be.
+        panels.radial_menu.update();
+        if let Some(wait) = panels.radial_menu.time_to_open() {
+            wake_after(control_flow, wait);
+        }
+
+        // Handle events.
+        handle_event(
+            event,
+            control_flow,
+            &mut window,
+            &mut renderer,
+            &mut gpu_resources,
+            &mut world,
+            &mut panels,
+        );
+    })
+}
+
+// The embedded viewer, if the page asks for one, with the download of its
+// structure started.  Only the web build can be embedded.
+#[cfg(target_arch = "wasm32")]
+fn embedded_viewer(event_loop: &EventLoop<()>) -> EmbeddedViewer {
+    let Some(url) = platform::page::query_parameter(ui::embed::STRUCTURE_PARAMETER) else {
+        return EmbeddedViewer::default();
+    };
+    let viewer = EmbeddedViewer::new(url.clone());
+    let download = viewer.download();
+    let proxy = event_loop.create_proxy();
+    platform::page::fetch_text(&url, move |result| {
+        *download.borrow_mut() = Some(result);
+        // Wake the event loop, so that the structure is shown.
+        let _ = proxy.send_event(());
+    });
+    viewer
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn embedded_viewer(_event_loop: &EventLoop<()>) -> EmbeddedViewer {
+    EmbeddedViewer::default()
+}
+
+pub fn start(event_loop_builder: &mut EventLoopBuilder<()>) {
+    let menu = menubar::setup_menu_bar(event_loop_builder);
+    let event_loop = event_loop_builder.build();
+    let embed = embedded_viewer(&event_loop);
+
+    // Create the main window.
+    let window = match WindowBuilder::new().with_title(APP_NAME).build(&event_loop) {
+        Err(e) => {
+            println!("Failed to create window: {}", e);
+            std::process::exit(1);
+        }
+        Ok(window) => window,
+    };
+
+    // Add the menu bar to the window / application instance, using native
+    // APIs.  The embedded viewer has no menus.
+    if !embed.is_active() {
+        menubar::attach_menu_bar(&window, &menu);
+    }
 
     #[cfg(not(target_arch = "wasm32"))]
     {
         #[cfg(not(target_os = "android"))]
         {
-            env_logger::init();
+            let logger = env_logger::Logger::from_default_env();
+            let max_level = logger.filter();
+            ui::status::init_logger(logger, max_level);
         }
         #[cfg(target_os = "android")]
         {
@@ -369,7 +4245,7 @@ pub fn start(event_loop_builder: &mut EventLoopBuilder<()>) {
                 android_logger::Config::default().with_max_level(log::LevelFilter::Trace),
             );
         }
-        run(event_loop, Some(window));
+        run(event_loop, Some(window), embed);
     }
     #[cfg(target_arch = "wasm32")]
     {
@@ -401,7 +4277,7 @@ pub fn start(event_loop_builder: &mut EventLoopBuilder<()>) {
                     .ok()
             })
             .expect("Couldn't append canvas to document body.");
-        run(event_loop, Some(window));
+        run(event_loop, Some(window), embed);
     }
 }
 
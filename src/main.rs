@@ -4,7 +4,13 @@
 
 #[cfg(not(any(target_os = "android")))]
 pub fn main() {
+    use atomcad::thumbnail::{self, THUMBNAIL_COMMAND};
     use winit::event_loop::EventLoopBuilder;
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some(THUMBNAIL_COMMAND) {
+        std::process::exit(thumbnail::run(args.into_iter().skip(1)));
+    }
     atomcad::start(&mut EventLoopBuilder::new())
 }
 
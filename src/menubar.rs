@@ -2,8 +2,9 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this file,
 // You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use crate::platform::menubar::{attach_menu, configure_event_loop, Menu};
+use crate::platform::menubar::{attach_menu, configure_event_loop, poll_commands, Menu};
 use crate::APP_NAME;
+use std::sync::Mutex;
 use winit::event_loop::EventLoopBuilder;
 use winit::window::Window;
 
@@ -20,48 +21,56 @@ pub struct MenuSpec {
 
 impl Default for MenuSpec {
     fn default() -> Self {
-        MenuSpec::new(APP_NAME).and_then(MenuItem::SubMenu(
-            MenuSpec::new("")
-                .and_then(MenuItem::new(
-                    &format!("About {}", APP_NAME),
+        MenuSpec::new(APP_NAME)
+            .and_then(MenuItem::SubMenu(
+                MenuSpec::new("")
+                    .and_then(MenuItem::new(
+                        &format!("About {}", APP_NAME),
+                        MenuShortcut::None,
+                        MenuAction::System(SystemAction::LaunchAboutWindow),
+                    ))
+                    .and_then(MenuItem::Separator)
+                    .and_then(MenuItem::new(
+                        "Settings...",
+                        MenuShortcut::System(SystemShortcut::Preferences),
+                        MenuAction::System(SystemAction::LaunchPreferences),
+                    ))
+                    .and_then(MenuItem::Separator)
+                    .and_then(MenuItem::new(
+                        "Services",
+                        MenuShortcut::None,
+                        MenuAction::System(SystemAction::ServicesMenu),
+                    ))
+                    .and_then(MenuItem::Separator)
+                    .and_then(MenuItem::new(
+                        &format!("Hide {}", APP_NAME),
+                        MenuShortcut::System(SystemShortcut::HideApp),
+                        MenuAction::System(SystemAction::HideApp),
+                    ))
+                    .and_then(MenuItem::new(
+                        "Hide Others",
+                        MenuShortcut::System(SystemShortcut::HideOthers),
+                        MenuAction::System(SystemAction::HideOthers),
+                    ))
+                    .and_then(MenuItem::new(
+                        "Show All",
+                        MenuShortcut::None,
+                        MenuAction::System(SystemAction::ShowAll),
+                    ))
+                    .and_then(MenuItem::Separator)
+                    .and_then(MenuItem::new(
+                        &format!("Quit {}", APP_NAME),
+                        MenuShortcut::System(SystemShortcut::QuitApp),
+                        MenuAction::System(SystemAction::Terminate),
+                    )),
+            ))
+            .and_then(MenuItem::SubMenu(MenuSpec::new("Tools").and_then(
+                MenuItem::new(
+                    "Measurements",
                     MenuShortcut::None,
-                    MenuAction::System(SystemAction::LaunchAboutWindow),
-                ))
-                .and_then(MenuItem::Separator)
-                .and_then(MenuItem::new(
-                    "Settings...",
-                    MenuShortcut::System(SystemShortcut::Preferences),
-                    MenuAction::System(SystemAction::LaunchPreferences),
-                ))
-                .and_then(MenuItem::Separator)
-                .and_then(MenuItem::new(
-                    "Services",
-                    MenuShortcut::None,
-                    MenuAction::System(SystemAction::ServicesMenu),
-                ))
-                .and_then(MenuItem::Separator)
-                .and_then(MenuItem::new(
-                    &format!("Hide {}", APP_NAME),
-                    MenuShortcut::System(SystemShortcut::HideApp),
-                    MenuAction::System(SystemAction::HideApp),
-                ))
-                .and_then(MenuItem::new(
-                    "Hide Others",
-                    MenuShortcut::System(SystemShortcut::HideOthers),
-                    MenuAction::System(SystemAction::HideOthers),
-                ))
-                .and_then(MenuItem::new(
-                    "Show All",
-                    MenuShortcut::None,
-                    MenuAction::System(SystemAction::ShowAll),
-                ))
-                .and_then(MenuItem::Separator)
-                .and_then(MenuItem::new(
-                    &format!("Quit {}", APP_NAME),
-                    MenuShortcut::System(SystemShortcut::QuitApp),
-                    MenuAction::System(SystemAction::Terminate),
-                )),
-        ))
+                    MenuAction::App(AppCommand::ToggleMeasurements),
+                ),
+            )))
     }
 }
 
@@ -146,6 +155,7 @@ impl std::ops::BitOr for ModifierKeys {
 // invoked.
 pub enum MenuAction {
     System(SystemAction),
+    App(AppCommand),
 }
 
 pub enum SystemAction {
@@ -158,6 +168,47 @@ pub enum SystemAction {
     Terminate,
 }
 
+// A command of the application itself.  When its menu item is chosen, the
+// platform's menubar queues it for the event loop to carry out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AppCommand {
+    ToggleMeasurements,
+}
+
+#[allow(dead_code)] // Not used by all platforms.
+impl AppCommand {
+    const ALL: [AppCommand; 1] = [AppCommand::ToggleMeasurements];
+
+    // A number identifying the command to the platform's menus.  Zero is left
+    // for items that are not commands.
+    pub fn tag(self) -> isize {
+        self as isize + 1
+    }
+
+    pub fn from_tag(tag: isize) -> Option<Self> {
+        Self::ALL.into_iter().find(|command| command.tag() == tag)
+    }
+}
+
+static COMMANDS: Mutex<Vec<AppCommand>> = Mutex::new(Vec::new());
+
+// Queues a command chosen from the menubar.
+#[allow(dead_code)] // Not used by all platforms.
+pub fn queue_command(command: AppCommand) {
+    if let Ok(mut commands) = COMMANDS.lock() {
+        commands.push(command);
+    }
+}
+
+// The commands chosen from the menubar since this was last called, in order.
+pub fn take_commands() -> Vec<AppCommand> {
+    poll_commands();
+    COMMANDS
+        .lock()
+        .map(|mut commands| std::mem::take(&mut *commands))
+        .unwrap_or_default()
+}
+
 pub fn setup_menu_bar<T: 'static>(event_loop_builder: &mut EventLoopBuilder<T>) -> Menu {
     configure_event_loop(event_loop_builder)
 }
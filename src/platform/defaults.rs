@@ -17,6 +17,110 @@ pub mod menubar {
     }
 
     pub fn attach_menu(_window: &Window, _menu: &Menu) {}
+
+    pub fn poll_commands() {}
+}
+
+// Native file dialogs, shown through rfd, which supports each of the desktop
+// platforms.
+#[cfg(not(any(target_family = "wasm", target_os = "android", target_os = "ios")))]
+pub mod file_dialog {
+    use std::path::PathBuf;
+
+    // Asks the user to choose a file to open, offering only those with one of
+    // the extensions of `filters` (each a name and its extensions).  Blocks
+    // until the dialog is closed, and returns `None` if it was cancelled.
+    pub fn pick_file(title: &str, filters: &[(&str, &[&str])]) -> Option<PathBuf> {
+        let mut dialog = rfd::FileDialog::new().set_title(title);
+        for (name, extensions) in filters {
+            dialog = dialog.add_filter(name, extensions);
+        }
+        dialog.pick_file()
+    }
+
+    // Asks the user where to save a file, suggesting `file_name`.  Returns
+    // `None` if the dialog was cancelled.
+    pub fn save_file(title: &str, file_name: &str, filters: &[(&str, &[&str])]) -> Option<PathBuf> {
+        let mut dialog = rfd::FileDialog::new()
+            .set_title(title)
+            .set_file_name(file_name);
+        for (name, extensions) in filters {
+            dialog = dialog.add_filter(name, extensions);
+        }
+        dialog.save_file()
+    }
+}
+
+// The system clipboard, through arboard, which supports each of the desktop
+// platforms.
+#[cfg(not(any(target_family = "wasm", target_os = "android", target_os = "ios")))]
+pub mod clipboard {
+    use std::cell::RefCell;
+
+    thread_local! {
+        // On X11, copied text is handed out by the application that copied
+        // it for as long as its clipboard handle lives, so the handle is kept.
+        static CLIPBOARD: RefCell<Option<arboard::Clipboard>> = const { RefCell::new(None) };
+    }
+
+    fn with_clipboard<T>(f: impl FnOnce(&mut arboard::Clipboard) -> Option<T>) -> Option<T> {
+        CLIPBOARD.with(|clipboard| {
+            let mut clipboard = clipboard.borrow_mut();
+            if clipboard.is_none() {
+                *clipboard = arboard::Clipboard::new()
+                    .map_err(|err| log::warn!("could not open the clipboard: {}", err))
+                    .ok();
+            }
+            f(clipboard.as_mut()?)
+        })
+    }
+
+    // Puts `text` on the clipboard.  Returns whether it could.
+    pub fn set_text(text: &str) -> bool {
+        with_clipboard(|clipboard| {
+            clipboard
+                .set_text(text)
+                .map_err(|err| log::warn!("could not copy to the clipboard: {}", err))
+                .ok()
+        })
+        .is_some()
+    }
+
+    // The text on the clipboard, if it holds text.
+    pub fn get_text() -> Option<String> {
+        with_clipboard(|clipboard| clipboard.get_text().ok())
+    }
+}
+
+// For platforms without access to the system clipboard yet.  Nothing is
+// copied to it, and it never holds any text.
+#[allow(dead_code)]
+pub mod no_clipboard {
+    pub fn set_text(_text: &str) -> bool {
+        false
+    }
+
+    pub fn get_text() -> Option<String> {
+        None
+    }
+}
+
+// For platforms without a file dialog yet.  Never gives a file.
+#[allow(dead_code)]
+pub mod no_file_dialog {
+    use std::path::PathBuf;
+
+    pub fn pick_file(_title: &str, _filters: &[(&str, &[&str])]) -> Option<PathBuf> {
+        None
+    }
+
+    pub fn save_file(
+        _title: &str,
+        _file_name: &str,
+        _filters: &[(&str, &[&str])],
+    ) -> Option<PathBuf> {
+        None
+    }
 }
 
 // End of File
@@ -5,5 +5,7 @@
 // FIXME: Should use the UiKit APIs to setup a hamburger menu for our
 // "menubar."
 pub use super::defaults::menubar;
+pub use super::defaults::no_clipboard as clipboard;
+pub use super::defaults::no_file_dialog as file_dialog;
 
 // End of File
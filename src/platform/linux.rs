@@ -3,6 +3,8 @@
 // You can obtain one at http://mozilla.org/MPL/2.0/.
 
 // FIXME: Should use the gtk APIs to setup the menubar for the main window(s).
+pub use super::defaults::clipboard;
+pub use super::defaults::file_dialog;
 pub use super::defaults::menubar;
 
 // End of File
@@ -2,6 +2,8 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this file,
 // You can obtain one at http://mozilla.org/MPL/2.0/.
 
+pub use super::defaults::clipboard;
+pub use super::defaults::file_dialog;
 pub use crate::platform_impl::menubar;
 
 // End of File
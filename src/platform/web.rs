@@ -4,5 +4,52 @@
 
 // FIXME: We should investigate using egui to create a menubar on web.
 pub use super::defaults::menubar;
+// FIXME: rfd can show a file dialog on web, but only asynchronously.
+pub use super::defaults::no_file_dialog as file_dialog;
+// FIXME: The web clipboard API is asynchronous, and only allowed while
+// handling input.
+pub use super::defaults::no_clipboard as clipboard;
+
+// Reading the page's query string and downloading from the page's server,
+// for the embedded viewer.
+pub mod page {
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    // The value of the query parameter `name` of the page's URL, if it has
+    // one.
+    pub fn query_parameter(name: &str) -> Option<String> {
+        let search = web_sys::window()?.location().search().ok()?;
+        web_sys::UrlSearchParams::new_with_str(&search)
+            .ok()?
+            .get(name)
+    }
+
+    // Downloads the text at `url` in the background, passing it (or why it
+    // could not be downloaded) to `done` when finished.
+    pub fn fetch_text(url: &str, done: impl FnOnce(Result<String, String>) + 'static) {
+        let url = url.to_owned();
+        wasm_bindgen_futures::spawn_local(async move {
+            done(fetch(&url).await);
+        });
+    }
+
+    async fn fetch(url: &str) -> Result<String, String> {
+        let window = web_sys::window().ok_or("there is no browser window")?;
+        let response: web_sys::Response = JsFuture::from(window.fetch_with_str(url))
+            .await
+            .and_then(|response| response.dyn_into())
+            .map_err(|err| format!("{:?}", err))?;
+        if !response.ok() {
+            return Err(format!("the server answered {}", response.status()));
+        }
+        let text = response.text().map_err(|err| format!("{:?}", err))?;
+        JsFuture::from(text)
+            .await
+            .map_err(|err| format!("{:?}", err))?
+            .as_string()
+            .ok_or_else(|| "the response is not text".to_owned())
+    }
+}
 
 // End of File
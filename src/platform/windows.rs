@@ -4,6 +4,8 @@
 
 // FIXME: Should use the win32 APIs to setup the menubar for the main
 //        window(s).
+pub use super::defaults::clipboard;
+pub use super::defaults::file_dialog;
 pub use crate::platform_impl::windows::menubar;
 
 // End of File
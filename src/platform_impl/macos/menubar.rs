@@ -2,16 +2,19 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this file,
 // You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use objc::declare::ClassDecl;
 use objc::rc::autoreleasepool;
-use objc::runtime::Object;
+use objc::runtime::{Class, Object, Sel};
 use objc::{class, msg_send, sel, sel_impl};
+use std::sync::Once;
 
 use winit::{
     event_loop::EventLoopBuilder, platform::macos::EventLoopBuilderExtMacOS, window::Window,
 };
 
 use crate::menubar::{
-    MenuAction, MenuItem, MenuShortcut, MenuSpec, ModifierKeys, SystemAction, SystemShortcut,
+    queue_command, AppCommand, MenuAction, MenuItem, MenuShortcut, MenuSpec, ModifierKeys,
+    SystemAction, SystemShortcut,
 };
 
 fn nsstring(s: &str) -> *mut Object {
@@ -27,9 +30,36 @@ fn nsstring(s: &str) -> *mut Object {
     }
 }
 
+// Called by the menu items of the application's own commands, which carry the
+// command's tag.
+extern "C" fn menu_command(_this: &Object, _cmd: Sel, sender: *mut Object) {
+    let tag: isize = unsafe { msg_send![sender, tag] };
+    if let Some(command) = AppCommand::from_tag(tag) {
+        queue_command(command);
+    }
+}
+
+// The class of the object that the command menu items are sent to.
+fn command_target_class() -> &'static Class {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| {
+        let mut decl = ClassDecl::new("AtomCADMenuTarget", class!(NSObject))
+            .expect("The menu target class shouldn't already be registered");
+        unsafe {
+            decl.add_method(
+                sel!(menuCommand:),
+                menu_command as extern "C" fn(&Object, Sel, *mut Object),
+            );
+        }
+        decl.register();
+    });
+    class!(AtomCADMenuTarget)
+}
+
 unsafe fn build_menu(
     _app: *mut Object,
     services_menu: *mut Object,
+    command_target: *mut Object,
     menu_spec: &MenuSpec,
 ) -> *mut Object {
     // Create root menu bar.
@@ -46,7 +76,12 @@ unsafe fn build_menu(
             MenuItem::Entry(title, shortcut, action) => {
                 let title = nsstring(title);
                 let mut is_service_menu = false;
+                let command = match action {
+                    MenuAction::App(command) => Some(*command),
+                    MenuAction::System(_) => None,
+                };
                 let action = match action {
+                    MenuAction::App(_) => Some(sel!(menuCommand:)),
                     MenuAction::System(action) => match action {
                         SystemAction::LaunchAboutWindow => {
                             Some(sel!(orderFrontStandardAboutPanel:))
@@ -121,6 +156,10 @@ unsafe fn build_menu(
                     let _: () = msg_send![item, setKeyEquivalentModifierMask: modifiermask];
                 }
                 item = msg_send![item, autorelease];
+                if let Some(command) = command {
+                    let _: () = msg_send![item, setTarget: command_target];
+                    let _: () = msg_send![item, setTag: command.tag()];
+                }
                 if is_service_menu {
                     let _: () = msg_send![item, setSubmenu: services_menu];
                 }
@@ -130,7 +169,7 @@ unsafe fn build_menu(
                 let item: *mut Object = msg_send![class![NSMenuItem], alloc];
                 let item: *mut Object = msg_send![item, init];
                 let item: *mut Object = msg_send![item, autorelease];
-                let submenu = build_menu(_app, services_menu, submenu);
+                let submenu = build_menu(_app, services_menu, command_target, submenu);
                 let _: () = msg_send![item, setSubmenu: submenu];
                 let _: () = msg_send![menuobj, addItem: item];
             }
@@ -149,6 +188,9 @@ pub fn configure_event_loop<T: 'static>(event_loop_builder: &mut EventLoopBuilde
     Menu
 }
 
+// Commands are queued as their items are chosen, so there is nothing to poll.
+pub fn poll_commands() {}
+
 pub fn attach_menu(
     // On some platforms, e.g. Windows and Linux, the menu bar is part of the
     // window itself, and we need to add it to each individual window.  But
@@ -172,8 +214,12 @@ pub fn attach_menu(
         let services_menu: *mut Object = msg_send![services_menu, autorelease];
         let _: () = msg_send![app, setServicesMenu: services_menu];
 
+        // Menu items only hold on to their target weakly, so it is never
+        // released.
+        let command_target: *mut Object = msg_send![command_target_class(), new];
+
         // Turn the menubar description into a Cocoa menu.
-        let obj = build_menu(app, services_menu, &menu_bar_spec);
+        let obj = build_menu(app, services_menu, command_target, &menu_bar_spec);
 
         // Register the menu with the NSApplication object.
         let _: () = msg_send![app, setMainMenu: obj];
@@ -1,10 +1,10 @@
 use crate::{
-    menubar::{MenuAction, MenuItem, MenuSpec, SystemAction},
+    menubar::{queue_command, AppCommand, MenuAction, MenuItem, MenuSpec, SystemAction},
     APP_LICENSE, APP_NAME, APP_VERSION,
 };
 
 pub use muda::Menu;
-use muda::{AboutMetadata, PredefinedMenuItem, Submenu};
+use muda::{AboutMetadata, MenuEvent, MenuId, PredefinedMenuItem, Submenu};
 use winit::{event_loop::EventLoopBuilder, window::Window};
 
 pub fn configure_event_loop<T: 'static>(event_loop_builder: &mut EventLoopBuilder<T>) -> Menu {
@@ -35,12 +35,32 @@ pub fn attach_menu(window: &Window, menu_bar: &Menu) {
         .expect("Initializing the menubar shouldn't return an error");
 }
 
+// Queues the commands whose items have been chosen since this was last called.
+// muda sends each click through a channel, identifying the item by the id it
+// was given, which for commands is their tag.
+pub fn poll_commands() {
+    for event in MenuEvent::receiver().try_iter() {
+        if let Some(command) = event.id.0.parse().ok().and_then(AppCommand::from_tag) {
+            queue_command(command);
+        }
+    }
+}
+
+fn command_item(title: &str, command: AppCommand) -> muda::MenuItem {
+    muda::MenuItem::with_id(MenuId(command.tag().to_string()), title, true, None)
+}
+
 fn build_menu(menu_spec: &MenuSpec) -> Menu {
     let menu_bar = Menu::new();
 
     for menu_item in &menu_spec.items {
         match menu_item {
-            MenuItem::Entry(_title, _shortcut, action) => match action {
+            MenuItem::Entry(title, _shortcut, action) => match action {
+                MenuAction::App(command) => {
+                    menu_bar
+                        .append(&command_item(title, *command))
+                        .expect("Appending a command menu item shouldn't return an error.");
+                }
                 MenuAction::System(SystemAction::HideApp) => {
                     menu_bar
                         .append(&PredefinedMenuItem::hide(None))
@@ -93,7 +113,12 @@ fn build_sub_menu(sub_menu_spec: &MenuSpec) -> Submenu {
 
     for menu_item in &sub_menu_spec.items {
         match menu_item {
-            MenuItem::Entry(_title, _shortcut, action) => match action {
+            MenuItem::Entry(title, _shortcut, action) => match action {
+                MenuAction::App(command) => {
+                    sub_menu
+                        .append(&command_item(title, *command))
+                        .expect("Appending a command menu item shouldn't return an error.");
+                }
                 MenuAction::System(SystemAction::HideApp) => {
                     sub_menu
                         .append(&PredefinedMenuItem::hide(None))
@@ -0,0 +1,255 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Rendering previews of saved molecules and structure files from the command
+//! line, without opening a window, so that file browsers and web galleries can
+//! generate them in bulk:
+//!
+//! ```text
+//! atomcad thumbnail <file> [--size <pixels>] [--output <png>] [--exr <exr>]
+//! ```
+//!
+//! The file can be a saved molecule (`.json`), or anything else that the
+//! application can open.  The image is square, 256 pixels on a side unless
+//! `--size` is given, and is written next to the file with its extension
+//! changed to `.png` unless `--output` is given.
+//!
+//! With `--exr`, the render is also written as an OpenEXR image for
+//! compositing, with the color in the `R`, `G`, `B` and `A` channels, the
+//! distance in front of the camera in `Z`, and the normals in the camera's
+//! space in `N.X`, `N.Y` and `N.Z`.
+
+use std::path::{Path, PathBuf};
+
+use crate::exr;
+
+use render::{AtomColoring, OutputColorSpace, RenderOptions, Renderer};
+use scene::{Assembly, Component};
+use ultraviolet::{Mat4, Vec3};
+use winit::dpi::PhysicalSize;
+
+use crate::camera::ArcballCamera;
+
+/// The subcommand that renders a thumbnail, as the first argument.
+pub const THUMBNAIL_COMMAND: &str = "thumbnail";
+
+// The side of the image when no size is given, in pixels.
+const DEFAULT_SIZE: u32 = 256;
+
+// The view that thumbnails are rendered from, as a yaw and pitch in radians:
+// from the front, a little to the side and above, so that depth shows.
+const VIEW: (f32, f32) = (0.6, 0.4);
+
+/// What to render, read from the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThumbnailArgs {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub size: u32,
+    /// Where to write the OpenEXR image, if anywhere.
+    pub exr: Option<PathBuf>,
+}
+
+impl ThumbnailArgs {
+    /// Reads the arguments that follow the subcommand.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut input = None;
+        let mut output = None;
+        let mut size = DEFAULT_SIZE;
+        let mut exr = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" | "--output" => {
+                    output = Some(args.next().ok_or("--output needs a file name")?.into());
+                }
+                "--exr" => {
+                    exr = Some(args.next().ok_or("--exr needs a file name")?.into());
+                }
+                "-s" | "--size" => {
+                    size = args
+                        .next()
+                        .and_then(|size| size.parse().ok())
+                        .filter(|size| *size > 0)
+                        .ok_or("--size needs a number of pixels")?;
+                }
+                _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+                _ if input.is_none() => input = Some(PathBuf::from(arg)),
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+
+        let input: PathBuf = input.ok_or("no file to render was given")?;
+        Ok(Self {
+            output: output.unwrap_or_else(|| input.with_extension("png")),
+            input,
+            size,
+            exr,
+        })
+    }
+}
+
+/// Runs the thumbnail subcommand with the arguments that follow it, returning
+/// the process's exit code.
+pub fn run(args: impl IntoIterator<Item = String>) -> i32 {
+    let result = ThumbnailArgs::parse(args).and_then(|args| {
+        futures::executor::block_on(render_thumbnail(&args))?;
+        println!("wrote {}", args.output.display());
+        if let Some(exr) = &args.exr {
+            println!("wrote {}", exr.display());
+        }
+        Ok(())
+    });
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{}: {}", THUMBNAIL_COMMAND, err);
+            eprintln!(
+                "usage: atomcad {} <file> [--size <pixels>] [--output <png>] [--exr <exr>]",
+                THUMBNAIL_COMMAND
+            );
+            1
+        }
+    }
+}
+
+/// Renders the file `args.input` into a PNG image at `args.output`, and an
+/// OpenEXR image at `args.exr` if it is given.
+pub async fn render_thumbnail(args: &ThumbnailArgs) -> Result<(), String> {
+    let molecule = crate::read_molecule(&args.input)?;
+    if molecule.repr.graph.node_count() == 0 {
+        return Err(format!("{} has no atoms", args.input.display()));
+    }
+    let bounds = molecule.repr.bounding_box();
+    let center = (bounds.min + bounds.max) * 0.5;
+    let radius = ((bounds.max - bounds.min).mag() * 0.5).max(1.0);
+    let mut world =
+        Assembly::from_components([Component::from_molecule(molecule, Mat4::default())]);
+
+    let size = PhysicalSize::new(args.size, args.size);
+    let (mut renderer, gpu_resources) = Renderer::new_headless(
+        size,
+        RenderOptions {
+            fxaa: Some(()),
+            attempt_gpu_driven: true,
+            background: render::DEFAULT_BACKGROUND,
+            fog: None,
+            color_scheme: Default::default(),
+            coloring: AtomColoring::Element,
+            // Image files are tagged as sRGB.
+            output_color_space: OutputColorSpace::Srgb,
+            atom_limits: Default::default(),
+            depth_prepass: false,
+            culling: None,
+            conservative_limits: false,
+        },
+    )
+    .await
+    .ok_or("no GPU is available to render with")?;
+    renderer.set_camera(ArcballCamera::new(Vec3::zero(), 100.0, 1.0));
+    renderer.camera().orbit_to(VIEW.0, VIEW.1, false);
+    renderer.camera().frame(center, radius);
+
+    world.synchronize_buffers(&gpu_resources);
+    let (atoms, transforms, tints) = world.collect_atoms_and_transforms();
+    renderer.render(atoms, transforms, tints);
+    let pixels = renderer
+        .read_pixels()
+        .ok_or("could not read back the rendered image")?;
+
+    write_png(&args.output, size, &pixels)?;
+    if let Some(path) = &args.exr {
+        let depth = renderer
+            .read_depth()
+            .ok_or("could not read back the rendered depth")?;
+        let normals = renderer.read_normals();
+        write_exr(path, size, &pixels, &depth, &normals)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn write_png(path: &Path, size: PhysicalSize<u32>, rgba: &[u8]) -> Result<(), String> {
+    let file = std::fs::File::create(path).map_err(|err| err.to_string())?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), size.width, size.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(rgba))
+        .map_err(|err| err.to_string())
+}
+
+// Writes the color, depth and normals of a render as the layers of an OpenEXR
+// image.  The color is converted from sRGB, as OpenEXR images are linear.
+fn write_exr(
+    path: &Path,
+    size: PhysicalSize<u32>,
+    rgba: &[u8],
+    depth: &[f32],
+    normals: &[Vec3],
+) -> Result<(), String> {
+    let color = |channel: usize| -> Vec<f32> {
+        rgba.chunks_exact(4)
+            .map(|pixel| srgb_to_linear(pixel[channel]))
+            .collect()
+    };
+    let alpha: Vec<f32> = rgba
+        .chunks_exact(4)
+        .map(|pixel| pixel[3] as f32 / 255.0)
+        .collect();
+    let (red, green, blue) = (color(0), color(1), color(2));
+    let normal_x: Vec<f32> = normals.iter().map(|normal| normal.x).collect();
+    let normal_y: Vec<f32> = normals.iter().map(|normal| normal.y).collect();
+    let normal_z: Vec<f32> = normals.iter().map(|normal| normal.z).collect();
+
+    let channels = [
+        exr::Channel {
+            name: "R",
+            values: &red,
+        },
+        exr::Channel {
+            name: "G",
+            values: &green,
+        },
+        exr::Channel {
+            name: "B",
+            values: &blue,
+        },
+        exr::Channel {
+            name: "A",
+            values: &alpha,
+        },
+        exr::Channel {
+            name: "Z",
+            values: depth,
+        },
+        exr::Channel {
+            name: "N.X",
+            values: &normal_x,
+        },
+        exr::Channel {
+            name: "N.Y",
+            values: &normal_y,
+        },
+        exr::Channel {
+            name: "N.Z",
+            values: &normal_z,
+        },
+    ];
+    let file = std::fs::File::create(path).map_err(|err| err.to_string())?;
+    let mut out = std::io::BufWriter::new(file);
+    exr::write(&mut out, size.width, size.height, &channels).map_err(|err| err.to_string())
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// End of File
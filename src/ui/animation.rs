@@ -0,0 +1,55 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Playback of the motion of driven components.
+//!
+//! Drives move with the wall clock rather than the frame count, so that an
+//! animation runs at the same speed however fast the scene renders.
+
+use instant::Instant;
+use scene::Assembly;
+
+/// The longest step an animation takes between two frames, in seconds.  This
+/// keeps parts from jumping when a frame is very late, e.g. after the window
+/// was hidden.
+const MAX_STEP: f32 = 0.1;
+
+#[derive(Default)]
+pub struct AnimationControls {
+    /// When the previous frame was drawn, if the animation is playing.
+    playing_since: Option<Instant>,
+}
+
+impl AnimationControls {
+    pub fn is_playing(&self) -> bool {
+        self.playing_since.is_some()
+    }
+
+    pub fn toggle(&mut self) {
+        self.playing_since = match self.playing_since {
+            Some(_) => None,
+            None => Some(Instant::now()),
+        };
+    }
+
+    /// Stops playing and puts every drive back where it was designed.
+    pub fn stop(&mut self, assembly: &mut Assembly) {
+        self.playing_since = None;
+        assembly.reset_drives();
+    }
+
+    /// Moves the drives on by the time since the previous frame, if the
+    /// animation is playing.  Returns whether anything moved.
+    pub fn advance(&mut self, assembly: &mut Assembly) -> bool {
+        let Some(previous) = self.playing_since else {
+            return false;
+        };
+        let now = Instant::now();
+        self.playing_since = Some(now);
+        let seconds = (now - previous).as_secs_f32().min(MAX_STEP);
+        assembly.advance_drives(seconds)
+    }
+}
+
+// End of File
@@ -0,0 +1,253 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The bond tool, which bonds two atoms of a molecule with a
+//! [`Edit::CreateBond`].  The first atom clicked is marked, and a rubber band
+//! is drawn from it to the cursor until a second atom is clicked.  Bonding
+//! atoms that are already bonded changes the order of their bond.  The keys 1
+//! to 3 choose the order of the bonds made.
+
+use common::ids::AtomSpecifier;
+use molecule::{
+    edit::{CreateBond, Edit},
+    BondOrder,
+};
+use periodic_table::Element;
+use render::{AtomBuffer, AtomKind, AtomRepr, GlobalRenderResources};
+use scene::{Assembly, ComponentId};
+use ultraviolet::{Mat4, Vec3};
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use super::tools::{OverlayDraw, Tool, ToolContext};
+
+/// The distance between the dots of the rubber band, in angstroms.
+const BAND_SPACING: f32 = 0.5;
+
+/// The color that the rubber band is drawn in.
+const BAND_TINT: Vec3 = Vec3::new(1.0, 0.85, 0.3);
+
+/// What a click with the bond tool did.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BondOutcome {
+    /// The atom was marked as the first of the bond.
+    Marked,
+    /// The marked atom was clicked again, and is no longer marked.
+    Unmarked,
+    /// The atoms were bonded, in the molecule of this component.
+    Bonded(ComponentId),
+    /// The second atom is in another molecule than the first, and the first
+    /// is still marked.
+    DifferentMolecules,
+    /// The molecule is locked, and nothing was marked.
+    Locked,
+    /// There was no atom under the cursor.
+    Missed,
+}
+
+pub struct BondTool {
+    active: bool,
+    order: BondOrder,
+    /// The first atom of the bond, once it has been clicked.
+    first: Option<(ComponentId, AtomSpecifier)>,
+    /// The ends of the rubber band, in world space.
+    band: Option<(Vec3, Vec3)>,
+    buffer: Option<AtomBuffer>,
+    buffer_stale: bool,
+}
+
+impl Default for BondTool {
+    fn default() -> Self {
+        Self {
+            active: false,
+            order: 1,
+            first: None,
+            band: None,
+            buffer: None,
+            buffer_stale: false,
+        }
+    }
+}
+
+impl BondTool {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// The order of the bonds that the tool creates.
+    pub fn order(&self) -> BondOrder {
+        self.order
+    }
+
+    /// Sets the order of the bonds that the tool creates, from 1 to 3.
+    /// Returns false if `order` is not one of those.
+    pub fn set_order(&mut self, order: BondOrder) -> bool {
+        if !(1..=3).contains(&order) {
+            return false;
+        }
+        self.order = order;
+        true
+    }
+
+    pub fn first(&self) -> Option<&(ComponentId, AtomSpecifier)> {
+        self.first.as_ref()
+    }
+
+    /// Forgets the first atom, if one was clicked.
+    pub fn unmark(&mut self) {
+        self.first = None;
+        self.set_band(None);
+    }
+
+    /// Applies a click on `hit`, the atom under the cursor (if any).
+    pub fn click(
+        &mut self,
+        assembly: &mut Assembly,
+        hit: Option<(ComponentId, AtomSpecifier)>,
+    ) -> BondOutcome {
+        let Some((component, atom)) = hit else {
+            return BondOutcome::Missed;
+        };
+        let Some((first_component, first)) = self.first.clone() else {
+            if assembly.is_locked(component) != Some(false) {
+                return BondOutcome::Locked;
+            }
+            self.first = Some((component, atom));
+            return BondOutcome::Marked;
+        };
+
+        if first_component != component {
+            return BondOutcome::DifferentMolecules;
+        }
+        self.unmark();
+        if first == atom {
+            return BondOutcome::Unmarked;
+        }
+        let Some(molecule) = assembly
+            .component_mut(component)
+            .and_then(|component| component.molecule_mut())
+        else {
+            return BondOutcome::Locked;
+        };
+        molecule.add_edit(Edit::CreateBond(CreateBond {
+            atoms: [first, atom],
+            order: self.order,
+        }));
+        BondOutcome::Bonded(component)
+    }
+
+    /// Stretches the rubber band from the first atom to the atom under the
+    /// cursor, or, with no atom under it, to the point on the ray through the
+    /// cursor that is closest to the first atom.
+    pub fn hover(
+        &mut self,
+        assembly: &Assembly,
+        hit: Option<(ComponentId, AtomSpecifier)>,
+        ray: Option<(Vec3, Vec3)>,
+    ) {
+        let start = self
+            .first
+            .as_ref()
+            .and_then(|(id, atom)| assembly.atom_position(*id, atom));
+        let band = start.and_then(|start| {
+            let end = match hit {
+                Some((id, atom)) => assembly.atom_position(id, &atom)?,
+                None => {
+                    let (origin, direction) = ray?;
+                    origin + direction * (start - origin).dot(direction).max(0.0)
+                }
+            };
+            Some((start, end))
+        });
+        self.set_band(band);
+    }
+
+    fn set_band(&mut self, band: Option<(Vec3, Vec3)>) {
+        if band != self.band {
+            self.band = band;
+            self.buffer_stale = true;
+        }
+    }
+}
+
+impl Tool for BondTool {
+    fn set_active(&mut self, active: bool) {
+        self.active = active;
+        self.unmark();
+    }
+
+    fn hint(&self) -> Option<String> {
+        Some(format!(
+            "bonding atoms with order {} bonds: click two atoms, or press 1 to 3 to change \
+             the order",
+            self.order
+        ))
+    }
+
+    fn on_pointer_down(&mut self, context: ToolContext) -> bool {
+        let hit = context.pointer.hit(context.assembly);
+        match self.click(context.assembly, hit) {
+            BondOutcome::Marked => log::info!("click a second atom to bond to"),
+            BondOutcome::Unmarked => log::info!("bond cancelled"),
+            BondOutcome::Bonded(_) => log::info!("bonded the atoms"),
+            BondOutcome::DifferentMolecules => {
+                log::warn!("only atoms of the same molecule can be bonded")
+            }
+            BondOutcome::Locked => log::info!("the molecule is locked"),
+            BondOutcome::Missed => return false,
+        }
+        true
+    }
+
+    fn on_pointer_move(&mut self, context: ToolContext) -> bool {
+        if self.first.is_some() {
+            let hit = context.pointer.hit(context.assembly);
+            self.hover(context.assembly, hit, context.pointer.ray);
+        }
+        false
+    }
+
+    fn on_key(&mut self, _context: ToolContext, key: KeyCode, _modifiers: &ModifiersState) -> bool {
+        let order = match key {
+            KeyCode::Escape if self.first.is_some() => {
+                self.unmark();
+                return true;
+            }
+            KeyCode::Digit1 => 1,
+            KeyCode::Digit2 => 2,
+            KeyCode::Digit3 => 3,
+            _ => return false,
+        };
+        self.set_order(order);
+        log::info!("bonding atoms with order {} bonds", order);
+        true
+    }
+
+    fn synchronize_buffers(&mut self, gpu_resources: &GlobalRenderResources) {
+        if !self.buffer_stale {
+            return;
+        }
+        self.buffer_stale = false;
+        self.buffer = self.band.and_then(|(start, end)| {
+            let steps = ((end - start).mag() / BAND_SPACING) as usize;
+            if steps == 0 {
+                return None;
+            }
+            let dots = (0..=steps).map(|step| AtomRepr {
+                pos: start + (end - start) * (step as f32 / steps as f32),
+                kind: AtomKind::new(Element::Hydrogen),
+                property: 0.0,
+            });
+            Some(AtomBuffer::new(gpu_resources, dots.collect::<Vec<_>>()))
+        });
+    }
+
+    fn render_overlay(&self) -> Vec<OverlayDraw<'_>> {
+        match (self.band, &self.buffer) {
+            (Some(_), Some(buffer)) => vec![(buffer, Mat4::default(), Some(BAND_TINT))],
+            _ => Vec::new(),
+        }
+    }
+}
+
+// End of File
@@ -0,0 +1,221 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The element brush, e.g. for doping a lattice.  Dragging across atoms
+//! changes them into atoms of the element chosen from the palette, which is
+//! the same as the placement tool's and is chosen with the number keys.  The
+//! atoms painted so far are drawn as the new element while dragging, and
+//! letting go records the whole stroke as one [`Edit::SetElement`] per
+//! molecule, so that it shows up (and is taken back) as a single change.
+
+use common::ids::AtomSpecifier;
+use molecule::edit::{Edit, EditContext, SetElement};
+use periodic_table::Element;
+use render::{AtomBuffer, AtomKind, AtomRepr, GlobalRenderResources};
+use scene::{Assembly, ComponentId};
+use ultraviolet::{Mat4, Vec3};
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use super::{
+    placement::{self, PALETTE, PALETTE_KEYS},
+    tools::{OverlayDraw, Tool, ToolContext},
+};
+
+/// How far the painted atoms are drawn in front of the atoms they cover, in
+/// angstroms, so that they are not hidden behind them.
+const PREVIEW_NUDGE: f32 = 0.05;
+
+pub struct BrushTool {
+    element: Element,
+    painting: bool,
+    /// The atoms painted during the current stroke, in the order they were
+    /// painted, with where they are in world space.
+    stroke: Vec<(ComponentId, AtomSpecifier, Vec3)>,
+    view_direction: Vec3,
+    /// The painted atoms, uploaded when the stroke changes.
+    buffer: Option<AtomBuffer>,
+    buffer_stale: bool,
+}
+
+impl Default for BrushTool {
+    fn default() -> Self {
+        Self {
+            element: Element::Carbon,
+            painting: false,
+            stroke: Vec::new(),
+            view_direction: -Vec3::unit_z(),
+            buffer: None,
+            buffer_stale: false,
+        }
+    }
+}
+
+impl BrushTool {
+    pub fn set_element(&mut self, element: Element) {
+        self.element = element;
+    }
+
+    /// Adds `atom` of `component` to the stroke.  Atoms that are already of
+    /// the brush's element, or in locked molecules, are left alone.  Returns
+    /// whether the atom was added.
+    pub fn paint(
+        &mut self,
+        assembly: &Assembly,
+        component: ComponentId,
+        atom: AtomSpecifier,
+    ) -> bool {
+        if self
+            .stroke
+            .iter()
+            .any(|(painted, spec, _)| *painted == component && *spec == atom)
+            || assembly.is_locked(component) != Some(false)
+        {
+            return false;
+        }
+        let Some((_, molecule, transform)) = assembly
+            .visible_molecules()
+            .into_iter()
+            .find(|(id, ..)| *id == component)
+        else {
+            return false;
+        };
+        let (Some(node), Some(pos)) = (molecule.repr.find_atom(&atom), molecule.repr.pos(&atom))
+        else {
+            return false;
+        };
+        if node.element == self.element {
+            return false;
+        }
+        let pos = transform.transform_point3(*pos);
+        self.stroke.push((component, atom, pos));
+        self.buffer_stale = true;
+        true
+    }
+
+    /// Records the stroke as one edit in each molecule it painted, and starts
+    /// a new one.  Returns how many atoms were changed.
+    pub fn commit(&mut self, assembly: &mut Assembly) -> usize {
+        let mut stroke = std::mem::take(&mut self.stroke);
+        self.buffer_stale = true;
+        let mut changed = 0;
+        while let Some((component, ..)) = stroke.first() {
+            let component = *component;
+            let (targets, rest): (Vec<_>, Vec<_>) = stroke
+                .into_iter()
+                .partition(|(painted, ..)| *painted == component);
+            stroke = rest;
+            let Some(molecule) = assembly
+                .component_mut(component)
+                .and_then(|component| component.molecule_mut())
+            else {
+                continue;
+            };
+            changed += targets.len();
+            molecule.add_edit(Edit::SetElement(SetElement {
+                targets: targets.into_iter().map(|(_, atom, _)| atom).collect(),
+                element: self.element,
+            }));
+        }
+        changed
+    }
+}
+
+impl Tool for BrushTool {
+    fn set_active(&mut self, _active: bool) {
+        self.painting = false;
+        if !self.stroke.is_empty() {
+            self.stroke.clear();
+            self.buffer_stale = true;
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        Some(format!(
+            "painting elements: drag across atoms to change them, {}",
+            placement::palette_line(self.element)
+        ))
+    }
+
+    fn on_pointer_down(&mut self, context: ToolContext) -> bool {
+        let Some((component, atom)) = context.pointer.hit(context.assembly) else {
+            return false;
+        };
+        self.painting = true;
+        self.paint(context.assembly, component, atom);
+        true
+    }
+
+    fn on_pointer_move(&mut self, context: ToolContext) -> bool {
+        if let Some((_, direction)) = context.pointer.ray {
+            self.view_direction = direction;
+        }
+        if !self.painting {
+            return false;
+        }
+        if let Some((component, atom)) = context.pointer.hit(context.assembly) {
+            self.paint(context.assembly, component, atom);
+        }
+        true
+    }
+
+    fn on_pointer_up(&mut self, context: ToolContext) -> bool {
+        if !std::mem::take(&mut self.painting) {
+            return false;
+        }
+        match self.commit(context.assembly) {
+            0 => log::info!("no atoms were changed"),
+            changed => log::info!("changed {} atoms to {}", changed, self.element.symbol()),
+        }
+        true
+    }
+
+    fn on_key(&mut self, _context: ToolContext, key: KeyCode, _modifiers: &ModifiersState) -> bool {
+        let Some(index) = PALETTE_KEYS
+            .iter()
+            .position(|palette_key| *palette_key == key)
+        else {
+            return false;
+        };
+        // Atoms already painted keep the element they were painted with
+        // until the stroke ends, so the element cannot change during one.
+        if !self.painting {
+            self.element = PALETTE[index];
+            log::info!(
+                "painting elements: {}",
+                placement::palette_line(self.element)
+            );
+        }
+        true
+    }
+
+    fn synchronize_buffers(&mut self, gpu_resources: &GlobalRenderResources) {
+        if !self.buffer_stale {
+            return;
+        }
+        self.buffer_stale = false;
+        self.buffer = (!self.stroke.is_empty()).then(|| {
+            AtomBuffer::new(
+                gpu_resources,
+                self.stroke.iter().map(|(.., pos)| AtomRepr {
+                    pos: *pos,
+                    kind: AtomKind::new(self.element),
+                    property: 0.0,
+                }),
+            )
+        });
+    }
+
+    fn render_overlay(&self) -> Vec<OverlayDraw<'_>> {
+        match &self.buffer {
+            Some(buffer) => vec![(
+                buffer,
+                Mat4::from_translation(-self.view_direction * PREVIEW_NUDGE),
+                None,
+            )],
+            None => Vec::new(),
+        }
+    }
+}
+
+// End of File
@@ -0,0 +1,188 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Copying selected atoms and pasting them elsewhere.  A copy takes the
+//! selected atoms and the bonds between them as a [`Fragment`], which can be
+//! pasted into a molecule as a single [`Edit::AtomImport`], or as a new
+//! molecule of its own.  Each paste is put a little further from the original
+//! atoms than the one before, so that they do not land on top of each other.
+//!
+//! Fragments also have a text form, so that they can go through the system
+//! clipboard between windows.
+
+use molecule::edit::{AtomImport, Edit, EditContext};
+use molecule::{parts, MoleculeEditor};
+use scene::{Assembly, Component, ComponentId};
+use serde::{Deserialize, Serialize};
+use ultraviolet::{Mat4, Vec3};
+
+use super::selection::Selection;
+
+/// How far each paste is moved from the one before, in angstroms.
+pub const PASTE_OFFSET: Vec3 = Vec3::new(2.0, 2.0, 0.0);
+
+// The first line of the text form, so that other text is not mistaken for a
+// fragment.
+const TEXT_HEADER: &str = "atomCAD fragment";
+
+/// Copied atoms and the bonds between them.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Fragment {
+    /// The atoms, with their positions relative to `origin`.
+    pub atoms: AtomImport,
+    /// Where the centroid of the atoms was when they were copied, in world
+    /// space.
+    pub origin: Vec3,
+}
+
+impl Fragment {
+    /// The atoms `selection` of the molecule it belongs to, in the order of
+    /// the molecule's atoms.  Returns `None` if nothing is selected.
+    pub fn from_selection(selection: &Selection, assembly: &Assembly) -> Option<Self> {
+        let id = selection.component()?;
+        let molecule = assembly.component(id)?.molecule()?;
+        let transform = assembly.world_transform(id)?;
+        let atoms: Vec<_> = EditContext::atoms(&molecule.repr)
+            .into_iter()
+            .filter(|atom| selection.atoms().contains(atom))
+            .collect();
+        if atoms.is_empty() {
+            return None;
+        }
+
+        let Edit::AtomImport(mut import) = parts::extract_atoms(&molecule.repr, &atoms, "") else {
+            return None;
+        };
+        for (_, pos) in &mut import.atoms {
+            *pos = transform.transform_point3(*pos);
+        }
+        let origin =
+            import.atoms.iter().map(|(_, pos)| *pos).sum::<Vec3>() / import.atoms.len() as f32;
+        for (_, pos) in &mut import.atoms {
+            *pos -= origin;
+        }
+        import.name = format!("{} atoms from {}", import.atoms.len(), molecule.name());
+        Some(Self {
+            atoms: import,
+            origin,
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        let json = serde_json::to_string_pretty(self).unwrap_or_default();
+        format!("{}\n{}", TEXT_HEADER, json)
+    }
+
+    /// Reads the text form of a fragment.  Returns `None` for any other text.
+    pub fn from_text(text: &str) -> Option<Self> {
+        let json = text.trim_start().strip_prefix(TEXT_HEADER)?;
+        serde_json::from_str(json).ok()
+    }
+
+    // The import that puts the atoms at `origin`, in the space of a component
+    // whose world transform is `transform`.
+    fn placed(&self, origin: Vec3, transform: Mat4) -> AtomImport {
+        let inverse = transform.inversed();
+        let mut import = self.atoms.clone();
+        for (_, pos) in &mut import.atoms {
+            *pos = inverse.transform_point3(origin + *pos);
+        }
+        import
+    }
+}
+
+/// Why a fragment could not be pasted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PasteError {
+    /// Nothing has been copied.
+    Empty,
+    /// The molecule to paste into does not exist.
+    NoMolecule,
+    /// The molecule to paste into is locked.
+    Locked,
+}
+
+#[derive(Default)]
+pub struct Clipboard {
+    fragment: Option<Fragment>,
+    /// How many times the fragment has been pasted.
+    pastes: u32,
+}
+
+impl Clipboard {
+    pub fn fragment(&self) -> Option<&Fragment> {
+        self.fragment.as_ref()
+    }
+
+    /// Copies the selected atoms.  Returns the fragment copied, or `None` if
+    /// nothing is selected, which leaves the clipboard as it was.
+    pub fn copy(&mut self, selection: &Selection, assembly: &Assembly) -> Option<&Fragment> {
+        let fragment = Fragment::from_selection(selection, assembly)?;
+        self.set(fragment);
+        self.fragment()
+    }
+
+    /// Puts a fragment on the clipboard, e.g. one pasted from another window.
+    /// Pasting starts over from the original position, unless it is the
+    /// fragment already there.
+    pub fn set(&mut self, fragment: Fragment) {
+        let same = self
+            .fragment
+            .as_ref()
+            .is_some_and(|current| current.to_text() == fragment.to_text());
+        if !same {
+            self.pastes = 0;
+        }
+        self.fragment = Some(fragment);
+    }
+
+    /// Pastes the fragment into the molecule `id`, as one edit at its history
+    /// step.  Returns the number of atoms pasted.
+    pub fn paste_into(
+        &mut self,
+        assembly: &mut Assembly,
+        id: ComponentId,
+    ) -> Result<usize, PasteError> {
+        let origin = self.next_origin().ok_or(PasteError::Empty)?;
+        let transform = assembly.world_transform(id).ok_or(PasteError::NoMolecule)?;
+        if assembly.is_locked(id) != Some(false) {
+            return Err(PasteError::Locked);
+        }
+        let fragment = self.fragment.as_ref().ok_or(PasteError::Empty)?;
+        let import = fragment.placed(origin, transform);
+        let count = import.atoms.len();
+        let molecule = assembly
+            .component_mut(id)
+            .and_then(|component| component.molecule_mut())
+            .ok_or(PasteError::NoMolecule)?;
+        molecule.add_edit(Edit::AtomImport(import));
+        self.pastes += 1;
+        Ok(count)
+    }
+
+    /// Pastes the fragment as a new molecule, placed with its origin at the
+    /// centroid of the atoms.  Returns the new component.
+    pub fn paste_as_component(
+        &mut self,
+        assembly: &mut Assembly,
+    ) -> Result<ComponentId, PasteError> {
+        let origin = self.next_origin().ok_or(PasteError::Empty)?;
+        let fragment = self.fragment.as_ref().ok_or(PasteError::Empty)?;
+        let molecule = MoleculeEditor::from_feature(Edit::AtomImport(fragment.atoms.clone()));
+        let id = assembly.add_component(Component::from_molecule(
+            molecule,
+            Mat4::from_translation(origin),
+        ));
+        self.pastes += 1;
+        Ok(id)
+    }
+
+    // Where the centroid of the next paste goes.
+    fn next_origin(&self) -> Option<Vec3> {
+        let fragment = self.fragment.as_ref()?;
+        Some(fragment.origin + PASTE_OFFSET * (self.pastes + 1) as f32)
+    }
+}
+
+// End of File
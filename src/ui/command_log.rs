@@ -0,0 +1,158 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The log of commands run on molecules this session, oldest first.
+//!
+//! Every edit added to a molecule, by whichever tool or panel, is logged
+//! along with its parameters.  A logged command can be run again on the
+//! molecule it was first run on, or copied as a line of a build script (see
+//! [`molecule::build_script`]), so that what was done by hand can be written
+//! down and repeated.  Molecules that are opened or loaded are not logged: only
+//! the edits added to them afterwards are.
+
+use std::collections::HashMap;
+
+use common::ids::EditId;
+use molecule::{build_script, edit::Edit};
+use scene::{Assembly, ComponentId};
+
+/// How many commands are kept before the oldest are forgotten.
+const MAX_ENTRIES: usize = 500;
+
+/// A command as it was run.
+#[derive(Clone, Debug)]
+pub struct CommandEntry {
+    /// The component whose molecule the command was run on.
+    pub component: ComponentId,
+    pub edit: Edit,
+    /// The command as a line of a build script, if it can be written as one.
+    pub script: Option<String>,
+}
+
+/// One line of the command log.
+#[derive(Clone, Debug)]
+pub struct CommandLogRow {
+    /// The command's position in the log, starting from 1.
+    pub number: usize,
+    pub label: String,
+    pub script: Option<String>,
+    pub highlighted: bool,
+}
+
+/// Why a logged command could not be run again.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RerunError {
+    NothingHighlighted,
+    /// The component it was run on has since been deleted.
+    MissingComponent,
+    Locked,
+}
+
+#[derive(Default)]
+pub struct CommandLog {
+    entries: Vec<CommandEntry>,
+    /// The id that the next edit of each molecule will be given, as last seen.
+    /// Edits are numbered in the order they are added, so those from here on
+    /// are new.
+    next_ids: HashMap<ComponentId, EditId>,
+    highlighted: Option<usize>,
+}
+
+impl CommandLog {
+    /// Logs the edits added to the molecules since this was last called.  This
+    /// is cheap, and should be called often, e.g. every frame.
+    pub fn sync(&mut self, assembly: &Assembly) {
+        for component in assembly.components() {
+            let Some(molecule) = component.molecule() else {
+                continue;
+            };
+            let edits = molecule.edits();
+            let next_id = edits.next_id();
+            let Some(seen) = self.next_ids.insert(component.id(), next_id) else {
+                continue;
+            };
+            for id in seen..next_id {
+                if let Some(edit) = edits.get(&id) {
+                    self.push(CommandEntry {
+                        component: component.id(),
+                        edit: edit.clone(),
+                        script: build_script::command(edit),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Stops following the molecule of `component`, e.g. when it is replaced,
+    /// so that the edits it already has are not logged as new.
+    pub fn forget(&mut self, component: ComponentId) {
+        self.next_ids.remove(&component);
+    }
+
+    pub fn entries(&self) -> &[CommandEntry] {
+        &self.entries
+    }
+
+    pub fn rows(&self) -> Vec<CommandLogRow> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| CommandLogRow {
+                number: index + 1,
+                label: entry.edit.name(),
+                script: entry.script.clone(),
+                highlighted: self.highlighted == Some(index),
+            })
+            .collect()
+    }
+
+    pub fn highlighted(&self) -> Option<&CommandEntry> {
+        self.entries.get(self.highlighted?)
+    }
+
+    /// Highlights the command before the highlighted one, starting from the
+    /// newest and wrapping around after the oldest.
+    pub fn highlight_previous(&mut self) -> Option<&CommandEntry> {
+        let count = self.entries.len();
+        self.highlighted = match self.highlighted {
+            _ if count == 0 => None,
+            Some(index) if index > 0 => Some(index - 1),
+            _ => Some(count - 1),
+        };
+        self.highlighted()
+    }
+
+    /// Runs the highlighted command again, on the molecule it was first run
+    /// on.  Returns that molecule's component.  The command is logged again
+    /// when it is next synced.
+    pub fn rerun(&self, assembly: &mut Assembly) -> Result<ComponentId, RerunError> {
+        let entry = self.highlighted().ok_or(RerunError::NothingHighlighted)?;
+        match assembly.is_locked(entry.component) {
+            None => return Err(RerunError::MissingComponent),
+            Some(true) => return Err(RerunError::Locked),
+            Some(false) => {}
+        }
+        let molecule = assembly
+            .component_mut(entry.component)
+            .and_then(|component| component.molecule_mut())
+            .ok_or(RerunError::MissingComponent)?;
+        molecule.add_edit(entry.edit.clone());
+        Ok(entry.component)
+    }
+
+    /// The highlighted command as a line of a build script, to be copied.
+    pub fn script(&self) -> Option<&str> {
+        self.highlighted()?.script.as_deref()
+    }
+
+    fn push(&mut self, entry: CommandEntry) {
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.remove(0);
+            self.highlighted = self.highlighted.and_then(|index| index.checked_sub(1));
+        }
+        self.entries.push(entry);
+    }
+}
+
+// End of File
@@ -0,0 +1,187 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Side-by-side comparison of two variants of a design.
+//!
+//! The variants are shown in two views that share one camera, so they are
+//! always seen from the same viewpoint.  The right-hand variant is rigidly
+//! superposed onto the left-hand one over the atoms they have in common (those
+//! with the same specifier), so that differences in shape stand out rather
+//! than differences in position.
+
+use molecule::{edit::EditContext, superposition, MoleculeEditor};
+use render::{GlobalRenderResources, Renderer};
+use scene::{Assembly, Component, ComponentId};
+use ultraviolet::Mat4;
+
+/// One of the two variants being compared.
+pub struct CompareSide {
+    pub label: String,
+    assembly: Assembly,
+}
+
+impl CompareSide {
+    fn new(label: String, assembly: Assembly) -> Self {
+        Self { label, assembly }
+    }
+}
+
+/// How well the two variants line up once superposed.
+#[derive(Clone, Copy, Debug)]
+pub struct Alignment {
+    /// The number of atoms present in both variants.
+    pub matched_atoms: usize,
+    /// The root-mean-square distance between the matched atoms, in angstroms.
+    pub rmsd: f32,
+}
+
+#[derive(Default)]
+pub struct CompareView {
+    sides: Option<(CompareSide, CompareSide)>,
+    alignment: Option<Alignment>,
+}
+
+impl CompareView {
+    pub fn is_open(&self) -> bool {
+        self.sides.is_some()
+    }
+
+    pub fn close(&mut self) {
+        self.sides = None;
+        self.alignment = None;
+    }
+
+    /// Compares the molecule in `component` at two steps of its history.
+    /// Returns false if the component does not hold a molecule or either step
+    /// is past the end of its history.
+    pub fn open_history(
+        &mut self,
+        assembly: &Assembly,
+        component: ComponentId,
+        left_step: usize,
+        right_step: usize,
+    ) -> bool {
+        let Some(molecule) = assembly.component(component).and_then(|c| c.molecule()) else {
+            return false;
+        };
+        if left_step.max(right_step) > molecule.edits().len() {
+            return false;
+        }
+
+        let label = |step| format!("{} @ step {}", molecule.name(), step);
+        let side = |step| {
+            let component = Component::from_molecule(molecule.fork_at(step), Mat4::identity());
+            let id = component.id();
+            (id, Assembly::from_components([component]))
+        };
+        let ((left_id, left), (right_id, right)) = (side(left_step), side(right_step));
+        self.open(
+            (label(left_step), left),
+            (label(right_step), right),
+            &[(left_id, right_id)],
+        );
+        true
+    }
+
+    /// Compares two saved projects, given the contents of their files.  Each
+    /// molecule of one is matched with the molecule of the same component in
+    /// the other.
+    pub fn open_projects(&mut self, left: &str, right: &str) -> serde_json::Result<()> {
+        let left: Assembly = serde_json::from_str(left)?;
+        let right: Assembly = serde_json::from_str(right)?;
+        let shared: Vec<(ComponentId, ComponentId)> = left
+            .components()
+            .filter(|component| component.molecule().is_some())
+            .map(Component::id)
+            .filter(|id| right.component(*id).is_some_and(|c| c.molecule().is_some()))
+            .map(|id| (id, id))
+            .collect();
+        self.open(
+            (left.name().to_owned(), left),
+            (right.name().to_owned(), right),
+            &shared,
+        );
+        Ok(())
+    }
+
+    // Shows `left` and `right` side by side, where `shared` pairs each
+    // molecule of `left` with its counterpart in `right`.
+    fn open(
+        &mut self,
+        left: (String, Assembly),
+        right: (String, Assembly),
+        shared: &[(ComponentId, ComponentId)],
+    ) {
+        // Superpose the right-hand variant onto the left-hand one over the
+        // atoms they share.
+        let (mut mobile, mut target) = (Vec::new(), Vec::new());
+        for &(left_id, right_id) in shared {
+            let (Some((left_molecule, left_transform)), Some((right_molecule, right_transform))) =
+                (placed(&left.1, left_id), placed(&right.1, right_id))
+            else {
+                continue;
+            };
+            let (left_molecule, right_molecule) = (&left_molecule.repr, &right_molecule.repr);
+            for spec in EditContext::atoms(right_molecule) {
+                if let (Some(right_pos), Some(left_pos)) =
+                    (right_molecule.pos(&spec), left_molecule.pos(&spec))
+                {
+                    mobile.push(right_transform.transform_point3(*right_pos));
+                    target.push(left_transform.transform_point3(*left_pos));
+                }
+            }
+        }
+        let superposition = superposition::superpose(&mobile, &target);
+
+        self.alignment = superposition.map(|superposition| Alignment {
+            matched_atoms: mobile.len(),
+            rmsd: superposition.rmsd,
+        });
+        let right_transform = superposition.map_or(Mat4::identity(), |s| s.transform());
+        let right_assembly =
+            Assembly::from_components([Component::from_assembly(right.1, right_transform)]);
+        self.sides = Some((
+            CompareSide::new(left.0, left.1),
+            CompareSide::new(right.0, right_assembly),
+        ));
+    }
+
+    pub fn sides(&self) -> Option<(&CompareSide, &CompareSide)> {
+        self.sides.as_ref().map(|(left, right)| (left, right))
+    }
+
+    /// The result of superposing the variants, or `None` if they have no atoms
+    /// in common.
+    pub fn alignment(&self) -> Option<Alignment> {
+        self.alignment
+    }
+
+    pub fn synchronize_buffers(&mut self, gpu_resources: &GlobalRenderResources) {
+        if let Some((left, right)) = &mut self.sides {
+            left.assembly.synchronize_buffers(gpu_resources);
+            right.assembly.synchronize_buffers(gpu_resources);
+        }
+    }
+
+    /// Draws the variants side by side.  Does nothing if the view is closed.
+    pub fn render(&self, renderer: &mut Renderer) {
+        let Some((left, right)) = &self.sides else {
+            return;
+        };
+        let (left_atoms, mut transforms, mut tints) = left.assembly.collect_atoms_and_transforms();
+        let (right_atoms, right_transforms, right_tints) =
+            right.assembly.collect_atoms_and_transforms();
+        transforms.extend(right_transforms);
+        tints.extend(right_tints);
+        renderer.render_side_by_side(left_atoms, right_atoms, transforms, tints);
+    }
+}
+
+// The molecule of the component `id` of `assembly`, and where it is placed.
+fn placed(assembly: &Assembly, id: ComponentId) -> Option<(&MoleculeEditor, Mat4)> {
+    let molecule = assembly.component(id)?.molecule()?;
+    Some((molecule, assembly.world_transform(id)?))
+}
+
+// End of File
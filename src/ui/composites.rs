@@ -0,0 +1,125 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A library of composite edits, such as "add methyl group", saved from one
+//! molecule to be applied again to other atoms.  Applying one adds a copy of
+//! it whose anchors are the selected atoms instead of the atoms it was built
+//! on.  A composite with one anchor is applied once to each selected atom; one
+//! with more takes as many atoms, matched to its anchors in the order the
+//! atoms were made.
+
+use common::ids::AtomSpecifier;
+use molecule::{
+    composite::{AnchorCountError, Composite},
+    edit::EditContext,
+};
+use scene::Assembly;
+
+use super::selection::Selection;
+
+/// Why a composite could not be applied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApplyCompositeError {
+    /// Nothing has been saved to the library.
+    Empty,
+    /// No atoms are selected.
+    NoAtoms,
+    /// The selected atoms belong to a locked molecule.
+    Locked,
+    AnchorCount(AnchorCountError),
+}
+
+#[derive(Default)]
+pub struct CompositeLibrary {
+    composites: Vec<Composite>,
+    chosen: usize,
+}
+
+impl CompositeLibrary {
+    pub fn composites(&self) -> &[Composite] {
+        &self.composites
+    }
+
+    /// The composite that is applied next.
+    pub fn chosen(&self) -> Option<&Composite> {
+        self.composites.get(self.chosen)
+    }
+
+    /// Chooses the next composite, going back to the first after the last.
+    pub fn cycle(&mut self) -> Option<&Composite> {
+        if !self.composites.is_empty() {
+            self.chosen = (self.chosen + 1) % self.composites.len();
+        }
+        self.chosen()
+    }
+
+    /// Saves a copy of `composite`, replacing one of the same name, and
+    /// chooses it.
+    pub fn save(&mut self, composite: &Composite) {
+        let mut composite = composite.clone();
+        composite.collapsed = true;
+        match self
+            .composites
+            .iter()
+            .position(|other| other.name == composite.name)
+        {
+            Some(index) => {
+                self.composites[index] = composite;
+                self.chosen = index;
+            }
+            None => {
+                self.composites.push(composite);
+                self.chosen = self.composites.len() - 1;
+            }
+        }
+    }
+
+    /// Applies the chosen composite to the selected atoms.  Returns how many
+    /// copies were added.
+    pub fn apply(
+        &self,
+        assembly: &mut Assembly,
+        selection: &Selection,
+    ) -> Result<usize, ApplyCompositeError> {
+        let composite = self.chosen().ok_or(ApplyCompositeError::Empty)?;
+        let component = selection.component().ok_or(ApplyCompositeError::NoAtoms)?;
+        let molecule = assembly
+            .component_mut(component)
+            .ok_or(ApplyCompositeError::NoAtoms)?
+            .molecule_mut()
+            .ok_or(ApplyCompositeError::Locked)?;
+        let targets: Vec<AtomSpecifier> = EditContext::atoms(&molecule.repr)
+            .into_iter()
+            .filter(|atom| selection.atoms().contains(atom))
+            .collect();
+        if targets.is_empty() {
+            return Err(ApplyCompositeError::NoAtoms);
+        }
+
+        let groups: Vec<&[AtomSpecifier]> = match composite.anchors().len() {
+            0 => vec![&[]],
+            1 => targets.chunks(1).collect(),
+            _ => vec![&targets[..]],
+        };
+        for group in &groups {
+            molecule
+                .add_composite(composite, group)
+                .map_err(ApplyCompositeError::AnchorCount)?;
+        }
+        Ok(groups.len())
+    }
+
+    /// Replaces the library with the one saved by `json`.
+    pub fn load(&mut self, json: &str) -> serde_json::Result<()> {
+        self.composites = serde_json::from_str(json)?;
+        self.chosen = 0;
+        Ok(())
+    }
+
+    pub fn json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&self.composites)
+    }
+}
+
+// End of File
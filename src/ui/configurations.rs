@@ -0,0 +1,134 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The dropdown of named configurations, e.g. "full assembly", "cutaway" and
+//! "mechanism only", each of which shows the assembly in its own way.
+//!
+//! The configurations are kept in the assembly rather than here, so that they
+//! are saved along with it.
+
+use render::RenderCamera;
+use scene::Assembly;
+
+/// How much each step of the explode control spreads the assembly apart.
+pub const EXPLODE_STEP: f32 = 0.25;
+
+#[derive(Clone, Debug)]
+pub struct ConfigurationRow {
+    pub name: String,
+    /// Whether this is the configuration last saved or switched to.
+    pub active: bool,
+}
+
+#[derive(Default)]
+pub struct ConfigurationMenu {
+    open: bool,
+    active: Option<String>,
+}
+
+impl ConfigurationMenu {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    /// The name of the configuration last saved or switched to, if any.
+    pub fn active(&self) -> Option<&str> {
+        self.active.as_deref()
+    }
+
+    /// Marks the configuration `name` as the active one, if the assembly has
+    /// it, without switching to it.
+    pub fn set_active(&mut self, assembly: &Assembly, name: Option<&str>) {
+        self.active = name
+            .filter(|name| assembly.configurations().iter().any(|c| c.name == *name))
+            .map(str::to_owned);
+    }
+
+    pub fn rows(&self, assembly: &Assembly) -> Vec<ConfigurationRow> {
+        assembly
+            .configurations()
+            .iter()
+            .map(|configuration| ConfigurationRow {
+                name: configuration.name.clone(),
+                active: self.active.as_ref() == Some(&configuration.name),
+            })
+            .collect()
+    }
+
+    /// The name that the next new configuration is given by default.
+    pub fn next_name(&self, assembly: &Assembly) -> String {
+        let count = assembly.configurations().len();
+        (count + 1..)
+            .map(|n| format!("Configuration {}", n))
+            .find(|name| assembly.configurations().iter().all(|c| c.name != *name))
+            .expect("there is always an unused name")
+    }
+
+    /// Saves how the assembly and the camera are shown now as the
+    /// configuration `name`, replacing any with that name, and makes it the
+    /// active one.
+    pub fn save(&mut self, assembly: &mut Assembly, camera: &RenderCamera, name: String) {
+        let mut configuration = assembly.capture_configuration(name.clone());
+        configuration.camera = camera.pose();
+        configuration.projection = camera.projection();
+        assembly.save_configuration(configuration);
+        self.active = Some(name);
+    }
+
+    /// Switches to the configuration in row `index`, turning the camera to
+    /// its saved pose.  Closes the dropdown.  Returns whether there was such a
+    /// configuration.
+    pub fn choose(
+        &mut self,
+        assembly: &mut Assembly,
+        camera: &mut RenderCamera,
+        index: usize,
+    ) -> bool {
+        let Some(configuration) = assembly.configurations().get(index).cloned() else {
+            return false;
+        };
+        assembly.apply_configuration(&configuration);
+        if let Some(pose) = configuration.camera {
+            camera.set_pose(pose, true);
+        }
+        camera.set_projection(configuration.projection);
+        self.active = Some(configuration.name);
+        self.open = false;
+        true
+    }
+
+    /// Switches to the configuration after the active one, going back to the
+    /// first after the last.  Returns the name of the configuration switched
+    /// to, if there are any.
+    pub fn cycle(&mut self, assembly: &mut Assembly, camera: &mut RenderCamera) -> Option<String> {
+        let configurations = assembly.configurations();
+        let next = match configurations
+            .iter()
+            .position(|c| self.active.as_ref() == Some(&c.name))
+        {
+            Some(index) => (index + 1) % configurations.len(),
+            None => 0,
+        };
+        self.choose(assembly, camera, next)
+            .then(|| self.active.clone())
+            .flatten()
+    }
+
+    /// Deletes the configuration in row `index`.
+    pub fn remove(&mut self, assembly: &mut Assembly, index: usize) {
+        let Some(name) = assembly.configurations().get(index).map(|c| c.name.clone()) else {
+            return;
+        };
+        assembly.remove_configuration(&name);
+        if self.active.as_ref() == Some(&name) {
+            self.active = None;
+        }
+    }
+}
+
+// End of File
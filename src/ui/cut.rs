@@ -0,0 +1,501 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The cut tool, for sculpting a part out of a molecule such as an imported
+//! crystal.  Clicking a molecule places a cutting shape on it, which is drawn
+//! as a wireframe of dots and can be dragged about, resized and turned before
+//! the cut is made.  The atoms that the cut would delete are previewed in red,
+//! and Enter commits the cut as an [`Edit::Cut`].
+
+use molecule::{
+    cut::{Cut, CutShape},
+    edit::{Edit, EditContext},
+};
+use periodic_table::Element;
+use render::{AtomBuffer, AtomKind, AtomRepr, GlobalRenderResources};
+use scene::{Assembly, ComponentId};
+use ultraviolet::{Mat4, Rotor3, Vec3};
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use super::{
+    passivation::SPECIES,
+    tools::{OverlayDraw, Tool, ToolContext},
+};
+
+/// The color that atoms the cut would delete are drawn in.
+const REMOVED_TINT: Vec3 = Vec3::new(1.0, 0.25, 0.2);
+
+/// The color of the dots that outline the cutting shape.
+const GIZMO_TINT: Vec3 = Vec3::new(1.0, 0.85, 0.3);
+
+/// How far apart the dots of the outline are, in angstroms.
+const GIZMO_SPACING: f32 = 0.6;
+
+/// How far the previewed atoms are drawn in front of the atoms they cover, in
+/// angstroms, so that they are not hidden behind them.
+const PREVIEW_NUDGE: f32 = 0.05;
+
+/// The size that a shape starts out at, in angstroms.
+const DEFAULT_SIZE: f32 = 5.0;
+
+/// How much each press of `+` or `-` grows or shrinks the shape.
+const SIZE_STEP: f32 = 1.1;
+
+/// The kinds of shape that can be cut with, in the order of the number keys
+/// that choose them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShapeKind {
+    #[default]
+    Plane,
+    Box,
+    Sphere,
+    Cylinder,
+}
+
+impl ShapeKind {
+    pub const ALL: [ShapeKind; 4] = [
+        ShapeKind::Plane,
+        ShapeKind::Box,
+        ShapeKind::Sphere,
+        ShapeKind::Cylinder,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShapeKind::Plane => "plane",
+            ShapeKind::Box => "box",
+            ShapeKind::Sphere => "sphere",
+            ShapeKind::Cylinder => "cylinder",
+        }
+    }
+}
+
+/// The molecule being cut, and the state of the gizmo on it.  Positions are in
+/// the molecule's own space, which the edit works in.
+struct Target {
+    component: ComponentId,
+    /// The molecule's transform into the world.
+    transform: Mat4,
+    center: Vec3,
+}
+
+pub struct CutTool {
+    active: bool,
+    target: Option<Target>,
+    kind: ShapeKind,
+    /// The normal of the plane (pointing at the side that is deleted), or the
+    /// axis of the cylinder.
+    axis: Vec3,
+    /// The radius of the sphere and cylinder, and half the width of the box
+    /// and the length of the cylinder.  The plane is drawn this big.
+    size: f32,
+    keep_inside: bool,
+    cap: Option<Element>,
+    dragging: bool,
+    /// The direction the camera looks along, towards which the preview is
+    /// nudged.
+    view_direction: Vec3,
+    removed: Vec<AtomRepr>,
+    removed_buffer: Option<AtomBuffer>,
+    gizmo_buffer: Option<AtomBuffer>,
+    buffers_stale: bool,
+}
+
+impl Default for CutTool {
+    fn default() -> Self {
+        Self {
+            active: false,
+            target: None,
+            kind: ShapeKind::default(),
+            axis: Vec3::unit_z(),
+            size: DEFAULT_SIZE,
+            keep_inside: true,
+            cap: None,
+            dragging: false,
+            view_direction: -Vec3::unit_z(),
+            removed: Vec::new(),
+            removed_buffer: None,
+            gizmo_buffer: None,
+            buffers_stale: false,
+        }
+    }
+}
+
+impl CutTool {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn target(&self) -> Option<ComponentId> {
+        self.target.as_ref().map(|target| target.component)
+    }
+
+    pub fn kind(&self) -> ShapeKind {
+        self.kind
+    }
+
+    /// The shape as it stands, in the target molecule's space, or `None` if no
+    /// molecule has been clicked yet.
+    pub fn shape(&self) -> Option<CutShape> {
+        let center = self.target.as_ref()?.center;
+        Some(match self.kind {
+            ShapeKind::Plane => CutShape::HalfSpace(common::Plane::new(center, self.axis)),
+            // A cube looks the same from every axis, so is never turned.
+            ShapeKind::Box => CutShape::Box {
+                center,
+                half_extents: Vec3::broadcast(self.size),
+                rotation: Rotor3::identity(),
+            },
+            ShapeKind::Sphere => CutShape::Sphere {
+                center,
+                radius: self.size,
+            },
+            ShapeKind::Cylinder => CutShape::Cylinder {
+                center,
+                axis: self.axis,
+                radius: self.size,
+                half_length: self.size,
+            },
+        })
+    }
+
+    /// The edit that the tool would make, or `None` if there is no shape yet.
+    pub fn cut(&self) -> Option<Cut> {
+        Some(Cut {
+            keep_inside: self.keep_inside,
+            cap: self.cap,
+            ..Cut::new(self.shape()?)
+        })
+    }
+
+    /// The settings as a line of text.
+    pub fn summary(&self) -> String {
+        let cut = match self.cut() {
+            Some(cut) => cut.describe(),
+            None => format!("{} (click a molecule to place it)", self.kind.name()),
+        };
+        format!("cutting: {}, size {:.1} Å", cut, self.size)
+    }
+
+    /// Places the shape on the molecule `component`, centered on `center` (in
+    /// world space).  Locked molecules cannot be cut.
+    fn place(&mut self, assembly: &Assembly, component: ComponentId, center: Vec3) -> bool {
+        if assembly.is_locked(component) != Some(false) {
+            return false;
+        }
+        let Some(transform) = assembly.world_transform(component) else {
+            return false;
+        };
+        self.target = Some(Target {
+            component,
+            transform,
+            center: transform.inversed().transform_point3(center),
+        });
+        true
+    }
+
+    // Moves the shape to where the pointer ray crosses the plane through its
+    // center facing the camera.  The plane only moves along its normal, so
+    // that dragging it sideways does not slide it within itself.
+    fn drag(&mut self, ray: (Vec3, Vec3)) {
+        let Some(target) = &mut self.target else {
+            return;
+        };
+        let (origin, direction) = ray;
+        let center = target.transform.transform_point3(target.center);
+        let grabbed = origin + direction * (center - origin).dot(direction);
+        let local = target.transform.inversed().transform_point3(grabbed);
+        target.center = match self.kind {
+            ShapeKind::Plane => target.center + self.axis * (local - target.center).dot(self.axis),
+            _ => local,
+        };
+    }
+
+    /// Works out which atoms the cut would delete, and redraws the preview.
+    fn refresh(&mut self, assembly: &Assembly) {
+        self.buffers_stale = true;
+        self.removed.clear();
+        let (Some(target), Some(cut)) = (&self.target, self.cut()) else {
+            return;
+        };
+        let Some(molecule) = assembly
+            .component(target.component)
+            .and_then(|component| component.molecule())
+        else {
+            self.target = None;
+            return;
+        };
+        let repr = &molecule.repr;
+        for atom in cut.removed(repr) {
+            let (Some(node), Some(pos)) = (
+                EditContext::find_atom(repr, &atom),
+                EditContext::pos(repr, &atom),
+            ) else {
+                continue;
+            };
+            self.removed.push(AtomRepr {
+                pos: *pos,
+                kind: AtomKind::new(node.element),
+                property: 0.0,
+            });
+        }
+    }
+
+    /// Adds the cut to the target molecule.  Returns whether it was made.
+    fn commit(&mut self, assembly: &mut Assembly) -> bool {
+        let (Some(target), Some(cut)) = (&self.target, self.cut()) else {
+            return false;
+        };
+        let Some(molecule) = assembly
+            .component_mut(target.component)
+            .and_then(|component| component.molecule_mut())
+        else {
+            return false;
+        };
+        let removed = self.removed.len();
+        molecule.add_edit(Edit::Cut(cut));
+        log::info!("cut {} atoms from {}", removed, molecule.name());
+        self.target = None;
+        self.refresh(assembly);
+        true
+    }
+
+    // The dots outlining the shape, in the target molecule's space.
+    fn gizmo_points(&self) -> Vec<Vec3> {
+        let Some(shape) = self.shape() else {
+            return Vec::new();
+        };
+        let (u, v) = perpendiculars(self.axis);
+        let mut points = Vec::new();
+        match shape {
+            CutShape::HalfSpace(plane) => {
+                let center = plane.point;
+                ring(&mut points, center, u, v, self.size);
+                line(&mut points, center - u * self.size, center + u * self.size);
+                line(&mut points, center - v * self.size, center + v * self.size);
+                // A spike shows which side is deleted.
+                line(&mut points, center, center + plane.normal * self.size * 0.5);
+            }
+            CutShape::Box {
+                center,
+                half_extents,
+                ..
+            } => {
+                let corner = |signs: [f32; 3]| center + Vec3::from(signs) * half_extents;
+                for edge in 0..4 {
+                    let (a, b) = (
+                        if edge & 1 == 0 { -1.0 } else { 1.0 },
+                        if edge & 2 == 0 { -1.0 } else { 1.0 },
+                    );
+                    line(&mut points, corner([-1.0, a, b]), corner([1.0, a, b]));
+                    line(&mut points, corner([a, -1.0, b]), corner([a, 1.0, b]));
+                    line(&mut points, corner([a, b, -1.0]), corner([a, b, 1.0]));
+                }
+            }
+            CutShape::Sphere { center, radius } => {
+                ring(&mut points, center, Vec3::unit_x(), Vec3::unit_y(), radius);
+                ring(&mut points, center, Vec3::unit_y(), Vec3::unit_z(), radius);
+                ring(&mut points, center, Vec3::unit_z(), Vec3::unit_x(), radius);
+            }
+            CutShape::Cylinder {
+                center,
+                axis,
+                radius,
+                half_length,
+            } => {
+                for end in [-half_length, half_length] {
+                    ring(&mut points, center + axis * end, u, v, radius);
+                }
+                for side in [u, -u, v, -v] {
+                    let foot = center + side * radius;
+                    line(
+                        &mut points,
+                        foot - axis * half_length,
+                        foot + axis * half_length,
+                    );
+                }
+            }
+        }
+        points
+    }
+}
+
+// Two unit vectors perpendicular to `axis` and to each other.
+fn perpendiculars(axis: Vec3) -> (Vec3, Vec3) {
+    let other = if axis.x.abs() < 0.9 {
+        Vec3::unit_x()
+    } else {
+        Vec3::unit_y()
+    };
+    let u = axis.cross(other).normalized();
+    (u, axis.cross(u))
+}
+
+// Dots along the circle of `radius` about `center` in the plane of `u` and
+// `v`.
+fn ring(points: &mut Vec<Vec3>, center: Vec3, u: Vec3, v: Vec3, radius: f32) {
+    let count = ((std::f32::consts::TAU * radius / GIZMO_SPACING).ceil() as usize).max(8);
+    points.extend((0..count).map(|i| {
+        let angle = std::f32::consts::TAU * i as f32 / count as f32;
+        center + (u * angle.cos() + v * angle.sin()) * radius
+    }));
+}
+
+// Dots along the line from `start` to `end`, including both ends.
+fn line(points: &mut Vec<Vec3>, start: Vec3, end: Vec3) {
+    let count = (((end - start).mag() / GIZMO_SPACING).ceil() as usize).max(1);
+    points.extend((0..=count).map(|i| start + (end - start) * (i as f32 / count as f32)));
+}
+
+impl Tool for CutTool {
+    fn set_active(&mut self, active: bool) {
+        self.active = active;
+        self.dragging = false;
+        if !active {
+            self.target = None;
+            self.removed.clear();
+            self.buffers_stale = true;
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        Some(
+            "cutting: click a molecule to place the shape and drag to move it, 1-4 to choose \
+             the shape, X/Y/Z to turn it, +/- to resize it, I to keep inside or outside, C to \
+             cap, Enter to cut"
+                .to_owned(),
+        )
+    }
+
+    fn on_pointer_down(&mut self, context: ToolContext) -> bool {
+        let hit = context.pointer.hit(context.assembly);
+        let grabbing = match (&hit, self.target()) {
+            (Some((component, _)), Some(target)) => *component == target,
+            (None, target) => target.is_some(),
+            (Some(_), None) => false,
+        };
+        if grabbing {
+            self.dragging = true;
+            return true;
+        }
+        let Some((component, atom)) = hit else {
+            return false;
+        };
+        let Some(center) = context.assembly.atom_position(component, &atom) else {
+            return false;
+        };
+        if !self.place(context.assembly, component, center) {
+            log::info!("the molecule is locked");
+            return true;
+        }
+        self.refresh(context.assembly);
+        log::info!("{}", self.summary());
+        true
+    }
+
+    fn on_pointer_move(&mut self, context: ToolContext) -> bool {
+        let Some(ray) = context.pointer.ray else {
+            return false;
+        };
+        self.view_direction = ray.1;
+        if !self.dragging {
+            return false;
+        }
+        self.drag(ray);
+        self.refresh(context.assembly);
+        true
+    }
+
+    fn on_pointer_up(&mut self, _context: ToolContext) -> bool {
+        std::mem::take(&mut self.dragging)
+    }
+
+    fn on_key(&mut self, context: ToolContext, key: KeyCode, modifiers: &ModifiersState) -> bool {
+        // Keys held with a modifier are shortcuts meant for the application.
+        if modifiers.alt_key() || modifiers.control_key() || modifiers.super_key() {
+            return false;
+        }
+        match key {
+            KeyCode::Digit1 | KeyCode::Digit2 | KeyCode::Digit3 | KeyCode::Digit4 => {
+                let index = match key {
+                    KeyCode::Digit1 => 0,
+                    KeyCode::Digit2 => 1,
+                    KeyCode::Digit3 => 2,
+                    _ => 3,
+                };
+                self.kind = ShapeKind::ALL[index];
+            }
+            KeyCode::KeyX | KeyCode::KeyY | KeyCode::KeyZ => {
+                let axis = match key {
+                    KeyCode::KeyX => Vec3::unit_x(),
+                    KeyCode::KeyY => Vec3::unit_y(),
+                    _ => Vec3::unit_z(),
+                };
+                // Choosing the same axis again turns the shape around.
+                self.axis = if self.axis == axis { -axis } else { axis };
+            }
+            KeyCode::Equal | KeyCode::NumpadAdd => self.size *= SIZE_STEP,
+            KeyCode::Minus | KeyCode::NumpadSubtract => self.size /= SIZE_STEP,
+            KeyCode::KeyI => self.keep_inside = !self.keep_inside,
+            KeyCode::KeyC => {
+                let index = self
+                    .cap
+                    .and_then(|cap| SPECIES.iter().position(|species| *species == cap))
+                    .map_or(0, |index| index + 1);
+                self.cap = SPECIES.get(index).copied();
+            }
+            KeyCode::Enter | KeyCode::NumpadEnter => {
+                if !self.commit(context.assembly) {
+                    log::info!("click a molecule to place the shape first");
+                }
+                return true;
+            }
+            KeyCode::Escape if self.target.is_some() => {
+                self.target = None;
+            }
+            _ => return false,
+        }
+        self.refresh(context.assembly);
+        log::info!("{}", self.summary());
+        true
+    }
+
+    fn synchronize_buffers(&mut self, gpu_resources: &GlobalRenderResources) {
+        if !self.buffers_stale {
+            return;
+        }
+        self.buffers_stale = false;
+        self.removed_buffer = (!self.removed.is_empty())
+            .then(|| AtomBuffer::new(gpu_resources, self.removed.clone()));
+        let gizmo = self.gizmo_points();
+        self.gizmo_buffer = (!gizmo.is_empty()).then(|| {
+            AtomBuffer::new(
+                gpu_resources,
+                gizmo.into_iter().map(|pos| AtomRepr {
+                    pos,
+                    kind: AtomKind::new(Element::Hydrogen),
+                    property: 0.0,
+                }),
+            )
+        });
+    }
+
+    fn render_overlay(&self) -> Vec<OverlayDraw<'_>> {
+        let Some(target) = &self.target else {
+            return Vec::new();
+        };
+        let nudged =
+            Mat4::from_translation(-self.view_direction * PREVIEW_NUDGE) * target.transform;
+        let mut draws = Vec::new();
+        if let Some(buffer) = &self.removed_buffer {
+            draws.push((buffer, nudged, Some(REMOVED_TINT)));
+        }
+        if let Some(buffer) = &self.gizmo_buffer {
+            draws.push((buffer, target.transform, Some(GIZMO_TINT)));
+        }
+        draws
+    }
+}
+
+// End of File
@@ -0,0 +1,155 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The problems panel: the edits in every molecule of the assembly that failed
+//! the last time they were applied, e.g. because an atom they refer to was made
+//! by an edit that has since been deleted.  The molecule is still built, but
+//! may not be what was intended.  Choosing a problem shows its molecule's
+//! history with the failed edit highlighted, and selects the atoms the edit
+//! refers to or made.
+
+use common::ids::EditId;
+use molecule::selection::{self, AtomSelection};
+use scene::{Assembly, ComponentId};
+
+use super::edit_history::EditHistory;
+
+/// One problem, i.e. one failed edit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiagnosticRow {
+    pub component: ComponentId,
+    pub edit: EditId,
+    /// E.g. "Ethanol #3 Bonded Atom: it refers to an atom that does not
+    /// exist (1.0:0 is missing)".
+    pub description: String,
+    /// Whether the problem was the last one chosen.
+    pub highlighted: bool,
+}
+
+impl DiagnosticRow {
+    /// The atoms of the molecule that the failed edit refers to or made, of
+    /// those that exist.
+    pub fn atoms(&self, assembly: &Assembly) -> AtomSelection {
+        let Some(molecule) = assembly
+            .component(self.component)
+            .and_then(|component| component.molecule())
+        else {
+            return AtomSelection::new();
+        };
+        match molecule.edits().get(&self.edit) {
+            Some(edit) => selection::edit_atoms(&molecule.repr, self.edit, edit),
+            None => AtomSelection::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DiagnosticsPanel {
+    // The problems as of the last refresh.
+    known: Vec<(ComponentId, EditId, String)>,
+    highlighted: Option<(ComponentId, EditId)>,
+}
+
+impl DiagnosticsPanel {
+    /// Lists the problems in the assembly, molecule by molecule, each in
+    /// history order.
+    pub fn rows(&self, assembly: &Assembly) -> Vec<DiagnosticRow> {
+        let mut rows = Vec::new();
+        for component in assembly.components() {
+            let Some(molecule) = component.molecule() else {
+                continue;
+            };
+            let edits = molecule.edits();
+            for diagnostic in molecule.diagnostics() {
+                let (Some(index), Some(edit)) =
+                    (edits.position(diagnostic.edit), edits.get(&diagnostic.edit))
+                else {
+                    continue;
+                };
+                rows.push(DiagnosticRow {
+                    component: component.id(),
+                    edit: diagnostic.edit,
+                    description: format!(
+                        "{} #{} {}: {}",
+                        molecule.name(),
+                        index + 1,
+                        edit.name(),
+                        diagnostic
+                    ),
+                    highlighted: self.highlighted == Some((component.id(), diagnostic.edit)),
+                });
+            }
+        }
+        rows
+    }
+
+    /// Looks for problems again, and returns those that were not there the
+    /// last time, e.g. to tell the user about an edit that just failed.
+    pub fn refresh(&mut self, assembly: &Assembly) -> Vec<DiagnosticRow> {
+        let rows = self.rows(assembly);
+        let new = rows
+            .iter()
+            .filter(|row| {
+                !self.known.iter().any(|(component, edit, description)| {
+                    *component == row.component
+                        && *edit == row.edit
+                        && *description == row.description
+                })
+            })
+            .cloned()
+            .collect();
+        self.known = rows
+            .into_iter()
+            .map(|row| (row.component, row.edit, row.description))
+            .collect();
+        if self.highlighted.is_some_and(|highlighted| {
+            !self
+                .known
+                .iter()
+                .any(|(component, edit, _)| (*component, *edit) == highlighted)
+        }) {
+            self.highlighted = None;
+        }
+        new
+    }
+
+    /// Chooses the problem after the last one chosen, going back to the first
+    /// after the last, and shows it in the edit history.  Returns `None` if
+    /// there are no problems.
+    pub fn choose_next(
+        &mut self,
+        assembly: &Assembly,
+        history: &mut EditHistory,
+    ) -> Option<DiagnosticRow> {
+        let rows = self.rows(assembly);
+        let next = match rows.iter().position(|row| row.highlighted) {
+            Some(index) => (index + 1) % rows.len(),
+            None => 0,
+        };
+        let row = rows.get(next)?;
+        self.choose(assembly, history, row.component, row.edit)
+    }
+
+    /// Chooses the failed edit `edit` of the molecule `component`, and shows
+    /// it in the edit history.  Returns `None` if it has not failed.
+    pub fn choose(
+        &mut self,
+        assembly: &Assembly,
+        history: &mut EditHistory,
+        component: ComponentId,
+        edit: EditId,
+    ) -> Option<DiagnosticRow> {
+        let mut row = self
+            .rows(assembly)
+            .into_iter()
+            .find(|row| row.component == component && row.edit == edit)?;
+        self.highlighted = Some((row.component, row.edit));
+        row.highlighted = true;
+        history.show(row.component);
+        history.highlight(Some(row.edit));
+        Some(row)
+    }
+}
+
+// End of File
@@ -0,0 +1,265 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The documents open in the window, each an assembly with its own selection,
+//! camera, history and file, switched between with a row of tabs.
+//!
+//! Only one document is shown at a time, and its state is kept where the rest
+//! of the application works on it: its assembly is the world, its selection,
+//! history and the panels that refer to its components are the panels', and
+//! its camera is the renderer's.  The others
+//! are parked here as [`Document`]s.  Switching parks the shown document and
+//! takes out the chosen one to be shown in its place.  The clipboard is shared
+//! by every document, so that parts can be copied from one and pasted into
+//! another.
+
+use std::path::{Path, PathBuf};
+
+use render::{CameraPose, Projection};
+use scene::{Assembly, ProjectHistory};
+
+use super::{
+    command_log::CommandLog, compare::CompareView, configurations::ConfigurationMenu,
+    diagnostics::DiagnosticsPanel, inspector::Inspector, links::LinkedFiles, outliner::Outliner,
+    parts::PartsOffer, selection::Selection, timeline::Timeline,
+};
+
+/// What a document's tab is labelled when it has no name or file.
+const UNTITLED: &str = "Untitled";
+
+/// An open project, as it is kept while it is not being shown.
+#[derive(Default)]
+pub struct Document {
+    pub assembly: Assembly,
+    pub selection: Selection,
+    /// Where the camera was when the document was last shown, or `None` if it
+    /// has not been shown yet.
+    pub camera: Option<(CameraPose, Projection)>,
+    pub history: ProjectHistory,
+    /// The file the project was opened from or last saved to.
+    pub path: Option<PathBuf>,
+    /// The project as it was last opened or saved, or `None` if it never was.
+    pub saved: Option<String>,
+    // The panels that hold the ids of the assembly's components, or other
+    // state that belongs to it, and so must not be shown with another.
+    pub command_log: CommandLog,
+    pub compare: CompareView,
+    pub configurations: ConfigurationMenu,
+    pub diagnostics: DiagnosticsPanel,
+    pub inspector: Inspector,
+    pub links: LinkedFiles,
+    pub outliner: Outliner,
+    pub parts: PartsOffer,
+    pub timeline: Timeline,
+}
+
+/// One tab of the tab row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocumentTab {
+    pub index: usize,
+    pub label: String,
+    pub path: Option<PathBuf>,
+    /// Whether this is the document being shown.
+    pub active: bool,
+}
+
+// A place in the tab row, holding a parked document, or only the file of the
+// one being shown and what was last saved to it.
+enum Slot {
+    Shown {
+        path: Option<PathBuf>,
+        saved: Option<String>,
+    },
+    Parked(Box<Document>),
+}
+
+impl Slot {
+    fn shown(document: &Document) -> Self {
+        Slot::Shown {
+            path: document.path.clone(),
+            saved: document.saved.clone(),
+        }
+    }
+}
+
+pub struct Documents {
+    slots: Vec<Slot>,
+    active: usize,
+    // Whether closing the document being shown was refused because of its
+    // unsaved changes, so that asking again closes it anyway.
+    close_refused: bool,
+}
+
+impl Default for Documents {
+    fn default() -> Self {
+        Self {
+            slots: vec![Slot::Shown {
+                path: None,
+                saved: None,
+            }],
+            active: 0,
+            close_refused: false,
+        }
+    }
+}
+
+impl Documents {
+    /// The position of the document being shown in the tab row.
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    /// The file that the document being shown was opened from or saved to.
+    pub fn active_path(&self) -> Option<&Path> {
+        match &self.slots[self.active] {
+            Slot::Shown { path, .. } => path.as_deref(),
+            Slot::Parked(_) => unreachable!("the active document is shown"),
+        }
+    }
+
+    /// Records that `assembly`, the document being shown, was opened from or
+    /// saved to `path`, so that it has no unsaved changes.
+    pub fn set_active_path(&mut self, path: impl Into<PathBuf>, assembly: &Assembly) {
+        self.slots[self.active] = Slot::Shown {
+            path: Some(path.into()),
+            saved: serde_json::to_string(assembly).ok(),
+        };
+    }
+
+    /// Whether `shown`, the document being shown, was changed since it was
+    /// last opened or saved.  A document that never was has unsaved changes
+    /// once it has any components.
+    pub fn has_unsaved_changes(&self, shown: &Assembly) -> bool {
+        match &self.slots[self.active] {
+            Slot::Shown {
+                saved: Some(saved), ..
+            } => serde_json::to_string(shown).map_or(true, |json| json != *saved),
+            Slot::Shown { saved: None, .. } => shown.components().next().is_some(),
+            Slot::Parked(_) => unreachable!("the active document is shown"),
+        }
+    }
+
+    /// Lists the tabs, where `shown` is the assembly of the document being
+    /// shown.
+    pub fn tabs(&self, shown: &Assembly) -> Vec<DocumentTab> {
+        self.slots
+            .iter()
+            .enumerate()
+            .map(|(index, slot)| {
+                let (assembly, path) = match slot {
+                    Slot::Shown { path, .. } => (shown, path.as_ref()),
+                    Slot::Parked(document) => (&document.assembly, document.path.as_ref()),
+                };
+                DocumentTab {
+                    index,
+                    label: label(assembly, path),
+                    path: path.cloned(),
+                    active: index == self.active,
+                }
+            })
+            .collect()
+    }
+
+    /// Parks `shown`, the document being shown, and adds `document` after it,
+    /// to be shown instead.  Returns `document`.
+    pub fn open(&mut self, shown: Document, document: Document) -> Document {
+        self.park(shown);
+        self.active += 1;
+        self.slots.insert(self.active, Slot::shown(&document));
+        document
+    }
+
+    /// Parks `shown`, the document being shown, and takes out the document
+    /// at `index` to be shown instead.  Returns `shown` again if `index` is
+    /// that of the shown document, or out of range.
+    pub fn switch_to(&mut self, index: usize, shown: Document) -> Document {
+        if index == self.active || index >= self.slots.len() {
+            return shown;
+        }
+        self.park(shown);
+        self.active = index;
+        self.take_active()
+    }
+
+    /// Switches to the next document in the tab row, or the previous one if
+    /// not `forward`, wrapping around at either end.
+    pub fn cycle(&mut self, forward: bool, shown: Document) -> Document {
+        let count = self.slots.len();
+        let index = if forward {
+            (self.active + 1) % count
+        } else {
+            (self.active + count - 1) % count
+        };
+        self.switch_to(index, shown)
+    }
+
+    /// Closes `shown`, the document being shown, and takes out the one next
+    /// to it to be shown instead.  If it has unsaved changes, closing it is
+    /// refused once, and only done when asked again.
+    pub fn close_active(&mut self, shown: &Assembly) -> Result<Document, CloseError> {
+        if self.slots.len() == 1 {
+            return Err(CloseError::LastDocument);
+        }
+        if self.has_unsaved_changes(shown) && !self.close_refused {
+            self.close_refused = true;
+            return Err(CloseError::UnsavedChanges);
+        }
+        self.slots.remove(self.active);
+        self.active = self.active.min(self.slots.len() - 1);
+        Ok(self.take_active())
+    }
+
+    // Keeps `shown` in the slot of the document being shown.
+    fn park(&mut self, mut shown: Document) {
+        if let Slot::Shown { path, saved } = &mut self.slots[self.active] {
+            shown.path = path.take();
+            shown.saved = saved.take();
+        }
+        self.slots[self.active] = Slot::Parked(Box::new(shown));
+        self.close_refused = false;
+    }
+
+    // Takes the parked document out of the active slot, to be shown.
+    fn take_active(&mut self) -> Document {
+        let slot = std::mem::replace(
+            &mut self.slots[self.active],
+            Slot::Shown {
+                path: None,
+                saved: None,
+            },
+        );
+        self.close_refused = false;
+        match slot {
+            Slot::Parked(document) => {
+                let document = *document;
+                self.slots[self.active] = Slot::shown(&document);
+                document
+            }
+            Slot::Shown { .. } => unreachable!("only the active document is shown"),
+        }
+    }
+}
+
+/// Why the document being shown was not closed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseError {
+    /// It is the only document open.
+    LastDocument,
+    /// It has changes that were not saved.  Closing it again closes it anyway.
+    UnsavedChanges,
+}
+
+// What the tab of `assembly`, saved at `path`, is labelled: its name, or the
+// name of its file if it has none.
+fn label(assembly: &Assembly, path: Option<&PathBuf>) -> String {
+    if !assembly.name().is_empty() {
+        return assembly.name().to_owned();
+    }
+    path.and_then(|path| path.file_stem())
+        .map_or(UNTITLED.to_owned(), |stem| {
+            stem.to_string_lossy().into_owned()
+        })
+}
+
+// End of File
@@ -0,0 +1,116 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Random point defects in one step, e.g. doping a lattice with boron or
+//! leaving vacancies in it.  How many atoms are changed, and whether they are
+//! substituted or deleted, is chosen from a short list of presets.  Each
+//! doping gets a new random seed, which is recorded in the edit so that the
+//! same atoms are chosen whenever the history is replayed.
+
+use common::ids::AtomSpecifier;
+use molecule::{doping::Doping, edit::Edit};
+use periodic_table::Element;
+
+/// A kind of doping that can be chosen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DopingPreset {
+    /// The fraction of atoms that are changed.
+    pub fraction: f32,
+    /// Whether the atoms are deleted rather than substituted.
+    pub vacancies: bool,
+}
+
+/// The presets, in the order they are cycled through.
+pub const PRESETS: [DopingPreset; 6] = [
+    DopingPreset {
+        fraction: 0.01,
+        vacancies: false,
+    },
+    DopingPreset {
+        fraction: 0.05,
+        vacancies: false,
+    },
+    DopingPreset {
+        fraction: 0.1,
+        vacancies: false,
+    },
+    DopingPreset {
+        fraction: 0.01,
+        vacancies: true,
+    },
+    DopingPreset {
+        fraction: 0.05,
+        vacancies: true,
+    },
+    DopingPreset {
+        fraction: 0.1,
+        vacancies: true,
+    },
+];
+
+pub struct DopingPanel {
+    preset: usize,
+    next_seed: u64,
+}
+
+impl Default for DopingPanel {
+    fn default() -> Self {
+        // Seeded from the clock, so that each session dopes differently.
+        let now = instant::SystemTime::now()
+            .duration_since(instant::SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64);
+        Self {
+            preset: 0,
+            next_seed: now,
+        }
+    }
+}
+
+impl DopingPanel {
+    pub fn preset(&self) -> DopingPreset {
+        PRESETS[self.preset]
+    }
+
+    /// Moves on to the next preset, going back to the first after the last.
+    pub fn cycle_preset(&mut self) -> DopingPreset {
+        self.preset = (self.preset + 1) % PRESETS.len();
+        self.preset()
+    }
+
+    /// The preset as text, e.g. "5% substituted", for the log.
+    pub fn describe(&self) -> String {
+        let preset = self.preset();
+        format!(
+            "{}% {}",
+            (preset.fraction * 100.0).round(),
+            if preset.vacancies {
+                "vacancies"
+            } else {
+                "substituted"
+            }
+        )
+    }
+
+    /// The edit that dopes `atoms` (such as the selection), or every atom if
+    /// there are none, substituting `element` unless the preset leaves
+    /// vacancies.  Each edit gets a new seed.
+    pub fn edit(
+        &mut self,
+        element: Element,
+        atoms: impl IntoIterator<Item = AtomSpecifier>,
+    ) -> Edit {
+        let preset = self.preset();
+        let seed = self.next_seed;
+        self.next_seed = self.next_seed.wrapping_add(1);
+        Edit::Doping(Doping {
+            substitute: (!preset.vacancies).then_some(element),
+            fraction: preset.fraction,
+            elements: Vec::new(),
+            atoms: atoms.into_iter().collect(),
+            seed,
+        })
+    }
+}
+
+// End of File
@@ -0,0 +1,554 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The list of edits that make up the molecule being worked on.
+//!
+//! Several edits can be selected at once and changed together: suppressed,
+//! deleted, grouped into a folder, or exported as a fragment.  Each such bulk
+//! change is a single step for undo.  A single selected edit can also be moved
+//! earlier or later in the history.  Deleting or moving edits is refused if a
+//! later edit would lose atoms it refers to, unless it is forced.
+//!
+//! Edits can be grouped into folders, which can hold other folders.  The
+//! history is shown as a tree, in which each folder appears where its first
+//! edit would, followed by everything in it unless it is collapsed.
+//!
+//! Edits next to each other can also be combined into a composite edit, which
+//! is a single edit made of several steps (see [`Composite`]).  Its steps are
+//! listed under it in the tree unless it is collapsed.
+
+use std::collections::BTreeSet;
+
+use common::ids::{AtomSpecifier, EditId};
+use molecule::{
+    composite::{CombineError, Composite},
+    edit::{Dependency, Edit, EditList, FolderId},
+    MoleculeEditor,
+};
+use scene::{Assembly, ComponentId};
+
+/// One line of the edit history.
+#[derive(Clone, Debug)]
+pub struct EditHistoryRow {
+    pub id: EditId,
+    /// The edit's position in the history, starting from 1.
+    pub number: usize,
+    pub label: String,
+    /// Whether the edit is before the molecule's current history step, i.e.
+    /// whether its effects are part of the molecule as it is displayed.
+    pub applied: bool,
+    pub highlighted: bool,
+    pub selected: bool,
+    /// Whether the edit is skipped when the molecule is built.
+    pub suppressed: bool,
+    /// Why the edit failed, if it did the last time it was applied.
+    pub problem: Option<String>,
+    /// The name of the folder the edit is in, if any.
+    pub folder: Option<String>,
+    /// Whether the edit's steps are hidden, if it is a composite.
+    pub collapsed: Option<bool>,
+}
+
+/// A step of a composite edit in the tree view of the edit history.
+#[derive(Clone, Debug)]
+pub struct StepRow {
+    /// The composite that the step is part of.
+    pub composite: EditId,
+    /// The step's position in the composite, starting from 1.
+    pub number: usize,
+    pub label: String,
+}
+
+/// A folder in the tree view of the edit history.
+#[derive(Clone, Debug)]
+pub struct FolderRow {
+    pub id: FolderId,
+    pub name: String,
+    pub collapsed: bool,
+    /// Whether the folder, or one it is in, is suppressed.
+    pub suppressed: bool,
+    /// How many edits the folder holds, including those in folders inside it.
+    pub edit_count: usize,
+}
+
+#[derive(Clone, Debug)]
+pub enum EditTreeEntry {
+    Folder(FolderRow),
+    Edit(EditHistoryRow),
+    Step(StepRow),
+}
+
+/// One line of the tree view of the edit history.
+#[derive(Clone, Debug)]
+pub struct EditTreeRow {
+    /// How many folders (or composites) the line is in.
+    pub depth: usize,
+    pub entry: EditTreeEntry,
+}
+
+// An edit or a folder, in the order the tree is built in.
+#[derive(Clone, Copy)]
+enum TreeItem {
+    Edit(EditId),
+    Folder(FolderId),
+}
+
+/// The edit history panel.  It shows the edits of one molecule at a time and
+/// can highlight one of them, e.g. to show which edit created a clicked atom.
+#[derive(Default)]
+pub struct EditHistory {
+    component: Option<ComponentId>,
+    highlighted: Option<EditId>,
+    selected: BTreeSet<EditId>,
+}
+
+impl EditHistory {
+    /// The component whose molecule is shown.
+    pub fn component(&self) -> Option<ComponentId> {
+        self.component
+    }
+
+    /// Shows the edits of `component`, which should hold a molecule.
+    pub fn show(&mut self, component: ComponentId) {
+        if self.component != Some(component) {
+            self.component = Some(component);
+            self.highlighted = None;
+            self.selected.clear();
+        }
+    }
+
+    pub fn highlighted(&self) -> Option<EditId> {
+        self.highlighted
+    }
+
+    pub fn highlight(&mut self, edit: Option<EditId>) {
+        self.highlighted = edit;
+    }
+
+    /// Shows the molecule containing an atom and highlights the edit that
+    /// created that atom, or the composite that it is a step of.
+    pub fn jump_to_atom(
+        &mut self,
+        assembly: &Assembly,
+        component: ComponentId,
+        atom: &AtomSpecifier,
+    ) {
+        self.show(component);
+        self.highlighted = Some(self.edit_of(assembly, atom));
+    }
+
+    pub fn selected(&self) -> &BTreeSet<EditId> {
+        &self.selected
+    }
+
+    /// Adds an edit to the selection, or takes it out if it is already in it.
+    pub fn toggle_selected(&mut self, edit: EditId) {
+        if !self.selected.remove(&edit) {
+            self.selected.insert(edit);
+        }
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selected.clear();
+    }
+
+    /// Selects the edit that created an atom, along with those already
+    /// selected, or deselects it if it was selected.  Shows the molecule
+    /// containing the atom first, which clears the selection if it was not
+    /// already shown.
+    pub fn toggle_atom(
+        &mut self,
+        assembly: &Assembly,
+        component: ComponentId,
+        atom: &AtomSpecifier,
+    ) {
+        self.show(component);
+        self.toggle_selected(self.edit_of(assembly, atom));
+    }
+
+    // The edit in the shown molecule's history that created `atom`.
+    fn edit_of(&self, assembly: &Assembly, atom: &AtomSpecifier) -> EditId {
+        match self.molecule(assembly) {
+            Some(molecule) => molecule.edits().top_level(atom.edit_id()),
+            None => atom.edit_id(),
+        }
+    }
+
+    /// Suppresses the selected edits, or restores them if they are all
+    /// suppressed already.  Returns whether they are now suppressed.
+    pub fn suppress_selected(&mut self, assembly: &mut Assembly) -> Option<bool> {
+        let ids = self.selected_ids();
+        let molecule = self.molecule_mut(assembly)?;
+        let suppressed = !ids.iter().all(|id| molecule.edits().is_suppressed(*id));
+        molecule.suppress_edits(&ids, suppressed);
+        Some(suppressed)
+    }
+
+    /// Deletes the selected edits, and clears the selection.  Returns how
+    /// many were deleted.  Unless `force` is set, nothing is deleted if later
+    /// edits refer to atoms that the selected ones created, and those edits
+    /// are described instead.
+    pub fn delete_selected(
+        &mut self,
+        assembly: &mut Assembly,
+        force: bool,
+    ) -> Result<usize, Vec<String>> {
+        let ids = self.selected_ids();
+        let Some(molecule) = self.molecule_mut(assembly) else {
+            return Ok(0);
+        };
+        if let Err(dependents) = molecule.remove_edits(&ids, force) {
+            return Err(describe_dependencies(molecule, &dependents));
+        }
+        self.selected.clear();
+        if self.highlighted.is_some_and(|id| ids.contains(&id)) {
+            self.highlighted = None;
+        }
+        Ok(ids.len())
+    }
+
+    /// Moves the selected edit one place earlier in the history, or later if
+    /// not `earlier`.  Returns its new number, or `None` if there is not
+    /// exactly one edit selected or it cannot move further.  Unless `force` is
+    /// set, it is not moved past an edit that it depends on, or that depends
+    /// on it, and the dependencies are described instead.
+    pub fn move_selected(
+        &mut self,
+        assembly: &mut Assembly,
+        earlier: bool,
+        force: bool,
+    ) -> Result<Option<usize>, Vec<String>> {
+        let [id] = self.selected_ids()[..] else {
+            return Ok(None);
+        };
+        let Some(molecule) = self.molecule_mut(assembly) else {
+            return Ok(None);
+        };
+        let Some(old) = molecule.edits().position(id) else {
+            return Ok(None);
+        };
+        let position = match earlier {
+            true if old > 0 => old - 1,
+            false if old + 1 < molecule.edits().len() => old + 1,
+            _ => return Ok(None),
+        };
+        if let Err(conflicts) = molecule.move_edit(id, position, force) {
+            return Err(describe_dependencies(molecule, &conflicts));
+        }
+        Ok(Some(position + 1))
+    }
+
+    /// Puts the selected edits into a new folder named `name`.
+    pub fn group_selected(&mut self, assembly: &mut Assembly, name: String) {
+        let ids = self.selected_ids();
+        if let Some(molecule) = self.molecule_mut(assembly) {
+            molecule.group_edits(&ids, name);
+        }
+    }
+
+    /// Combines the selected edits, which must be next to each other, into a
+    /// composite edit named `name`, which is then the only edit selected and
+    /// is highlighted.
+    pub fn combine_selected(
+        &mut self,
+        assembly: &mut Assembly,
+        name: String,
+    ) -> Result<(), CombineError> {
+        let ids = self.selected_ids();
+        let Some(molecule) = self.molecule_mut(assembly) else {
+            return Err(CombineError::NotAdjacent);
+        };
+        let id = molecule.combine_edits(&ids, name)?;
+        self.selected = BTreeSet::from([id]);
+        self.highlighted = Some(id);
+        Ok(())
+    }
+
+    /// The name that the next new composite is given by default.
+    pub fn next_composite_name(&self, assembly: &Assembly) -> String {
+        let count = self.molecule(assembly).map_or(0, |molecule| {
+            molecule
+                .edits()
+                .into_iter()
+                .filter(|edit| matches!(edit, Edit::Composite(_)))
+                .count()
+        });
+        format!("Composite {}", count + 1)
+    }
+
+    /// The highlighted edit, if it is a composite.
+    pub fn highlighted_composite<'a>(&self, assembly: &'a Assembly) -> Option<&'a Composite> {
+        match self.molecule(assembly)?.edits().get(&self.highlighted?)? {
+            Edit::Composite(composite) => Some(composite),
+            _ => None,
+        }
+    }
+
+    /// Replaces the highlighted composite with its steps.  Returns its name
+    /// and how many steps it had.
+    pub fn split_highlighted(&mut self, assembly: &mut Assembly) -> Option<(String, usize)> {
+        let name = self.highlighted_composite(assembly)?.name.clone();
+        let id = self.highlighted?;
+        let steps = self.molecule_mut(assembly)?.split_composite(id)?;
+        self.selected.remove(&id);
+        self.highlighted = None;
+        Some((name, steps))
+    }
+
+    /// An edit that reproduces the atoms created by the selected edits, for
+    /// use as a fragment elsewhere.
+    pub fn export_selected(&self, assembly: &Assembly, name: &str) -> Option<Edit> {
+        let molecule = self.molecule(assembly)?;
+        Some(molecule.fragment(&self.selected_ids(), name))
+    }
+
+    /// Reverts the most recent bulk change to the shown molecule's edits.
+    /// Returns whether there was one.
+    pub fn undo(&mut self, assembly: &mut Assembly) -> bool {
+        let undone = self
+            .molecule_mut(assembly)
+            .is_some_and(|molecule| molecule.undo());
+        if undone {
+            self.selected.clear();
+        }
+        undone
+    }
+
+    /// The name that the next new folder is given by default.
+    pub fn next_folder_name(&self, assembly: &Assembly) -> String {
+        let count = self
+            .molecule(assembly)
+            .map_or(0, |molecule| molecule.edits().folders().len());
+        format!("Folder {}", count + 1)
+    }
+
+    /// Lists the edits of the shown molecule, in history order.
+    pub fn rows(&self, assembly: &Assembly) -> Vec<EditHistoryRow> {
+        let Some(molecule) = self.molecule(assembly) else {
+            return Vec::new();
+        };
+
+        molecule
+            .edits()
+            .order()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &id)| self.edit_row(molecule, index, id))
+            .collect()
+    }
+
+    /// Lists the folders and edits of the shown molecule as a tree, leaving
+    /// out the contents of collapsed folders.
+    pub fn tree(&self, assembly: &Assembly) -> Vec<EditTreeRow> {
+        let mut rows = Vec::new();
+        if let Some(molecule) = self.molecule(assembly) {
+            for item in tree_items(molecule.edits(), None) {
+                self.push_tree_rows(molecule, item, 0, &mut rows);
+            }
+        }
+        rows
+    }
+
+    /// Collapses or expands the highlighted edit if it is a composite, and
+    /// otherwise the folder that it is directly in.  Returns whether it is
+    /// now collapsed.
+    pub fn toggle_collapsed(&mut self, assembly: &mut Assembly) -> Option<bool> {
+        let edit = self.highlighted?;
+        let molecule = self.molecule_mut(assembly)?;
+        if let Some(Edit::Composite(composite)) = molecule.edits().get(&edit) {
+            let collapsed = !composite.collapsed;
+            molecule.set_composite_collapsed(edit, collapsed);
+            return Some(collapsed);
+        }
+        let folder = molecule.edits().folder_of(edit)?;
+        let (id, collapsed) = (folder.id, !folder.collapsed);
+        molecule.set_folder_collapsed(id, collapsed);
+        Some(collapsed)
+    }
+
+    /// Suppresses the folder that the highlighted edit is directly in, or
+    /// restores it if it is suppressed.  Returns whether it is now suppressed.
+    pub fn toggle_folder_suppressed(&mut self, assembly: &mut Assembly) -> Option<bool> {
+        let edit = self.highlighted?;
+        let molecule = self.molecule_mut(assembly)?;
+        let folder = molecule.edits().folder_of(edit)?;
+        let (id, suppressed) = (folder.id, !folder.suppressed);
+        molecule.suppress_folder(id, suppressed);
+        Some(suppressed)
+    }
+
+    /// Removes the folder that the highlighted edit is directly in, leaving
+    /// its contents in the folder it was in.  Returns the folder's name.
+    pub fn ungroup(&mut self, assembly: &mut Assembly) -> Option<String> {
+        let edit = self.highlighted?;
+        let molecule = self.molecule_mut(assembly)?;
+        let folder = molecule.edits().folder_of(edit)?;
+        let (id, name) = (folder.id, folder.name.clone());
+        molecule.ungroup_edits(id);
+        Some(name)
+    }
+
+    /// Forgets the shown molecule if it has been removed from the assembly.
+    pub fn prune(&mut self, assembly: &Assembly) {
+        if let Some(id) = self.component {
+            if assembly.component(id).is_none() {
+                self.component = None;
+                self.highlighted = None;
+                self.selected.clear();
+            }
+        }
+    }
+
+    fn edit_row(
+        &self,
+        molecule: &MoleculeEditor,
+        index: usize,
+        id: EditId,
+    ) -> Option<EditHistoryRow> {
+        let edits = molecule.edits();
+        let edit = edits.get(&id)?;
+        Some(EditHistoryRow {
+            id,
+            number: index + 1,
+            label: edit.name(),
+            applied: index < molecule.history_step(),
+            highlighted: self.highlighted == Some(id),
+            selected: self.selected.contains(&id),
+            suppressed: edits.is_suppressed(id),
+            problem: molecule
+                .diagnostic(id)
+                .map(|diagnostic| diagnostic.to_string()),
+            folder: edits.folder_of(id).map(|folder| folder.name.clone()),
+            collapsed: match edit {
+                Edit::Composite(composite) => Some(composite.collapsed),
+                _ => None,
+            },
+        })
+    }
+
+    fn push_tree_rows(
+        &self,
+        molecule: &MoleculeEditor,
+        item: TreeItem,
+        depth: usize,
+        rows: &mut Vec<EditTreeRow>,
+    ) {
+        let edits = molecule.edits();
+        match item {
+            TreeItem::Edit(id) => {
+                let index = edits.position(id).unwrap_or_default();
+                if let Some(row) = self.edit_row(molecule, index, id) {
+                    rows.push(EditTreeRow {
+                        depth,
+                        entry: EditTreeEntry::Edit(row),
+                    });
+                }
+                if let Some(Edit::Composite(composite)) = edits.get(&id) {
+                    if !composite.collapsed {
+                        rows.extend(composite.steps.iter().enumerate().map(
+                            |(index, (_, step))| EditTreeRow {
+                                depth: depth + 1,
+                                entry: EditTreeEntry::Step(StepRow {
+                                    composite: id,
+                                    number: index + 1,
+                                    label: step.name(),
+                                }),
+                            },
+                        ));
+                    }
+                }
+            }
+            TreeItem::Folder(id) => {
+                let Some(folder) = edits.folder(id) else {
+                    return;
+                };
+                rows.push(EditTreeRow {
+                    depth,
+                    entry: EditTreeEntry::Folder(FolderRow {
+                        id,
+                        name: folder.name.clone(),
+                        collapsed: folder.collapsed,
+                        suppressed: is_folder_suppressed(edits, id),
+                        edit_count: edits.folder_edits(id).len(),
+                    }),
+                });
+                if !folder.collapsed {
+                    for child in tree_items(edits, Some(id)) {
+                        self.push_tree_rows(molecule, child, depth + 1, rows);
+                    }
+                }
+            }
+        }
+    }
+
+    fn molecule<'a>(&self, assembly: &'a Assembly) -> Option<&'a MoleculeEditor> {
+        assembly.component(self.component?)?.molecule()
+    }
+
+    fn selected_ids(&self) -> Vec<EditId> {
+        self.selected.iter().copied().collect()
+    }
+
+    fn molecule_mut<'a>(&self, assembly: &'a mut Assembly) -> Option<&'a mut MoleculeEditor> {
+        assembly.component_mut(self.component?)?.molecule_mut()
+    }
+}
+
+// The edits and folders directly in the folder `parent` (or at the top level),
+// each placed where its first edit is in the history.  Empty folders go last.
+fn tree_items(edits: &EditList, parent: Option<FolderId>) -> Vec<TreeItem> {
+    let mut items: Vec<(usize, TreeItem)> = edits
+        .order()
+        .iter()
+        .enumerate()
+        .filter(|(_, id)| edits.folder_of(**id).map(|folder| folder.id) == parent)
+        .map(|(index, id)| (index, TreeItem::Edit(*id)))
+        .collect();
+    for folder in edits.subfolders(parent) {
+        let first = edits
+            .folder_edits(folder.id)
+            .first()
+            .and_then(|id| edits.position(*id))
+            .unwrap_or(usize::MAX);
+        items.push((first, TreeItem::Folder(folder.id)));
+    }
+    items.sort_by_key(|(index, _)| *index);
+    items.into_iter().map(|(_, item)| item).collect()
+}
+
+// Describes each dependency as e.g. "#4 Bonded Atom refers to 1 atom of #1
+// Root Atom", for the log.
+fn describe_dependencies(molecule: &MoleculeEditor, dependencies: &[Dependency]) -> Vec<String> {
+    let edits = molecule.edits();
+    let describe = |id: EditId| match (edits.position(id), edits.get(&id)) {
+        (Some(index), Some(edit)) => format!("#{} {}", index + 1, edit.name()),
+        _ => "a missing edit".to_owned(),
+    };
+    dependencies
+        .iter()
+        .map(|dependency| {
+            let count = dependency.atoms.len();
+            format!(
+                "{} refers to {} atom{} of {}",
+                describe(dependency.dependent),
+                count,
+                if count == 1 { "" } else { "s" },
+                describe(dependency.dependency)
+            )
+        })
+        .collect()
+}
+
+fn is_folder_suppressed(edits: &EditList, id: FolderId) -> bool {
+    let mut folder = edits.folder(id);
+    while let Some(current) = folder {
+        if current.suppressed {
+            return true;
+        }
+        folder = current.parent.and_then(|parent| edits.folder(parent));
+    }
+    false
+}
+
+// End of File
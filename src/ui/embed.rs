@@ -0,0 +1,103 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The embedded viewer, a read-only configuration of the web build for showing
+//! a finished design in a web page:
+//!
+//! ```text
+//! index.html?structure=designs/gear.json
+//! ```
+//!
+//! The structure is downloaded from the URL given by the `structure` query
+//! parameter, and can be anything that the application can open.  There is no
+//! menu bar, and the only things that can be done are orbiting the camera and
+//! measuring between atoms; every other command is ignored.
+
+use std::{cell::RefCell, rc::Rc};
+
+use winit::{
+    event::{ElementState, KeyEvent, WindowEvent},
+    keyboard::{KeyCode, ModifiersState},
+};
+
+/// The query parameter giving the URL of the structure to show.
+pub const STRUCTURE_PARAMETER: &str = "structure";
+
+/// The contents of the structure once it has been downloaded, or why it could
+/// not be.
+pub type Download = Rc<RefCell<Option<Result<String, String>>>>;
+
+#[derive(Default)]
+pub struct EmbeddedViewer {
+    url: Option<String>,
+    download: Download,
+}
+
+impl EmbeddedViewer {
+    /// A viewer of the structure at `url`, which is yet to be downloaded.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: Some(url.into()),
+            download: Default::default(),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.url.is_some()
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Where the download of the structure is to be put when it finishes.
+    pub fn download(&self) -> Download {
+        self.download.clone()
+    }
+
+    /// The structure's name and file extension, from the last part of its URL.
+    pub fn file_name(&self) -> (String, Option<String>) {
+        let path = self.url.as_deref().unwrap_or_default();
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        let file = path.rsplit('/').next().unwrap_or_default();
+        match file.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => {
+                (stem.to_owned(), Some(extension.to_ascii_lowercase()))
+            }
+            _ if !file.is_empty() => (file.to_owned(), None),
+            _ => ("Molecule".to_owned(), None),
+        }
+    }
+
+    /// Takes the downloaded structure, once it has arrived.
+    pub fn take_download(&self) -> Option<Result<String, String>> {
+        self.download.borrow_mut().take()
+    }
+
+    /// Whether the viewer passes `event` on to the application.  Only the keys
+    /// of the measurement tool are; everything else only moves the camera.
+    pub fn allows(&self, event: &WindowEvent, modifiers: &ModifiersState) -> bool {
+        if !self.is_active() {
+            return true;
+        }
+        match event {
+            WindowEvent::KeyboardInput { event, .. } => allows_key(event, modifiers),
+            _ => false,
+        }
+    }
+}
+
+fn allows_key(key: &KeyEvent, modifiers: &ModifiersState) -> bool {
+    if key.state != ElementState::Released {
+        return false;
+    }
+    match key.physical_key {
+        KeyCode::KeyR => modifiers.alt_key(),
+        KeyCode::Space => !modifiers.shift_key(),
+        KeyCode::Enter | KeyCode::NumpadEnter | KeyCode::Escape => true,
+        _ => false,
+    }
+}
+
+// End of File
@@ -0,0 +1,51 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Saving captured frames (see [`render::FrameCapture`]) next to the project,
+//! for users to attach to reports of rendering bugs.  Each capture is saved as
+//! an image of what the frame drew, and a JSON file of the adapter, driver and
+//! settings that drew it, named after the project with `.capture-<n>` and the
+//! lowest number not already taken.  Captures of projects that have not been
+//! opened from or saved to a file are saved in the working directory.
+
+use std::path::{Path, PathBuf};
+
+use render::FrameCapture;
+
+// What captures are named after when there is no project file.
+const DEFAULT_STEM: &str = "atomcad";
+
+/// Saves `capture` next to the project saved at `project`, and returns the
+/// path of the image.
+pub fn save(capture: &FrameCapture, project: Option<&Path>) -> Result<PathBuf, String> {
+    let json = serde_json::to_string_pretty(capture).map_err(|err| err.to_string())?;
+    let (image, report) = next_paths(project);
+    crate::thumbnail::write_png(&image, capture.size(), &capture.pixels)?;
+    std::fs::write(&report, json).map_err(|err| err.to_string())?;
+    Ok(image)
+}
+
+// The paths of the image and report of the next capture.
+fn next_paths(project: Option<&Path>) -> (PathBuf, PathBuf) {
+    let (directory, stem) = match project {
+        Some(path) => (
+            path.parent().unwrap_or(Path::new("")).to_owned(),
+            path.file_stem()
+                .map_or(DEFAULT_STEM.into(), |stem| stem.to_string_lossy()),
+        ),
+        None => (PathBuf::new(), DEFAULT_STEM.into()),
+    };
+    (1..)
+        .map(|number| {
+            let name = format!("{}.capture-{}", stem, number);
+            (
+                directory.join(format!("{}.png", name)),
+                directory.join(format!("{}.json", name)),
+            )
+        })
+        .find(|(image, report)| !image.exists() && !report.exists())
+        .expect("some capture number is free")
+}
+
+// End of File
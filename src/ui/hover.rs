@@ -0,0 +1,76 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Hovering over atoms.  The atom under the cursor is found again every frame,
+//! as the camera or the design may have moved under a still cursor, and is
+//! drawn with a glowing rim.  A tooltip describes it.
+
+use common::ids::AtomSpecifier;
+use molecule::edit::EditContext as _;
+use periodic_table::Element;
+use render::Interactions;
+use scene::{Assembly, ComponentId};
+use ultraviolet::Vec3;
+
+/// What the tooltip by the cursor says about the hovered atom.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tooltip {
+    pub element: Element,
+    pub specifier: AtomSpecifier,
+    /// Where the atom is drawn, in world space.
+    pub position: Vec3,
+}
+
+impl Tooltip {
+    pub fn text(&self) -> String {
+        format!(
+            "{:?} {} at ({:.3}, {:.3}, {:.3}) Å",
+            self.element, self.specifier, self.position.x, self.position.y, self.position.z
+        )
+    }
+}
+
+#[derive(Default)]
+pub struct Hover {
+    hovered: Option<(ComponentId, AtomSpecifier)>,
+}
+
+impl Hover {
+    pub fn hovered(&self) -> Option<&(ComponentId, AtomSpecifier)> {
+        self.hovered.as_ref()
+    }
+
+    /// Notes the atom under the cursor, if any.  Returns whether it is
+    /// another atom than before.
+    pub fn update(&mut self, hit: Option<(ComponentId, AtomSpecifier)>) -> bool {
+        if hit == self.hovered {
+            return false;
+        }
+        self.hovered = hit;
+        true
+    }
+
+    /// What the renderer is to highlight.
+    pub fn interactions(&self, assembly: &Assembly) -> Interactions {
+        Interactions {
+            hovered: self
+                .hovered
+                .as_ref()
+                .and_then(|(id, atom)| assembly.hovered_atom(*id, atom)),
+        }
+    }
+
+    /// The tooltip for the hovered atom, if there is one and it still exists.
+    pub fn tooltip(&self, assembly: &Assembly) -> Option<Tooltip> {
+        let (id, atom) = self.hovered.as_ref()?;
+        let molecule = assembly.component(*id)?.molecule()?;
+        Some(Tooltip {
+            element: molecule.repr.find_atom(atom)?.element,
+            specifier: atom.clone(),
+            position: assembly.atom_position(*id, atom)?,
+        })
+    }
+}
+
+// End of File
@@ -0,0 +1,214 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Opening large files without freezing the window.  The file is read, parsed
+//! and its edits applied on a worker thread, while a progress dialog shows how
+//! far it has got and offers to cancel.  Nothing is added to the assembly
+//! until the molecule is complete, so a cancelled import leaves no trace.
+//!
+//! The web build has no threads, so there the work is done as soon as the
+//! import starts, and the dialog only ever shows it finished.
+
+use std::sync::{
+    atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering},
+    mpsc, Arc,
+};
+
+use molecule::MoleculeEditor;
+
+/// The steps of an import, in the order they happen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportStage {
+    Reading,
+    Parsing,
+    Applying,
+}
+
+impl ImportStage {
+    const ALL: [ImportStage; 3] = [
+        ImportStage::Reading,
+        ImportStage::Parsing,
+        ImportStage::Applying,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ImportStage::Reading => "reading",
+            ImportStage::Parsing => "parsing",
+            ImportStage::Applying => "applying edits",
+        }
+    }
+
+    // The share of the overall progress that is done when this step starts,
+    // and when it ends.  Reading is measured as it goes; the other steps only
+    // report when they start.
+    fn span(self) -> (f32, f32) {
+        match self {
+            ImportStage::Reading => (0.0, 0.4),
+            ImportStage::Parsing => (0.4, 0.8),
+            ImportStage::Applying => (0.8, 1.0),
+        }
+    }
+}
+
+/// How far an import has got, shared between the worker doing it and the
+/// dialog showing it.
+#[derive(Default)]
+pub struct ImportProgress {
+    stage: AtomicU8,
+    // The fraction of the current step that is done, as the bits of an `f32`.
+    fraction: AtomicU32,
+    cancelled: AtomicBool,
+}
+
+impl ImportProgress {
+    pub fn stage(&self) -> ImportStage {
+        ImportStage::ALL[self.stage.load(Ordering::Relaxed) as usize]
+    }
+
+    /// Moves on to `stage`, with none of it done.
+    pub fn set_stage(&self, stage: ImportStage) {
+        self.fraction.store(0, Ordering::Relaxed);
+        self.stage.store(stage as u8, Ordering::Relaxed);
+    }
+
+    /// Records how much of the current step is done, from 0 to 1.
+    pub fn set_fraction(&self, fraction: f32) {
+        self.fraction
+            .store(fraction.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /// How much of the whole import is done, from 0 to 1.
+    pub fn overall(&self) -> f32 {
+        let (start, end) = self.stage().span();
+        let fraction = f32::from_bits(self.fraction.load(Ordering::Relaxed));
+        start + (end - start) * fraction
+    }
+
+    /// Whether the worker should give up at its next opportunity.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// An import under way.
+pub struct ImportJob {
+    name: String,
+    // The path of the file, if the molecule is to be linked to it rather than
+    // only embedded.
+    link: Option<String>,
+    progress: Arc<ImportProgress>,
+    result: mpsc::Receiver<Result<MoleculeEditor, String>>,
+}
+
+impl ImportJob {
+    /// Starts `work` on a worker thread to import the file called `name`.  The
+    /// work should report its progress, and stop early with an error once the
+    /// import is cancelled.
+    pub fn start(
+        name: impl Into<String>,
+        work: impl FnOnce(&ImportProgress) -> Result<MoleculeEditor, String> + Send + 'static,
+    ) -> Self {
+        let progress = Arc::new(ImportProgress::default());
+        let (sender, result) = mpsc::channel();
+        let worker_progress = progress.clone();
+        let run = move || {
+            // If the import was cancelled, no one is waiting for the result.
+            let _ = sender.send(work(&worker_progress));
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(run);
+        #[cfg(target_arch = "wasm32")]
+        run();
+
+        Self {
+            name: name.into(),
+            link: None,
+            progress,
+            result,
+        }
+    }
+
+    /// Links the molecule, once imported, to the file at `path`, so that it
+    /// can be updated when the file changes (see [`scene::ExternalRef`]).
+    pub fn linking(mut self, path: impl Into<String>) -> Self {
+        self.link = Some(path.into());
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn progress(&self) -> &ImportProgress {
+        &self.progress
+    }
+
+    /// The text of the progress dialog.
+    pub fn dialog_text(&self) -> String {
+        format!(
+            "Importing {}: {} ({:.0}%), press Escape to cancel",
+            self.name,
+            self.progress.stage().name(),
+            self.progress.overall() * 100.0
+        )
+    }
+
+    /// The molecule, or why it could not be imported, once the worker has
+    /// finished.
+    fn poll(&self) -> Option<Result<MoleculeEditor, String>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Some(Err("the import stopped unexpectedly".into()))
+            }
+        }
+    }
+}
+
+/// The progress dialog, shown while a file is imported.  Only one import runs
+/// at a time.
+#[derive(Default)]
+pub struct ImportDialog {
+    job: Option<ImportJob>,
+}
+
+impl ImportDialog {
+    pub fn is_busy(&self) -> bool {
+        self.job.is_some()
+    }
+
+    pub fn job(&self) -> Option<&ImportJob> {
+        self.job.as_ref()
+    }
+
+    /// Shows the dialog for `job`, which takes the place of any import
+    /// already under way.
+    pub fn open(&mut self, job: ImportJob) {
+        self.cancel();
+        self.job = Some(job);
+    }
+
+    /// Stops the import and closes the dialog, returning the name of the file
+    /// that was being imported.
+    pub fn cancel(&mut self) -> Option<String> {
+        let job = self.job.take()?;
+        job.progress.cancelled.store(true, Ordering::Relaxed);
+        Some(job.name)
+    }
+
+    /// Closes the dialog once the import has finished, with the name of the
+    /// file, the molecule read from it, and the path to link the molecule to,
+    /// if any.
+    pub fn take_finished(
+        &mut self,
+    ) -> Option<(String, Result<MoleculeEditor, String>, Option<String>)> {
+        let result = self.job.as_ref()?.poll()?;
+        let job = self.job.take()?;
+        Some((job.name, result, job.link))
+    }
+}
+
+// End of File
@@ -0,0 +1,257 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The properties of a molecule that are not part of its edits, shown as a
+//! list of fields.  For now these say where the molecule is placed within its
+//! component: how far it is moved along each axis, and how far it is turned
+//! about each, as angles applied about x, then y, then z.  Unlike the
+//! component's transform, this placement belongs to the molecule, so it is
+//! kept when the molecule is exported.  One field is chosen at a time, and is
+//! stepped up or down.
+//!
+//! The inspector also says where an atom of the molecule came from (see
+//! [`molecule::provenance`]).
+
+use common::ids::AtomSpecifier;
+use molecule::{provenance::Provenance, MoleculeEditor};
+use scene::{Assembly, ComponentId};
+use ultraviolet::{Mat3, Rotor3, Vec3};
+
+/// How far stepping an offset moves the molecule, in angstroms.
+pub const OFFSET_STEP: f32 = 0.5;
+
+/// How far stepping an angle turns the molecule, in degrees.
+pub const ANGLE_STEP: f32 = 15.0;
+
+/// A field of the inspector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InspectorField {
+    OffsetX,
+    OffsetY,
+    OffsetZ,
+    AngleX,
+    AngleY,
+    AngleZ,
+}
+
+impl InspectorField {
+    /// Every field, in the order they are listed.
+    pub const ALL: [InspectorField; 6] = [
+        InspectorField::OffsetX,
+        InspectorField::OffsetY,
+        InspectorField::OffsetZ,
+        InspectorField::AngleX,
+        InspectorField::AngleY,
+        InspectorField::AngleZ,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            InspectorField::OffsetX => "Offset x",
+            InspectorField::OffsetY => "Offset y",
+            InspectorField::OffsetZ => "Offset z",
+            InspectorField::AngleX => "Rotation x",
+            InspectorField::AngleY => "Rotation y",
+            InspectorField::AngleZ => "Rotation z",
+        }
+    }
+
+    /// The amount that one step changes the field by.
+    pub fn step(self) -> f32 {
+        match self {
+            InspectorField::OffsetX | InspectorField::OffsetY | InspectorField::OffsetZ => {
+                OFFSET_STEP
+            }
+            InspectorField::AngleX | InspectorField::AngleY | InspectorField::AngleZ => ANGLE_STEP,
+        }
+    }
+
+    pub fn unit(self) -> &'static str {
+        match self {
+            InspectorField::OffsetX | InspectorField::OffsetY | InspectorField::OffsetZ => " Å",
+            InspectorField::AngleX | InspectorField::AngleY | InspectorField::AngleZ => "°",
+        }
+    }
+}
+
+/// One line of the inspector.
+#[derive(Clone, Debug)]
+pub struct InspectorRow {
+    pub field: InspectorField,
+    pub label: &'static str,
+    pub value: f32,
+    pub unit: &'static str,
+    pub chosen: bool,
+}
+
+/// Why a field could not be changed.
+#[derive(Debug, PartialEq, Eq)]
+pub enum InspectorError {
+    /// The component does not exist, or holds a sub-assembly.
+    NotAMolecule,
+    Locked,
+}
+
+/// The inspector panel, which shows the molecule that the caller passes in,
+/// e.g. the one on the timeline.
+#[derive(Default)]
+pub struct Inspector {
+    field: usize,
+}
+
+impl Inspector {
+    /// The field that stepping changes.
+    pub fn field(&self) -> InspectorField {
+        InspectorField::ALL[self.field]
+    }
+
+    /// Chooses the next field, going back to the first after the last.
+    pub fn next_field(&mut self) -> InspectorField {
+        self.field = (self.field + 1) % InspectorField::ALL.len();
+        self.field()
+    }
+
+    /// Lists the fields of the molecule held by `component`, or nothing if it
+    /// holds none.
+    pub fn rows(&self, assembly: &Assembly, component: ComponentId) -> Vec<InspectorRow> {
+        let Some(molecule) = assembly.component(component).and_then(|c| c.molecule()) else {
+            return Vec::new();
+        };
+        let (offset, angles) = (molecule.offset(), angles(molecule.rotation()));
+        InspectorField::ALL
+            .iter()
+            .map(|&field| InspectorRow {
+                field,
+                label: field.label(),
+                value: match field {
+                    InspectorField::OffsetX => offset.x,
+                    InspectorField::OffsetY => offset.y,
+                    InspectorField::OffsetZ => offset.z,
+                    InspectorField::AngleX => angles.x,
+                    InspectorField::AngleY => angles.y,
+                    InspectorField::AngleZ => angles.z,
+                },
+                unit: field.unit(),
+                chosen: field == self.field(),
+            })
+            .collect()
+    }
+
+    /// Where the atom `atom` of the molecule held by `component` came from, or
+    /// `None` if there is no such atom, or the edit that created it has been
+    /// deleted.
+    pub fn provenance(
+        &self,
+        assembly: &Assembly,
+        component: ComponentId,
+        atom: &AtomSpecifier,
+    ) -> Option<Provenance> {
+        let molecule = assembly.component(component)?.molecule()?;
+        molecule::provenance::find(molecule, atom)
+    }
+
+    /// Changes the chosen field of the molecule held by `component` by
+    /// `steps` steps, which may be negative.  Returns the new value.
+    pub fn step(
+        &self,
+        assembly: &mut Assembly,
+        component: ComponentId,
+        steps: f32,
+    ) -> Result<f32, InspectorError> {
+        let field = self.field();
+        let value = self.value(assembly, component)? + steps * field.step();
+        self.set(assembly, component, value)
+    }
+
+    /// Sets the chosen field of the molecule held by `component` to `value`,
+    /// in angstroms or degrees.  Returns the value as it was stored, which for
+    /// angles may differ, e.g. by a whole turn.
+    pub fn set(
+        &self,
+        assembly: &mut Assembly,
+        component: ComponentId,
+        value: f32,
+    ) -> Result<f32, InspectorError> {
+        let molecule = molecule_mut(assembly, component)?;
+
+        let (mut offset, mut angles) = (molecule.offset(), angles(molecule.rotation()));
+        match self.field() {
+            InspectorField::OffsetX => offset.x = value,
+            InspectorField::OffsetY => offset.y = value,
+            InspectorField::OffsetZ => offset.z = value,
+            InspectorField::AngleX => angles.x = value,
+            InspectorField::AngleY => angles.y = value,
+            InspectorField::AngleZ => angles.z = value,
+        }
+        molecule.set_offset(offset);
+        molecule.set_rotation(rotation(angles));
+
+        self.value(assembly, component)
+    }
+
+    // The value of the chosen field.
+    fn value(&self, assembly: &Assembly, component: ComponentId) -> Result<f32, InspectorError> {
+        let field = self.field();
+        self.rows(assembly, component)
+            .into_iter()
+            .find(|row| row.field == field)
+            .map(|row| row.value)
+            .ok_or(InspectorError::NotAMolecule)
+    }
+
+    /// Puts the molecule held by `component` back where it was built, with no
+    /// offset or rotation.
+    pub fn reset(
+        &self,
+        assembly: &mut Assembly,
+        component: ComponentId,
+    ) -> Result<(), InspectorError> {
+        let molecule = molecule_mut(assembly, component)?;
+        molecule.set_offset(Vec3::zero());
+        molecule.set_rotation(Rotor3::identity());
+        Ok(())
+    }
+}
+
+// The molecule held by `component`, to be changed.
+fn molecule_mut(
+    assembly: &mut Assembly,
+    component: ComponentId,
+) -> Result<&mut MoleculeEditor, InspectorError> {
+    let component = assembly
+        .component_mut(component)
+        .ok_or(InspectorError::NotAMolecule)?;
+    if component.molecule().is_none() {
+        return Err(InspectorError::NotAMolecule);
+    }
+    component.molecule_mut().ok_or(InspectorError::Locked)
+}
+
+// The rotation that turns by `angles.x` degrees about x, then `angles.y` about
+// y, then `angles.z` about z.
+fn rotation(angles: Vec3) -> Rotor3 {
+    (Mat3::from_rotation_z(angles.z.to_radians())
+        * Mat3::from_rotation_y(angles.y.to_radians())
+        * Mat3::from_rotation_x(angles.x.to_radians()))
+    .into_rotor3()
+}
+
+// The angles, in degrees, that `rotation` turns by, as taken by `rotation`.
+// The angle about y is kept between -90 and 90 degrees, and the others
+// between -180 and 180.
+fn angles(rotation: Rotor3) -> Vec3 {
+    // Indexed by column, then row.
+    let m = rotation.into_matrix();
+    let y = (-m[0][2]).atan2(m[0][0].hypot(m[0][1]));
+    let (x, z) = if m[0][2].abs() < 0.9999 {
+        (m[1][2].atan2(m[2][2]), m[0][1].atan2(m[0][0]))
+    } else {
+        // Turned a quarter turn about y, where turns about x and z are the
+        // same, so all of it is put about x.
+        ((-m[2][1]).atan2(m[1][1]), 0.0)
+    };
+    Vec3::new(x.to_degrees(), y.to_degrees(), z.to_degrees())
+}
+
+// End of File
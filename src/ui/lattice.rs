@@ -0,0 +1,48 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Building blocks of crystal, as new molecules.  The structure is chosen from
+//! a short list, and the block is made at the structure's usual lattice
+//! constant and put where the camera is looking.
+
+use molecule::{
+    edit::Edit,
+    lattice::{Lattice, LatticeKind},
+    MoleculeEditor,
+};
+
+pub struct LatticePanel {
+    pub kind: LatticeKind,
+    /// The number of conventional cells along each edge of new blocks.
+    pub extents: [u32; 3],
+}
+
+impl Default for LatticePanel {
+    fn default() -> Self {
+        Self {
+            kind: LatticeKind::DiamondCubic,
+            extents: [4, 4, 4],
+        }
+    }
+}
+
+impl LatticePanel {
+    /// Moves on to the next structure in the list, going back to the first
+    /// after the last.
+    pub fn cycle_kind(&mut self) -> LatticeKind {
+        let index = LatticeKind::ALL
+            .iter()
+            .position(|kind| *kind == self.kind)
+            .unwrap_or(0);
+        self.kind = LatticeKind::ALL[(index + 1) % LatticeKind::ALL.len()];
+        self.kind
+    }
+
+    /// A new molecule holding a block of the chosen structure.
+    pub fn build(&self) -> MoleculeEditor {
+        MoleculeEditor::from_feature(Edit::Lattice(Lattice::new(self.kind, self.extents)))
+    }
+}
+
+// End of File
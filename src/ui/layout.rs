@@ -0,0 +1,81 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The layout of the window when a design was last worked on: which panels
+//! were open, which tool was in use, which configuration was active and where
+//! the camera was.  It is kept in the assembly as a [`SavedView`], so that it
+//! is saved along with the design and reopening it shows exactly the view
+//! that its author left it in.
+
+use render::RenderCamera;
+use scene::{Assembly, SavedView};
+
+use super::{tools::ToolKind, Panels};
+
+/// The names that open panels are saved under.
+const STATISTICS_PANEL: &str = "statistics";
+const CONFIGURATIONS_PANEL: &str = "configurations";
+
+/// Describes the current layout of the window and the camera.
+pub fn capture(panels: &Panels, camera: &RenderCamera) -> SavedView {
+    let open_panels = [
+        (STATISTICS_PANEL, panels.statistics.is_open()),
+        (CONFIGURATIONS_PANEL, panels.configurations.is_open()),
+    ]
+    .into_iter()
+    .filter(|(_, open)| *open)
+    .map(|(name, _)| name.to_owned())
+    .collect();
+    SavedView {
+        camera: camera.pose(),
+        projection: camera.projection(),
+        open_panels,
+        tool: Some(panels.tools.active().name().to_owned()),
+        configuration: panels.configurations.active().map(str::to_owned),
+    }
+}
+
+/// Keeps the current layout in the assembly, to be saved with it.
+pub fn remember(panels: &Panels, camera: &RenderCamera, assembly: &mut Assembly) {
+    assembly.set_saved_view(Some(capture(panels, camera)));
+}
+
+/// Shows the assembly the way its saved view describes, if it has one.
+/// Tools that edit the design are not chosen unless `editing` is allowed.
+/// Returns whether there was a view to restore.
+pub fn restore(
+    panels: &mut Panels,
+    camera: &mut RenderCamera,
+    assembly: &Assembly,
+    editing: bool,
+) -> bool {
+    let Some(view) = assembly.saved_view() else {
+        return false;
+    };
+    if let Some(pose) = view.camera {
+        camera.set_pose(pose, false);
+    }
+    camera.set_projection(view.projection);
+
+    let open = |name: &str| view.open_panels.iter().any(|panel| panel == name);
+    if panels.statistics.is_open() != open(STATISTICS_PANEL) {
+        panels.statistics.toggle();
+    }
+    if panels.configurations.is_open() != open(CONFIGURATIONS_PANEL) {
+        panels.configurations.toggle();
+    }
+
+    let tool = ToolKind::ALL
+        .into_iter()
+        .find(|kind| view.tool.as_deref() == Some(kind.name()))
+        .filter(|kind| editing || !kind.edits())
+        .unwrap_or_default();
+    panels.tools.choose(tool);
+    panels
+        .configurations
+        .set_active(assembly, view.configuration.as_deref());
+    true
+}
+
+// End of File
@@ -0,0 +1,123 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Checking the files that linked components refer to (see
+//! [`scene::ExternalRef`]).  When a project is loaded, or on request, each
+//! linked file is read again; if it has changed since the component's copy was
+//! made, the new molecule is read from it and offered in place of the copy.
+//! The offer can be taken, updating every changed component, or declined,
+//! keeping the copies until the links are next checked.
+
+use molecule::MoleculeEditor;
+use scene::{Assembly, ComponentId};
+
+/// A linked component whose file has changed.
+pub struct ChangedLink {
+    pub component: ComponentId,
+    pub name: String,
+    pub uri: String,
+    // The molecule read from the file as it is now, and what the file holds.
+    molecule: MoleculeEditor,
+    contents: Vec<u8>,
+}
+
+/// A linked component whose file could not be read, which keeps its copy.
+#[derive(Clone, Debug)]
+pub struct UnavailableLink {
+    pub component: ComponentId,
+    pub name: String,
+    pub uri: String,
+    pub reason: String,
+}
+
+#[derive(Default)]
+pub struct LinkedFiles {
+    changed: Vec<ChangedLink>,
+    unavailable: Vec<UnavailableLink>,
+}
+
+impl LinkedFiles {
+    /// Reads the file of every linked component with `read`, and offers to
+    /// update those that have changed, with the molecule that `parse` reads
+    /// from the file's path or URL and contents.  Returns how many linked
+    /// components there are.
+    pub fn check(
+        &mut self,
+        assembly: &Assembly,
+        read: impl Fn(&str) -> Result<Vec<u8>, String>,
+        parse: impl Fn(&str, &[u8]) -> Result<MoleculeEditor, String>,
+    ) -> usize {
+        self.changed.clear();
+        self.unavailable.clear();
+        let mut linked = 0;
+        for component in assembly.components() {
+            let Some(source) = component.source() else {
+                continue;
+            };
+            linked += 1;
+            let unavailable = |reason: String| UnavailableLink {
+                component: component.id(),
+                name: component.name().to_owned(),
+                uri: source.uri.clone(),
+                reason,
+            };
+            let contents = match read(&source.uri) {
+                Ok(contents) => contents,
+                Err(reason) => {
+                    self.unavailable.push(unavailable(reason));
+                    continue;
+                }
+            };
+            if !source.is_changed(&contents) {
+                continue;
+            }
+            match parse(&source.uri, &contents) {
+                Ok(molecule) => self.changed.push(ChangedLink {
+                    component: component.id(),
+                    name: component.name().to_owned(),
+                    uri: source.uri.clone(),
+                    molecule,
+                    contents,
+                }),
+                Err(reason) => self.unavailable.push(unavailable(reason)),
+            }
+        }
+        linked
+    }
+
+    /// The components whose files have changed, if the offer to update them
+    /// has not yet been answered.
+    pub fn changed(&self) -> &[ChangedLink] {
+        &self.changed
+    }
+
+    /// The components whose files could not be read when last checked.
+    pub fn unavailable(&self) -> &[UnavailableLink] {
+        &self.unavailable
+    }
+
+    /// Updates every changed component from its file.  Returns the components
+    /// updated; locked ones, and those deleted since the check, are not.
+    pub fn update(&mut self, assembly: &mut Assembly) -> Vec<ComponentId> {
+        self.changed
+            .drain(..)
+            .filter_map(|link| {
+                assembly
+                    .component_mut(link.component)?
+                    .update_source(link.molecule, &link.contents)
+                    .then_some(link.component)
+            })
+            .collect()
+    }
+
+    /// Keeps the copies of the changed components as they are, declining the
+    /// offer until the links are next checked.
+    pub fn keep(&mut self) -> usize {
+        let kept = self.changed.len();
+        self.changed.clear();
+        kept
+    }
+}
+
+// End of File
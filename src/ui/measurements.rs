@@ -0,0 +1,176 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The measurement tool, which measures distances, angles and dihedrals
+//! between picked atoms and labels them in the view.
+//!
+//! Measuring does not change the design, so the tool works in review mode.
+
+use common::ids::AtomSpecifier;
+use render::RenderCamera;
+use scene::{Assembly, ComponentId, Measurement, MeasurementValue};
+use ultraviolet::Vec3;
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    keyboard::{KeyCode, ModifiersState},
+};
+
+use super::tools::{Tool, ToolContext};
+
+/// The most atoms a measurement can be between (a dihedral).
+const MAX_ATOMS: usize = 4;
+
+/// A measurement to be drawn over the view.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeasurementLabel {
+    /// The index of the measurement in `Assembly::measurements`.
+    pub index: usize,
+    pub value: MeasurementValue,
+    /// Where the label is anchored: the pixel the middle of the atoms is drawn
+    /// at.
+    pub position: PhysicalPosition<f64>,
+}
+
+#[derive(Default)]
+pub struct MeasurementTool {
+    active: bool,
+    /// The atoms picked for the next measurement, in order.
+    picked: Vec<(ComponentId, AtomSpecifier)>,
+}
+
+impl MeasurementTool {
+    /// While the tool is active, picked atoms are collected for measuring and
+    /// the measurements are labelled.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn toggle(&mut self) {
+        self.active = !self.active;
+        self.picked.clear();
+    }
+
+    pub fn picked(&self) -> &[(ComponentId, AtomSpecifier)] {
+        &self.picked
+    }
+
+    /// Adds an atom to those being measured between.  Picking a fifth atom
+    /// starts over from it, and picking the last atom again removes it.
+    pub fn pick(&mut self, component: ComponentId, atom: AtomSpecifier) {
+        let entry = (component, atom);
+        if self.picked.last() == Some(&entry) {
+            self.picked.pop();
+            return;
+        }
+        if self.picked.len() == MAX_ATOMS {
+            self.picked.clear();
+        }
+        self.picked.push(entry);
+    }
+
+    /// Measures between the picked atoms without keeping the measurement.
+    pub fn preview(&self, assembly: &Assembly) -> Option<MeasurementValue> {
+        assembly.measure(&Measurement::new(self.picked.clone())?)
+    }
+
+    /// Keeps a measurement between the picked atoms in the scene.  Returns
+    /// `false`, leaving the atoms picked, unless 2 to 4 atoms are picked.
+    pub fn commit(&mut self, assembly: &mut Assembly) -> bool {
+        let Some(measurement) = Measurement::new(self.picked.clone()) else {
+            return false;
+        };
+        assembly.add_measurement(measurement);
+        self.picked.clear();
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.picked.clear();
+    }
+
+    /// Lays out a label for each measurement that can currently be measured,
+    /// i.e. whose atoms all exist and are visible.  There are none while the
+    /// tool is inactive.
+    pub fn labels(
+        &self,
+        assembly: &Assembly,
+        camera: &RenderCamera,
+        viewport_size: &PhysicalSize<u32>,
+    ) -> Vec<MeasurementLabel> {
+        if !self.active {
+            return Vec::new();
+        }
+
+        assembly
+            .measurements()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, measurement)| {
+                let positions = measurement
+                    .atoms()
+                    .iter()
+                    .map(|(id, atom)| assembly.atom_position(*id, atom))
+                    .collect::<Option<Vec<Vec3>>>()?;
+                let value = Measurement::value(&positions)?;
+                let center =
+                    positions.iter().fold(Vec3::zero(), |sum, p| sum + *p) / positions.len() as f32;
+                Some(MeasurementLabel {
+                    index,
+                    value,
+                    position: camera.project(center, viewport_size)?,
+                })
+            })
+            .collect()
+    }
+}
+
+impl MeasurementTool {
+    // Picks the atom under the pointer, if any, and reports the measurement
+    // so far.
+    fn pick_at_pointer(&mut self, context: ToolContext) -> bool {
+        let Some((component, atom)) = context.pointer.hit(context.assembly) else {
+            return false;
+        };
+        self.pick(component, atom);
+        match self.preview(context.assembly) {
+            Some(value) => log::info!("{} atoms picked: {}", self.picked.len(), value),
+            None => log::info!("{} atom picked", self.picked.len()),
+        }
+        true
+    }
+}
+
+impl Tool for MeasurementTool {
+    fn set_active(&mut self, active: bool) {
+        if active != self.active {
+            self.toggle();
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        Some("measuring: click (or press Space over) 2 to 4 atoms, then Enter".to_owned())
+    }
+
+    fn on_pointer_down(&mut self, context: ToolContext) -> bool {
+        self.pick_at_pointer(context)
+    }
+
+    fn on_key(&mut self, context: ToolContext, key: KeyCode, modifiers: &ModifiersState) -> bool {
+        match key {
+            KeyCode::Space if !modifiers.shift_key() => {
+                self.pick_at_pointer(context);
+            }
+            KeyCode::Enter | KeyCode::NumpadEnter => {
+                if !self.commit(context.assembly) {
+                    log::info!("pick 2 to 4 atoms to measure between");
+                }
+            }
+            KeyCode::Escape if !self.picked.is_empty() => self.clear(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+// End of File
@@ -0,0 +1,153 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Panels drawn in the overlay on top of the 3D view.
+//!
+//! Each panel is a small state machine that is independent of the widget
+//! toolkit: it produces a description of what should be drawn (e.g. a list of
+//! rows), and exposes one method per user interaction (click, toggle, drag,
+//! etc.) which applies that interaction to the scene.  This keeps the
+//! behaviour of the panels out of the overlay layer, which only has to draw
+//! what it is given and forward input events.
+
+use winit::{dpi::PhysicalPosition, keyboard::ModifiersState};
+
+/// Playback of the motion of driven components.
+pub mod animation;
+/// Bonding two atoms by clicking them in turn.
+pub mod bonding;
+/// Changing atoms into another element by dragging across them.
+pub mod brush;
+/// Copying selected atoms, and pasting them into a molecule or as a new one.
+pub mod clipboard;
+/// The commands run on molecules this session, to be run again or copied as
+/// script.
+pub mod command_log;
+/// Two variants of a design shown side by side.
+pub mod compare;
+/// Composite edits saved to be applied again elsewhere.
+pub mod composites;
+/// Named ways of showing the assembly, switched between from a dropdown.
+pub mod configurations;
+/// Cutting atoms out of a molecule with a shape placed in the view.
+pub mod cut;
+/// The edits that failed the last time they were applied.
+pub mod diagnostics;
+/// The projects open in the window, switched between with tabs.
+pub mod documents;
+/// Substituting or deleting a random fraction of atoms, e.g. to dope a lattice.
+pub mod doping;
+/// The list of edits that make up a molecule.
+pub mod edit_history;
+/// The read-only viewer that the web build becomes when embedded in a page.
+pub mod embed;
+/// Saving captured frames next to the project, for bug reports.
+pub mod frame_capture;
+/// The atom under the cursor, which is highlighted and described in a
+/// tooltip.
+pub mod hover;
+/// Reading large files on a worker thread, with a progress dialog.
+pub mod import;
+/// The properties of a molecule that are not part of its edits, such as where
+/// it is placed within its component.
+pub mod inspector;
+/// Blocks of crystal, built from a choice of lattice structures.
+pub mod lattice;
+/// The panels, tool and camera that an assembly was last shown with, kept with
+/// the assembly.
+pub mod layout;
+/// Molecules linked to files of their own, and updating them when the files
+/// change.
+pub mod links;
+/// Distances, angles and dihedrals between picked atoms.
+pub mod measurements;
+/// Grafting functional groups onto atoms, chosen from a palette.
+pub mod moieties;
+/// The point that the camera turns about, marked while it turns.
+pub mod orbit_marker;
+/// The tree view of the assembly hierarchy.
+pub mod outliner;
+/// Laying out the panels over the 3D view, and matching clicks to them.
+pub mod overlay;
+/// Repeated parts of a molecule that could be instanced.
+pub mod parts;
+/// Capping the dangling bonds of under-coordinated atoms.
+pub mod passivation;
+/// Duplicates, and linear and circular patterns, of a molecule.
+pub mod patterns;
+/// Adding atoms by clicking, with the element chosen from a palette.
+pub mod placement;
+/// Peptides and nucleic acids built from a typed sequence.
+pub mod polymer;
+/// The ring of common commands that opens when the view is pressed and held.
+pub mod radial_menu;
+/// Read-only review of a shared design, with comments.
+pub mod review;
+/// Finding atoms and edits by specifier, element, or name.
+pub mod search;
+/// The atoms selected for the next command.
+pub mod selection;
+/// Live counts, GPU memory usage and timings for the current scene.
+pub mod statistics;
+/// The lines along the bottom of the window showing the last report.
+pub mod status;
+/// The history scrubber along the bottom of the window.
+pub mod timeline;
+/// The toolbar, and the interactive tools it switches between.
+pub mod tools;
+/// Turning the camera around the design when the application is left idle.
+pub mod turntable;
+/// The formula, net charge and problems of the assembly, checked on demand
+/// and before exporting.
+pub mod validation;
+
+/// The state of each of the panels that the application currently shows.
+#[derive(Default)]
+pub struct Panels {
+    pub animation: animation::AnimationControls,
+    pub clipboard: clipboard::Clipboard,
+    pub command_log: command_log::CommandLog,
+    pub compare: compare::CompareView,
+    pub composites: composites::CompositeLibrary,
+    pub configurations: configurations::ConfigurationMenu,
+    pub diagnostics: diagnostics::DiagnosticsPanel,
+    pub documents: documents::Documents,
+    pub doping: doping::DopingPanel,
+    pub edit_history: edit_history::EditHistory,
+    pub embed: embed::EmbeddedViewer,
+    pub history: scene::ProjectHistory,
+    pub hover: hover::Hover,
+    pub import: import::ImportDialog,
+    pub inspector: inspector::Inspector,
+    pub lattice: lattice::LatticePanel,
+    pub links: links::LinkedFiles,
+    pub orbit_marker: orbit_marker::OrbitMarker,
+    pub outliner: outliner::Outliner,
+    pub overlay: overlay::OverlayLayer,
+    pub parts: parts::PartsOffer,
+    pub passivation: passivation::PassivationPanel,
+    pub patterns: patterns::PatternPanel,
+    pub polymer: polymer::PolymerPanel,
+    pub radial_menu: radial_menu::RadialMenu,
+    pub review: review::ReviewMode,
+    pub search: search::SearchBox,
+    pub selection: selection::Selection,
+    pub statistics: statistics::StatisticsPanel,
+    pub timeline: timeline::Timeline,
+    pub tools: tools::ToolManager,
+    pub turntable: turntable::IdleTurntable,
+    pub validation: validation::ValidationPanel,
+    pub input: InputState,
+}
+
+/// What is known of the pointer and the keyboard between events, which do not
+/// each carry it.
+#[derive(Clone, Copy, Default)]
+pub struct InputState {
+    /// Where the pointer last was in the window.
+    pub cursor_pos: PhysicalPosition<f64>,
+    pub modifiers: ModifiersState,
+}
+
+// End of File
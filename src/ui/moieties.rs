@@ -0,0 +1,228 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The moiety tool.  A functional group is chosen from a palette of the
+//! built-in moieties (see [`molecule::moiety`]), and each click grafts it onto
+//! the atom under the cursor as an [`Edit::AttachMoiety`], in place of one of
+//! the atom's hydrogens, or in place of the hydrogen clicked.  While the tool
+//! is active, the group's heavy atoms are previewed where they would go.  The
+//! number keys choose the moiety, and scrolling turns it about the new bond.
+
+use common::ids::AtomSpecifier;
+use molecule::{
+    edit::Edit,
+    moiety::{self, AttachMoiety, Moiety},
+};
+use render::{AtomBuffer, AtomKind, AtomRepr, GlobalRenderResources};
+use scene::{Assembly, ComponentId};
+use ultraviolet::{Mat4, Vec3};
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use super::{
+    placement::PALETTE_KEYS,
+    tools::{OverlayDraw, Tool, ToolContext},
+};
+
+/// The color that the previewed moiety is drawn in, as for placing atoms.
+const PREVIEW_TINT: Vec3 = Vec3::new(0.4, 1.0, 0.6);
+
+/// How far one line of scrolling turns the moiety, in degrees.
+const DEGREES_PER_LINE: f32 = 15.0;
+
+/// What a click would do.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoietyPreview {
+    pub component: ComponentId,
+    pub target: AtomSpecifier,
+    /// Where the moiety's heavy atoms would go, in world space.
+    pub atoms: Vec<(periodic_table::Element, Vec3)>,
+}
+
+#[derive(Default)]
+pub struct MoietyTool {
+    chosen: usize,
+    orientation: f32,
+    preview: Option<MoietyPreview>,
+    /// The previewed atoms, uploaded when the preview changes.
+    buffer: Option<AtomBuffer>,
+    buffer_stale: bool,
+}
+
+impl MoietyTool {
+    /// The moiety that a click grafts.
+    pub fn moiety(&self) -> &'static Moiety {
+        &moiety::library()[self.chosen]
+    }
+
+    /// How far the moiety is turned about the new bond, in degrees.
+    pub fn orientation(&self) -> f32 {
+        self.orientation
+    }
+
+    /// Chooses the moiety in palette slot `index`.  Returns false if there is
+    /// no such slot.
+    pub fn choose(&mut self, index: usize) -> bool {
+        if index >= moiety::library().len() {
+            return false;
+        }
+        self.chosen = index;
+        true
+    }
+
+    pub fn preview(&self) -> Option<&MoietyPreview> {
+        self.preview.as_ref()
+    }
+
+    /// Works out where the moiety would go on the atom under the cursor, if
+    /// any.  Locked molecules cannot be added to.
+    pub fn hover(&mut self, assembly: &Assembly, hit: Option<(ComponentId, AtomSpecifier)>) {
+        let preview = hit.and_then(|(component, target)| {
+            let (_, molecule, transform) = assembly
+                .visible_molecules()
+                .into_iter()
+                .find(|(id, ..)| *id == component)
+                .filter(|_| assembly.is_locked(component) == Some(false))?;
+            let graft = self.edit(target.clone()).graft(&molecule.repr).ok()?;
+            let atoms = graft
+                .moiety
+                .atoms
+                .iter()
+                .zip(graft.positions)
+                .map(|((element, _), pos)| (*element, transform.transform_point3(pos)))
+                .collect();
+            Some(MoietyPreview {
+                component,
+                target,
+                atoms,
+            })
+        });
+        if preview != self.preview {
+            self.preview = preview;
+            self.buffer_stale = true;
+        }
+    }
+
+    /// Grafts the previewed moiety.  Returns the molecule it was added to, or
+    /// `None` if nothing is previewed or the molecule is locked.
+    pub fn attach(&mut self, assembly: &mut Assembly) -> Option<ComponentId> {
+        let preview = self.preview.take()?;
+        self.buffer_stale = true;
+        let molecule = assembly.component_mut(preview.component)?.molecule_mut()?;
+        molecule.add_edit(Edit::AttachMoiety(self.edit(preview.target)));
+        Some(preview.component)
+    }
+
+    /// The palette as a line of text, with the chosen moiety in brackets.
+    pub fn palette_line(&self) -> String {
+        let entries: Vec<String> = moiety::library()
+            .iter()
+            .take(PALETTE_KEYS.len())
+            .enumerate()
+            .map(|(index, moiety)| {
+                if index == self.chosen {
+                    format!("{} [{}]", index + 1, moiety.name)
+                } else {
+                    format!("{} {}", index + 1, moiety.name)
+                }
+            })
+            .collect();
+        entries.join("  ")
+    }
+
+    fn edit(&self, target: AtomSpecifier) -> AttachMoiety {
+        AttachMoiety {
+            target,
+            moiety_id: self.moiety().id.clone(),
+            orientation: self.orientation,
+        }
+    }
+}
+
+impl Tool for MoietyTool {
+    fn set_active(&mut self, _active: bool) {
+        if self.preview.take().is_some() {
+            self.buffer_stale = true;
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        Some(format!(
+            "attaching moieties: click an atom, scroll to turn, {}",
+            self.palette_line()
+        ))
+    }
+
+    fn on_pointer_down(&mut self, context: ToolContext) -> bool {
+        let name = &self.moiety().name;
+        match self.attach(context.assembly) {
+            Some(id) => log::info!(
+                "attached {} to {}",
+                name,
+                context
+                    .assembly
+                    .component(id)
+                    .map_or("the molecule", |component| component.name())
+            ),
+            None => log::info!("no atom to attach the moiety to here"),
+        }
+        true
+    }
+
+    fn on_pointer_move(&mut self, context: ToolContext) -> bool {
+        let hit = context.pointer.hit(context.assembly);
+        self.hover(context.assembly, hit);
+        false
+    }
+
+    fn on_scroll(&mut self, context: ToolContext, lines: f32) -> bool {
+        if self.preview.is_none() {
+            return false;
+        }
+        self.orientation = (self.orientation + lines * DEGREES_PER_LINE).rem_euclid(360.0);
+        let hit = context.pointer.hit(context.assembly);
+        self.hover(context.assembly, hit);
+        true
+    }
+
+    fn on_key(&mut self, context: ToolContext, key: KeyCode, _modifiers: &ModifiersState) -> bool {
+        let Some(index) = PALETTE_KEYS
+            .iter()
+            .position(|palette_key| *palette_key == key)
+        else {
+            return false;
+        };
+        if self.choose(index) {
+            log::info!("attaching moieties: {}", self.palette_line());
+            let hit = context.pointer.hit(context.assembly);
+            self.hover(context.assembly, hit);
+        }
+        true
+    }
+
+    fn synchronize_buffers(&mut self, gpu_resources: &GlobalRenderResources) {
+        if !self.buffer_stale {
+            return;
+        }
+        self.buffer_stale = false;
+        self.buffer = self.preview.as_ref().map(|preview| {
+            AtomBuffer::new(
+                gpu_resources,
+                preview.atoms.iter().map(|(element, pos)| AtomRepr {
+                    pos: *pos,
+                    kind: AtomKind::new(*element),
+                    property: 0.0,
+                }),
+            )
+        });
+    }
+
+    fn render_overlay(&self) -> Vec<OverlayDraw<'_>> {
+        match &self.buffer {
+            Some(buffer) => vec![(buffer, Mat4::identity(), Some(PREVIEW_TINT))],
+            None => Vec::new(),
+        }
+    }
+}
+
+// End of File
@@ -0,0 +1,64 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A marker drawn at the point that the camera turns about, while it is
+//! turning, so that it is clear what the view orbits.  Inside a hollow
+//! structure the focus is often behind a wall, so it can be pushed away from
+//! the camera or pulled towards it (see [`CameraPose::with_orbit_radius`])
+//! and the marker shows where it ends up.
+//!
+//! [`CameraPose::with_orbit_radius`]: render::CameraPose::with_orbit_radius
+
+use periodic_table::Element;
+use render::{AtomBuffer, AtomKind, AtomRepr, GlobalRenderResources, RenderCamera};
+use ultraviolet::{Mat4, Vec3};
+
+use super::tools::OverlayDraw;
+
+/// The color the marker is drawn in: pale, so that it does not hide the
+/// atoms around it.
+const MARKER_TINT: Vec3 = Vec3::new(0.85, 0.9, 1.0);
+
+#[derive(Default)]
+pub struct OrbitMarker {
+    /// One atom at the origin, moved to the focus when drawn.
+    buffer: Option<AtomBuffer>,
+    /// Where the marker is drawn, or `None` while the camera is not turning.
+    focus: Option<Vec3>,
+}
+
+impl OrbitMarker {
+    /// Follows the camera's focus while it turns, and hides the marker
+    /// otherwise.
+    pub fn update(&mut self, camera: &RenderCamera) {
+        self.focus = camera
+            .is_orbiting()
+            .then(|| camera.pose())
+            .flatten()
+            .map(|pose| pose.focus);
+    }
+
+    pub fn synchronize_buffers(&mut self, gpu_resources: &GlobalRenderResources) {
+        if self.buffer.is_none() {
+            self.buffer = Some(AtomBuffer::new(
+                gpu_resources,
+                [AtomRepr {
+                    pos: Vec3::zero(),
+                    kind: AtomKind::new(Element::Hydrogen),
+                    property: 0.0,
+                }],
+            ));
+        }
+    }
+
+    pub fn render_overlay(&self) -> Option<OverlayDraw<'_>> {
+        Some((
+            self.buffer.as_ref()?,
+            Mat4::from_translation(self.focus?),
+            Some(MARKER_TINT),
+        ))
+    }
+}
+
+// End of File
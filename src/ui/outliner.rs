@@ -0,0 +1,318 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The tree of the assembly's components, for rearranging, hiding, locking
+//! and tinting them.  While the outliner is open it receives all typing: the
+//! arrow keys move through the rows and fold and unfold sub-assemblies, and a
+//! component is picked up and dropped onto another row to move it, with the
+//! keyboard or by dragging its row.
+
+use std::collections::HashSet;
+
+use scene::{Assembly, AssemblyCommand, CommandError, ComponentId, ProjectHistory};
+use ultraviolet::Vec3;
+
+/// What a row in the outliner represents.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OutlinerNode {
+    /// A component holding a sub-assembly.
+    Assembly(ComponentId),
+    /// A component holding a molecule.
+    Component(ComponentId),
+    /// The molecule held by a component, shown as the component's only child.
+    Molecule(ComponentId),
+}
+
+impl OutlinerNode {
+    /// The component that this row belongs to.  Clicking on, hiding, or
+    /// dragging a row always acts on this component.
+    pub fn component(&self) -> ComponentId {
+        match *self {
+            OutlinerNode::Assembly(id)
+            | OutlinerNode::Component(id)
+            | OutlinerNode::Molecule(id) => id,
+        }
+    }
+}
+
+/// A single line of the outliner, in display order.
+#[derive(Clone, Debug)]
+pub struct OutlinerRow {
+    pub node: OutlinerNode,
+    /// Nesting level, used for indentation.  Direct children of the root
+    /// assembly have depth 0.
+    pub depth: usize,
+    pub label: String,
+    /// Whether the row has children (and therefore an expand/collapse arrow).
+    pub expandable: bool,
+    pub expanded: bool,
+    pub visible: bool,
+    pub locked: bool,
+    /// The color the component is drawn in, if it overrides its elements'.
+    pub tint: Option<Vec3>,
+    pub selected: bool,
+}
+
+/// The state of the scene outliner: which nodes are expanded, which component
+/// is selected, and any drag-and-drop in progress.  Components are tracked by
+/// `ComponentId`, so the state stays valid as the assembly is edited.
+#[derive(Default)]
+pub struct Outliner {
+    open: bool,
+    expanded: HashSet<ComponentId>,
+    selected: Option<ComponentId>,
+    dragging: Option<ComponentId>,
+}
+
+impl Outliner {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Opens the outliner, selecting the first row if nothing is selected.
+    pub fn open(&mut self, assembly: &Assembly) {
+        self.open = true;
+        self.prune(assembly);
+        if self.selected.is_none() {
+            self.selected = assembly.direct_children().first().map(|c| c.id());
+        }
+    }
+
+    /// Closes the outliner, dropping any component being dragged where it
+    /// was.  Which rows are expanded is kept for next time.
+    pub fn close(&mut self) {
+        self.open = false;
+        self.dragging = None;
+    }
+
+    /// Flattens the visible part of the tree into rows, in display order.
+    pub fn rows(&self, assembly: &Assembly) -> Vec<OutlinerRow> {
+        let mut rows = Vec::new();
+        self.push_rows(assembly, 0, &mut rows);
+        rows
+    }
+
+    fn push_rows(&self, assembly: &Assembly, depth: usize, rows: &mut Vec<OutlinerRow>) {
+        for component in assembly.direct_children() {
+            let id = component.id();
+            let expanded = self.expanded.contains(&id);
+            let node = match component.sub_assembly() {
+                Some(_) => OutlinerNode::Assembly(id),
+                None => OutlinerNode::Component(id),
+            };
+
+            rows.push(OutlinerRow {
+                node,
+                depth,
+                label: label(component.name(), "Unnamed Component"),
+                expandable: true,
+                expanded,
+                visible: component.is_visible(),
+                locked: component.is_locked(),
+                tint: component.tint(),
+                selected: self.selected == Some(id),
+            });
+
+            if !expanded {
+                continue;
+            }
+
+            if let Some(sub_assembly) = component.sub_assembly() {
+                self.push_rows(sub_assembly, depth + 1, rows);
+            } else if let Some(molecule) = component.molecule() {
+                rows.push(OutlinerRow {
+                    node: OutlinerNode::Molecule(id),
+                    depth: depth + 1,
+                    label: label(molecule.name(), "Unnamed Molecule"),
+                    expandable: false,
+                    expanded: false,
+                    visible: component.is_visible(),
+                    locked: component.is_locked(),
+                    tint: component.tint(),
+                    selected: self.selected == Some(id),
+                });
+            }
+        }
+    }
+
+    pub fn is_expanded(&self, id: ComponentId) -> bool {
+        self.expanded.contains(&id)
+    }
+
+    pub fn toggle_expanded(&mut self, id: ComponentId) {
+        if !self.expanded.remove(&id) {
+            self.expanded.insert(id);
+        }
+    }
+
+    pub fn set_expanded(&mut self, id: ComponentId, expanded: bool) {
+        if expanded {
+            self.expanded.insert(id);
+        } else {
+            self.expanded.remove(&id);
+        }
+    }
+
+    /// Selects the component of the next row that belongs to another
+    /// component, or of the previous one if not `forward`.  Selects the first
+    /// row if nothing is selected, and stays put at either end.
+    pub fn select_next(&mut self, assembly: &Assembly, forward: bool) {
+        let rows = self.rows(assembly);
+        let components = rows.iter().map(|row| row.node.component());
+        let current = self
+            .selected
+            .and_then(|id| components.clone().position(|c| c == id));
+        self.selected = match current {
+            None => components.clone().next(),
+            Some(index) if forward => components
+                .clone()
+                .skip(index)
+                .find(|c| Some(*c) != self.selected)
+                .or(self.selected),
+            Some(index) => components.take(index).next_back().or(self.selected),
+        };
+    }
+
+    /// The row of the selected component.
+    pub fn selected_node(&self, assembly: &Assembly) -> Option<OutlinerNode> {
+        let id = self.selected?;
+        self.rows(assembly)
+            .into_iter()
+            .map(|row| row.node)
+            .find(|node| node.component() == id)
+    }
+
+    pub fn selected(&self) -> Option<ComponentId> {
+        self.selected
+    }
+
+    /// Selects the component `id`, expanding the sub-assemblies that hold it so
+    /// that its row is shown.
+    pub fn reveal(&mut self, assembly: &Assembly, id: ComponentId) {
+        let Some(path) = assembly.path(id) else {
+            return;
+        };
+        self.expanded.extend(path.ancestors());
+        self.selected = Some(path.id());
+    }
+
+    /// Handles a click on a row.  Clicking the selected row again deselects it.
+    pub fn click(&mut self, node: OutlinerNode) {
+        let id = node.component();
+        self.selected = if self.selected == Some(id) {
+            None
+        } else {
+            Some(id)
+        };
+    }
+
+    /// Handles a click on a row's visibility toggle.
+    pub fn toggle_visibility(&mut self, assembly: &mut Assembly, node: OutlinerNode) {
+        if let Some(component) = assembly.component_mut(node.component()) {
+            component.set_visible(!component.is_visible());
+        }
+    }
+
+    /// Handles a click on a row's lock toggle.
+    pub fn toggle_locked(&mut self, assembly: &mut Assembly, node: OutlinerNode) {
+        if let Some(component) = assembly.component_mut(node.component()) {
+            component.set_locked(!component.is_locked());
+        }
+    }
+
+    /// Handles a color being picked for a row.  `None` goes back to coloring by
+    /// element.
+    pub fn set_tint(&mut self, assembly: &mut Assembly, node: OutlinerNode, tint: Option<Vec3>) {
+        if let Some(component) = assembly.component_mut(node.component()) {
+            component.set_tint(tint);
+        }
+    }
+
+    /// Starts dragging a row.
+    pub fn begin_drag(&mut self, node: OutlinerNode) {
+        self.dragging = Some(node.component());
+    }
+
+    pub fn dragging(&self) -> Option<ComponentId> {
+        self.dragging
+    }
+
+    pub fn cancel_drag(&mut self) {
+        self.dragging = None;
+    }
+
+    /// Finishes a drag by dropping the dragged component onto `target`.
+    /// Dropping onto a sub-assembly moves the component into it; dropping onto
+    /// anything else moves the component just after the target, into the
+    /// target's parent.  Passing `None` (e.g. dropping on empty space below the
+    /// tree) moves the component to the end of the root assembly.  The move is
+    /// recorded in `history`, to be undone.
+    pub fn drop_onto(
+        &mut self,
+        assembly: &mut Assembly,
+        history: &mut ProjectHistory,
+        target: Option<OutlinerNode>,
+    ) -> Result<(), CommandError> {
+        let Some(dragged) = self.dragging.take() else {
+            return Ok(());
+        };
+        if target.is_some_and(|node| node.component() == dragged) {
+            return Ok(());
+        }
+
+        let (parent, index) = match target {
+            Some(OutlinerNode::Assembly(id)) => (Some(id), None),
+            Some(node) => match assembly.location(node.component()) {
+                // Taking the component out first moves the target up if the
+                // component was before it.
+                Some((parent, index)) => match assembly.location(dragged) {
+                    Some((from, from_index)) if from == parent && from_index < index => {
+                        (parent, Some(index))
+                    }
+                    _ => (parent, Some(index + 1)),
+                },
+                None => (None, None),
+            },
+            None => (None, None),
+        };
+        let description = assembly
+            .component(dragged)
+            .map_or_else(|| "move".to_owned(), |c| format!("move {}", c.name()));
+
+        history.execute(
+            assembly,
+            AssemblyCommand::Move {
+                id: dragged,
+                parent,
+                index,
+            },
+            description,
+        )
+    }
+
+    /// Forgets state that refers to components which no longer exist.
+    pub fn prune(&mut self, assembly: &Assembly) {
+        self.expanded.retain(|id| assembly.component(*id).is_some());
+        if let Some(id) = self.selected {
+            if assembly.component(id).is_none() {
+                self.selected = None;
+            }
+        }
+        if let Some(id) = self.dragging {
+            if assembly.component(id).is_none() {
+                self.dragging = None;
+            }
+        }
+    }
+}
+
+fn label(name: &str, fallback: &str) -> String {
+    if name.is_empty() {
+        fallback.to_owned()
+    } else {
+        name.to_owned()
+    }
+}
+
+// End of File
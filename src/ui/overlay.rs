@@ -0,0 +1,521 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Lays out what the panels describe over the 3D view, and finds which part of
+//! it a click lands on.
+
+use super::{outliner::OutlinerNode, status, timeline::TIMELINE_HEIGHT, tools::ToolKind, Panels};
+use common::ids::EditId;
+use render::{Overlay, Renderer};
+use scene::{Assembly, ComponentId};
+use ultraviolet::{Vec2, Vec4};
+use winit::{dpi::PhysicalPosition, window::Window};
+
+// Distances in logical pixels: around the edge of the window and between
+// panels, and between the edge of a panel and what is in it.
+const MARGIN: f32 = 8.0;
+const PADDING: f32 = 6.0;
+
+// Colors, in linear RGB with alpha.
+const PANEL_COLOR: Vec4 = Vec4::new(0.01, 0.01, 0.012, 0.8);
+const ACTIVE_COLOR: Vec4 = Vec4::new(0.05, 0.22, 0.6, 0.9);
+const TEXT_COLOR: Vec4 = Vec4::new(0.9, 0.9, 0.9, 1.0);
+const MUTED_COLOR: Vec4 = Vec4::new(0.4, 0.4, 0.4, 1.0);
+const WARNING_COLOR: Vec4 = Vec4::new(1.0, 0.45, 0.1, 1.0);
+
+/// A part of the overlay that does something when clicked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hit {
+    /// A button of the toolbar.
+    Tool(ToolKind),
+    /// A row of the outliner, which is selected, dragged and dropped onto.
+    OutlinerRow(OutlinerNode),
+    /// The arrow that folds and unfolds a row of the outliner.
+    OutlinerFold(ComponentId),
+    OutlinerVisibility(OutlinerNode),
+    OutlinerLock(OutlinerNode),
+    /// The outliner anywhere but on a row, which a row is dropped onto to
+    /// move it to the end of the root assembly.
+    OutlinerEmpty,
+    /// A row of the problems panel: the failed edit of a molecule.
+    Problem(ComponentId, EditId),
+}
+
+/// What was drawn over the view in the last frame, so that clicks can be
+/// matched to it.
+#[derive(Default)]
+pub struct OverlayLayer {
+    // The parts that can be clicked, in the order they were drawn.
+    hits: Vec<(Region, Hit)>,
+    // What the button that is held was pressed on, if it was on the overlay.
+    pressed: Option<Hit>,
+}
+
+impl OverlayLayer {
+    /// The topmost part of the overlay under `position`, if any.
+    pub fn hit(&self, position: PhysicalPosition<f64>) -> Option<Hit> {
+        let (x, y) = (position.x as f32, position.y as f32);
+        self.hits
+            .iter()
+            .rev()
+            .find(|(region, _)| region.contains(x, y))
+            .map(|(_, hit)| *hit)
+    }
+
+    /// Notes a press at `position`, and returns what it landed on.
+    pub fn press(&mut self, position: PhysicalPosition<f64>) -> Option<Hit> {
+        self.pressed = self.hit(position);
+        self.pressed
+    }
+
+    /// Whether a press on the overlay is still held.
+    pub fn is_pressed(&self) -> bool {
+        self.pressed.is_some()
+    }
+
+    /// Ends the press, returning what it started on and what it ended on.
+    pub fn release(&mut self, position: PhysicalPosition<f64>) -> Option<(Hit, Option<Hit>)> {
+        let pressed = self.pressed.take()?;
+        Some((pressed, self.hit(position)))
+    }
+}
+
+/// Lays out the panels over the view for the next frame.
+pub fn draw(
+    panels: &mut Panels,
+    world: &Assembly,
+    window: &Window,
+    renderer: &mut Renderer,
+) -> Overlay {
+    let mut painter = Painter::new(window);
+
+    // The labels are beside the atoms, under the panels.
+    measurement_labels(&mut painter, panels, world, renderer);
+
+    // Down the left: the toolbar, the active tool's hint, and the outliner.
+    let mut top = toolbar(&mut painter, panels);
+    if let Some(hint) = panels.tools.active_tool().hint() {
+        top = painter
+            .panel(painter.margin(), top, &[(hint, TEXT_COLOR)])
+            .bottom()
+            + painter.scaled(MARGIN) / 2.0;
+    }
+    if panels.outliner.is_open() {
+        outliner(&mut painter, panels, world, top);
+    }
+    if panels.statistics.is_open() {
+        statistics(&mut painter, panels);
+    }
+    problems(&mut painter, panels, world);
+    status_lines(&mut painter);
+    tooltip(&mut painter, panels, world);
+    radial_menu(&mut painter, panels);
+
+    panels.overlay.hits = painter.hits;
+    painter.overlay
+}
+
+// Each measurement's value, in a box centered on the middle of its atoms.
+fn measurement_labels(
+    painter: &mut Painter,
+    panels: &Panels,
+    world: &Assembly,
+    renderer: &mut Renderer,
+) {
+    let view_size = renderer.view_size();
+    let labels = panels
+        .tools
+        .measurements
+        .labels(world, renderer.camera(), &view_size);
+    let padding = painter.scaled(PADDING);
+    for label in labels {
+        let text = label.value.to_string();
+        let x = label.position.x as f32 - painter.overlay.text_width(&text) / 2.0 - padding;
+        let y = label.position.y as f32 - painter.overlay.line_height() / 2.0 - padding;
+        painter.panel(x, y, &[(text, TEXT_COLOR)]);
+    }
+}
+
+// The tools in a row along the top of the window.  Returns where the row ends
+// below.
+fn toolbar(painter: &mut Painter, panels: &Panels) -> f32 {
+    let editing = panels.review.allows_editing();
+    let (mut x, y) = (painter.margin(), painter.margin());
+    let mut bottom = y;
+    for button in panels.tools.toolbar() {
+        let color = if button.kind.edits() && !editing {
+            MUTED_COLOR
+        } else {
+            TEXT_COLOR
+        };
+        let mut label = vec![(button.label, color)];
+        label.extend(button.shortcut.map(|shortcut| (shortcut, MUTED_COLOR)));
+        let region = painter.button(x, y, &label, button.active);
+        painter.hits.push((region, Hit::Tool(button.kind)));
+        x += region.width + painter.scaled(MARGIN) / 2.0;
+        bottom = region.bottom();
+    }
+    bottom + painter.scaled(MARGIN) / 2.0
+}
+
+// The outliner's rows, from `top` down, with toggles to fold, hide and lock
+// them.  While a row is dragged, the row it would be dropped onto is
+// highlighted.
+fn outliner(painter: &mut Painter, panels: &Panels, world: &Assembly, top: f32) {
+    let outliner = &panels.outliner;
+    let rows = outliner.rows(world);
+    let padding = painter.scaled(PADDING);
+    let line_height = painter.overlay.line_height();
+    let advance = painter.overlay.text_width("  ") - painter.overlay.text_width(" ");
+    let dragging = outliner.dragging();
+    let footer = match dragging.and_then(|id| world.component(id)) {
+        Some(component) => format!(
+            "Moving {}: drop it on a row (M), or here to put it last (Shift+M)",
+            component.name()
+        ),
+        None => "Drag or M moves, V hides, L locks, Esc closes".to_owned(),
+    };
+
+    // Each row is indented by its depth, and has room for its fold arrow, and
+    // its tint, visibility and lock on the right.
+    let widest = rows
+        .iter()
+        .map(|row| (2 * row.depth + 2) as f32 * advance + painter.overlay.text_width(&row.label))
+        .fold(0.0, f32::max);
+    let width = (widest + 7.0 * advance).max(painter.overlay.text_width(&footer)) + 2.0 * padding;
+    let panel = Region {
+        x: painter.margin(),
+        y: top,
+        width,
+        height: (rows.len() + 1) as f32 * line_height + 2.0 * padding,
+    };
+    painter.fill(panel, PANEL_COLOR);
+    painter.hits.push((panel, Hit::OutlinerEmpty));
+
+    let cursor = panels.input.cursor_pos;
+    let right = panel.x + width - padding;
+    for (index, row) in rows.iter().enumerate() {
+        let y = top + padding + index as f32 * line_height;
+        let region = Region {
+            x: panel.x,
+            y,
+            width,
+            height: line_height,
+        };
+        let dragged = dragging == Some(row.node.component());
+        let drop_target =
+            dragging.is_some() && !dragged && region.contains(cursor.x as f32, cursor.y as f32);
+        if row.selected || drop_target {
+            painter.fill(region, ACTIVE_COLOR);
+        }
+        painter.hits.push((region, Hit::OutlinerRow(row.node)));
+
+        let x = panel.x + padding + (2 * row.depth) as f32 * advance;
+        if row.expandable {
+            let arrow = if row.expanded { "-" } else { "+" };
+            painter.overlay.text(x, y, arrow, TEXT_COLOR);
+            let fold = Region {
+                x,
+                y,
+                width: advance,
+                height: line_height,
+            };
+            painter
+                .hits
+                .push((fold, Hit::OutlinerFold(row.node.component())));
+        }
+        let color = if dragged || !row.visible {
+            MUTED_COLOR
+        } else {
+            TEXT_COLOR
+        };
+        painter
+            .overlay
+            .text(x + 2.0 * advance, y, &row.label, color);
+
+        if let Some(tint) = row.tint {
+            let size = line_height - 2.0 * painter.overlay.text_width(".");
+            let swatch = Vec4::new(tint.x, tint.y, tint.z, 1.0);
+            painter
+                .overlay
+                .rect(right - 6.0 * advance, y, size, size, swatch);
+        }
+        let toggles = [
+            ("V", row.visible, 4.0, Hit::OutlinerVisibility(row.node)),
+            ("L", row.locked, 2.0, Hit::OutlinerLock(row.node)),
+        ];
+        for (letter, on, from_right, hit) in toggles {
+            let x = right - from_right * advance;
+            let color = if on { TEXT_COLOR } else { MUTED_COLOR };
+            painter.overlay.text(x, y, letter, color);
+            let toggle = Region {
+                x: x - advance / 2.0,
+                y,
+                width: 2.0 * advance,
+                height: line_height,
+            };
+            painter.hits.push((toggle, hit));
+        }
+    }
+
+    let y = top + padding + rows.len() as f32 * line_height;
+    painter
+        .overlay
+        .text(panel.x + padding, y, &footer, MUTED_COLOR);
+}
+
+// The statistics of the scene, in the top right corner.
+fn statistics(painter: &mut Painter, panels: &Panels) {
+    let mut lines = vec![("Statistics (F3 closes)".to_owned(), MUTED_COLOR)];
+    lines.extend(
+        panels
+            .statistics
+            .statistics()
+            .lines()
+            .into_iter()
+            .map(|line| (line, TEXT_COLOR)),
+    );
+    let width = lines
+        .iter()
+        .map(|(text, _)| painter.overlay.text_width(text))
+        .fold(0.0, f32::max)
+        + painter.scaled(2.0 * PADDING);
+    let x = painter.width - painter.margin() - width;
+    painter.panel(x, painter.margin(), &lines);
+}
+
+// The edits that failed, if any, in the bottom right corner above the timeline
+// strip.  The one last chosen is highlighted.
+fn problems(painter: &mut Painter, panels: &Panels, world: &Assembly) {
+    let rows = panels.diagnostics.rows(world);
+    if rows.is_empty() {
+        return;
+    }
+    let title = match rows.len() {
+        1 => "1 edit failed (F8 chooses it)".to_owned(),
+        count => format!("{} edits failed (F8 chooses the next)", count),
+    };
+    let padding = painter.scaled(PADDING);
+    let line_height = painter.overlay.line_height();
+    let width = rows
+        .iter()
+        .map(|row| painter.overlay.text_width(&row.description))
+        .fold(painter.overlay.text_width(&title), f32::max)
+        + 2.0 * padding;
+    let height = (rows.len() + 1) as f32 * line_height + 2.0 * padding;
+    let panel = Region {
+        x: painter.width - painter.margin() - width,
+        y: painter.height - painter.scaled(TIMELINE_HEIGHT as f32 + MARGIN) - height,
+        width,
+        height,
+    };
+    painter.fill(panel, PANEL_COLOR);
+    painter
+        .overlay
+        .text(panel.x + padding, panel.y + padding, &title, MUTED_COLOR);
+    for (index, row) in rows.iter().enumerate() {
+        let region = Region {
+            x: panel.x,
+            y: panel.y + padding + (index + 1) as f32 * line_height,
+            width,
+            height: line_height,
+        };
+        if row.highlighted {
+            painter.fill(region, ACTIVE_COLOR);
+        }
+        painter
+            .hits
+            .push((region, Hit::Problem(row.component, row.edit)));
+        painter.overlay.text(
+            region.x + padding,
+            region.y,
+            &row.description,
+            WARNING_COLOR,
+        );
+    }
+}
+
+// The last report, above the timeline strip.
+fn status_lines(painter: &mut Painter) {
+    let lines: Vec<_> = status::current()
+        .into_iter()
+        .map(|message| {
+            let color = if message.warning {
+                WARNING_COLOR
+            } else {
+                TEXT_COLOR
+            };
+            (message.text, color)
+        })
+        .collect();
+    if lines.is_empty() {
+        return;
+    }
+    let y = painter.height
+        - painter.scaled(TIMELINE_HEIGHT as f32)
+        - lines.len() as f32 * painter.overlay.line_height()
+        - painter.scaled(2.0 * PADDING + MARGIN);
+    painter.panel(painter.margin(), y, &lines);
+}
+
+// What the atom under the cursor is, below and to the right of it, or to the
+// left and above where it would not fit.
+fn tooltip(painter: &mut Painter, panels: &Panels, world: &Assembly) {
+    // Atoms are not hovered in the comparison of two designs.
+    if panels.compare.is_open() {
+        return;
+    }
+    let Some(tooltip) = panels.hover.tooltip(world) else {
+        return;
+    };
+    let text = tooltip.text();
+    let offset = painter.scaled(2.0 * MARGIN);
+    let width = painter.overlay.text_width(&text) + painter.scaled(2.0 * PADDING);
+    let height = painter.overlay.line_height() + painter.scaled(2.0 * PADDING);
+    let cursor = panels.input.cursor_pos;
+    let (mut x, mut y) = (cursor.x as f32 + offset, cursor.y as f32 + offset);
+    if x + width > painter.width {
+        x = cursor.x as f32 - offset - width;
+    }
+    if y + height > painter.height {
+        y = cursor.y as f32 - offset - height;
+    }
+    painter.panel(x, y, &[(text, TEXT_COLOR)]);
+}
+
+// The ring of the radial menu while it is open, with the item that lifting
+// would choose highlighted.
+fn radial_menu(painter: &mut Painter, panels: &Panels) {
+    let Some(ring) = panels.radial_menu.ring() else {
+        return;
+    };
+    let center = Vec2::new(ring.center.x as f32, ring.center.y as f32);
+    let (inner, outer) = (ring.inner_radius as f32, ring.outer_radius as f32);
+    // The slices are kept apart by a gap as wide as a margin at the ring's
+    // outer edge.
+    let gap = painter.margin() / outer / 2.0;
+    for button in &ring.buttons {
+        let color = if button.highlighted {
+            ACTIVE_COLOR
+        } else {
+            PANEL_COLOR
+        };
+        let (start, end) = (button.angles.0 as f32, button.angles.1 as f32);
+        painter
+            .overlay
+            .arc(center, inner, outer, start + gap, end - gap, color);
+        let width = painter.overlay.text_width(button.label);
+        let height = painter.overlay.line_height();
+        painter.overlay.text(
+            button.position.x as f32 - width / 2.0,
+            button.position.y as f32 - height / 2.0,
+            button.label,
+            TEXT_COLOR,
+        );
+    }
+}
+
+// A rectangle of the window, in physical pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Region {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+impl Region {
+    fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+}
+
+// Draws boxes of text into an overlay, and collects the parts that can be
+// clicked.
+struct Painter {
+    overlay: Overlay,
+    hits: Vec<(Region, Hit)>,
+    // The number of physical pixels to each logical one.
+    scale_factor: f32,
+    width: f32,
+    height: f32,
+}
+
+impl Painter {
+    fn new(window: &Window) -> Self {
+        Self {
+            overlay: Overlay::new(window.scale_factor()),
+            hits: Vec::new(),
+            scale_factor: window.scale_factor() as f32,
+            width: window.inner_size().width as f32,
+            height: window.inner_size().height as f32,
+        }
+    }
+
+    fn scaled(&self, logical: f32) -> f32 {
+        logical * self.scale_factor
+    }
+
+    fn margin(&self) -> f32 {
+        self.scaled(MARGIN)
+    }
+
+    // A box around words in a line, each in its own color, highlighted if
+    // `active`.
+    fn button(&mut self, x: f32, y: f32, words: &[(&str, Vec4)], active: bool) -> Region {
+        let padding = self.scaled(PADDING);
+        let space = self.overlay.text_width("  ") - self.overlay.text_width(" ");
+        let width = words
+            .iter()
+            .map(|(text, _)| self.overlay.text_width(text) + space)
+            .sum::<f32>()
+            - space;
+        let region = Region {
+            x,
+            y,
+            width: width + 2.0 * padding,
+            height: self.overlay.line_height() + 2.0 * padding,
+        };
+        let background = if active { ACTIVE_COLOR } else { PANEL_COLOR };
+        self.fill(region, background);
+        let mut left = x + padding;
+        for (text, color) in words {
+            left += self.overlay.text(left, y + padding, text, *color) + space;
+        }
+        region
+    }
+
+    // A box around lines of text, each in its own color.
+    fn panel(&mut self, x: f32, y: f32, lines: &[(String, Vec4)]) -> Region {
+        let padding = self.scaled(PADDING);
+        let line_height = self.overlay.line_height();
+        let width = lines
+            .iter()
+            .map(|(text, _)| self.overlay.text_width(text))
+            .fold(0.0, f32::max);
+        let region = Region {
+            x,
+            y,
+            width: width + 2.0 * padding,
+            height: lines.len() as f32 * line_height + 2.0 * padding,
+        };
+        self.fill(region, PANEL_COLOR);
+        for (index, (text, color)) in lines.iter().enumerate() {
+            let top = y + padding + index as f32 * line_height;
+            self.overlay.text(x + padding, top, text, *color);
+        }
+        region
+    }
+
+    fn fill(&mut self, region: Region, color: Vec4) {
+        self.overlay
+            .rect(region.x, region.y, region.width, region.height, color);
+    }
+}
+
+// End of File
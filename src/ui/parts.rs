@@ -0,0 +1,93 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! An offer to replace the repeated parts of a molecule with instances of a
+//! single copy.
+//!
+//! The analysis is run on request rather than after every edit, as matching
+//! the parts of a large molecule against each other is slow.
+
+use molecule::parts::{self, RepeatedPart};
+use scene::{Assembly, AssemblyCommand, ComponentId, InstancePartsError, ProjectHistory};
+
+/// How far apart, in angstroms RMSD, two copies of a part may be and still be
+/// treated as identical.  This allows for the small differences left over
+/// after relaxation.
+const TOLERANCE: f32 = 0.05;
+
+/// A summary of one repeated part, for display in a list.
+#[derive(Clone, Debug)]
+pub struct PartRow {
+    pub atoms: usize,
+    pub copies: usize,
+    /// The atoms that would no longer need to be stored if the copies were
+    /// instanced, all of them being drawn from one molecule.
+    pub saved_atoms: usize,
+}
+
+#[derive(Default)]
+pub struct PartsOffer {
+    offer: Option<(ComponentId, Vec<RepeatedPart>)>,
+}
+
+impl PartsOffer {
+    /// Looks for repeated parts in the molecule `component`, and offers to
+    /// instance them if there are any.  Returns whether an offer was made.
+    pub fn analyze(&mut self, assembly: &Assembly, component: ComponentId) -> bool {
+        self.offer = assembly
+            .component(component)
+            .and_then(|component| component.molecule())
+            .map(|molecule| parts::find_repeated_parts(&molecule.repr, TOLERANCE))
+            .filter(|parts| !parts.is_empty())
+            .map(|parts| (component, parts));
+        self.offer.is_some()
+    }
+
+    pub fn has_offer(&self) -> bool {
+        self.offer.is_some()
+    }
+
+    pub fn rows(&self) -> Vec<PartRow> {
+        let Some((_, parts)) = &self.offer else {
+            return Vec::new();
+        };
+        parts
+            .iter()
+            .map(|part| PartRow {
+                atoms: part.atom_count(),
+                copies: part.instances.len(),
+                saved_atoms: part.redundant_atoms(),
+            })
+            .collect()
+    }
+
+    pub fn dismiss(&mut self) {
+        self.offer = None;
+    }
+
+    /// Instances the parts that were found, as a step of `history` that puts
+    /// the molecule back when it is undone.  Does nothing if there is no
+    /// offer.
+    pub fn accept(
+        &mut self,
+        assembly: &mut Assembly,
+        history: &mut ProjectHistory,
+    ) -> Result<(), InstancePartsError> {
+        let Some((component, parts)) = self.offer.take() else {
+            return Ok(());
+        };
+        let replaced = assembly.instance_parts(component, &parts)?;
+        let description = format!("instance the parts of {}", replaced.name());
+        history.record(
+            assembly,
+            AssemblyCommand::Replace {
+                component: Box::new(replaced),
+            },
+            description,
+        );
+        Ok(())
+    }
+}
+
+// End of File
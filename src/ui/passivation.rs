@@ -0,0 +1,58 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Capping the dangling bonds of a molecule in one step, e.g. after cutting a
+//! block out of a lattice.  The terminating species is chosen from a short
+//! list, and the atoms to cap can be limited to some elements and to those
+//! with few enough neighbours.
+
+use common::ids::AtomSpecifier;
+use molecule::{edit::Edit, passivation::Passivation};
+use periodic_table::Element;
+
+/// The species that dangling bonds can be capped with, in the order they are
+/// cycled through.
+pub const SPECIES: [Element; 3] = [Element::Hydrogen, Element::Fluorine, Element::Chlorine];
+
+#[derive(Default)]
+pub struct PassivationPanel {
+    pub criteria: Passivation,
+}
+
+impl PassivationPanel {
+    /// Moves on to the next species in [`SPECIES`], going back to the first
+    /// after the last.
+    pub fn cycle_species(&mut self) -> Element {
+        let index = SPECIES
+            .iter()
+            .position(|species| *species == self.criteria.species)
+            .map_or(0, |index| index + 1);
+        self.criteria.species = SPECIES[index % SPECIES.len()];
+        self.criteria.species
+    }
+
+    /// Caps only atoms of `element` if it is not already among those capped,
+    /// or stops limiting to it if it is.  With no elements chosen, every
+    /// element is capped.
+    pub fn toggle_element(&mut self, element: Element) {
+        let elements = &mut self.criteria.elements;
+        match elements.iter().position(|other| *other == element) {
+            Some(index) => {
+                elements.remove(index);
+            }
+            None => elements.push(element),
+        }
+    }
+
+    /// The edit that caps the atoms that match the criteria, among `atoms`
+    /// if there are any (such as the selection), or among every atom.
+    pub fn edit(&self, atoms: impl IntoIterator<Item = AtomSpecifier>) -> Edit {
+        Edit::Passivation(Passivation {
+            atoms: atoms.into_iter().collect(),
+            ..self.criteria.clone()
+        })
+    }
+}
+
+// End of File
@@ -0,0 +1,90 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Duplicates and patterns of a molecule.  The copies are laid out from the
+//! current view: duplicates and linear patterns run across the screen, spaced
+//! so that neighbouring copies do not overlap, and circular patterns go around
+//! the point that the camera looks at, about the line of sight.
+
+use render::CameraPose;
+use scene::{Assembly, ComponentId, Pattern, PatternError};
+use ultraviolet::Vec3;
+
+/// The gap left between neighbouring copies, in angstroms.
+const SPACING_MARGIN: f32 = 2.0;
+
+/// The shapes of pattern that can be made.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternShape {
+    Duplicate,
+    Linear,
+    Circular,
+}
+
+pub struct PatternPanel {
+    /// The number of instances in new linear patterns, counting the seed.
+    pub linear_count: u32,
+    /// The number of instances in new circular patterns, counting the seed.
+    pub circular_count: u32,
+}
+
+impl Default for PatternPanel {
+    fn default() -> Self {
+        Self {
+            linear_count: 4,
+            circular_count: 6,
+        }
+    }
+}
+
+impl PatternPanel {
+    /// The patterns in the assembly, for display in a list.
+    pub fn rows(&self, assembly: &Assembly) -> Vec<String> {
+        assembly.patterns().iter().map(Pattern::name).collect()
+    }
+
+    /// Makes a pattern of copies of the molecule `seed`, laid out from the
+    /// camera `pose`.
+    pub fn add(
+        &self,
+        assembly: &mut Assembly,
+        seed: ComponentId,
+        shape: PatternShape,
+        pose: &CameraPose,
+    ) -> Result<Pattern, PatternError> {
+        let molecule = assembly
+            .component(seed)
+            .ok_or(PatternError::UnknownComponent)?
+            .molecule()
+            .ok_or(PatternError::NotAMolecule)?;
+        // The diagonal of the bounding box is as wide as the molecule can be
+        // in any direction, however it is turned.
+        let bounds = molecule.repr.bounding_box();
+        let spacing = (bounds.max - bounds.min).mag() + SPACING_MARGIN;
+        let across = Vec3::new(pose.yaw.cos(), -pose.yaw.sin(), 0.0);
+        let line_of_sight = Vec3::new(
+            pose.yaw.sin() * pose.pitch.cos(),
+            pose.yaw.cos() * pose.pitch.cos(),
+            pose.pitch.sin(),
+        );
+
+        let pattern = match shape {
+            PatternShape::Duplicate => Pattern::duplicate(seed, across * spacing),
+            PatternShape::Linear => Pattern::linear(seed, self.linear_count, across * spacing),
+            PatternShape::Circular => {
+                Pattern::circular(seed, self.circular_count, pose.focus, line_of_sight)
+            }
+        };
+        assembly.add_pattern(pattern)?;
+        Ok(pattern)
+    }
+
+    /// Removes the pattern made last.
+    pub fn remove_last(&self, assembly: &mut Assembly) -> Option<Pattern> {
+        let last = assembly.patterns().len().checked_sub(1)?;
+        assembly.remove_pattern(last)
+    }
+}
+
+// End of File
@@ -0,0 +1,306 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The atom placement tool.  An element is chosen from a palette, and each
+//! click adds an atom of it: bonded to the atom under the cursor, as a
+//! [`Edit::BondedAtom`], or as the first atom of a new molecule if there is no
+//! atom under the cursor.  While the tool is active, the atom that a click
+//! would add is previewed where it would go.  The number keys choose the
+//! element.
+
+use common::ids::AtomSpecifier;
+use molecule::{
+    edit::{self, BondedAtom, Edit},
+    MoleculeEditor,
+};
+use periodic_table::Element;
+use render::{AtomBuffer, AtomKind, AtomRepr, GlobalRenderResources};
+use scene::{Assembly, Component, ComponentId, ProjectHistory};
+use ultraviolet::{Mat4, Vec3};
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use super::tools::{OverlayDraw, Tool, ToolContext};
+
+/// The elements offered by the palette, in order.
+pub const PALETTE: [Element; 9] = [
+    Element::Hydrogen,
+    Element::Carbon,
+    Element::Nitrogen,
+    Element::Oxygen,
+    Element::Fluorine,
+    Element::Silicon,
+    Element::Phosphorus,
+    Element::Sulfur,
+    Element::Chlorine,
+];
+
+/// The keys that choose the elements of the palette, in order.
+pub const PALETTE_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+/// The color that the previewed atom is drawn in, so that it cannot be
+/// mistaken for an atom of the design.
+const PREVIEW_TINT: Vec3 = Vec3::new(0.4, 1.0, 0.6);
+
+#[derive(Clone, Debug)]
+pub struct PaletteEntry {
+    pub element: Element,
+    pub selected: bool,
+}
+
+/// Where a click would add an atom.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Placement {
+    /// Bonded to an atom of a molecule.
+    Bonded {
+        component: ComponentId,
+        target: AtomSpecifier,
+    },
+    /// As the first atom of a new molecule.
+    New,
+}
+
+/// What a click would do: where the new atom would go, in world space.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preview {
+    pub placement: Placement,
+    pub position: Vec3,
+}
+
+pub struct PlacementTool {
+    active: bool,
+    element: Element,
+    preview: Option<Preview>,
+    /// The previewed atom, uploaded when the preview changes.
+    buffer: Option<AtomBuffer>,
+    buffer_stale: bool,
+}
+
+impl Default for PlacementTool {
+    fn default() -> Self {
+        Self {
+            active: false,
+            element: Element::Carbon,
+            preview: None,
+            buffer: None,
+            buffer_stale: false,
+        }
+    }
+}
+
+impl PlacementTool {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn element(&self) -> Element {
+        self.element
+    }
+
+    pub fn palette(&self) -> Vec<PaletteEntry> {
+        PALETTE
+            .iter()
+            .map(|element| PaletteEntry {
+                element: *element,
+                selected: *element == self.element,
+            })
+            .collect()
+    }
+
+    /// Chooses the element in palette slot `index`.  Returns false if there is
+    /// no such slot.
+    pub fn choose(&mut self, index: usize) -> bool {
+        let Some(element) = PALETTE.get(index) else {
+            return false;
+        };
+        self.element = *element;
+        self.buffer_stale = true;
+        true
+    }
+
+    pub fn preview(&self) -> Option<&Preview> {
+        self.preview.as_ref()
+    }
+
+    /// Works out where a click would add an atom, given the atom under the
+    /// cursor (if any) and the ray through it.  Locked molecules cannot be
+    /// added to.  With no atom under the cursor, the new molecule goes where
+    /// the ray passes closest to `focus`, the point the camera looks at.
+    pub fn hover(
+        &mut self,
+        assembly: &Assembly,
+        hit: Option<(ComponentId, AtomSpecifier)>,
+        ray: Option<(Vec3, Vec3)>,
+        focus: Vec3,
+    ) {
+        let preview = match hit {
+            Some((component, target)) => assembly
+                .visible_molecules()
+                .into_iter()
+                .find(|(id, ..)| *id == component)
+                .filter(|_| assembly.is_locked(component) == Some(false))
+                .and_then(|(_, molecule, transform)| {
+                    let pos =
+                        edit::bonded_atom_position(&target, self.element, &molecule.repr).ok()?;
+                    Some(Preview {
+                        placement: Placement::Bonded { component, target },
+                        position: transform.transform_point3(pos),
+                    })
+                }),
+            None => ray.map(|(origin, direction)| Preview {
+                placement: Placement::New,
+                position: origin + direction * (focus - origin).dot(direction).max(0.0),
+            }),
+        };
+        self.set_preview(preview);
+    }
+
+    /// Adds the previewed atom to the assembly.  Returns the molecule it was
+    /// added to, or `None` if nothing is previewed or the molecule is locked.
+    /// A new molecule is recorded in `history`, to be undone.
+    pub fn place(
+        &mut self,
+        assembly: &mut Assembly,
+        history: &mut ProjectHistory,
+    ) -> Option<ComponentId> {
+        let preview = self.preview.take()?;
+        self.buffer_stale = true;
+        match preview.placement {
+            Placement::Bonded { component, target } => {
+                let molecule = assembly.component_mut(component)?.molecule_mut()?;
+                molecule.add_edit(Edit::BondedAtom(BondedAtom {
+                    target,
+                    element: self.element,
+                }));
+                Some(component)
+            }
+            Placement::New => {
+                let molecule = MoleculeEditor::from_feature(Edit::RootAtom(self.element));
+                let id = assembly.add_component(Component::from_molecule(
+                    molecule,
+                    Mat4::from_translation(preview.position),
+                ));
+                history.record_added(assembly, id);
+                Some(id)
+            }
+        }
+    }
+
+    /// The palette as a line of text, with the chosen element in brackets.
+    pub fn palette_line(&self) -> String {
+        palette_line(self.element)
+    }
+
+    fn set_preview(&mut self, preview: Option<Preview>) {
+        if preview != self.preview {
+            // Only the position changes while hovering over empty space, and
+            // the position is in the transform rather than the buffer.
+            self.buffer_stale |= preview.is_some() != self.preview.is_some();
+            self.preview = preview;
+        }
+    }
+}
+
+/// The palette as a line of text, with `chosen` in brackets.
+pub fn palette_line(chosen: Element) -> String {
+    let entries: Vec<String> = PALETTE
+        .iter()
+        .enumerate()
+        .map(|(index, element)| {
+            let symbol = element.symbol();
+            if *element == chosen {
+                format!("{} [{}]", index + 1, symbol)
+            } else {
+                format!("{} {}", index + 1, symbol)
+            }
+        })
+        .collect();
+    entries.join("  ")
+}
+
+impl Tool for PlacementTool {
+    fn set_active(&mut self, active: bool) {
+        self.active = active;
+        self.set_preview(None);
+    }
+
+    fn hint(&self) -> Option<String> {
+        Some(format!("placing atoms: {}", self.palette_line()))
+    }
+
+    fn on_pointer_down(&mut self, context: ToolContext) -> bool {
+        let element = self.element;
+        match self.place(context.assembly, context.history) {
+            Some(id) => log::info!(
+                "added {} to {}",
+                element.symbol(),
+                context
+                    .assembly
+                    .component(id)
+                    .map_or("a new molecule", |component| component.name())
+            ),
+            None => log::info!("nothing to add the atom to here"),
+        }
+        true
+    }
+
+    fn on_pointer_move(&mut self, context: ToolContext) -> bool {
+        let pointer = context.pointer;
+        let hit = pointer.hit(context.assembly);
+        self.hover(context.assembly, hit, pointer.ray, pointer.focus);
+        false
+    }
+
+    fn on_key(&mut self, _context: ToolContext, key: KeyCode, _modifiers: &ModifiersState) -> bool {
+        let Some(index) = PALETTE_KEYS
+            .iter()
+            .position(|palette_key| *palette_key == key)
+        else {
+            return false;
+        };
+        if self.choose(index) {
+            log::info!("placing atoms: {}", self.palette_line());
+        }
+        true
+    }
+
+    fn synchronize_buffers(&mut self, gpu_resources: &GlobalRenderResources) {
+        if !self.buffer_stale {
+            return;
+        }
+        self.buffer_stale = false;
+        self.buffer = self.preview.as_ref().map(|_| {
+            AtomBuffer::new(
+                gpu_resources,
+                [AtomRepr {
+                    pos: Vec3::zero(),
+                    kind: AtomKind::new(self.element),
+                    property: 0.0,
+                }],
+            )
+        });
+    }
+
+    fn render_overlay(&self) -> Vec<OverlayDraw<'_>> {
+        let (Some(preview), Some(buffer)) = (&self.preview, &self.buffer) else {
+            return Vec::new();
+        };
+        vec![(
+            buffer,
+            Mat4::from_translation(preview.position),
+            Some(PREVIEW_TINT),
+        )]
+    }
+}
+
+// End of File
@@ -0,0 +1,85 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Building peptides and nucleic acids from a typed sequence, as new
+//! molecules.  While the panel is open it receives all typing: letters are
+//! added to the sequence, and the kind of polymer is chosen from a short list.
+//! The molecule is relaxed once built and put where the camera is looking.
+
+use molecule::{edit::Edit, polymer::Polymer, residues::ResidueKind, MoleculeEditor};
+
+pub struct PolymerPanel {
+    open: bool,
+    polymer: Polymer,
+}
+
+impl Default for PolymerPanel {
+    fn default() -> Self {
+        Self {
+            open: false,
+            polymer: Polymer::new(ResidueKind::AminoAcid, ""),
+        }
+    }
+}
+
+impl PolymerPanel {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+    }
+
+    /// Closes the panel, discarding the sequence.  The kind of polymer is
+    /// kept for next time.
+    pub fn close(&mut self) {
+        self.open = false;
+        self.polymer.sequence.clear();
+    }
+
+    pub fn polymer(&self) -> &Polymer {
+        &self.polymer
+    }
+
+    /// Appends typed text to the sequence.  Anything but letters is ignored.
+    pub fn push_str(&mut self, text: &str) {
+        self.polymer.sequence.extend(
+            text.chars()
+                .filter(char::is_ascii_alphabetic)
+                .map(|letter| letter.to_ascii_uppercase()),
+        );
+    }
+
+    /// Removes the last letter of the sequence.
+    pub fn backspace(&mut self) {
+        self.polymer.sequence.pop();
+    }
+
+    /// Moves on to the next kind of polymer: a helical or an extended
+    /// peptide, DNA as a double helix or a single strand, or RNA.
+    pub fn cycle_kind(&mut self) {
+        let polymer = &mut self.polymer;
+        (polymer.kind, polymer.helix, polymer.duplex) = match (polymer.kind, polymer.helix) {
+            (ResidueKind::AminoAcid, true) => (ResidueKind::AminoAcid, false, false),
+            (ResidueKind::AminoAcid, false) => (ResidueKind::Deoxyribonucleotide, true, true),
+            (ResidueKind::Deoxyribonucleotide, _) if polymer.duplex => {
+                (ResidueKind::Deoxyribonucleotide, true, false)
+            }
+            (ResidueKind::Deoxyribonucleotide, _) => (ResidueKind::Ribonucleotide, true, false),
+            (ResidueKind::Ribonucleotide, _) => (ResidueKind::AminoAcid, true, false),
+        };
+    }
+
+    /// A new molecule built from the sequence, or the first letter of the
+    /// sequence that is not a residue of the chosen kind.
+    pub fn build(&self) -> Result<MoleculeEditor, char> {
+        self.polymer.residues()?;
+        Ok(MoleculeEditor::from_feature(Edit::Polymer(
+            self.polymer.clone(),
+        )))
+    }
+}
+
+// End of File
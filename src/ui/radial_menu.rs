@@ -0,0 +1,304 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A ring of the most used commands that opens around a finger or pen pressed
+//! and held on the view, so that tablet users need not reach for the menubar.
+//! Sliding towards an item highlights it, and lifting chooses it; lifting in
+//! the middle of the ring chooses nothing.  A press that moves before the
+//! menu opens is a drag, and is left to the camera.
+
+use std::time::Duration;
+
+use instant::Instant;
+use winit::dpi::PhysicalPosition;
+
+/// How long a press is held before the menu opens.
+pub const HOLD_DURATION: Duration = Duration::from_millis(500);
+
+/// How far a press can wander, in logical pixels, and still count as held
+/// rather than dragged.
+const HOLD_SLOP: f64 = 12.0;
+
+/// How far from the middle of the ring, in logical pixels, the pointer has to
+/// be for an item to be highlighted.
+const DEAD_ZONE: f64 = 24.0;
+
+/// How far from the middle of the ring the items are drawn, in logical pixels.
+pub const RING_RADIUS: f64 = 80.0;
+
+/// How far the ring reaches from its middle, in logical pixels.
+const RING_OUTER_RADIUS: f64 = 128.0;
+
+/// The commands on the ring.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RadialItem {
+    PlaceAtom,
+    Bond,
+    Delete,
+    Measure,
+    Undo,
+}
+
+impl RadialItem {
+    /// Every item, clockwise from the top of the ring.
+    pub const ALL: [RadialItem; 5] = [
+        RadialItem::PlaceAtom,
+        RadialItem::Bond,
+        RadialItem::Delete,
+        RadialItem::Measure,
+        RadialItem::Undo,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RadialItem::PlaceAtom => "Add atom",
+            RadialItem::Bond => "Bond",
+            RadialItem::Delete => "Delete",
+            RadialItem::Measure => "Measure",
+            RadialItem::Undo => "Undo",
+        }
+    }
+}
+
+/// An item as it is drawn on the ring.
+#[derive(Clone, Debug)]
+pub struct RadialButton {
+    pub item: RadialItem,
+    pub label: &'static str,
+    /// Where the middle of the item is, in physical pixels.
+    pub position: PhysicalPosition<f64>,
+    /// Where the item's slice of the ring starts and ends, in radians
+    /// clockwise from the top.
+    pub angles: (f64, f64),
+    pub highlighted: bool,
+}
+
+/// The open menu as it is drawn: a ring around where it was pressed, cut into
+/// a slice for each item.  Distances are in physical pixels.
+#[derive(Clone, Debug)]
+pub struct RadialRing {
+    pub center: PhysicalPosition<f64>,
+    /// Inside this, lifting chooses nothing.
+    pub inner_radius: f64,
+    pub outer_radius: f64,
+    pub buttons: Vec<RadialButton>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+enum State {
+    #[default]
+    Idle,
+    /// A finger or pen is down, but has not yet been held long enough.
+    Pressed {
+        id: u64,
+        at: PhysicalPosition<f64>,
+        since: Instant,
+        /// The scale factor of the window pressed in, from logical to
+        /// physical pixels.
+        scale_factor: f64,
+    },
+    Open {
+        id: u64,
+        center: PhysicalPosition<f64>,
+        highlighted: Option<RadialItem>,
+        scale_factor: f64,
+    },
+}
+
+#[derive(Default)]
+pub struct RadialMenu {
+    state: State,
+}
+
+impl RadialMenu {
+    pub fn is_open(&self) -> bool {
+        matches!(self.state, State::Open { .. })
+    }
+
+    /// The item that lifting the pointer would choose.
+    pub fn highlighted(&self) -> Option<RadialItem> {
+        match self.state {
+            State::Open { highlighted, .. } => highlighted,
+            _ => None,
+        }
+    }
+
+    /// The items of the open menu, as they are drawn.
+    pub fn buttons(&self) -> Vec<RadialButton> {
+        let State::Open {
+            center,
+            highlighted,
+            scale_factor,
+            ..
+        } = self.state
+        else {
+            return Vec::new();
+        };
+        let radius = RING_RADIUS * scale_factor;
+        let slice = std::f64::consts::TAU / RadialItem::ALL.len() as f64;
+        RadialItem::ALL
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                let angle = item_angle(index);
+                RadialButton {
+                    item: *item,
+                    label: item.label(),
+                    position: PhysicalPosition::new(
+                        center.x + radius * angle.sin(),
+                        center.y - radius * angle.cos(),
+                    ),
+                    angles: (angle - slice / 2.0, angle + slice / 2.0),
+                    highlighted: highlighted == Some(*item),
+                }
+            })
+            .collect()
+    }
+
+    /// The open menu, as it is drawn.
+    pub fn ring(&self) -> Option<RadialRing> {
+        let State::Open {
+            center,
+            scale_factor,
+            ..
+        } = self.state
+        else {
+            return None;
+        };
+        Some(RadialRing {
+            center,
+            inner_radius: DEAD_ZONE * scale_factor,
+            outer_radius: RING_OUTER_RADIUS * scale_factor,
+            buttons: self.buttons(),
+        })
+    }
+
+    /// Starts timing a press of the finger or pen `id` at `position`, in a
+    /// window with the scale factor `scale_factor`.  Only one press is
+    /// followed at a time.
+    pub fn press(&mut self, id: u64, position: PhysicalPosition<f64>, scale_factor: f64) {
+        self.state = match self.state {
+            State::Idle => State::Pressed {
+                id,
+                at: position,
+                since: Instant::now(),
+                scale_factor,
+            },
+            // A second finger makes a gesture, not a press.
+            _ => State::Idle,
+        };
+    }
+
+    /// How long until a press that is still held opens the menu.
+    pub fn time_to_open(&self) -> Option<Duration> {
+        match self.state {
+            State::Pressed { since, .. } => Some(HOLD_DURATION.saturating_sub(since.elapsed())),
+            _ => None,
+        }
+    }
+
+    /// Opens the menu if the press has been held long enough.  Returns whether
+    /// it just opened.
+    pub fn update(&mut self) -> bool {
+        match self.state {
+            State::Pressed {
+                id,
+                at,
+                since,
+                scale_factor,
+            } if since.elapsed() >= HOLD_DURATION => {
+                self.state = State::Open {
+                    id,
+                    center: at,
+                    highlighted: None,
+                    scale_factor,
+                };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Follows the pointer `id` to `position`, highlighting the item it points
+    /// towards.  A press that wanders too far before the menu opens is
+    /// forgotten.  Returns whether the highlighted item changed.
+    pub fn move_to(&mut self, id: u64, position: PhysicalPosition<f64>) -> bool {
+        match &mut self.state {
+            State::Pressed {
+                id: pressed,
+                at,
+                scale_factor,
+                ..
+            } if *pressed == id => {
+                if distance(*at, position) > HOLD_SLOP * *scale_factor {
+                    self.state = State::Idle;
+                }
+                false
+            }
+            State::Open {
+                id: pressed,
+                center,
+                highlighted,
+                scale_factor,
+            } if *pressed == id => {
+                let item = item_toward(*center, position, *scale_factor);
+                let changed = item != *highlighted;
+                *highlighted = item;
+                changed
+            }
+            _ => false,
+        }
+    }
+
+    /// Ends the press of the pointer `id`, closing the menu.  Returns the item
+    /// chosen, if the menu was open and an item was highlighted.
+    pub fn release(&mut self, id: u64) -> Option<RadialItem> {
+        let chosen = match self.state {
+            State::Pressed { id: pressed, .. } | State::Open { id: pressed, .. }
+                if pressed != id =>
+            {
+                return None;
+            }
+            State::Open { highlighted, .. } => highlighted,
+            _ => None,
+        };
+        self.state = State::Idle;
+        chosen
+    }
+
+    /// Closes the menu without choosing anything.
+    pub fn cancel(&mut self) {
+        self.state = State::Idle;
+    }
+}
+
+// The angle of the `index`th item, clockwise from the top of the ring.
+fn item_angle(index: usize) -> f64 {
+    std::f64::consts::TAU * index as f64 / RadialItem::ALL.len() as f64
+}
+
+// The item whose slice of the ring `position` lies in, unless it is too close
+// to the middle to tell.
+fn item_toward(
+    center: PhysicalPosition<f64>,
+    position: PhysicalPosition<f64>,
+    scale_factor: f64,
+) -> Option<RadialItem> {
+    if distance(center, position) < DEAD_ZONE * scale_factor {
+        return None;
+    }
+    // Window coordinates run downwards, so up the screen is -y.
+    let angle = (position.x - center.x)
+        .atan2(center.y - position.y)
+        .rem_euclid(std::f64::consts::TAU);
+    let slice = std::f64::consts::TAU / RadialItem::ALL.len() as f64;
+    let index = ((angle + slice / 2.0) / slice) as usize % RadialItem::ALL.len();
+    Some(RadialItem::ALL[index])
+}
+
+fn distance(a: PhysicalPosition<f64>, b: PhysicalPosition<f64>) -> f64 {
+    (a.x - b.x).hypot(a.y - b.y)
+}
+
+// End of File
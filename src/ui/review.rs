@@ -0,0 +1,113 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Review mode, in which a shared design can be looked around, measured and
+//! commented on, but not changed.
+//!
+//! Comments are kept apart from the design, so that a reviewer can send them
+//! back without touching the file they were given.
+
+use common::ids::AtomSpecifier;
+use serde::{Deserialize, Serialize};
+
+/// A reviewer's note on a molecule, or on one atom of it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Comment {
+    /// The name of the molecule commented on.  Component IDs are not stable
+    /// between sessions, so they cannot be saved.
+    pub molecule: String,
+    pub atom: Option<AtomSpecifier>,
+    pub text: String,
+}
+
+#[derive(Default)]
+pub struct ReviewMode {
+    active: bool,
+    comments: Vec<Comment>,
+    /// The comment being typed, if any.
+    draft: Option<Comment>,
+}
+
+impl ReviewMode {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    /// Whether commands that change the design may run.  They are refused
+    /// while reviewing; looking around and commenting are always allowed.
+    pub fn allows_editing(&self) -> bool {
+        !self.active
+    }
+
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    pub fn remove_comment(&mut self, index: usize) {
+        if index < self.comments.len() {
+            self.comments.remove(index);
+        }
+    }
+
+    /// Replaces the comments with those saved by `comments_json`.
+    pub fn load_comments(&mut self, json: &str) -> serde_json::Result<()> {
+        self.comments = serde_json::from_str(json)?;
+        Ok(())
+    }
+
+    pub fn comments_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&self.comments)
+    }
+
+    pub fn is_drafting(&self) -> bool {
+        self.draft.is_some()
+    }
+
+    /// Starts typing a comment on `molecule`, or on one of its atoms.
+    pub fn begin_comment(&mut self, molecule: impl Into<String>, atom: Option<AtomSpecifier>) {
+        self.draft = Some(Comment {
+            molecule: molecule.into(),
+            atom,
+            text: String::new(),
+        });
+    }
+
+    pub fn draft(&self) -> Option<&Comment> {
+        self.draft.as_ref()
+    }
+
+    /// Appends typed text to the comment being written.
+    pub fn push_str(&mut self, text: &str) {
+        if let Some(draft) = &mut self.draft {
+            draft.text.push_str(text);
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        if let Some(draft) = &mut self.draft {
+            draft.text.pop();
+        }
+    }
+
+    /// Adds the comment being written to the list.  Empty comments are
+    /// dropped.  Returns the comment if one was added.
+    pub fn commit(&mut self) -> Option<&Comment> {
+        let draft = self.draft.take()?;
+        if draft.text.trim().is_empty() {
+            return None;
+        }
+        self.comments.push(draft);
+        self.comments.last()
+    }
+
+    pub fn cancel(&mut self) {
+        self.draft = None;
+    }
+}
+
+// End of File
@@ -0,0 +1,227 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Finding atoms and edits by typing.  A query is matched against:
+//!
+//! * atom specifiers, in the notation of `AtomSpecifier`'s `Display` impl
+//!   (e.g. `3` or `1.0/3.1:2`),
+//! * element symbols and names (e.g. `Si` or `silicon`), and
+//! * edit names, by case-insensitive substring.
+
+use common::ids::{AtomSpecifier, EditId};
+use molecule::MoleculeEditor;
+use periodic_table::Element;
+use render::RenderCamera;
+use scene::{Assembly, ComponentId};
+use ultraviolet::{Mat4, Vec3};
+
+/// The most results that will be listed for a single query.
+const MAX_HITS: usize = 100;
+
+/// The radius of the region framed around a single atom, in angstroms.  This is
+/// large enough to show the atom's immediate neighbors for context.
+const ATOM_FRAME_RADIUS: f32 = 8.0;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SearchTarget {
+    Atom(AtomSpecifier),
+    Edit(EditId),
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchHit {
+    /// The component holding the molecule that the hit belongs to.
+    pub component: ComponentId,
+    pub target: SearchTarget,
+    pub label: String,
+    /// The world space center of the hit, if it currently has any atoms.
+    pub focus: Option<Vec3>,
+    /// The radius of the region to frame around `focus`.
+    pub radius: f32,
+}
+
+/// The search field and its result list.
+#[derive(Default)]
+pub struct SearchBox {
+    open: bool,
+    query: String,
+    hits: Vec<SearchHit>,
+    highlighted: usize,
+}
+
+impl SearchBox {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+    }
+
+    /// Closes the search box, discarding the query and its results.
+    pub fn close(&mut self) {
+        self.open = false;
+        self.query.clear();
+        self.hits.clear();
+        self.highlighted = 0;
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn set_query(&mut self, query: impl Into<String>, assembly: &Assembly) {
+        self.query = query.into();
+        self.hits = search(&self.query, assembly);
+        self.highlighted = 0;
+    }
+
+    /// Appends typed text to the query.
+    pub fn push_str(&mut self, text: &str, assembly: &Assembly) {
+        let query = format!("{}{}", self.query, text);
+        self.set_query(query, assembly);
+    }
+
+    /// Removes the last character of the query.
+    pub fn backspace(&mut self, assembly: &Assembly) {
+        let mut query = std::mem::take(&mut self.query);
+        query.pop();
+        self.set_query(query, assembly);
+    }
+
+    pub fn hits(&self) -> &[SearchHit] {
+        &self.hits
+    }
+
+    pub fn highlighted(&self) -> Option<&SearchHit> {
+        self.hits.get(self.highlighted)
+    }
+
+    /// Moves the highlight up (negative `delta`) or down the result list,
+    /// stopping at either end.
+    pub fn move_highlight(&mut self, delta: isize) {
+        if !self.hits.is_empty() {
+            self.highlighted = self
+                .highlighted
+                .saturating_add_signed(delta)
+                .min(self.hits.len() - 1);
+        }
+    }
+
+    /// Chooses the highlighted hit: frames the camera on it and closes the
+    /// search box.  Returns the chosen hit, if there was one.
+    pub fn choose(&mut self, camera: &mut RenderCamera) -> Option<SearchHit> {
+        let hit = self.highlighted().cloned()?;
+        if let Some(focus) = hit.focus {
+            camera.frame(focus, hit.radius);
+        }
+        self.close();
+        Some(hit)
+    }
+}
+
+fn element_from_name(name: &str) -> Option<Element> {
+    (Element::MIN as u8..=Element::MAX as u8)
+        .filter_map(Element::from_atomic_number)
+        .find(|element| format!("{:?}", element).eq_ignore_ascii_case(name))
+}
+
+/// Returns the center and radius of the sphere around the atoms created by
+/// `edit_id`, in the molecule's local space.
+fn edit_bounds(molecule: &MoleculeEditor, edit_id: EditId) -> Option<(Vec3, f32)> {
+    let positions: Vec<Vec3> = molecule
+        .repr
+        .graph
+        .node_indices()
+        .filter(|index| molecule.repr.spec(*index).edit_id() == edit_id)
+        .map(|index| molecule.repr.dense_positions()[index.index()])
+        .collect();
+    if positions.is_empty() {
+        return None;
+    }
+
+    let center =
+        positions.iter().fold(Vec3::zero(), |acc, pos| acc + *pos) / positions.len() as f32;
+    let radius = positions
+        .iter()
+        .map(|pos| (*pos - center).mag())
+        .fold(0.0, f32::max);
+
+    Some((center, radius + ATOM_FRAME_RADIUS / 2.0))
+}
+
+fn search(query: &str, assembly: &Assembly) -> Vec<SearchHit> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let spec = query.parse::<AtomSpecifier>().ok();
+    let element = Element::from_symbol(query).or_else(|| element_from_name(query));
+    let needle = query.to_lowercase();
+
+    let mut hits = Vec::new();
+    for component in assembly.components() {
+        let Some(molecule) = component.molecule() else {
+            continue;
+        };
+        let transform = assembly
+            .world_transform(component.id())
+            .unwrap_or_else(Mat4::identity);
+
+        if spec.is_some() || element.is_some() {
+            for index in molecule.repr.graph.node_indices() {
+                let atom = &molecule.repr.graph[index];
+                let atom_spec = molecule.repr.spec(index);
+                if Some(&atom_spec) != spec.as_ref() && Some(atom.element) != element {
+                    continue;
+                }
+
+                hits.push(SearchHit {
+                    component: component.id(),
+                    label: format!(
+                        "{}: {} {}",
+                        molecule.name(),
+                        atom.element.symbol(),
+                        atom_spec
+                    ),
+                    target: SearchTarget::Atom(atom_spec),
+                    focus: Some(
+                        transform.transform_point3(molecule.repr.dense_positions()[index.index()]),
+                    ),
+                    radius: ATOM_FRAME_RADIUS,
+                });
+                if hits.len() == MAX_HITS {
+                    return hits;
+                }
+            }
+        }
+
+        for (index, &edit_id) in molecule.edits().order().iter().enumerate() {
+            let Some(edit) = molecule.edits().get(&edit_id) else {
+                continue;
+            };
+            let name = edit.name();
+            if !name.to_lowercase().contains(&needle) {
+                continue;
+            }
+
+            let bounds = edit_bounds(molecule, edit_id);
+            hits.push(SearchHit {
+                component: component.id(),
+                target: SearchTarget::Edit(edit_id),
+                label: format!("{}: #{} {}", molecule.name(), index + 1, name),
+                focus: bounds.map(|(center, _)| transform.transform_point3(center)),
+                radius: bounds.map_or(ATOM_FRAME_RADIUS, |(_, radius)| radius),
+            });
+            if hits.len() == MAX_HITS {
+                return hits;
+            }
+        }
+    }
+
+    hits
+}
+
+// End of File
@@ -0,0 +1,201 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The atoms selected for the next command, all of which belong to one
+//! molecule.
+
+use std::collections::HashMap;
+
+use common::ids::AtomSpecifier;
+use molecule::{
+    dynamics::{self, ShakeOptions, ShakeReport},
+    selection::{self, AtomSelection},
+    MoleculeEditor,
+};
+use scene::{Assembly, ComponentId};
+
+/// How far from the selection `select_within` reaches by default, in
+/// angstroms: roughly the first shell of solvent around a site.
+pub const DEFAULT_PROXIMITY: f32 = 5.0;
+
+#[derive(Default)]
+pub struct Selection {
+    /// The molecule the selected atoms belong to.
+    component: Option<ComponentId>,
+    atoms: AtomSelection,
+}
+
+impl Selection {
+    pub fn component(&self) -> Option<ComponentId> {
+        self.component
+    }
+
+    pub fn atoms(&self) -> &AtomSelection {
+        &self.atoms
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.atoms.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.component = None;
+        self.atoms.clear();
+    }
+
+    /// A line describing the selection, e.g. "3 atoms of ComponentId(2)
+    /// selected".
+    pub fn summary(&self) -> String {
+        match self.component {
+            Some(component) if !self.is_empty() => {
+                format!("{} atoms of {:?} selected", self.atoms.len(), component)
+            }
+            _ => "nothing selected".to_owned(),
+        }
+    }
+
+    /// Selects `atoms` of the molecule `component`, instead of what was
+    /// selected.
+    pub fn select(&mut self, component: ComponentId, atoms: AtomSelection) {
+        self.component = Some(component);
+        self.atoms = atoms;
+    }
+
+    /// Adds an atom to the selection, or removes it if it is already selected.
+    /// Selecting an atom of another molecule starts a new selection.
+    pub fn toggle(&mut self, component: ComponentId, atom: AtomSpecifier) {
+        if self.component != Some(component) {
+            self.clear();
+            self.component = Some(component);
+        }
+        if !self.atoms.remove(&atom) {
+            self.atoms.insert(atom);
+        }
+    }
+
+    /// Follows the selected atoms of the molecule `component` to their new
+    /// specifiers, after its atoms were renumbered.
+    pub fn remap(
+        &mut self,
+        component: ComponentId,
+        mapping: &HashMap<AtomSpecifier, AtomSpecifier>,
+    ) {
+        if self.component != Some(component) {
+            return;
+        }
+        self.atoms = self
+            .atoms
+            .drain()
+            .map(|atom| mapping.get(&atom).cloned().unwrap_or(atom))
+            .collect();
+    }
+
+    /// Extends the selection across `shells` bonds.
+    pub fn grow(&mut self, assembly: &Assembly, shells: usize) {
+        match self.molecule(assembly) {
+            Some(molecule) => self.atoms = selection::grow(&molecule.repr, &self.atoms, shells),
+            None => self.clear(),
+        }
+    }
+
+    /// Takes the outermost `shells` layers of atoms off the selection.
+    pub fn shrink(&mut self, assembly: &Assembly, shells: usize) {
+        match self.molecule(assembly) {
+            Some(molecule) => self.atoms = selection::shrink(&molecule.repr, &self.atoms, shells),
+            None => self.clear(),
+        }
+    }
+
+    /// Selects the whole of every fragment that an atom is selected in.
+    pub fn select_connected(&mut self, assembly: &Assembly) {
+        match self.molecule(assembly) {
+            Some(molecule) => self.atoms = selection::connected(&molecule.repr, &self.atoms),
+            None => self.clear(),
+        }
+    }
+
+    /// Selects the atoms of the molecule that are not selected, and deselects
+    /// those that are.
+    pub fn invert(&mut self, assembly: &Assembly) {
+        match self.molecule(assembly) {
+            Some(molecule) => self.atoms = selection::invert(&molecule.repr, &self.atoms),
+            None => self.clear(),
+        }
+    }
+
+    /// Extends the selection to every atom of the molecule within `distance`
+    /// angstroms of it.
+    pub fn select_within(&mut self, assembly: &Assembly, distance: f32) {
+        match self.molecule(assembly) {
+            Some(molecule) => {
+                self.atoms = selection::within_distance(&molecule.repr, &self.atoms, distance)
+            }
+            None => self.clear(),
+        }
+    }
+
+    /// Saves the selection with its molecule under the first unused name of
+    /// the form "Selection N", and returns the name.  Returns `None` if
+    /// nothing is selected or the molecule is locked.
+    pub fn save_named(&self, assembly: &mut Assembly) -> Option<String> {
+        if self.atoms.is_empty() {
+            return None;
+        }
+        let molecule = assembly.component_mut(self.component?)?.molecule_mut()?;
+        let count = molecule.named_selections().len();
+        let name = (count + 1..)
+            .map(|n| format!("Selection {}", n))
+            .find(|name| molecule.named_selection(name).is_none())
+            .expect("there is always an unused name");
+        molecule.save_named_selection(name.clone(), self.atoms.iter().cloned());
+        Some(name)
+    }
+
+    /// Selects the named selection saved with the selected molecule after
+    /// the one that is selected now, going back to the first after the last.
+    /// With nothing selected, the named selections of `fallback` are used.
+    /// Returns the name of the selection, or `None` if there are none.
+    pub fn cycle_named(
+        &mut self,
+        assembly: &Assembly,
+        fallback: Option<ComponentId>,
+    ) -> Option<String> {
+        let component = self.component.filter(|_| !self.is_empty()).or(fallback)?;
+        let molecule = assembly.component(component)?.molecule()?;
+        let saved = molecule.named_selections();
+        let current = saved.iter().position(|named| {
+            self.component == Some(component) && named.select(&molecule.repr) == self.atoms
+        });
+        let next = saved.get(current.map_or(0, |index| (index + 1) % saved.len()))?;
+        self.component = Some(component);
+        self.atoms = next.select(&molecule.repr);
+        Some(next.name.clone())
+    }
+
+    /// Shakes the selected atoms and relaxes the molecule again several times,
+    /// to see whether it returns to the same geometry.  The molecule is not
+    /// changed.
+    pub fn shake(&self, assembly: &Assembly, options: &ShakeOptions) -> Option<ShakeReport> {
+        if self.atoms.is_empty() {
+            return None;
+        }
+        let molecule = self.molecule(assembly)?;
+        Some(dynamics::shake(
+            &molecule.repr,
+            &self.atoms,
+            molecule.force_field(),
+            molecule.vdw_cutoff(),
+            molecule.convergence(),
+            options,
+        ))
+    }
+
+    // The molecule the selection belongs to.  If it no longer exists, the
+    // commands clear the selection.
+    fn molecule<'a>(&self, assembly: &'a Assembly) -> Option<&'a MoleculeEditor> {
+        assembly.component(self.component?)?.molecule()
+    }
+}
+
+// End of File
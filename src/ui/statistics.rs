@@ -0,0 +1,186 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A live summary of the size of the scene and the cost of working with it.
+
+use instant::Instant;
+use render::{GpuMemoryUsage, Renderer, SceneLoad};
+use scene::Assembly;
+use std::time::Duration;
+
+/// How often the panel recomputes its statistics.  Walking the scene is cheap
+/// but not free, so there is no point doing it more often than it can be read.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A snapshot of the statistics of a scene.
+#[derive(Clone, Debug, Default)]
+pub struct SceneStatistics {
+    pub components: usize,
+    pub molecules: usize,
+    pub atoms: usize,
+    pub bonds: usize,
+    pub edits: usize,
+    pub checkpoints: usize,
+    pub gpu_memory: GpuMemoryUsage,
+    /// The draw calls that the last frame was drawn with.
+    pub draw_calls: usize,
+    /// How the last frame compared with the limits on atoms drawn.
+    pub scene_load: SceneLoad,
+    /// The molecules of the last frame culled for being drawn too small.
+    pub culled: usize,
+    /// The slowest relaxation among the molecules' most recent rebuilds.
+    pub last_relax: Option<Duration>,
+    /// The slowest of the molecules' most recent rebuilds from their edits.
+    pub last_reconstruction: Option<Duration>,
+    /// The molecules whose most recent relaxation stopped before converging.
+    pub unconverged_relaxations: usize,
+}
+
+impl SceneStatistics {
+    pub fn collect(assembly: &Assembly, renderer: &Renderer) -> Self {
+        let mut stats = Self {
+            components: assembly.components().count(),
+            ..Default::default()
+        };
+
+        for molecule in assembly.components().filter_map(|c| c.molecule()) {
+            stats.molecules += 1;
+            stats.atoms += molecule.repr.graph.node_count();
+            stats.bonds += molecule.repr.graph.edge_count();
+            stats.edits += molecule.edits().len();
+            stats.checkpoints += molecule.checkpoint_count();
+
+            let timings = molecule.timings();
+            stats.last_relax = stats.last_relax.max(timings.last_relax);
+            stats.last_reconstruction = stats.last_reconstruction.max(timings.last_reconstruction);
+            if molecule
+                .relax_report()
+                .is_some_and(|report| !report.status.is_converged())
+            {
+                stats.unconverged_relaxations += 1;
+            }
+        }
+
+        let atoms = assembly
+            .components()
+            .filter_map(|c| c.molecule())
+            .filter_map(|molecule| molecule.repr.atoms());
+        stats.gpu_memory = renderer.gpu_memory(atoms);
+        stats.draw_calls = renderer.draw_calls();
+        stats.scene_load = renderer.scene_load();
+        stats.culled = renderer.culled_draws();
+
+        stats
+    }
+
+    /// Formats the statistics as lines of `label: value` text.
+    pub fn lines(&self) -> Vec<String> {
+        let duration = |d: Option<Duration>| match d {
+            Some(d) => format!("{:.2} ms", d.as_secs_f64() * 1000.0),
+            None => "-".to_owned(),
+        };
+
+        vec![
+            format!("Components: {}", self.components),
+            format!("Molecules: {}", self.molecules),
+            format!("Atoms: {}", self.atoms),
+            format!("Bonds: {}", self.bonds),
+            format!("Edits: {}", self.edits),
+            format!("Checkpoints: {}", self.checkpoints),
+            format!("GPU atoms: {}", format_bytes(self.gpu_memory.atoms)),
+            format!(
+                "GPU transforms: {}",
+                format_bytes(self.gpu_memory.transforms)
+            ),
+            format!(
+                "GPU render targets: {}",
+                format_bytes(self.gpu_memory.render_targets)
+            ),
+            format!("GPU uniforms: {}", format_bytes(self.gpu_memory.uniforms)),
+            format!("GPU total: {}", format_bytes(self.gpu_memory.total())),
+            format!("Draw calls: {}", self.draw_calls),
+            format!("Scene: {}", self.scene_load),
+            format!("Culled molecules: {}", self.culled),
+            format!("Last relax: {}", duration(self.last_relax)),
+            format!(
+                "Last reconstruction: {}",
+                duration(self.last_reconstruction)
+            ),
+            format!("Unconverged relaxations: {}", self.unconverged_relaxations),
+        ]
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// The statistics panel.  While it is open, it keeps a snapshot of the scene's
+/// statistics that is refreshed every frame, at most every [`REFRESH_INTERVAL`].
+#[derive(Default)]
+pub struct StatisticsPanel {
+    open: bool,
+    stats: SceneStatistics,
+    last_refresh: Option<Instant>,
+}
+
+impl StatisticsPanel {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        // Make sure the first frame after opening shows current numbers.
+        self.last_refresh = None;
+    }
+
+    pub fn statistics(&self) -> &SceneStatistics {
+        &self.stats
+    }
+
+    /// Called once per frame.  Returns true if the statistics were recomputed.
+    pub fn refresh(&mut self, assembly: &Assembly, renderer: &Renderer) -> bool {
+        if !self.open {
+            return false;
+        }
+        if let Some(last_refresh) = self.last_refresh {
+            if last_refresh.elapsed() < REFRESH_INTERVAL {
+                return false;
+            }
+        }
+
+        self.stats = SceneStatistics::collect(assembly, renderer);
+        self.last_refresh = Some(Instant::now());
+        true
+    }
+
+    /// How long until the statistics are due to be recomputed, so that the
+    /// panel can be kept current while nothing else happens.  `None` while
+    /// it is closed.
+    pub fn time_until_refresh(&self) -> Option<Duration> {
+        if !self.open {
+            return None;
+        }
+        Some(match self.last_refresh {
+            Some(last_refresh) => REFRESH_INTERVAL.saturating_sub(last_refresh.elapsed()),
+            None => Duration::ZERO,
+        })
+    }
+}
+
+// End of File
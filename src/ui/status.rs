@@ -0,0 +1,103 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The lines along the bottom of the window showing what the application last
+//! reported.  Reports are made through the log as usual, and the logger keeps
+//! the last of them for the status lines.
+
+use instant::Instant;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{sync::Mutex, time::Duration};
+
+/// How long a report stays on the status lines.
+pub const STATUS_DURATION: Duration = Duration::from_secs(6);
+
+// Messages logged this close together are lines of the same report, such as
+// the rows of a list.
+const REPORT_GAP: Duration = Duration::from_millis(100);
+
+// The most lines of a report that are shown, from its end.
+const MAX_LINES: usize = 12;
+
+/// A line of a report shown on the status lines.
+#[derive(Clone, Debug)]
+pub struct StatusMessage {
+    pub text: String,
+    /// Whether it warns that something did not work.
+    pub warning: bool,
+    reported: Instant,
+}
+
+static REPORT: Mutex<Vec<StatusMessage>> = Mutex::new(Vec::new());
+
+/// The lines of the last report, unless it has been shown long enough.
+pub fn current() -> Vec<StatusMessage> {
+    let Ok(report) = REPORT.lock() else {
+        return Vec::new();
+    };
+    match report.last() {
+        Some(last) if last.reported.elapsed() < STATUS_DURATION => report.clone(),
+        _ => Vec::new(),
+    }
+}
+
+/// How long until the report shown is cleared, if there is one.
+pub fn time_until_cleared() -> Option<Duration> {
+    let last = current().pop()?;
+    Some(STATUS_DURATION.saturating_sub(last.reported.elapsed()))
+}
+
+/// Makes `inner` the logger, keeping the application's reports for the status
+/// lines on the way.  `max_level` is the most detailed level that `inner` logs.
+pub fn init_logger(inner: impl Log + 'static, max_level: LevelFilter) {
+    let logger = Box::leak(Box::new(StatusLogger { inner }));
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max_level.max(LevelFilter::Info));
+    }
+}
+
+struct StatusLogger<L> {
+    inner: L,
+}
+
+impl<L: Log> Log for StatusLogger<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        is_report(metadata) || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if is_report(record.metadata()) {
+            if let Ok(mut report) = REPORT.lock() {
+                if report
+                    .last()
+                    .is_some_and(|last| last.reported.elapsed() > REPORT_GAP)
+                {
+                    report.clear();
+                }
+                report.push(StatusMessage {
+                    text: record.args().to_string(),
+                    warning: record.level() <= Level::Warn,
+                    reported: Instant::now(),
+                });
+                let excess = report.len().saturating_sub(MAX_LINES);
+                report.drain(..excess);
+            }
+        }
+        if self.inner.enabled(record.metadata()) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+// Whether a record is one of the application's own reports, rather than a
+// detail or something from a library.
+fn is_report(metadata: &Metadata) -> bool {
+    metadata.level() <= Level::Info && metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+}
+
+// End of File
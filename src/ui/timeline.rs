@@ -0,0 +1,157 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A scrubber along the bottom of the window for moving through a molecule's
+//! edit history.
+//!
+//! Positions along the timeline are given as fractions of its width, from 0.0
+//! (before the first edit) to 1.0 (after the last edit).
+
+use instant::Instant;
+use scene::{Assembly, ComponentId};
+use std::time::Duration;
+
+/// The height of the timeline strip, in logical pixels.
+pub const TIMELINE_HEIGHT: f64 = 48.0;
+
+/// The minimum time between two history changes while dragging.  Moving
+/// through the history can involve relaxing the molecule, so it is not
+/// possible to keep up with every mouse motion event.
+const THROTTLE: Duration = Duration::from_millis(100);
+
+/// A tick mark on the timeline.  There is one per edit, placed at the history
+/// step just after that edit is applied.
+#[derive(Clone, Debug)]
+pub struct TimelineTick {
+    pub step: usize,
+    pub position: f32,
+    pub label: String,
+    /// Whether a checkpoint is cached at this step, which makes it quick to
+    /// jump to.
+    pub checkpoint: bool,
+}
+
+#[derive(Default)]
+pub struct Timeline {
+    component: Option<ComponentId>,
+    dragging: bool,
+    pending_step: Option<usize>,
+    last_applied: Option<Instant>,
+}
+
+impl Timeline {
+    /// Shows the history of `component`, which should hold a molecule.
+    pub fn show(&mut self, component: ComponentId) {
+        if self.component != Some(component) {
+            self.component = Some(component);
+            self.dragging = false;
+            self.pending_step = None;
+        }
+    }
+
+    /// The component whose history is shown: the one last passed to `show`,
+    /// or the first molecule in the assembly if that no longer exists.
+    pub fn component(&self, assembly: &Assembly) -> Option<ComponentId> {
+        self.component
+            .filter(|id| assembly.component(*id).is_some())
+            .or_else(|| {
+                assembly
+                    .components()
+                    .find(|component| component.molecule().is_some())
+                    .map(|component| component.id())
+            })
+    }
+
+    pub fn ticks(&self, assembly: &Assembly) -> Vec<TimelineTick> {
+        let Some(molecule) = self
+            .component(assembly)
+            .and_then(|id| assembly.component(id)?.molecule())
+        else {
+            return Vec::new();
+        };
+
+        let edits = molecule.edits();
+        let checkpoints = molecule.checkpoint_steps();
+        edits
+            .order()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, id)| {
+                let step = index + 1;
+                Some(TimelineTick {
+                    step,
+                    position: step as f32 / edits.len() as f32,
+                    label: edits.get(id)?.name(),
+                    checkpoint: checkpoints.binary_search(&step).is_ok(),
+                })
+            })
+            .collect()
+    }
+
+    /// The position of the playhead, which follows the pointer while dragging.
+    pub fn playhead(&self, assembly: &Assembly) -> Option<f32> {
+        let molecule = assembly.component(self.component(assembly)?)?.molecule()?;
+        let step = self.pending_step.unwrap_or(molecule.history_step());
+        Some(step as f32 / molecule.edits().len().max(1) as f32)
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.dragging
+    }
+
+    pub fn begin_drag(&mut self, position: f32, assembly: &mut Assembly) {
+        self.dragging = true;
+        self.drag_to(position, assembly);
+    }
+
+    /// Moves the playhead to the step nearest `position`.  The molecule is only
+    /// updated if enough time has passed since the last update; otherwise the
+    /// step is remembered and applied by a later call to `update`.
+    pub fn drag_to(&mut self, position: f32, assembly: &mut Assembly) {
+        if !self.dragging {
+            return;
+        }
+        let Some(len) = self
+            .component(assembly)
+            .and_then(|id| assembly.component(id)?.molecule())
+            .map(|molecule| molecule.edits().len())
+        else {
+            return;
+        };
+
+        self.pending_step = Some((position.clamp(0.0, 1.0) * len as f32).round() as usize);
+        self.update(assembly);
+    }
+
+    /// Ends the drag, applying the step under the playhead immediately.
+    pub fn end_drag(&mut self, assembly: &mut Assembly) {
+        self.dragging = false;
+        self.last_applied = None;
+        self.update(assembly);
+    }
+
+    /// Applies the pending step, if there is one and the throttle allows it.
+    /// Called once per frame so that the final position of a drag is applied
+    /// even if the pointer stops moving.  Returns the step that was applied.
+    pub fn update(&mut self, assembly: &mut Assembly) -> Option<usize> {
+        if let Some(last_applied) = self.last_applied {
+            if last_applied.elapsed() < THROTTLE {
+                return None;
+            }
+        }
+
+        let step = self.pending_step?;
+        let molecule = assembly
+            .component_mut(self.component(assembly)?)?
+            .molecule_mut()?;
+        self.pending_step = None;
+        if step != molecule.history_step() {
+            molecule.set_history_step(step);
+            self.last_applied = Some(Instant::now());
+        }
+        Some(step)
+    }
+}
+
+// End of File
@@ -0,0 +1,410 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The toolbar, and the interactive tools that it switches between.
+//!
+//! One tool is active at a time, and it gets the first say over pointer and
+//! key input in the 3D view.  Whatever it leaves alone goes to the camera, so
+//! that the view can still be turned by dragging where a tool has nothing to
+//! do.  Rotating the view is itself a tool, which leaves everything alone, and
+//! is the one that is active when no other is.
+
+use common::ids::AtomSpecifier;
+use render::{AtomBuffer, GlobalRenderResources};
+use scene::{Assembly, ComponentId, ProjectHistory};
+use ultraviolet::{Mat4, Vec3};
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use super::{
+    bonding::BondTool, brush::BrushTool, cut::CutTool, measurements::MeasurementTool,
+    moieties::MoietyTool, placement::PlacementTool, selection::Selection,
+};
+
+/// Where the pointer is in the scene.
+pub struct Pointer {
+    /// The ray from the camera through the pointer, as an origin and a
+    /// direction, if the pointer is over the view.
+    pub ray: Option<(Vec3, Vec3)>,
+    /// The point that the camera looks at.
+    pub focus: Vec3,
+}
+
+impl Pointer {
+    /// The atom under the pointer, if any.
+    pub fn hit(&self, assembly: &Assembly) -> Option<(ComponentId, AtomSpecifier)> {
+        let (origin, direction) = self.ray?;
+        assembly.pick(origin, direction)
+    }
+
+    /// Every atom under the pointer, front to back.
+    pub fn hits(&self, assembly: &Assembly) -> Vec<(ComponentId, AtomSpecifier)> {
+        match self.ray {
+            Some((origin, direction)) => assembly.pick_all(origin, direction),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// What the tools act on.
+pub struct ToolContext<'a> {
+    pub assembly: &'a mut Assembly,
+    /// Where changes to the assembly's components are recorded, to be undone.
+    pub history: &'a mut ProjectHistory,
+    pub selection: &'a mut Selection,
+    pub pointer: &'a Pointer,
+}
+
+/// Atoms that a tool draws over the scene, e.g. a preview of what it would
+/// do, with their transform and tint.
+pub type OverlayDraw<'a> = (&'a AtomBuffer, Mat4, Option<Vec3>);
+
+/// An interactive tool.  The input methods return whether the tool handled
+/// the input; if not, it goes on to the camera.
+pub trait Tool {
+    /// Called when the tool is switched to or away from.
+    fn set_active(&mut self, active: bool);
+
+    /// A line telling the user how to use the tool, shown when it is switched
+    /// to.
+    fn hint(&self) -> Option<String> {
+        None
+    }
+
+    fn on_pointer_down(&mut self, _context: ToolContext) -> bool {
+        false
+    }
+
+    fn on_pointer_move(&mut self, _context: ToolContext) -> bool {
+        false
+    }
+
+    fn on_pointer_up(&mut self, _context: ToolContext) -> bool {
+        false
+    }
+
+    /// Called when the wheel is scrolled by `lines`, which is positive when
+    /// scrolling up and may be fractional, e.g. from a trackpad.
+    fn on_scroll(&mut self, _context: ToolContext, _lines: f32) -> bool {
+        false
+    }
+
+    /// Called when a key is released.
+    fn on_key(
+        &mut self,
+        _context: ToolContext,
+        _key: KeyCode,
+        _modifiers: &ModifiersState,
+    ) -> bool {
+        false
+    }
+
+    fn synchronize_buffers(&mut self, _gpu_resources: &GlobalRenderResources) {}
+
+    fn render_overlay(&self) -> Vec<OverlayDraw<'_>> {
+        Vec::new()
+    }
+}
+
+/// The tools on the toolbar.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ToolKind {
+    #[default]
+    RotateView,
+    Select,
+    PlaceAtom,
+    Moiety,
+    Brush,
+    Bond,
+    Measure,
+    Cut,
+}
+
+impl ToolKind {
+    /// Every tool, in the order they appear on the toolbar.
+    pub const ALL: [ToolKind; 8] = [
+        ToolKind::RotateView,
+        ToolKind::Select,
+        ToolKind::PlaceAtom,
+        ToolKind::Moiety,
+        ToolKind::Brush,
+        ToolKind::Bond,
+        ToolKind::Measure,
+        ToolKind::Cut,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ToolKind::RotateView => "Rotate view",
+            ToolKind::Select => "Select",
+            ToolKind::PlaceAtom => "Place atom",
+            ToolKind::Moiety => "Attach moiety",
+            ToolKind::Brush => "Element brush",
+            ToolKind::Bond => "Bond",
+            ToolKind::Measure => "Measure",
+            ToolKind::Cut => "Cut",
+        }
+    }
+
+    /// The key that switches to the tool, if it has one.
+    pub fn shortcut(self) -> Option<&'static str> {
+        match self {
+            ToolKind::RotateView | ToolKind::Select => None,
+            ToolKind::PlaceAtom => Some("Alt+H"),
+            ToolKind::Moiety => Some("Alt+Shift+Z"),
+            ToolKind::Brush => Some("Alt+Shift+W"),
+            ToolKind::Bond => Some("Alt+J"),
+            ToolKind::Measure => Some("Alt+R"),
+            ToolKind::Cut => Some("Alt+Shift+X"),
+        }
+    }
+
+    /// Whether the tool changes the design, so cannot be used while
+    /// reviewing.
+    pub fn edits(self) -> bool {
+        matches!(
+            self,
+            ToolKind::PlaceAtom
+                | ToolKind::Moiety
+                | ToolKind::Brush
+                | ToolKind::Bond
+                | ToolKind::Cut
+        )
+    }
+}
+
+/// A button of the toolbar.
+#[derive(Clone, Debug)]
+pub struct ToolbarButton {
+    pub kind: ToolKind,
+    pub label: &'static str,
+    pub shortcut: Option<&'static str>,
+    pub active: bool,
+}
+
+/// Leaves all input to the camera.
+#[derive(Default)]
+pub struct RotateViewTool;
+
+impl Tool for RotateViewTool {
+    fn set_active(&mut self, _active: bool) {}
+}
+
+/// Clicking an atom adds it to the selection, or takes it out.  Where the
+/// click passes through several atoms, the front one is chosen, and pressing
+/// C (or scrolling while still holding the button) chooses the one behind it
+/// instead, going back to the front after the last.
+#[derive(Default)]
+pub struct SelectTool {
+    /// The atoms under the last click, front to back.
+    candidates: Vec<(ComponentId, AtomSpecifier)>,
+    /// Which of the candidates the click chose.
+    chosen: usize,
+    /// Whether the button is still held since the click.
+    holding: bool,
+    /// Lines scrolled while holding that have not yet moved on a candidate.
+    scrolled: f32,
+}
+
+impl SelectTool {
+    // Chooses the candidate `steps` on from the one chosen, behind it if
+    // positive, by taking back the last choice and making the new one.
+    fn cycle(&mut self, selection: &mut Selection, steps: isize) -> bool {
+        let count = self.candidates.len();
+        if count < 2 {
+            return false;
+        }
+        let (component, atom) = self.candidates[self.chosen].clone();
+        selection.toggle(component, atom);
+        self.chosen = (self.chosen as isize + steps).rem_euclid(count as isize) as usize;
+        let (component, atom) = self.candidates[self.chosen].clone();
+        selection.toggle(component, atom.clone());
+        log::info!(
+            "chose atom {} ({} of {} under the click): {}",
+            atom,
+            self.chosen + 1,
+            count,
+            selection.summary()
+        );
+        true
+    }
+}
+
+impl Tool for SelectTool {
+    fn set_active(&mut self, _active: bool) {
+        self.candidates.clear();
+        self.holding = false;
+    }
+
+    fn hint(&self) -> Option<String> {
+        Some(
+            "selecting: click atoms to select them, C for the atom behind, Escape to clear"
+                .to_owned(),
+        )
+    }
+
+    fn on_pointer_down(&mut self, context: ToolContext) -> bool {
+        self.candidates = context.pointer.hits(context.assembly);
+        self.chosen = 0;
+        self.scrolled = 0.0;
+        let Some((component, atom)) = self.candidates.first().cloned() else {
+            return false;
+        };
+        self.holding = true;
+        context.selection.toggle(component, atom);
+        if self.candidates.len() > 1 {
+            log::info!(
+                "{} ({} atoms under the click, C for the next)",
+                context.selection.summary(),
+                self.candidates.len()
+            );
+        } else {
+            log::info!("{}", context.selection.summary());
+        }
+        true
+    }
+
+    fn on_pointer_up(&mut self, _context: ToolContext) -> bool {
+        self.holding = false;
+        false
+    }
+
+    fn on_scroll(&mut self, context: ToolContext, lines: f32) -> bool {
+        if !self.holding {
+            return false;
+        }
+        // Scrolling down goes further into the scene.
+        self.scrolled -= lines;
+        let steps = self.scrolled.trunc();
+        self.scrolled -= steps;
+        if steps != 0.0 {
+            self.cycle(context.selection, steps as isize);
+        }
+        true
+    }
+
+    fn on_key(&mut self, context: ToolContext, key: KeyCode, modifiers: &ModifiersState) -> bool {
+        match key {
+            KeyCode::KeyC if modifiers.is_empty() || *modifiers == ModifiersState::SHIFT => {
+                let steps = if modifiers.shift_key() { -1 } else { 1 };
+                self.cycle(context.selection, steps)
+            }
+            KeyCode::Escape if !context.selection.is_empty() => {
+                self.candidates.clear();
+                context.selection.clear();
+                log::info!("{}", context.selection.summary());
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Holds the tools, and routes input to the active one.
+#[derive(Default)]
+pub struct ToolManager {
+    active: ToolKind,
+    view: RotateViewTool,
+    select: SelectTool,
+    pub placement: PlacementTool,
+    pub moieties: MoietyTool,
+    pub brush: BrushTool,
+    pub bonding: BondTool,
+    pub measurements: MeasurementTool,
+    pub cutting: CutTool,
+}
+
+impl ToolManager {
+    pub fn active(&self) -> ToolKind {
+        self.active
+    }
+
+    pub fn is_active(&self, kind: ToolKind) -> bool {
+        self.active == kind
+    }
+
+    /// Switches to the tool `kind`.  The element brush starts out with the
+    /// element chosen for placing atoms.
+    pub fn choose(&mut self, kind: ToolKind) {
+        if kind == self.active {
+            return;
+        }
+        self.active_tool().set_active(false);
+        if kind == ToolKind::Brush {
+            self.brush.set_element(self.placement.element());
+        }
+        self.active = kind;
+        self.active_tool().set_active(true);
+    }
+
+    /// Switches to the tool `kind`, or back to rotating the view if it is
+    /// already active.
+    pub fn toggle(&mut self, kind: ToolKind) {
+        if kind == self.active {
+            self.choose(ToolKind::RotateView);
+        } else {
+            self.choose(kind);
+        }
+    }
+
+    /// Switches to the next tool on the toolbar (or the previous one if not
+    /// `forward`), going around from the end to the start.  Tools that edit
+    /// the design are skipped unless `editing` is allowed.
+    pub fn cycle(&mut self, forward: bool, editing: bool) {
+        let count = ToolKind::ALL.len();
+        let index = ToolKind::ALL
+            .iter()
+            .position(|kind| *kind == self.active)
+            .unwrap_or_default();
+        let next = (1..count)
+            .map(|step| {
+                let offset = if forward { step } else { count - step };
+                ToolKind::ALL[(index + offset) % count]
+            })
+            .find(|kind| editing || !kind.edits());
+        if let Some(kind) = next {
+            self.choose(kind);
+        }
+    }
+
+    pub fn toolbar(&self) -> Vec<ToolbarButton> {
+        ToolKind::ALL
+            .iter()
+            .map(|kind| ToolbarButton {
+                kind: *kind,
+                label: kind.name(),
+                shortcut: kind.shortcut(),
+                active: *kind == self.active,
+            })
+            .collect()
+    }
+
+    pub fn active_tool(&mut self) -> &mut dyn Tool {
+        match self.active {
+            ToolKind::RotateView => &mut self.view,
+            ToolKind::Select => &mut self.select,
+            ToolKind::PlaceAtom => &mut self.placement,
+            ToolKind::Moiety => &mut self.moieties,
+            ToolKind::Brush => &mut self.brush,
+            ToolKind::Bond => &mut self.bonding,
+            ToolKind::Measure => &mut self.measurements,
+            ToolKind::Cut => &mut self.cutting,
+        }
+    }
+
+    /// What the active tool draws over the scene.
+    pub fn render_overlay(&self) -> Vec<OverlayDraw<'_>> {
+        match self.active {
+            ToolKind::RotateView => self.view.render_overlay(),
+            ToolKind::Select => self.select.render_overlay(),
+            ToolKind::PlaceAtom => self.placement.render_overlay(),
+            ToolKind::Moiety => self.moieties.render_overlay(),
+            ToolKind::Brush => self.brush.render_overlay(),
+            ToolKind::Bond => self.bonding.render_overlay(),
+            ToolKind::Measure => self.measurements.render_overlay(),
+            ToolKind::Cut => self.cutting.render_overlay(),
+        }
+    }
+}
+
+// End of File
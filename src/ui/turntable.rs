@@ -0,0 +1,115 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The idle turntable, which slowly turns the camera around the design once
+//! nothing has been touched for a while, like a screensaver.  This is meant
+//! for screens that show a design on display, so it is off unless the
+//! [`TURNTABLE_ENV_VAR`] environment variable gives the number of idle seconds
+//! before turning starts.  Any input stops the turning at once.
+
+use std::time::Duration;
+
+use instant::Instant;
+use render::RenderCamera;
+
+/// The environment variable giving the number of seconds without input after
+/// which the camera starts to turn.
+pub const TURNTABLE_ENV_VAR: &str = "ATOMCAD_TURNTABLE";
+
+/// How fast the camera turns, in radians per second: about one turn a minute.
+const TURN_SPEED: f32 = 0.1;
+
+/// The longest step the camera turns between two frames, in seconds.  This
+/// keeps the view from jumping when a frame is very late.
+const MAX_STEP: f32 = 0.1;
+
+pub struct IdleTurntable {
+    /// How long to wait for input before turning, or `None` if the turntable
+    /// is off.
+    delay: Option<Duration>,
+    last_input: Instant,
+    /// When the previous frame was drawn, if the camera is turning.
+    turning_since: Option<Instant>,
+}
+
+impl Default for IdleTurntable {
+    fn default() -> Self {
+        Self {
+            delay: None,
+            last_input: Instant::now(),
+            turning_since: None,
+        }
+    }
+}
+
+impl IdleTurntable {
+    /// The turntable configured by the [`TURNTABLE_ENV_VAR`] environment
+    /// variable, which is off if it is unset or malformed.
+    pub fn from_env() -> Self {
+        let mut turntable = Self::default();
+        if let Ok(seconds) = std::env::var(TURNTABLE_ENV_VAR) {
+            let delay = seconds
+                .trim()
+                .parse()
+                .ok()
+                .and_then(|seconds| Duration::try_from_secs_f32(seconds).ok());
+            match delay {
+                Some(delay) => turntable.set_delay(Some(delay)),
+                None => log::warn!("ignoring malformed {}={:?}", TURNTABLE_ENV_VAR, seconds),
+            }
+        }
+        turntable
+    }
+
+    pub fn delay(&self) -> Option<Duration> {
+        self.delay
+    }
+
+    /// Sets how long to wait for input before turning, or turns the turntable
+    /// off with `None`.  The wait starts over.
+    pub fn set_delay(&mut self, delay: Option<Duration>) {
+        self.delay = delay;
+        self.input();
+    }
+
+    pub fn is_turning(&self) -> bool {
+        self.turning_since.is_some()
+    }
+
+    /// Notes that the user did something, which stops the turning and starts
+    /// the wait over.
+    pub fn input(&mut self) {
+        self.last_input = Instant::now();
+        self.turning_since = None;
+    }
+
+    /// How long until the camera starts turning, if it is not turning yet and
+    /// the turntable is on.
+    pub fn time_until_turning(&self) -> Option<Duration> {
+        if self.is_turning() {
+            return None;
+        }
+        Some(self.delay?.saturating_sub(self.last_input.elapsed()))
+    }
+
+    /// Turns the camera by the time since the previous frame, once the wait
+    /// is over.  Returns whether the camera is turning.
+    pub fn advance(&mut self, camera: &mut RenderCamera) -> bool {
+        if !self.is_turning() && self.time_until_turning() != Some(Duration::ZERO) {
+            return false;
+        }
+        let Some(pose) = camera.pose() else {
+            return false;
+        };
+        let now = Instant::now();
+        let seconds = self
+            .turning_since
+            .map_or(0.0, |previous| (now - previous).as_secs_f32().min(MAX_STEP));
+        self.turning_since = Some(now);
+        camera.orbit_to(pose.yaw + TURN_SPEED * seconds, pose.pitch, false);
+        true
+    }
+}
+
+// End of File
@@ -0,0 +1,122 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The validation report: the assembly's formula and net charge, and a list
+//! of the problems found in it, each of which can be clicked to frame its
+//! atoms.  See [`scene::validate`] for what is checked.
+
+use render::RenderCamera;
+use scene::{Assembly, ValidationReport};
+use ultraviolet::Vec3;
+
+/// The radius of the region framed around an issue's atoms, beyond the atoms
+/// themselves, in angstroms.  This shows their neighbors for context.
+const FRAME_MARGIN: f32 = 6.0;
+
+#[derive(Clone, Debug)]
+pub struct ValidationRow {
+    pub description: String,
+    /// Whether the camera was last framed on this issue.
+    pub highlighted: bool,
+}
+
+#[derive(Default)]
+pub struct ValidationPanel {
+    report: Option<ValidationReport>,
+    highlighted: Option<usize>,
+}
+
+impl ValidationPanel {
+    pub fn is_open(&self) -> bool {
+        self.report.is_some()
+    }
+
+    pub fn close(&mut self) {
+        self.report = None;
+        self.highlighted = None;
+    }
+
+    pub fn report(&self) -> Option<&ValidationReport> {
+        self.report.as_ref()
+    }
+
+    /// Validates the assembly as it is now, replacing the previous report.
+    pub fn run(&mut self, assembly: &Assembly) -> &ValidationReport {
+        self.highlighted = None;
+        self.report.insert(scene::validate(assembly))
+    }
+
+    /// A line summing up the report, e.g. "C2H6O, neutral, 2 issues".
+    pub fn summary(&self) -> Option<String> {
+        let report = self.report.as_ref()?;
+        let charge = match report.net_charge {
+            0 => "neutral".to_owned(),
+            charge => format!("net charge {:+}", charge),
+        };
+        let issues = match report.issues.len() {
+            0 => "no issues".to_owned(),
+            1 => "1 issue".to_owned(),
+            count => format!("{} issues", count),
+        };
+        Some(format!(
+            "{}, {}, {}",
+            report.formula_string(),
+            charge,
+            issues
+        ))
+    }
+
+    pub fn rows(&self, assembly: &Assembly) -> Vec<ValidationRow> {
+        let Some(report) = &self.report else {
+            return Vec::new();
+        };
+        report
+            .issues
+            .iter()
+            .enumerate()
+            .map(|(index, issue)| ValidationRow {
+                description: issue.description(assembly),
+                highlighted: self.highlighted == Some(index),
+            })
+            .collect()
+    }
+
+    /// Frames the camera on the atoms of the issue in row `index`.  Returns
+    /// whether there was such an issue with atoms still in the assembly.
+    pub fn choose(&mut self, assembly: &Assembly, camera: &mut RenderCamera, index: usize) -> bool {
+        let Some(issue) = self.report.as_ref().and_then(|r| r.issues.get(index)) else {
+            return false;
+        };
+        let positions: Vec<Vec3> = issue
+            .atoms
+            .iter()
+            .filter_map(|(id, atom)| assembly.atom_position(*id, atom))
+            .collect();
+        if positions.is_empty() {
+            return false;
+        }
+
+        let center =
+            positions.iter().fold(Vec3::zero(), |sum, pos| sum + *pos) / positions.len() as f32;
+        let radius = positions
+            .iter()
+            .map(|pos| (*pos - center).mag())
+            .fold(0.0, f32::max);
+        camera.frame(center, radius + FRAME_MARGIN);
+        self.highlighted = Some(index);
+        true
+    }
+
+    /// Frames the camera on the issue after the one last framed, going back
+    /// to the first after the last.  Returns the index of the issue framed.
+    pub fn choose_next(&mut self, assembly: &Assembly, camera: &mut RenderCamera) -> Option<usize> {
+        let count = self.report.as_ref()?.issues.len();
+        let start = self.highlighted.map_or(0, |index| index + 1);
+        (0..count)
+            .map(|offset| (start + offset) % count)
+            .find(|index| self.choose(assembly, camera, *index))
+    }
+}
+
+// End of File